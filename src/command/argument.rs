@@ -0,0 +1,499 @@
+use std::sync::Arc;
+
+use rand::seq::SliceRandom;
+use rust_mc_proto::{DataWriter, Packet};
+
+use crate::{ServerError, player::context::ClientContext};
+
+// Парсеры аргументов команд
+//
+// Айдишники парсеров из реестра minecraft:command_argument_type, клиент по ним подсвечивает
+// синтаксис и сам проверяет простые аргументы, а сервер парсит их еще раз при выполнении
+
+/// Как brigadier:string читает строку
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StringKind {
+	Word,     // одно слово до пробела
+	Quotable, // слово или фраза в кавычках
+	Greedy,   // все до конца команды
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum ArgumentParser {
+	Bool,
+	Float { min: Option<f32>, max: Option<f32> },
+	Double { min: Option<f64>, max: Option<f64> },
+	Integer { min: Option<i32>, max: Option<i32> },
+	Long { min: Option<i64>, max: Option<i64> },
+	String(StringKind),
+	Entity { single: bool, players_only: bool }, // пока сущности это только игроки
+	GameProfile,
+	BlockPos,
+	ColumnPos,
+	Vec3,
+	Vec2,
+}
+
+/// Разобранное значение аргумента
+#[derive(Clone)]
+pub enum ArgumentValue {
+	Bool(bool),
+	Float(f32),
+	Double(f64),
+	Integer(i32),
+	Long(i64),
+	String(String),
+	Players(Vec<Arc<ClientContext>>),
+	Profile(String),
+	BlockPos(i32, i32, i32),
+	ColumnPos(i32, i32),
+	Vec3(f64, f64, f64),
+	Vec2(f64, f64),
+}
+
+// Пишет флаги и границы числового парсера
+fn write_range<T>(
+	packet: &mut Packet,
+	min: Option<T>,
+	max: Option<T>,
+	write: fn(&mut Packet, T) -> Result<(), ServerError>,
+) -> Result<(), ServerError> {
+	packet.write_byte(min.is_some() as u8 | (max.is_some() as u8) << 1)?;
+	if let Some(min) = min {
+		write(packet, min)?;
+	}
+	if let Some(max) = max {
+		write(packet, max)?;
+	}
+	Ok(())
+}
+
+// Слово до пробела
+fn token(input: &str) -> &str {
+	input.split(' ').next().unwrap_or_default()
+}
+
+// Число как его читает brigadier: только цифры, точка и минус, так что nan, inf и 1e5 не проходят
+fn is_number(token: &str) -> bool {
+	!token.is_empty()
+		&& token
+			.chars()
+			.all(|o| o.is_ascii_digit() || o == '.' || o == '-')
+}
+
+fn parse_number<T: std::str::FromStr + PartialOrd + std::fmt::Display + Copy>(
+	input: &str,
+	min: Option<T>,
+	max: Option<T>,
+) -> Result<(T, usize), String> {
+	let token = token(input);
+	let invalid = || format!("Invalid number '{token}'");
+	if !is_number(token) {
+		return Err(invalid());
+	}
+	let value: T = token.parse().map_err(|_| invalid())?;
+
+	if let Some(min) = min
+		&& value < min
+	{
+		return Err(format!("Number must not be less than {min}, found {value}"));
+	}
+	if let Some(max) = max
+		&& value > max
+	{
+		return Err(format!("Number must not be more than {max}, found {value}"));
+	}
+
+	Ok((value, token.len()))
+}
+
+// Координата, ~ значит относительно отправителя
+fn parse_coordinate(token: &str, origin: f64) -> Result<f64, String> {
+	let invalid = || format!("Invalid coordinate '{token}'");
+	let number = |text: &str| match is_number(text) {
+		true => text.parse::<f64>().map_err(|_| invalid()),
+		false => Err(invalid()),
+	};
+
+	let value = match token.strip_prefix('~') {
+		Some("") => origin,
+		Some(offset) => origin + number(offset)?,
+		None => number(token)?,
+	};
+
+	// Очень длинное число все равно превращается в бесконечность
+	if !value.is_finite() {
+		return Err(invalid());
+	}
+	Ok(value)
+}
+
+// Несколько координат через пробел, origin это позиция отправителя по тем же осям
+fn parse_coordinates(input: &str, origin: &[f64]) -> Result<(Vec<f64>, usize), String> {
+	let mut values = Vec::with_capacity(origin.len());
+	let mut used = 0;
+
+	for (i, origin) in origin.iter().enumerate() {
+		if i > 0 {
+			if !input[used..].starts_with(' ') {
+				return Err("Incomplete position".to_string());
+			}
+			used += 1;
+		}
+		let token = token(&input[used..]);
+		if token.is_empty() {
+			return Err("Incomplete position".to_string());
+		}
+		values.push(parse_coordinate(token, *origin)?);
+		used += token.len();
+	}
+
+	Ok((values, used))
+}
+
+fn parse_string(input: &str, kind: StringKind) -> Result<(String, usize), String> {
+	match kind {
+		StringKind::Greedy => Ok((input.to_string(), input.len())),
+		StringKind::Quotable if input.starts_with('"') => {
+			let mut value = String::new();
+			let mut escaped = false;
+			for (i, char) in input.char_indices().skip(1) {
+				match char {
+					_ if escaped => {
+						value.push(char);
+						escaped = false;
+					}
+					'\\' => escaped = true,
+					'"' => return Ok((value, i + 1)),
+					_ => value.push(char),
+				}
+			}
+			Err("Unclosed quoted string".to_string())
+		}
+		_ => {
+			let token = token(input);
+			if token.is_empty() {
+				return Err("Expected string".to_string());
+			}
+			Ok((token.to_string(), token.len()))
+		}
+	}
+}
+
+// Игроки по имени или селектору @s, @p, @a, @r
+fn parse_players(
+	input: &str,
+	sender: &Arc<ClientContext>,
+	single: bool,
+) -> Result<(Vec<Arc<ClientContext>>, usize), String> {
+	let token = token(input);
+	let server = &sender.server;
+
+	let players = match token {
+		"@s" => vec![sender.clone()],
+		"@a" | "@e" => server.players(),
+		"@r" => server
+			.players()
+			.choose(&mut rand::thread_rng())
+			.cloned()
+			.into_iter()
+			.collect(),
+		"@p" => {
+			let (x, y, z) = sender.entity_info().unwrap().position();
+			let distance = |player: &Arc<ClientContext>| {
+				let (px, py, pz) = player.entity_info().unwrap().position();
+				(px - x).powi(2) + (py - y).powi(2) + (pz - z).powi(2)
+			};
+			server
+				.players()
+				.into_iter()
+				.min_by(|a, b| distance(a).total_cmp(&distance(b)))
+				.into_iter()
+				.collect()
+		}
+		_ if token.starts_with('@') => return Err(format!("Unknown selector type '{token}'")),
+		name => server.get_player_by_name(name).into_iter().collect(),
+	};
+
+	if players.is_empty() {
+		return Err("No player was found".to_string());
+	}
+	if single && players.len() > 1 {
+		return Err(
+			"Only one player is allowed, but the provided selector allows more than one".to_string(),
+		);
+	}
+
+	Ok((players, token.len()))
+}
+
+impl ArgumentParser {
+	/// Айди парсера в реестре minecraft:command_argument_type
+	pub fn id(&self) -> i32 {
+		match self {
+			ArgumentParser::Bool => 0,
+			ArgumentParser::Float { .. } => 1,
+			ArgumentParser::Double { .. } => 2,
+			ArgumentParser::Integer { .. } => 3,
+			ArgumentParser::Long { .. } => 4,
+			ArgumentParser::String(_) => 5,
+			ArgumentParser::Entity { .. } => 6,
+			ArgumentParser::GameProfile => 7,
+			ArgumentParser::BlockPos => 8,
+			ArgumentParser::ColumnPos => 9,
+			ArgumentParser::Vec3 => 10,
+			ArgumentParser::Vec2 => 11,
+		}
+	}
+
+	/// Свойства парсера для пакета Commands
+	pub fn properties(&self) -> Result<Vec<u8>, ServerError> {
+		let mut packet = Packet::empty(0);
+
+		match *self {
+			ArgumentParser::Float { min, max } => {
+				write_range(&mut packet, min, max, |p, o| Ok(p.write_float(o)?))?
+			}
+			ArgumentParser::Double { min, max } => {
+				write_range(&mut packet, min, max, |p, o| Ok(p.write_double(o)?))?
+			}
+			ArgumentParser::Integer { min, max } => {
+				write_range(&mut packet, min, max, |p, o| Ok(p.write_int(o)?))?
+			}
+			ArgumentParser::Long { min, max } => {
+				write_range(&mut packet, min, max, |p, o| Ok(p.write_long(o)?))?
+			}
+			ArgumentParser::String(kind) => packet.write_varint(match kind {
+				StringKind::Word => 0,
+				StringKind::Quotable => 1,
+				StringKind::Greedy => 2,
+			})?,
+			ArgumentParser::Entity {
+				single,
+				players_only,
+			} => packet.write_byte(single as u8 | (players_only as u8) << 1)?,
+			_ => {}
+		}
+
+		Ok(packet.get_bytes().to_vec())
+	}
+
+	/// Нужно ли спрашивать подсказки у сервера (minecraft:ask_server)
+	pub fn suggests_from_server(&self) -> bool {
+		matches!(
+			self,
+			ArgumentParser::Entity { .. } | ArgumentParser::GameProfile
+		)
+	}
+
+	/// Подсказки по умолчанию для недописанного аргумента
+	pub fn suggestions(&self, sender: &Arc<ClientContext>) -> Vec<String> {
+		match self {
+			ArgumentParser::Bool => vec!["true".to_string(), "false".to_string()],
+			ArgumentParser::Entity { .. } | ArgumentParser::GameProfile => {
+				let mut names: Vec<String> = sender
+					.server
+					.players()
+					.iter()
+					.filter_map(|o| o.player_info())
+					.map(|o| o.name)
+					.collect();
+				if matches!(self, ArgumentParser::Entity { .. }) {
+					names.extend(["@s", "@p", "@a", "@r"].map(String::from));
+				}
+				names
+			}
+			_ => Vec::new(),
+		}
+	}
+
+	/// Разбирает аргумент с начала input, возвращает значение и сколько байт занято
+	pub fn parse(
+		&self,
+		input: &str,
+		sender: &Arc<ClientContext>,
+	) -> Result<(ArgumentValue, usize), String> {
+		let position = sender.entity_info().unwrap().position();
+
+		Ok(match *self {
+			ArgumentParser::Bool => match token(input) {
+				"true" => (ArgumentValue::Bool(true), 4),
+				"false" => (ArgumentValue::Bool(false), 5),
+				token => return Err(format!("Invalid boolean '{token}'")),
+			},
+			ArgumentParser::Float { min, max } => {
+				let (value, used) = parse_number(input, min, max)?;
+				(ArgumentValue::Float(value), used)
+			}
+			ArgumentParser::Double { min, max } => {
+				let (value, used) = parse_number(input, min, max)?;
+				(ArgumentValue::Double(value), used)
+			}
+			ArgumentParser::Integer { min, max } => {
+				let (value, used) = parse_number(input, min, max)?;
+				(ArgumentValue::Integer(value), used)
+			}
+			ArgumentParser::Long { min, max } => {
+				let (value, used) = parse_number(input, min, max)?;
+				(ArgumentValue::Long(value), used)
+			}
+			ArgumentParser::String(kind) => {
+				let (value, used) = parse_string(input, kind)?;
+				(ArgumentValue::String(value), used)
+			}
+			ArgumentParser::Entity { single, .. } => {
+				let (players, used) = parse_players(input, sender, single)?;
+				(ArgumentValue::Players(players), used)
+			}
+			ArgumentParser::GameProfile => {
+				let token = token(input);
+				if token.is_empty() {
+					return Err("Expected player name".to_string());
+				}
+				(ArgumentValue::Profile(token.to_string()), token.len())
+			}
+			ArgumentParser::BlockPos => {
+				let origin = [position.0.floor(), position.1.floor(), position.2.floor()];
+				let (values, used) = parse_coordinates(input, &origin)?;
+				let [x, y, z] = [values[0], values[1], values[2]].map(|o| o.floor() as i32);
+				(ArgumentValue::BlockPos(x, y, z), used)
+			}
+			ArgumentParser::ColumnPos => {
+				let origin = [position.0.floor(), position.2.floor()];
+				let (values, used) = parse_coordinates(input, &origin)?;
+				let [x, z] = [values[0], values[1]].map(|o| o.floor() as i32);
+				(ArgumentValue::ColumnPos(x, z), used)
+			}
+			ArgumentParser::Vec3 => {
+				let origin = [position.0, position.1, position.2];
+				let (values, used) = parse_coordinates(input, &origin)?;
+				(ArgumentValue::Vec3(values[0], values[1], values[2]), used)
+			}
+			ArgumentParser::Vec2 => {
+				let origin = [position.0, position.2];
+				let (values, used) = parse_coordinates(input, &origin)?;
+				(ArgumentValue::Vec2(values[0], values[1]), used)
+			}
+		})
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::{config::Config, context::ServerContext, player::context::test_player};
+
+	fn sender() -> Arc<ClientContext> {
+		let server = Arc::new(ServerContext::new(Arc::new(Config::default())));
+		let player = test_player(&server, "Steve", 0);
+		player
+			.entity_info()
+			.unwrap()
+			.set_position((10.5, 64.0, -5.5));
+		player
+	}
+
+	fn parse(parser: ArgumentParser, input: &str) -> Result<(ArgumentValue, usize), String> {
+		parser.parse(input, &sender())
+	}
+
+	#[test]
+	fn coordinates() {
+		let Ok((ArgumentValue::Vec3(x, y, z), used)) = parse(ArgumentParser::Vec3, "1 ~2 ~ rest")
+		else {
+			panic!("не разобралось");
+		};
+		assert_eq!((x, y, z, used), (1.0, 66.0, -5.5, 6));
+
+		let Ok((ArgumentValue::BlockPos(x, y, z), _)) = parse(ArgumentParser::BlockPos, "~ ~-1 ~0.5")
+		else {
+			panic!("не разобралось");
+		};
+		assert_eq!((x, y, z), (10, 63, -6));
+
+		assert!(parse(ArgumentParser::Vec3, "1 2").is_err());
+	}
+
+	#[test]
+	fn coordinates_must_be_finite() {
+		let huge = "9".repeat(400);
+		for input in [
+			"nan 0 0",
+			"0 inf 0",
+			"0 0 -infinity",
+			"~NaN 0 0",
+			"1e5 0 0",
+			&format!("{huge} 0 0"),
+			&format!("~{huge} 0 0"),
+		] {
+			assert!(parse(ArgumentParser::Vec3, input).is_err(), "{input}");
+		}
+	}
+
+	#[test]
+	fn numbers() {
+		let integer = ArgumentParser::Integer {
+			min: Some(0),
+			max: Some(10),
+		};
+		assert!(matches!(
+			parse(integer.clone(), "7 x"),
+			Ok((ArgumentValue::Integer(7), 1))
+		));
+		assert!(parse(integer.clone(), "11").is_err());
+		assert!(parse(integer, "-1").is_err());
+
+		let double = ArgumentParser::Double {
+			min: None,
+			max: None,
+		};
+		assert!(matches!(
+			parse(double.clone(), "-2.5"),
+			Ok((ArgumentValue::Double(-2.5), 4))
+		));
+		assert!(parse(double.clone(), "nan").is_err());
+		assert!(parse(double, "+1").is_err());
+	}
+
+	#[test]
+	fn strings() {
+		let quotable = ArgumentParser::String(StringKind::Quotable);
+		let Ok((ArgumentValue::String(value), used)) = parse(quotable.clone(), r#""a \"b\" c" d"#)
+		else {
+			panic!("не разобралось");
+		};
+		assert_eq!((value.as_str(), used), (r#"a "b" c"#, 11));
+		assert!(parse(quotable, "\"open").is_err());
+
+		let Ok((ArgumentValue::String(value), used)) =
+			parse(ArgumentParser::String(StringKind::Word), "word rest")
+		else {
+			panic!("не разобралось");
+		};
+		assert_eq!((value.as_str(), used), ("word", 4));
+	}
+
+	#[test]
+	fn players() {
+		let sender = sender();
+		test_player(&sender.server, "Alex", 0);
+		let single = ArgumentParser::Entity {
+			single: true,
+			players_only: true,
+		};
+
+		let Ok((ArgumentValue::Players(players), _)) = single.parse("alex", &sender) else {
+			panic!("не разобралось");
+		};
+		assert_eq!(players[0].player_info().unwrap().name, "Alex");
+
+		let Ok((ArgumentValue::Players(players), _)) = single.parse("@s", &sender) else {
+			panic!("не разобралось");
+		};
+		assert_eq!(players[0].id, sender.id);
+
+		assert!(single.parse("@a", &sender).is_err());
+		assert!(single.parse("@x", &sender).is_err());
+		assert!(single.parse("Nobody", &sender).is_err());
+	}
+}
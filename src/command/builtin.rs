@@ -0,0 +1,242 @@
+use std::sync::Arc;
+
+use super::{
+	CommandBuilder, CommandContext, CommandDispatcher, argument,
+	argument::{ArgumentParser, StringKind},
+	literal,
+};
+use crate::{
+	ServerError,
+	context::World,
+	play::{
+		entity::move_entity,
+		helper::{set_game_mode, sync_player_pos},
+		world::change_world,
+	},
+	player::context::{ClientContext, GameMode},
+};
+
+// Встроенные команды сервера, плагины могут заменить их своими через add_command
+
+pub fn register(dispatcher: &mut CommandDispatcher) {
+	dispatcher.register(gamemode());
+	dispatcher.register(kill());
+	dispatcher.register(teleport());
+	dispatcher.register(help());
+	dispatcher.register(tps());
+	dispatcher.register(world());
+	dispatcher.register(stop());
+}
+
+const PLAYERS: ArgumentParser = ArgumentParser::Entity {
+	single: false,
+	players_only: true,
+};
+
+const PLAYER: ArgumentParser = ArgumentParser::Entity {
+	single: true,
+	players_only: true,
+};
+
+// Цели команды: из аргумента targets, а если его нет, то сам отправитель
+fn targets(context: &CommandContext) -> Vec<Arc<ClientContext>> {
+	match context.players("targets") {
+		Some(players) => players.to_vec(),
+		None => vec![context.sender.clone()],
+	}
+}
+
+fn name(client: &Arc<ClientContext>) -> String {
+	client.player_info().map(|o| o.name).unwrap_or_default()
+}
+
+fn gamemode() -> CommandBuilder {
+	let mut command = literal("gamemode").requires(2).alias("gm");
+
+	for (mode_name, game_mode) in [
+		("survival", GameMode::Survival),
+		("creative", GameMode::Creative),
+		("adventure", GameMode::Adventure),
+		("spectator", GameMode::Spectator),
+	] {
+		let execute = move |context: &CommandContext| {
+			for target in targets(context) {
+				set_game_mode(target.clone(), game_mode)?;
+				context.reply(&format!("Set {}'s game mode to {mode_name}", name(&target)))?;
+			}
+			Ok(())
+		};
+
+		command = command.then(
+			literal(mode_name)
+				.executes(execute)
+				.then(argument("targets", PLAYERS).executes(execute)),
+		);
+	}
+
+	command
+}
+
+fn kill() -> CommandBuilder {
+	// Пока нет здоровья, смерть это просто телепорт на спавн
+	let execute = |context: &CommandContext| {
+		for target in targets(context) {
+			let (x, y, z) = target.world()?.spawn_point();
+			let entity = target.entity_info().unwrap().entity.clone();
+			move_entity(&target.server, &entity, (x, y, z), None, false)?;
+			sync_player_pos(target.clone(), x, y, z, 0.0, 0.0, 0.0, 0.0, 0.0, 0)?;
+			context.reply(&format!("Killed {}", name(&target)))?;
+		}
+		Ok(())
+	};
+
+	literal("kill")
+		.requires(2)
+		.executes(execute)
+		.then(argument("targets", PLAYERS).executes(execute))
+}
+
+// Телепортирует цели в точку мира, углы поворота не меняются (флаги 0x18 - yaw и pitch относительные)
+fn teleport_to(
+	context: &CommandContext,
+	targets: Vec<Arc<ClientContext>>,
+	world: &Arc<World>,
+	(x, y, z): (f64, f64, f64),
+) -> Result<(), ServerError> {
+	for target in targets {
+		if target.world()?.name != world.name {
+			change_world(&target, world, Some((x, y, z)))?;
+		} else {
+			let entity = target.entity_info().unwrap().entity.clone();
+			move_entity(&target.server, &entity, (x, y, z), None, false)?;
+			sync_player_pos(target.clone(), x, y, z, 0.0, 0.0, 0.0, 0.0, 0.0, 0x18)?;
+		}
+		context.reply(&format!(
+			"Teleported {} to {x:.2}, {y:.2}, {z:.2}",
+			name(&target)
+		))?;
+	}
+	Ok(())
+}
+
+fn teleport() -> CommandBuilder {
+	// Как в ванилле, координаты считаются в мире того, кто выполняет команду
+	let to_location = |context: &CommandContext| {
+		let world = context.sender.world()?;
+		teleport_to(
+			context,
+			targets(context),
+			&world,
+			context.vec3("location").unwrap(),
+		)
+	};
+
+	let to_player = |context: &CommandContext| {
+		let destination = context.players("destination").unwrap()[0].clone();
+		let position = destination.entity_info().unwrap().position();
+		teleport_to(context, targets(context), &destination.world()?, position)
+	};
+
+	literal("teleport")
+		.requires(2)
+		.alias("tp")
+		.then(argument("location", ArgumentParser::Vec3).executes(to_location))
+		.then(argument("destination", PLAYER).executes(to_player))
+		.then(
+			argument("targets", PLAYERS)
+				.then(argument("location", ArgumentParser::Vec3).executes(to_location))
+				.then(argument("destination", PLAYER).executes(to_player)),
+		)
+}
+
+fn help() -> CommandBuilder {
+	literal("help").executes(|context| {
+		let level = context.sender.entity_info().unwrap().permission_level();
+		let mut commands = context.sender.server.commands().commands(level);
+		commands.sort();
+
+		for command in commands {
+			context.reply(&format!("/{command}"))?;
+		}
+		Ok(())
+	})
+}
+
+fn tps() -> CommandBuilder {
+	literal("tps").executes(|context| {
+		let planner = context.sender.server.planner();
+		context.reply(&format!(
+			"TPS: {:.1}, MSPT: {:.2}",
+			planner.tps(),
+			planner.mspt()
+		))
+	})
+}
+
+fn stop() -> CommandBuilder {
+	literal("stop").requires(4).executes(|context| {
+		context.reply("Stopping the server")?;
+		context.sender.server.stop();
+		Ok(())
+	})
+}
+
+// Имя мира без minecraft:, так его можно ввести одним словом
+fn short_name(name: &str) -> &str {
+	name.strip_prefix("minecraft:").unwrap_or(name)
+}
+
+fn world() -> CommandBuilder {
+	let list = |context: &CommandContext| {
+		let current = context.sender.world()?.name.clone();
+		let names = context.sender.server.world.names();
+
+		context.reply(&format!("You are in {current}"))?;
+		context.reply(&format!("Worlds: {}", names.join(", ")))
+	};
+
+	let go = |context: &CommandContext| {
+		let name = context.string("name").unwrap();
+		let worlds = &context.sender.server.world;
+		let world = worlds
+			.get(name)
+			.or_else(|| worlds.get(&format!("minecraft:{name}")))
+			.ok_or_else(|| ServerError::Command(format!("Unknown world: {name}")))?;
+
+		change_world(&context.sender, &world, None)?;
+		context.reply(&format!("Moved to {}", world.name))
+	};
+
+	literal("world").requires(2).executes(list).then(
+		argument("name", ArgumentParser::String(StringKind::Word))
+			.suggests(|context, _| {
+				let names = context.sender.server.world.names();
+				names.iter().map(|o| short_name(o).to_string()).collect()
+			})
+			.executes(go),
+	)
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::{config::Config, context::ServerContext, player::context::test_player};
+
+	#[test]
+	fn stop_requires_operator() {
+		let server = Arc::new(ServerContext::new(Arc::new(Config::default())));
+		let player = test_player(&server, "Steve", 0);
+
+		assert!(server.commands().execute(player, "stop").is_err());
+		assert!(!server.is_stopping());
+	}
+
+	#[test]
+	fn stop_asks_server_to_stop() {
+		let server = Arc::new(ServerContext::new(Arc::new(Config::default())));
+		let operator = test_player(&server, "Alex", 4);
+
+		server.commands().execute(operator, "stop").unwrap();
+		assert!(server.is_stopping());
+	}
+}
@@ -0,0 +1,695 @@
+use std::{
+	collections::{HashMap, VecDeque},
+	sync::Arc,
+};
+
+use crate::{
+	ServerError,
+	data::component::TextComponent,
+	play::helper::send_system_message,
+	player::context::ClientContext,
+	protocol::packets::{
+		VarInt,
+		clientbound::play::{CommandNode, Commands},
+	},
+};
+
+pub mod argument;
+pub mod builtin;
+
+use argument::{ArgumentParser, ArgumentValue};
+
+// Команды в стиле brigadier
+//
+// Команды хранятся деревом: корень, литералы (слова) и аргументы с парсерами
+// Клиенту это дерево уходит пакетом Commands, по нему он сам подсвечивает и дополняет команды,
+// а подсказки для аргументов с minecraft:ask_server спрашивает у сервера
+// Узел виден игроку только если его уровень прав не меньше requirement узла
+
+pub type CommandExecutor = Arc<dyn Fn(&CommandContext) -> Result<(), ServerError> + Send + Sync>;
+pub type SuggestionProvider = Arc<dyn Fn(&CommandContext, &str) -> Vec<String> + Send + Sync>;
+
+const ROOT: usize = 0;
+
+#[derive(Clone)]
+enum NodeKind {
+	Root,
+	Literal(String),
+	Argument(String, ArgumentParser),
+}
+
+#[derive(Clone)]
+struct Node {
+	kind: NodeKind,
+	children: Vec<usize>,
+	redirect: Option<usize>,
+	requirement: u8,
+	executor: Option<CommandExecutor>,
+	suggestions: Option<SuggestionProvider>,
+}
+
+/// Контекст выполнения команды: кто вызвал и разобранные аргументы
+pub struct CommandContext {
+	pub sender: Arc<ClientContext>,
+	pub input: String,
+	args: HashMap<String, ArgumentValue>,
+}
+
+impl CommandContext {
+	pub fn argument(&self, name: &str) -> Option<&ArgumentValue> {
+		self.args.get(name)
+	}
+
+	pub fn bool(&self, name: &str) -> Option<bool> {
+		match self.argument(name)? {
+			ArgumentValue::Bool(value) => Some(*value),
+			_ => None,
+		}
+	}
+
+	pub fn integer(&self, name: &str) -> Option<i32> {
+		match self.argument(name)? {
+			ArgumentValue::Integer(value) => Some(*value),
+			_ => None,
+		}
+	}
+
+	pub fn long(&self, name: &str) -> Option<i64> {
+		match self.argument(name)? {
+			ArgumentValue::Long(value) => Some(*value),
+			_ => None,
+		}
+	}
+
+	pub fn float(&self, name: &str) -> Option<f32> {
+		match self.argument(name)? {
+			ArgumentValue::Float(value) => Some(*value),
+			_ => None,
+		}
+	}
+
+	pub fn double(&self, name: &str) -> Option<f64> {
+		match self.argument(name)? {
+			ArgumentValue::Double(value) => Some(*value),
+			_ => None,
+		}
+	}
+
+	/// Строка, для GameProfile это имя игрока
+	pub fn string(&self, name: &str) -> Option<&str> {
+		match self.argument(name)? {
+			ArgumentValue::String(value) | ArgumentValue::Profile(value) => Some(value),
+			_ => None,
+		}
+	}
+
+	pub fn players(&self, name: &str) -> Option<&[Arc<ClientContext>]> {
+		match self.argument(name)? {
+			ArgumentValue::Players(players) => Some(players),
+			_ => None,
+		}
+	}
+
+	pub fn block_pos(&self, name: &str) -> Option<(i32, i32, i32)> {
+		match self.argument(name)? {
+			ArgumentValue::BlockPos(x, y, z) => Some((*x, *y, *z)),
+			_ => None,
+		}
+	}
+
+	pub fn vec3(&self, name: &str) -> Option<(f64, f64, f64)> {
+		match self.argument(name)? {
+			ArgumentValue::Vec3(x, y, z) => Some((*x, *y, *z)),
+			_ => None,
+		}
+	}
+
+	/// Отправляет сообщение тому, кто вызвал команду
+	pub fn reply(&self, message: &str) -> Result<(), ServerError> {
+		send_system_message(
+			self.sender.clone(),
+			TextComponent::builder().text(message).build(),
+			false,
+		)
+	}
+}
+
+/// Узел команды для регистрации, собирается через literal() и argument()
+pub struct CommandBuilder {
+	kind: NodeKind,
+	children: Vec<CommandBuilder>,
+	requirement: u8,
+	executor: Option<CommandExecutor>,
+	suggestions: Option<SuggestionProvider>,
+	aliases: Vec<String>,
+}
+
+/// Литерал, слово которое надо написать как есть
+pub fn literal(name: &str) -> CommandBuilder {
+	CommandBuilder::new(NodeKind::Literal(name.to_string()))
+}
+
+/// Аргумент, значение достается из контекста по имени
+pub fn argument(name: &str, parser: ArgumentParser) -> CommandBuilder {
+	CommandBuilder::new(NodeKind::Argument(name.to_string(), parser))
+}
+
+impl CommandBuilder {
+	fn new(kind: NodeKind) -> CommandBuilder {
+		CommandBuilder {
+			kind,
+			children: Vec::new(),
+			requirement: 0,
+			executor: None,
+			suggestions: None,
+			aliases: Vec::new(),
+		}
+	}
+
+	pub fn then(mut self, child: CommandBuilder) -> Self {
+		self.children.push(child);
+		self
+	}
+
+	/// Что делать, если команда заканчивается на этом узле
+	pub fn executes<F>(mut self, executor: F) -> Self
+	where
+		F: Fn(&CommandContext) -> Result<(), ServerError> + Send + Sync + 'static,
+	{
+		self.executor = Some(Arc::new(executor));
+		self
+	}
+
+	/// Минимальный уровень прав (0-4), без него игрок узел не видит и не может вызвать
+	pub fn requires(mut self, level: u8) -> Self {
+		self.requirement = level;
+		self
+	}
+
+	/// Свои подсказки для аргумента, получает уже написанное начало
+	pub fn suggests<F>(mut self, provider: F) -> Self
+	where
+		F: Fn(&CommandContext, &str) -> Vec<String> + Send + Sync + 'static,
+	{
+		self.suggestions = Some(Arc::new(provider));
+		self
+	}
+
+	/// Другое имя для команды, имеет смысл только у литерала в корне
+	pub fn alias(mut self, name: &str) -> Self {
+		self.aliases.push(name.to_string());
+		self
+	}
+}
+
+// Ошибка разбора: на каком байте ввода и что не так
+struct ParseError {
+	cursor: usize,
+	message: String,
+}
+
+/// Дерево зарегистрированных команд
+pub struct CommandDispatcher {
+	nodes: Vec<Node>,
+}
+
+impl Default for CommandDispatcher {
+	fn default() -> Self {
+		Self::new()
+	}
+}
+
+impl CommandDispatcher {
+	pub fn new() -> CommandDispatcher {
+		CommandDispatcher {
+			nodes: vec![Node {
+				kind: NodeKind::Root,
+				children: Vec::new(),
+				redirect: None,
+				requirement: 0,
+				executor: None,
+				suggestions: None,
+			}],
+		}
+	}
+
+	/// Регистрирует команду, команда с тем же именем заменяется
+	pub fn register(&mut self, command: CommandBuilder) {
+		let aliases = command.aliases.clone();
+		let index = self.insert(command);
+
+		self.detach(index);
+		self.nodes[ROOT].children.push(index);
+
+		// Алиас это литерал, который перенаправляет на детей команды
+		for alias in aliases {
+			let target = &self.nodes[index];
+			let node = Node {
+				kind: NodeKind::Literal(alias),
+				children: Vec::new(),
+				redirect: Some(index),
+				requirement: target.requirement,
+				executor: target.executor.clone(),
+				suggestions: None,
+			};
+			self.nodes.push(node);
+			let alias = self.nodes.len() - 1;
+			self.detach(alias);
+			self.nodes[ROOT].children.push(alias);
+		}
+	}
+
+	// Убирает из корня команду с тем же именем, что у index
+	fn detach(&mut self, index: usize) {
+		let name = self.name(index);
+		let children = self.nodes[ROOT]
+			.children
+			.iter()
+			.copied()
+			.filter(|o| self.name(*o) != name)
+			.collect();
+		self.nodes[ROOT].children = children;
+	}
+
+	fn insert(&mut self, builder: CommandBuilder) -> usize {
+		let children = builder
+			.children
+			.into_iter()
+			.map(|o| self.insert(o))
+			.collect();
+
+		self.nodes.push(Node {
+			kind: builder.kind,
+			children,
+			redirect: None,
+			requirement: builder.requirement,
+			executor: builder.executor,
+			suggestions: builder.suggestions,
+		});
+
+		self.nodes.len() - 1
+	}
+
+	fn name(&self, index: usize) -> &str {
+		match &self.nodes[index].kind {
+			NodeKind::Root => "",
+			NodeKind::Literal(name) | NodeKind::Argument(name, _) => name,
+		}
+	}
+
+	// Дети узла с учетом редиректа, сначала литералы, как в brigadier
+	fn children(&self, index: usize, level: u8) -> Vec<usize> {
+		let node = &self.nodes[index];
+		let source = node.redirect.unwrap_or(index);
+
+		let mut children: Vec<usize> = self.nodes[source]
+			.children
+			.iter()
+			.copied()
+			.filter(|o| self.nodes[*o].requirement <= level)
+			.collect();
+		children.sort_by_key(|o| matches!(self.nodes[*o].kind, NodeKind::Argument(..)));
+		children
+	}
+
+	/// Имена команд в корне, доступные с этим уровнем прав
+	pub fn commands(&self, level: u8) -> Vec<String> {
+		self
+			.children(ROOT, level)
+			.into_iter()
+			.map(|o| self.name(o).to_string())
+			.collect()
+	}
+
+	// Ищет путь по дереву, который разбирает весь ввод, возвращает последний узел
+	fn parse(
+		&self,
+		index: usize,
+		input: &str,
+		cursor: usize,
+		context: &mut CommandContext,
+		level: u8,
+	) -> Result<usize, ParseError> {
+		if cursor == input.len() {
+			return Ok(index);
+		}
+
+		let start = if index == ROOT {
+			cursor
+		} else if input[cursor..].starts_with(' ') {
+			cursor + 1
+		} else {
+			return Err(ParseError {
+				cursor,
+				message: "Expected whitespace to end one argument, but found trailing data".to_string(),
+			});
+		};
+		let rest = &input[start..];
+
+		let mut error = ParseError {
+			cursor: start,
+			message: "Unknown or incomplete command".to_string(),
+		};
+		let mut keep_deepest = |new: ParseError| {
+			if new.cursor > error.cursor {
+				error = new;
+			}
+		};
+
+		for child in self.children(index, level) {
+			match &self.nodes[child].kind {
+				NodeKind::Root => {}
+				NodeKind::Literal(name) => {
+					if rest.split(' ').next() != Some(name) {
+						continue;
+					}
+					match self.parse(child, input, start + name.len(), context, level) {
+						Ok(index) => return Ok(index),
+						Err(new) => keep_deepest(new),
+					}
+				}
+				NodeKind::Argument(name, parser) => {
+					let (value, used) = match parser.parse(rest, &context.sender) {
+						Ok(parsed) => parsed,
+						Err(message) => {
+							keep_deepest(ParseError {
+								cursor: start,
+								message,
+							});
+							continue;
+						}
+					};
+
+					context.args.insert(name.clone(), value);
+					match self.parse(child, input, start + used, context, level) {
+						Ok(index) => return Ok(index),
+						Err(new) => keep_deepest(new),
+					}
+					context.args.remove(name);
+				}
+			}
+		}
+
+		Err(error)
+	}
+
+	/// Выполняет команду без слеша от имени игрока
+	///
+	/// Ошибки разбора и ServerError::Command от самой команды стоит показать игроку
+	pub fn execute(&self, sender: Arc<ClientContext>, input: &str) -> Result<(), ServerError> {
+		let level = sender.entity_info().unwrap().permission_level();
+		let mut context = CommandContext {
+			sender,
+			input: input.to_string(),
+			args: HashMap::new(),
+		};
+
+		let executor = match self.parse(ROOT, input, 0, &mut context, level) {
+			Ok(index) => self.nodes[index].executor.clone(),
+			Err(error) => return Err(command_error(input, error)),
+		};
+
+		match executor {
+			Some(executor) => executor(&context),
+			None => Err(command_error(
+				input,
+				ParseError {
+					cursor: input.len(),
+					message: "Unknown or incomplete command".to_string(),
+				},
+			)),
+		}
+	}
+
+	// Собирает подсказки для последнего недописанного слова
+	fn collect_suggestions(
+		&self,
+		index: usize,
+		cursor: usize,
+		context: &mut CommandContext,
+		level: u8,
+		result: &mut (usize, Vec<String>),
+	) {
+		let input = context.input.clone();
+
+		let start = if index == ROOT {
+			cursor
+		} else if input[cursor..].starts_with(' ') {
+			cursor + 1
+		} else {
+			return;
+		};
+		let rest = &input[start..];
+		let last_word = !rest.contains(' ');
+
+		for child in self.children(index, level) {
+			let node = &self.nodes[child];
+			match &node.kind {
+				NodeKind::Root => {}
+				NodeKind::Literal(name) => {
+					if last_word && name.starts_with(rest) {
+						result.0 = start;
+						result.1.push(name.clone());
+					} else if rest.split(' ').next() == Some(name) {
+						self.collect_suggestions(child, start + name.len(), context, level, result);
+					}
+				}
+				NodeKind::Argument(name, parser) => {
+					if last_word {
+						let suggestions = match &node.suggestions {
+							Some(provider) => provider(context, rest),
+							None => parser.suggestions(&context.sender),
+						};
+						result.0 = start;
+						result
+							.1
+							.extend(suggestions.into_iter().filter(|o| o.starts_with(rest)));
+					}
+
+					if let Ok((value, used)) = parser.parse(rest, &context.sender)
+						&& start + used < input.len()
+					{
+						context.args.insert(name.clone(), value);
+						self.collect_suggestions(child, start + used, context, level, result);
+						context.args.remove(name);
+					}
+				}
+			}
+		}
+	}
+
+	/// Подсказки для ввода без слеша: с какого байта заменять и на что
+	pub fn suggest(&self, sender: Arc<ClientContext>, input: &str) -> (usize, Vec<String>) {
+		let level = sender.entity_info().unwrap().permission_level();
+		let mut context = CommandContext {
+			sender,
+			input: input.to_string(),
+			args: HashMap::new(),
+		};
+
+		let mut result = (input.len(), Vec::new());
+		self.collect_suggestions(ROOT, 0, &mut context, level, &mut result);
+		result.1.sort();
+		result.1.dedup();
+		result
+	}
+
+	/// Пакет Commands с узлами, которые доступны с этим уровнем прав
+	pub fn to_packet(&self, level: u8) -> Result<Commands, ServerError> {
+		// Нумеруем видимые узлы обходом в ширину от корня
+		let mut order = vec![ROOT];
+		let mut ids = HashMap::from([(ROOT, 0)]);
+		let mut queue = VecDeque::from([ROOT]);
+
+		while let Some(index) = queue.pop_front() {
+			for &child in &self.nodes[index].children {
+				if self.nodes[child].requirement > level || ids.contains_key(&child) {
+					continue;
+				}
+				ids.insert(child, order.len() as i32);
+				order.push(child);
+				queue.push_back(child);
+			}
+		}
+
+		let mut nodes = Vec::with_capacity(order.len());
+		for index in order {
+			let node = &self.nodes[index];
+
+			let children = node
+				.children
+				.iter()
+				.filter_map(|o| ids.get(o))
+				.map(|o| VarInt(*o))
+				.collect();
+			let redirect = node.redirect.and_then(|o| ids.get(&o)).map(|o| VarInt(*o));
+
+			let (kind, parser, properties, suggestions) = match &node.kind {
+				NodeKind::Root => (0, 0, Vec::new(), None),
+				NodeKind::Literal(_) => (1, 0, Vec::new(), None),
+				NodeKind::Argument(_, parser) => {
+					let ask_server = node.suggestions.is_some() || parser.suggests_from_server();
+					(
+						2,
+						parser.id(),
+						parser.properties()?,
+						ask_server.then(|| "minecraft:ask_server".to_string()),
+					)
+				}
+			};
+
+			nodes.push(CommandNode {
+				kind,
+				executable: node.executor.is_some(),
+				children,
+				redirect,
+				name: self.name(index).to_string(),
+				parser: VarInt(parser),
+				properties,
+				suggestions,
+			});
+		}
+
+		Ok(Commands {
+			nodes,
+			root: VarInt(0),
+		})
+	}
+}
+
+// Сообщение как у ванильного сервера: что не так и кусок ввода до места ошибки
+fn command_error(input: &str, error: ParseError) -> ServerError {
+	let cursor = error.cursor.min(input.len());
+	let before: String = input[..cursor]
+		.chars()
+		.rev()
+		.take(10)
+		.collect::<Vec<_>>()
+		.into_iter()
+		.rev()
+		.collect();
+	let ellipsis = if before.len() < cursor { "..." } else { "" };
+
+	ServerError::Command(format!(
+		"{}\n{ellipsis}{before}{}<--[HERE]",
+		error.message,
+		&input[cursor..]
+	))
+}
+
+#[cfg(test)]
+mod tests {
+	use std::sync::Mutex;
+
+	use super::*;
+	use crate::{config::Config, context::ServerContext, player::context::test_player};
+
+	// Диспетчер с командой give <count> [reason...], в calls пишется то, с чем она вызвана
+	fn dispatcher(calls: &Arc<Mutex<Vec<(i32, String)>>>) -> CommandDispatcher {
+		let record = |calls: &Arc<Mutex<Vec<(i32, String)>>>| {
+			let calls = calls.clone();
+			move |context: &CommandContext| {
+				let count = context.integer("count").unwrap();
+				let reason = context.string("reason").unwrap_or_default().to_string();
+				calls.lock().unwrap().push((count, reason));
+				Ok(())
+			}
+		};
+
+		let mut dispatcher = CommandDispatcher::new();
+		dispatcher.register(
+			literal("give").alias("g").then(
+				argument(
+					"count",
+					ArgumentParser::Integer {
+						min: Some(1),
+						max: None,
+					},
+				)
+				.executes(record(calls))
+				.then(
+					argument(
+						"reason",
+						ArgumentParser::String(argument::StringKind::Greedy),
+					)
+					.executes(record(calls)),
+				),
+			),
+		);
+		dispatcher.register(literal("gamerule").requires(2).executes(|_| Ok(())));
+		dispatcher
+	}
+
+	fn player(level: u8) -> Arc<ClientContext> {
+		let server = Arc::new(ServerContext::new(Arc::new(Config::default())));
+		test_player(&server, "Steve", level)
+	}
+
+	#[test]
+	fn executes_with_arguments() {
+		let calls = Arc::new(Mutex::new(Vec::new()));
+		let dispatcher = dispatcher(&calls);
+		let player = player(0);
+
+		dispatcher.execute(player.clone(), "give 3").unwrap();
+		dispatcher
+			.execute(player.clone(), "g 5 for the win")
+			.unwrap();
+		assert_eq!(
+			*calls.lock().unwrap(),
+			vec![(3, String::new()), (5, "for the win".to_string())]
+		);
+
+		let Err(ServerError::Command(message)) = dispatcher.execute(player.clone(), "give 0") else {
+			panic!("0 меньше минимума");
+		};
+		assert!(message.contains("give 0<--[HERE]"), "{message}");
+		assert!(dispatcher.execute(player.clone(), "give").is_err());
+		assert!(dispatcher.execute(player, "take 1").is_err());
+	}
+
+	#[test]
+	fn permission_hides_commands() {
+		let calls = Arc::new(Mutex::new(Vec::new()));
+		let dispatcher = dispatcher(&calls);
+
+		assert!(dispatcher.execute(player(0), "gamerule").is_err());
+		dispatcher.execute(player(2), "gamerule").unwrap();
+
+		assert!(!dispatcher.commands(0).contains(&"gamerule".to_string()));
+		assert!(dispatcher.commands(2).contains(&"gamerule".to_string()));
+	}
+
+	#[test]
+	fn register_replaces_command() {
+		let calls = Arc::new(Mutex::new(Vec::new()));
+		let mut dispatcher = dispatcher(&calls);
+		dispatcher.register(literal("give").executes(|_| Ok(())));
+
+		dispatcher.execute(player(0), "give").unwrap();
+		assert_eq!(
+			dispatcher
+				.commands(0)
+				.iter()
+				.filter(|o| *o == "give")
+				.count(),
+			1
+		);
+	}
+
+	#[test]
+	fn suggestions() {
+		let calls = Arc::new(Mutex::new(Vec::new()));
+		let dispatcher = dispatcher(&calls);
+
+		assert_eq!(
+			dispatcher.suggest(player(0), "g"),
+			(0, vec!["g".to_string(), "give".to_string()])
+		);
+		assert_eq!(
+			dispatcher.suggest(player(2), "ga"),
+			(0, vec!["gamerule".to_string()])
+		);
+		assert_eq!(dispatcher.suggest(player(0), "ga"), (2, Vec::new()));
+	}
+}
@@ -2,6 +2,16 @@ use std::{fs, path::PathBuf};
 
 use serde::{Deserialize, Serialize};
 use serde_default::DefaultFromSerde;
+use uuid::Uuid;
+
+use crate::{
+	player::context::GameMode,
+	protocol::forwarding::ForwardingMode,
+	world::{
+		generator::{flat::FlatSettings, noise::NoiseSettings},
+		region::Compression,
+	},
+};
 
 #[derive(Debug, DefaultFromSerde, Serialize, Deserialize, Clone)]
 pub struct BindConfig {
@@ -9,6 +19,12 @@ pub struct BindConfig {
 	pub host: String,
 	#[serde(default = "default_timeout")]
 	pub timeout: u64,
+	#[serde(default)]
+	pub proxy_protocol: bool, // HAProxy PROXY protocol v1/v2 от балансировщика перед сервером
+	#[serde(default)]
+	pub proxy_trusted: Vec<String>, // подсети балансировщиков, например "10.0.0.0/8", без них сервер не запустится
+	#[serde(default = "default_workers")]
+	pub workers: usize, // потоки для логина и отключения клиентов
 }
 
 #[derive(Debug, DefaultFromSerde, Serialize, Deserialize, Clone)]
@@ -17,6 +33,125 @@ pub struct ServerConfig {
 	pub online_mode: bool,
 	#[serde(default = "default_compression")]
 	pub compression_threshold: Option<usize>,
+	#[serde(default = "default_session_server")]
+	pub session_server: String,
+	#[serde(default)]
+	pub prevent_proxy_connections: bool,
+	#[serde(default)]
+	pub game_mode: GameMode, // режим игры новых игроков
+	#[serde(default)]
+	pub permission_level: u8, // уровень прав обычных игроков, 0-4
+	#[serde(default)]
+	pub ops: Vec<String>, // ники или uuid операторов
+	#[serde(default = "default_op_permission_level")]
+	pub op_permission_level: u8, // уровень прав операторов из ops
+	#[serde(default)]
+	pub forwarding: ForwardingMode, // "none", "legacy" (BungeeCord) или "velocity"
+	#[serde(default)]
+	pub forwarding_secret: String, // секрет из forwarding.secret Velocity
+	#[serde(default = "default_shutdown_message")]
+	pub shutdown_message: String, // с чем кикаются игроки при остановке, в формате MiniMessage
+}
+
+impl ServerConfig {
+	/// Уровень прав игрока: op_permission_level для тех, кто есть в ops, иначе permission_level
+	pub fn permission_level(&self, name: &str, uuid: Uuid) -> u8 {
+		let op = self
+			.ops
+			.iter()
+			.any(|o| o.eq_ignore_ascii_case(name) || Uuid::parse_str(o).is_ok_and(|o| o == uuid));
+
+		if op {
+			self.op_permission_level.max(self.permission_level).min(4)
+		} else {
+			self.permission_level.min(4)
+		}
+	}
+}
+
+// То, что видно в списке серверов
+#[derive(Debug, DefaultFromSerde, Serialize, Deserialize, Clone)]
+pub struct StatusConfig {
+	#[serde(default = "default_motd")]
+	pub motd: String, // в формате MiniMessage, например "<gold>Hello <bold>world</bold>"
+	#[serde(default = "default_version_name")]
+	pub version_name: String,
+	#[serde(default = "default_max_players")]
+	pub max_players: i32,
+	#[serde(default = "default_favicon")]
+	pub favicon: String, // png 64x64, если файла нет - без иконки
+	#[serde(default = "default_sample_size")]
+	pub sample_size: usize, // сколько игроков показывать при наведении на онлайн, 0 - никого
+	#[serde(default)]
+	pub hide_players: bool, // не показывать онлайн вообще, в списке будет "???"
+}
+
+#[derive(Debug, DefaultFromSerde, Serialize, Deserialize, Clone)]
+pub struct WorldConfig {
+	#[serde(default = "default_world_path")]
+	pub path: String, // папка основного мира, в нем появляются игроки
+	#[serde(default = "default_world_name")]
+	pub name: String,
+	#[serde(default = "default_dimension")]
+	pub dimension: String,
+	#[serde(default)]
+	pub seed: i64,
+	#[serde(default = "default_region_compression")]
+	pub region_compression: Compression,
+	#[serde(default = "default_autosave_interval")]
+	pub autosave_interval: u64, // в секундах, 0 - не сохранять автоматически
+	#[serde(default = "default_generator")]
+	pub generator: String, // flat, void, noise или имя генератора из плагина
+	#[serde(default)]
+	pub flat: FlatSettings,
+	#[serde(default)]
+	pub noise: NoiseSettings,
+	#[serde(default)]
+	pub blocks_report: Option<String>, // путь до ванильного blocks.json, без него знаем только базовые блоки
+	#[serde(default)]
+	pub registries_report: Option<String>, // путь до ванильного registries.json, оттуда берутся айди предметов и реестров для тегов
+	#[serde(default)]
+	pub worlds: Vec<WorldSettings>, // остальные миры, загружаются вместе с основным
+	#[serde(default = "default_datapacks")]
+	pub datapacks: String, // папка с датапаками (папки и zip с pack.mcmeta)
+}
+
+impl WorldConfig {
+	/// Настройки основного мира
+	pub fn main_world(&self) -> WorldSettings {
+		WorldSettings {
+			name: self.name.clone(),
+			dimension: self.dimension.clone(),
+			generator: self.generator.clone(),
+			path: self.path.clone(),
+			seed: self.seed,
+		}
+	}
+}
+
+// Настройки одного мира, через них же плагины создают миры (WorldContext::create_world)
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct WorldSettings {
+	pub name: String, // например "minecraft:the_nether", у клиента это имя измерения
+	#[serde(default = "default_dimension")]
+	pub dimension: String, // тип измерения, от него зависит высота мира
+	#[serde(default = "default_generator")]
+	pub generator: String,
+	pub path: String,
+	#[serde(default)]
+	pub seed: i64,
+}
+
+impl WorldSettings {
+	pub fn new(name: &str, dimension: &str, generator: &str, path: &str) -> WorldSettings {
+		WorldSettings {
+			name: name.to_string(),
+			dimension: dimension.to_string(),
+			generator: generator.to_string(),
+			path: path.to_string(),
+			seed: 0,
+		}
+	}
 }
 
 #[derive(Debug, DefaultFromSerde, Serialize, Deserialize, Clone)]
@@ -25,6 +160,10 @@ pub struct Config {
 	pub bind: BindConfig,
 	#[serde(default)]
 	pub server: ServerConfig,
+	#[serde(default)]
+	pub world: WorldConfig,
+	#[serde(default)]
+	pub status: StatusConfig,
 }
 
 fn default_host() -> String {
@@ -33,15 +172,65 @@ fn default_host() -> String {
 fn default_timeout() -> u64 {
 	5
 }
+fn default_workers() -> usize {
+	16
+}
 fn default_compression() -> Option<usize> {
 	Some(256)
 }
+fn default_op_permission_level() -> u8 {
+	4
+}
+fn default_session_server() -> String {
+	"https://sessionserver.mojang.com".to_string()
+}
+fn default_shutdown_message() -> String {
+	"Server closed".to_string()
+}
+
+fn default_motd() -> String {
+	"<gray>A Minecraft Server".to_string()
+}
+fn default_version_name() -> String {
+	"1.21.5".to_string()
+}
+fn default_max_players() -> i32 {
+	20
+}
+fn default_favicon() -> String {
+	"server-icon.png".to_string()
+}
+fn default_sample_size() -> usize {
+	12
+}
+
+fn default_world_path() -> String {
+	"world".to_string()
+}
+fn default_world_name() -> String {
+	"minecraft:overworld".to_string()
+}
+fn default_dimension() -> String {
+	"minecraft:overworld".to_string()
+}
+fn default_datapacks() -> String {
+	"datapacks".to_string()
+}
+fn default_region_compression() -> Compression {
+	Compression::Zlib
+}
+fn default_autosave_interval() -> u64 {
+	300
+}
+fn default_generator() -> String {
+	"flat".to_string()
+}
 
 impl Config {
 	pub fn load_from_file(path: PathBuf) -> Option<Config> {
 		if !fs::exists(&path).unwrap_or_default() {
 			let table = Config::default();
-			fs::create_dir_all(&path.parent()?).ok()?;
+			fs::create_dir_all(path.parent()?).ok()?;
 			fs::write(&path, toml::to_string_pretty(&table).ok()?).ok()?;
 			return Some(table);
 		}
@@ -50,3 +239,27 @@ impl Config {
 		Some(table)
 	}
 }
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn players_are_not_operators_by_default() {
+		let config = ServerConfig::default();
+		assert_eq!(config.permission_level("Notch", Uuid::from_u128(1)), 0);
+	}
+
+	#[test]
+	fn operators_by_name_or_uuid() {
+		let uuid = Uuid::from_u128(7);
+		let config: ServerConfig = toml::from_str(&format!(
+			"ops = [\"Notch\", \"{uuid}\"]\nop_permission_level = 3"
+		))
+		.unwrap();
+
+		assert_eq!(config.permission_level("notch", Uuid::from_u128(1)), 3);
+		assert_eq!(config.permission_level("jeb_", uuid), 3);
+		assert_eq!(config.permission_level("jeb_", Uuid::from_u128(1)), 0);
+	}
+}
@@ -1,78 +1,197 @@
 use std::{
-	net::SocketAddr,
-	sync::{Arc, atomic::AtomicI32},
+	collections::HashMap,
+	path::Path,
+	sync::{
+		Arc, Condvar, Mutex, OnceLock, RwLock,
+		atomic::{AtomicI32, Ordering},
+	},
+	thread,
 };
 
+use craftflow_nbt::DynNBT;
 use dashmap::DashMap;
 use itertools::Itertools;
+use log::{info, warn};
+use sha1::{Digest, Sha1};
 use uuid::Uuid;
 
 use super::{
-	config::Config,
+	ServerError,
+	command::{CommandBuilder, CommandDispatcher, builtin},
+	config::{Config, WorldConfig, WorldSettings},
+	data::slot::nbt::ItemNbt,
 	event::{Listener, PacketHandler},
-	player::context::ClientContext,
+	play::{planner::Planner, workers::WorkerPool},
+	player::{
+		context::ClientContext,
+		data::{PlayerDataStore, save_player},
+		registry::ClientRegistry,
+	},
+	protocol::{
+		encryption::ServerKeys,
+		session::{MojangSessionVerifier, SessionVerifier},
+		status::{favicon_url, load_favicon},
+	},
+	world::{
+		block::BlockRegistry,
+		chunk::{BlockState, Chunk},
+		datapack::load_datapacks,
+		dimension::DimensionType,
+		entity::EntityRegistry,
+		generator::{FlatGenerator, NoiseGenerator, VoidGenerator, WorldGenerator},
+		item::ItemRegistry,
+		light::{LightChange, LightEngine},
+		region::Compression,
+		registry::{DIMENSION_TYPE, Registries, TagValue},
+		storage::WorldStorage,
+	},
 };
 
 // Контекст сервера
 // Должен быть обернут в Arc для передачи между потоками
 pub struct ServerContext {
 	pub config: Arc<Config>,
-	pub clients: DashMap<SocketAddr, Arc<ClientContext>>,
+	pub clients: ClientRegistry,
 	pub world: WorldContext,
+	commands: CommandDispatcher,
+	planner: Planner,
+	workers: WorkerPool,
+	chunk_workers: WorkerPool,
 	listeners: Vec<Box<dyn Listener>>,
 	handlers: Vec<Box<dyn PacketHandler>>,
+	session_verifier: Box<dyn SessionVerifier>,
+	keys: OnceLock<ServerKeys>,
+	favicon: Option<String>,
+	stop: (Mutex<bool>, Condvar), // запрошена ли остановка, ее ждет ServerHandle::wait
 }
 
 impl ServerContext {
 	pub fn new(config: Arc<Config>) -> ServerContext {
+		let mut commands = CommandDispatcher::new();
+		builtin::register(&mut commands);
+
 		ServerContext {
+			commands,
+			planner: Planner::new(),
+			workers: WorkerPool::new("worker", config.bind.workers),
+			chunk_workers: WorkerPool::new("chunks", chunk_workers()),
+			session_verifier: Box::new(MojangSessionVerifier::new(&config.server.session_server)),
+			world: WorldContext::new(&config),
+			favicon: load_favicon(&config.status.favicon),
 			config,
 			listeners: Vec::new(),
 			handlers: Vec::new(),
-			clients: DashMap::new(),
-			world: WorldContext::new(),
+			clients: ClientRegistry::new(),
+			keys: OnceLock::new(),
+			stop: (Mutex::new(false), Condvar::new()),
+		}
+	}
+
+	/// Просит остановить сервер, саму остановку делает ServerHandle
+	pub fn stop(&self) {
+		*self.stop.0.lock().unwrap() = true;
+		self.stop.1.notify_all();
+	}
+
+	pub fn is_stopping(&self) -> bool {
+		*self.stop.0.lock().unwrap()
+	}
+
+	/// Ждет, пока кто-нибудь не вызовет stop
+	pub fn wait_stop(&self) {
+		let mut stop = self.stop.0.lock().unwrap();
+		while !*stop {
+			stop = self.stop.1.wait(stop).unwrap();
+		}
+	}
+
+	/// RSA ключи сервера, генерируются при первом обращении
+	pub fn keys(self: &Arc<Self>) -> Result<&ServerKeys, ServerError> {
+		if let Some(keys) = self.keys.get() {
+			return Ok(keys);
 		}
+		let keys = ServerKeys::generate()?;
+		Ok(self.keys.get_or_init(|| keys))
+	}
+
+	/// Иконка для списка серверов в виде data url
+	pub fn favicon(&self) -> Option<&str> {
+		self.favicon.as_deref()
+	}
+
+	/// Заменяет иконку, png лучше 64x64
+	pub fn set_favicon(&mut self, png: &[u8]) {
+		self.favicon = Some(favicon_url(png));
 	}
 
+	pub fn set_session_verifier(&mut self, verifier: Box<dyn SessionVerifier>) {
+		self.session_verifier = verifier;
+	}
+
+	pub fn session_verifier(self: &Arc<Self>) -> &dyn SessionVerifier {
+		self.session_verifier.as_ref()
+	}
+
+	// Игроки ищутся только среди тех, кто уже в игре
+
 	pub fn get_player_by_uuid(self: &Arc<Self>, uuid: Uuid) -> Option<Arc<ClientContext>> {
 		self
 			.clients
-			.iter()
+			.by_uuid(uuid)
 			.filter(|o| o.entity_info().is_some())
-			.find(|o| {
-				let info = o.player_info();
-				if let Some(info) = info {
-					info.uuid == uuid
-				} else {
-					false
-				}
-			})
-			.map(|o| o.clone())
 	}
 
+	/// Поиск по имени без учета регистра
 	pub fn get_player_by_name(self: &Arc<Self>, name: &str) -> Option<Arc<ClientContext>> {
 		self
 			.clients
-			.iter()
+			.by_name(name)
+			.filter(|o| o.entity_info().is_some())
+	}
+
+	pub fn get_player_by_entity(self: &Arc<Self>, entity_id: i32) -> Option<Arc<ClientContext>> {
+		self
+			.clients
+			.by_entity(entity_id)
 			.filter(|o| o.entity_info().is_some())
-			.find(|o| {
-				let info = o.player_info();
-				if let Some(info) = info {
-					info.name == name
-				} else {
-					false
-				}
-			})
-			.map(|o| o.clone())
 	}
 
 	pub fn players(self: &Arc<Self>) -> Vec<Arc<ClientContext>> {
 		self
 			.clients
-			.iter()
+			.all()
+			.into_iter()
 			.filter(|o| o.player_info().is_some())
 			.filter(|o| o.entity_info().is_some())
-			.map(|o| o.clone())
+			.collect()
+	}
+
+	/// Вызывает f для каждого игрока в игре
+	pub fn for_each_player(self: &Arc<Self>, mut f: impl FnMut(&Arc<ClientContext>)) {
+		for player in self.players() {
+			f(&player);
+		}
+	}
+
+	pub fn player_count(self: &Arc<Self>) -> usize {
+		self.players().len()
+	}
+
+	/// Сохраняет данные всех игроков в игре, возвращает сколько сохранено
+	pub fn save_players(self: &Arc<Self>) -> Result<usize, ServerError> {
+		let players = self.players();
+		for player in players.iter() {
+			save_player(player)?;
+		}
+		Ok(players.len())
+	}
+
+	/// Игроки в мире с этим именем
+	pub fn players_in(self: &Arc<Self>, world: &str) -> Vec<Arc<ClientContext>> {
+		self
+			.players()
+			.into_iter()
+			.filter(|o| o.entity_info().is_some_and(|o| o.entity.world() == world))
 			.collect()
 	}
 
@@ -84,31 +203,422 @@ impl ServerContext {
 		self.listeners.push(listener);
 	}
 
-	pub fn packet_handlers<F, K>(self: &Arc<Self>, sort_by: F) -> Vec<&Box<dyn PacketHandler>>
+	/// Регистрирует команду, встроенная команда с тем же именем заменяется
+	pub fn add_command(&mut self, command: CommandBuilder) {
+		self.commands.register(command);
+	}
+
+	pub fn commands(self: &Arc<Self>) -> &CommandDispatcher {
+		&self.commands
+	}
+
+	/// Планировщик задач на тиках сервера
+	pub fn planner(self: &Arc<Self>) -> &Planner {
+		&self.planner
+	}
+
+	/// Пул потоков для логина и отключения клиентов, тик не должен ждать диск и сеть
+	pub fn workers(self: &Arc<Self>) -> &WorkerPool {
+		&self.workers
+	}
+
+	/// Пул потоков для генерации и отправки чанков, отдельно от workers,
+	/// чтобы зависшие логины не задерживали чанки
+	pub fn chunk_workers(self: &Arc<Self>) -> &WorkerPool {
+		&self.chunk_workers
+	}
+
+	/// Регистрирует генератор мира, в конфиге его можно выбрать по имени (world.generator)
+	pub fn add_generator(&mut self, name: &str, generator: Box<dyn WorldGenerator>) {
+		self.world.add_generator(name, generator);
+	}
+
+	/// Свой биом, data - NBT как в датапаке (worldgen/biome/*.json)
+	pub fn add_biome(&mut self, id: &str, data: DynNBT) -> i32 {
+		self.world.registries.add_biome(id, data)
+	}
+
+	/// Свой тип измерения, после этого его можно указывать в мирах
+	pub fn add_dimension_type(&mut self, dimension: &DimensionType) -> i32 {
+		self.world.registries.add_dimension_type(dimension)
+	}
+
+	pub fn add_tag(&mut self, registry: &str, tag: &str, values: Vec<TagValue>) {
+		self.world.registries.add_tag(registry, tag, values);
+	}
+
+	pub fn packet_handlers<F, K>(self: &Arc<Self>, sort_by: F) -> Vec<&dyn PacketHandler>
 	where
 		K: Ord,
-		F: FnMut(&&Box<dyn PacketHandler>) -> K,
+		F: FnMut(&&dyn PacketHandler) -> K,
 	{
-		self.handlers.iter().sorted_by_key(sort_by).collect_vec()
+		self
+			.handlers
+			.iter()
+			.map(|o| o.as_ref())
+			.sorted_by_key(sort_by)
+			.collect_vec()
 	}
 
-	pub fn listeners<F, K>(self: &Arc<Self>, sort_by: F) -> Vec<&Box<dyn Listener>>
+	pub fn listeners<F, K>(self: &Arc<Self>, sort_by: F) -> Vec<&dyn Listener>
 	where
 		K: Ord,
-		F: FnMut(&&Box<dyn Listener>) -> K,
+		F: FnMut(&&dyn Listener) -> K,
 	{
-		self.listeners.iter().sorted_by_key(sort_by).collect_vec()
+		self
+			.listeners
+			.iter()
+			.map(|o| o.as_ref())
+			.sorted_by_key(sort_by)
+			.collect_vec()
 	}
 }
 
+// Все миры сервера и общие для них реестры
 pub struct WorldContext {
-	pub entity_id_counter: AtomicI32,
+	pub blocks: BlockRegistry,
+	pub items: ItemRegistry,
+	pub registries: Registries, // биомы, типы измерений и прочее, что уходит клиенту в Configuration
+	pub player_data: PlayerDataStore, // playerdata в папке основного мира
+	default: String,            // мир, в котором появляются игроки
+	compression: Compression,
+	worlds: DashMap<String, Arc<World>>,
+	generators: HashMap<String, Arc<dyn WorldGenerator>>,
+	entity_id_counter: AtomicI32, // айди сущностей общие на все миры
 }
 
 impl WorldContext {
-	pub fn new() -> WorldContext {
-		WorldContext {
+	pub fn new(config: &Config) -> WorldContext {
+		let blocks = match &config.world.blocks_report {
+			Some(path) => BlockRegistry::load_report(path).unwrap_or_else(|error| {
+				warn!("Не удалось загрузить {path}: {error:?}, используем встроенные блоки");
+				BlockRegistry::new()
+			}),
+			None => BlockRegistry::new(),
+		};
+
+		let items = match &config.world.registries_report {
+			Some(path) => ItemRegistry::load_report(path).unwrap_or_else(|error| {
+				warn!("Не удалось загрузить {path}: {error:?}, используем встроенные предметы");
+				ItemRegistry::new()
+			}),
+			None => ItemRegistry::new(),
+		};
+
+		// Датапаки грузятся до плагинов, так что плагины могут перезаписать их записи
+		let mut registries = Registries::vanilla();
+		if let Some(path) = &config.world.registries_report
+			&& let Err(error) = registries.load_report(path)
+		{
+			warn!("Не удалось загрузить {path}: {error:?}, используем встроенные реестры");
+		}
+		for pack in load_datapacks(&config.world.datapacks) {
+			let (entries, tags) = pack.apply(&mut registries);
+			info!(
+				"Загружен датапак {}: записей {entries}, тегов {tags}",
+				pack.name
+			);
+		}
+
+		let mut world = WorldContext {
+			blocks,
+			items,
+			registries,
+			player_data: PlayerDataStore::new(Path::new(&config.world.path).join("playerdata")),
+			default: config.world.name.clone(),
+			compression: config.world.region_compression,
+			worlds: DashMap::new(),
+			generators: HashMap::new(),
 			entity_id_counter: AtomicI32::new(0),
+		};
+
+		world.add_generator("flat", Box::new(FlatGenerator::new(&config.world.flat)));
+		world.add_generator("void", Box::new(VoidGenerator));
+		world.add_generator(
+			"noise",
+			Box::new(NoiseGenerator::new(config.world.noise.clone())),
+		);
+
+		world
+	}
+
+	pub fn add_generator(&mut self, name: &str, generator: Box<dyn WorldGenerator>) {
+		self
+			.generators
+			.insert(name.to_string(), Arc::from(generator));
+	}
+
+	pub fn generator(&self, name: &str) -> Result<Arc<dyn WorldGenerator>, ServerError> {
+		self
+			.generators
+			.get(name)
+			.cloned()
+			.ok_or_else(|| ServerError::UnknownGenerator(name.to_string()))
+	}
+
+	/// Загружает основной мир и остальные миры из конфига, зовется при старте сервера
+	pub fn load_worlds(&self, config: &WorldConfig) -> Result<(), ServerError> {
+		self.create_world(&config.main_world())?;
+		for settings in config.worlds.iter() {
+			self.create_world(settings)?;
+		}
+		Ok(())
+	}
+
+	/// Создает мир, если в его папке уже есть чанки - они подгрузятся с диска
+	pub fn create_world(&self, settings: &WorldSettings) -> Result<Arc<World>, ServerError> {
+		// minecraft: в имени типа можно не писать
+		let name = match self.registries.id(DIMENSION_TYPE, &settings.dimension) {
+			Some(_) => settings.dimension.clone(),
+			None => format!("minecraft:{}", settings.dimension),
+		};
+		let (dimension, dimension_id) = self
+			.registries
+			.dimension_type(&name)
+			.zip(self.registries.id(DIMENSION_TYPE, &name))
+			.ok_or_else(|| ServerError::UnknownDimension(settings.dimension.clone()))?;
+		dimension.validate()?;
+
+		let world = Arc::new(World {
+			name: settings.name.clone(),
+			dimension,
+			dimension_id,
+			hashed_seed: hash_seed(settings.seed),
+			entities: EntityRegistry::new(),
+			storage: WorldStorage::new(&settings.path, self.compression),
+			generator_name: settings.generator.clone(),
+			generator: self.generator(&settings.generator)?,
+			spawn_point: RwLock::new(None),
+			light: Mutex::new(()),
+		});
+
+		match self.worlds.entry(settings.name.clone()) {
+			dashmap::Entry::Occupied(_) => Err(ServerError::WorldExists(settings.name.clone())),
+			dashmap::Entry::Vacant(entry) => {
+				entry.insert(world.clone());
+				Ok(world)
+			}
+		}
+	}
+
+	/// Сохраняет и выгружает мир, игроков из него надо увести заранее (play::world::unload_world)
+	///
+	/// Основной мир выгрузить нельзя
+	pub fn unload_world(&self, name: &str) -> Result<Arc<World>, ServerError> {
+		if name == self.default {
+			return Err(ServerError::MainWorld);
+		}
+
+		let world = self
+			.get(name)
+			.ok_or_else(|| ServerError::UnknownWorld(name.to_string()))?;
+		world.storage.save_all()?;
+		self.worlds.remove(name);
+
+		Ok(world)
+	}
+
+	pub fn get(&self, name: &str) -> Option<Arc<World>> {
+		self.worlds.get(name).map(|o| o.clone())
+	}
+
+	/// Основной мир, в нем появляются игроки
+	pub fn default_world(&self) -> Result<Arc<World>, ServerError> {
+		self
+			.get(&self.default)
+			.ok_or_else(|| ServerError::UnknownWorld(self.default.clone()))
+	}
+
+	/// Все миры, основной первым
+	pub fn worlds(&self) -> Vec<Arc<World>> {
+		self
+			.worlds
+			.iter()
+			.map(|o| o.clone())
+			.sorted_by_key(|o| (o.name != self.default, o.name.clone()))
+			.collect()
+	}
+
+	/// Имена всех миров, основной первым
+	pub fn names(&self) -> Vec<String> {
+		self.worlds().into_iter().map(|o| o.name.clone()).collect()
+	}
+
+	/// Перевод предметов в NBT по реестрам сервера
+	pub fn item_nbt(&self) -> ItemNbt<'_> {
+		ItemNbt::new(&self.items, &self.registries)
+	}
+
+	/// Свободный айди для новой сущности в любом мире
+	pub fn next_entity_id(&self) -> i32 {
+		self.entity_id_counter.fetch_add(1, Ordering::SeqCst)
+	}
+
+	/// Сохраняет измененные чанки всех миров, возвращает сколько сохранено
+	pub fn save_all(&self) -> Result<usize, ServerError> {
+		let mut count = 0;
+		for world in self.worlds() {
+			count += world.storage.save_all()?;
 		}
+		Ok(count)
+	}
+}
+
+// Генерация чанков нагружает процессор, больше потоков, чем ядер, смысла нет
+fn chunk_workers() -> usize {
+	thread::available_parallelism().map_or(4, |o| o.get())
+}
+
+// Хеш сида для Login и Respawn, клиент использует его только для шума биомов,
+// поэтому берем sha1 вместо ванильного sha256
+fn hash_seed(seed: i64) -> i64 {
+	let hash = Sha1::digest(seed.to_be_bytes());
+	i64::from_be_bytes(hash[..8].try_into().unwrap())
+}
+
+// Один мир: свои тип измерения, генератор, чанки, сущности и точка спавна
+pub struct World {
+	pub name: String,
+	pub dimension: DimensionType,
+	dimension_id: i32, // айди типа в реестре, который знает клиент
+	pub hashed_seed: i64,
+	pub entities: EntityRegistry,
+	pub storage: WorldStorage,
+	generator_name: String,
+	generator: Arc<dyn WorldGenerator>,
+	spawn_point: RwLock<Option<(f64, f64, f64)>>, // None - берется у генератора
+	light: Mutex<()>, // свет считается по одной области за раз, см. LightEngine
+}
+
+impl World {
+	pub fn generator(&self) -> &dyn WorldGenerator {
+		self.generator.as_ref()
+	}
+
+	/// Суперплоский ли мир, клиент тогда рисует горизонт ниже
+	pub fn is_flat(&self) -> bool {
+		self.generator_name == "flat"
+	}
+
+	/// Айди типа измерения у клиента
+	pub fn dimension_id(&self) -> i32 {
+		self.dimension_id
+	}
+
+	/// Чанк из памяти, с диска, либо сгенерированный
+	pub fn chunk(&self, x: i32, z: i32) -> Result<Arc<RwLock<Chunk>>, ServerError> {
+		self
+			.storage
+			.chunk_or_insert_with(x, z, || self.generator.generate(x, z, &self.dimension))
+	}
+
+	// Центральный чанк и загруженные соседи для LightEngine
+	fn light_region(
+		&self,
+		x: i32,
+		z: i32,
+		center: Arc<RwLock<Chunk>>,
+	) -> Vec<Option<Arc<RwLock<Chunk>>>> {
+		let mut chunks = Vec::with_capacity(9);
+		for dz in -1..=1 {
+			for dx in -1..=1 {
+				if dx == 0 && dz == 0 {
+					chunks.push(Some(center.clone()));
+				} else {
+					chunks.push(self.storage.loaded_chunk(x + dx, z + dz));
+				}
+			}
+		}
+		chunks
+	}
+
+	/// Считает свет в чанке, если он еще не посчитан
+	///
+	/// Возвращает чанки, в которых поменялся свет (включая этот),
+	/// соседям надо отправить Update Light
+	pub fn light_chunk(&self, x: i32, z: i32) -> Result<Vec<LightChange>, ServerError> {
+		let chunk = self.chunk(x, z)?;
+		if chunk.read().unwrap().is_light_on() {
+			return Ok(Vec::new());
+		}
+
+		let _lock = self.light.lock().unwrap();
+
+		// Пока ждали, свет мог посчитать другой поток
+		if chunk.read().unwrap().is_light_on() {
+			return Ok(Vec::new());
+		}
+
+		let region = self.light_region(x, z, chunk);
+		let mut engine = LightEngine::new(x, z, &region, &self.dimension);
+		engine.light_center();
+		Ok(engine.finish())
+	}
+
+	/// Блок по абсолютным координатам, чанк при необходимости загружается
+	pub fn get_block(&self, x: i32, y: i32, z: i32) -> Result<BlockState, ServerError> {
+		let chunk = self.chunk(x >> 4, z >> 4)?;
+		let block = chunk
+			.read()
+			.unwrap()
+			.get_block((x & 15) as usize, y, (z & 15) as usize);
+		Ok(block)
+	}
+
+	/// Блок из уже загруженного чанка, None если чанк не загружен, ничего не генерирует
+	pub fn loaded_block(&self, x: i32, y: i32, z: i32) -> Option<BlockState> {
+		let chunk = self.storage.loaded_chunk(x >> 4, z >> 4)?;
+		let block = chunk
+			.read()
+			.unwrap()
+			.get_block((x & 15) as usize, y, (z & 15) as usize);
+		Some(block)
+	}
+
+	/// Ставит блок (координаты абсолютные) и пересчитывает вокруг него свет
+	///
+	/// Возвращает старый блок и чанки, в которых поменялся свет
+	pub fn set_block(
+		&self,
+		x: i32,
+		y: i32,
+		z: i32,
+		block: BlockState,
+	) -> Result<(BlockState, Vec<LightChange>), ServerError> {
+		let (chunk_x, chunk_z) = (x >> 4, z >> 4);
+		let chunk = self.chunk(chunk_x, chunk_z)?;
+
+		let _lock = self.light.lock().unwrap();
+
+		let (old, light_on) = {
+			let mut chunk = chunk.write().unwrap();
+			let light_on = chunk.is_light_on();
+			let old = chunk.set_block((x & 15) as usize, y, (z & 15) as usize, block.clone());
+			chunk.set_light_on(light_on);
+			(old, light_on)
+		};
+
+		// В неосвещенном чанке свет посчитается целиком когда он понадобится
+		if !light_on || old == block {
+			return Ok((old, Vec::new()));
+		}
+
+		let region = self.light_region(chunk_x, chunk_z, chunk);
+		let mut engine = LightEngine::new(chunk_x, chunk_z, &region, &self.dimension);
+		engine.update_block(x, y, z);
+		Ok((old, engine.finish()))
+	}
+
+	/// Где появляются игроки, если не задано через set_spawn_point - решает генератор
+	pub fn spawn_point(&self) -> (f64, f64, f64) {
+		self
+			.spawn_point
+			.read()
+			.unwrap()
+			.unwrap_or_else(|| self.generator.spawn_point(&self.dimension))
+	}
+
+	pub fn set_spawn_point(&self, position: (f64, f64, f64)) {
+		*self.spawn_point.write().unwrap() = Some(position);
 	}
 }
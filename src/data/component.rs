@@ -1,42 +1,172 @@
-use std::io::Read;
+use std::{collections::HashMap, io::Read};
 
+use craftflow_nbt::DynNBT;
 use palette::{Hsl, IntoColor, Srgb};
 use rust_mc_proto::Packet;
-use serde::{Deserialize, Serialize};
-use serde_with::skip_serializing_none;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use serde_json::{Map, Value, json};
+use uuid::Uuid;
 
 use crate::ServerError;
 
-use super::ReadWriteNBT;
+use super::{
+	NBT_MAX_DEPTH, ReadWriteNBT, json_to_nbt, legacy, minimessage, nbt_to_json, nbt_too_deep,
+};
 
-#[derive(Debug, Serialize, Deserialize, Clone)]
-#[skip_serializing_none]
+// Текст-компоненты чата в формате 1.21.5
+//
+// Две формы: json (serde_json::Value) для конфигов и чата, NBT для пакетов, каждая пишется напрямую.
+// В NBT bool становятся байтами, uuid - массивом интов, а компонент из одного текста пишется просто строкой.
+// Читается все, что может прислать клиент или написать человек: строки, числа, списки,
+// обертки {"": ...} из NBT и старые clickEvent/hoverEvent
+
+/// 16 именованных цветов: имя, код в legacy форматировании и RGB
+pub const NAMED_COLORS: &[(&str, char, u32)] = &[
+	("black", '0', 0x000000),
+	("dark_blue", '1', 0x0000AA),
+	("dark_green", '2', 0x00AA00),
+	("dark_aqua", '3', 0x00AAAA),
+	("dark_red", '4', 0xAA0000),
+	("dark_purple", '5', 0xAA00AA),
+	("gold", '6', 0xFFAA00),
+	("gray", '7', 0xAAAAAA),
+	("dark_gray", '8', 0x555555),
+	("blue", '9', 0x5555FF),
+	("green", 'a', 0x55FF55),
+	("aqua", 'b', 0x55FFFF),
+	("red", 'c', 0xFF5555),
+	("light_purple", 'd', 0xFF55FF),
+	("yellow", 'e', 0xFFFF55),
+	("white", 'f', 0xFFFFFF),
+];
+
+/// RGB цвета компонента, именованного или #RRGGBB
+pub fn color_rgb(color: &str) -> Option<u32> {
+	match color.strip_prefix('#') {
+		// from_str_radix пропускает знак, поэтому цифры проверяются отдельно
+		Some(hex) if hex.len() == 6 && hex.chars().all(|o| o.is_ascii_hexdigit()) => {
+			u32::from_str_radix(hex, 16).ok()
+		}
+		Some(_) => None,
+		None => NAMED_COLORS
+			.iter()
+			.find(|o| o.0 == color.replace("grey", "gray"))
+			.map(|o| o.2),
+	}
+}
+
+/// Чем заполнен компонент, тип в json определяется по ключу
+#[derive(Debug, Clone, PartialEq)]
+pub enum Content {
+	Text(String),
+	Translatable {
+		key: String,
+		fallback: Option<String>,
+		with: Vec<TextComponent>,
+	},
+	Score {
+		name: String, // имя игрока, селектор или * (тот, кто видит)
+		objective: String,
+	},
+	Selector {
+		selector: String,
+		separator: Option<Box<TextComponent>>,
+	},
+	Keybind(String), // например key.jump, клиент подставит свою клавишу
+	Nbt {
+		path: String,
+		interpret: Option<bool>,
+		separator: Option<Box<TextComponent>>,
+		source: NbtSource,
+	},
+}
+
+/// Откуда nbt компонент берет данные
+#[derive(Debug, Clone, PartialEq)]
+pub enum NbtSource {
+	Block(String),  // координаты
+	Entity(String), // селектор
+	Storage(String),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum ClickEvent {
+	OpenUrl(String),
+	OpenFile(String),
+	RunCommand(String), // со слешем
+	SuggestCommand(String),
+	ChangePage(i32),
+	CopyToClipboard(String),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum HoverEvent {
+	ShowText(Box<TextComponent>),
+	ShowItem {
+		id: String,
+		count: i32,
+		components: Option<DynNBT>, // компоненты предмета как в /give
+	},
+	ShowEntity {
+		id: String, // тип сущности
+		uuid: Uuid,
+		name: Option<Box<TextComponent>>,
+	},
+}
+
+#[derive(Debug, Clone, PartialEq)]
 pub struct TextComponent {
-	pub text: String,
-	pub color: Option<String>,
+	pub content: Content,
+	pub color: Option<String>, // имя цвета или #RRGGBB
+	pub font: Option<String>,
 	pub bold: Option<bool>,
 	pub italic: Option<bool>,
 	pub underlined: Option<bool>,
 	pub strikethrough: Option<bool>,
 	pub obfuscated: Option<bool>,
+	pub shadow_color: Option<i32>, // ARGB
+	pub insertion: Option<String>, // вставляется в чат по шифт-клику
+	pub click_event: Option<ClickEvent>,
+	pub hover_event: Option<HoverEvent>,
 	pub extra: Option<Vec<TextComponent>>,
-	// TODO: добавить все остальные стандартные поля для текст-компонента типа клик ивентов и сделать отдельный структ для транслейт компонент
 }
 
 impl TextComponent {
 	pub fn new(text: String) -> Self {
+		Self::from_content(Content::Text(text))
+	}
+
+	pub fn from_content(content: Content) -> Self {
 		Self {
-			text,
+			content,
 			color: None,
+			font: None,
 			bold: None,
 			italic: None,
 			underlined: None,
 			strikethrough: None,
 			obfuscated: None,
+			shadow_color: None,
+			insertion: None,
+			click_event: None,
+			hover_event: None,
 			extra: None,
 		}
 	}
 
+	/// Текст по ключу перевода клиента, %s заменяются на with
+	pub fn translatable(key: &str, with: Vec<TextComponent>) -> Self {
+		Self::from_content(Content::Translatable {
+			key: key.to_string(),
+			fallback: None,
+			with,
+		})
+	}
+
+	pub fn keybind(key: &str) -> Self {
+		Self::from_content(Content::Keybind(key.to_string()))
+	}
+
 	pub fn rainbow_offset(text: String, offset: i64) -> TextComponent {
 		if text.is_empty() {
 			return TextComponent::new(text);
@@ -97,12 +227,379 @@ impl TextComponent {
 		TextComponentBuilder::new()
 	}
 
+	/// Только текст без стилей и детей, такой пишется просто строкой
+	pub fn is_plain(&self) -> bool {
+		matches!(self.content, Content::Text(_)) && *self == Self::from_content(self.content.clone())
+	}
+
+	/// Текст без форматирования, переводы и прочее не раскрываются
+	pub fn plain_text(&self) -> String {
+		let mut text = match &self.content {
+			Content::Text(text) => text.clone(),
+			Content::Translatable { key, fallback, .. } => fallback.clone().unwrap_or(key.clone()),
+			Content::Score { name, .. } => name.clone(),
+			Content::Selector { selector, .. } => selector.clone(),
+			Content::Keybind(key) => key.clone(),
+			Content::Nbt { path, .. } => path.clone(),
+		};
+		for child in self.extra.iter().flatten() {
+			text.push_str(&child.plain_text());
+		}
+		text
+	}
+
+	/// Компонент из разметки вида `<gold>Hello <bold>world`, см. data::minimessage
+	pub fn from_mini(text: &str) -> TextComponent {
+		minimessage::parse(text)
+	}
+
+	pub fn to_mini(&self) -> String {
+		minimessage::serialize(self)
+	}
+
+	/// Компонент из текста с цветовыми кодами, code - & или §
+	pub fn from_legacy(text: &str, code: char) -> TextComponent {
+		legacy::parse(text, code)
+	}
+
+	pub fn to_legacy(&self, code: char) -> String {
+		legacy::serialize(self, code)
+	}
+
 	pub fn as_json(self) -> Result<String, ServerError> {
-		serde_json::to_string(&self).map_err(|_| ServerError::SerTextComponent)
+		serde_json::to_string(&self.to_value()).map_err(|_| ServerError::SerTextComponent)
 	}
 
 	pub fn from_json(text: &str) -> Result<TextComponent, ServerError> {
-		serde_json::from_str(text).map_err(|_| ServerError::DeTextComponent)
+		let value: Value = serde_json::from_str(text).map_err(|_| ServerError::DeTextComponent)?;
+		Self::from_value(&value)
+	}
+
+	/// Json форма компонента
+	pub fn to_value(&self) -> Value {
+		if let Content::Text(text) = &self.content
+			&& self.is_plain()
+		{
+			return Value::String(text.clone());
+		}
+
+		let mut map = Map::new();
+
+		match &self.content {
+			Content::Text(text) => {
+				map.insert("text".into(), text.clone().into());
+			}
+			Content::Translatable {
+				key,
+				fallback,
+				with,
+			} => {
+				map.insert("translate".into(), key.clone().into());
+				if let Some(fallback) = fallback {
+					map.insert("fallback".into(), fallback.clone().into());
+				}
+				if !with.is_empty() {
+					map.insert("with".into(), with.iter().map(|o| o.to_value()).collect());
+				}
+			}
+			Content::Score { name, objective } => {
+				map.insert(
+					"score".into(),
+					json!({ "name": name, "objective": objective }),
+				);
+			}
+			Content::Selector {
+				selector,
+				separator,
+			} => {
+				map.insert("selector".into(), selector.clone().into());
+				if let Some(separator) = separator {
+					map.insert("separator".into(), separator.to_value());
+				}
+			}
+			Content::Keybind(key) => {
+				map.insert("keybind".into(), key.clone().into());
+			}
+			Content::Nbt {
+				path,
+				interpret,
+				separator,
+				source,
+			} => {
+				map.insert("nbt".into(), path.clone().into());
+				if let Some(interpret) = interpret {
+					map.insert("interpret".into(), (*interpret).into());
+				}
+				if let Some(separator) = separator {
+					map.insert("separator".into(), separator.to_value());
+				}
+				let (key, value) = match source {
+					NbtSource::Block(value) => ("block", value),
+					NbtSource::Entity(value) => ("entity", value),
+					NbtSource::Storage(value) => ("storage", value),
+				};
+				map.insert(key.into(), value.clone().into());
+			}
+		}
+
+		let mut insert = |key: &str, value: Option<Value>| {
+			if let Some(value) = value {
+				map.insert(key.into(), value);
+			}
+		};
+
+		insert("color", self.color.clone().map(Value::from));
+		insert("font", self.font.clone().map(Value::from));
+		insert("bold", self.bold.map(Value::from));
+		insert("italic", self.italic.map(Value::from));
+		insert("underlined", self.underlined.map(Value::from));
+		insert("strikethrough", self.strikethrough.map(Value::from));
+		insert("obfuscated", self.obfuscated.map(Value::from));
+		insert("shadow_color", self.shadow_color.map(Value::from));
+		insert("insertion", self.insertion.clone().map(Value::from));
+		insert(
+			"click_event",
+			self.click_event.as_ref().map(|o| o.to_value()),
+		);
+		insert(
+			"hover_event",
+			self.hover_event.as_ref().map(|o| o.to_value()),
+		);
+		insert(
+			"extra",
+			self
+				.extra
+				.as_ref()
+				.filter(|o| !o.is_empty())
+				.map(|o| o.iter().map(|o| o.to_value()).collect()),
+		);
+
+		Value::Object(map)
+	}
+
+	/// Компонент из json, понимает и то, что получается из NBT через nbt_to_json
+	pub fn from_value(value: &Value) -> Result<TextComponent, ServerError> {
+		let map = match value {
+			Value::String(text) => return Ok(TextComponent::new(text.clone())),
+			Value::Number(number) => return Ok(TextComponent::new(number.to_string())),
+			Value::Bool(value) => return Ok(TextComponent::new(value.to_string())),
+			// Первый элемент - родитель, остальные - его дети
+			Value::Array(values) => {
+				let (first, rest) = values.split_first().ok_or(ServerError::DeTextComponent)?;
+				let mut parent = TextComponent::from_value(first)?;
+				if !rest.is_empty() {
+					let mut extra = parent.extra.take().unwrap_or_default();
+					for value in rest {
+						extra.push(TextComponent::from_value(value)?);
+					}
+					parent.extra = Some(extra);
+				}
+				return Ok(parent);
+			}
+			Value::Object(map) => map,
+			Value::Null => return Err(ServerError::DeTextComponent),
+		};
+
+		// Обертка для разнотипных списков в NBT
+		if let Some(value) = map.get("") {
+			return TextComponent::from_value(value);
+		}
+
+		let mut component = TextComponent::from_content(read_content(map)?);
+
+		component.color = string(map, "color");
+		component.font = string(map, "font");
+		component.bold = boolean(map, "bold");
+		component.italic = boolean(map, "italic");
+		component.underlined = boolean(map, "underlined");
+		component.strikethrough = boolean(map, "strikethrough");
+		component.obfuscated = boolean(map, "obfuscated");
+		component.shadow_color = map.get("shadow_color").and_then(read_shadow_color);
+		component.insertion = string(map, "insertion");
+		component.click_event = match map.get("click_event").or(map.get("clickEvent")) {
+			Some(value) => Some(ClickEvent::from_value(value)?),
+			None => None,
+		};
+		component.hover_event = match map.get("hover_event").or(map.get("hoverEvent")) {
+			Some(value) => Some(HoverEvent::from_value(value)?),
+			None => None,
+		};
+		component.extra = match map.get("extra") {
+			Some(Value::Array(values)) => Some(
+				values
+					.iter()
+					.map(TextComponent::from_value)
+					.collect::<Result<_, _>>()?,
+			),
+			Some(_) => return Err(ServerError::DeTextComponent),
+			None => None,
+		};
+
+		Ok(component)
+	}
+
+	/// NBT форма компонента, как ее ждет клиент в пакетах
+	///
+	/// Пишется без json, иначе компоненты предмета в show_item теряют типы тегов
+	pub fn to_nbt(&self) -> DynNBT {
+		if let Content::Text(text) = &self.content
+			&& self.is_plain()
+		{
+			return DynNBT::String(text.clone());
+		}
+
+		let mut map = HashMap::new();
+
+		match &self.content {
+			Content::Text(text) => {
+				map.insert("text".into(), nbt_string(text));
+			}
+			Content::Translatable {
+				key,
+				fallback,
+				with,
+			} => {
+				map.insert("translate".into(), nbt_string(key));
+				if let Some(fallback) = fallback {
+					map.insert("fallback".into(), nbt_string(fallback));
+				}
+				if !with.is_empty() {
+					map.insert("with".into(), nbt_components(with));
+				}
+			}
+			Content::Score { name, objective } => {
+				map.insert(
+					"score".into(),
+					DynNBT::Compound(HashMap::from([
+						("name".into(), nbt_string(name)),
+						("objective".into(), nbt_string(objective)),
+					])),
+				);
+			}
+			Content::Selector {
+				selector,
+				separator,
+			} => {
+				map.insert("selector".into(), nbt_string(selector));
+				if let Some(separator) = separator {
+					map.insert("separator".into(), separator.to_nbt());
+				}
+			}
+			Content::Keybind(key) => {
+				map.insert("keybind".into(), nbt_string(key));
+			}
+			Content::Nbt {
+				path,
+				interpret,
+				separator,
+				source,
+			} => {
+				map.insert("nbt".into(), nbt_string(path));
+				if let Some(interpret) = interpret {
+					map.insert("interpret".into(), DynNBT::Byte(*interpret as i8));
+				}
+				if let Some(separator) = separator {
+					map.insert("separator".into(), separator.to_nbt());
+				}
+				let (key, value) = match source {
+					NbtSource::Block(value) => ("block", value),
+					NbtSource::Entity(value) => ("entity", value),
+					NbtSource::Storage(value) => ("storage", value),
+				};
+				map.insert(key.into(), nbt_string(value));
+			}
+		}
+
+		let mut insert = |key: &str, value: Option<DynNBT>| {
+			if let Some(value) = value {
+				map.insert(key.into(), value);
+			}
+		};
+		let flag = |value: Option<bool>| value.map(|o| DynNBT::Byte(o as i8));
+
+		insert("color", self.color.as_deref().map(nbt_string));
+		insert("font", self.font.as_deref().map(nbt_string));
+		insert("bold", flag(self.bold));
+		insert("italic", flag(self.italic));
+		insert("underlined", flag(self.underlined));
+		insert("strikethrough", flag(self.strikethrough));
+		insert("obfuscated", flag(self.obfuscated));
+		insert("shadow_color", self.shadow_color.map(DynNBT::Int));
+		insert("insertion", self.insertion.as_deref().map(nbt_string));
+		insert("click_event", self.click_event.as_ref().map(|o| o.to_nbt()));
+		insert("hover_event", self.hover_event.as_ref().map(|o| o.to_nbt()));
+		insert(
+			"extra",
+			self
+				.extra
+				.as_ref()
+				.filter(|o| !o.is_empty())
+				.map(|o| nbt_components(o)),
+		);
+
+		DynNBT::Compound(map)
+	}
+
+	/// Компонент из NBT, читается напрямую, как и пишется
+	pub fn from_nbt(nbt: &DynNBT) -> Result<TextComponent, ServerError> {
+		let map = match nbt {
+			DynNBT::String(text) => return Ok(TextComponent::new(text.clone())),
+			DynNBT::List(values) => {
+				let (first, rest) = values.split_first().ok_or(ServerError::DeTextComponent)?;
+				let mut parent = TextComponent::from_nbt(first)?;
+				if !rest.is_empty() {
+					let mut extra = parent.extra.take().unwrap_or_default();
+					for value in rest {
+						extra.push(TextComponent::from_nbt(value)?);
+					}
+					parent.extra = Some(extra);
+				}
+				return Ok(parent);
+			}
+			DynNBT::Compound(map) => map,
+			number => {
+				return nbt_number_text(number)
+					.map(TextComponent::new)
+					.ok_or(ServerError::DeTextComponent);
+			}
+		};
+
+		// Обертка для разнотипных списков
+		if let Some(value) = map.get("") {
+			return TextComponent::from_nbt(value);
+		}
+
+		let mut component = TextComponent::from_content(read_nbt_content(map)?);
+
+		component.color = nbt_get_string(map, "color");
+		component.font = nbt_get_string(map, "font");
+		component.bold = nbt_get_boolean(map, "bold");
+		component.italic = nbt_get_boolean(map, "italic");
+		component.underlined = nbt_get_boolean(map, "underlined");
+		component.strikethrough = nbt_get_boolean(map, "strikethrough");
+		component.obfuscated = nbt_get_boolean(map, "obfuscated");
+		component.shadow_color = map.get("shadow_color").and_then(read_nbt_shadow_color);
+		component.insertion = nbt_get_string(map, "insertion");
+		component.click_event = match map.get("click_event").or(map.get("clickEvent")) {
+			Some(value) => Some(ClickEvent::from_nbt(value)?),
+			None => None,
+		};
+		component.hover_event = match map.get("hover_event").or(map.get("hoverEvent")) {
+			Some(value) => Some(HoverEvent::from_nbt(value)?),
+			None => None,
+		};
+		component.extra = match map.get("extra") {
+			Some(DynNBT::List(values)) => Some(
+				values
+					.iter()
+					.map(TextComponent::from_nbt)
+					.collect::<Result<_, _>>()?,
+			),
+			Some(_) => return Err(ServerError::DeTextComponent),
+			None => None,
+		};
+
+		Ok(component)
 	}
 }
 
@@ -112,82 +609,654 @@ impl Default for TextComponent {
 	}
 }
 
+impl From<&str> for TextComponent {
+	fn from(text: &str) -> Self {
+		TextComponent::new(text.to_string())
+	}
+}
+
+impl Serialize for TextComponent {
+	fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+		self.to_value().serialize(serializer)
+	}
+}
+
+impl<'de> Deserialize<'de> for TextComponent {
+	fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+		let value = Value::deserialize(deserializer)?;
+		TextComponent::from_value(&value).map_err(serde::de::Error::custom)
+	}
+}
+
+impl ClickEvent {
+	pub fn to_value(&self) -> Value {
+		match self {
+			ClickEvent::OpenUrl(url) => json!({ "action": "open_url", "url": url }),
+			ClickEvent::OpenFile(path) => json!({ "action": "open_file", "path": path }),
+			ClickEvent::RunCommand(command) => json!({ "action": "run_command", "command": command }),
+			ClickEvent::SuggestCommand(command) => {
+				json!({ "action": "suggest_command", "command": command })
+			}
+			ClickEvent::ChangePage(page) => json!({ "action": "change_page", "page": page }),
+			ClickEvent::CopyToClipboard(value) => {
+				json!({ "action": "copy_to_clipboard", "value": value })
+			}
+		}
+	}
+
+	pub fn to_nbt(&self) -> DynNBT {
+		let (action, key, value) = match self {
+			ClickEvent::OpenUrl(url) => ("open_url", "url", nbt_string(url)),
+			ClickEvent::OpenFile(path) => ("open_file", "path", nbt_string(path)),
+			ClickEvent::RunCommand(command) => ("run_command", "command", nbt_string(command)),
+			ClickEvent::SuggestCommand(command) => ("suggest_command", "command", nbt_string(command)),
+			ClickEvent::ChangePage(page) => ("change_page", "page", DynNBT::Int(*page)),
+			ClickEvent::CopyToClipboard(value) => ("copy_to_clipboard", "value", nbt_string(value)),
+		};
+		DynNBT::Compound(HashMap::from([
+			("action".into(), nbt_string(action)),
+			(key.into(), value),
+		]))
+	}
+
+	pub fn from_nbt(nbt: &DynNBT) -> Result<ClickEvent, ServerError> {
+		let map = nbt.as_compound().ok_or(ServerError::DeTextComponent)?;
+		let field = |key: &str| {
+			nbt_get_string(map, key)
+				.or_else(|| nbt_get_string(map, "value"))
+				.ok_or(ServerError::DeTextComponent)
+		};
+
+		Ok(match nbt_get_string(map, "action").as_deref() {
+			Some("open_url") => ClickEvent::OpenUrl(field("url")?),
+			Some("open_file") => ClickEvent::OpenFile(field("path")?),
+			Some("run_command") => ClickEvent::RunCommand(field("command")?),
+			Some("suggest_command") => ClickEvent::SuggestCommand(field("command")?),
+			Some("change_page") => ClickEvent::ChangePage(
+				map
+					.get("page")
+					.or(map.get("value"))
+					.and_then(nbt_integer)
+					.ok_or(ServerError::DeTextComponent)?,
+			),
+			Some("copy_to_clipboard") => ClickEvent::CopyToClipboard(field("value")?),
+			_ => return Err(ServerError::DeTextComponent),
+		})
+	}
+
+	/// Понимает и новый формат, и старый clickEvent с полем value
+	pub fn from_value(value: &Value) -> Result<ClickEvent, ServerError> {
+		let map = value.as_object().ok_or(ServerError::DeTextComponent)?;
+		let field = |key: &str| {
+			string(map, key)
+				.or_else(|| string(map, "value"))
+				.ok_or(ServerError::DeTextComponent)
+		};
+
+		Ok(match string(map, "action").as_deref() {
+			Some("open_url") => ClickEvent::OpenUrl(field("url")?),
+			Some("open_file") => ClickEvent::OpenFile(field("path")?),
+			Some("run_command") => ClickEvent::RunCommand(field("command")?),
+			Some("suggest_command") => ClickEvent::SuggestCommand(field("command")?),
+			Some("change_page") => ClickEvent::ChangePage(
+				map
+					.get("page")
+					.or(map.get("value"))
+					.and_then(integer)
+					.ok_or(ServerError::DeTextComponent)?,
+			),
+			Some("copy_to_clipboard") => ClickEvent::CopyToClipboard(field("value")?),
+			_ => return Err(ServerError::DeTextComponent),
+		})
+	}
+}
+
+impl HoverEvent {
+	pub fn to_value(&self) -> Value {
+		match self {
+			HoverEvent::ShowText(text) => json!({ "action": "show_text", "value": text.to_value() }),
+			HoverEvent::ShowItem {
+				id,
+				count,
+				components,
+			} => {
+				let mut value = json!({ "action": "show_item", "id": id, "count": count });
+				if let Some(components) = components {
+					value["components"] = nbt_to_json(components);
+				}
+				value
+			}
+			HoverEvent::ShowEntity { id, uuid, name } => {
+				let mut value =
+					json!({ "action": "show_entity", "id": id, "uuid": uuid.hyphenated().to_string() });
+				if let Some(name) = name {
+					value["name"] = name.to_value();
+				}
+				value
+			}
+		}
+	}
+
+	pub fn to_nbt(&self) -> DynNBT {
+		let mut map = HashMap::new();
+		match self {
+			HoverEvent::ShowText(text) => {
+				map.insert("action".into(), nbt_string("show_text"));
+				map.insert("value".into(), text.to_nbt());
+			}
+			HoverEvent::ShowItem {
+				id,
+				count,
+				components,
+			} => {
+				map.insert("action".into(), nbt_string("show_item"));
+				map.insert("id".into(), nbt_string(id));
+				map.insert("count".into(), DynNBT::Int(*count));
+				if let Some(components) = components {
+					map.insert("components".into(), components.clone());
+				}
+			}
+			HoverEvent::ShowEntity { id, uuid, name } => {
+				map.insert("action".into(), nbt_string("show_entity"));
+				map.insert("id".into(), nbt_string(id));
+				map.insert("uuid".into(), nbt_uuid(uuid));
+				if let Some(name) = name {
+					map.insert("name".into(), name.to_nbt());
+				}
+			}
+		}
+		DynNBT::Compound(map)
+	}
+
+	pub fn from_nbt(nbt: &DynNBT) -> Result<HoverEvent, ServerError> {
+		let map = nbt.as_compound().ok_or(ServerError::DeTextComponent)?;
+		let contents = match map.get("contents").and_then(|o| o.as_compound()) {
+			Some(contents) => contents,
+			None => map,
+		};
+
+		Ok(match nbt_get_string(map, "action").as_deref() {
+			Some("show_text") => HoverEvent::ShowText(Box::new(TextComponent::from_nbt(
+				map
+					.get("value")
+					.or(map.get("contents"))
+					.ok_or(ServerError::DeTextComponent)?,
+			)?)),
+			Some("show_item") => HoverEvent::ShowItem {
+				id: nbt_get_string(contents, "id").ok_or(ServerError::DeTextComponent)?,
+				count: contents.get("count").and_then(nbt_integer).unwrap_or(1),
+				components: contents.get("components").cloned(),
+			},
+			Some("show_entity") => HoverEvent::ShowEntity {
+				id: nbt_get_string(contents, "type")
+					.or_else(|| nbt_get_string(contents, "id"))
+					.ok_or(ServerError::DeTextComponent)?,
+				uuid: match contents.get("type") {
+					Some(_) => contents.get("id"),
+					None => contents.get("uuid"),
+				}
+				.and_then(read_nbt_uuid)
+				.ok_or(ServerError::DeTextComponent)?,
+				name: match contents.get("name") {
+					Some(name) => Some(Box::new(TextComponent::from_nbt(name)?)),
+					None => None,
+				},
+			},
+			_ => return Err(ServerError::DeTextComponent),
+		})
+	}
+
+	/// Понимает и новый формат, и старый hoverEvent с contents
+	pub fn from_value(value: &Value) -> Result<HoverEvent, ServerError> {
+		let map = value.as_object().ok_or(ServerError::DeTextComponent)?;
+		let contents = match map.get("contents").and_then(|o| o.as_object()) {
+			Some(contents) => contents,
+			None => map,
+		};
+
+		Ok(match string(map, "action").as_deref() {
+			Some("show_text") => HoverEvent::ShowText(Box::new(TextComponent::from_value(
+				map
+					.get("value")
+					.or(map.get("contents"))
+					.ok_or(ServerError::DeTextComponent)?,
+			)?)),
+			Some("show_item") => HoverEvent::ShowItem {
+				id: string(contents, "id").ok_or(ServerError::DeTextComponent)?,
+				count: contents.get("count").and_then(integer).unwrap_or(1),
+				components: contents.get("components").map(json_to_nbt),
+			},
+			// В старом формате тип лежал в type, а uuid в id
+			Some("show_entity") => HoverEvent::ShowEntity {
+				id: string(contents, "type")
+					.or_else(|| string(contents, "id"))
+					.ok_or(ServerError::DeTextComponent)?,
+				uuid: match contents.get("type") {
+					Some(_) => contents.get("id"),
+					None => contents.get("uuid"),
+				}
+				.and_then(read_uuid)
+				.ok_or(ServerError::DeTextComponent)?,
+				name: match contents.get("name") {
+					Some(name) => Some(Box::new(TextComponent::from_value(name)?)),
+					None => None,
+				},
+			},
+			_ => return Err(ServerError::DeTextComponent),
+		})
+	}
+}
+
+fn read_content(map: &Map<String, Value>) -> Result<Content, ServerError> {
+	let separator = match map.get("separator") {
+		Some(value) => Some(Box::new(TextComponent::from_value(value)?)),
+		None => None,
+	};
+
+	if let Some(text) = map.get("text") {
+		return Ok(Content::Text(match text {
+			Value::String(text) => text.clone(),
+			other => other.to_string(),
+		}));
+	}
+
+	if let Some(key) = string(map, "translate") {
+		let with = match map.get("with") {
+			Some(Value::Array(values)) => values
+				.iter()
+				.map(TextComponent::from_value)
+				.collect::<Result<_, _>>()?,
+			_ => Vec::new(),
+		};
+		return Ok(Content::Translatable {
+			key,
+			fallback: string(map, "fallback"),
+			with,
+		});
+	}
+
+	if let Some(score) = map.get("score").and_then(|o| o.as_object()) {
+		return Ok(Content::Score {
+			name: string(score, "name").ok_or(ServerError::DeTextComponent)?,
+			objective: string(score, "objective").ok_or(ServerError::DeTextComponent)?,
+		});
+	}
+
+	if let Some(selector) = string(map, "selector") {
+		return Ok(Content::Selector {
+			selector,
+			separator,
+		});
+	}
+
+	if let Some(key) = string(map, "keybind") {
+		return Ok(Content::Keybind(key));
+	}
+
+	if let Some(path) = string(map, "nbt") {
+		let source = if let Some(block) = string(map, "block") {
+			NbtSource::Block(block)
+		} else if let Some(entity) = string(map, "entity") {
+			NbtSource::Entity(entity)
+		} else if let Some(storage) = string(map, "storage") {
+			NbtSource::Storage(storage)
+		} else {
+			return Err(ServerError::DeTextComponent);
+		};
+		return Ok(Content::Nbt {
+			path,
+			interpret: boolean(map, "interpret"),
+			separator,
+			source,
+		});
+	}
+
+	Err(ServerError::DeTextComponent)
+}
+
+fn string(map: &Map<String, Value>, key: &str) -> Option<String> {
+	map.get(key).and_then(|o| o.as_str()).map(|o| o.to_string())
+}
+
+// В NBT bool приходит байтом
+fn boolean(map: &Map<String, Value>, key: &str) -> Option<bool> {
+	match map.get(key)? {
+		Value::Bool(value) => Some(*value),
+		Value::Number(number) => number.as_i64().map(|o| o != 0),
+		_ => None,
+	}
+}
+
+fn integer(value: &Value) -> Option<i32> {
+	value.as_i64().map(|o| o as i32)
+}
+
+// Тень либо ARGB числом, либо списком из 4 float от 0 до 1
+fn read_shadow_color(value: &Value) -> Option<i32> {
+	match value {
+		Value::Number(number) => number.as_i64().map(|o| o as i32),
+		Value::Array(values) if values.len() == 4 => {
+			let channel = |i: usize| ((values[i].as_f64().unwrap_or(0.0) * 255.0).round() as u32) & 0xFF;
+			Some(((channel(3) << 24) | (channel(0) << 16) | (channel(1) << 8) | channel(2)) as i32)
+		}
+		_ => None,
+	}
+}
+
+// Строкой с дефисами или массивом из 4 интов, как в NBT
+fn read_uuid(value: &Value) -> Option<Uuid> {
+	match value {
+		Value::String(text) => Uuid::parse_str(text).ok(),
+		Value::Array(values) if values.len() == 4 => {
+			let mut bytes = [0; 16];
+			for (i, value) in values.iter().enumerate() {
+				bytes[i * 4..i * 4 + 4].copy_from_slice(&(value.as_i64()? as i32).to_be_bytes());
+			}
+			Some(Uuid::from_bytes(bytes))
+		}
+		_ => None,
+	}
+}
+
+fn read_nbt_content(map: &HashMap<String, DynNBT>) -> Result<Content, ServerError> {
+	let separator = match map.get("separator") {
+		Some(value) => Some(Box::new(TextComponent::from_nbt(value)?)),
+		None => None,
+	};
+
+	if let Some(text) = map.get("text") {
+		return Ok(Content::Text(match text {
+			DynNBT::String(text) => text.clone(),
+			other => nbt_number_text(other).ok_or(ServerError::DeTextComponent)?,
+		}));
+	}
+
+	if let Some(key) = nbt_get_string(map, "translate") {
+		let with = match map.get("with") {
+			Some(DynNBT::List(values)) => values
+				.iter()
+				.map(TextComponent::from_nbt)
+				.collect::<Result<_, _>>()?,
+			_ => Vec::new(),
+		};
+		return Ok(Content::Translatable {
+			key,
+			fallback: nbt_get_string(map, "fallback"),
+			with,
+		});
+	}
+
+	if let Some(score) = map.get("score").and_then(|o| o.as_compound()) {
+		return Ok(Content::Score {
+			name: nbt_get_string(score, "name").ok_or(ServerError::DeTextComponent)?,
+			objective: nbt_get_string(score, "objective").ok_or(ServerError::DeTextComponent)?,
+		});
+	}
+
+	if let Some(selector) = nbt_get_string(map, "selector") {
+		return Ok(Content::Selector {
+			selector,
+			separator,
+		});
+	}
+
+	if let Some(key) = nbt_get_string(map, "keybind") {
+		return Ok(Content::Keybind(key));
+	}
+
+	if let Some(path) = nbt_get_string(map, "nbt") {
+		let source = if let Some(block) = nbt_get_string(map, "block") {
+			NbtSource::Block(block)
+		} else if let Some(entity) = nbt_get_string(map, "entity") {
+			NbtSource::Entity(entity)
+		} else if let Some(storage) = nbt_get_string(map, "storage") {
+			NbtSource::Storage(storage)
+		} else {
+			return Err(ServerError::DeTextComponent);
+		};
+		return Ok(Content::Nbt {
+			path,
+			interpret: nbt_get_boolean(map, "interpret"),
+			separator,
+			source,
+		});
+	}
+
+	Err(ServerError::DeTextComponent)
+}
+
+fn nbt_string(text: &str) -> DynNBT {
+	DynNBT::String(text.to_string())
+}
+
+// Список в NBT однотипный: если кроме строк есть компаунды, строки оборачиваются в {"text": ...}
+fn nbt_components(components: &[TextComponent]) -> DynNBT {
+	let values = components.iter().map(|o| o.to_nbt()).collect::<Vec<_>>();
+	if values.iter().all(|o| matches!(o, DynNBT::String(_))) {
+		return DynNBT::List(values);
+	}
+
+	DynNBT::List(
+		values
+			.into_iter()
+			.map(|o| match o {
+				DynNBT::String(text) => {
+					DynNBT::Compound(HashMap::from([("text".to_string(), DynNBT::String(text))]))
+				}
+				o => o,
+			})
+			.collect(),
+	)
+}
+
+fn nbt_uuid(uuid: &Uuid) -> DynNBT {
+	DynNBT::IntArray(
+		uuid
+			.as_bytes()
+			.chunks(4)
+			.map(|o| i32::from_be_bytes([o[0], o[1], o[2], o[3]]))
+			.collect(),
+	)
+}
+
+fn nbt_get_string(map: &HashMap<String, DynNBT>, key: &str) -> Option<String> {
+	match map.get(key)? {
+		DynNBT::String(text) => Some(text.clone()),
+		_ => None,
+	}
+}
+
+fn nbt_get_boolean(map: &HashMap<String, DynNBT>, key: &str) -> Option<bool> {
+	nbt_integer(map.get(key)?).map(|o| o != 0)
+}
+
+fn nbt_integer(value: &DynNBT) -> Option<i32> {
+	match value {
+		DynNBT::Byte(value) => Some(*value as i32),
+		DynNBT::Short(value) => Some(*value as i32),
+		DynNBT::Int(value) => Some(*value),
+		DynNBT::Long(value) => Some(*value as i32),
+		_ => None,
+	}
+}
+
+// Число как текст, для компонентов вида {"text": 5}
+fn nbt_number_text(value: &DynNBT) -> Option<String> {
+	Some(match value {
+		DynNBT::Byte(value) => value.to_string(),
+		DynNBT::Short(value) => value.to_string(),
+		DynNBT::Int(value) => value.to_string(),
+		DynNBT::Long(value) => value.to_string(),
+		DynNBT::Float(value) => value.to_string(),
+		DynNBT::Double(value) => value.to_string(),
+		_ => return None,
+	})
+}
+
+fn read_nbt_shadow_color(value: &DynNBT) -> Option<i32> {
+	match value {
+		DynNBT::List(values) if values.len() == 4 => {
+			let channel = |i: usize| {
+				let value = match &values[i] {
+					DynNBT::Float(value) => *value as f64,
+					DynNBT::Double(value) => *value,
+					_ => 0.0,
+				};
+				((value * 255.0).round() as u32) & 0xFF
+			};
+			Some(((channel(3) << 24) | (channel(0) << 16) | (channel(1) << 8) | channel(2)) as i32)
+		}
+		value => nbt_integer(value),
+	}
+}
+
+fn read_nbt_uuid(value: &DynNBT) -> Option<Uuid> {
+	let ints = match value {
+		DynNBT::String(text) => return Uuid::parse_str(text).ok(),
+		DynNBT::IntArray(values) => values.clone(),
+		DynNBT::List(values) => values.iter().map(nbt_integer).collect::<Option<_>>()?,
+		_ => return None,
+	};
+	if ints.len() != 4 {
+		return None;
+	}
+
+	let mut bytes = [0; 16];
+	for (i, value) in ints.iter().enumerate() {
+		bytes[i * 4..i * 4 + 4].copy_from_slice(&value.to_be_bytes());
+	}
+	Some(Uuid::from_bytes(bytes))
+}
+
 pub struct TextComponentBuilder {
-	text: String,
-	color: Option<String>,
-	bold: Option<bool>,
-	italic: Option<bool>,
-	underlined: Option<bool>,
-	strikethrough: Option<bool>,
-	obfuscated: Option<bool>,
-	extra: Option<Vec<TextComponent>>,
+	component: TextComponent,
+}
+
+impl Default for TextComponentBuilder {
+	fn default() -> Self {
+		Self::new()
+	}
 }
 
 impl TextComponentBuilder {
 	pub fn new() -> Self {
 		Self {
-			text: String::new(),
-			color: None,
-			bold: None,
-			italic: None,
-			underlined: None,
-			strikethrough: None,
-			obfuscated: None,
-			extra: None,
+			component: TextComponent::default(),
 		}
 	}
 
 	pub fn text(mut self, text: &str) -> Self {
-		self.text = text.to_string();
+		self.component.content = Content::Text(text.to_string());
+		self
+	}
+
+	pub fn content(mut self, content: Content) -> Self {
+		self.component.content = content;
+		self
+	}
+
+	pub fn translate(mut self, key: &str, with: Vec<TextComponent>) -> Self {
+		self.component.content = Content::Translatable {
+			key: key.to_string(),
+			fallback: None,
+			with,
+		};
+		self
+	}
+
+	pub fn keybind(mut self, key: &str) -> Self {
+		self.component.content = Content::Keybind(key.to_string());
+		self
+	}
+
+	pub fn score(mut self, name: &str, objective: &str) -> Self {
+		self.component.content = Content::Score {
+			name: name.to_string(),
+			objective: objective.to_string(),
+		};
+		self
+	}
+
+	pub fn selector(mut self, selector: &str) -> Self {
+		self.component.content = Content::Selector {
+			selector: selector.to_string(),
+			separator: None,
+		};
+		self
+	}
+
+	pub fn nbt(mut self, path: &str, source: NbtSource) -> Self {
+		self.component.content = Content::Nbt {
+			path: path.to_string(),
+			interpret: None,
+			separator: None,
+			source,
+		};
 		self
 	}
 
 	pub fn color(mut self, color: &str) -> Self {
-		self.color = Some(color.to_string());
+		self.component.color = Some(color.to_string());
+		self
+	}
+
+	pub fn font(mut self, font: &str) -> Self {
+		self.component.font = Some(font.to_string());
 		self
 	}
 
 	pub fn bold(mut self, bold: bool) -> Self {
-		self.bold = Some(bold);
+		self.component.bold = Some(bold);
 		self
 	}
 
 	pub fn italic(mut self, italic: bool) -> Self {
-		self.italic = Some(italic);
+		self.component.italic = Some(italic);
 		self
 	}
 
 	pub fn underlined(mut self, underlined: bool) -> Self {
-		self.underlined = Some(underlined);
+		self.component.underlined = Some(underlined);
 		self
 	}
 
 	pub fn strikethrough(mut self, strikethrough: bool) -> Self {
-		self.strikethrough = Some(strikethrough);
+		self.component.strikethrough = Some(strikethrough);
 		self
 	}
 
 	pub fn obfuscated(mut self, obfuscated: bool) -> Self {
-		self.obfuscated = Some(obfuscated);
+		self.component.obfuscated = Some(obfuscated);
+		self
+	}
+
+	pub fn shadow_color(mut self, argb: i32) -> Self {
+		self.component.shadow_color = Some(argb);
+		self
+	}
+
+	pub fn insertion(mut self, insertion: &str) -> Self {
+		self.component.insertion = Some(insertion.to_string());
+		self
+	}
+
+	pub fn click_event(mut self, event: ClickEvent) -> Self {
+		self.component.click_event = Some(event);
+		self
+	}
+
+	pub fn hover_event(mut self, event: HoverEvent) -> Self {
+		self.component.hover_event = Some(event);
 		self
 	}
 
 	pub fn extra(mut self, extra: Vec<TextComponent>) -> Self {
-		self.extra = Some(extra);
+		self.component.extra = Some(extra);
 		self
 	}
 
 	pub fn build(self) -> TextComponent {
-		TextComponent {
-			text: self.text,
-			color: self.color,
-			bold: self.bold,
-			italic: self.italic,
-			underlined: self.underlined,
-			strikethrough: self.strikethrough,
-			obfuscated: self.obfuscated,
-			extra: self.extra,
-		}
+		self.component
 	}
 }
 
@@ -200,16 +1269,198 @@ impl ReadWriteNBT<TextComponent> for Packet {
 			.get_mut()
 			.read_to_end(&mut data)
 			.map_err(|_| ServerError::DeTextComponent)?;
-		let (remaining, value) =
+		if nbt_too_deep(&data, NBT_MAX_DEPTH) {
+			return Err(ServerError::DeTextComponent);
+		}
+		let (remaining, value): (_, DynNBT) =
 			craftflow_nbt::from_slice(&data).map_err(|_| ServerError::DeTextComponent)?;
 		self
 			.get_mut()
 			.set_position(pos + (data.len() - remaining.len()) as u64);
-		Ok(value)
+		TextComponent::from_nbt(&value)
 	}
 
 	fn write_nbt(&mut self, val: &TextComponent) -> Result<(), ServerError> {
-		craftflow_nbt::to_writer(self.get_mut(), val).map_err(|_| ServerError::SerTextComponent)?;
+		craftflow_nbt::to_writer(self.get_mut(), &val.to_nbt())
+			.map_err(|_| ServerError::SerTextComponent)?;
 		Ok(())
 	}
 }
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn item_components() -> DynNBT {
+		DynNBT::Compound(HashMap::from([
+			("minecraft:damage".to_string(), DynNBT::Int(5)),
+			("minecraft:unbreakable".to_string(), DynNBT::Byte(1)),
+			("minecraft:repair_cost".to_string(), DynNBT::Short(3)),
+			("custom:time".to_string(), DynNBT::Long(1 << 40)),
+			("custom:ints".to_string(), DynNBT::IntArray(vec![1, 2, 3])),
+			(
+				"custom:bytes".to_string(),
+				DynNBT::ByteArray(vec![255, 0, 1]),
+			),
+			(
+				"custom:floats".to_string(),
+				DynNBT::List(vec![DynNBT::Float(0.5), DynNBT::Float(1.5)]),
+			),
+		]))
+	}
+
+	// Компонент, в котором есть все виды содержимого, стилей и событий
+	fn full_component() -> TextComponent {
+		let entity = TextComponent::builder()
+			.selector("@p")
+			.hover_event(HoverEvent::ShowEntity {
+				id: "minecraft:pig".to_string(),
+				uuid: Uuid::from_u128(0x0123_4567_89ab_cdef_0123_4567_89ab_cdef),
+				name: Some(Box::new("Pig".into())),
+			})
+			.build();
+
+		TextComponent::builder()
+			.translate("chat.type.text", vec!["Steve".into(), entity])
+			.color("#FF8800")
+			.font("minecraft:uniform")
+			.bold(true)
+			.italic(false)
+			.shadow_color(0x7F00FF00)
+			.insertion("hello")
+			.click_event(ClickEvent::ChangePage(3))
+			.hover_event(HoverEvent::ShowItem {
+				id: "minecraft:diamond_sword".to_string(),
+				count: 2,
+				components: Some(item_components()),
+			})
+			.extra(vec![
+				"plain".into(),
+				TextComponent::keybind("key.jump"),
+				TextComponent::builder()
+					.score("*", "kills")
+					.underlined(true)
+					.build(),
+				TextComponent::builder()
+					.nbt("Items[0]", NbtSource::Storage("test:data".to_string()))
+					.click_event(ClickEvent::RunCommand("/say hi".to_string()))
+					.build(),
+			])
+			.build()
+	}
+
+	#[test]
+	fn json_round_trip() {
+		let component = full_component();
+		let json = component.clone().as_json().unwrap();
+		let read = TextComponent::from_json(&json).unwrap();
+
+		// json не различает типы чисел, поэтому компоненты предмета сравниваем отдельно
+		let strip = |mut component: TextComponent| {
+			component.hover_event = None;
+			component
+		};
+		assert_eq!(strip(read), strip(component));
+	}
+
+	#[test]
+	fn nbt_round_trip_keeps_tag_types() {
+		let component = full_component();
+		assert_eq!(
+			TextComponent::from_nbt(&component.to_nbt()).unwrap(),
+			component
+		);
+
+		let Some(HoverEvent::ShowItem { components, .. }) =
+			TextComponent::from_nbt(&component.to_nbt())
+				.unwrap()
+				.hover_event
+		else {
+			panic!("show_item потерялся");
+		};
+		assert_eq!(components, Some(item_components()));
+	}
+
+	#[test]
+	fn nbt_packet_round_trip() {
+		let component = full_component();
+		let mut packet = Packet::empty(0);
+		packet.write_nbt(&component).unwrap();
+
+		let mut packet = Packet::from_bytes(0, packet.get_bytes());
+		let read: TextComponent = packet.read_nbt().unwrap();
+		assert_eq!(read, component);
+	}
+
+	#[test]
+	fn nbt_form() {
+		assert_eq!(
+			TextComponent::from("text").to_nbt(),
+			DynNBT::String("text".to_string())
+		);
+
+		let nbt = full_component().to_nbt();
+		let map = nbt.as_compound().unwrap();
+		assert_eq!(map.get("bold"), Some(&DynNBT::Byte(1)));
+		assert_eq!(map.get("shadow_color"), Some(&DynNBT::Int(0x7F00FF00)));
+
+		// Строки в разнотипном списке обернуты
+		let Some(DynNBT::List(extra)) = map.get("extra") else {
+			panic!("нет extra");
+		};
+		assert!(extra.iter().all(|o| matches!(o, DynNBT::Compound(_))));
+
+		let Some(DynNBT::List(with)) = map.get("with") else {
+			panic!("нет with");
+		};
+		let hover = with[1].as_compound().unwrap()["hover_event"]
+			.as_compound()
+			.unwrap();
+		assert!(matches!(hover.get("uuid"), Some(DynNBT::IntArray(o)) if o.len() == 4));
+	}
+
+	#[test]
+	fn nbt_reads_legacy_forms() {
+		let nbt = DynNBT::List(vec![
+			DynNBT::Compound(HashMap::from([(String::new(), DynNBT::Int(1))])),
+			DynNBT::String("a".to_string()),
+			DynNBT::Compound(HashMap::from([("text".to_string(), DynNBT::Byte(2))])),
+		]);
+		assert_eq!(TextComponent::from_nbt(&nbt).unwrap().plain_text(), "1a2");
+
+		let uuid = Uuid::from_u128(42);
+		let hover = DynNBT::Compound(HashMap::from([
+			(
+				"action".to_string(),
+				DynNBT::String("show_entity".to_string()),
+			),
+			(
+				"contents".to_string(),
+				DynNBT::Compound(HashMap::from([
+					(
+						"type".to_string(),
+						DynNBT::String("minecraft:cow".to_string()),
+					),
+					("id".to_string(), DynNBT::String(uuid.to_string())),
+				])),
+			),
+		]));
+		assert!(matches!(
+			HoverEvent::from_nbt(&hover).unwrap(),
+			HoverEvent::ShowEntity { uuid: read, .. } if read == uuid
+		));
+	}
+
+	#[test]
+	fn hex_colors() {
+		assert_eq!(color_rgb("#FF8800"), Some(0xFF8800));
+		assert_eq!(color_rgb("#ff8800"), Some(0xFF8800));
+		assert_eq!(color_rgb("gold"), Some(0xFFAA00));
+		assert_eq!(color_rgb("dark_grey"), color_rgb("dark_gray"));
+		assert_eq!(color_rgb("#+12345"), None);
+		assert_eq!(color_rgb("#-12345"), None);
+		assert_eq!(color_rgb("#12345"), None);
+		assert_eq!(color_rgb("#12345G"), None);
+		assert_eq!(color_rgb("purple"), None);
+	}
+}
@@ -0,0 +1,206 @@
+use super::component::{NAMED_COLORS, TextComponent, color_rgb};
+
+// Старое форматирование кодами: `&6Hello &lworld` или то же самое через §
+//
+// Цвет сбрасывает все остальное форматирование, &r сбрасывает вообще все.
+// Hex цвета понимаются в виде &#RRGGBB и как в Bukkit &x&R&R&G&G&B&B, пишутся во втором виде
+
+#[derive(Debug, Clone, Default, PartialEq)]
+struct Style {
+	color: Option<String>,
+	bold: bool,
+	italic: bool,
+	underlined: bool,
+	strikethrough: bool,
+	obfuscated: bool,
+}
+
+impl Style {
+	fn component(&self, text: String) -> TextComponent {
+		let flag = |value: bool| value.then_some(true);
+		let mut component = TextComponent::new(text);
+		component.color = self.color.clone();
+		component.bold = flag(self.bold);
+		component.italic = flag(self.italic);
+		component.underlined = flag(self.underlined);
+		component.strikethrough = flag(self.strikethrough);
+		component.obfuscated = flag(self.obfuscated);
+		component
+	}
+
+	// Стиль ребенка поверх стиля родителя
+	fn inherit(&self, component: &TextComponent) -> Style {
+		Style {
+			color: component.color.clone().or(self.color.clone()),
+			bold: component.bold.unwrap_or(self.bold),
+			italic: component.italic.unwrap_or(self.italic),
+			underlined: component.underlined.unwrap_or(self.underlined),
+			strikethrough: component.strikethrough.unwrap_or(self.strikethrough),
+			obfuscated: component.obfuscated.unwrap_or(self.obfuscated),
+		}
+	}
+}
+
+/// Разбирает текст с кодами, code - обычно & или §
+pub fn parse(input: &str, code: char) -> TextComponent {
+	let chars: Vec<char> = input.chars().collect();
+	let mut out = Vec::new();
+	let mut style = Style::default();
+	let mut text = String::new();
+	let mut i = 0;
+
+	let mut flush = |text: &mut String, style: &Style| {
+		if !text.is_empty() {
+			out.push(style.component(std::mem::take(text)));
+		}
+	};
+
+	while i < chars.len() {
+		if chars[i] != code || i + 1 >= chars.len() {
+			text.push(chars[i]);
+			i += 1;
+			continue;
+		}
+
+		let next = chars[i + 1].to_ascii_lowercase();
+
+		if let Some(color) = hex_color(&chars[i + 1..], code) {
+			flush(&mut text, &style);
+			style = Style {
+				color: Some(color),
+				..Style::default()
+			};
+			i += if next == '#' { 8 } else { 14 };
+			continue;
+		}
+
+		if let Some((name, ..)) = NAMED_COLORS.iter().find(|o| o.1 == next) {
+			flush(&mut text, &style);
+			style = Style {
+				color: Some(name.to_string()),
+				..Style::default()
+			};
+			i += 2;
+			continue;
+		}
+
+		match next {
+			'k' | 'l' | 'm' | 'n' | 'o' => flush(&mut text, &style),
+			'r' => {
+				flush(&mut text, &style);
+				style = Style::default();
+			}
+			_ => {
+				text.push(chars[i]);
+				i += 1;
+				continue;
+			}
+		}
+
+		match next {
+			'k' => style.obfuscated = true,
+			'l' => style.bold = true,
+			'm' => style.strikethrough = true,
+			'n' => style.underlined = true,
+			'o' => style.italic = true,
+			_ => {}
+		}
+		i += 2;
+	}
+
+	flush(&mut text, &style);
+
+	match out.len() {
+		0 => TextComponent::default(),
+		1 => out.pop().unwrap(),
+		_ => TextComponent {
+			extra: Some(out),
+			..Default::default()
+		},
+	}
+}
+
+/// Пишет компонент кодами, все что не текст (переводы, клики, шрифты) теряется
+pub fn serialize(component: &TextComponent, code: char) -> String {
+	let mut runs = Vec::new();
+	flatten(component, &Style::default(), &mut runs);
+
+	let mut out = String::new();
+	let mut current = Style::default();
+
+	for (style, text) in runs {
+		if text.is_empty() {
+			continue;
+		}
+
+		if style != current {
+			// Выключить форматирование можно только сбросом, а цвет сбрасывает его сам
+			match &style.color {
+				Some(color) => out.push_str(&color_code(color, code)),
+				None => out.push_str(&format!("{code}r")),
+			}
+
+			let flags = [
+				(style.obfuscated, 'k'),
+				(style.bold, 'l'),
+				(style.strikethrough, 'm'),
+				(style.underlined, 'n'),
+				(style.italic, 'o'),
+			];
+			for (_, c) in flags.iter().filter(|o| o.0) {
+				out.push(code);
+				out.push(*c);
+			}
+
+			current = style;
+		}
+
+		out.push_str(&text);
+	}
+
+	out
+}
+
+fn flatten(component: &TextComponent, parent: &Style, out: &mut Vec<(Style, String)>) {
+	let style = parent.inherit(component);
+
+	let mut content = component.clone();
+	content.extra = None;
+	out.push((style.clone(), content.plain_text()));
+
+	for child in component.extra.iter().flatten() {
+		flatten(child, &style, out);
+	}
+}
+
+fn color_code(color: &str, code: char) -> String {
+	if let Some((_, c, _)) = NAMED_COLORS.iter().find(|o| o.0 == color) {
+		return format!("{code}{c}");
+	}
+
+	let hex = format!("{:06x}", color_rgb(color).unwrap_or(0xFFFFFF));
+	let mut out = format!("{code}x");
+	for c in hex.chars() {
+		out.push(code);
+		out.push(c);
+	}
+	out
+}
+
+// #RRGGBB или xRRGGBB с кодом перед каждой цифрой, chars начинается после первого кода
+fn hex_color(chars: &[char], code: char) -> Option<String> {
+	let digits: String = match chars.first()?.to_ascii_lowercase() {
+		'#' => chars.get(1..7)?.iter().collect(),
+		'x' => chars
+			.get(1..13)?
+			.chunks(2)
+			.map(|o| (o[0] == code).then_some(o[1]))
+			.collect::<Option<String>>()?,
+		_ => return None,
+	};
+
+	digits
+		.chars()
+		.all(|o| o.is_ascii_hexdigit())
+		.then(|| format!("#{}", digits.to_uppercase()))
+}
@@ -0,0 +1,606 @@
+use std::{
+	collections::{BTreeMap, BTreeSet},
+	marker::PhantomData,
+};
+
+use craftflow_nbt::DynNBT;
+use rust_mc_proto::{DataReader, DataWriter, Packet};
+use uuid::Uuid;
+
+use crate::{
+	ServerError,
+	data::{component::TextComponent, slot::Slot},
+	protocol::packets::{PacketField, Position, VarInt, VarLong},
+};
+
+// Метаданные сущностей
+//
+// Передаются списком (индекс, тип, значение), список заканчивается индексом 0xFF
+// Индексы полей зависят от типа сущности и наследуются: у игрока сначала поля Entity, потом LivingEntity, потом Player
+// EntityMetadata помнит, какие поля поменялись, их раз в тик рассылает play::entity
+
+/// Поза сущности, тип сериализатора Pose
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Pose {
+	#[default]
+	Standing,
+	FallFlying,
+	Sleeping,
+	Swimming,
+	SpinAttack,
+	Sneaking,
+	LongJumping,
+	Dying,
+	Croaking,
+	UsingTongue,
+	Sitting,
+	Roaring,
+	Sniffing,
+	Emerging,
+	Digging,
+	Sliding,
+	Shooting,
+	Inhaling,
+}
+
+impl Pose {
+	const ALL: [Pose; 18] = [
+		Pose::Standing,
+		Pose::FallFlying,
+		Pose::Sleeping,
+		Pose::Swimming,
+		Pose::SpinAttack,
+		Pose::Sneaking,
+		Pose::LongJumping,
+		Pose::Dying,
+		Pose::Croaking,
+		Pose::UsingTongue,
+		Pose::Sitting,
+		Pose::Roaring,
+		Pose::Sniffing,
+		Pose::Emerging,
+		Pose::Digging,
+		Pose::Sliding,
+		Pose::Shooting,
+		Pose::Inhaling,
+	];
+
+	pub fn id(self) -> i32 {
+		self as i32
+	}
+
+	pub fn from_id(id: i32) -> Option<Pose> {
+		Pose::ALL.get(usize::try_from(id).ok()?).copied()
+	}
+}
+
+/// Значение поля метаданных, вариант определяет тип сериализатора
+#[derive(Debug, Clone, PartialEq)]
+pub enum MetadataValue {
+	Byte(i8),
+	VarInt(i32),
+	VarLong(i64),
+	Float(f32),
+	String(String),
+	Text(TextComponent),
+	OptionalText(Option<TextComponent>),
+	Slot(Slot),
+	Boolean(bool),
+	Rotations(f32, f32, f32),
+	Position(Position),
+	OptionalPosition(Option<Position>),
+	Direction(i32), // 0 - вниз, 1 - вверх, 2 - север, 3 - юг, 4 - запад, 5 - восток
+	OptionalUuid(Option<Uuid>),
+	BlockState(i32),
+	OptionalBlockState(Option<i32>),
+	Nbt(DynNBT),
+	VillagerData(i32, i32, i32), // тип, профессия, уровень
+	OptionalVarInt(Option<i32>),
+	Pose(Pose),
+	Vector3(f32, f32, f32),
+	Quaternion(f32, f32, f32, f32),
+}
+
+impl MetadataValue {
+	/// Айди типа сериализатора (minecraft:entity_data_serializers)
+	pub fn serializer(&self) -> i32 {
+		match self {
+			MetadataValue::Byte(_) => 0,
+			MetadataValue::VarInt(_) => 1,
+			MetadataValue::VarLong(_) => 2,
+			MetadataValue::Float(_) => 3,
+			MetadataValue::String(_) => 4,
+			MetadataValue::Text(_) => 5,
+			MetadataValue::OptionalText(_) => 6,
+			MetadataValue::Slot(_) => 7,
+			MetadataValue::Boolean(_) => 8,
+			MetadataValue::Rotations(..) => 9,
+			MetadataValue::Position(_) => 10,
+			MetadataValue::OptionalPosition(_) => 11,
+			MetadataValue::Direction(_) => 12,
+			MetadataValue::OptionalUuid(_) => 13,
+			MetadataValue::BlockState(_) => 14,
+			MetadataValue::OptionalBlockState(_) => 15,
+			MetadataValue::Nbt(_) => 16,
+			MetadataValue::VillagerData(..) => 19,
+			MetadataValue::OptionalVarInt(_) => 20,
+			MetadataValue::Pose(_) => 21,
+			MetadataValue::Vector3(..) => 33,
+			MetadataValue::Quaternion(..) => 34,
+		}
+	}
+
+	fn read(serializer: i32, packet: &mut Packet) -> Result<MetadataValue, ServerError> {
+		Ok(match serializer {
+			0 => MetadataValue::Byte(packet.read_signed_byte()?),
+			1 => MetadataValue::VarInt(packet.read_varint()?),
+			2 => MetadataValue::VarLong(packet.read_varlong()?),
+			3 => MetadataValue::Float(packet.read_float()?),
+			4 => MetadataValue::String(packet.read_string()?),
+			5 => MetadataValue::Text(TextComponent::read_field(packet)?),
+			6 => MetadataValue::OptionalText(Option::read_field(packet)?),
+			7 => MetadataValue::Slot(Slot::read_field(packet)?),
+			8 => MetadataValue::Boolean(packet.read_boolean()?),
+			9 => MetadataValue::Rotations(
+				packet.read_float()?,
+				packet.read_float()?,
+				packet.read_float()?,
+			),
+			10 => MetadataValue::Position(Position::read_field(packet)?),
+			11 => MetadataValue::OptionalPosition(Option::read_field(packet)?),
+			12 => MetadataValue::Direction(packet.read_varint()?),
+			13 => MetadataValue::OptionalUuid(Option::read_field(packet)?),
+			14 => MetadataValue::BlockState(packet.read_varint()?),
+			15 => MetadataValue::OptionalBlockState(match packet.read_varint()? {
+				0 => None,
+				id => Some(id),
+			}),
+			16 => MetadataValue::Nbt(DynNBT::read_field(packet)?),
+			19 => MetadataValue::VillagerData(
+				packet.read_varint()?,
+				packet.read_varint()?,
+				packet.read_varint()?,
+			),
+			20 => MetadataValue::OptionalVarInt(match packet.read_varint()? {
+				0 => None,
+				value => Some(value - 1),
+			}),
+			21 => {
+				MetadataValue::Pose(Pose::from_id(packet.read_varint()?).ok_or(ServerError::WrongPacket)?)
+			}
+			33 => MetadataValue::Vector3(
+				packet.read_float()?,
+				packet.read_float()?,
+				packet.read_float()?,
+			),
+			34 => MetadataValue::Quaternion(
+				packet.read_float()?,
+				packet.read_float()?,
+				packet.read_float()?,
+				packet.read_float()?,
+			),
+			_ => return Err(ServerError::WrongPacket),
+		})
+	}
+
+	fn write(&self, packet: &mut Packet) -> Result<(), ServerError> {
+		match self {
+			MetadataValue::Byte(value) => packet.write_signed_byte(*value)?,
+			MetadataValue::VarInt(value)
+			| MetadataValue::Direction(value)
+			| MetadataValue::BlockState(value) => packet.write_varint(*value)?,
+			MetadataValue::VarLong(value) => packet.write_varlong(*value)?,
+			MetadataValue::Float(value) => packet.write_float(*value)?,
+			MetadataValue::String(value) => packet.write_string(value)?,
+			MetadataValue::Text(value) => value.write_field(packet)?,
+			MetadataValue::OptionalText(value) => value.write_field(packet)?,
+			MetadataValue::Slot(value) => value.write_field(packet)?,
+			MetadataValue::Boolean(value) => packet.write_boolean(*value)?,
+			MetadataValue::Rotations(x, y, z) | MetadataValue::Vector3(x, y, z) => {
+				packet.write_float(*x)?;
+				packet.write_float(*y)?;
+				packet.write_float(*z)?;
+			}
+			MetadataValue::Position(value) => value.write_field(packet)?,
+			MetadataValue::OptionalPosition(value) => value.write_field(packet)?,
+			MetadataValue::OptionalUuid(value) => value.write_field(packet)?,
+			MetadataValue::OptionalBlockState(value) => packet.write_varint(value.unwrap_or(0))?,
+			MetadataValue::Nbt(value) => value.write_field(packet)?,
+			MetadataValue::VillagerData(kind, profession, level) => {
+				packet.write_varint(*kind)?;
+				packet.write_varint(*profession)?;
+				packet.write_varint(*level)?;
+			}
+			MetadataValue::OptionalVarInt(value) => {
+				packet.write_varint(value.map(|o| o + 1).unwrap_or(0))?
+			}
+			MetadataValue::Pose(value) => packet.write_varint(value.id())?,
+			MetadataValue::Quaternion(x, y, z, w) => {
+				packet.write_float(*x)?;
+				packet.write_float(*y)?;
+				packet.write_float(*z)?;
+				packet.write_float(*w)?;
+			}
+		}
+		Ok(())
+	}
+}
+
+/// Тип, который можно положить в поле метаданных
+pub trait MetadataType: Sized {
+	fn into_value(self) -> MetadataValue;
+	fn from_value(value: &MetadataValue) -> Option<Self>;
+}
+
+macro_rules! metadata_type {
+	($ty:ty, $variant:ident) => {
+		metadata_type!($ty, $variant, |o| o, |o: &$ty| o.clone());
+	};
+	($ty:ty, $variant:ident, $into:expr, $from:expr) => {
+		impl MetadataType for $ty {
+			fn into_value(self) -> MetadataValue {
+				MetadataValue::$variant($into(self))
+			}
+
+			fn from_value(value: &MetadataValue) -> Option<Self> {
+				match value {
+					MetadataValue::$variant(value) => Some($from(value)),
+					_ => None,
+				}
+			}
+		}
+	};
+}
+
+metadata_type!(i8, Byte);
+metadata_type!(VarInt, VarInt, |o: VarInt| o.0, |o: &i32| VarInt(*o));
+metadata_type!(VarLong, VarLong, |o: VarLong| o.0, |o: &i64| VarLong(*o));
+metadata_type!(f32, Float);
+metadata_type!(String, String);
+metadata_type!(TextComponent, Text);
+metadata_type!(Option<TextComponent>, OptionalText);
+metadata_type!(Slot, Slot);
+metadata_type!(bool, Boolean);
+metadata_type!(Position, Position);
+metadata_type!(Option<Position>, OptionalPosition);
+metadata_type!(Option<Uuid>, OptionalUuid);
+metadata_type!(DynNBT, Nbt);
+metadata_type!(Pose, Pose);
+
+/// Поле метаданных: индекс и тип значения
+pub struct Field<T> {
+	pub index: u8,
+	marker: PhantomData<fn() -> T>,
+}
+
+impl<T> Clone for Field<T> {
+	fn clone(&self) -> Self {
+		*self
+	}
+}
+
+impl<T> Copy for Field<T> {}
+
+impl<T> Field<T> {
+	pub const fn new(index: u8) -> Field<T> {
+		Field {
+			index,
+			marker: PhantomData,
+		}
+	}
+}
+
+/// Поля базовых типов сущностей 1.21.5, у наследников индексы продолжаются
+pub mod fields {
+	use super::{Field, Pose};
+	use crate::data::component::TextComponent;
+	use crate::protocol::packets::{Position, VarInt};
+	use craftflow_nbt::DynNBT;
+
+	/// Все сущности
+	pub mod entity {
+		use super::*;
+
+		pub const FLAGS: Field<i8> = Field::new(0); // см. flags
+		pub const AIR_SUPPLY: Field<VarInt> = Field::new(1);
+		pub const CUSTOM_NAME: Field<Option<TextComponent>> = Field::new(2);
+		pub const CUSTOM_NAME_VISIBLE: Field<bool> = Field::new(3);
+		pub const SILENT: Field<bool> = Field::new(4);
+		pub const NO_GRAVITY: Field<bool> = Field::new(5);
+		pub const POSE: Field<Pose> = Field::new(6);
+		pub const TICKS_FROZEN: Field<VarInt> = Field::new(7);
+
+		/// Биты поля FLAGS
+		pub mod flags {
+			pub const ON_FIRE: u8 = 0x01;
+			pub const CROUCHING: u8 = 0x02;
+			pub const SPRINTING: u8 = 0x08;
+			pub const SWIMMING: u8 = 0x10;
+			pub const INVISIBLE: u8 = 0x20;
+			pub const GLOWING: u8 = 0x40;
+			pub const FALL_FLYING: u8 = 0x80;
+		}
+	}
+
+	/// Выброшенные предметы
+	pub mod item {
+		use super::*;
+		use crate::data::slot::Slot;
+
+		pub const ITEM: Field<Slot> = Field::new(8);
+	}
+
+	/// Живые сущности (мобы, игроки, стойки для брони)
+	pub mod living {
+		use super::*;
+
+		pub const HAND_STATES: Field<i8> = Field::new(8);
+		pub const HEALTH: Field<f32> = Field::new(9);
+		// 10 - частицы эффектов, тип Particles пока не поддерживается
+		pub const EFFECT_AMBIENCE: Field<bool> = Field::new(11);
+		pub const ARROWS: Field<VarInt> = Field::new(12);
+		pub const BEE_STINGERS: Field<VarInt> = Field::new(13);
+		pub const SLEEPING_POSITION: Field<Option<Position>> = Field::new(14);
+	}
+
+	/// Игроки
+	pub mod player {
+		use super::*;
+
+		pub const ADDITIONAL_HEARTS: Field<f32> = Field::new(15);
+		pub const SCORE: Field<VarInt> = Field::new(16);
+		pub const SKIN_PARTS: Field<i8> = Field::new(17);
+		pub const MAIN_HAND: Field<i8> = Field::new(18); // 0 - левая, 1 - правая
+		pub const LEFT_SHOULDER: Field<DynNBT> = Field::new(19);
+		pub const RIGHT_SHOULDER: Field<DynNBT> = Field::new(20);
+	}
+}
+
+/// Список полей метаданных, как он идет в Set Entity Metadata
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct Metadata(pub Vec<(u8, MetadataValue)>);
+
+impl PacketField for Metadata {
+	fn read_field(packet: &mut Packet) -> Result<Self, ServerError> {
+		let mut entries = Vec::new();
+		loop {
+			let index = packet.read_byte()?;
+			if index == 0xFF {
+				break;
+			}
+			let serializer = packet.read_varint()?;
+			entries.push((index, MetadataValue::read(serializer, packet)?));
+		}
+		Ok(Metadata(entries))
+	}
+
+	fn write_field(&self, packet: &mut Packet) -> Result<(), ServerError> {
+		for (index, value) in &self.0 {
+			packet.write_byte(*index)?;
+			packet.write_varint(value.serializer())?;
+			value.write(packet)?;
+		}
+		packet.write_byte(0xFF)?;
+		Ok(())
+	}
+}
+
+/// Метаданные одной сущности с отметками об изменениях
+///
+/// Хранятся только выставленные поля, остальные клиент берет по умолчанию для типа сущности
+#[derive(Debug, Clone, Default)]
+pub struct EntityMetadata {
+	values: BTreeMap<u8, MetadataValue>,
+	dirty: BTreeSet<u8>,
+}
+
+impl EntityMetadata {
+	pub fn new() -> EntityMetadata {
+		EntityMetadata::default()
+	}
+
+	pub fn set<T: MetadataType>(&mut self, field: Field<T>, value: T) {
+		self.set_value(field.index, value.into_value());
+	}
+
+	pub fn get<T: MetadataType>(&self, field: Field<T>) -> Option<T> {
+		T::from_value(self.values.get(&field.index)?)
+	}
+
+	/// Выставляет поле любого типа, для полей, которых нет в fields
+	pub fn set_value(&mut self, index: u8, value: MetadataValue) {
+		if self.values.get(&index) != Some(&value) {
+			self.values.insert(index, value);
+			self.dirty.insert(index);
+		}
+	}
+
+	pub fn value(&self, index: u8) -> Option<&MetadataValue> {
+		self.values.get(&index)
+	}
+
+	/// Включает или выключает бит в байтовом поле, например entity::FLAGS
+	pub fn set_flag(&mut self, field: Field<i8>, flag: u8, enabled: bool) {
+		let flags = self.get(field).unwrap_or(0) as u8;
+		let flags = if enabled { flags | flag } else { flags & !flag };
+		self.set(field, flags as i8);
+	}
+
+	pub fn flag(&self, field: Field<i8>, flag: u8) -> bool {
+		self.get(field).unwrap_or(0) as u8 & flag != 0
+	}
+
+	pub fn is_dirty(&self) -> bool {
+		!self.dirty.is_empty()
+	}
+
+	/// Все выставленные поля, для спавна сущности
+	pub fn all(&self) -> Metadata {
+		Metadata(
+			self
+				.values
+				.iter()
+				.map(|(index, value)| (*index, value.clone()))
+				.collect(),
+		)
+	}
+
+	/// Поля, поменявшиеся с прошлого вызова
+	pub fn take_dirty(&mut self) -> Metadata {
+		let dirty = std::mem::take(&mut self.dirty);
+		Metadata(
+			dirty
+				.into_iter()
+				.filter_map(|o| Some((o, self.values.get(&o)?.clone())))
+				.collect(),
+		)
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::fields::{entity, living, player};
+	use super::*;
+
+	fn reread(metadata: &Metadata) -> Metadata {
+		let mut packet = Packet::empty(0);
+		metadata.write_field(&mut packet).unwrap();
+		let mut packet = Packet::from_bytes(0, packet.get_bytes());
+		Metadata::read_field(&mut packet).unwrap()
+	}
+
+	#[test]
+	fn values_round_trip() {
+		let metadata = Metadata(vec![
+			(0, MetadataValue::Byte(-3)),
+			(1, MetadataValue::VarInt(300)),
+			(2, MetadataValue::VarLong(-1 << 40)),
+			(3, MetadataValue::Float(0.5)),
+			(4, MetadataValue::String("name".to_string())),
+			(
+				5,
+				MetadataValue::Text(TextComponent::new("text".to_string())),
+			),
+			(6, MetadataValue::OptionalText(None)),
+			(7, MetadataValue::Slot(Slot::new(1, 5))),
+			(8, MetadataValue::Boolean(true)),
+			(9, MetadataValue::Rotations(1.0, 2.0, 3.0)),
+			(10, MetadataValue::Position(Position { x: 1, y: -2, z: 3 })),
+			(11, MetadataValue::OptionalPosition(None)),
+			(12, MetadataValue::Direction(5)),
+			(13, MetadataValue::OptionalUuid(Some(Uuid::from_u128(42)))),
+			(14, MetadataValue::BlockState(1)),
+			(15, MetadataValue::OptionalBlockState(Some(9))),
+			(16, MetadataValue::OptionalBlockState(None)),
+			(17, MetadataValue::VillagerData(1, 2, 3)),
+			(18, MetadataValue::OptionalVarInt(Some(0))),
+			(19, MetadataValue::OptionalVarInt(None)),
+			(20, MetadataValue::Pose(Pose::Sneaking)),
+			(21, MetadataValue::Vector3(0.0, -1.0, 2.5)),
+			(22, MetadataValue::Quaternion(0.0, 0.0, 0.0, 1.0)),
+		]);
+
+		assert_eq!(reread(&metadata), metadata);
+	}
+
+	#[test]
+	fn wire_format() {
+		let metadata = Metadata(vec![
+			(0, MetadataValue::Byte(0x02)),
+			(6, MetadataValue::Pose(Pose::Sneaking)),
+			(20, MetadataValue::OptionalVarInt(Some(0))),
+		]);
+		let mut packet = Packet::empty(0);
+		metadata.write_field(&mut packet).unwrap();
+
+		// (индекс, сериализатор, значение)..., 0xFF; OptionalVarInt хранит значение + 1
+		assert_eq!(packet.get_bytes(), [0, 0, 2, 6, 21, 5, 20, 20, 1, 0xFF]);
+		assert_eq!(reread(&Metadata::default()), Metadata::default());
+	}
+
+	#[test]
+	fn unknown_serializer_and_pose_are_errors() {
+		let mut packet = Packet::from_bytes(0, &[0, 17, 0, 0xFF]);
+		assert!(Metadata::read_field(&mut packet).is_err());
+
+		let mut packet = Packet::from_bytes(0, &[6, 21, 18, 0xFF]);
+		assert!(Metadata::read_field(&mut packet).is_err());
+
+		let mut packet = Packet::from_bytes(0, &[0, 0, 1]);
+		assert!(Metadata::read_field(&mut packet).is_err());
+	}
+
+	#[test]
+	fn typed_fields() {
+		let mut metadata = EntityMetadata::new();
+		metadata.set(living::HEALTH, 20.0);
+		metadata.set(entity::POSE, Pose::Sleeping);
+		metadata.set(player::SCORE, VarInt(7));
+
+		assert_eq!(metadata.get(living::HEALTH), Some(20.0));
+		assert_eq!(metadata.get(entity::POSE), Some(Pose::Sleeping));
+		assert_eq!(metadata.get(player::SCORE).map(|o| o.0), Some(7));
+		assert_eq!(metadata.get(entity::CUSTOM_NAME), None);
+
+		// Поле другого типа под тем же индексом не читается
+		metadata.set_value(entity::SILENT.index, MetadataValue::VarInt(1));
+		assert_eq!(metadata.get(entity::SILENT), None);
+		assert_eq!(
+			metadata.value(entity::SILENT.index),
+			Some(&MetadataValue::VarInt(1))
+		);
+	}
+
+	#[test]
+	fn flags() {
+		let mut metadata = EntityMetadata::new();
+		assert!(!metadata.flag(entity::FLAGS, entity::flags::CROUCHING));
+
+		metadata.set_flag(entity::FLAGS, entity::flags::CROUCHING, true);
+		metadata.set_flag(entity::FLAGS, entity::flags::FALL_FLYING, true);
+		assert!(metadata.flag(entity::FLAGS, entity::flags::CROUCHING));
+		assert!(metadata.flag(entity::FLAGS, entity::flags::FALL_FLYING));
+		assert_eq!(metadata.get(entity::FLAGS), Some(0x82u8 as i8));
+
+		metadata.set_flag(entity::FLAGS, entity::flags::CROUCHING, false);
+		assert!(!metadata.flag(entity::FLAGS, entity::flags::CROUCHING));
+		assert_eq!(metadata.get(entity::FLAGS), Some(0x80u8 as i8));
+	}
+
+	#[test]
+	fn dirty_tracking() {
+		let mut metadata = EntityMetadata::new();
+		assert!(!metadata.is_dirty());
+
+		metadata.set(living::HEALTH, 20.0);
+		metadata.set(entity::POSE, Pose::Standing);
+		assert!(metadata.is_dirty());
+		assert_eq!(
+			metadata.take_dirty().0,
+			[
+				(6, MetadataValue::Pose(Pose::Standing)),
+				(9, MetadataValue::Float(20.0)),
+			]
+		);
+		assert!(!metadata.is_dirty());
+		assert!(metadata.take_dirty().0.is_empty());
+
+		// То же значение не считается изменением
+		metadata.set(living::HEALTH, 20.0);
+		assert!(!metadata.is_dirty());
+
+		metadata.set(living::HEALTH, 15.0);
+		assert_eq!(metadata.take_dirty().0, [(9, MetadataValue::Float(15.0))]);
+
+		// all отдает все поля и не трогает отметки
+		metadata.set(entity::POSE, Pose::Sneaking);
+		assert_eq!(
+			metadata.all().0,
+			[
+				(6, MetadataValue::Pose(Pose::Sneaking)),
+				(9, MetadataValue::Float(15.0)),
+			]
+		);
+		assert!(metadata.is_dirty());
+	}
+}
@@ -0,0 +1,734 @@
+use palette::{Hsl, IntoColor, Srgb};
+use uuid::Uuid;
+
+use super::component::{
+	ClickEvent, Content, HoverEvent, NAMED_COLORS, NbtSource, TextComponent, color_rgb,
+};
+
+// Разметка в стиле MiniMessage: `<gold>Hello <bold>world</bold>`
+//
+// Парсер не строгий: неизвестные и незакрытые теги просто остаются текстом или закрываются в конце,
+// аргументы тегов идут через `:`, их можно брать в кавычки, `\<` - это просто символ <.
+// Стиль не вкладывается деревом, а раскладывается по плоскому списку кусков текста,
+// так проще красить градиенты, которые проходят через несколько тегов
+
+/// Разбирает разметку в компонент
+pub fn parse(input: &str) -> TextComponent {
+	let mut parser = Parser {
+		stack: Vec::new(),
+		out: Vec::new(),
+	};
+
+	let mut text = String::new();
+	let mut chars = input.char_indices().peekable();
+
+	while let Some((i, c)) = chars.next() {
+		match c {
+			'\\' => match chars.peek() {
+				Some((_, next @ ('<' | '\\'))) => {
+					text.push(*next);
+					chars.next();
+				}
+				_ => text.push(c),
+			},
+			'<' => match read_tag(&input[i + 1..]) {
+				Some((tag, length)) if parser.accepts(&tag) => {
+					parser.push_text(&mut text);
+					parser.apply(tag);
+					for _ in 0..length {
+						chars.next();
+					}
+				}
+				_ => text.push(c),
+			},
+			c => text.push(c),
+		}
+	}
+
+	parser.push_text(&mut text);
+	parser.finish()
+}
+
+/// Собирает разметку из компонента, parse от нее дает тот же текст с тем же стилем
+pub fn serialize(component: &TextComponent) -> String {
+	let mut out = String::new();
+	write(component, &mut out);
+	out
+}
+
+struct Tag {
+	close: bool,
+	name: String, // см. canonical
+	raw: String,  // имя как написано, из него берется цвет у <red> и <#ff0000>
+	args: Vec<String>,
+}
+
+enum Kind {
+	Style(Box<TextComponent>),
+	Gradient {
+		colors: Vec<u32>,
+		start: usize,
+	},
+	Rainbow {
+		reverse: bool,
+		phase: i64,
+		start: usize,
+	},
+}
+
+struct Open {
+	name: String,
+	kind: Kind,
+}
+
+struct Parser {
+	stack: Vec<Open>,
+	out: Vec<TextComponent>,
+}
+
+impl Parser {
+	fn accepts(&self, tag: &Tag) -> bool {
+		if tag.close {
+			return tag.name.is_empty() || self.stack.iter().any(|o| o.name == tag.name);
+		}
+		tag.name == "reset" || content(tag).is_some() || open(tag, self.out.len()).is_some()
+	}
+
+	fn apply(&mut self, tag: Tag) {
+		if tag.close {
+			// </> закрывает последний тег
+			let index = match tag.name.is_empty() {
+				true => self.stack.len().checked_sub(1),
+				false => self.stack.iter().rposition(|o| o.name == tag.name),
+			};
+			if let Some(index) = index {
+				while self.stack.len() > index {
+					let open = self.stack.pop().unwrap();
+					self.close(open);
+				}
+			}
+		} else if tag.name == "reset" {
+			while let Some(open) = self.stack.pop() {
+				self.close(open);
+			}
+		} else if let Some(content) = content(&tag) {
+			self.push(content);
+		} else if let Some(open) = open(&tag, self.out.len()) {
+			self.stack.push(open);
+		}
+	}
+
+	fn push_text(&mut self, text: &mut String) {
+		if !text.is_empty() {
+			self.push(Content::Text(std::mem::take(text)));
+		}
+	}
+
+	fn push(&mut self, content: Content) {
+		let mut component = TextComponent::from_content(content);
+		for open in self.stack.iter() {
+			if let Kind::Style(style) = &open.kind {
+				merge(&mut component, style);
+			}
+		}
+		self.out.push(component);
+	}
+
+	fn close(&mut self, open: Open) {
+		match open.kind {
+			Kind::Style(_) => {}
+			Kind::Gradient { colors, start } => {
+				self.recolor(start, |i, total| gradient(&colors, i, total))
+			}
+			Kind::Rainbow {
+				reverse,
+				phase,
+				start,
+			} => self.recolor(start, |i, total| {
+				let i = if reverse { total - 1 - i } else { i };
+				let hue = ((i as i64 + phase).rem_euclid(total as i64)) as f32 / total as f32 * 360.0;
+				let rgb: Srgb = Hsl::new(hue, 1.0, 0.5).into_color();
+				let channel = |o: f32| (o * 255.0).round() as u32;
+				(channel(rgb.red) << 16) | (channel(rgb.green) << 8) | channel(rgb.blue)
+			}),
+		}
+	}
+
+	// Красит куски начиная с start посимвольно
+	fn recolor(&mut self, start: usize, color: impl Fn(usize, usize) -> u32) {
+		let runs = self.out.split_off(start);
+		let total = runs
+			.iter()
+			.map(|o| match &o.content {
+				Content::Text(text) => text.chars().count(),
+				_ => 1,
+			})
+			.sum::<usize>()
+			.max(1);
+
+		let mut i = 0;
+		for run in runs {
+			let mut paint = |mut component: TextComponent| {
+				component.color = Some(format!("#{:06X}", color(i, total)));
+				i += 1;
+				self.out.push(component);
+			};
+
+			match &run.content {
+				Content::Text(text) => {
+					for c in text.chars() {
+						let mut component = run.clone();
+						component.content = Content::Text(c.to_string());
+						paint(component);
+					}
+				}
+				_ => paint(run),
+			}
+		}
+	}
+
+	fn finish(mut self) -> TextComponent {
+		while let Some(open) = self.stack.pop() {
+			self.close(open);
+		}
+
+		match self.out.len() {
+			0 => TextComponent::default(),
+			1 => self.out.pop().unwrap(),
+			_ => TextComponent {
+				extra: Some(self.out),
+				..Default::default()
+			},
+		}
+	}
+}
+
+// Читает тег после <, возвращает его и сколько символов он занял вместе с >
+fn read_tag(input: &str) -> Option<(Tag, usize)> {
+	let mut args = vec![String::new()];
+	let mut quote = None;
+	let mut escaped = false;
+
+	for (length, c) in input.chars().enumerate() {
+		match (quote, c) {
+			(Some(_), _) if escaped => {
+				args.last_mut().unwrap().push(c);
+				escaped = false;
+			}
+			(Some(_), '\\') => escaped = true,
+			(Some(q), c) if c == q => quote = None,
+			(Some(_), c) => args.last_mut().unwrap().push(c),
+			(None, '\'' | '"') => quote = Some(c),
+			(None, ':') => args.push(String::new()),
+			(None, '<') => return None,
+			(None, '>') => {
+				let name = args.remove(0).to_lowercase();
+				let (close, name) = match name.strip_prefix('/') {
+					Some(name) => (true, name.to_string()),
+					None => (false, name),
+				};
+				if (name.is_empty() && !close)
+					|| !name
+						.chars()
+						.all(|o| o.is_ascii_alphanumeric() || "_-!#".contains(o))
+				{
+					return None;
+				}
+				return Some((
+					Tag {
+						close,
+						name: canonical(&name),
+						raw: name,
+						args,
+					},
+					length + 1,
+				));
+			}
+			(None, c) => args.last_mut().unwrap().push(c),
+		}
+	}
+
+	None
+}
+
+// Имя, по которому тег закрывается: все цвета закрываются как color, сокращения - как полное имя
+fn canonical(name: &str) -> String {
+	let (negated, name) = match name.strip_prefix('!') {
+		Some(name) => (true, name),
+		None => (false, name),
+	};
+
+	let name = match name {
+		"b" => "bold",
+		"i" | "em" => "italic",
+		"u" => "underlined",
+		"st" => "strikethrough",
+		"obf" => "obfuscated",
+		"colour" | "c" => "color",
+		"insertion" => "insert",
+		"tr" | "translate" => "lang",
+		"tr_or" | "translate_or" => "lang_or",
+		"sel" => "selector",
+		"br" => "newline",
+		name if name.starts_with('#') || color_rgb(name).is_some() => "color",
+		name => name,
+	};
+
+	match negated {
+		true => format!("!{name}"),
+		false => name.to_string(),
+	}
+}
+
+// Теги, которые сами являются содержимым и не закрываются
+fn content(tag: &Tag) -> Option<Content> {
+	let arg = |i: usize| tag.args.get(i).cloned();
+	let components = |from: usize| tag.args.iter().skip(from).map(|o| parse(o)).collect();
+
+	Some(match tag.name.as_str() {
+		"newline" => Content::Text("\n".to_string()),
+		"lang" => Content::Translatable {
+			key: arg(0)?,
+			fallback: None,
+			with: components(1),
+		},
+		"lang_or" => Content::Translatable {
+			key: arg(0)?,
+			fallback: arg(1),
+			with: components(2),
+		},
+		"key" => Content::Keybind(arg(0)?),
+		"selector" => Content::Selector {
+			selector: arg(0)?,
+			separator: arg(1).map(|o| Box::new(parse(&o))),
+		},
+		"score" => Content::Score {
+			name: arg(0)?,
+			objective: arg(1)?,
+		},
+		"nbt" => Content::Nbt {
+			source: match arg(0)?.as_str() {
+				"block" => NbtSource::Block(arg(1)?),
+				"entity" => NbtSource::Entity(arg(1)?),
+				"storage" => NbtSource::Storage(arg(1)?),
+				_ => return None,
+			},
+			path: arg(2)?,
+			interpret: arg(3).map(|o| o == "interpret"),
+			separator: None,
+		},
+		_ => return None,
+	})
+}
+
+// Теги, которые открывают стиль до закрывающего тега
+fn open(tag: &Tag, start: usize) -> Option<Open> {
+	let arg = |i: usize| tag.args.get(i).cloned();
+	let mut style = TextComponent::default();
+
+	let (negated, name) = match tag.name.strip_prefix('!') {
+		Some(name) => (true, name),
+		None => (false, tag.name.as_str()),
+	};
+
+	match name {
+		"color" => {
+			// <red>, <#ff0000> или <color:red>
+			let color = match tag.args.is_empty() {
+				true => tag.raw.clone(),
+				false => arg(0)?,
+			};
+			style.color = Some(match color.starts_with('#') {
+				true => format!("#{:06X}", color_rgb(&color)?),
+				false => {
+					color_rgb(&color)?;
+					color.to_lowercase().replace("grey", "gray")
+				}
+			});
+		}
+		"bold" => style.bold = Some(!negated),
+		"italic" => style.italic = Some(!negated),
+		"underlined" => style.underlined = Some(!negated),
+		"strikethrough" => style.strikethrough = Some(!negated),
+		"obfuscated" => style.obfuscated = Some(!negated),
+		"font" => style.font = Some(arg(0)?),
+		"insert" => style.insertion = Some(arg(0)?),
+		"shadow" => {
+			style.shadow_color = Some(match negated {
+				true => 0,
+				false => {
+					let rgb = color_rgb(&arg(0)?)?;
+					let alpha = arg(1).and_then(|o| o.parse::<f32>().ok()).unwrap_or(0.25);
+					(((alpha.clamp(0.0, 1.0) * 255.0).round() as u32) << 24 | rgb) as i32
+				}
+			})
+		}
+		"click" => {
+			let value = arg(1)?;
+			style.click_event = Some(match arg(0)?.as_str() {
+				"open_url" => ClickEvent::OpenUrl(value),
+				"open_file" => ClickEvent::OpenFile(value),
+				"run_command" => ClickEvent::RunCommand(value),
+				"suggest_command" => ClickEvent::SuggestCommand(value),
+				"change_page" => ClickEvent::ChangePage(value.parse().ok()?),
+				"copy_to_clipboard" => ClickEvent::CopyToClipboard(value),
+				_ => return None,
+			});
+		}
+		"hover" => {
+			style.hover_event = Some(match arg(0)?.as_str() {
+				"show_text" => HoverEvent::ShowText(Box::new(parse(&arg(1)?))),
+				"show_item" => HoverEvent::ShowItem {
+					id: arg(1)?,
+					count: arg(2).and_then(|o| o.parse().ok()).unwrap_or(1),
+					components: None,
+				},
+				"show_entity" => HoverEvent::ShowEntity {
+					id: arg(1)?,
+					uuid: Uuid::parse_str(&arg(2)?).ok()?,
+					name: arg(3).map(|o| Box::new(parse(&o))),
+				},
+				_ => return None,
+			});
+		}
+		"gradient" => {
+			let mut colors = tag
+				.args
+				.iter()
+				.filter(|o| o.parse::<f32>().is_err()) // фаза не поддерживается
+				.map(|o| color_rgb(o))
+				.collect::<Option<Vec<_>>>()?;
+			match colors.len() {
+				0 => colors = vec![0xFFFFFF, 0x000000],
+				1 => colors.push(colors[0]),
+				_ => {}
+			}
+			return Some(Open {
+				name: tag.name.clone(),
+				kind: Kind::Gradient { colors, start },
+			});
+		}
+		"rainbow" => {
+			let arg = arg(0).unwrap_or_default();
+			let (reverse, phase) = match arg.strip_prefix('!') {
+				Some(phase) => (true, phase),
+				None => (false, arg.as_str()),
+			};
+			return Some(Open {
+				name: tag.name.clone(),
+				kind: Kind::Rainbow {
+					reverse,
+					phase: phase.parse().unwrap_or(0),
+					start,
+				},
+			});
+		}
+		_ => return None,
+	}
+
+	Some(Open {
+		name: tag.name.clone(),
+		kind: Kind::Style(Box::new(style)),
+	})
+}
+
+// Переносит заданные в style поля в component
+fn merge(component: &mut TextComponent, style: &TextComponent) {
+	macro_rules! merge {
+		($($field:ident),*) => {
+			$(if style.$field.is_some() {
+				component.$field = style.$field.clone();
+			})*
+		};
+	}
+	merge!(
+		color,
+		font,
+		bold,
+		italic,
+		underlined,
+		strikethrough,
+		obfuscated,
+		shadow_color,
+		insertion,
+		click_event,
+		hover_event
+	);
+}
+
+fn gradient(colors: &[u32], i: usize, total: usize) -> u32 {
+	let t = match total {
+		0 | 1 => 0.0,
+		total => i as f32 / (total - 1) as f32,
+	};
+	let segment = t * (colors.len() - 1) as f32;
+	let index = (segment.floor() as usize).min(colors.len() - 2);
+	let local = segment - index as f32;
+
+	let (from, to) = (colors[index], colors[index + 1]);
+	let channel = |shift: u32| {
+		let a = ((from >> shift) & 0xFF) as f32;
+		let b = ((to >> shift) & 0xFF) as f32;
+		((a + (b - a) * local).round() as u32) << shift
+	};
+	channel(16) | channel(8) | channel(0)
+}
+
+fn write(component: &TextComponent, out: &mut String) {
+	let mut close = Vec::new();
+	let mut tag = |open: String, name: &str| {
+		out.push_str(&format!("<{open}>"));
+		close.push(format!("</{name}>"));
+	};
+
+	if let Some(color) = &component.color {
+		match NAMED_COLORS.iter().any(|o| o.0 == color) {
+			true => tag(color.clone(), color),
+			false => tag(format!("color:{color}"), "color"),
+		}
+	}
+
+	let decorations = [
+		("bold", component.bold),
+		("italic", component.italic),
+		("underlined", component.underlined),
+		("strikethrough", component.strikethrough),
+		("obfuscated", component.obfuscated),
+	];
+	for (name, value) in decorations {
+		match value {
+			Some(true) => tag(name.to_string(), name),
+			Some(false) => tag(format!("!{name}"), &format!("!{name}")),
+			None => {}
+		}
+	}
+
+	if let Some(font) = &component.font {
+		tag(format!("font:{}", quote(font)), "font");
+	}
+	if let Some(insertion) = &component.insertion {
+		tag(format!("insert:{}", quote(insertion)), "insert");
+	}
+	if let Some(shadow) = component.shadow_color {
+		// Трех знаков хватает, чтобы parse вернул тот же байт альфы
+		let alpha = ((shadow as u32) >> 24) as f32 / 255.0;
+		tag(
+			format!("shadow:#{:06X}:{alpha:.3}", shadow as u32 & 0xFFFFFF),
+			"shadow",
+		);
+	}
+
+	if let Some(event) = &component.click_event {
+		let (action, value) = match event {
+			ClickEvent::OpenUrl(value) => ("open_url", value.clone()),
+			ClickEvent::OpenFile(value) => ("open_file", value.clone()),
+			ClickEvent::RunCommand(value) => ("run_command", value.clone()),
+			ClickEvent::SuggestCommand(value) => ("suggest_command", value.clone()),
+			ClickEvent::ChangePage(page) => ("change_page", page.to_string()),
+			ClickEvent::CopyToClipboard(value) => ("copy_to_clipboard", value.clone()),
+		};
+		tag(format!("click:{action}:{}", quote(&value)), "click");
+	}
+
+	if let Some(event) = &component.hover_event {
+		let value = match event {
+			HoverEvent::ShowText(text) => format!("show_text:{}", quote(&serialize(text))),
+			HoverEvent::ShowItem { id, count, .. } => format!("show_item:{}:{count}", quote(id)),
+			HoverEvent::ShowEntity { id, uuid, name } => {
+				let mut value = format!("show_entity:{}:{uuid}", quote(id));
+				if let Some(name) = name {
+					value.push_str(&format!(":{}", quote(&serialize(name))));
+				}
+				value
+			}
+		};
+		tag(format!("hover:{value}"), "hover");
+	}
+
+	match &component.content {
+		Content::Text(text) => out.push_str(&text.replace('\\', "\\\\").replace('<', "\\<")),
+		Content::Translatable {
+			key,
+			fallback,
+			with,
+		} => {
+			match fallback {
+				Some(fallback) => out.push_str(&format!("<lang_or:{}:{}", quote(key), quote(fallback))),
+				None => out.push_str(&format!("<lang:{}", quote(key))),
+			}
+			for arg in with {
+				out.push_str(&format!(":{}", quote(&serialize(arg))));
+			}
+			out.push('>');
+		}
+		Content::Score { name, objective } => {
+			out.push_str(&format!("<score:{}:{}>", quote(name), quote(objective)))
+		}
+		Content::Selector {
+			selector,
+			separator,
+		} => {
+			out.push_str(&format!("<selector:{}", quote(selector)));
+			if let Some(separator) = separator {
+				out.push_str(&format!(":{}", quote(&serialize(separator))));
+			}
+			out.push('>');
+		}
+		Content::Keybind(key) => out.push_str(&format!("<key:{}>", quote(key))),
+		Content::Nbt {
+			path,
+			interpret,
+			source,
+			..
+		} => {
+			let (source, id) = match source {
+				NbtSource::Block(id) => ("block", id),
+				NbtSource::Entity(id) => ("entity", id),
+				NbtSource::Storage(id) => ("storage", id),
+			};
+			out.push_str(&format!("<nbt:{source}:{}:{}", quote(id), quote(path)));
+			if *interpret == Some(true) {
+				out.push_str(":interpret");
+			}
+			out.push('>');
+		}
+	}
+
+	for child in component.extra.iter().flatten() {
+		write(child, out);
+	}
+
+	for tag in close.iter().rev() {
+		out.push_str(tag);
+	}
+}
+
+// Аргумент в кавычках, если без них он сломает тег
+fn quote(value: &str) -> String {
+	match value.contains([':', '<', '>', '\'', '"', '\\', ' ']) {
+		true => format!("'{}'", value.replace('\\', "\\\\").replace('\'', "\\'")),
+		false => value.to_string(),
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn text(text: &str) -> TextComponent {
+		TextComponent::new(text.to_string())
+	}
+
+	fn colored(value: &str, color: &str) -> TextComponent {
+		TextComponent {
+			color: Some(color.to_string()),
+			..text(value)
+		}
+	}
+
+	#[test]
+	fn nested_styles() {
+		let component = parse("<gold>Hello <bold>world</bold>!</gold> bye");
+		let extra = component.extra.unwrap();
+
+		assert_eq!(extra.len(), 4);
+		assert_eq!(extra[0], colored("Hello ", "gold"));
+		assert_eq!(
+			extra[1],
+			TextComponent {
+				bold: Some(true),
+				..colored("world", "gold")
+			}
+		);
+		assert_eq!(extra[2], colored("!", "gold"));
+		assert_eq!(extra[3], text(" bye"));
+	}
+
+	#[test]
+	fn aliases_and_closing() {
+		// Сокращения закрываются полным именем, </> закрывает последний тег, незакрытые - в конце
+		let extra = parse("<b>a</bold><i><#00ff00>b</>c<u>d").extra.unwrap();
+
+		assert_eq!(extra[0].bold, Some(true));
+		assert_eq!(extra[1].italic, Some(true));
+		assert_eq!(extra[1].color.as_deref(), Some("#00FF00"));
+		assert_eq!(extra[2].color, None);
+		assert_eq!(extra[2].italic, Some(true));
+		assert_eq!(extra[3].underlined, Some(true));
+		assert_eq!(extra[3].italic, Some(true));
+
+		let reset = parse("<red><bold>a<reset>b").extra.unwrap();
+		assert_eq!(reset[1], text("b"));
+	}
+
+	#[test]
+	fn broken_tags_stay_text() {
+		assert_eq!(parse("<nope>x"), text("<nope>x"));
+		assert_eq!(parse("a < b > c"), text("a < b > c"));
+		assert_eq!(parse(r"\<red>x"), text("<red>x"));
+		assert_eq!(
+			parse(r"\\<red>x").extra.unwrap(),
+			[text(r"\"), colored("x", "red")]
+		);
+		assert_eq!(parse(r"\\"), text(r"\"));
+		assert_eq!(parse("<#+12345>x"), text("<#+12345>x"));
+		assert_eq!(parse("</red>x"), text("</red>x"));
+	}
+
+	#[test]
+	fn gradient_colors_each_char() {
+		let extra = parse("<gradient:#000000:#ffffff>abc</gradient>")
+			.extra
+			.unwrap();
+		let colors = extra
+			.iter()
+			.map(|o| o.color.as_deref().unwrap())
+			.collect::<Vec<_>>();
+
+		assert_eq!(colors, ["#000000", "#808080", "#FFFFFF"]);
+		assert_eq!(extra[1].plain_text(), "b");
+	}
+
+	#[test]
+	fn shadow_alpha() {
+		assert_eq!(parse("<shadow:red>x").shadow_color, Some(0x40FF5555));
+		assert_eq!(
+			parse("<shadow:#000000:1>x").shadow_color,
+			Some(0xFF000000u32 as i32)
+		);
+		assert_eq!(parse("<!shadow>x").shadow_color, Some(0));
+
+		for alpha in 0..=255u32 {
+			let shadow = ((alpha << 24) | 0x123456) as i32;
+			let component = TextComponent {
+				shadow_color: Some(shadow),
+				..text("x")
+			};
+			assert_eq!(parse(&serialize(&component)), component, "{alpha}");
+		}
+		assert_eq!(
+			parse("<shadow:red:0.004>x"),
+			parse(&serialize(&parse("<shadow:red:0.004>x")))
+		);
+	}
+
+	#[test]
+	fn serialize_round_trip() {
+		let inputs = [
+			"<gold>Hello <bold>world</bold>!</gold>",
+			"<color:#FF8800><!italic>x",
+			"<click:run_command:'/tp @s ~ ~1 ~'>go",
+			"<hover:show_text:'<red>hi'>x",
+			"<lang:chat.type.text:Steve:'<bold>hi'>",
+			"<lang_or:unknown.key:fallback>",
+			"<key:key.jump> <score:Steve:kills> <selector:@p>",
+			"<insert:'a:b'><font:minecraft:uniform>x",
+			r"a \\<red> \<b> \\",
+		];
+
+		for input in inputs {
+			let component = parse(input);
+			assert_eq!(parse(&serialize(&component)), component, "{input}");
+		}
+	}
+}
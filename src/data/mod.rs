@@ -1,4 +1,4 @@
-use std::io::Read;
+use std::{collections::HashMap, io::Read};
 
 use craftflow_nbt::DynNBT;
 use rust_mc_proto::{DataReader, DataWriter, Packet};
@@ -6,6 +6,9 @@ use rust_mc_proto::{DataReader, DataWriter, Packet};
 use super::ServerError;
 
 pub mod component;
+pub mod legacy;
+pub mod metadata;
+pub mod minimessage;
 pub mod slot;
 
 // Трейт для чтения NBT-совместимых приколов
@@ -22,6 +25,9 @@ impl ReadWriteNBT<DynNBT> for Packet {
 			.get_mut()
 			.read_to_end(&mut data)
 			.map_err(|_| ServerError::DeNbt)?;
+		if nbt_too_deep(&data, NBT_MAX_DEPTH) {
+			return Err(ServerError::DeNbt);
+		}
 		let (remaining, value) = craftflow_nbt::from_slice(&data).map_err(|_| ServerError::DeNbt)?;
 		self
 			.get_mut()
@@ -35,6 +41,156 @@ impl ReadWriteNBT<DynNBT> for Packet {
 	}
 }
 
+/// Максимальная вложенность компаундов и списков в NBT из пакетов, как у ванильного NbtAccounter
+pub const NBT_MAX_DEPTH: usize = 512;
+
+/// Вложены ли компаунды и списки сетевого NBT (корень без имени) глубже limit
+///
+/// craftflow_nbt читает рекурсивно, и пакет с NBT в тысячи уровней кладет сервер переполнением стека,
+/// поэтому глубина проверяется заранее и без рекурсии. Битые данные не считаются глубокими,
+/// их отбракует сам парсер
+pub fn nbt_too_deep(data: &[u8], limit: usize) -> bool {
+	scan_nbt_depth(data, limit).unwrap_or(false)
+}
+
+enum NbtFrame {
+	Compound,
+	List(u8, i32), // тип элементов и сколько их осталось
+}
+
+fn scan_nbt_depth(data: &[u8], limit: usize) -> Option<bool> {
+	let mut data = data;
+	let mut stack = Vec::new();
+
+	let root = take(&mut data, 1)?[0];
+	enter_nbt_tag(&mut data, &mut stack, root)?;
+
+	loop {
+		if stack.len() > limit {
+			return Some(true);
+		}
+
+		let tag = match stack.last_mut() {
+			None => return Some(false),
+			Some(NbtFrame::Compound) => {
+				let tag = take(&mut data, 1)?[0];
+				if tag == 0 {
+					stack.pop();
+					continue;
+				}
+				let name = take(&mut data, 2)?;
+				take(&mut data, u16::from_be_bytes([name[0], name[1]]) as usize)?;
+				tag
+			}
+			Some(NbtFrame::List(tag, remaining)) => {
+				if *remaining <= 0 {
+					stack.pop();
+					continue;
+				}
+				*remaining -= 1;
+				*tag
+			}
+		};
+
+		enter_nbt_tag(&mut data, &mut stack, tag)?;
+	}
+}
+
+// Пропускает значение тега, у компаундов и списков только открывает их
+fn enter_nbt_tag(data: &mut &[u8], stack: &mut Vec<NbtFrame>, tag: u8) -> Option<()> {
+	let length = |data: &mut &[u8], size: usize| -> Option<usize> {
+		let length = i32::from_be_bytes(take(data, 4)?.try_into().ok()?);
+		usize::try_from(length).ok()?.checked_mul(size)
+	};
+
+	match tag {
+		1 => take(data, 1).map(|_| ()),
+		2 => take(data, 2).map(|_| ()),
+		3 | 5 => take(data, 4).map(|_| ()),
+		4 | 6 => take(data, 8).map(|_| ()),
+		7 => length(data, 1).and_then(|o| take(data, o)).map(|_| ()),
+		8 => {
+			let size = take(data, 2)?;
+			take(data, u16::from_be_bytes([size[0], size[1]]) as usize).map(|_| ())
+		}
+		9 => {
+			let tag = take(data, 1)?[0];
+			let count = i32::from_be_bytes(take(data, 4)?.try_into().ok()?);
+			stack.push(NbtFrame::List(tag, count));
+			Some(())
+		}
+		10 => {
+			stack.push(NbtFrame::Compound);
+			Some(())
+		}
+		11 => length(data, 4).and_then(|o| take(data, o)).map(|_| ()),
+		12 => length(data, 8).and_then(|o| take(data, o)).map(|_| ()),
+		_ => None,
+	}
+}
+
+fn take<'a>(data: &mut &'a [u8], size: usize) -> Option<&'a [u8]> {
+	if data.len() < size {
+		return None;
+	}
+	let (head, rest) = data.split_at(size);
+	*data = rest;
+	Some(head)
+}
+
+/// Переводит json (например из датапака) в NBT
+///
+/// Целые числа становятся Int или Long, дробные - Double, bool - Byte, null пропускается
+pub fn json_to_nbt(value: &serde_json::Value) -> DynNBT {
+	use serde_json::Value;
+
+	match value {
+		Value::Null => DynNBT::Compound(HashMap::new()),
+		Value::Bool(value) => DynNBT::Byte(*value as i8),
+		Value::Number(number) => match number.as_i64() {
+			Some(value) => match i32::try_from(value) {
+				Ok(value) => DynNBT::Int(value),
+				Err(_) => DynNBT::Long(value),
+			},
+			None => DynNBT::Double(number.as_f64().unwrap_or(0.0)),
+		},
+		Value::String(value) => DynNBT::String(value.clone()),
+		Value::Array(values) => DynNBT::List(values.iter().map(json_to_nbt).collect()),
+		Value::Object(map) => DynNBT::Compound(
+			map
+				.iter()
+				.filter(|o| !o.1.is_null())
+				.map(|(key, value)| (key.clone(), json_to_nbt(value)))
+				.collect(),
+		),
+	}
+}
+
+/// Обратно из NBT в json, типы чисел теряются, массивы становятся списками
+pub fn nbt_to_json(value: &DynNBT) -> serde_json::Value {
+	use serde_json::Value;
+
+	match value {
+		DynNBT::Long(value) => Value::from(*value),
+		DynNBT::Int(value) => Value::from(*value),
+		DynNBT::Short(value) => Value::from(*value),
+		DynNBT::Byte(value) => Value::from(*value),
+		DynNBT::Double(value) => Value::from(*value),
+		DynNBT::Float(value) => Value::from(*value),
+		DynNBT::String(value) => Value::from(value.clone()),
+		DynNBT::List(values) => Value::Array(values.iter().map(nbt_to_json).collect()),
+		DynNBT::Compound(map) => Value::Object(
+			map
+				.iter()
+				.map(|(key, value)| (key.clone(), nbt_to_json(value)))
+				.collect(),
+		),
+		DynNBT::LongArray(values) => Value::from(values.clone()),
+		DynNBT::IntArray(values) => Value::from(values.clone()),
+		DynNBT::ByteArray(values) => Value::from(values.clone()),
+	}
+}
+
 pub trait ReadWritePosition: DataReader + DataWriter {
 	fn read_position(&mut self) -> Result<(i64, i64, i64), ServerError>;
 	fn write_position(&mut self, x: i64, y: i64, z: i64) -> Result<(), ServerError>;
@@ -50,3 +206,57 @@ impl ReadWritePosition for Packet {
 		Ok(self.write_long(((x & 0x3FFFFFF) << 38) | ((z & 0x3FFFFFF) << 12) | (y & 0xFFF))?)
 	}
 }
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	// Список списков глубиной depth, в самом низу пустой список
+	fn nested_lists(depth: usize) -> Vec<u8> {
+		let mut data = vec![9];
+		for _ in 0..depth {
+			data.extend_from_slice(&[9, 0, 0, 0, 1]);
+		}
+		data.extend_from_slice(&[0, 0, 0, 0, 0]);
+		data
+	}
+
+	// Компаунды, вложенные через поле "a"
+	fn nested_compounds(depth: usize) -> Vec<u8> {
+		let mut data = vec![10];
+		for _ in 0..depth {
+			data.extend_from_slice(&[10, 0, 1, b'a']);
+		}
+		data.extend(std::iter::repeat_n(0, depth + 1));
+		data
+	}
+
+	#[test]
+	fn deep_nbt_is_detected() {
+		assert!(!nbt_too_deep(&nested_lists(100), NBT_MAX_DEPTH));
+		assert!(nbt_too_deep(&nested_lists(100_000), NBT_MAX_DEPTH));
+		assert!(!nbt_too_deep(&nested_compounds(100), NBT_MAX_DEPTH));
+		assert!(nbt_too_deep(&nested_compounds(100_000), NBT_MAX_DEPTH));
+	}
+
+	#[test]
+	fn deep_nbt_packet_is_rejected() {
+		let mut packet = Packet::from_bytes(0, &nested_lists(100_000));
+		assert!(matches!(
+			ReadWriteNBT::<DynNBT>::read_nbt(&mut packet),
+			Err(ServerError::DeNbt)
+		));
+
+		let mut packet = Packet::from_bytes(0, &nested_compounds(100));
+		assert!(ReadWriteNBT::<DynNBT>::read_nbt(&mut packet).is_ok());
+	}
+
+	#[test]
+	fn broken_nbt_is_not_deep() {
+		let mut data = nested_compounds(10);
+		data.truncate(20);
+		assert!(!nbt_too_deep(&data, NBT_MAX_DEPTH));
+		assert!(!nbt_too_deep(&[], NBT_MAX_DEPTH));
+		assert!(!nbt_too_deep(&[99], NBT_MAX_DEPTH));
+	}
+}
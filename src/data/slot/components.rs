@@ -0,0 +1,769 @@
+use craftflow_nbt::DynNBT;
+use rust_mc_proto::{DataReader, DataWriter, Packet};
+use uuid::Uuid;
+
+use super::{NestedField, Slot};
+use crate::{
+	ServerError,
+	data::component::TextComponent,
+	protocol::{
+		packets::{PacketField, Position, VarInt, packet_field},
+		session::ProfileProperty,
+	},
+};
+
+// Компоненты данных предметов 1.21.5
+//
+// Айдишник компонента это его номер в реестре minecraft:data_component_type,
+// значение пишется без префикса длины, поэтому незнакомый компонент пропустить нельзя
+// и каждый тип должен уметь прочитать себя полностью
+//
+// Компоненты со слотами или другими компонентами внутри (nested) читаются через NestedField
+// с глубиной, иначе клиент присылает слот в слоте в слоте и кладет сервер переполнением стека
+
+/// Максимальная вложенность компонентов, у ванильных предметов больше пары уровней не бывает
+pub const MAX_NESTING: usize = 16;
+
+/// Айди из реестра или значение прямо в пакете (ID or X): VarInt 0 значит что дальше идет значение,
+/// иначе это айди + 1
+#[derive(Debug, Clone, PartialEq)]
+pub enum Holder<T> {
+	Reference(i32),
+	Direct(T),
+}
+
+impl<T: PacketField> PacketField for Holder<T> {
+	fn read_field(packet: &mut Packet) -> Result<Self, ServerError> {
+		Ok(match packet.read_varint()? {
+			0 => Holder::Direct(T::read_field(packet)?),
+			id => Holder::Reference(id - 1),
+		})
+	}
+
+	fn write_field(&self, packet: &mut Packet) -> Result<(), ServerError> {
+		match self {
+			Holder::Reference(id) => Ok(packet.write_varint(id + 1)?),
+			Holder::Direct(value) => {
+				packet.write_varint(0)?;
+				value.write_field(packet)
+			}
+		}
+	}
+}
+
+/// Либо холдер, либо просто ключ реестра, который клиент может не знать (Boolean true - холдер)
+#[derive(Debug, Clone, PartialEq)]
+pub enum EitherHolder<T> {
+	Holder(T),
+	Key(String),
+}
+
+impl<T: PacketField> PacketField for EitherHolder<T> {
+	fn read_field(packet: &mut Packet) -> Result<Self, ServerError> {
+		Ok(match packet.read_boolean()? {
+			true => EitherHolder::Holder(T::read_field(packet)?),
+			false => EitherHolder::Key(packet.read_string()?),
+		})
+	}
+
+	fn write_field(&self, packet: &mut Packet) -> Result<(), ServerError> {
+		match self {
+			EitherHolder::Holder(value) => {
+				packet.write_boolean(true)?;
+				value.write_field(packet)
+			}
+			EitherHolder::Key(key) => {
+				packet.write_boolean(false)?;
+				Ok(packet.write_string(key)?)
+			}
+		}
+	}
+}
+
+/// Набор айдишников реестра: VarInt 0 и имя тега, либо кол-во + 1 и сами айди
+#[derive(Debug, Clone, PartialEq)]
+pub enum IdSet {
+	Tag(String),
+	Ids(Vec<i32>),
+}
+
+impl PacketField for IdSet {
+	fn read_field(packet: &mut Packet) -> Result<Self, ServerError> {
+		Ok(match packet.read_usize_varint()? {
+			0 => IdSet::Tag(packet.read_string()?),
+			count => {
+				let mut ids = Vec::with_capacity((count - 1).min(1024));
+				for _ in 1..count {
+					ids.push(packet.read_varint()?);
+				}
+				IdSet::Ids(ids)
+			}
+		})
+	}
+
+	fn write_field(&self, packet: &mut Packet) -> Result<(), ServerError> {
+		match self {
+			IdSet::Tag(tag) => {
+				packet.write_varint(0)?;
+				Ok(packet.write_string(tag)?)
+			}
+			IdSet::Ids(ids) => {
+				packet.write_usize_varint(ids.len() + 1)?;
+				for id in ids {
+					packet.write_varint(*id)?;
+				}
+				Ok(())
+			}
+		}
+	}
+}
+
+packet_field! {
+	/// Звук, заданный прямо в пакете
+	pub struct SoundEvent {
+		pub name: String,
+		pub fixed_range: Option<f32>,
+	}
+}
+
+packet_field! {
+	pub struct Enchantment {
+		pub kind: VarInt,
+		pub level: VarInt,
+	}
+}
+
+/// Проверка свойства блока: либо точное значение, либо диапазон
+#[derive(Debug, Clone, PartialEq)]
+pub struct PropertyMatcher {
+	pub name: String,
+	pub value: PropertyValue,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum PropertyValue {
+	Exact(String),
+	Range { min: String, max: String },
+}
+
+impl PacketField for PropertyMatcher {
+	fn read_field(packet: &mut Packet) -> Result<Self, ServerError> {
+		let name = packet.read_string()?;
+		let value = match packet.read_boolean()? {
+			true => PropertyValue::Exact(packet.read_string()?),
+			false => PropertyValue::Range {
+				min: packet.read_string()?,
+				max: packet.read_string()?,
+			},
+		};
+		Ok(PropertyMatcher { name, value })
+	}
+
+	fn write_field(&self, packet: &mut Packet) -> Result<(), ServerError> {
+		packet.write_string(&self.name)?;
+		match &self.value {
+			PropertyValue::Exact(value) => {
+				packet.write_boolean(true)?;
+				packet.write_string(value)?;
+			}
+			PropertyValue::Range { min, max } => {
+				packet.write_boolean(false)?;
+				packet.write_string(min)?;
+				packet.write_string(max)?;
+			}
+		}
+		Ok(())
+	}
+}
+
+packet_field! {
+	/// Частичная проверка компонента, сам предикат в NBT
+	pub struct PartialComponentPredicate {
+		pub kind: VarInt,
+		pub predicate: DynNBT,
+	}
+}
+
+packet_field! {
+	/// Проверка блока для can_place_on и can_break
+	pub struct BlockPredicate {
+		pub blocks: Option<IdSet>,
+		pub properties: Option<Vec<PropertyMatcher>>,
+		pub nbt: Option<DynNBT>,
+		pub exact_components: Vec<DataComponent>,
+		pub partial_components: Vec<PartialComponentPredicate>,
+	}
+}
+
+packet_field! {
+	pub struct AttributeModifier {
+		pub attribute: VarInt,
+		pub id: String,
+		pub amount: f64,
+		pub operation: VarInt,
+		pub slot: VarInt,
+	}
+}
+
+packet_field! {
+	pub struct CustomModelData {
+		pub floats: Vec<f32>,
+		pub flags: Vec<bool>,
+		pub strings: Vec<String>,
+		pub colors: Vec<i32>,
+	}
+}
+
+packet_field! {
+	pub struct TooltipDisplay {
+		pub hide_tooltip: bool,
+		pub hidden_components: Vec<VarInt>,
+	}
+}
+
+packet_field! {
+	pub struct Food {
+		pub nutrition: VarInt,
+		pub saturation: f32,
+		pub can_always_eat: bool,
+	}
+}
+
+packet_field! {
+	pub struct PotionEffectDetails {
+		pub amplifier: VarInt,
+		pub duration: VarInt,
+		pub ambient: bool,
+		pub show_particles: bool,
+		pub show_icon: bool,
+		pub hidden_effect: Option<Box<PotionEffectDetails>>,
+	}
+}
+
+packet_field! {
+	pub struct PotionEffect {
+		pub kind: VarInt,
+		pub details: PotionEffectDetails,
+	}
+}
+
+/// Эффект при использовании предмета (consumable и death_protection)
+#[derive(Debug, Clone, PartialEq)]
+pub enum ConsumeEffect {
+	ApplyEffects {
+		effects: Vec<PotionEffect>,
+		probability: f32,
+	},
+	RemoveEffects(IdSet),
+	ClearAllEffects,
+	TeleportRandomly(f32),
+	PlaySound(Holder<SoundEvent>),
+}
+
+impl PacketField for ConsumeEffect {
+	fn read_field(packet: &mut Packet) -> Result<Self, ServerError> {
+		Ok(match packet.read_varint()? {
+			0 => ConsumeEffect::ApplyEffects {
+				effects: PacketField::read_field(packet)?,
+				probability: packet.read_float()?,
+			},
+			1 => ConsumeEffect::RemoveEffects(IdSet::read_field(packet)?),
+			2 => ConsumeEffect::ClearAllEffects,
+			3 => ConsumeEffect::TeleportRandomly(packet.read_float()?),
+			4 => ConsumeEffect::PlaySound(Holder::read_field(packet)?),
+			_ => return Err(ServerError::WrongPacket),
+		})
+	}
+
+	fn write_field(&self, packet: &mut Packet) -> Result<(), ServerError> {
+		match self {
+			ConsumeEffect::ApplyEffects {
+				effects,
+				probability,
+			} => {
+				packet.write_varint(0)?;
+				effects.write_field(packet)?;
+				Ok(packet.write_float(*probability)?)
+			}
+			ConsumeEffect::RemoveEffects(effects) => {
+				packet.write_varint(1)?;
+				effects.write_field(packet)
+			}
+			ConsumeEffect::ClearAllEffects => Ok(packet.write_varint(2)?),
+			ConsumeEffect::TeleportRandomly(diameter) => {
+				packet.write_varint(3)?;
+				Ok(packet.write_float(*diameter)?)
+			}
+			ConsumeEffect::PlaySound(sound) => {
+				packet.write_varint(4)?;
+				sound.write_field(packet)
+			}
+		}
+	}
+}
+
+packet_field! {
+	pub struct Consumable {
+		pub consume_seconds: f32,
+		pub animation: VarInt,
+		pub sound: Holder<SoundEvent>,
+		pub has_consume_particles: bool,
+		pub effects: Vec<ConsumeEffect>,
+	}
+}
+
+packet_field! {
+	pub struct UseCooldown {
+		pub seconds: f32,
+		pub cooldown_group: Option<String>,
+	}
+}
+
+packet_field! {
+	pub struct ToolRule {
+		pub blocks: IdSet,
+		pub speed: Option<f32>,
+		pub correct_for_drops: Option<bool>,
+	}
+}
+
+packet_field! {
+	pub struct Tool {
+		pub rules: Vec<ToolRule>,
+		pub default_mining_speed: f32,
+		pub damage_per_block: VarInt,
+		pub can_destroy_blocks_in_creative: bool,
+	}
+}
+
+packet_field! {
+	pub struct Weapon {
+		pub item_damage_per_attack: VarInt,
+		pub disable_blocking_for_seconds: f32,
+	}
+}
+
+packet_field! {
+	pub struct Equippable {
+		pub slot: VarInt,
+		pub equip_sound: Holder<SoundEvent>,
+		pub asset_id: Option<String>,
+		pub camera_overlay: Option<String>,
+		pub allowed_entities: Option<IdSet>,
+		pub dispensable: bool,
+		pub swappable: bool,
+		pub damage_on_hurt: bool,
+		pub equip_on_interact: bool,
+	}
+}
+
+packet_field! {
+	pub struct DamageReduction {
+		pub horizontal_blocking_angle: f32,
+		pub damage_types: Option<IdSet>,
+		pub base: f32,
+		pub factor: f32,
+	}
+}
+
+packet_field! {
+	pub struct ItemDamage {
+		pub threshold: f32,
+		pub base: f32,
+		pub factor: f32,
+	}
+}
+
+packet_field! {
+	pub struct BlocksAttacks {
+		pub block_delay_seconds: f32,
+		pub disable_cooldown_scale: f32,
+		pub damage_reductions: Vec<DamageReduction>,
+		pub item_damage: ItemDamage,
+		pub bypassed_by: Option<String>,
+		pub block_sound: Option<Holder<SoundEvent>>,
+		pub disable_sound: Option<Holder<SoundEvent>>,
+	}
+}
+
+packet_field! {
+	pub struct PotionContents {
+		pub potion: Option<VarInt>,
+		pub custom_color: Option<i32>,
+		pub custom_effects: Vec<PotionEffect>,
+		pub custom_name: Option<String>,
+	}
+}
+
+packet_field! {
+	pub struct StewEffect {
+		pub kind: VarInt,
+		pub duration: VarInt,
+	}
+}
+
+packet_field! {
+	/// Текст книги, filtered это версия после фильтра чата
+	pub struct FilterableString {
+		pub raw: String,
+		pub filtered: Option<String>,
+	}
+}
+
+packet_field! {
+	pub struct FilterableText {
+		pub raw: TextComponent,
+		pub filtered: Option<TextComponent>,
+	}
+}
+
+packet_field! {
+	pub struct WrittenBookContent {
+		pub title: FilterableString,
+		pub author: String,
+		pub generation: VarInt,
+		pub pages: Vec<FilterableText>,
+		pub resolved: bool,
+	}
+}
+
+packet_field! {
+	pub struct AssetOverride {
+		pub equipment: String,
+		pub asset: String,
+	}
+}
+
+packet_field! {
+	pub struct TrimMaterial {
+		pub asset_name: String,
+		pub overrides: Vec<AssetOverride>,
+		pub description: TextComponent,
+	}
+}
+
+packet_field! {
+	pub struct TrimPattern {
+		pub asset_id: String,
+		pub description: TextComponent,
+		pub decal: bool,
+	}
+}
+
+packet_field! {
+	pub struct Trim {
+		pub material: Holder<TrimMaterial>,
+		pub pattern: Holder<TrimPattern>,
+	}
+}
+
+packet_field! {
+	pub struct Instrument {
+		pub sound: Holder<SoundEvent>,
+		pub use_duration: f32,
+		pub range: f32,
+		pub description: TextComponent,
+	}
+}
+
+packet_field! {
+	pub struct JukeboxSong {
+		pub sound: Holder<SoundEvent>,
+		pub description: TextComponent,
+		pub length_seconds: f32,
+		pub comparator_output: VarInt,
+	}
+}
+
+packet_field! {
+	pub struct GlobalPosition {
+		pub dimension: String,
+		pub position: Position,
+	}
+}
+
+packet_field! {
+	pub struct LodestoneTracker {
+		pub target: Option<GlobalPosition>,
+		pub tracked: bool,
+	}
+}
+
+packet_field! {
+	pub struct FireworkExplosion {
+		pub shape: VarInt,
+		pub colors: Vec<i32>,
+		pub fade_colors: Vec<i32>,
+		pub trail: bool,
+		pub twinkle: bool,
+	}
+}
+
+packet_field! {
+	pub struct Fireworks {
+		pub flight_duration: VarInt,
+		pub explosions: Vec<FireworkExplosion>,
+	}
+}
+
+packet_field! {
+	pub struct Profile {
+		pub name: Option<String>,
+		pub uuid: Option<Uuid>,
+		pub properties: Vec<ProfileProperty>,
+	}
+}
+
+packet_field! {
+	pub struct BannerPattern {
+		pub asset_id: String,
+		pub translation_key: String,
+	}
+}
+
+packet_field! {
+	pub struct BannerLayer {
+		pub pattern: Holder<BannerPattern>,
+		pub color: VarInt,
+	}
+}
+
+packet_field! {
+	pub struct BlockStateProperty {
+		pub name: String,
+		pub value: String,
+	}
+}
+
+packet_field! {
+	pub struct Bee {
+		pub entity_data: DynNBT,
+		pub ticks_in_hive: VarInt,
+		pub min_ticks_in_hive: VarInt,
+	}
+}
+
+packet_field! {
+	pub struct PaintingVariant {
+		pub width: i32,
+		pub height: i32,
+		pub asset_id: String,
+		pub title: Option<TextComponent>,
+		pub author: Option<TextComponent>,
+	}
+}
+
+// Пишет значение варианта енама, у компонентов без значения писать нечего
+macro_rules! write_component {
+	($component:expr, $packet:expr, $variant:ident) => {
+		if let DataComponent::$variant = $component {
+			return Ok(());
+		}
+	};
+	($component:expr, $packet:expr, $variant:ident, $ty:ty) => {
+		if let DataComponent::$variant(value) = $component {
+			return <$ty as PacketField>::write_field(value, $packet);
+		}
+	};
+}
+
+// Читает значение варианта, nested - с учетом глубины
+macro_rules! read_component {
+	($packet:expr, $depth:expr, $variant:ident) => {
+		DataComponent::$variant
+	};
+	($packet:expr, $depth:expr, $variant:ident, $ty:ty) => {
+		DataComponent::$variant(<$ty as PacketField>::read_field($packet)?)
+	};
+	($packet:expr, $depth:expr, nested $variant:ident, $ty:ty) => {
+		DataComponent::$variant(<$ty as NestedField>::read_nested($packet, $depth + 1)?)
+	};
+}
+
+macro_rules! data_components {
+	($($id:literal $name:literal => $(@$nested:ident)? $variant:ident $(($ty:ty))?),* $(,)?) => {
+		/// Компонент данных предмета вместе со значением
+		#[derive(Debug, Clone, PartialEq)]
+		pub enum DataComponent {
+			$($variant $(($ty))?),*
+		}
+
+		impl DataComponent {
+			/// Айди типа компонента в реестре minecraft:data_component_type
+			pub fn kind(&self) -> i32 {
+				match self {
+					$(DataComponent::$variant { .. } => $id),*
+				}
+			}
+
+			/// Имя типа компонента, например minecraft:custom_name
+			pub fn name(&self) -> &'static str {
+				Self::kind_name(self.kind()).unwrap()
+			}
+
+			pub fn kind_name(kind: i32) -> Option<&'static str> {
+				match kind {
+					$($id => Some($name),)*
+					_ => None,
+				}
+			}
+
+			pub fn kind_by_name(name: &str) -> Option<i32> {
+				match name {
+					$($name => Some($id),)*
+					_ => None,
+				}
+			}
+
+			/// Читает значение компонента, тип которого уже прочитан
+			///
+			/// depth - на какой глубине внутри других слотов и компонентов лежит этот
+			pub fn read_value(packet: &mut Packet, kind: i32, depth: usize) -> Result<Self, ServerError> {
+				if depth >= MAX_NESTING {
+					return Err(ServerError::WrongPacket);
+				}
+				match kind {
+					$($id => Ok(read_component!(packet, depth, $($nested)? $variant $(, $ty)?)),)*
+					_ => Err(ServerError::WrongPacket),
+				}
+			}
+
+			/// Пишет только значение, без айди типа
+			pub fn write_value(&self, packet: &mut Packet) -> Result<(), ServerError> {
+				$(write_component!(self, packet, $variant $(, $ty)?);)*
+				Ok(())
+			}
+		}
+	};
+}
+
+data_components! {
+	0 "minecraft:custom_data" => CustomData(DynNBT),
+	1 "minecraft:max_stack_size" => MaxStackSize(VarInt),
+	2 "minecraft:max_damage" => MaxDamage(VarInt),
+	3 "minecraft:damage" => Damage(VarInt),
+	4 "minecraft:unbreakable" => Unbreakable,
+	5 "minecraft:custom_name" => CustomName(TextComponent),
+	6 "minecraft:item_name" => ItemName(TextComponent),
+	7 "minecraft:item_model" => ItemModel(String),
+	8 "minecraft:lore" => Lore(Vec<TextComponent>),
+	9 "minecraft:rarity" => Rarity(VarInt),
+	10 "minecraft:enchantments" => Enchantments(Vec<Enchantment>),
+	11 "minecraft:can_place_on" => @nested CanPlaceOn(Vec<BlockPredicate>),
+	12 "minecraft:can_break" => @nested CanBreak(Vec<BlockPredicate>),
+	13 "minecraft:attribute_modifiers" => AttributeModifiers(Vec<AttributeModifier>),
+	14 "minecraft:custom_model_data" => CustomModelData(CustomModelData),
+	15 "minecraft:tooltip_display" => TooltipDisplay(TooltipDisplay),
+	16 "minecraft:repair_cost" => RepairCost(VarInt),
+	17 "minecraft:creative_slot_lock" => CreativeSlotLock,
+	18 "minecraft:enchantment_glint_override" => EnchantmentGlintOverride(bool),
+	19 "minecraft:intangible_projectile" => IntangibleProjectile(DynNBT),
+	20 "minecraft:food" => Food(Food),
+	21 "minecraft:consumable" => Consumable(Consumable),
+	22 "minecraft:use_remainder" => @nested UseRemainder(Box<Slot>),
+	23 "minecraft:use_cooldown" => UseCooldown(UseCooldown),
+	24 "minecraft:damage_resistant" => DamageResistant(String),
+	25 "minecraft:tool" => Tool(Tool),
+	26 "minecraft:weapon" => Weapon(Weapon),
+	27 "minecraft:enchantable" => Enchantable(VarInt),
+	28 "minecraft:equippable" => Equippable(Equippable),
+	29 "minecraft:repairable" => Repairable(IdSet),
+	30 "minecraft:glider" => Glider,
+	31 "minecraft:tooltip_style" => TooltipStyle(String),
+	32 "minecraft:death_protection" => DeathProtection(Vec<ConsumeEffect>),
+	33 "minecraft:blocks_attacks" => BlocksAttacks(BlocksAttacks),
+	34 "minecraft:stored_enchantments" => StoredEnchantments(Vec<Enchantment>),
+	35 "minecraft:dyed_color" => DyedColor(i32),
+	36 "minecraft:map_color" => MapColor(i32),
+	37 "minecraft:map_id" => MapId(VarInt),
+	38 "minecraft:map_decorations" => MapDecorations(DynNBT),
+	39 "minecraft:map_post_processing" => MapPostProcessing(VarInt),
+	40 "minecraft:charged_projectiles" => @nested ChargedProjectiles(Vec<Slot>),
+	41 "minecraft:bundle_contents" => @nested BundleContents(Vec<Slot>),
+	42 "minecraft:potion_contents" => PotionContents(PotionContents),
+	43 "minecraft:potion_duration_scale" => PotionDurationScale(f32),
+	44 "minecraft:suspicious_stew_effects" => SuspiciousStewEffects(Vec<StewEffect>),
+	45 "minecraft:writable_book_content" => WritableBookContent(Vec<FilterableString>),
+	46 "minecraft:written_book_content" => WrittenBookContent(WrittenBookContent),
+	47 "minecraft:trim" => Trim(Trim),
+	48 "minecraft:debug_stick_state" => DebugStickState(DynNBT),
+	49 "minecraft:entity_data" => EntityData(DynNBT),
+	50 "minecraft:bucket_entity_data" => BucketEntityData(DynNBT),
+	51 "minecraft:block_entity_data" => BlockEntityData(DynNBT),
+	52 "minecraft:instrument" => Instrument(EitherHolder<Holder<Instrument>>),
+	53 "minecraft:provides_trim_material" => ProvidesTrimMaterial(EitherHolder<Holder<TrimMaterial>>),
+	54 "minecraft:ominous_bottle_amplifier" => OminousBottleAmplifier(VarInt),
+	55 "minecraft:jukebox_playable" => JukeboxPlayable(EitherHolder<Holder<JukeboxSong>>),
+	56 "minecraft:provides_banner_patterns" => ProvidesBannerPatterns(String),
+	57 "minecraft:recipes" => Recipes(DynNBT),
+	58 "minecraft:lodestone_tracker" => LodestoneTracker(LodestoneTracker),
+	59 "minecraft:firework_explosion" => FireworkExplosion(FireworkExplosion),
+	60 "minecraft:fireworks" => Fireworks(Fireworks),
+	61 "minecraft:profile" => Profile(Profile),
+	62 "minecraft:note_block_sound" => NoteBlockSound(String),
+	63 "minecraft:banner_patterns" => BannerPatterns(Vec<BannerLayer>),
+	64 "minecraft:base_color" => BaseColor(VarInt),
+	65 "minecraft:pot_decorations" => PotDecorations(Vec<VarInt>),
+	66 "minecraft:container" => @nested Container(Vec<Slot>),
+	67 "minecraft:block_state" => BlockState(Vec<BlockStateProperty>),
+	68 "minecraft:bees" => Bees(Vec<Bee>),
+	69 "minecraft:lock" => Lock(DynNBT),
+	70 "minecraft:container_loot" => ContainerLoot(DynNBT),
+	71 "minecraft:break_sound" => BreakSound(Holder<SoundEvent>),
+	72 "minecraft:villager/variant" => VillagerVariant(VarInt),
+	73 "minecraft:wolf/variant" => WolfVariant(VarInt),
+	74 "minecraft:wolf/sound_variant" => WolfSoundVariant(VarInt),
+	75 "minecraft:wolf/collar" => WolfCollar(VarInt),
+	76 "minecraft:fox/variant" => FoxVariant(VarInt),
+	77 "minecraft:salmon/size" => SalmonSize(VarInt),
+	78 "minecraft:parrot/variant" => ParrotVariant(VarInt),
+	79 "minecraft:tropical_fish/pattern" => TropicalFishPattern(VarInt),
+	80 "minecraft:tropical_fish/base_color" => TropicalFishBaseColor(VarInt),
+	81 "minecraft:tropical_fish/pattern_color" => TropicalFishPatternColor(VarInt),
+	82 "minecraft:mooshroom/variant" => MooshroomVariant(VarInt),
+	83 "minecraft:rabbit/variant" => RabbitVariant(VarInt),
+	84 "minecraft:pig/variant" => PigVariant(VarInt),
+	85 "minecraft:cow/variant" => CowVariant(VarInt),
+	86 "minecraft:chicken/variant" => ChickenVariant(EitherHolder<VarInt>),
+	87 "minecraft:frog/variant" => FrogVariant(VarInt),
+	88 "minecraft:horse/variant" => HorseVariant(VarInt),
+	89 "minecraft:painting/variant" => PaintingVariant(Holder<PaintingVariant>),
+	90 "minecraft:llama/variant" => LlamaVariant(VarInt),
+	91 "minecraft:axolotl/variant" => AxolotlVariant(VarInt),
+	92 "minecraft:cat/variant" => CatVariant(VarInt),
+	93 "minecraft:cat/collar" => CatCollar(VarInt),
+	94 "minecraft:sheep/color" => SheepColor(VarInt),
+	95 "minecraft:shulker/color" => ShulkerColor(VarInt),
+}
+
+// В списках (например exact_components у BlockPredicate) компонент пишется вместе с айди типа
+impl PacketField for DataComponent {
+	fn read_field(packet: &mut Packet) -> Result<Self, ServerError> {
+		DataComponent::read_nested(packet, 0)
+	}
+
+	fn write_field(&self, packet: &mut Packet) -> Result<(), ServerError> {
+		packet.write_varint(self.kind())?;
+		self.write_value(packet)
+	}
+}
+
+impl NestedField for DataComponent {
+	fn read_nested(packet: &mut Packet, depth: usize) -> Result<Self, ServerError> {
+		let kind = packet.read_varint()?;
+		DataComponent::read_value(packet, kind, depth)
+	}
+}
+
+// Как packet_field!, только exact_components читаются с глубиной
+impl NestedField for BlockPredicate {
+	fn read_nested(packet: &mut Packet, depth: usize) -> Result<Self, ServerError> {
+		Ok(BlockPredicate {
+			blocks: PacketField::read_field(packet)?,
+			properties: PacketField::read_field(packet)?,
+			nbt: PacketField::read_field(packet)?,
+			exact_components: NestedField::read_nested(packet, depth)?,
+			partial_components: PacketField::read_field(packet)?,
+		})
+	}
+}
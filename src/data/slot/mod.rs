@@ -0,0 +1,369 @@
+use rust_mc_proto::{DataReader, DataWriter, Packet};
+
+use crate::{ServerError, protocol::packets::PacketField, world::item::ItemId};
+
+pub mod components;
+pub mod nbt;
+
+use components::DataComponent;
+
+// Слоты предметов
+//
+// Полный слот (Slot) передается с сервера и в Set Creative Mode Slot, в нем все значения компонентов
+// С 1.21.5 в Click Container клиент шлет хешированный слот (HashedSlot): вместо значений компонентов
+// только их CRC32C хеши, так клиенту не нужно уметь кодировать все компоненты обратно
+
+/// Стак предметов, пустой слот это count = 0
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Slot {
+	pub item_id: ItemId,
+	pub count: i32,
+	pub components: Vec<DataComponent>, // добавленные или измененные относительно дефолтных
+	pub removed_components: Vec<i32>,   // айди типов убранных дефолтных компонентов
+}
+
+impl Slot {
+	pub fn empty() -> Slot {
+		Slot::default()
+	}
+
+	pub fn new(item_id: ItemId, count: i32) -> Slot {
+		Slot {
+			item_id,
+			count,
+			components: Vec::new(),
+			removed_components: Vec::new(),
+		}
+	}
+
+	pub fn is_empty(&self) -> bool {
+		self.count <= 0
+	}
+
+	/// Можно ли сложить стаки вместе: тот же предмет с теми же компонентами
+	pub fn is_same_item(&self, other: &Slot) -> bool {
+		self.item_id == other.item_id
+			&& self.components == other.components
+			&& self.removed_components == other.removed_components
+	}
+
+	/// Размер стака, у предметов без max_stack_size считаем что 64, данных о дефолтных компонентах пока нет
+	pub fn max_stack_size(&self) -> i32 {
+		self
+			.components
+			.iter()
+			.find_map(|o| match o {
+				DataComponent::MaxStackSize(size) => Some(size.0),
+				_ => None,
+			})
+			.unwrap_or(64)
+	}
+
+	/// Копия стака с другим кол-вом
+	pub fn with_count(&self, count: i32) -> Slot {
+		Slot {
+			count,
+			..self.clone()
+		}
+	}
+
+	/// Отрезает от стака до count предметов
+	pub fn split(&mut self, count: i32) -> Slot {
+		let count = count.min(self.count).max(0);
+		let taken = self.with_count(count);
+		self.count -= count;
+		if self.is_empty() {
+			*self = Slot::empty();
+		}
+		taken
+	}
+
+	pub fn with_component(mut self, component: DataComponent) -> Slot {
+		self.set_component(component);
+		self
+	}
+
+	pub fn component(&self, kind: i32) -> Option<&DataComponent> {
+		self.components.iter().find(|o| o.kind() == kind)
+	}
+
+	/// Добавляет компонент, заменяя компонент того же типа
+	pub fn set_component(&mut self, component: DataComponent) {
+		let kind = component.kind();
+		self.removed_components.retain(|o| *o != kind);
+		match self.components.iter_mut().find(|o| o.kind() == kind) {
+			Some(old) => *old = component,
+			None => self.components.push(component),
+		}
+	}
+
+	/// Убирает компонент у стака, в том числе дефолтный компонент предмета
+	pub fn remove_component(&mut self, kind: i32) {
+		self.components.retain(|o| o.kind() != kind);
+		if !self.removed_components.contains(&kind) {
+			self.removed_components.push(kind);
+		}
+	}
+}
+
+pub trait ReadWriteSlot: DataReader + DataWriter {
+	fn read_slot(&mut self) -> Result<Slot, ServerError>;
+	/// Слот, вложенный в компоненты другого слота на глубине depth
+	fn read_nested_slot(&mut self, depth: usize) -> Result<Slot, ServerError>;
+	fn write_slot(&mut self, val: &Slot) -> Result<(), ServerError>;
+}
+
+/// Поле, внутри которого могут быть слоты, читается с глубиной вложенности (см. components::MAX_NESTING)
+pub trait NestedField: Sized {
+	fn read_nested(packet: &mut Packet, depth: usize) -> Result<Self, ServerError>;
+}
+
+impl NestedField for Slot {
+	fn read_nested(packet: &mut Packet, depth: usize) -> Result<Self, ServerError> {
+		packet.read_nested_slot(depth)
+	}
+}
+
+impl<T: NestedField> NestedField for Box<T> {
+	fn read_nested(packet: &mut Packet, depth: usize) -> Result<Self, ServerError> {
+		Ok(Box::new(T::read_nested(packet, depth)?))
+	}
+}
+
+impl<T: NestedField> NestedField for Vec<T> {
+	fn read_nested(packet: &mut Packet, depth: usize) -> Result<Self, ServerError> {
+		let count = packet.read_usize_varint()?;
+		let mut items = Vec::with_capacity(count.min(1024));
+		for _ in 0..count {
+			items.push(T::read_nested(packet, depth)?);
+		}
+		Ok(items)
+	}
+}
+
+impl ReadWriteSlot for Packet {
+	fn read_slot(&mut self) -> Result<Slot, ServerError> {
+		self.read_nested_slot(0)
+	}
+
+	fn read_nested_slot(&mut self, depth: usize) -> Result<Slot, ServerError> {
+		let count = self.read_varint()?;
+		if count <= 0 {
+			return Ok(Slot::empty());
+		}
+
+		let item_id = self.read_varint()?;
+		let added = self.read_usize_varint()?;
+		let removed = self.read_usize_varint()?;
+
+		let mut components = Vec::with_capacity(added.min(128));
+		for _ in 0..added {
+			components.push(DataComponent::read_nested(self, depth)?);
+		}
+
+		let mut removed_components = Vec::with_capacity(removed.min(128));
+		for _ in 0..removed {
+			removed_components.push(self.read_varint()?);
+		}
+
+		Ok(Slot {
+			item_id,
+			count,
+			components,
+			removed_components,
+		})
+	}
+
+	fn write_slot(&mut self, val: &Slot) -> Result<(), ServerError> {
+		if val.is_empty() {
+			return Ok(self.write_varint(0)?);
+		}
+
+		self.write_varint(val.count)?;
+		self.write_varint(val.item_id)?;
+		self.write_usize_varint(val.components.len())?;
+		self.write_usize_varint(val.removed_components.len())?;
+
+		for component in &val.components {
+			component.write_field(self)?;
+		}
+
+		for kind in &val.removed_components {
+			self.write_varint(*kind)?;
+		}
+
+		Ok(())
+	}
+}
+
+impl PacketField for Slot {
+	fn read_field(packet: &mut Packet) -> Result<Self, ServerError> {
+		packet.read_slot()
+	}
+
+	fn write_field(&self, packet: &mut Packet) -> Result<(), ServerError> {
+		packet.write_slot(self)
+	}
+}
+
+/// Слот из Click Container, у компонентов вместо значений хеши, пустой слот это count = 0
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct HashedSlot {
+	pub item_id: ItemId,
+	pub count: i32,
+	pub components: Vec<(i32, i32)>, // айди типа и CRC32C хеш значения
+	pub removed_components: Vec<i32>,
+}
+
+impl HashedSlot {
+	pub fn empty() -> HashedSlot {
+		HashedSlot::default()
+	}
+
+	pub fn is_empty(&self) -> bool {
+		self.count <= 0
+	}
+
+	/// Похож ли слот на настоящий: предмет, кол-во и набор типов компонентов
+	///
+	/// Хеши значений не сверяются, для этого нужно кодировать компоненты как это делает клиент,
+	/// так что изменение одного только значения компонента тут не заметить
+	pub fn matches(&self, slot: &Slot) -> bool {
+		if self.is_empty() || slot.is_empty() {
+			return self.is_empty() && slot.is_empty();
+		}
+
+		self.item_id == slot.item_id
+			&& self.count == slot.count
+			&& self.components.len() == slot.components.len()
+			&& self
+				.components
+				.iter()
+				.all(|(kind, _)| slot.component(*kind).is_some())
+			&& self.removed_components.len() == slot.removed_components.len()
+			&& self
+				.removed_components
+				.iter()
+				.all(|o| slot.removed_components.contains(o))
+	}
+}
+
+pub trait ReadWriteHashedSlot: DataReader + DataWriter {
+	fn read_hashed_slot(&mut self) -> Result<HashedSlot, ServerError>;
+	fn write_hashed_slot(&mut self, val: &HashedSlot) -> Result<(), ServerError>;
+}
+
+impl ReadWriteHashedSlot for Packet {
+	fn read_hashed_slot(&mut self) -> Result<HashedSlot, ServerError> {
+		if !self.read_boolean()? {
+			return Ok(HashedSlot::empty());
+		}
+
+		let item_id = self.read_varint()?;
+		let count = self.read_varint()?;
+
+		let added = self.read_usize_varint()?;
+		let mut components = Vec::with_capacity(added.min(128));
+		for _ in 0..added {
+			components.push((self.read_varint()?, self.read_int()?));
+		}
+
+		let removed = self.read_usize_varint()?;
+		let mut removed_components = Vec::with_capacity(removed.min(128));
+		for _ in 0..removed {
+			removed_components.push(self.read_varint()?);
+		}
+
+		Ok(HashedSlot {
+			item_id,
+			count,
+			components,
+			removed_components,
+		})
+	}
+
+	fn write_hashed_slot(&mut self, val: &HashedSlot) -> Result<(), ServerError> {
+		self.write_boolean(!val.is_empty())?;
+		if val.is_empty() {
+			return Ok(());
+		}
+
+		self.write_varint(val.item_id)?;
+		self.write_varint(val.count)?;
+
+		self.write_usize_varint(val.components.len())?;
+		for (kind, hash) in &val.components {
+			self.write_varint(*kind)?;
+			self.write_int(*hash)?;
+		}
+
+		self.write_usize_varint(val.removed_components.len())?;
+		for kind in &val.removed_components {
+			self.write_varint(*kind)?;
+		}
+
+		Ok(())
+	}
+}
+
+impl PacketField for HashedSlot {
+	fn read_field(packet: &mut Packet) -> Result<Self, ServerError> {
+		packet.read_hashed_slot()
+	}
+
+	fn write_field(&self, packet: &mut Packet) -> Result<(), ServerError> {
+		packet.write_hashed_slot(self)
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	// Слот, в котором depth раз вложен контейнер с одним слотом
+	fn nested(depth: usize) -> Slot {
+		let mut slot = Slot::new(1, 1);
+		for _ in 0..depth {
+			slot = Slot::new(1, 1).with_component(DataComponent::Container(vec![slot]));
+		}
+		slot
+	}
+
+	fn round_trip(slot: &Slot) -> Result<Slot, ServerError> {
+		let mut packet = Packet::empty(0);
+		packet.write_slot(slot)?;
+		Packet::from_bytes(0, packet.get_bytes()).read_slot()
+	}
+
+	#[test]
+	fn nested_slots_round_trip() {
+		let slot = nested(4).with_component(DataComponent::UseRemainder(Box::new(nested(2))));
+		assert_eq!(round_trip(&slot).unwrap(), slot);
+	}
+
+	#[test]
+	fn too_deep_slots_are_rejected() {
+		assert!(round_trip(&nested(components::MAX_NESTING)).is_ok());
+		assert!(matches!(
+			round_trip(&nested(components::MAX_NESTING + 1)),
+			Err(ServerError::WrongPacket)
+		));
+	}
+
+	#[test]
+	fn deep_block_predicates_are_rejected() {
+		// can_place_on -> exact_components -> can_place_on -> ... без слотов между ними
+		let mut component = DataComponent::Unbreakable;
+		for _ in 0..100 {
+			component = DataComponent::CanPlaceOn(vec![components::BlockPredicate {
+				blocks: None,
+				properties: None,
+				nbt: None,
+				exact_components: vec![component],
+				partial_components: Vec::new(),
+			}]);
+		}
+		let slot = Slot::new(1, 1).with_component(component);
+		assert!(round_trip(&slot).is_err());
+	}
+}
@@ -0,0 +1,1488 @@
+use std::collections::HashMap;
+
+use craftflow_nbt::DynNBT;
+use log::warn;
+use uuid::Uuid;
+
+use super::{
+	Slot,
+	components::{
+		BannerLayer, Bee, BlockStateProperty, CustomModelData, DataComponent, EitherHolder,
+		Enchantment, FilterableString, FilterableText, FireworkExplosion, Fireworks, Food,
+		GlobalPosition, Holder, IdSet, LodestoneTracker, MAX_NESTING, PotionContents, PotionEffect,
+		PotionEffectDetails, Profile, SoundEvent, StewEffect, TooltipDisplay, Trim, UseCooldown,
+		Weapon, WrittenBookContent,
+	},
+};
+use crate::{
+	data::component::TextComponent,
+	protocol::{
+		packets::{Position, VarInt},
+		session::ProfileProperty,
+	},
+	world::{item::ItemRegistry, registry::Registries},
+};
+
+// Предметы в NBT как у ванильного 1.21.5: {id, count, components}
+//
+// Компоненты пишутся ванильными кодеками, айди из реестров - именами, убранные дефолтные компоненты
+// пишутся как "!minecraft:имя": {}. Реестры, которых у сервера нет (атрибуты, звуки, блоки),
+// перевести не получится, поэтому компоненты с ними (tool, equippable, consumable, can_break и т.п.)
+// при сохранении теряются с предупреждением. Ванильные предметы хранят такие компоненты только если
+// их поменяли командой, дефолтные значения в NBT не пишутся
+
+const ENCHANTMENT: &str = "minecraft:enchantment";
+const TRIM_MATERIAL: &str = "minecraft:trim_material";
+const TRIM_PATTERN: &str = "minecraft:trim_pattern";
+const BANNER_PATTERN: &str = "minecraft:banner_pattern";
+const INSTRUMENT: &str = "minecraft:instrument";
+const JUKEBOX_SONG: &str = "minecraft:jukebox_song";
+const PAINTING_VARIANT: &str = "minecraft:painting_variant";
+const WOLF_VARIANT: &str = "minecraft:wolf_variant";
+const WOLF_SOUND_VARIANT: &str = "minecraft:wolf_sound_variant";
+const PIG_VARIANT: &str = "minecraft:pig_variant";
+const COW_VARIANT: &str = "minecraft:cow_variant";
+const CHICKEN_VARIANT: &str = "minecraft:chicken_variant";
+const FROG_VARIANT: &str = "minecraft:frog_variant";
+const CAT_VARIANT: &str = "minecraft:cat_variant";
+
+// Перечисления пишутся по имени, айди - номер в списке
+const DYE_COLORS: &[&str] = &[
+	"white",
+	"orange",
+	"magenta",
+	"light_blue",
+	"yellow",
+	"lime",
+	"pink",
+	"gray",
+	"light_gray",
+	"cyan",
+	"purple",
+	"blue",
+	"brown",
+	"green",
+	"red",
+	"black",
+];
+const RARITIES: &[&str] = &["common", "uncommon", "rare", "epic"];
+const FIREWORK_SHAPES: &[&str] = &["small_ball", "large_ball", "star", "creeper", "burst"];
+const FOX_VARIANTS: &[&str] = &["red", "snow"];
+const SALMON_SIZES: &[&str] = &["small", "medium", "large"];
+const PARROT_VARIANTS: &[&str] = &["red_blue", "blue", "green", "yellow_blue", "gray"];
+const MOOSHROOM_VARIANTS: &[&str] = &["red", "brown"];
+const HORSE_VARIANTS: &[&str] = &[
+	"white",
+	"creamy",
+	"chestnut",
+	"brown",
+	"black",
+	"gray",
+	"dark_brown",
+];
+const LLAMA_VARIANTS: &[&str] = &["creamy", "white", "brown", "gray"];
+const AXOLOTL_VARIANTS: &[&str] = &["lucy", "wild", "gold", "cyan", "blue"];
+
+// У этих перечислений айди идут не подряд
+const RABBIT_VARIANTS: &[(&str, i32)] = &[
+	("brown", 0),
+	("white", 1),
+	("black", 2),
+	("white_splotched", 3),
+	("gold", 4),
+	("salt", 5),
+	("evil", 99),
+];
+const TROPICAL_FISH_PATTERNS: &[(&str, i32)] = &[
+	("kob", 0),
+	("sunstreak", 256),
+	("snooper", 512),
+	("dasher", 768),
+	("brinely", 1024),
+	("spotty", 1280),
+	("flopper", 1),
+	("stripey", 257),
+	("glitter", 513),
+	("blockfish", 769),
+	("betty", 1025),
+	("clayfish", 1281),
+];
+
+// Несинхронизируемые реестры, клиенту они не уходят, поэтому в registries.json их нет
+const VILLAGER_TYPES: &[&str] = &[
+	"minecraft:desert",
+	"minecraft:jungle",
+	"minecraft:plains",
+	"minecraft:savanna",
+	"minecraft:snow",
+	"minecraft:swamp",
+	"minecraft:taiga",
+];
+const MOB_EFFECTS: &[&str] = &[
+	"minecraft:speed",
+	"minecraft:slowness",
+	"minecraft:haste",
+	"minecraft:mining_fatigue",
+	"minecraft:strength",
+	"minecraft:instant_health",
+	"minecraft:instant_damage",
+	"minecraft:jump_boost",
+	"minecraft:nausea",
+	"minecraft:regeneration",
+	"minecraft:resistance",
+	"minecraft:fire_resistance",
+	"minecraft:water_breathing",
+	"minecraft:invisibility",
+	"minecraft:blindness",
+	"minecraft:night_vision",
+	"minecraft:hunger",
+	"minecraft:weakness",
+	"minecraft:poison",
+	"minecraft:wither",
+	"minecraft:health_boost",
+	"minecraft:absorption",
+	"minecraft:saturation",
+	"minecraft:glowing",
+	"minecraft:levitation",
+	"minecraft:luck",
+	"minecraft:unluck",
+	"minecraft:slow_falling",
+	"minecraft:conduit_power",
+	"minecraft:dolphins_grace",
+	"minecraft:bad_omen",
+	"minecraft:hero_of_the_village",
+	"minecraft:darkness",
+	"minecraft:trial_omen",
+	"minecraft:raid_omen",
+	"minecraft:wind_charged",
+	"minecraft:weaving",
+	"minecraft:oozing",
+	"minecraft:infested",
+];
+const POTIONS: &[&str] = &[
+	"minecraft:water",
+	"minecraft:mundane",
+	"minecraft:thick",
+	"minecraft:awkward",
+	"minecraft:night_vision",
+	"minecraft:long_night_vision",
+	"minecraft:invisibility",
+	"minecraft:long_invisibility",
+	"minecraft:leaping",
+	"minecraft:long_leaping",
+	"minecraft:strong_leaping",
+	"minecraft:fire_resistance",
+	"minecraft:long_fire_resistance",
+	"minecraft:swiftness",
+	"minecraft:long_swiftness",
+	"minecraft:strong_swiftness",
+	"minecraft:slowness",
+	"minecraft:long_slowness",
+	"minecraft:strong_slowness",
+	"minecraft:turtle_master",
+	"minecraft:long_turtle_master",
+	"minecraft:strong_turtle_master",
+	"minecraft:water_breathing",
+	"minecraft:long_water_breathing",
+	"minecraft:healing",
+	"minecraft:strong_healing",
+	"minecraft:harming",
+	"minecraft:strong_harming",
+	"minecraft:poison",
+	"minecraft:long_poison",
+	"minecraft:strong_poison",
+	"minecraft:regeneration",
+	"minecraft:long_regeneration",
+	"minecraft:strong_regeneration",
+	"minecraft:strength",
+	"minecraft:long_strength",
+	"minecraft:strong_strength",
+	"minecraft:weakness",
+	"minecraft:long_weakness",
+	"minecraft:luck",
+	"minecraft:slow_falling",
+	"minecraft:long_slow_falling",
+	"minecraft:wind_charged",
+	"minecraft:weaving",
+	"minecraft:oozing",
+	"minecraft:infested",
+];
+
+/// Перевод стаков в NBT и обратно, нужны реестры, чтобы писать айди именами
+#[derive(Clone, Copy)]
+pub struct ItemNbt<'a> {
+	pub items: &'a ItemRegistry,
+	pub registries: &'a Registries,
+}
+
+impl<'a> ItemNbt<'a> {
+	pub fn new(items: &'a ItemRegistry, registries: &'a Registries) -> ItemNbt<'a> {
+		ItemNbt { items, registries }
+	}
+
+	/// Стак в виде {id, count, components}, None для пустого слота и неизвестного предмета
+	pub fn write_slot(&self, slot: &Slot) -> Option<HashMap<String, DynNBT>> {
+		if slot.is_empty() {
+			return None;
+		}
+		let Some(name) = self.items.name(slot.item_id) else {
+			warn!(
+				"Предмет {} не найден в реестре, он не сохранится",
+				slot.item_id
+			);
+			return None;
+		};
+
+		let mut components = HashMap::new();
+		for component in &slot.components {
+			// Эти компоненты только для клиента, ванильный сервер их тоже не сохраняет
+			if matches!(
+				component,
+				DataComponent::CreativeSlotLock | DataComponent::MapPostProcessing(_)
+			) {
+				continue;
+			}
+			match self.write_component(component) {
+				Some(value) => {
+					components.insert(component.name().to_string(), value);
+				}
+				None => warn!(
+					"Компонент {} у {name} не сохраняется в NBT, он пропадет",
+					component.name()
+				),
+			}
+		}
+		for kind in &slot.removed_components {
+			if let Some(kind) = DataComponent::kind_name(*kind) {
+				components.insert(format!("!{kind}"), unit());
+			}
+		}
+
+		let mut compound = HashMap::from([
+			("id".to_string(), string(name)),
+			("count".to_string(), DynNBT::Int(slot.count)),
+		]);
+		if !components.is_empty() {
+			compound.insert("components".to_string(), DynNBT::Compound(components));
+		}
+		Some(compound)
+	}
+
+	/// Стак из NBT, неизвестный предмет пропускается, непонятые компоненты теряются
+	pub fn read_slot(&self, nbt: &DynNBT) -> Option<Slot> {
+		self.read_nested_slot(nbt, 0)
+	}
+
+	fn read_nested_slot(&self, nbt: &DynNBT, depth: usize) -> Option<Slot> {
+		if depth >= MAX_NESTING {
+			return None;
+		}
+		let fields = Fields::of(nbt)?;
+
+		let name = full_name(&fields.string("id")?);
+		let Some(item_id) = self.items.id(&name) else {
+			warn!("Неизвестный предмет {name}, он пропадет");
+			return None;
+		};
+		// До 1.20.5 кол-во было в байтовом Count
+		let count = fields.int("count").or(fields.int("Count")).unwrap_or(1);
+
+		let mut slot = Slot::new(item_id, count);
+		let components = fields.get("components").and_then(|o| o.as_compound());
+		for (key, value) in components.into_iter().flatten() {
+			if let Some(removed) = key.strip_prefix('!') {
+				match DataComponent::kind_by_name(&full_name(removed)) {
+					Some(kind) => slot.remove_component(kind),
+					None => warn!("Неизвестный компонент {removed} у {name}"),
+				}
+				continue;
+			}
+			match self.read_component(&full_name(key), value, depth) {
+				Some(component) => slot.set_component(component),
+				None => warn!("Не удалось прочитать компонент {key} у {name}, он пропадет"),
+			}
+		}
+
+		// Порядок ключей в compound не сохраняется, а стаки сравниваются вместе с порядком компонентов
+		slot.components.sort_by_key(|o| o.kind());
+		slot.removed_components.sort();
+
+		Some(slot).filter(|o| !o.is_empty())
+	}
+
+	fn write_component(&self, component: &DataComponent) -> Option<DynNBT> {
+		use DataComponent as C;
+
+		Some(match component {
+			C::CustomData(value)
+			| C::IntangibleProjectile(value)
+			| C::MapDecorations(value)
+			| C::DebugStickState(value)
+			| C::EntityData(value)
+			| C::BucketEntityData(value)
+			| C::BlockEntityData(value)
+			| C::Recipes(value)
+			| C::Lock(value)
+			| C::ContainerLoot(value) => value.clone(),
+			C::MaxStackSize(value)
+			| C::MaxDamage(value)
+			| C::Damage(value)
+			| C::RepairCost(value)
+			| C::MapId(value)
+			| C::OminousBottleAmplifier(value) => DynNBT::Int(value.0),
+			C::DyedColor(value) | C::MapColor(value) => DynNBT::Int(*value),
+			C::PotionDurationScale(value) => DynNBT::Float(*value),
+			C::EnchantmentGlintOverride(value) => boolean(*value),
+			C::Unbreakable | C::Glider => unit(),
+			C::ItemModel(value) | C::TooltipStyle(value) | C::NoteBlockSound(value) => string(value),
+			C::CustomName(text) | C::ItemName(text) => text.to_nbt(),
+			C::Lore(lines) => DynNBT::List(lines.iter().map(|o| o.to_nbt()).collect()),
+			C::Rarity(value) => name_of(RARITIES, value.0)?,
+			C::Enchantments(list) | C::StoredEnchantments(list) => self.write_enchantments(list)?,
+			C::CustomModelData(data) => compound([
+				(
+					"floats",
+					Some(DynNBT::List(
+						data.floats.iter().map(|o| DynNBT::Float(*o)).collect(),
+					)),
+				),
+				(
+					"flags",
+					Some(DynNBT::List(
+						data.flags.iter().map(|o| boolean(*o)).collect(),
+					)),
+				),
+				(
+					"strings",
+					Some(DynNBT::List(
+						data.strings.iter().map(|o| string(o)).collect(),
+					)),
+				),
+				("colors", Some(DynNBT::IntArray(data.colors.clone()))),
+			]),
+			C::TooltipDisplay(display) => compound([
+				("hide_tooltip", Some(boolean(display.hide_tooltip))),
+				(
+					"hidden_components",
+					Some(DynNBT::List(
+						display
+							.hidden_components
+							.iter()
+							.map(|o| DataComponent::kind_name(o.0).map(string))
+							.collect::<Option<_>>()?,
+					)),
+				),
+			]),
+			C::Food(food) => compound([
+				("nutrition", Some(DynNBT::Int(food.nutrition.0))),
+				("saturation", Some(DynNBT::Float(food.saturation))),
+				("can_always_eat", Some(boolean(food.can_always_eat))),
+			]),
+			C::UseRemainder(slot) => DynNBT::Compound(self.write_slot(slot)?),
+			C::UseCooldown(cooldown) => compound([
+				("seconds", Some(DynNBT::Float(cooldown.seconds))),
+				(
+					"cooldown_group",
+					cooldown.cooldown_group.as_deref().map(string),
+				),
+			]),
+			C::DamageResistant(tag) => compound([("types", Some(string(&format!("#{tag}"))))]),
+			C::Weapon(weapon) => compound([
+				(
+					"item_damage_per_attack",
+					Some(DynNBT::Int(weapon.item_damage_per_attack.0)),
+				),
+				(
+					"disable_blocking_for_seconds",
+					Some(DynNBT::Float(weapon.disable_blocking_for_seconds)),
+				),
+			]),
+			C::Enchantable(value) => compound([("value", Some(DynNBT::Int(value.0)))]),
+			C::Repairable(items) => compound([("items", Some(self.write_item_set(items)?))]),
+			C::PotionContents(contents) => self.write_potion_contents(contents)?,
+			C::SuspiciousStewEffects(effects) => DynNBT::List(
+				effects
+					.iter()
+					.map(|o| {
+						Some(compound([
+							("id", Some(name_of(MOB_EFFECTS, o.kind.0)?)),
+							("duration", Some(DynNBT::Int(o.duration.0))),
+						]))
+					})
+					.collect::<Option<_>>()?,
+			),
+			C::WritableBookContent(pages) => compound([(
+				"pages",
+				Some(DynNBT::List(
+					pages.iter().map(write_filterable_string).collect(),
+				)),
+			)]),
+			C::WrittenBookContent(book) => compound([
+				("title", Some(write_filterable_string(&book.title))),
+				("author", Some(string(&book.author))),
+				("generation", Some(DynNBT::Int(book.generation.0))),
+				(
+					"pages",
+					Some(DynNBT::List(
+						book.pages.iter().map(write_filterable_text).collect(),
+					)),
+				),
+				("resolved", Some(boolean(book.resolved))),
+			]),
+			C::Trim(trim) => compound([
+				(
+					"material",
+					Some(self.write_holder(TRIM_MATERIAL, &trim.material)?),
+				),
+				(
+					"pattern",
+					Some(self.write_holder(TRIM_PATTERN, &trim.pattern)?),
+				),
+			]),
+			C::Instrument(value) => self.write_either(INSTRUMENT, value)?,
+			C::ProvidesTrimMaterial(value) => self.write_either(TRIM_MATERIAL, value)?,
+			C::JukeboxPlayable(value) => self.write_either(JUKEBOX_SONG, value)?,
+			C::ProvidesBannerPatterns(tag) => string(&format!("#{tag}")),
+			C::LodestoneTracker(tracker) => compound([
+				(
+					"target",
+					tracker.target.as_ref().map(|o| {
+						compound([
+							("dimension", Some(string(&o.dimension))),
+							(
+								"pos",
+								Some(DynNBT::IntArray(vec![
+									o.position.x as i32,
+									o.position.y as i32,
+									o.position.z as i32,
+								])),
+							),
+						])
+					}),
+				),
+				("tracked", Some(boolean(tracker.tracked))),
+			]),
+			C::FireworkExplosion(explosion) => write_firework_explosion(explosion)?,
+			C::Fireworks(fireworks) => compound([
+				(
+					"flight_duration",
+					Some(DynNBT::Byte(fireworks.flight_duration.0 as i8)),
+				),
+				(
+					"explosions",
+					Some(DynNBT::List(
+						fireworks
+							.explosions
+							.iter()
+							.map(write_firework_explosion)
+							.collect::<Option<_>>()?,
+					)),
+				),
+			]),
+			C::Profile(profile) => compound([
+				("name", profile.name.as_deref().map(string)),
+				("id", profile.uuid.as_ref().map(uuid)),
+				(
+					"properties",
+					Some(DynNBT::List(
+						profile
+							.properties
+							.iter()
+							.map(|o| {
+								compound([
+									("name", Some(string(&o.name))),
+									("value", Some(string(&o.value))),
+									("signature", o.signature.as_deref().map(string)),
+								])
+							})
+							.collect(),
+					)),
+				),
+			]),
+			C::BannerPatterns(layers) => DynNBT::List(
+				layers
+					.iter()
+					.map(|o| {
+						Some(compound([
+							(
+								"pattern",
+								Some(self.write_holder(BANNER_PATTERN, &o.pattern)?),
+							),
+							("color", Some(name_of(DYE_COLORS, o.color.0)?)),
+						]))
+					})
+					.collect::<Option<_>>()?,
+			),
+			C::BaseColor(color)
+			| C::WolfCollar(color)
+			| C::CatCollar(color)
+			| C::SheepColor(color)
+			| C::ShulkerColor(color)
+			| C::TropicalFishBaseColor(color)
+			| C::TropicalFishPatternColor(color) => name_of(DYE_COLORS, color.0)?,
+			C::PotDecorations(items) => DynNBT::List(
+				items
+					.iter()
+					.map(|o| self.items.name(o.0).map(string))
+					.collect::<Option<_>>()?,
+			),
+			C::Container(slots) => DynNBT::List(
+				slots
+					.iter()
+					.enumerate()
+					.filter(|(_, o)| !o.is_empty())
+					.map(|(index, o)| {
+						Some(compound([
+							("slot", Some(DynNBT::Int(index as i32))),
+							("item", Some(DynNBT::Compound(self.write_slot(o)?))),
+						]))
+					})
+					.collect::<Option<_>>()?,
+			),
+			C::ChargedProjectiles(slots) | C::BundleContents(slots) => DynNBT::List(
+				slots
+					.iter()
+					.filter(|o| !o.is_empty())
+					.map(|o| self.write_slot(o).map(DynNBT::Compound))
+					.collect::<Option<_>>()?,
+			),
+			C::BlockState(properties) => DynNBT::Compound(
+				properties
+					.iter()
+					.map(|o| (o.name.clone(), string(&o.value)))
+					.collect(),
+			),
+			C::Bees(bees) => DynNBT::List(
+				bees
+					.iter()
+					.map(|o| {
+						compound([
+							("entity_data", Some(o.entity_data.clone())),
+							("ticks_in_hive", Some(DynNBT::Int(o.ticks_in_hive.0))),
+							(
+								"min_ticks_in_hive",
+								Some(DynNBT::Int(o.min_ticks_in_hive.0)),
+							),
+						])
+					})
+					.collect(),
+			),
+			C::BreakSound(Holder::Direct(sound)) => compound([
+				("sound_id", Some(string(&sound.name))),
+				("range", sound.fixed_range.map(DynNBT::Float)),
+			]),
+			C::VillagerVariant(value) => name_of(VILLAGER_TYPES, value.0)?,
+			C::WolfVariant(value) => self.entry_name(WOLF_VARIANT, value.0)?,
+			C::WolfSoundVariant(value) => self.entry_name(WOLF_SOUND_VARIANT, value.0)?,
+			C::PigVariant(value) => self.entry_name(PIG_VARIANT, value.0)?,
+			C::CowVariant(value) => self.entry_name(COW_VARIANT, value.0)?,
+			C::FrogVariant(value) => self.entry_name(FROG_VARIANT, value.0)?,
+			C::CatVariant(value) => self.entry_name(CAT_VARIANT, value.0)?,
+			C::ChickenVariant(EitherHolder::Holder(value)) => {
+				self.entry_name(CHICKEN_VARIANT, value.0)?
+			}
+			C::ChickenVariant(EitherHolder::Key(key)) => string(key),
+			C::PaintingVariant(value) => self.write_holder(PAINTING_VARIANT, value)?,
+			C::FoxVariant(value) => name_of(FOX_VARIANTS, value.0)?,
+			C::SalmonSize(value) => name_of(SALMON_SIZES, value.0)?,
+			C::ParrotVariant(value) => name_of(PARROT_VARIANTS, value.0)?,
+			C::MooshroomVariant(value) => name_of(MOOSHROOM_VARIANTS, value.0)?,
+			C::HorseVariant(value) => name_of(HORSE_VARIANTS, value.0)?,
+			C::LlamaVariant(value) => name_of(LLAMA_VARIANTS, value.0)?,
+			C::AxolotlVariant(value) => name_of(AXOLOTL_VARIANTS, value.0)?,
+			C::RabbitVariant(value) => sparse_name_of(RABBIT_VARIANTS, value.0)?,
+			C::TropicalFishPattern(value) => sparse_name_of(TROPICAL_FISH_PATTERNS, value.0)?,
+			// Внутри айди из реестров, которых у сервера нет
+			C::CanPlaceOn(_)
+			| C::CanBreak(_)
+			| C::AttributeModifiers(_)
+			| C::Consumable(_)
+			| C::Tool(_)
+			| C::Equippable(_)
+			| C::DeathProtection(_)
+			| C::BlocksAttacks(_)
+			| C::BreakSound(Holder::Reference(_))
+			| C::CreativeSlotLock
+			| C::MapPostProcessing(_) => return None,
+		})
+	}
+
+	fn read_component(&self, name: &str, nbt: &DynNBT, depth: usize) -> Option<DataComponent> {
+		use DataComponent as C;
+
+		let text = |nbt: &DynNBT| TextComponent::from_nbt(nbt).ok();
+		let varint = |nbt: &DynNBT| int(nbt).map(VarInt);
+
+		Some(match name {
+			"minecraft:custom_data" => C::CustomData(nbt.clone()),
+			"minecraft:max_stack_size" => C::MaxStackSize(varint(nbt)?),
+			"minecraft:max_damage" => C::MaxDamage(varint(nbt)?),
+			"minecraft:damage" => C::Damage(varint(nbt)?),
+			"minecraft:unbreakable" => C::Unbreakable,
+			"minecraft:custom_name" => C::CustomName(text(nbt)?),
+			"minecraft:item_name" => C::ItemName(text(nbt)?),
+			"minecraft:item_model" => C::ItemModel(full_name(nbt.as_string()?)),
+			"minecraft:lore" => C::Lore(list(nbt, text)?),
+			"minecraft:rarity" => C::Rarity(VarInt(id_of(RARITIES, nbt)?)),
+			"minecraft:enchantments" => C::Enchantments(self.read_enchantments(nbt)?),
+			"minecraft:custom_model_data" => {
+				let fields = Fields::of(nbt)?;
+				C::CustomModelData(CustomModelData {
+					floats: fields.list("floats", float)?,
+					flags: fields.list("flags", boolean_of)?,
+					strings: fields.list("strings", |o| o.as_string().cloned())?,
+					colors: fields.list("colors", int)?,
+				})
+			}
+			"minecraft:tooltip_display" => {
+				let fields = Fields::of(nbt)?;
+				C::TooltipDisplay(TooltipDisplay {
+					hide_tooltip: fields.boolean("hide_tooltip").unwrap_or(false),
+					hidden_components: fields.list("hidden_components", |o| {
+						DataComponent::kind_by_name(&full_name(o.as_string()?)).map(VarInt)
+					})?,
+				})
+			}
+			"minecraft:repair_cost" => C::RepairCost(varint(nbt)?),
+			"minecraft:enchantment_glint_override" => C::EnchantmentGlintOverride(boolean_of(nbt)?),
+			"minecraft:intangible_projectile" => C::IntangibleProjectile(nbt.clone()),
+			"minecraft:food" => {
+				let fields = Fields::of(nbt)?;
+				C::Food(Food {
+					nutrition: VarInt(fields.int("nutrition")?),
+					saturation: fields.float("saturation")?,
+					can_always_eat: fields.boolean("can_always_eat").unwrap_or(false),
+				})
+			}
+			"minecraft:use_remainder" => {
+				C::UseRemainder(Box::new(self.read_nested_slot(nbt, depth + 1)?))
+			}
+			"minecraft:use_cooldown" => {
+				let fields = Fields::of(nbt)?;
+				C::UseCooldown(UseCooldown {
+					seconds: fields.float("seconds")?,
+					cooldown_group: fields.string("cooldown_group").map(|o| full_name(&o)),
+				})
+			}
+			"minecraft:damage_resistant" => {
+				let types = Fields::of(nbt)?.string("types")?;
+				C::DamageResistant(full_name(types.strip_prefix('#')?))
+			}
+			"minecraft:weapon" => {
+				let fields = Fields::of(nbt)?;
+				C::Weapon(Weapon {
+					item_damage_per_attack: VarInt(fields.int("item_damage_per_attack").unwrap_or(1)),
+					disable_blocking_for_seconds: fields.float("disable_blocking_for_seconds").unwrap_or(0.0),
+				})
+			}
+			"minecraft:enchantable" => C::Enchantable(VarInt(Fields::of(nbt)?.int("value")?)),
+			"minecraft:repairable" => C::Repairable(self.read_item_set(Fields::of(nbt)?.get("items")?)?),
+			"minecraft:glider" => C::Glider,
+			"minecraft:tooltip_style" => C::TooltipStyle(full_name(nbt.as_string()?)),
+			"minecraft:stored_enchantments" => C::StoredEnchantments(self.read_enchantments(nbt)?),
+			// До 1.21.5 цвет лежал в {rgb, show_in_tooltip}
+			"minecraft:dyed_color" => C::DyedColor(int(nbt).or_else(|| Fields::of(nbt)?.int("rgb"))?),
+			"minecraft:map_color" => C::MapColor(int(nbt)?),
+			"minecraft:map_id" => C::MapId(varint(nbt)?),
+			"minecraft:map_decorations" => C::MapDecorations(nbt.clone()),
+			"minecraft:charged_projectiles" => {
+				C::ChargedProjectiles(list(nbt, |o| self.read_nested_slot(o, depth + 1))?)
+			}
+			"minecraft:bundle_contents" => {
+				C::BundleContents(list(nbt, |o| self.read_nested_slot(o, depth + 1))?)
+			}
+			"minecraft:potion_contents" => C::PotionContents(read_potion_contents(nbt)?),
+			"minecraft:potion_duration_scale" => C::PotionDurationScale(float(nbt)?),
+			"minecraft:suspicious_stew_effects" => C::SuspiciousStewEffects(list(nbt, |o| {
+				let fields = Fields::of(o)?;
+				Some(StewEffect {
+					kind: VarInt(id_of(MOB_EFFECTS, fields.get("id")?)?),
+					duration: VarInt(fields.int("duration").unwrap_or(160)),
+				})
+			})?),
+			"minecraft:writable_book_content" => {
+				C::WritableBookContent(Fields::of(nbt)?.list("pages", read_filterable_string)?)
+			}
+			"minecraft:written_book_content" => {
+				let fields = Fields::of(nbt)?;
+				C::WrittenBookContent(WrittenBookContent {
+					title: read_filterable_string(fields.get("title")?)?,
+					author: fields.string("author")?,
+					generation: VarInt(fields.int("generation").unwrap_or(0)),
+					pages: fields.list("pages", read_filterable_text)?,
+					resolved: fields.boolean("resolved").unwrap_or(false),
+				})
+			}
+			"minecraft:trim" => {
+				let fields = Fields::of(nbt)?;
+				C::Trim(Trim {
+					material: self.read_holder(TRIM_MATERIAL, fields.get("material")?)?,
+					pattern: self.read_holder(TRIM_PATTERN, fields.get("pattern")?)?,
+				})
+			}
+			"minecraft:debug_stick_state" => C::DebugStickState(nbt.clone()),
+			"minecraft:entity_data" => C::EntityData(nbt.clone()),
+			"minecraft:bucket_entity_data" => C::BucketEntityData(nbt.clone()),
+			"minecraft:block_entity_data" => C::BlockEntityData(nbt.clone()),
+			"minecraft:instrument" => C::Instrument(self.read_either(INSTRUMENT, nbt)?),
+			"minecraft:provides_trim_material" => {
+				C::ProvidesTrimMaterial(self.read_either(TRIM_MATERIAL, nbt)?)
+			}
+			"minecraft:ominous_bottle_amplifier" => C::OminousBottleAmplifier(varint(nbt)?),
+			// До 1.21.5 песня лежала в {song, show_in_tooltip}
+			"minecraft:jukebox_playable" => {
+				let song = match nbt {
+					DynNBT::Compound(_) => Fields::of(nbt)?.get("song")?,
+					_ => nbt,
+				};
+				C::JukeboxPlayable(self.read_either(JUKEBOX_SONG, song)?)
+			}
+			"minecraft:provides_banner_patterns" => {
+				C::ProvidesBannerPatterns(full_name(nbt.as_string()?.strip_prefix('#')?))
+			}
+			"minecraft:recipes" => C::Recipes(nbt.clone()),
+			"minecraft:lodestone_tracker" => {
+				let fields = Fields::of(nbt)?;
+				let target = match fields.get("target") {
+					Some(target) => {
+						let target = Fields::of(target)?;
+						let pos = target.list("pos", int)?;
+						let [x, y, z] = pos[..] else {
+							return None;
+						};
+						Some(GlobalPosition {
+							dimension: full_name(&target.string("dimension")?),
+							position: Position {
+								x: x as i64,
+								y: y as i64,
+								z: z as i64,
+							},
+						})
+					}
+					None => None,
+				};
+				C::LodestoneTracker(LodestoneTracker {
+					target,
+					tracked: fields.boolean("tracked").unwrap_or(true),
+				})
+			}
+			"minecraft:firework_explosion" => C::FireworkExplosion(read_firework_explosion(nbt)?),
+			"minecraft:fireworks" => {
+				let fields = Fields::of(nbt)?;
+				C::Fireworks(Fireworks {
+					flight_duration: VarInt(fields.int("flight_duration").unwrap_or(0)),
+					explosions: fields.list("explosions", read_firework_explosion)?,
+				})
+			}
+			"minecraft:profile" => C::Profile(read_profile(nbt)?),
+			"minecraft:note_block_sound" => C::NoteBlockSound(full_name(nbt.as_string()?)),
+			"minecraft:banner_patterns" => C::BannerPatterns(list(nbt, |o| {
+				let fields = Fields::of(o)?;
+				Some(BannerLayer {
+					pattern: self.read_holder(BANNER_PATTERN, fields.get("pattern")?)?,
+					color: VarInt(id_of(DYE_COLORS, fields.get("color")?)?),
+				})
+			})?),
+			"minecraft:base_color" => C::BaseColor(VarInt(id_of(DYE_COLORS, nbt)?)),
+			"minecraft:pot_decorations" => C::PotDecorations(list(nbt, |o| {
+				self.items.id(&full_name(o.as_string()?)).map(VarInt)
+			})?),
+			"minecraft:container" => {
+				let mut slots = Vec::new();
+				for entry in nbt.as_list()? {
+					let fields = Fields::of(entry)?;
+					let index = usize::try_from(fields.int("slot")?).ok()?.min(255);
+					let slot = self.read_nested_slot(fields.get("item")?, depth + 1)?;
+					if slots.len() <= index {
+						slots.resize(index + 1, Slot::empty());
+					}
+					slots[index] = slot;
+				}
+				C::Container(slots)
+			}
+			"minecraft:block_state" => C::BlockState(
+				nbt
+					.as_compound()?
+					.iter()
+					.map(|(name, value)| {
+						Some(BlockStateProperty {
+							name: name.clone(),
+							value: value.as_string()?.clone(),
+						})
+					})
+					.collect::<Option<_>>()?,
+			),
+			"minecraft:bees" => C::Bees(list(nbt, |o| {
+				let fields = Fields::of(o)?;
+				Some(Bee {
+					entity_data: fields.get("entity_data").cloned().unwrap_or_else(unit),
+					ticks_in_hive: VarInt(fields.int("ticks_in_hive").unwrap_or(0)),
+					min_ticks_in_hive: VarInt(fields.int("min_ticks_in_hive").unwrap_or(0)),
+				})
+			})?),
+			"minecraft:lock" => C::Lock(nbt.clone()),
+			"minecraft:container_loot" => C::ContainerLoot(nbt.clone()),
+			"minecraft:break_sound" => {
+				let fields = Fields::of(nbt)?;
+				C::BreakSound(Holder::Direct(SoundEvent {
+					name: full_name(&fields.string("sound_id")?),
+					fixed_range: fields.float("range"),
+				}))
+			}
+			"minecraft:villager/variant" => C::VillagerVariant(VarInt(id_of(VILLAGER_TYPES, nbt)?)),
+			"minecraft:wolf/variant" => C::WolfVariant(self.entry_id(WOLF_VARIANT, nbt)?),
+			"minecraft:wolf/sound_variant" => {
+				C::WolfSoundVariant(self.entry_id(WOLF_SOUND_VARIANT, nbt)?)
+			}
+			"minecraft:wolf/collar" => C::WolfCollar(VarInt(id_of(DYE_COLORS, nbt)?)),
+			"minecraft:fox/variant" => C::FoxVariant(VarInt(id_of(FOX_VARIANTS, nbt)?)),
+			"minecraft:salmon/size" => C::SalmonSize(VarInt(id_of(SALMON_SIZES, nbt)?)),
+			"minecraft:parrot/variant" => C::ParrotVariant(VarInt(id_of(PARROT_VARIANTS, nbt)?)),
+			"minecraft:tropical_fish/pattern" => {
+				C::TropicalFishPattern(VarInt(sparse_id_of(TROPICAL_FISH_PATTERNS, nbt)?))
+			}
+			"minecraft:tropical_fish/base_color" => {
+				C::TropicalFishBaseColor(VarInt(id_of(DYE_COLORS, nbt)?))
+			}
+			"minecraft:tropical_fish/pattern_color" => {
+				C::TropicalFishPatternColor(VarInt(id_of(DYE_COLORS, nbt)?))
+			}
+			"minecraft:mooshroom/variant" => C::MooshroomVariant(VarInt(id_of(MOOSHROOM_VARIANTS, nbt)?)),
+			"minecraft:rabbit/variant" => C::RabbitVariant(VarInt(sparse_id_of(RABBIT_VARIANTS, nbt)?)),
+			"minecraft:pig/variant" => C::PigVariant(self.entry_id(PIG_VARIANT, nbt)?),
+			"minecraft:cow/variant" => C::CowVariant(self.entry_id(COW_VARIANT, nbt)?),
+			"minecraft:chicken/variant" => {
+				let key = full_name(nbt.as_string()?);
+				C::ChickenVariant(match self.registries.id(CHICKEN_VARIANT, &key) {
+					Some(id) => EitherHolder::Holder(VarInt(id)),
+					None => EitherHolder::Key(key),
+				})
+			}
+			"minecraft:frog/variant" => C::FrogVariant(self.entry_id(FROG_VARIANT, nbt)?),
+			"minecraft:horse/variant" => C::HorseVariant(VarInt(id_of(HORSE_VARIANTS, nbt)?)),
+			"minecraft:painting/variant" => C::PaintingVariant(self.read_holder(PAINTING_VARIANT, nbt)?),
+			"minecraft:llama/variant" => C::LlamaVariant(VarInt(id_of(LLAMA_VARIANTS, nbt)?)),
+			"minecraft:axolotl/variant" => C::AxolotlVariant(VarInt(id_of(AXOLOTL_VARIANTS, nbt)?)),
+			"minecraft:cat/variant" => C::CatVariant(self.entry_id(CAT_VARIANT, nbt)?),
+			"minecraft:cat/collar" => C::CatCollar(VarInt(id_of(DYE_COLORS, nbt)?)),
+			"minecraft:sheep/color" => C::SheepColor(VarInt(id_of(DYE_COLORS, nbt)?)),
+			"minecraft:shulker/color" => C::ShulkerColor(VarInt(id_of(DYE_COLORS, nbt)?)),
+			_ => return None,
+		})
+	}
+
+	// С 1.21.5 уровни лежат прямо в компоненте, раньше были в {levels, show_in_tooltip}
+	fn write_enchantments(&self, list: &[Enchantment]) -> Option<DynNBT> {
+		Some(DynNBT::Compound(
+			list
+				.iter()
+				.map(|o| {
+					let name = self.registries.name(ENCHANTMENT, o.kind.0)?;
+					Some((name.to_string(), DynNBT::Int(o.level.0)))
+				})
+				.collect::<Option<_>>()?,
+		))
+	}
+
+	fn read_enchantments(&self, nbt: &DynNBT) -> Option<Vec<Enchantment>> {
+		let map = nbt.as_compound()?;
+		let levels = match map.get("levels") {
+			Some(levels) => levels.as_compound()?,
+			None => map,
+		};
+
+		let mut list = levels
+			.iter()
+			.map(|(name, level)| {
+				Some(Enchantment {
+					kind: VarInt(self.registries.id(ENCHANTMENT, &full_name(name))?),
+					level: VarInt(int(level)?),
+				})
+			})
+			.collect::<Option<Vec<_>>>()?;
+		list.sort_by_key(|o| o.kind.0);
+		Some(list)
+	}
+
+	// Набор предметов: тег, один предмет или список
+	fn write_item_set(&self, set: &IdSet) -> Option<DynNBT> {
+		Some(match set {
+			IdSet::Tag(tag) => string(&format!("#{tag}")),
+			IdSet::Ids(ids) if ids.len() == 1 => string(self.items.name(ids[0])?),
+			IdSet::Ids(ids) => DynNBT::List(
+				ids
+					.iter()
+					.map(|o| self.items.name(*o).map(string))
+					.collect::<Option<_>>()?,
+			),
+		})
+	}
+
+	fn read_item_set(&self, nbt: &DynNBT) -> Option<IdSet> {
+		let item = |nbt: &DynNBT| self.items.id(&full_name(nbt.as_string()?));
+		Some(match nbt {
+			DynNBT::String(value) => match value.strip_prefix('#') {
+				Some(tag) => IdSet::Tag(full_name(tag)),
+				None => IdSet::Ids(vec![item(nbt)?]),
+			},
+			_ => IdSet::Ids(list(nbt, item)?),
+		})
+	}
+
+	fn write_potion_contents(&self, contents: &PotionContents) -> Option<DynNBT> {
+		Some(compound([
+			(
+				"potion",
+				match &contents.potion {
+					Some(potion) => Some(name_of(POTIONS, potion.0)?),
+					None => None,
+				},
+			),
+			("custom_color", contents.custom_color.map(DynNBT::Int)),
+			(
+				"custom_effects",
+				Some(DynNBT::List(
+					contents
+						.custom_effects
+						.iter()
+						.map(write_potion_effect)
+						.collect::<Option<_>>()?,
+				)),
+			),
+			("custom_name", contents.custom_name.as_deref().map(string)),
+		]))
+	}
+
+	fn entry_name(&self, registry: &str, id: i32) -> Option<DynNBT> {
+		self.registries.name(registry, id).map(string)
+	}
+
+	fn entry_id(&self, registry: &str, nbt: &DynNBT) -> Option<VarInt> {
+		self
+			.registries
+			.id(registry, &full_name(nbt.as_string()?))
+			.map(VarInt)
+	}
+
+	// Значение прямо в холдере пришлось бы писать кодеком записи реестра, сервер их не знает
+	fn write_holder<T>(&self, registry: &str, holder: &Holder<T>) -> Option<DynNBT> {
+		match holder {
+			Holder::Reference(id) => self.entry_name(registry, *id),
+			Holder::Direct(_) => None,
+		}
+	}
+
+	fn read_holder<T>(&self, registry: &str, nbt: &DynNBT) -> Option<Holder<T>> {
+		self.entry_id(registry, nbt).map(|o| Holder::Reference(o.0))
+	}
+
+	fn write_either<T>(&self, registry: &str, value: &EitherHolder<Holder<T>>) -> Option<DynNBT> {
+		match value {
+			EitherHolder::Holder(holder) => self.write_holder(registry, holder),
+			EitherHolder::Key(key) => Some(string(key)),
+		}
+	}
+
+	// Запись, которой нет в реестре сервера, остается ключом, клиент может знать ее из датапака
+	fn read_either<T>(&self, registry: &str, nbt: &DynNBT) -> Option<EitherHolder<Holder<T>>> {
+		let key = full_name(nbt.as_string()?);
+		Some(match self.registries.id(registry, &key) {
+			Some(id) => EitherHolder::Holder(Holder::Reference(id)),
+			None => EitherHolder::Key(key),
+		})
+	}
+}
+
+// Поля составного тега
+struct Fields<'b>(&'b HashMap<String, DynNBT>);
+
+impl<'b> Fields<'b> {
+	fn of(nbt: &'b DynNBT) -> Option<Fields<'b>> {
+		nbt.as_compound().map(Fields)
+	}
+
+	fn get(&self, key: &str) -> Option<&'b DynNBT> {
+		self.0.get(key)
+	}
+
+	fn int(&self, key: &str) -> Option<i32> {
+		self.get(key).and_then(int)
+	}
+
+	fn float(&self, key: &str) -> Option<f32> {
+		self.get(key).and_then(float)
+	}
+
+	fn boolean(&self, key: &str) -> Option<bool> {
+		self.get(key).and_then(boolean_of)
+	}
+
+	fn string(&self, key: &str) -> Option<String> {
+		self.get(key).and_then(|o| o.as_string()).cloned()
+	}
+
+	/// Список, без ключа - пустой, None если хоть один элемент не прочитался
+	fn list<T>(&self, key: &str, read: impl Fn(&DynNBT) -> Option<T>) -> Option<Vec<T>> {
+		match self.get(key) {
+			Some(nbt) => list(nbt, read),
+			None => Some(Vec::new()),
+		}
+	}
+}
+
+fn list<T>(nbt: &DynNBT, read: impl Fn(&DynNBT) -> Option<T>) -> Option<Vec<T>> {
+	match nbt {
+		DynNBT::List(values) => values.iter().map(read).collect(),
+		DynNBT::IntArray(values) => values.iter().map(|o| read(&DynNBT::Int(*o))).collect(),
+		DynNBT::ByteArray(values) => values
+			.iter()
+			.map(|o| read(&DynNBT::Byte(*o as i8)))
+			.collect(),
+		DynNBT::LongArray(values) => values.iter().map(|o| read(&DynNBT::Long(*o))).collect(),
+		_ => None,
+	}
+}
+
+// Составной тег, поля с None не пишутся
+fn compound<const N: usize>(fields: [(&str, Option<DynNBT>); N]) -> DynNBT {
+	DynNBT::Compound(
+		fields
+			.into_iter()
+			.filter_map(|(key, value)| Some((key.to_string(), value?)))
+			.collect(),
+	)
+}
+
+fn unit() -> DynNBT {
+	DynNBT::Compound(HashMap::new())
+}
+
+fn string(value: &str) -> DynNBT {
+	DynNBT::String(value.to_string())
+}
+
+fn boolean(value: bool) -> DynNBT {
+	DynNBT::Byte(value as i8)
+}
+
+fn int(nbt: &DynNBT) -> Option<i32> {
+	match nbt {
+		DynNBT::Byte(value) => Some(*value as i32),
+		DynNBT::Short(value) => Some(*value as i32),
+		DynNBT::Int(value) => Some(*value),
+		DynNBT::Long(value) => i32::try_from(*value).ok(),
+		_ => None,
+	}
+}
+
+fn float(nbt: &DynNBT) -> Option<f32> {
+	match nbt {
+		DynNBT::Float(value) => Some(*value),
+		DynNBT::Double(value) => Some(*value as f32),
+		_ => int(nbt).map(|o| o as f32),
+	}
+}
+
+fn boolean_of(nbt: &DynNBT) -> Option<bool> {
+	int(nbt).map(|o| o != 0)
+}
+
+fn uuid(uuid: &Uuid) -> DynNBT {
+	DynNBT::IntArray(
+		uuid
+			.as_bytes()
+			.chunks(4)
+			.map(|o| i32::from_be_bytes([o[0], o[1], o[2], o[3]]))
+			.collect(),
+	)
+}
+
+fn read_uuid(nbt: &DynNBT) -> Option<Uuid> {
+	if let DynNBT::String(text) = nbt {
+		return Uuid::parse_str(text).ok();
+	}
+	let ints = list(nbt, int)?;
+	if ints.len() != 4 {
+		return None;
+	}
+	let mut bytes = [0; 16];
+	for (i, value) in ints.iter().enumerate() {
+		bytes[i * 4..i * 4 + 4].copy_from_slice(&value.to_be_bytes());
+	}
+	Some(Uuid::from_bytes(bytes))
+}
+
+// Имена без пространства имен считаются ванильными, как и в командах
+fn full_name(name: &str) -> String {
+	match name.contains(':') {
+		true => name.to_string(),
+		false => format!("minecraft:{name}"),
+	}
+}
+
+fn name_of(names: &[&str], id: i32) -> Option<DynNBT> {
+	names.get(usize::try_from(id).ok()?).map(|o| string(o))
+}
+
+fn id_of(names: &[&str], nbt: &DynNBT) -> Option<i32> {
+	let name = nbt.as_string()?;
+	let full = full_name(name);
+	names
+		.iter()
+		.position(|o| *o == name || *o == full)
+		.map(|o| o as i32)
+}
+
+fn sparse_name_of(names: &[(&str, i32)], id: i32) -> Option<DynNBT> {
+	names
+		.iter()
+		.find(|(_, o)| *o == id)
+		.map(|(name, _)| string(name))
+}
+
+fn sparse_id_of(names: &[(&str, i32)], nbt: &DynNBT) -> Option<i32> {
+	let name = nbt.as_string()?;
+	names.iter().find(|(o, _)| o == name).map(|(_, id)| *id)
+}
+
+// Фильтруемые значения книг: {raw, filtered} или просто значение
+fn write_filterable_string(value: &FilterableString) -> DynNBT {
+	compound([
+		("raw", Some(string(&value.raw))),
+		("filtered", value.filtered.as_deref().map(string)),
+	])
+}
+
+fn read_filterable_string(nbt: &DynNBT) -> Option<FilterableString> {
+	if let DynNBT::String(raw) = nbt {
+		return Some(FilterableString {
+			raw: raw.clone(),
+			filtered: None,
+		});
+	}
+	let fields = Fields::of(nbt)?;
+	Some(FilterableString {
+		raw: fields.string("raw")?,
+		filtered: fields.string("filtered"),
+	})
+}
+
+fn write_filterable_text(value: &FilterableText) -> DynNBT {
+	compound([
+		("raw", Some(value.raw.to_nbt())),
+		("filtered", value.filtered.as_ref().map(|o| o.to_nbt())),
+	])
+}
+
+fn read_filterable_text(nbt: &DynNBT) -> Option<FilterableText> {
+	if let Some(fields) = Fields::of(nbt)
+		&& let Some(raw) = fields.get("raw")
+	{
+		return Some(FilterableText {
+			raw: TextComponent::from_nbt(raw).ok()?,
+			filtered: match fields.get("filtered") {
+				Some(filtered) => Some(TextComponent::from_nbt(filtered).ok()?),
+				None => None,
+			},
+		});
+	}
+	Some(FilterableText {
+		raw: TextComponent::from_nbt(nbt).ok()?,
+		filtered: None,
+	})
+}
+
+fn write_firework_explosion(explosion: &FireworkExplosion) -> Option<DynNBT> {
+	Some(compound([
+		("shape", Some(name_of(FIREWORK_SHAPES, explosion.shape.0)?)),
+		("colors", Some(DynNBT::IntArray(explosion.colors.clone()))),
+		(
+			"fade_colors",
+			Some(DynNBT::IntArray(explosion.fade_colors.clone())),
+		),
+		("has_trail", Some(boolean(explosion.trail))),
+		("has_twinkle", Some(boolean(explosion.twinkle))),
+	]))
+}
+
+fn read_firework_explosion(nbt: &DynNBT) -> Option<FireworkExplosion> {
+	let fields = Fields::of(nbt)?;
+	Some(FireworkExplosion {
+		shape: VarInt(id_of(FIREWORK_SHAPES, fields.get("shape")?)?),
+		colors: fields.list("colors", int)?,
+		fade_colors: fields.list("fade_colors", int)?,
+		trail: fields.boolean("has_trail").unwrap_or(false),
+		twinkle: fields.boolean("has_twinkle").unwrap_or(false),
+	})
+}
+
+// Профиль головы: просто ник или {name, id, properties}
+fn read_profile(nbt: &DynNBT) -> Option<Profile> {
+	if let DynNBT::String(name) = nbt {
+		return Some(Profile {
+			name: Some(name.clone()),
+			uuid: None,
+			properties: Vec::new(),
+		});
+	}
+	let fields = Fields::of(nbt)?;
+	Some(Profile {
+		name: fields.string("name"),
+		uuid: match fields.get("id") {
+			Some(id) => Some(read_uuid(id)?),
+			None => None,
+		},
+		properties: fields.list("properties", |o| {
+			let fields = Fields::of(o)?;
+			Some(ProfileProperty {
+				name: fields.string("name")?,
+				value: fields.string("value")?,
+				signature: fields.string("signature"),
+			})
+		})?,
+	})
+}
+
+// Эффект это детали вместе с id, у скрытого эффекта id нет
+fn write_potion_effect(effect: &PotionEffect) -> Option<DynNBT> {
+	let mut details = write_effect_details(&effect.details);
+	if let DynNBT::Compound(map) = &mut details {
+		map.insert("id".to_string(), name_of(MOB_EFFECTS, effect.kind.0)?);
+	}
+	Some(details)
+}
+
+fn write_effect_details(details: &PotionEffectDetails) -> DynNBT {
+	compound([
+		("amplifier", Some(DynNBT::Byte(details.amplifier.0 as i8))),
+		("duration", Some(DynNBT::Int(details.duration.0))),
+		("ambient", Some(boolean(details.ambient))),
+		("show_particles", Some(boolean(details.show_particles))),
+		("show_icon", Some(boolean(details.show_icon))),
+		(
+			"hidden_effect",
+			details.hidden_effect.as_deref().map(write_effect_details),
+		),
+	])
+}
+
+fn read_potion_effect(nbt: &DynNBT) -> Option<PotionEffect> {
+	Some(PotionEffect {
+		kind: VarInt(id_of(MOB_EFFECTS, Fields::of(nbt)?.get("id")?)?),
+		details: read_effect_details(nbt)?,
+	})
+}
+
+fn read_effect_details(nbt: &DynNBT) -> Option<PotionEffectDetails> {
+	let fields = Fields::of(nbt)?;
+	let show_particles = fields.boolean("show_particles").unwrap_or(true);
+	Some(PotionEffectDetails {
+		amplifier: VarInt(fields.int("amplifier").unwrap_or(0)),
+		duration: VarInt(fields.int("duration").unwrap_or(0)),
+		ambient: fields.boolean("ambient").unwrap_or(false),
+		show_particles,
+		show_icon: fields.boolean("show_icon").unwrap_or(show_particles),
+		hidden_effect: match fields.get("hidden_effect") {
+			Some(hidden) => Some(Box::new(read_effect_details(hidden)?)),
+			None => None,
+		},
+	})
+}
+
+// Зелье: просто имя зелья или {potion, custom_color, custom_effects, custom_name}
+fn read_potion_contents(nbt: &DynNBT) -> Option<PotionContents> {
+	if let DynNBT::String(_) = nbt {
+		return Some(PotionContents {
+			potion: Some(VarInt(id_of(POTIONS, nbt)?)),
+			custom_color: None,
+			custom_effects: Vec::new(),
+			custom_name: None,
+		});
+	}
+	let fields = Fields::of(nbt)?;
+	Some(PotionContents {
+		potion: match fields.get("potion") {
+			Some(potion) => Some(VarInt(id_of(POTIONS, potion)?)),
+			None => None,
+		},
+		custom_color: fields.int("custom_color"),
+		custom_effects: fields.list("custom_effects", read_potion_effect)?,
+		custom_name: fields.string("custom_name"),
+	})
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::data::slot::components::{Equippable, Tool};
+
+	fn items() -> ItemRegistry {
+		let mut items = ItemRegistry::new();
+		items.register("minecraft:diamond_sword", 900);
+		items.register("minecraft:shulker_box", 901);
+		items.register("minecraft:potion", 902);
+		items
+	}
+
+	fn round_trip(slot: &Slot) -> Option<Slot> {
+		let (items, registries) = (items(), Registries::vanilla());
+		let codec = ItemNbt::new(&items, &registries);
+		codec.read_slot(&DynNBT::Compound(codec.write_slot(slot)?))
+	}
+
+	fn sorted(mut slot: Slot) -> Slot {
+		slot.components.sort_by_key(|o| o.kind());
+		slot
+	}
+
+	#[test]
+	fn components_round_trip() {
+		let registries = Registries::vanilla();
+		let sharpness = registries.id(ENCHANTMENT, "minecraft:sharpness").unwrap();
+
+		let slot = Slot::new(900, 1)
+			.with_component(DataComponent::Damage(VarInt(12)))
+			.with_component(DataComponent::CustomName(TextComponent::new(
+				"Меч".to_string(),
+			)))
+			.with_component(DataComponent::Lore(vec![TextComponent::new(
+				"a".to_string(),
+			)]))
+			.with_component(DataComponent::Rarity(VarInt(3)))
+			.with_component(DataComponent::Enchantments(vec![Enchantment {
+				kind: VarInt(sharpness),
+				level: VarInt(5),
+			}]))
+			.with_component(DataComponent::Unbreakable)
+			.with_component(DataComponent::DyedColor(0x123456))
+			.with_component(DataComponent::CustomData(DynNBT::Compound(HashMap::from(
+				[("plugin".to_string(), DynNBT::Long(7))],
+			))))
+			.with_component(DataComponent::PotionContents(PotionContents {
+				potion: Some(VarInt(13)),
+				custom_color: Some(0xff0000),
+				custom_effects: vec![PotionEffect {
+					kind: VarInt(1),
+					details: PotionEffectDetails {
+						amplifier: VarInt(2),
+						duration: VarInt(200),
+						ambient: false,
+						show_particles: true,
+						show_icon: true,
+						hidden_effect: None,
+					},
+				}],
+				custom_name: None,
+			}))
+			.with_component(DataComponent::RabbitVariant(VarInt(99)))
+			.with_component(DataComponent::Container(vec![
+				Slot::empty(),
+				Slot::new(1, 64),
+			]));
+
+		assert_eq!(round_trip(&slot), Some(sorted(slot)));
+	}
+
+	#[test]
+	fn removed_components_round_trip() {
+		let mut slot = Slot::new(900, 1);
+		slot.remove_component(DataComponent::Unbreakable.kind());
+		let nbt = {
+			let (items, registries) = (items(), Registries::vanilla());
+			ItemNbt::new(&items, &registries).write_slot(&slot).unwrap()
+		};
+
+		let components = nbt["components"].as_compound().unwrap();
+		assert!(components.contains_key("!minecraft:unbreakable"));
+		assert_eq!(round_trip(&slot), Some(slot));
+	}
+
+	#[test]
+	fn reads_vanilla_items() {
+		let (items, registries) = (items(), Registries::vanilla());
+		let codec = ItemNbt::new(&items, &registries);
+
+		let nbt = compound([
+			("id", Some(string("diamond_sword"))),
+			("count", Some(DynNBT::Byte(1))),
+			(
+				"components",
+				Some(compound([
+					("minecraft:damage", Some(DynNBT::Int(5))),
+					("custom_name", Some(string("Меч"))),
+					(
+						"minecraft:enchantments",
+						Some(compound([(
+							"levels",
+							Some(compound([("minecraft:sharpness", Some(DynNBT::Int(3)))])),
+						)])),
+					),
+					(
+						"minecraft:potion_contents",
+						Some(string("minecraft:swiftness")),
+					),
+					("minecraft:unknown", Some(unit())),
+				])),
+			),
+		]);
+
+		let slot = codec.read_slot(&nbt).unwrap();
+		assert_eq!(slot.item_id, 900);
+		assert_eq!(slot.count, 1);
+		assert_eq!(
+			slot.component(DataComponent::Damage(VarInt(0)).kind()),
+			Some(&DataComponent::Damage(VarInt(5)))
+		);
+		assert!(matches!(
+			slot.component(DataComponent::Enchantments(Vec::new()).kind()),
+			Some(DataComponent::Enchantments(list)) if list[0].level.0 == 3
+		));
+		assert!(matches!(
+			slot.component(DataComponent::kind_by_name("minecraft:custom_name").unwrap()),
+			Some(DataComponent::CustomName(_))
+		));
+		assert!(matches!(
+			slot.component(42),
+			Some(DataComponent::PotionContents(PotionContents {
+				potion: Some(VarInt(13)),
+				..
+			}))
+		));
+		assert_eq!(slot.components.len(), 4);
+	}
+
+	#[test]
+	fn unsupported_components_are_dropped() {
+		let slot = Slot::new(900, 1)
+			.with_component(DataComponent::Damage(VarInt(1)))
+			.with_component(DataComponent::CreativeSlotLock)
+			.with_component(DataComponent::Tool(Tool {
+				rules: Vec::new(),
+				default_mining_speed: 1.0,
+				damage_per_block: VarInt(1),
+				can_destroy_blocks_in_creative: true,
+			}))
+			.with_component(DataComponent::Equippable(Equippable {
+				slot: VarInt(5),
+				equip_sound: Holder::Reference(0),
+				asset_id: None,
+				camera_overlay: None,
+				allowed_entities: None,
+				dispensable: true,
+				swappable: true,
+				damage_on_hurt: true,
+				equip_on_interact: false,
+			}));
+
+		assert_eq!(
+			round_trip(&slot),
+			Some(Slot::new(900, 1).with_component(DataComponent::Damage(VarInt(1))))
+		);
+	}
+
+	#[test]
+	fn unknown_items_are_skipped() {
+		assert_eq!(round_trip(&Slot::new(12345, 1)), None);
+		assert_eq!(round_trip(&Slot::empty()), None);
+	}
+}
@@ -1,7 +1,17 @@
 use rust_mc_proto::Packet;
 
-use super::{ServerError, player::context::ClientContext, protocol::ConnectionState};
-use std::sync::Arc;
+use super::{
+	ServerError,
+	context::ServerContext,
+	data::component::TextComponent,
+	player::{
+		context::ClientContext,
+		inventory::{ClickMode, Container},
+	},
+	protocol::{ConnectionState, status::StatusResponse},
+	world::chunk::BlockState,
+};
+use std::sync::{Arc, Mutex};
 
 #[macro_export]
 macro_rules! generate_handlers {
@@ -20,7 +30,9 @@ macro_rules! generate_handlers {
 
 /// Пример использования:
 ///
-///     trigger_event!(client, status, &mut response, state);
+/// ```ignore
+/// trigger_event!(client, status, &mut response, state);
+/// ```
 #[macro_export]
 macro_rules! trigger_event {
     ($client:ident, $event:ident $(, $arg_ty:expr)* $(,)?) => {{
@@ -55,9 +67,35 @@ macro_rules! trigger_event_ignore {
 }
 
 pub trait Listener: Sync + Send {
-	generate_handlers!(status, &mut String);
+	generate_handlers!(status, &mut StatusResponse);
 	generate_handlers!(plugin_message, &str, &[u8]);
 	generate_handlers!(disconnect);
+	// Координаты блока абсолютные, выставленный cancel отменяет действие и возвращает блок клиенту
+	generate_handlers!(block_break, (i32, i32, i32), &BlockState, &mut bool);
+	generate_handlers!(block_place, (i32, i32, i32), &BlockState, &mut bool);
+	// Клик в окне до того как сервер его применит, контейнер None если открыт инвентарь игрока
+	// Выставленный cancel отменяет клик и возвращает клиенту содержимое окна, так делаются меню
+	generate_handlers!(
+		container_click,
+		Option<Arc<Mutex<Container>>>,
+		i16,
+		i8,
+		ClickMode,
+		&mut bool
+	);
+	generate_handlers!(container_close, Arc<Mutex<Container>>);
+	// Команда без слеша до разбора, выставленный cancel не дает ее выполнить
+	generate_handlers!(command, &str, &mut bool);
+
+	// Сервер останавливается, игроки еще не кикнуты, причину кика можно поменять
+	// Клиента у события нет, поэтому оно без generate_handlers!
+	fn on_shutdown_priority(&self) -> i8 {
+		0
+	}
+
+	fn on_shutdown(&self, _: Arc<ServerContext>, _: &mut TextComponent) -> Result<(), ServerError> {
+		Ok(())
+	}
 }
 
 pub trait PacketHandler: Sync + Send {
@@ -1,32 +1,60 @@
-use std::{error::Error, fmt::Display, net::TcpListener, sync::Arc, thread, time::Duration};
+use std::{
+	error::Error,
+	fmt::Display,
+	sync::{
+		Arc,
+		atomic::{AtomicBool, Ordering},
+	},
+	thread::{self, JoinHandle},
+	time::Duration,
+};
 
 use context::ServerContext;
-use ignore_result::Ignore;
-use log::{error, info};
+use data::component::TextComponent;
+use log::{error, info, warn};
+use network::{Reactor, ReactorHandle};
+use play::planner::{TICKS_PER_SECOND, run_ticks};
 use player::context::ClientContext;
-use protocol::handler::handle_connection;
-use rust_mc_proto::{MinecraftConnection, ProtocolError};
+use protocol::{forwarding::ForwardingMode, handler::run_connection};
+use rust_mc_proto::ProtocolError;
 
+pub mod command;
 pub mod config;
 pub mod context;
 pub mod data;
 pub mod event;
+pub mod network;
 pub mod play;
 pub mod player;
 pub mod protocol;
+pub mod world;
 
 // Ошибки сервера
 #[derive(Debug)]
 pub enum ServerError {
-	UnexpectedPacket(u8),    // Неожиданный пакет
-	WrongPacket,             // Пакет поломан, неверные данные
-	Protocol(ProtocolError), // Ошибка в протоколе при работе с rust_mc_proto
+	UnexpectedPacket(u8),     // Неожиданный пакет
+	WrongPacket,              // Пакет поломан, неверные данные
+	Protocol(ProtocolError),  // Ошибка в протоколе при работе с rust_mc_proto
 	ConnectionClosed, // Соединение закрыто, единственная ошибка которая не логируется у handle_connection
 	SerTextComponent, // Ошибка при сериализации текст-компонента
 	DeTextComponent,  // Ошибка при десериализации текст-компонента
 	SerNbt,           // Ошибка при сериализации nbt
 	DeNbt,            // Ошибка при десериализации nbt
 	UnexpectedState, // Указывает на то что этот пакет не может быть отправлен в данном режиме (в основном через ProtocolHelper)
+	Encryption,      // Ошибка при генерации ключей, расшифровке shared secret или включении шифрования
+	SessionServer(String), // Сессионный сервер не ответил или ответил какую-то дичь
+	NotAuthenticated, // Игрок не прошел проверку через сессионный сервер
+	Storage(String), // Ошибка при чтении или записи мира на диск
+	UnknownGenerator(String), // В конфиге указан генератор мира, который никто не зарегистрировал
+	UnknownDimension(String), // Тип измерения, которого нет в реестре
+	InvalidDimension(String), // Тип измерения с высотой, которую не примет клиент
+	UnknownWorld(String), // Мир с таким именем не загружен
+	WorldExists(String), // Мир с таким именем уже загружен
+	MainWorld,       // Основной мир нельзя выгрузить
+	Datapack(String), // Датапак не читается: нет pack.mcmeta, битый zip или json
+	ProxyProtocol(String), // Нет или битый заголовок PROXY protocol, либо неверная подсеть в конфиге
+	Forwarding(String), // Прокси не переслал данные игрока или подпись Velocity не сошлась
+	Command(String), // Команда не разобралась или не выполнилась, текст показывается игроку
 	Other(String), // Другая ошибка, либо очень специфичная, либо хз, лучше не использовать и создавать новое поле ошибки
 }
 
@@ -50,60 +78,201 @@ impl From<ProtocolError> for ServerError {
 	}
 }
 
-pub fn start_server(server: Arc<ServerContext>) {
+// Сколько ждать, пока кикнутые при остановке игроки сохранятся и отключатся
+const SHUTDOWN_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Запускает сервер в фоновых потоках, None если не удалось загрузить миры или забиндиться
+///
+/// Остановить сервер можно через возвращенный ServerHandle
+pub fn start_server(server: Arc<ServerContext>) -> Option<ServerHandle> {
+	// Миры грузятся здесь, а не в ServerContext::new, чтобы плагины успели добавить генераторы
+	if let Err(error) = server.world.load_worlds(&server.config.world) {
+		error!("Не удалось загрузить миры: {error:?}");
+		return None;
+	}
+
+	// С пустым секретом подпись Velocity подделает кто угодно
+	if server.config.server.forwarding == ForwardingMode::Velocity
+		&& server.config.server.forwarding_secret.is_empty()
+	{
+		error!("Включен форвардинг Velocity, но forwarding_secret пустой");
+		return None;
+	}
+
 	// Биндим сервер где надо
-	let Ok(listener) = TcpListener::bind(&server.config.bind.host) else {
-		error!(
-			"Не удалось забиндить сервер на {}",
-			&server.config.bind.host
-		);
-		return;
+	// Таймаут - через сколько секунд молчания клиента его отключать
+	let reactor = match Reactor::bind(
+		&server.config.bind.host,
+		Duration::from_secs(server.config.bind.timeout),
+	) {
+		Ok(reactor) => reactor,
+		Err(error) => {
+			error!(
+				"Не удалось забиндить сервер на {}: {error}",
+				&server.config.bind.host
+			);
+			return None;
+		}
+	};
+
+	// Настоящие адреса клиентов за балансировщиком
+	let reactor = if server.config.bind.proxy_protocol {
+		let mut trusted = Vec::new();
+		for cidr in server.config.bind.proxy_trusted.iter() {
+			match cidr.parse() {
+				Ok(cidr) => trusted.push(cidr),
+				Err(error) => {
+					error!("{error:?}");
+					return None;
+				}
+			}
+		}
+		// Заголовок может подделать кто угодно, поэтому без списка балансировщиков не запускаемся
+		if trusted.is_empty() {
+			error!("PROXY protocol включен, но в proxy_trusted нет ни одной подсети балансировщика");
+			return None;
+		}
+		reactor.with_proxy_protocol(trusted)
+	} else {
+		reactor
 	};
 
 	info!("Сервер запущен на {}", &server.config.bind.host);
 
-	while let Ok((stream, addr)) = listener.accept() {
+	// Периодически сохраняем измененные чанки и игроков на диск
+	if server.config.world.autosave_interval > 0 {
+		let period = server.config.world.autosave_interval * TICKS_PER_SECOND;
+
+		server.planner().run_repeating(period, period, |server, _| {
+			match server.world.save_all() {
+				Ok(0) => {}
+				Ok(count) => info!("Сохранено чанков: {count}"),
+				Err(error) => error!("Ошибка сохранения мира: {error:?}"),
+			}
+			if let Err(error) = server.save_players() {
+				error!("Ошибка сохранения игроков: {error:?}");
+			}
+			Ok(())
+		});
+	}
+
+	// Вся симуляция мира идет в одном потоке тиков
+	let ticking = Arc::new(AtomicBool::new(true));
+	let ticks = {
 		let server = server.clone();
+		let ticking = ticking.clone();
+		thread::spawn(move || run_ticks(server, ticking))
+	};
 
-		thread::spawn(move || {
-			info!("Подключение: {}", addr);
-
-			// Установка таймаутов на чтение и запись
-			// По умолчанию пусть будет 5 секунд, надо будет сделать настройку через конфиг
-			stream
-				.set_read_timeout(Some(Duration::from_secs(server.config.bind.timeout)))
-				.ignore();
-			stream
-				.set_write_timeout(Some(Duration::from_secs(server.config.bind.timeout)))
-				.ignore();
-
-			// Оборачиваем стрим в майнкрафт конекшн лично для нашего удовольствия
-			let conn = MinecraftConnection::new(stream);
-
-			// Создаем контекст клиента
-			// Передавется во все листенеры и хандлеры чтобы определять именно этот клиент
-			let client = Arc::new(ClientContext::new(server.clone(), conn));
-
-			// Добавляем клиента в список клиентов сервера
-			// Используем адрес как ключ, врятли ipv4 будет нам врать
-			server.clients.insert(client.addr, client.clone());
-
-			// Обработка подключения
-			// Если ошибка -> выводим
-			match handle_connection(client.clone()) {
-				Ok(_) => {}
-				Err(ServerError::ConnectionClosed) => {}
-				Err(error) => {
-					error!("Ошибка подключения: {error:?}");
-				}
-			};
+	let reactor_handle = reactor.handle();
+
+	// Реактор сам читает и пишет во все сокеты, логин идет в пуле потоков,
+	// а игроки живут на тиках. С сокетом все общаются через Connection
+	let reactor = {
+		let server = server.clone();
+		thread::spawn(move || run_reactor(server, reactor))
+	};
+
+	Some(ServerHandle {
+		server,
+		reactor: reactor_handle,
+		ticking,
+		tick_thread: ticks,
+		reactor_thread: reactor,
+	})
+}
 
-			trigger_event_ignore!(client, disconnect);
+fn run_reactor(server: Arc<ServerContext>, reactor: Reactor) {
+	let result = reactor.run(|conn| {
+		// Создаем контекст клиента
+		// Передавется во все листенеры и хандлеры чтобы определять именно этот клиент
+		let client = Arc::new(ClientContext::new(server.clone(), conn.clone()));
 
-			// Удаляем клиента из списка клиентов
-			server.clients.remove(&client.addr);
+		if !server.workers().submit(move || run_connection(client)) {
+			// Сервер останавливается, пул уже не принимает задачи
+			conn.close();
+		}
+	});
+
+	if let Err(error) = result {
+		error!("Реактор упал: {error}");
+		server.stop();
+	}
+}
+
+/// Запущенный сервер
+pub struct ServerHandle {
+	server: Arc<ServerContext>,
+	reactor: ReactorHandle,
+	ticking: Arc<AtomicBool>,
+	tick_thread: JoinHandle<()>,
+	reactor_thread: JoinHandle<()>,
+}
+
+impl ServerHandle {
+	pub fn server(&self) -> &Arc<ServerContext> {
+		&self.server
+	}
+
+	/// Ждет ServerContext::stop (команда /stop, сигнал) и останавливает сервер
+	pub fn wait(self) {
+		self.server.wait_stop();
+		self.shutdown();
+	}
+
+	/// Останавливает сервер, игроки кикаются с сообщением из конфига
+	pub fn shutdown(self) {
+		let reason = TextComponent::from_mini(&self.server.config.server.shutdown_message);
+		self.shutdown_with(reason);
+	}
 
-			info!("Отключение: {}", addr);
+	/// Останавливает сервер: закрывает порт, кикает всех с reason,
+	/// сохраняет миры и игроков и дожидается своих потоков
+	pub fn shutdown_with(self, mut reason: TextComponent) {
+		let server = self.server;
+		server.stop();
+
+		info!("Остановка сервера");
+		self.reactor.stop_accepting();
+
+		for listener in server.listeners(|o| o.on_shutdown_priority()).iter() {
+			if let Err(error) = listener.on_shutdown(server.clone(), &mut reason) {
+				error!("Ошибка в листенере остановки: {error:?}");
+			}
+		}
+
+		// Игроки сохраняются сами, когда их тик видит отключение
+		server.clients.for_each(|client| {
+			let _ = client.kick(reason.clone());
 		});
+		if !server.clients.wait_empty(SHUTDOWN_TIMEOUT) {
+			warn!(
+				"Не все клиенты отключились за {} секунд",
+				SHUTDOWN_TIMEOUT.as_secs()
+			);
+		}
+
+		// Тики останавливаются до сохранения, чтобы мир больше не менялся,
+		// поток ждем, иначе сохранение поедет параллельно с последним тиком
+		self.ticking.store(false, Ordering::SeqCst);
+		let _ = self.tick_thread.join();
+
+		// После остановки реактора все соединения закрыты, и логины в пуле сразу заканчиваются
+		self.reactor.stop();
+		let _ = self.reactor_thread.join();
+
+		// Дожидаемся отключений, которые еще сохраняют игроков, и отправки чанков
+		server.workers().shutdown();
+		server.chunk_workers().shutdown();
+
+		if let Err(error) = server.save_players() {
+			error!("Ошибка сохранения игроков: {error:?}");
+		}
+		match server.world.save_all() {
+			Ok(count) => info!("Сохранено чанков: {count}"),
+			Err(error) => error!("Ошибка сохранения мира: {error:?}"),
+		}
+
+		info!("Сервер остановлен");
 	}
 }
@@ -10,7 +10,7 @@ use rust_mc_serv::{
 	event::{Listener, PacketHandler},
 	play::{PlayHandler, PlayListener},
 	player::context::ClientContext,
-	protocol::ConnectionState,
+	protocol::{ConnectionState, status::StatusResponse},
 	start_server,
 };
 
@@ -20,60 +20,19 @@ impl Listener for ExampleListener {
 	fn on_status(
 		&self,
 		client: Arc<ClientContext>,
-		response: &mut String,
+		response: &mut StatusResponse,
 	) -> Result<(), ServerError> {
-		*response = format!(
-			"{{
-				\"version\": {{
-					\"name\": \"idk\",
-					\"protocol\": {}
-				}},
-				\"players\": {{
-					\"max\": 100,
-					\"online\": 42,
-					\"sample\": [
-						{{
-							\"name\": \"Жопа\",
-							\"id\": \"00000000-0000-0000-0000-000000000000\"
-						}}
-					]
-				}},
-				\"description\": {},
-				\"favicon\": \"data:image/png;base64,<data>\",
-				\"enforcesSecureChat\": false
-			}}",
-			client.handshake().unwrap().protocol_version,
-			TextComponent::builder()
-				.text("Hello World! ")
-				.extra(vec![
-					TextComponent::builder()
-						.text("Protocol: ")
-						.color("gold")
-						.extra(vec![
-							TextComponent::builder()
-								.text(&client.handshake().unwrap().protocol_version.to_string())
-								.underlined(true)
-								.build()
-						])
-						.build(),
-					TextComponent::builder()
-						.text("\nServer Addr: ")
-						.color("green")
-						.extra(vec![
-							TextComponent::builder()
-								.text(&format!(
-									"{}:{}",
-									client.handshake().unwrap().server_address,
-									client.handshake().unwrap().server_port
-								))
-								.underlined(true)
-								.build()
-						])
-						.build()
-				])
-				.build()
-				.as_json()?
-		);
+		let handshake = client.handshake().unwrap();
+
+		// Дописываем к motd из конфига версию протокола и адрес, по которому зашел клиент
+		response
+			.description
+			.extra
+			.get_or_insert_default()
+			.push(TextComponent::from_mini(&format!(
+				"\n<gold>Protocol: <u>{}</u></gold> <green>Server Addr: <u>{}:{}</u>",
+				handshake.protocol_version, handshake.server_address, handshake.server_port
+			)));
 
 		Ok(())
 	}
@@ -91,7 +50,7 @@ impl PacketHandler for ExamplePacketHandler {
 	) -> Result<(), ServerError> {
 		debug!(
 			"{} -> S\t| 0x{:02x}\t| {:?}\t| {} bytes",
-			client.addr.clone(),
+			client.addr(),
 			packet.id(),
 			state,
 			packet.len()
@@ -109,7 +68,7 @@ impl PacketHandler for ExamplePacketHandler {
 	) -> Result<(), ServerError> {
 		debug!(
 			"{} <- S\t| 0x{:02x}\t| {:?}\t| {} bytes",
-			client.addr.clone(),
+			client.addr(),
 			packet.id(),
 			state,
 			packet.len()
@@ -134,7 +93,7 @@ fn main() {
 	}
 
 	// Берем путь из аргумента либо по дефолту берем "./server.toml"
-	let config_path = PathBuf::from(args.get(0).unwrap_or(&"server.toml".to_string()));
+	let config_path = PathBuf::from(args.first().unwrap_or(&"server.toml".to_string()));
 
 	// Чтение конфига, если ошибка - выводим
 	let config = match Config::load_from_file(config_path) {
@@ -165,5 +124,15 @@ fn main() {
 	let server = Arc::new(server);
 
 	// Запускаем сервер из специально отведенной под это дело функцией
-	start_server(server);
+	let Some(handle) = start_server(server) else {
+		return;
+	};
+
+	// Ctrl+C и SIGTERM останавливают сервер так же, как /stop
+	let server = handle.server().clone();
+	if let Err(error) = ctrlc::set_handler(move || server.stop()) {
+		error!("Не удалось повесить обработчик сигналов: {error}");
+	}
+
+	handle.wait();
 }
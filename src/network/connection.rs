@@ -0,0 +1,412 @@
+use std::{
+	collections::VecDeque,
+	io::{self, Write},
+	net::SocketAddr,
+	sync::{
+		Arc, Condvar, Mutex, RwLock,
+		atomic::{AtomicBool, Ordering},
+	},
+	time::{Duration, Instant},
+};
+
+use log::debug;
+use mio::{Token, Waker};
+use rust_mc_proto::{Packet, read_packet, write_packet};
+
+use crate::{
+	ServerError,
+	protocol::encryption::{Cipher, Decryptor, Encryptor},
+};
+
+// Соединение с клиентом
+//
+// Сам сокет живет в реакторе (network::Reactor), он же читает из него и пишет в него
+// Соединение только хранит буферы: входящие пакеты которые уже прочитал реактор,
+// и исходящие байты которые реактор еще не успел записать в сокет
+//
+// Любой поток может писать пакеты и ждать входящие, ожидание идет через Condvar, без слипов
+//
+// Оба буфера ограничены: когда сервер не успевает разбирать входящие пакеты, реактор перестает
+// читать сокет, пока очередь не разберут наполовину, а клиент, который не читает свой сокет, отключается
+
+const MAX_INCOMING_PACKETS: usize = 1024;
+const MAX_INCOMING_BYTES: usize = 8 * 1024 * 1024;
+const MAX_OUTGOING_BYTES: usize = 64 * 1024 * 1024;
+
+pub struct Connection {
+	token: Token,
+	addr: RwLock<SocketAddr>, // меняется только реактором по заголовку PROXY protocol, до отдачи серверу
+	waker: Arc<Waker>,
+	wakeups: Arc<Mutex<Vec<Token>>>,
+	compression: RwLock<Option<usize>>,
+	outgoing: Mutex<Outgoing>,
+	incoming: Mutex<Incoming>,
+	incoming_signal: Condvar,
+	paused: AtomicBool, // реактор не читает сокет, пока очередь входящих не разберут
+	decryptor: Mutex<Option<Decryptor>>,
+	closed: AtomicBool,
+	last_read: Mutex<Instant>,
+}
+
+#[derive(Default)]
+struct Incoming {
+	packets: VecDeque<Packet>,
+	bytes: usize,
+}
+
+impl Incoming {
+	fn push(&mut self, packet: Packet) {
+		self.bytes += packet.len();
+		self.packets.push_back(packet);
+	}
+
+	fn pop(&mut self) -> Option<Packet> {
+		let packet = self.packets.pop_front()?;
+		self.bytes -= packet.len();
+		Some(packet)
+	}
+
+	fn is_full(&self) -> bool {
+		self.packets.len() >= MAX_INCOMING_PACKETS || self.bytes >= MAX_INCOMING_BYTES
+	}
+
+	fn is_drained(&self) -> bool {
+		self.packets.len() <= MAX_INCOMING_PACKETS / 2 && self.bytes <= MAX_INCOMING_BYTES / 2
+	}
+}
+
+struct Outgoing {
+	buffer: Vec<u8>,
+	encryptor: Option<Encryptor>,
+}
+
+impl Connection {
+	pub(super) fn new(
+		token: Token,
+		addr: SocketAddr,
+		waker: Arc<Waker>,
+		wakeups: Arc<Mutex<Vec<Token>>>,
+	) -> Connection {
+		Connection {
+			token,
+			addr: RwLock::new(addr),
+			waker,
+			wakeups,
+			compression: RwLock::new(None),
+			outgoing: Mutex::new(Outgoing {
+				buffer: Vec::new(),
+				encryptor: None,
+			}),
+			incoming: Mutex::new(Incoming::default()),
+			incoming_signal: Condvar::new(),
+			paused: AtomicBool::new(false),
+			decryptor: Mutex::new(None),
+			closed: AtomicBool::new(false),
+			last_read: Mutex::new(Instant::now()),
+		}
+	}
+
+	/// Соединение без сокета и реактора для тестов, все что пишется - остается в буфере
+	#[cfg(test)]
+	pub(crate) fn detached() -> Connection {
+		let poll = mio::Poll::new().unwrap();
+		let waker = Arc::new(Waker::new(poll.registry(), Token(1)).unwrap());
+		Connection::new(
+			Token(2),
+			"127.0.0.1:25565".parse().unwrap(),
+			waker,
+			Arc::new(Mutex::new(Vec::new())),
+		)
+	}
+
+	/// Забирает все, что записано в исходящий буфер, разобранное на пакеты, для тестов
+	#[cfg(test)]
+	pub(crate) fn take_sent(&self) -> Vec<Packet> {
+		let buffer = std::mem::take(&mut self.outgoing.lock().unwrap().buffer);
+		let mut data = buffer.as_slice();
+		let mut packets = Vec::new();
+		while !data.is_empty() {
+			packets.push(read_packet(&mut data, self.compression()).unwrap());
+		}
+		packets
+	}
+
+	pub fn addr(&self) -> SocketAddr {
+		*self.addr.read().unwrap()
+	}
+
+	pub(crate) fn set_addr(&self, addr: SocketAddr) {
+		*self.addr.write().unwrap() = addr;
+	}
+
+	pub fn compression(&self) -> Option<usize> {
+		*self.compression.read().unwrap()
+	}
+
+	pub fn set_compression(&self, threshold: Option<usize>) {
+		*self.compression.write().unwrap() = threshold;
+	}
+
+	/// Включает шифрование, все следующие байты в обе стороны шифруются
+	///
+	/// Вызывать сразу после чтения Encryption Response, пока клиент еще ничего не прислал
+	pub fn set_encryption(&self, shared_secret: &[u8]) -> Result<(), ServerError> {
+		let (encryptor, decryptor) = Cipher::new(shared_secret)?.split();
+		self.outgoing.lock().unwrap().encryptor = Some(encryptor);
+		*self.decryptor.lock().unwrap() = Some(decryptor);
+		Ok(())
+	}
+
+	pub fn is_encrypted(&self) -> bool {
+		self.outgoing.lock().unwrap().encryptor.is_some()
+	}
+
+	pub fn is_closed(&self) -> bool {
+		self.closed.load(Ordering::SeqCst)
+	}
+
+	/// Закрывает соединение, то что уже лежит в исходящем буфере реактор попытается дописать
+	pub fn close(&self) {
+		if !self.closed.swap(true, Ordering::SeqCst) {
+			// Под локом очереди, иначе ждущий может проверить closed и уснуть уже после notify
+			let _incoming = self.incoming.lock().unwrap();
+			self.incoming_signal.notify_all();
+			drop(_incoming);
+			self.wake();
+		}
+	}
+
+	/// Кладет пакет в исходящий буфер и будит реактор
+	///
+	/// Если в буфере уже больше MAX_OUTGOING_BYTES, клиент не читает сокет и соединение закрывается
+	pub fn write_packet(&self, packet: &Packet) -> Result<(), ServerError> {
+		if self.is_closed() {
+			return Err(ServerError::ConnectionClosed);
+		}
+
+		let mut data = Vec::new();
+		write_packet(&mut data, self.compression(), 1, packet)?;
+
+		let overflow = {
+			// Шифруем под локом буфера, чтобы порядок байтов совпадал с порядком шифрования
+			let mut outgoing = self.outgoing.lock().unwrap();
+			if outgoing.buffer.len() + data.len() > MAX_OUTGOING_BYTES {
+				true
+			} else {
+				if let Some(encryptor) = outgoing.encryptor.as_mut() {
+					encryptor.encrypt(&mut data);
+				}
+				outgoing.buffer.extend_from_slice(&data);
+				false
+			}
+		};
+
+		if overflow {
+			debug!("{} не читает сокет, отключаем", self.addr());
+			self.close();
+			return Err(ServerError::ConnectionClosed);
+		}
+
+		self.wake();
+
+		Ok(())
+	}
+
+	/// Сколько байт еще не записано в сокет
+	pub fn pending_bytes(&self) -> usize {
+		self.outgoing.lock().unwrap().buffer.len()
+	}
+
+	/// Ждет следующий пакет, пока соединение не закроется
+	pub fn read_packet(&self) -> Result<Packet, ServerError> {
+		let mut incoming = self.incoming.lock().unwrap();
+		loop {
+			if let Some(packet) = incoming.pop() {
+				self.resume_reading(&incoming);
+				return Ok(packet);
+			}
+			if self.is_closed() {
+				return Err(ServerError::ConnectionClosed);
+			}
+			incoming = self.incoming_signal.wait(incoming).unwrap();
+		}
+	}
+
+	/// Тоже самое что и read_packet, но не ждет
+	pub fn try_read_packet(&self) -> Result<Option<Packet>, ServerError> {
+		let mut incoming = self.incoming.lock().unwrap();
+		match incoming.pop() {
+			Some(packet) => {
+				self.resume_reading(&incoming);
+				Ok(Some(packet))
+			}
+			None if self.is_closed() => Err(ServerError::ConnectionClosed),
+			None => Ok(None),
+		}
+	}
+
+	// Просит реактор снова читать сокет, когда очередь разобрали
+	fn resume_reading(&self, incoming: &Incoming) {
+		if incoming.is_drained() && self.paused.swap(false, Ordering::SeqCst) {
+			// Пока стояли, клиент не мог ничего прислать, таймаут считаем заново
+			*self.last_read.lock().unwrap() = Instant::now();
+			self.wake();
+		}
+	}
+
+	/// Сколько времени клиент ничего не присылал
+	pub fn idle_time(&self) -> Duration {
+		self.last_read.lock().unwrap().elapsed()
+	}
+
+	fn wake(&self) {
+		self.wakeups.lock().unwrap().push(self.token);
+		let _ = self.waker.wake();
+	}
+
+	// Дальше функции только для реактора
+
+	/// Стоит ли чтение сокета, пока сервер не разберет очередь
+	pub(super) fn is_paused(&self) -> bool {
+		self.paused.load(Ordering::SeqCst)
+	}
+
+	/// Обрабатывает прочитанные из сокета байты, `buffer` - недочитанный хвост с прошлого раза
+	///
+	/// Возвращает false, если очередь входящих переполнена и сокет больше читать не надо
+	pub(super) fn receive(&self, data: &mut [u8], buffer: &mut Vec<u8>) -> Result<bool, ServerError> {
+		*self.last_read.lock().unwrap() = Instant::now();
+
+		if let Some(decryptor) = self.decryptor.lock().unwrap().as_mut() {
+			decryptor.decrypt(data);
+		}
+		buffer.extend_from_slice(data);
+
+		let mut received = false;
+		let mut incoming = self.incoming.lock().unwrap();
+
+		while let Some(size) = frame_size(buffer)? {
+			let packet = read_packet(&mut &buffer[..size], self.compression())?;
+			buffer.drain(..size);
+			incoming.push(packet);
+			received = true;
+		}
+
+		// Под локом очереди, чтобы resume_reading не проскочил между проверкой и паузой
+		let full = incoming.is_full();
+		if full {
+			self.paused.store(true, Ordering::SeqCst);
+		}
+		drop(incoming);
+
+		if received {
+			self.incoming_signal.notify_all();
+		}
+
+		Ok(!full)
+	}
+
+	/// Пишет исходящий буфер в сокет сколько влезет, возвращает true если записал все
+	pub(super) fn flush(&self, stream: &mut impl Write) -> io::Result<bool> {
+		let mut outgoing = self.outgoing.lock().unwrap();
+
+		while !outgoing.buffer.is_empty() {
+			match stream.write(&outgoing.buffer) {
+				Ok(0) => return Err(io::ErrorKind::WriteZero.into()),
+				Ok(n) => {
+					outgoing.buffer.drain(..n);
+				}
+				Err(e) if e.kind() == io::ErrorKind::WouldBlock => return Ok(false),
+				Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+				Err(e) => return Err(e),
+			}
+		}
+
+		Ok(true)
+	}
+}
+
+/// Размер первого пакета в буфере вместе с длиной, None если пакет еще не дочитан
+fn frame_size(buffer: &[u8]) -> Result<Option<usize>, ServerError> {
+	let mut length = 0usize;
+
+	for (i, byte) in buffer.iter().enumerate().take(3) {
+		length |= ((byte & 0x7F) as usize) << (7 * i);
+
+		if byte & 0x80 == 0 {
+			let size = i + 1 + length;
+			return Ok(if buffer.len() >= size {
+				Some(size)
+			} else {
+				None
+			});
+		}
+	}
+
+	if buffer.len() >= 3 {
+		// Длина пакета в протоколе не больше 3 байт VarInt
+		Err(ServerError::WrongPacket)
+	} else {
+		Ok(None)
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use rust_mc_proto::DataWriter;
+
+	fn frames(count: usize, size: usize) -> Vec<u8> {
+		let mut data = Vec::new();
+		for _ in 0..count {
+			let mut packet = Packet::empty(0x00);
+			packet.write_bytes(&vec![0; size]).unwrap();
+			write_packet(&mut data, None, 1, &packet).unwrap();
+		}
+		data
+	}
+
+	#[test]
+	fn reading_pauses_until_queue_drains() {
+		let connection = Connection::detached();
+		let mut buffer = Vec::new();
+
+		let mut data = frames(MAX_INCOMING_PACKETS - 1, 1);
+		assert!(connection.receive(&mut data, &mut buffer).unwrap());
+		assert!(!connection.is_paused());
+
+		let mut data = frames(1, 1);
+		assert!(!connection.receive(&mut data, &mut buffer).unwrap());
+		assert!(connection.is_paused());
+
+		for _ in 0..MAX_INCOMING_PACKETS / 2 - 1 {
+			connection.try_read_packet().unwrap().unwrap();
+		}
+		assert!(connection.is_paused());
+		connection.try_read_packet().unwrap().unwrap();
+		assert!(!connection.is_paused());
+		assert_eq!(*connection.wakeups.lock().unwrap(), vec![Token(2)]);
+	}
+
+	#[test]
+	fn large_packets_count_by_bytes() {
+		let connection = Connection::detached();
+		let mut buffer = Vec::new();
+
+		let mut data = frames(8, MAX_INCOMING_BYTES / 8);
+		assert!(!connection.receive(&mut data, &mut buffer).unwrap());
+		assert!(connection.is_paused());
+	}
+
+	#[test]
+	fn slow_reader_is_disconnected() {
+		let connection = Connection::detached();
+
+		let mut packet = Packet::empty(0x00);
+		packet.write_bytes(&vec![0; 1024 * 1024]).unwrap();
+		while connection.write_packet(&packet).is_ok() {}
+
+		assert!(connection.is_closed());
+		assert!(connection.pending_bytes() <= MAX_OUTGOING_BYTES);
+	}
+}
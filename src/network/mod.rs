@@ -0,0 +1,399 @@
+use std::{
+	collections::HashMap,
+	io::{self, Read},
+	net::{Shutdown, TcpListener as StdTcpListener},
+	sync::{
+		Arc, Mutex,
+		atomic::{AtomicBool, Ordering},
+	},
+	time::{Duration, Instant},
+};
+
+use connection::Connection;
+use log::{debug, error};
+use mio::{
+	Events, Interest, Poll, Token, Waker,
+	net::{TcpListener, TcpStream},
+};
+use proxy::{Cidr, parse_header};
+
+use crate::ServerError;
+
+pub mod connection;
+pub mod proxy;
+
+const LISTENER: Token = Token(0);
+const WAKER: Token = Token(1);
+const DRAIN_TIMEOUT: Duration = Duration::from_secs(5); // сколько дописывать буферы после stop
+
+// Реактор - один поток, который обслуживает все сокеты сервера
+//
+// Сокеты неблокирующие, реактор спит в poll пока не придут данные,
+// либо пока какое-нибудь соединение не разбудит его через Waker (когда есть что отправить или соединение закрыли)
+// Игровая логика реактор не трогает, она только читает и пишет пакеты через Connection
+pub struct Reactor {
+	poll: Poll,
+	listener: Option<TcpListener>, // None после stop_accepting
+	waker: Arc<Waker>,
+	accepting: Arc<AtomicBool>,
+	running: Arc<AtomicBool>,
+	wakeups: Arc<Mutex<Vec<Token>>>,
+	connections: HashMap<Token, Entry>,
+	next_token: usize,
+	timeout: Duration,
+	proxy: Option<Vec<Cidr>>, // PROXY protocol включен и от каких адресов ждать заголовок
+}
+
+/// Управление реактором из других потоков
+#[derive(Clone)]
+pub struct ReactorHandle {
+	waker: Arc<Waker>,
+	accepting: Arc<AtomicBool>,
+	running: Arc<AtomicBool>,
+}
+
+impl ReactorHandle {
+	/// Закрывает порт, уже подключенные клиенты продолжают работать
+	pub fn stop_accepting(&self) {
+		self.accepting.store(false, Ordering::SeqCst);
+		let _ = self.waker.wake();
+	}
+
+	/// Останавливает реактор: он дописывает что успеет и закрывает все соединения
+	pub fn stop(&self) {
+		self.accepting.store(false, Ordering::SeqCst);
+		self.running.store(false, Ordering::SeqCst);
+		let _ = self.waker.wake();
+	}
+}
+
+struct Entry {
+	stream: TcpStream,
+	connection: Arc<Connection>,
+	buffer: Vec<u8>,    // недочитанный пакет
+	proxy_header: bool, // ждем заголовок PROXY protocol, до него соединение не отдается серверу
+	paused: bool,       // очередь входящих переполнена, сокет не читаем
+}
+
+impl Reactor {
+	/// `timeout` - через сколько отключать клиента, который ничего не присылает
+	pub fn bind(host: &str, timeout: Duration) -> io::Result<Reactor> {
+		let listener = StdTcpListener::bind(host)?;
+		listener.set_nonblocking(true)?;
+		let mut listener = TcpListener::from_std(listener);
+
+		let poll = Poll::new()?;
+		poll
+			.registry()
+			.register(&mut listener, LISTENER, Interest::READABLE)?;
+		let waker = Arc::new(Waker::new(poll.registry(), WAKER)?);
+
+		Ok(Reactor {
+			poll,
+			listener: Some(listener),
+			waker,
+			accepting: Arc::new(AtomicBool::new(true)),
+			running: Arc::new(AtomicBool::new(true)),
+			wakeups: Arc::new(Mutex::new(Vec::new())),
+			connections: HashMap::new(),
+			next_token: WAKER.0 + 1,
+			timeout,
+			proxy: None,
+		})
+	}
+
+	/// Включает PROXY protocol v1/v2: соединения с `trusted` адресов (пустой список - ни с каких)
+	/// обязаны начинаться с заголовка, и адресом соединения становится адрес из него
+	///
+	/// Остальные соединения принимаются как обычно, подделать адрес они не могут
+	pub fn with_proxy_protocol(mut self, trusted: Vec<Cidr>) -> Reactor {
+		self.proxy = Some(trusted);
+		self
+	}
+
+	pub fn handle(&self) -> ReactorHandle {
+		ReactorHandle {
+			waker: self.waker.clone(),
+			accepting: self.accepting.clone(),
+			running: self.running.clone(),
+		}
+	}
+
+	/// Крутит цикл реактора, пока его не остановят через ReactorHandle
+	///
+	/// `on_connect` вызывается на каждое новое соединение
+	pub fn run<F>(mut self, mut on_connect: F) -> io::Result<()>
+	where
+		F: FnMut(Arc<Connection>),
+	{
+		let mut events = Events::with_capacity(1024);
+		let mut read_buffer = vec![0; 64 * 1024];
+		let mut last_idle_check = Instant::now();
+		let mut drain_deadline = None;
+
+		loop {
+			match self.poll.poll(&mut events, Some(Duration::from_secs(1))) {
+				Ok(_) => {}
+				Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+				Err(e) => return Err(e),
+			}
+
+			for event in events.iter() {
+				match event.token() {
+					LISTENER => self.accept(&mut on_connect),
+					WAKER => {} // разбираемся ниже
+					token => {
+						if event.is_readable() {
+							self.read(token, &mut read_buffer, &mut on_connect);
+						}
+						if event.is_writable() {
+							self.flush(token);
+						}
+					}
+				}
+			}
+
+			let wakeups = std::mem::take(&mut *self.wakeups.lock().unwrap());
+			for token in wakeups {
+				self.flush(token);
+				self.resume(token, &mut read_buffer, &mut on_connect);
+			}
+
+			if last_idle_check.elapsed() >= Duration::from_secs(1) {
+				last_idle_check = Instant::now();
+				self.close_idle();
+			}
+
+			if !self.accepting.load(Ordering::SeqCst)
+				&& let Some(mut listener) = self.listener.take()
+			{
+				let _ = self.poll.registry().deregister(&mut listener);
+			}
+
+			// После stop соединения закрываются, но реактор еще дописывает их буферы,
+			// иначе кик застрянет за недописанными чанками
+			if !self.running.load(Ordering::SeqCst) {
+				let deadline = *drain_deadline.get_or_insert_with(|| {
+					self.close_all();
+					Instant::now() + DRAIN_TIMEOUT
+				});
+
+				if self.connections.is_empty() || Instant::now() >= deadline {
+					let tokens = self.connections.keys().copied().collect::<Vec<_>>();
+					for token in tokens {
+						self.remove(token);
+					}
+					return Ok(());
+				}
+			}
+		}
+	}
+
+	fn accept<F>(&mut self, on_connect: &mut F)
+	where
+		F: FnMut(Arc<Connection>),
+	{
+		let Some(listener) = self.listener.as_ref() else {
+			return;
+		};
+
+		loop {
+			let (mut stream, addr) = match listener.accept() {
+				Ok(v) => v,
+				Err(e) if e.kind() == io::ErrorKind::WouldBlock => return,
+				Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+				Err(e) => {
+					error!("Ошибка при принятии подключения: {e}");
+					return;
+				}
+			};
+
+			let token = Token(self.next_token);
+			self.next_token += 1;
+
+			let _ = stream.set_nodelay(true);
+
+			if let Err(e) =
+				self
+					.poll
+					.registry()
+					.register(&mut stream, token, Interest::READABLE | Interest::WRITABLE)
+			{
+				error!("Не удалось зарегистрировать сокет {addr}: {e}");
+				continue;
+			}
+
+			let connection = Arc::new(Connection::new(
+				token,
+				addr,
+				self.waker.clone(),
+				self.wakeups.clone(),
+			));
+
+			let proxy_header = self
+				.proxy
+				.as_ref()
+				.is_some_and(|o| o.iter().any(|o| o.contains(addr.ip())));
+
+			self.connections.insert(
+				token,
+				Entry {
+					stream,
+					connection: connection.clone(),
+					buffer: Vec::new(),
+					proxy_header,
+					paused: false,
+				},
+			);
+
+			if !proxy_header {
+				on_connect(connection);
+			}
+		}
+	}
+
+	fn read<F>(&mut self, token: Token, read_buffer: &mut [u8], on_connect: &mut F)
+	where
+		F: FnMut(Arc<Connection>),
+	{
+		let Some(entry) = self.connections.get_mut(&token) else {
+			return;
+		};
+		if entry.paused {
+			return;
+		}
+
+		loop {
+			match entry.stream.read(read_buffer) {
+				Ok(0) => {
+					// Клиент закрыл соединение
+					self.remove(token);
+					return;
+				}
+				Ok(n) => {
+					let result = if entry.proxy_header {
+						entry.receive_proxy_header(&read_buffer[..n], on_connect)
+					} else {
+						entry
+							.connection
+							.receive(&mut read_buffer[..n], &mut entry.buffer)
+					};
+
+					match result {
+						Ok(true) => {}
+						Ok(false) => {
+							// Остальное подождет в сокете, клиента притормозит TCP
+							entry.paused = true;
+							return;
+						}
+						Err(e) => {
+							debug!("Ошибка чтения от {}: {e:?}", entry.connection.addr());
+							self.remove(token);
+							return;
+						}
+					}
+				}
+				Err(e) if e.kind() == io::ErrorKind::WouldBlock => return,
+				Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+				Err(_) => {
+					self.remove(token);
+					return;
+				}
+			}
+		}
+	}
+
+	// Соединение разобрало очередь входящих - дочитываем то, что накопилось в сокете,
+	// edge-triggered событие об этих байтах уже было
+	fn resume<F>(&mut self, token: Token, read_buffer: &mut [u8], on_connect: &mut F)
+	where
+		F: FnMut(Arc<Connection>),
+	{
+		let Some(entry) = self.connections.get_mut(&token) else {
+			return;
+		};
+
+		if entry.paused && !entry.connection.is_paused() {
+			entry.paused = false;
+			self.read(token, read_buffer, on_connect);
+		}
+	}
+
+	fn flush(&mut self, token: Token) {
+		let Some(entry) = self.connections.get_mut(&token) else {
+			return;
+		};
+
+		match entry.connection.flush(&mut entry.stream) {
+			// Все дописали, если соединение закрыто - можно убирать
+			Ok(true) if entry.connection.is_closed() => self.remove(token),
+			Ok(_) => {}
+			Err(_) => self.remove(token),
+		}
+	}
+
+	fn close_idle(&mut self) {
+		let idle = self
+			.connections
+			.iter()
+			.filter(|(_, o)| !o.paused && o.connection.idle_time() > self.timeout)
+			.map(|(token, _)| *token)
+			.collect::<Vec<_>>();
+
+		for token in idle {
+			self.remove(token);
+		}
+	}
+
+	// Закрывает все соединения, сокет убирается когда его буфер допишется (см. flush)
+	fn close_all(&mut self) {
+		let tokens = self.connections.keys().copied().collect::<Vec<_>>();
+
+		for token in tokens {
+			if let Some(entry) = self.connections.get(&token) {
+				entry.connection.close();
+			}
+			self.flush(token);
+		}
+	}
+
+	fn remove(&mut self, token: Token) {
+		if let Some(mut entry) = self.connections.remove(&token) {
+			let _ = self.poll.registry().deregister(&mut entry.stream);
+			let _ = entry.stream.shutdown(Shutdown::Both);
+			entry.connection.close();
+		}
+	}
+}
+
+impl Entry {
+	// Копит байты до конца заголовка, потом отдает соединение серверу, а остаток - соединению
+	fn receive_proxy_header<F>(
+		&mut self,
+		data: &[u8],
+		on_connect: &mut F,
+	) -> Result<bool, ServerError>
+	where
+		F: FnMut(Arc<Connection>),
+	{
+		self.buffer.extend_from_slice(data);
+
+		let Some((addr, size)) = parse_header(&self.buffer)? else {
+			return Ok(true);
+		};
+
+		if let Some(addr) = addr {
+			debug!("{} пришел через прокси {}", addr, self.connection.addr());
+			self.connection.set_addr(addr);
+		}
+		self.proxy_header = false;
+		on_connect(self.connection.clone());
+
+		let mut rest = self.buffer.split_off(size);
+		self.buffer.clear();
+		if rest.is_empty() {
+			return Ok(true);
+		}
+		self.connection.receive(&mut rest, &mut self.buffer)
+	}
+}
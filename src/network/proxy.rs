@@ -0,0 +1,298 @@
+use std::{
+	net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr},
+	str::FromStr,
+};
+
+use crate::ServerError;
+
+// HAProxy PROXY protocol v1 и v2
+//
+// Балансировщик перед сервером первым делом шлет заголовок с настоящим адресом клиента,
+// дальше идут обычные байты соединения. Спецификация: https://www.haproxy.org/download/2.9/doc/proxy-protocol.txt
+
+const V1_PREFIX: &[u8] = b"PROXY ";
+const V1_MAX_LENGTH: usize = 107; // вместе с \r\n
+const V2_SIGNATURE: &[u8] = b"\r\n\r\n\0\r\nQUIT\n";
+const V2_HEADER_LENGTH: usize = 16; // сигнатура, версия с командой, семейство адресов и длина
+
+/// Подсеть вроде 10.0.0.0/8 или fd00::/8, адрес без маски - одна машина
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Cidr {
+	pub addr: IpAddr,
+	pub prefix: u8,
+}
+
+impl Cidr {
+	pub fn contains(&self, addr: IpAddr) -> bool {
+		// IPv4 клиенты на двойном сокете приходят как ::ffff:a.b.c.d
+		let addr = match addr {
+			IpAddr::V6(v6) => v6.to_ipv4_mapped().map(IpAddr::V4).unwrap_or(addr),
+			addr => addr,
+		};
+
+		match (self.addr, addr) {
+			(IpAddr::V4(net), IpAddr::V4(addr)) => prefix_matches(
+				u32::from(net) as u128,
+				u32::from(addr) as u128,
+				self.prefix,
+				32,
+			),
+			(IpAddr::V6(net), IpAddr::V6(addr)) => {
+				prefix_matches(u128::from(net), u128::from(addr), self.prefix, 128)
+			}
+			_ => false,
+		}
+	}
+}
+
+impl FromStr for Cidr {
+	type Err = ServerError;
+
+	fn from_str(value: &str) -> Result<Cidr, ServerError> {
+		let error = || ServerError::ProxyProtocol(format!("неверная подсеть {value}"));
+
+		let (addr, prefix) = match value.split_once('/') {
+			Some((addr, prefix)) => (addr, Some(prefix)),
+			None => (value, None),
+		};
+		let addr: IpAddr = addr.trim().parse().map_err(|_| error())?;
+		let bits = if addr.is_ipv4() { 32 } else { 128 };
+		let prefix = match prefix {
+			Some(prefix) => prefix.trim().parse::<u8>().map_err(|_| error())?,
+			None => bits,
+		};
+
+		if prefix > bits {
+			return Err(error());
+		}
+
+		Ok(Cidr { addr, prefix })
+	}
+}
+
+fn prefix_matches(net: u128, addr: u128, prefix: u8, bits: u8) -> bool {
+	if prefix == 0 {
+		return true;
+	}
+	let shift = bits - prefix;
+	net >> shift == addr >> shift
+}
+
+/// Разбирает заголовок в начале буфера
+///
+/// Ok(None) - заголовок еще не дочитан, иначе адрес клиента и длина заголовка в байтах.
+/// Адрес None, если балансировщик проверяет сервер сам (LOCAL, UNKNOWN) или прислал не TCP адреса,
+/// тогда остается адрес сокета
+pub fn parse_header(buffer: &[u8]) -> Result<Option<(Option<SocketAddr>, usize)>, ServerError> {
+	let prefix = |signature: &[u8]| buffer.len().min(signature.len());
+
+	if buffer[..prefix(V2_SIGNATURE)] == V2_SIGNATURE[..prefix(V2_SIGNATURE)] {
+		parse_v2(buffer)
+	} else if buffer[..prefix(V1_PREFIX)] == V1_PREFIX[..prefix(V1_PREFIX)] {
+		parse_v1(buffer)
+	} else {
+		Err(ServerError::ProxyProtocol("нет заголовка".to_string()))
+	}
+}
+
+fn parse_v1(buffer: &[u8]) -> Result<Option<(Option<SocketAddr>, usize)>, ServerError> {
+	let error = || ServerError::ProxyProtocol("битый заголовок v1".to_string());
+
+	let Some(end) = buffer.windows(2).position(|o| o == b"\r\n") else {
+		return if buffer.len() >= V1_MAX_LENGTH {
+			Err(error())
+		} else {
+			Ok(None)
+		};
+	};
+	if end + 2 > V1_MAX_LENGTH {
+		return Err(error());
+	}
+
+	let line = std::str::from_utf8(&buffer[..end]).map_err(|_| error())?;
+	let parts: Vec<&str> = line.split(' ').collect();
+
+	let addr = match parts[..] {
+		["PROXY", "UNKNOWN", ..] => None,
+		["PROXY", family @ ("TCP4" | "TCP6"), source, _, port, _] => {
+			let ip: IpAddr = source.parse().map_err(|_| error())?;
+			if ip.is_ipv4() != (family == "TCP4") {
+				return Err(error());
+			}
+			Some(SocketAddr::new(ip, port.parse().map_err(|_| error())?))
+		}
+		_ => return Err(error()),
+	};
+
+	Ok(Some((addr, end + 2)))
+}
+
+fn parse_v2(buffer: &[u8]) -> Result<Option<(Option<SocketAddr>, usize)>, ServerError> {
+	let error = |text: &str| ServerError::ProxyProtocol(format!("заголовок v2: {text}"));
+
+	if buffer.len() < V2_HEADER_LENGTH {
+		return Ok(None);
+	}
+
+	let version = buffer[12] >> 4;
+	let command = buffer[12] & 0x0F;
+	let family = buffer[13];
+	let length = u16::from_be_bytes([buffer[14], buffer[15]]) as usize;
+
+	if version != 2 {
+		return Err(error("неизвестная версия"));
+	}
+	if buffer.len() < V2_HEADER_LENGTH + length {
+		return Ok(None);
+	}
+
+	let data = &buffer[V2_HEADER_LENGTH..V2_HEADER_LENGTH + length];
+	let size = V2_HEADER_LENGTH + length;
+	let port = |at: usize| u16::from_be_bytes([data[at], data[at + 1]]);
+
+	let addr = match (command, family) {
+		(0, _) => None, // LOCAL - проверка здоровья от самого балансировщика
+		(1, 0x11) if data.len() >= 12 => {
+			let ip = Ipv4Addr::new(data[0], data[1], data[2], data[3]);
+			Some(SocketAddr::new(IpAddr::V4(ip), port(8)))
+		}
+		(1, 0x21) if data.len() >= 36 => {
+			let ip: [u8; 16] = data[..16].try_into().unwrap();
+			Some(SocketAddr::new(IpAddr::V6(Ipv6Addr::from(ip)), port(32)))
+		}
+		(1, 0x11 | 0x21) => return Err(error("слишком короткие адреса")),
+		(1, _) => None, // UNSPEC, UDP и unix сокеты
+		_ => return Err(error("неизвестная команда")),
+	};
+
+	Ok(Some((addr, size)))
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn v2(command: u8, family: u8, data: &[u8]) -> Vec<u8> {
+		let mut header = V2_SIGNATURE.to_vec();
+		header.push(0x20 | command);
+		header.push(family);
+		header.extend_from_slice(&(data.len() as u16).to_be_bytes());
+		header.extend_from_slice(data);
+		header
+	}
+
+	fn addr(text: &str) -> Option<SocketAddr> {
+		Some(text.parse().unwrap())
+	}
+
+	#[test]
+	fn v1_tcp4_and_tcp6() {
+		let header = b"PROXY TCP4 192.168.1.2 10.0.0.1 56324 25565\r\n\x10\x00";
+		assert_eq!(
+			parse_header(header).unwrap(),
+			Some((addr("192.168.1.2:56324"), header.len() - 2))
+		);
+
+		let header = b"PROXY TCP6 2001:db8::1 ::1 4000 25565\r\n";
+		assert_eq!(
+			parse_header(header).unwrap(),
+			Some((addr("[2001:db8::1]:4000"), header.len()))
+		);
+	}
+
+	#[test]
+	fn v1_unknown() {
+		let header = b"PROXY UNKNOWN\r\n";
+		assert_eq!(parse_header(header).unwrap(), Some((None, header.len())));
+	}
+
+	#[test]
+	fn v1_truncated_and_broken() {
+		assert_eq!(parse_header(b"PRO").unwrap(), None);
+		assert_eq!(parse_header(b"PROXY TCP4 192.168.1.2 10.0").unwrap(), None);
+
+		assert!(parse_header(b"PROXY TCP4 2001:db8::1 ::1 4000 25565\r\n").is_err());
+		assert!(parse_header(b"PROXY TCP4 192.168.1.2 10.0.0.1 port 25565\r\n").is_err());
+		assert!(parse_header(b"PROXY UDP4 192.168.1.2 10.0.0.1 1 2\r\n").is_err());
+		assert!(parse_header(&[b'P', b'R', b'O', b'X', b'Y', b' '].repeat(20)).is_err());
+	}
+
+	#[test]
+	fn v2_tcp4_and_tcp6() {
+		let mut data = vec![192, 168, 1, 2, 10, 0, 0, 1];
+		data.extend_from_slice(&56324u16.to_be_bytes());
+		data.extend_from_slice(&25565u16.to_be_bytes());
+		let header = v2(1, 0x11, &data);
+		assert_eq!(
+			parse_header(&header).unwrap(),
+			Some((addr("192.168.1.2:56324"), header.len()))
+		);
+
+		let mut data = "2001:db8::1".parse::<Ipv6Addr>().unwrap().octets().to_vec();
+		data.extend_from_slice(&Ipv6Addr::LOCALHOST.octets());
+		data.extend_from_slice(&4000u16.to_be_bytes());
+		data.extend_from_slice(&25565u16.to_be_bytes());
+		// TLV после адресов пропускаются вместе с заголовком
+		data.extend_from_slice(&[0x04, 0, 1, 0]);
+		let header = v2(1, 0x21, &data);
+		assert_eq!(
+			parse_header(&header).unwrap(),
+			Some((addr("[2001:db8::1]:4000"), header.len()))
+		);
+	}
+
+	#[test]
+	fn v2_local_and_unspec() {
+		let header = v2(0, 0x00, &[]);
+		assert_eq!(parse_header(&header).unwrap(), Some((None, header.len())));
+
+		let header = v2(1, 0x00, &[]);
+		assert_eq!(parse_header(&header).unwrap(), Some((None, header.len())));
+	}
+
+	#[test]
+	fn v2_truncated() {
+		let header = v2(1, 0x11, &[192, 168, 1, 2, 10, 0, 0, 1, 0, 80, 0, 80]);
+		for length in [5, V2_HEADER_LENGTH - 1, V2_HEADER_LENGTH, header.len() - 1] {
+			assert_eq!(parse_header(&header[..length]).unwrap(), None, "{length}");
+		}
+
+		// Длина в заголовке меньше, чем нужно под адреса
+		assert!(parse_header(&v2(1, 0x11, &[192, 168, 1, 2])).is_err());
+		assert!(parse_header(&v2(1, 0x21, &[0; 12])).is_err());
+	}
+
+	#[test]
+	fn v2_bad_version_and_command() {
+		let mut header = v2(1, 0x11, &[0; 12]);
+		header[12] = 0x11;
+		assert!(parse_header(&header).is_err());
+
+		assert!(parse_header(&v2(5, 0x11, &[0; 12])).is_err());
+	}
+
+	#[test]
+	fn wrong_signature() {
+		assert!(parse_header(b"\x10\x00\xf2\x05").is_err());
+		assert!(parse_header(b"GET / HTTP/1.1\r\n").is_err());
+
+		let mut header = v2(1, 0x11, &[0; 12]);
+		header[5] = b'X';
+		assert!(parse_header(&header).is_err());
+	}
+
+	#[test]
+	fn cidr_matching() {
+		let net: Cidr = "10.0.0.0/8".parse().unwrap();
+		assert!(net.contains("10.1.2.3".parse().unwrap()));
+		assert!(net.contains("::ffff:10.1.2.3".parse().unwrap()));
+		assert!(!net.contains("11.0.0.1".parse().unwrap()));
+
+		let host: Cidr = "fd00::1".parse().unwrap();
+		assert!(host.contains("fd00::1".parse().unwrap()));
+		assert!(!host.contains("fd00::2".parse().unwrap()));
+
+		assert!("10.0.0.0/33".parse::<Cidr>().is_err());
+		assert!("nope".parse::<Cidr>().is_err());
+	}
+}
@@ -0,0 +1,502 @@
+use std::{sync::Arc, time::Duration};
+
+use super::{
+	chunk::send_light_changes,
+	inventory::{consume_held_item, set_player_slot},
+};
+use crate::{
+	ServerError,
+	context::{ServerContext, World},
+	data::slot::{Slot, components::DataComponent},
+	player::{
+		context::{ClientContext, GameMode},
+		inventory::slots,
+	},
+	protocol::packets::{
+		Position, VarInt,
+		clientbound::play::{AcknowledgeBlockChange, BlockUpdate},
+		serverbound::play::{PlayerAction, UseItemOn},
+	},
+	trigger_event,
+	world::{block::hardness, chunk::BlockState, light},
+};
+
+// Ломание и установка блоков
+//
+// Клиент сразу показывает результат у себя и ждет Acknowledge Block Change с тем же sequence,
+// если до подтверждения пришел Block Update, клиент берет блок из него, иначе откатывает свое предсказание
+
+// Смещения по граням блока в порядке айдишников из Player Action и Use Item On
+const FACES: [(i32, i32, i32); 6] = [
+	(0, -1, 0),
+	(0, 1, 0),
+	(0, 0, -1),
+	(0, 0, 1),
+	(-1, 0, 0),
+	(1, 0, 0),
+];
+
+// Блоки, на место которых можно ставить другие, как будто там воздух
+const REPLACEABLE: &[&str] = &[
+	"minecraft:water",
+	"minecraft:lava",
+	"minecraft:short_grass",
+	"minecraft:tall_grass",
+	"minecraft:fern",
+	"minecraft:large_fern",
+	"minecraft:dead_bush",
+	"minecraft:seagrass",
+	"minecraft:tall_seagrass",
+	"minecraft:vine",
+	"minecraft:fire",
+	"minecraft:soul_fire",
+	"minecraft:snow",
+	"minecraft:light",
+	"minecraft:structure_void",
+];
+
+// Скорость копания по материалу инструмента, для проверки времени берется самый быстрый случай:
+// считаем, что инструмент всегда подходящий
+const TOOL_SPEEDS: &[(&str, f32)] = &[
+	("minecraft:wooden_", 2.0),
+	("minecraft:stone_", 4.0),
+	("minecraft:iron_", 6.0),
+	("minecraft:diamond_", 8.0),
+	("minecraft:netherite_", 9.0),
+	("minecraft:golden_", 12.0),
+];
+
+const TOOLS: &[&str] = &["_pickaxe", "_axe", "_shovel", "_hoe"];
+
+const ENCHANTMENT: &str = "minecraft:enchantment";
+const EFFICIENCY: &str = "minecraft:efficiency";
+
+// Длина тика и допуск на задержки сети, как в ванилле блок можно сломать на 70% прогресса
+const TICK: Duration = Duration::from_millis(50);
+const DIG_TOLERANCE: f32 = 0.7;
+
+fn is_replaceable(block: &BlockState) -> bool {
+	block.is_air() || REPLACEABLE.contains(&block.name.as_str())
+}
+
+fn in_world(world: &World, y: i32) -> bool {
+	world.dimension.contains_y(y)
+}
+
+fn position(location: Position) -> (i32, i32, i32) {
+	(location.x as i32, location.y as i32, location.z as i32)
+}
+
+// Максимальная скорость копания предметом в руке
+fn dig_speed(client: &Arc<ClientContext>, item: &Slot) -> f32 {
+	let Some(name) = client.server.world.items.name(item.item_id) else {
+		return 1.0;
+	};
+
+	let mut speed = if name == "minecraft:shears" {
+		15.0
+	} else if name.ends_with("_sword") {
+		1.5
+	} else if TOOLS.iter().any(|o| name.ends_with(o)) {
+		TOOL_SPEEDS
+			.iter()
+			.find(|(prefix, _)| name.starts_with(prefix))
+			.map(|(_, speed)| *speed)
+			.unwrap_or(1.0)
+	} else {
+		1.0
+	};
+
+	// Эффективность ускоряет только инструменты, другие чары на скорость не влияют
+	if speed > 1.0
+		&& let Some(efficiency) = client.server.world.registries.id(ENCHANTMENT, EFFICIENCY)
+		&& let Some(DataComponent::Enchantments(enchantments)) = item
+			.components
+			.iter()
+			.find(|o| matches!(o, DataComponent::Enchantments(_)))
+		&& let Some(level) = enchantments
+			.iter()
+			.filter(|o| o.kind.0 == efficiency)
+			.map(|o| o.level.0)
+			.max()
+	{
+		speed += (level * level + 1) as f32;
+	}
+
+	speed
+}
+
+// Сколько тиков ломается блок, 0 - ломается сразу
+fn dig_ticks(client: &Arc<ClientContext>, block: &BlockState) -> Option<u32> {
+	let hardness = hardness(block);
+	if hardness < 0.0 {
+		return None;
+	}
+	if hardness == 0.0 {
+		return Some(0);
+	}
+
+	let item = client.entity_info().unwrap().held_item();
+	let progress = dig_speed(client, &item) / hardness / 30.0;
+	if progress >= 1.0 {
+		Some(0)
+	} else {
+		Some((1.0 / progress).ceil() as u32)
+	}
+}
+
+// Достает ли игрок до блока: от глаз до ближайшей точки блока, с запасом в блок как в ванилле
+fn can_reach(client: &Arc<ClientContext>, (x, y, z): (i32, i32, i32)) -> bool {
+	let info = client.entity_info().unwrap();
+	let (eye_x, eye_y, eye_z) = info.eye_position();
+
+	let dx = eye_x - eye_x.clamp(x as f64, x as f64 + 1.0);
+	let dy = eye_y - eye_y.clamp(y as f64, y as f64 + 1.0);
+	let dz = eye_z - eye_z.clamp(z as f64, z as f64 + 1.0);
+
+	let reach = info.game_mode().block_reach() + 1.0;
+	dx * dx + dy * dy + dz * dz <= reach * reach
+}
+
+// Стоит ли кто-то из игроков в блоке
+fn occupied(server: &Arc<ServerContext>, world: &World, (x, y, z): (i32, i32, i32)) -> bool {
+	server.players_in(&world.name).iter().any(|player| {
+		let info = player.entity_info().unwrap();
+		if info.game_mode() == GameMode::Spectator {
+			return false;
+		}
+
+		// Хитбокс игрока 0.6 x 1.8
+		let (px, py, pz) = info.position();
+		px + 0.3 > x as f64
+			&& px - 0.3 < x as f64 + 1.0
+			&& py + 1.8 > y as f64
+			&& py < y as f64 + 1.0
+			&& pz + 0.3 > z as f64
+			&& pz - 0.3 < z as f64 + 1.0
+	})
+}
+
+/// Рассылает Block Update всем игрокам мира, у которых загружен чанк с блоком
+pub fn send_block_update(
+	server: &Arc<ServerContext>,
+	world: &World,
+	(x, y, z): (i32, i32, i32),
+	block: &BlockState,
+) -> Result<(), ServerError> {
+	let packet = BlockUpdate {
+		location: Position {
+			x: x as i64,
+			y: y as i64,
+			z: z as i64,
+		},
+		block_id: VarInt(server.world.blocks.id(block).unwrap_or(1)),
+	};
+
+	for player in server.players_in(&world.name) {
+		if player.is_chunk_loaded(x >> 4, z >> 4) {
+			player.send(&packet)?;
+		}
+	}
+
+	Ok(())
+}
+
+// Показывает клиенту настоящий блок вместо того, что он предсказал
+fn revert_block(
+	client: &Arc<ClientContext>,
+	(x, y, z): (i32, i32, i32),
+) -> Result<(), ServerError> {
+	let world = client.world()?;
+	if !in_world(&world, y) || !client.is_chunk_loaded(x >> 4, z >> 4) {
+		return Ok(());
+	}
+
+	// Чанк, которого нет у сервера, не генерируем ради отката
+	let Some(block) = world.loaded_block(x, y, z) else {
+		return Ok(());
+	};
+	client.send(&BlockUpdate {
+		location: Position {
+			x: x as i64,
+			y: y as i64,
+			z: z as i64,
+		},
+		block_id: VarInt(client.server.world.blocks.id(&block).unwrap_or(1)),
+	})
+}
+
+// Ставит блок в мир и рассылает изменения блока и света
+fn change_block(
+	server: &Arc<ServerContext>,
+	world: &World,
+	(x, y, z): (i32, i32, i32),
+	block: BlockState,
+) -> Result<(), ServerError> {
+	let (_, changes) = world.set_block(x, y, z, block.clone())?;
+	send_block_update(server, world, (x, y, z), &block)?;
+	send_light_changes(server, world, &changes)
+}
+
+pub fn handle_player_action(
+	client: Arc<ClientContext>,
+	packet: PlayerAction,
+) -> Result<(), ServerError> {
+	let pos = position(packet.location);
+
+	match packet.status.0 {
+		0 => start_digging(&client, pos)?,
+		1 => {
+			client.entity_info().unwrap().stop_digging();
+		}
+		2 => finish_digging(&client, pos)?,
+		_ => return Ok(()), // дроп, еда и смена рук к блокам отношения не имеют
+	}
+
+	client.send(&AcknowledgeBlockChange {
+		sequence: packet.sequence,
+	})
+}
+
+// Блок, который игрок может ломать: в мире, в загруженном у него чанке и в досягаемости
+//
+// Проверки идут до чтения блока, чтобы координаты от клиента не заставляли сервер генерировать чанки
+fn target_block(
+	client: &Arc<ClientContext>,
+	world: &World,
+	pos: (i32, i32, i32),
+) -> Option<BlockState> {
+	let (x, y, z) = pos;
+	if !in_world(world, y) || !client.is_chunk_loaded(x >> 4, z >> 4) || !can_reach(client, pos) {
+		return None;
+	}
+
+	world.loaded_block(x, y, z).filter(|o| !o.is_air())
+}
+
+// Status 0: в креативе и для мгновенных блоков клиент больше ничего не пришлет, ломаем сразу
+fn start_digging(client: &Arc<ClientContext>, pos: (i32, i32, i32)) -> Result<(), ServerError> {
+	let info = client.entity_info().unwrap();
+	let game_mode = info.game_mode();
+	let world = client.world()?;
+
+	let Some(block) = target_block(client, &world, pos).filter(|_| game_mode.can_build()) else {
+		return revert_block(client, pos);
+	};
+
+	if game_mode == GameMode::Creative || dig_ticks(client, &block) == Some(0) {
+		info.stop_digging();
+		return break_block(client, &world, pos, block);
+	}
+
+	info.start_digging(pos);
+	Ok(())
+}
+
+// Status 2: ломаем, если этот блок начали ломать достаточно давно
+fn finish_digging(client: &Arc<ClientContext>, pos: (i32, i32, i32)) -> Result<(), ServerError> {
+	let info = client.entity_info().unwrap();
+	let world = client.world()?;
+
+	let Some(block) = target_block(client, &world, pos).filter(|_| info.game_mode().can_build())
+	else {
+		info.stop_digging();
+		return revert_block(client, pos);
+	};
+
+	let Some((_, since)) = info.stop_digging().filter(|(o, _)| *o == pos) else {
+		return revert_block(client, pos);
+	};
+	let Some(ticks) = dig_ticks(client, &block) else {
+		return revert_block(client, pos);
+	};
+
+	if since.elapsed() < TICK.mul_f32(ticks as f32 * DIG_TOLERANCE) {
+		return revert_block(client, pos);
+	}
+
+	break_block(client, &world, pos, block)
+}
+
+fn break_block(
+	client: &Arc<ClientContext>,
+	world: &World,
+	pos: (i32, i32, i32),
+	block: BlockState,
+) -> Result<(), ServerError> {
+	let mut cancel = false;
+	trigger_event!(client, block_break, pos, &block, &mut cancel);
+	if cancel {
+		return revert_block(client, pos);
+	}
+
+	change_block(&client.server, world, pos, BlockState::air())
+}
+
+pub fn handle_use_item_on(
+	client: Arc<ClientContext>,
+	packet: UseItemOn,
+) -> Result<(), ServerError> {
+	let clicked = position(packet.location);
+	let (dx, dy, dz) = FACES
+		.get(packet.face.0 as usize)
+		.copied()
+		.unwrap_or_default();
+
+	let world = client.world()?;
+	let registries = &client.server.world;
+
+	// Блоки читаются только после проверки досягаемости, см. target_block
+	let reachable = in_world(&world, clicked.1)
+		&& client.is_chunk_loaded(clicked.0 >> 4, clicked.2 >> 4)
+		&& can_reach(&client, clicked);
+	let target = if reachable
+		&& world
+			.loaded_block(clicked.0, clicked.1, clicked.2)
+			.is_some_and(|o| is_replaceable(&o))
+	{
+		clicked
+	} else {
+		(clicked.0 + dx, clicked.1 + dy, clicked.2 + dz)
+	};
+
+	let info = client.entity_info().unwrap();
+	let item = match packet.hand.0 {
+		0 => info.held_item(),
+		_ => info.inventory().offhand().clone(),
+	};
+	let hand = packet.hand.0;
+
+	// Блок, который ставится этим предметом, у блоков-предметов имя совпадает с блоком
+	let block = Some(item.item_id)
+		.filter(|_| !item.is_empty())
+		.and_then(|o| registries.items.name(o))
+		.filter(|o| *o != "minecraft:air")
+		.and_then(|o| registries.blocks.default_state(o))
+		.cloned();
+
+	match block {
+		Some(block) => place_block(&client, clicked, target, block, hand)?,
+		None => revert_block(&client, target)?,
+	}
+
+	client.send(&AcknowledgeBlockChange {
+		sequence: packet.sequence,
+	})
+}
+
+fn place_block(
+	client: &Arc<ClientContext>,
+	clicked: (i32, i32, i32),
+	pos: (i32, i32, i32),
+	block: BlockState,
+	hand: i32,
+) -> Result<(), ServerError> {
+	let game_mode = client.entity_info().unwrap().game_mode();
+	let world = client.world()?;
+
+	if !game_mode.can_build()
+		|| !in_world(&world, pos.1)
+		|| !client.is_chunk_loaded(pos.0 >> 4, pos.2 >> 4)
+		|| !can_reach(client, clicked)
+	{
+		return revert_block(client, pos);
+	}
+
+	if !world
+		.loaded_block(pos.0, pos.1, pos.2)
+		.is_some_and(|o| is_replaceable(&o))
+	{
+		return revert_block(client, pos);
+	}
+
+	// Сквозь непрозрачные блоки ходить нельзя, поэтому в игрока их не ставим
+	if light::opacity(&block) >= 15 && occupied(&client.server, &world, pos) {
+		return revert_block(client, pos);
+	}
+
+	let mut cancel = false;
+	trigger_event!(client, block_place, pos, &block, &mut cancel);
+	if cancel {
+		return revert_block(client, pos);
+	}
+
+	change_block(&client.server, &world, pos, block)?;
+
+	if game_mode != GameMode::Creative {
+		match hand {
+			0 => consume_held_item(client)?,
+			_ => {
+				let mut item = client.entity_info().unwrap().inventory().offhand().clone();
+				item.split(1);
+				set_player_slot(client, slots::OFFHAND, item)?;
+			}
+		}
+	}
+
+	Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::{config::Config, data::slot::components::Enchantment, player::context::test_player};
+
+	const PICKAXE: i32 = 900;
+
+	fn holding(item: Slot) -> Arc<ClientContext> {
+		let mut server = ServerContext::new(Arc::new(Config::default()));
+		server
+			.world
+			.items
+			.register("minecraft:diamond_pickaxe", PICKAXE);
+
+		let player = test_player(&Arc::new(server), "Steve", 0);
+		let info = player.entity_info().unwrap();
+		info.inventory().set_slot(slots::HOTBAR.start, item);
+		player
+	}
+
+	fn enchanted(name: &str, level: i32) -> Slot {
+		let registries = crate::world::registry::Registries::vanilla();
+		let kind = registries.id(ENCHANTMENT, name).unwrap();
+		Slot::new(PICKAXE, 1).with_component(DataComponent::Enchantments(vec![Enchantment {
+			kind: VarInt(kind),
+			level: VarInt(level),
+		}]))
+	}
+
+	#[test]
+	fn hand_and_tool() {
+		let stone = BlockState::new("minecraft:stone");
+
+		assert_eq!(dig_ticks(&holding(Slot::empty()), &stone), Some(45));
+		assert_eq!(dig_ticks(&holding(Slot::new(PICKAXE, 1)), &stone), Some(6));
+	}
+
+	#[test]
+	fn instant_and_unbreakable() {
+		let player = holding(Slot::empty());
+
+		assert_eq!(
+			dig_ticks(&player, &BlockState::new("minecraft:torch")),
+			Some(0)
+		);
+		assert_eq!(
+			dig_ticks(&player, &BlockState::new("minecraft:bedrock")),
+			None
+		);
+		assert!(dig_ticks(&player, &BlockState::new("minecraft:unknown_block")).unwrap() > 1000);
+	}
+
+	#[test]
+	fn only_efficiency_speeds_up() {
+		let stone = BlockState::new("minecraft:stone");
+
+		let efficiency = holding(enchanted(EFFICIENCY, 5));
+		assert_eq!(dig_ticks(&efficiency, &stone), Some(2));
+
+		let sharpness = holding(enchanted("minecraft:sharpness", 5));
+		assert_eq!(dig_ticks(&sharpness, &stone), Some(6));
+	}
+}
@@ -0,0 +1,619 @@
+use std::{collections::HashMap, sync::Arc};
+
+use rust_mc_proto::DataWriter;
+
+use crate::{
+	ServerError,
+	context::{ServerContext, World},
+	protocol::packets::{
+		VarInt,
+		clientbound::play::{ChunkDataAndUpdateLight, Heightmap, LightData, UpdateLight},
+	},
+	world::{
+		block::{BlockRegistry, BlockStateId, DIRECT_BITS},
+		chunk::{BlockState, Chunk, ChunkSection as StoredSection, SECTION_BIOMES, SECTION_BLOCKS},
+		dimension::DimensionType,
+		light::{LightChange, SECTION_LIGHT, SectionMask, section_light},
+		registry::Registries,
+	},
+};
+
+// Чанки в том виде, в котором они уходят клиенту
+//
+// На диске палитра хранит BlockState и может содержать уже неиспользуемые элементы,
+// а клиенту нужны айдишники глобальной палитры и самая компактная кодировка,
+// поэтому секции при отправке пересобираются заново
+
+pub const HEIGHTMAP_WORLD_SURFACE: i32 = 1;
+pub const HEIGHTMAP_MOTION_BLOCKING: i32 = 4;
+
+/// Сколько секций в масках света, они включают секцию под миром (бит 0) и над миром (последний бит)
+pub fn light_sections(dimension: &DimensionType) -> usize {
+	dimension.section_count() + 2
+}
+
+/// Маска всех секций света
+pub fn all_light_sections(dimension: &DimensionType) -> SectionMask {
+	SectionMask::all(light_sections(dimension))
+}
+
+// Блоки без коллизии, которые не попадают в MOTION_BLOCKING
+const NON_BLOCKING: &[&str] = &[
+	"minecraft:short_grass",
+	"minecraft:tall_grass",
+	"minecraft:fern",
+	"minecraft:large_fern",
+	"minecraft:dead_bush",
+	"minecraft:dandelion",
+	"minecraft:poppy",
+	"minecraft:torch",
+	"minecraft:wall_torch",
+	"minecraft:redstone_wire",
+	"minecraft:rail",
+	"minecraft:vine",
+];
+
+/// Как закодирован контейнер
+#[derive(Debug, Clone, PartialEq)]
+pub enum PaletteFormat {
+	/// Все значения одинаковые, данных нет
+	Single(i32),
+	/// Локальная палитра + индексы в ней
+	Indirect { bits: usize, palette: Vec<i32> },
+	/// Сразу айдишники из глобальной палитры
+	Direct { bits: usize },
+}
+
+// Контейнер айдишников (блоков или биомов) одной секции
+#[derive(Debug, Clone)]
+pub struct PalettedContainer {
+	ids: Vec<i32>,
+	min_bits: usize,
+	max_indirect_bits: usize,
+	direct_bits: usize,
+}
+
+impl PalettedContainer {
+	/// 4096 блоков, indirect палитра от 4 до 8 бит
+	pub fn blocks(ids: Vec<BlockStateId>) -> PalettedContainer {
+		PalettedContainer {
+			ids,
+			min_bits: 4,
+			max_indirect_bits: 8,
+			direct_bits: DIRECT_BITS,
+		}
+	}
+
+	/// 64 биома, indirect палитра от 1 до 3 бит, count - сколько всего биомов в реестре
+	pub fn biomes(ids: Vec<i32>, count: usize) -> PalettedContainer {
+		PalettedContainer {
+			ids,
+			min_bits: 1,
+			max_indirect_bits: 3,
+			direct_bits: bits_for(count),
+		}
+	}
+
+	pub fn len(&self) -> usize {
+		self.ids.len()
+	}
+
+	pub fn is_empty(&self) -> bool {
+		self.ids.is_empty()
+	}
+
+	pub fn get(&self, index: usize) -> i32 {
+		self.ids[index]
+	}
+
+	pub fn set(&mut self, index: usize, id: i32) {
+		self.ids[index] = id;
+	}
+
+	pub fn fill(&mut self, id: i32) {
+		self.ids.fill(id);
+	}
+
+	/// Самая компактная кодировка для текущих значений
+	pub fn format(&self) -> PaletteFormat {
+		let palette = self.palette();
+
+		if palette.len() <= 1 {
+			return PaletteFormat::Single(palette.first().copied().unwrap_or(0));
+		}
+
+		let bits = self.min_bits.max(bits_for(palette.len()));
+		if bits <= self.max_indirect_bits {
+			PaletteFormat::Indirect { bits, palette }
+		} else {
+			PaletteFormat::Direct {
+				bits: self.direct_bits,
+			}
+		}
+	}
+
+	// Уникальные значения в порядке появления
+	fn palette(&self) -> Vec<i32> {
+		let mut palette = Vec::new();
+		for id in self.ids.iter() {
+			if !palette.contains(id) {
+				palette.push(*id);
+			}
+		}
+		palette
+	}
+
+	/// Пишет контейнер в формате 1.21.5 (без длины массива лонгов)
+	pub fn write(&self, data: &mut Vec<u8>) -> Result<(), ServerError> {
+		match self.format() {
+			PaletteFormat::Single(id) => {
+				data.write_byte(0)?;
+				data.write_varint(id)?;
+			}
+			PaletteFormat::Indirect { bits, palette } => {
+				data.write_byte(bits as u8)?;
+				data.write_usize_varint(palette.len())?;
+				for id in palette.iter() {
+					data.write_varint(*id)?;
+				}
+
+				let indices = palette
+					.iter()
+					.enumerate()
+					.map(|(i, id)| (*id, i as u64))
+					.collect::<HashMap<_, _>>();
+
+				for long in pack(self.ids.iter().map(|o| indices[o]), bits, self.ids.len()) {
+					data.write_long(long)?;
+				}
+			}
+			PaletteFormat::Direct { bits } => {
+				data.write_byte(bits as u8)?;
+				for long in pack(self.ids.iter().map(|o| *o as u64), bits, self.ids.len()) {
+					data.write_long(long)?;
+				}
+			}
+		}
+
+		Ok(())
+	}
+}
+
+// Сколько бит нужно чтобы уместить `count` разных значений
+fn bits_for(count: usize) -> usize {
+	if count <= 1 {
+		0
+	} else {
+		(usize::BITS - (count - 1).leading_zeros()) as usize
+	}
+}
+
+// Упаковка значений в лонги, значения не переходят через границу лонга
+fn pack(values: impl Iterator<Item = u64>, bits: usize, size: usize) -> Vec<i64> {
+	let per_long = 64 / bits;
+	let mut longs = vec![0u64; size.div_ceil(per_long)];
+
+	for (i, value) in values.enumerate() {
+		longs[i / per_long] |= value << ((i % per_long) * bits);
+	}
+
+	longs.into_iter().map(|o| o as i64).collect()
+}
+
+// Секция чанка для отправки клиенту
+#[derive(Debug, Clone)]
+pub struct ChunkSection {
+	pub block_count: i16, // сколько не-воздушных блоков
+	pub blocks: PalettedContainer,
+	pub biomes: PalettedContainer,
+}
+
+impl ChunkSection {
+	pub fn from_section(
+		section: &StoredSection,
+		registry: &BlockRegistry,
+		registries: &Registries,
+	) -> ChunkSection {
+		// Айди считаем один раз на элемент палитры, блоки берут его по индексу в палитре
+		let palette = section
+			.blocks
+			.palette()
+			.iter()
+			.map(|o| {
+				(
+					registry.id(o).unwrap_or(1), // неизвестные блоки показываем камнем
+					o.is_air(),
+				)
+			})
+			.collect::<Vec<_>>();
+
+		let mut block_count = 0;
+		let blocks = (0..SECTION_BLOCKS)
+			.map(|i| {
+				let (id, air) = palette[section.blocks.palette_index(i)];
+				if !air {
+					block_count += 1;
+				}
+				id
+			})
+			.collect();
+
+		let biomes = (0..SECTION_BIOMES)
+			.map(|i| registries.biome_id(section.biomes.get(i)).unwrap_or(0))
+			.collect();
+
+		ChunkSection {
+			block_count,
+			blocks: PalettedContainer::blocks(blocks),
+			biomes: PalettedContainer::biomes(biomes, registries.biome_count()),
+		}
+	}
+
+	pub fn write(&self, data: &mut Vec<u8>) -> Result<(), ServerError> {
+		data.write_short(self.block_count)?;
+		self.blocks.write(data)?;
+		self.biomes.write(data)
+	}
+}
+
+// Чанк для Chunk Data and Update Light
+#[derive(Debug, Clone)]
+pub struct ChunkData {
+	pub x: i32,
+	pub z: i32,
+	pub sections: Vec<ChunkSection>,
+	pub heightmaps: Vec<Heightmap>,
+	pub light: LightData,
+}
+
+impl ChunkData {
+	pub fn from_chunk(
+		chunk: &Chunk,
+		registry: &BlockRegistry,
+		registries: &Registries,
+		dimension: &DimensionType,
+	) -> ChunkData {
+		let sections = world_sections(chunk, dimension)
+			.map(|(y, section)| match section {
+				Some(section) => ChunkSection::from_section(section, registry, registries),
+				None => ChunkSection::from_section(
+					&StoredSection::empty(y as i8, "minecraft:plains"),
+					registry,
+					registries,
+				),
+			})
+			.collect();
+
+		ChunkData {
+			x: chunk.x,
+			z: chunk.z,
+			sections,
+			heightmaps: heightmaps(chunk, dimension),
+			light: light_data(
+				chunk,
+				dimension,
+				all_light_sections(dimension),
+				all_light_sections(dimension),
+			),
+		}
+	}
+
+	pub fn to_packet(&self) -> Result<ChunkDataAndUpdateLight, ServerError> {
+		let mut data = Vec::new();
+		for section in self.sections.iter() {
+			section.write(&mut data)?;
+		}
+
+		Ok(ChunkDataAndUpdateLight {
+			x: self.x,
+			z: self.z,
+			heightmaps: self.heightmaps.clone(),
+			data,
+			block_entities: Vec::new(),
+			light: self.light.clone(),
+		})
+	}
+}
+
+/// Свет секций чанка из масок (в битах пакета, см. light_sections)
+///
+/// Полностью темные секции уходят в empty маски, над миром небесный свет всегда 15
+pub fn light_data(
+	chunk: &Chunk,
+	dimension: &DimensionType,
+	sky_mask: SectionMask,
+	block_mask: SectionMask,
+) -> LightData {
+	let (sky, block) = section_light(chunk, dimension);
+	let sections = light_sections(dimension);
+
+	let mut light = LightData {
+		sky_light_mask: Vec::new(),
+		block_light_mask: Vec::new(),
+		empty_sky_light_mask: Vec::new(),
+		empty_block_light_mask: Vec::new(),
+		sky_light: Vec::new(),
+		block_light: Vec::new(),
+	};
+	let (mut sky_bits, mut block_bits) = (SectionMask::new(), SectionMask::new());
+	let (mut empty_sky_bits, mut empty_block_bits) = (SectionMask::new(), SectionMask::new());
+
+	for bit in 0..sections {
+		let array = |arrays: &Vec<Vec<u8>>, above: u8| match bit {
+			0 => vec![0; SECTION_LIGHT],
+			bit if bit == sections - 1 => vec![above; SECTION_LIGHT],
+			bit => arrays[bit - 1].clone(),
+		};
+
+		if sky_mask.contains(bit) {
+			let array = array(&sky, 0xFF);
+			if array.iter().all(|o| *o == 0) {
+				empty_sky_bits.set(bit);
+			} else {
+				sky_bits.set(bit);
+				light.sky_light.push(array);
+			}
+		}
+
+		if block_mask.contains(bit) {
+			let array = array(&block, 0);
+			if array.iter().all(|o| *o == 0) {
+				empty_block_bits.set(bit);
+			} else {
+				block_bits.set(bit);
+				light.block_light.push(array);
+			}
+		}
+	}
+
+	light.sky_light_mask = sky_bits.to_longs();
+	light.block_light_mask = block_bits.to_longs();
+	light.empty_sky_light_mask = empty_sky_bits.to_longs();
+	light.empty_block_light_mask = empty_block_bits.to_longs();
+
+	light
+}
+
+/// Отправляет Update Light всем игрокам мира, у которых загружены поменявшиеся чанки
+pub fn send_light_changes(
+	server: &Arc<ServerContext>,
+	world: &World,
+	changes: &[LightChange],
+) -> Result<(), ServerError> {
+	for change in changes {
+		let viewers = server
+			.players_in(&world.name)
+			.into_iter()
+			.filter(|o| o.is_chunk_loaded(change.x, change.z))
+			.collect::<Vec<_>>();
+
+		if viewers.is_empty() {
+			continue;
+		}
+
+		let Some(chunk) = world.storage.loaded_chunk(change.x, change.z) else {
+			continue;
+		};
+
+		let packet = UpdateLight {
+			x: VarInt(change.x),
+			z: VarInt(change.z),
+			light: light_data(
+				&chunk.read().unwrap(),
+				&world.dimension,
+				change.sky.shifted(1),
+				change.block.shifted(1),
+			),
+		};
+
+		for viewer in viewers {
+			viewer.send(&packet)?;
+		}
+	}
+
+	Ok(())
+}
+
+// Секции в пределах высоты мира, снизу вверх, None если секции в чанке нет
+//
+// Ванильные чанки могут хранить секции за пределами мира (только со светом), клиенту они не нужны
+fn world_sections<'a>(
+	chunk: &'a Chunk,
+	dimension: &DimensionType,
+) -> impl DoubleEndedIterator<Item = (i32, Option<&'a StoredSection>)> {
+	let min_section = dimension.min_section();
+	(min_section..min_section + dimension.section_count() as i32).map(|y| (y, chunk.section(y)))
+}
+
+fn blocks_motion(state: &BlockState) -> bool {
+	!state.is_air() && !NON_BLOCKING.contains(&state.name.as_str())
+}
+
+/// WORLD_SURFACE и MOTION_BLOCKING для чанка
+///
+/// Значение это высота над низом мира самого верхнего подходящего блока + 1, 0 если блоков нет,
+/// размер значения ceil(log2(высота мира + 1)) бит
+pub fn heightmaps(chunk: &Chunk, dimension: &DimensionType) -> Vec<Heightmap> {
+	let height = dimension.height as usize;
+	let min_section = dimension.min_section();
+	let bits = bits_for(height + 1);
+
+	let mut surface = [0u64; 256];
+	let mut motion = [0u64; 256];
+
+	for (column, (surface, motion)) in surface.iter_mut().zip(motion.iter_mut()).enumerate() {
+		let (x, z) = (column & 15, column >> 4);
+
+		'sections: for (section_y, section) in world_sections(chunk, dimension).rev() {
+			// Секции целиком из воздуха не просматриваем
+			let Some(section) = section.filter(|o| !o.blocks.palette().iter().all(|o| o.is_air())) else {
+				continue;
+			};
+			let section_index = (section_y - min_section) as usize;
+
+			for y in (0..16).rev() {
+				let block = section.get_block(x, y, z);
+				let value = (section_index * 16 + y + 1) as u64;
+
+				if *surface == 0 && !block.is_air() {
+					*surface = value;
+				}
+				if blocks_motion(block) {
+					*motion = value;
+					break 'sections;
+				}
+			}
+		}
+	}
+
+	vec![
+		Heightmap {
+			kind: VarInt(HEIGHTMAP_WORLD_SURFACE),
+			data: pack(surface.into_iter(), bits, 256),
+		},
+		Heightmap {
+			kind: VarInt(HEIGHTMAP_MOTION_BLOCKING),
+			data: pack(motion.into_iter(), bits, 256),
+		},
+	]
+}
+
+#[cfg(test)]
+mod tests {
+	use std::io::Cursor;
+
+	use rust_mc_proto::DataReader;
+
+	use super::*;
+
+	// Читает контейнер обратно в айдишники, как это делает клиент
+	fn read_container(
+		data: &mut Cursor<Vec<u8>>,
+		size: usize,
+		max_indirect_bits: usize,
+	) -> Container {
+		let bits = data.read_byte().unwrap();
+		if bits == 0 {
+			return (bits, vec![data.read_varint().unwrap(); size]);
+		}
+
+		let palette = (bits as usize <= max_indirect_bits).then(|| {
+			let len = data.read_usize_varint().unwrap();
+			(0..len)
+				.map(|_| data.read_varint().unwrap())
+				.collect::<Vec<_>>()
+		});
+
+		let per_long = 64 / bits as usize;
+		let longs = (0..size.div_ceil(per_long))
+			.map(|_| data.read_long().unwrap() as u64)
+			.collect::<Vec<_>>();
+
+		let ids = (0..size)
+			.map(|i| {
+				let value = (longs[i / per_long] >> ((i % per_long) * bits as usize)) & ((1 << bits) - 1);
+				match &palette {
+					Some(palette) => palette[value as usize],
+					None => value as i32,
+				}
+			})
+			.collect();
+
+		(bits, ids)
+	}
+
+	// Биты и айдишники контейнера
+	type Container = (u8, Vec<i32>);
+
+	fn round_trip(section: &StoredSection) -> (i16, Container, Container) {
+		let registries = Registries::vanilla();
+		let mut data = Vec::new();
+		ChunkSection::from_section(section, &BlockRegistry::new(), &registries)
+			.write(&mut data)
+			.unwrap();
+
+		let mut data = Cursor::new(data);
+		let block_count = data.read_short().unwrap();
+		let blocks = read_container(&mut data, SECTION_BLOCKS, 8);
+		let biomes = read_container(&mut data, SECTION_BIOMES, 3);
+		assert_eq!(data.position() as usize, data.get_ref().len());
+
+		(block_count, blocks, biomes)
+	}
+
+	#[test]
+	fn single_value_section() {
+		let registries = Registries::vanilla();
+		let plains = registries.biome_id("minecraft:plains").unwrap();
+
+		let (count, blocks, biomes) = round_trip(&StoredSection::empty(0, "minecraft:plains"));
+		assert_eq!(count, 0);
+		assert_eq!(blocks, (0, vec![0; SECTION_BLOCKS]));
+		assert_eq!(biomes, (0, vec![plains; SECTION_BIOMES]));
+
+		let mut section = StoredSection::empty(0, "minecraft:plains");
+		for i in 0..SECTION_BLOCKS {
+			section.blocks.set(i, BlockState::new("minecraft:stone"));
+		}
+		let (count, blocks, _) = round_trip(&section);
+		assert_eq!(count, SECTION_BLOCKS as i16);
+		assert_eq!(blocks, (0, vec![1; SECTION_BLOCKS]));
+	}
+
+	#[test]
+	fn indirect_palette_section() {
+		let mut section = StoredSection::empty(0, "minecraft:plains");
+		let mut expected = vec![0; SECTION_BLOCKS];
+
+		for (i, id) in expected.iter_mut().enumerate() {
+			let (state, state_id) = match i % 7 {
+				0 => (BlockState::new("minecraft:stone"), 1),
+				1 => (BlockState::new("minecraft:dirt"), 10),
+				2 => (
+					BlockState::new("minecraft:grass_block").with_property("snowy", "true"),
+					8,
+				),
+				3 => (BlockState::new("minecraft:grass_block"), 9), // дефолтный стейт
+				4 => (BlockState::new("minecraft:not_a_block"), 1),
+				_ => continue,
+			};
+			section.blocks.set(i, state);
+			*id = state_id;
+		}
+		// Элемент палитры, который больше нигде не стоит
+		section.blocks.set(0, BlockState::new("minecraft:bedrock"));
+		section.blocks.set(0, BlockState::new("minecraft:stone"));
+
+		let (count, (bits, blocks), _) = round_trip(&section);
+		assert_eq!(count, expected.iter().filter(|o| **o != 0).count() as i16);
+		assert_eq!(bits, 4);
+		assert_eq!(blocks, expected);
+	}
+
+	#[test]
+	fn heightmaps_skip_non_blocking() {
+		let dimension = DimensionType::overworld();
+		let mut chunk = Chunk::empty(0, 0, dimension.min_section(), dimension.section_count());
+		chunk.set_block(0, 10, 0, BlockState::new("minecraft:stone"));
+		chunk.set_block(0, 11, 0, BlockState::new("minecraft:short_grass"));
+		chunk.set_block(1, -64, 0, BlockState::new("minecraft:bedrock"));
+
+		let bits = bits_for(dimension.height as usize + 1);
+		let unpack = |heightmap: &Heightmap, column: usize| {
+			let per_long = 64 / bits;
+			(heightmap.data[column / per_long] as u64 >> ((column % per_long) * bits)) & ((1 << bits) - 1)
+		};
+
+		let heightmaps = heightmaps(&chunk, &dimension);
+		let (surface, motion) = (&heightmaps[0], &heightmaps[1]);
+		assert_eq!(surface.kind.0, HEIGHTMAP_WORLD_SURFACE);
+		assert_eq!(motion.kind.0, HEIGHTMAP_MOTION_BLOCKING);
+
+		assert_eq!(unpack(surface, 0), 11 + 64 + 1);
+		assert_eq!(unpack(motion, 0), 10 + 64 + 1);
+		assert_eq!(unpack(surface, 1), 1);
+		assert_eq!(unpack(motion, 1), 1);
+		assert_eq!(unpack(surface, 2), 0);
+		assert_eq!(unpack(motion, 2), 0);
+	}
+}
@@ -0,0 +1,90 @@
+use std::sync::Arc;
+
+use crate::{
+	ServerError,
+	data::component::TextComponent,
+	player::context::ClientContext,
+	protocol::packets::{
+		VarInt,
+		clientbound::play::{CommandSuggestion, CommandSuggestionsResponse},
+		serverbound::play::CommandSuggestionsRequest,
+	},
+	trigger_event,
+};
+
+use super::helper::{send_entity_event, send_system_message};
+
+/// Отправляет клиенту дерево команд, доступных с его уровнем прав
+pub fn send_commands(client: &Arc<ClientContext>) -> Result<(), ServerError> {
+	let level = client.entity_info().unwrap().permission_level();
+	client.send(&client.server.commands().to_packet(level)?)
+}
+
+/// Меняет уровень прав игрока, клиенту уходит новый уровень и новое дерево команд
+pub fn set_permission_level(client: &Arc<ClientContext>, level: u8) -> Result<(), ServerError> {
+	let info = client.entity_info().unwrap();
+	info.set_permission_level(level);
+
+	// 24 - op level 0, 28 - op level 4
+	send_entity_event(client.clone(), info.entity_id, 24 + info.permission_level())?;
+	send_commands(client)
+}
+
+/// Выполняет команду, которую игрок написал в чат (без слеша)
+pub fn handle_chat_command(client: Arc<ClientContext>, command: String) -> Result<(), ServerError> {
+	let mut cancel = false;
+	trigger_event!(client, command, &command, &mut cancel);
+	if cancel {
+		return Ok(());
+	}
+
+	match client.server.commands().execute(client.clone(), &command) {
+		Err(ServerError::Command(message)) => send_system_message(
+			client,
+			TextComponent::builder().color("red").text(&message).build(),
+			false,
+		),
+		result => result,
+	}
+}
+
+// Клиент считает позиции в символах джавовой строки (UTF-16), а диспетчер - в байтах
+fn java_len(text: &str) -> i32 {
+	text.encode_utf16().count() as i32
+}
+
+pub fn handle_command_suggestions(
+	client: Arc<ClientContext>,
+	packet: CommandSuggestionsRequest,
+) -> Result<(), ServerError> {
+	// Клиент присылает текст вместе со слешем
+	let slash = packet.text.starts_with('/') as usize;
+	let input = &packet.text[slash..];
+
+	let (start, matches) = client.server.commands().suggest(client.clone(), input);
+
+	client.send(&CommandSuggestionsResponse {
+		id: packet.id,
+		start: VarInt(java_len(&packet.text[..start + slash])),
+		length: VarInt(java_len(&input[start..])),
+		matches: matches
+			.into_iter()
+			.map(|text| CommandSuggestion {
+				text,
+				tooltip: None,
+			})
+			.collect(),
+	})
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn positions_in_java_chars() {
+		assert_eq!(java_len("tp Steve"), 8);
+		assert_eq!(java_len("msg Ёжик"), 8);
+		assert_eq!(java_len("say 😀"), 6);
+	}
+}
@@ -1,30 +1,20 @@
-use std::io::Cursor;
 use std::sync::Arc;
 
-use rust_mc_proto::{DataWriter, Packet, read_packet};
-
-use crate::protocol::packet_id::*;
+use crate::protocol::packets::{KnownPack, clientbound, serverbound};
 use crate::{ServerError, player::context::ClientContext};
 
 pub fn send_update_tags(client: Arc<ClientContext>) -> Result<(), ServerError> {
-	// TODO: rewrite this hardcode bullshit
-
-	client.write_packet(&Packet::from_bytes(
-		clientbound::configuration::UPDATE_TAGS,
-		include_bytes!("update-tags.bin"),
-	))
+	client.send(&client.server.world.registries.update_tags())
 }
 
-pub fn send_registry_data(client: Arc<ClientContext>) -> Result<(), ServerError> {
-	// TODO: rewrite this hardcode bullshit
-
-	let mut registry_data = Cursor::new(include_bytes!("registry-data.bin"));
-
-	while let Ok(mut packet) = read_packet(&mut registry_data, None) {
-		packet.set_id(clientbound::configuration::REGISTRY_DATA);
-		client.write_packet(&packet)?;
+/// known - паки, которые клиент знает, записи из них уходят без данных
+pub fn send_registry_data(
+	client: Arc<ClientContext>,
+	known: &[KnownPack],
+) -> Result<(), ServerError> {
+	for packet in client.server.world.registries.registry_data(known) {
+		client.send(&packet)?;
 	}
-
 	Ok(())
 }
 
@@ -32,20 +22,18 @@ pub fn send_registry_data(client: Arc<ClientContext>) -> Result<(), ServerError>
 pub fn handle_configuration_state(
 	client: Arc<ClientContext>, // Контекст клиента
 ) -> Result<(), ServerError> {
-	let mut packet = Packet::empty(clientbound::configuration::FEATURE_FLAGS);
-	packet.write_varint(1)?;
-	packet.write_string("minecraft:vanilla")?;
-	client.write_packet(&packet)?;
-
-	let mut packet = Packet::empty(clientbound::configuration::KNOWN_PACKS);
-	packet.write_varint(1)?;
-	packet.write_string("minecraft")?;
-	packet.write_string("core")?;
-	packet.write_string("1.21.5")?;
-	client.write_packet(&packet)?;
-
-	client.read_packet(&[serverbound::configuration::KNOWN_PACKS])?;
-
-	send_registry_data(client.clone())?;
+	client.send(&clientbound::configuration::FeatureFlags {
+		flags: vec!["minecraft:vanilla".to_string()],
+	})?;
+
+	// Клиент отвечает, какие из предложенных паков у него есть
+	client.send(&clientbound::configuration::KnownPacks {
+		packs: client.server.world.registries.known_packs().to_vec(),
+	})?;
+	let known = client
+		.receive::<serverbound::configuration::KnownPacks>()?
+		.packs;
+
+	send_registry_data(client.clone(), &known)?;
 	send_update_tags(client.clone())
 }
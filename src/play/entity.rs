@@ -0,0 +1,586 @@
+use std::sync::Arc;
+
+use crate::{
+	ServerError,
+	context::{ServerContext, World},
+	player::context::ClientContext,
+	protocol::packets::{
+		Angle, VarInt,
+		clientbound::play::{
+			RemoveEntities, SetEntityMetadata, SetHeadRotation, SpawnEntity, TeleportEntity,
+			UpdateEntityPosition, UpdateEntityPositionAndRotation, UpdateEntityRotation,
+		},
+	},
+	world::entity::Entity,
+};
+
+// Отслеживание сущностей игроками
+//
+// У каждого игрока есть набор сущностей, которые заспавнены у его клиента (PlayerEntityInfo::tracked)
+// Сущность видно, если она в том же мире и ее чанк в пределах дальности прорисовки игрока
+// Движение рассылается только тем, у кого сущность заспавнена
+
+/// Дальность прорисовки игрока в чанках, по ней же отправляются чанки
+pub fn view_distance(client: &Arc<ClientContext>) -> i32 {
+	client
+		.client_info()
+		.map(|o| o.view_distance as i32 / 2)
+		.unwrap_or(2)
+}
+
+// Должна ли сущность быть заспавнена у игрока, свою сущность игрок не видит
+fn can_see(client: &Arc<ClientContext>, entity: &Entity) -> bool {
+	if entity.player == Some(client.id) {
+		return false;
+	}
+
+	let Some(info) = client.entity_info() else {
+		return false;
+	};
+
+	if info.entity.world() != entity.world() {
+		return false;
+	}
+
+	let (x, z) = info.entity.chunk();
+	let (entity_x, entity_z) = entity.chunk();
+	let distance = view_distance(client);
+
+	(x - entity_x).abs() <= distance && (z - entity_z).abs() <= distance
+}
+
+// Скорость в Spawn Entity передается в 1/8000 блока за тик
+fn encode_velocity(velocity: f64) -> i16 {
+	(velocity * 8000.0).clamp(i16::MIN as f64, i16::MAX as f64) as i16
+}
+
+// Дельта позиции в 1/4096 блока, None если не влезает в i16
+fn encode_delta(prev: f64, current: f64) -> Option<i16> {
+	let delta = (current * 4096.0).round() as i64 - (prev * 4096.0).round() as i64;
+	i16::try_from(delta).ok()
+}
+
+fn spawn_packet(entity: &Entity) -> SpawnEntity {
+	let (x, y, z) = entity.position();
+	let (yaw, pitch) = entity.rotation();
+	let (velocity_x, velocity_y, velocity_z) = entity.velocity();
+
+	SpawnEntity {
+		entity_id: VarInt(entity.id),
+		uuid: entity.uuid,
+		entity_type: VarInt(entity.entity_type),
+		x,
+		y,
+		z,
+		pitch: Angle(pitch),
+		yaw: Angle(yaw),
+		head_yaw: Angle(entity.head_yaw()),
+		data: VarInt(entity.data),
+		velocity_x: encode_velocity(velocity_x),
+		velocity_y: encode_velocity(velocity_y),
+		velocity_z: encode_velocity(velocity_z),
+	}
+}
+
+// Спавн вместе со всеми выставленными метаданными
+fn send_spawn(client: &Arc<ClientContext>, entity: &Entity) -> Result<(), ServerError> {
+	client.send(&spawn_packet(entity))?;
+
+	let metadata = entity.metadata().all();
+	if !metadata.0.is_empty() {
+		client.send(&SetEntityMetadata {
+			entity_id: VarInt(entity.id),
+			metadata,
+		})?;
+	}
+
+	Ok(())
+}
+
+/// Игроки, у которых заспавнена эта сущность
+pub fn viewers(server: &Arc<ServerContext>, entity: &Entity) -> Vec<Arc<ClientContext>> {
+	server
+		.players()
+		.into_iter()
+		.filter(|o| o.entity_info().is_some_and(|o| o.is_tracking(entity.id)))
+		.collect()
+}
+
+/// Спавнит у игрока сущности, которые вошли в дальность прорисовки, и убирает вышедшие
+pub fn update_tracking(client: &Arc<ClientContext>) -> Result<(), ServerError> {
+	let info = client.entity_info().unwrap();
+	let world = client.world()?;
+	let entities = &world.entities;
+
+	let mut spawned = Vec::new();
+	let mut removed = Vec::new();
+
+	{
+		let mut tracked = info.tracked();
+
+		for entity in entities.all() {
+			let visible = can_see(client, &entity);
+			let is_tracked = tracked.contains(&entity.id);
+
+			if visible && !is_tracked {
+				tracked.insert(entity.id);
+				spawned.push(entity);
+			} else if !visible && is_tracked {
+				tracked.remove(&entity.id);
+				removed.push(VarInt(entity.id));
+			}
+		}
+
+		// Сущности, которые уже убрали из мира
+		tracked.retain(|id| {
+			let alive = entities.contains(*id);
+			if !alive {
+				removed.push(VarInt(*id));
+			}
+			alive
+		});
+	}
+
+	if !removed.is_empty() {
+		client.send(&RemoveEntities {
+			entity_ids: removed,
+		})?;
+	}
+
+	for entity in spawned {
+		send_spawn(client, &entity)?;
+	}
+
+	Ok(())
+}
+
+/// Спавнит или убирает сущность у игроков, для которых она вошла в дальность или вышла из нее
+pub fn update_viewers(server: &Arc<ServerContext>, entity: &Entity) -> Result<(), ServerError> {
+	for player in server.players() {
+		let Some(info) = player.entity_info() else {
+			continue;
+		};
+
+		let visible = can_see(&player, entity);
+		let is_tracked = info.is_tracking(entity.id);
+
+		if visible && !is_tracked {
+			info.tracked().insert(entity.id);
+			send_spawn(&player, entity)?;
+		} else if !visible && is_tracked {
+			info.tracked().remove(&entity.id);
+			player.send(&RemoveEntities {
+				entity_ids: vec![VarInt(entity.id)],
+			})?;
+		}
+	}
+
+	Ok(())
+}
+
+/// Добавляет сущность в мир и спавнит ее у игроков рядом
+pub fn spawn_entity(
+	server: &Arc<ServerContext>,
+	world: &World,
+	entity: Arc<Entity>,
+) -> Result<(), ServerError> {
+	entity.set_world(&world.name);
+	world.entities.add(entity.clone());
+	update_viewers(server, &entity)
+}
+
+/// Убирает сущность из ее мира и у всех, у кого она была заспавнена
+pub fn remove_entity(server: &Arc<ServerContext>, entity_id: i32) -> Result<(), ServerError> {
+	for world in server.world.worlds() {
+		world.entities.remove(entity_id);
+	}
+
+	for player in server.players() {
+		let Some(info) = player.entity_info() else {
+			continue;
+		};
+
+		if info.tracked().remove(&entity_id) {
+			player.send(&RemoveEntities {
+				entity_ids: vec![VarInt(entity_id)],
+			})?;
+		}
+	}
+
+	Ok(())
+}
+
+/// Двигает и поворачивает сущность, зрителям уходит дельта или Teleport Entity, если дельта не влезла в i16
+///
+/// Если сущность перешла в другой чанк, пересчитывается, кому она видна,
+/// а для сущности игрока еще и какие сущности видны ему самому
+pub fn move_entity(
+	server: &Arc<ServerContext>,
+	entity: &Arc<Entity>,
+	position: (f64, f64, f64),
+	rotation: Option<(f32, f32)>,
+	on_ground: bool,
+) -> Result<(), ServerError> {
+	let prev = entity.position();
+	let prev_chunk = entity.chunk();
+
+	entity.set_position(position);
+	entity.set_on_ground(on_ground);
+	if let Some((yaw, pitch)) = rotation {
+		entity.set_rotation((yaw, pitch));
+		entity.set_head_yaw(yaw);
+	}
+
+	let (x, y, z) = position;
+	let (yaw, pitch) = entity.rotation();
+	let (velocity_x, velocity_y, velocity_z) = entity.velocity();
+	let entity_id = VarInt(entity.id);
+
+	let moved = prev != position;
+	let delta = (
+		encode_delta(prev.0, x),
+		encode_delta(prev.1, y),
+		encode_delta(prev.2, z),
+	);
+
+	for viewer in viewers(server, entity) {
+		match delta {
+			_ if !moved => {
+				if rotation.is_some() {
+					viewer.send(&UpdateEntityRotation {
+						entity_id,
+						yaw: Angle(yaw),
+						pitch: Angle(pitch),
+						on_ground,
+					})?;
+				}
+			}
+			(Some(delta_x), Some(delta_y), Some(delta_z)) => match rotation {
+				Some(_) => viewer.send(&UpdateEntityPositionAndRotation {
+					entity_id,
+					delta_x,
+					delta_y,
+					delta_z,
+					yaw: Angle(yaw),
+					pitch: Angle(pitch),
+					on_ground,
+				})?,
+				None => viewer.send(&UpdateEntityPosition {
+					entity_id,
+					delta_x,
+					delta_y,
+					delta_z,
+					on_ground,
+				})?,
+			},
+			_ => viewer.send(&TeleportEntity {
+				entity_id,
+				x,
+				y,
+				z,
+				velocity_x,
+				velocity_y,
+				velocity_z,
+				yaw,
+				pitch,
+				on_ground,
+			})?,
+		}
+
+		if rotation.is_some() {
+			viewer.send(&SetHeadRotation {
+				entity_id,
+				head_yaw: Angle(yaw),
+			})?;
+		}
+	}
+
+	if entity.chunk() != prev_chunk {
+		update_viewers(server, entity)?;
+
+		let client = entity.player.and_then(|o| server.clients.get(o));
+		if let Some(client) = client {
+			update_tracking(&client)?;
+		}
+	}
+
+	Ok(())
+}
+
+/// Рассылает измененные метаданные всех сущностей, вызывается раз в тик
+///
+/// Игроку его собственные метаданные тоже уходят, например поза и части скина
+/// Ошибки отправки игнорируются, отвалившийся игрок не должен мешать остальным
+pub fn flush_metadata(server: &Arc<ServerContext>) {
+	let entities = server
+		.world
+		.worlds()
+		.into_iter()
+		.flat_map(|o| o.entities.all());
+
+	for entity in entities {
+		let metadata = {
+			let mut metadata = entity.metadata();
+			if !metadata.is_dirty() {
+				continue;
+			}
+			metadata.take_dirty()
+		};
+
+		let packet = SetEntityMetadata {
+			entity_id: VarInt(entity.id),
+			metadata,
+		};
+
+		for viewer in viewers(server, &entity) {
+			let _ = viewer.send(&packet);
+		}
+
+		let owner = entity.player.and_then(|o| server.clients.get(o));
+		if let Some(owner) = owner {
+			let _ = owner.send(&packet);
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use rust_mc_proto::Packet;
+	use uuid::Uuid;
+
+	use super::*;
+	use crate::{
+		config::{Config, WorldSettings},
+		data::metadata::{MetadataValue, Pose, fields},
+		player::context::test_player,
+		protocol::{
+			packet_id::clientbound::play::{
+				REMOVE_ENTITIES, SET_ENTITY_METADATA, SPAWN_ENTITY, TELEPORT_ENTITY, UPDATE_ENTITY_POSITION,
+			},
+			packets::PacketData,
+		},
+		world::entity::ITEM,
+	};
+
+	// Сервер с пустым миром world, дальность прорисовки у тестовых игроков 2 чанка
+	fn server() -> (Arc<ServerContext>, Arc<World>) {
+		let server = Arc::new(ServerContext::new(Arc::new(Config::default())));
+		let path = std::env::temp_dir().join(format!("rust_mc_serv_entity_{}", std::process::id()));
+		let world = server
+			.world
+			.create_world(&WorldSettings::new(
+				"world",
+				"overworld",
+				"void",
+				path.to_str().unwrap(),
+			))
+			.unwrap();
+		(server, world)
+	}
+
+	fn player(
+		server: &Arc<ServerContext>,
+		world: &World,
+		name: &str,
+		position: (f64, f64, f64),
+	) -> Arc<ClientContext> {
+		let client = test_player(server, name, 0);
+		let entity = client.entity_info().unwrap().entity.clone();
+		entity.set_position(position);
+		// Как при входе: сначала сущность игрока, потом то, что видит он сам
+		spawn_entity(server, world, entity).unwrap();
+		update_tracking(&client).unwrap();
+		client.take_sent();
+		client
+	}
+
+	fn item(server: &Arc<ServerContext>, position: (f64, f64, f64)) -> Arc<Entity> {
+		let id = server.world.next_entity_id();
+		Arc::new(Entity::new(id, Uuid::from_u128(id as u128), ITEM).with_position(position))
+	}
+
+	fn ids(packets: &[Packet]) -> Vec<u8> {
+		packets.iter().map(|o| o.id()).collect()
+	}
+
+	fn removed(packet: &Packet) -> Vec<i32> {
+		let mut packet = Packet::from_bytes(packet.id(), packet.get_bytes());
+		RemoveEntities::decode(&mut packet)
+			.unwrap()
+			.entity_ids
+			.into_iter()
+			.map(|o| o.0)
+			.collect()
+	}
+
+	fn metadata(packet: &Packet) -> SetEntityMetadata {
+		let mut packet = Packet::from_bytes(packet.id(), packet.get_bytes());
+		SetEntityMetadata::decode(&mut packet).unwrap()
+	}
+
+	#[test]
+	fn spawns_only_in_view_distance() {
+		let (server, world) = server();
+		let client = player(&server, &world, "Steve", (0.5, 64.0, 0.5));
+
+		let near = item(&server, (40.0, 64.0, -20.0)); // чанк (2, -2)
+		let far = item(&server, (48.0, 64.0, 0.0)); // чанк (3, 0)
+		spawn_entity(&server, &world, near.clone()).unwrap();
+		spawn_entity(&server, &world, far.clone()).unwrap();
+
+		let sent = client.take_sent();
+		assert_eq!(ids(&sent), [SPAWN_ENTITY]);
+		let spawn =
+			SpawnEntity::decode(&mut Packet::from_bytes(SPAWN_ENTITY, sent[0].get_bytes())).unwrap();
+		assert_eq!(spawn.entity_id.0, near.id);
+		assert_eq!(spawn.entity_type.0, ITEM);
+		assert_eq!((spawn.x, spawn.z), (40.0, -20.0));
+
+		let info = client.entity_info().unwrap();
+		assert!(info.is_tracking(near.id));
+		assert!(!info.is_tracking(far.id));
+	}
+
+	#[test]
+	fn players_see_each_other_but_not_themselves() {
+		let (server, world) = server();
+		let steve = player(&server, &world, "Steve", (0.0, 64.0, 0.0));
+		let alex = player(&server, &world, "Alex", (20.0, 64.0, 0.0));
+
+		let steve_id = steve.entity_info().unwrap().entity.id;
+		let alex_id = alex.entity_info().unwrap().entity.id;
+
+		assert!(steve.entity_info().unwrap().is_tracking(alex_id));
+		assert!(alex.entity_info().unwrap().is_tracking(steve_id));
+		assert!(!steve.entity_info().unwrap().is_tracking(steve_id));
+		assert!(!alex.entity_info().unwrap().is_tracking(alex_id));
+	}
+
+	#[test]
+	fn movement_goes_to_viewers() {
+		let (server, world) = server();
+		let client = player(&server, &world, "Steve", (0.0, 64.0, 0.0));
+		let entity = item(&server, (1.0, 64.0, 1.0));
+		spawn_entity(&server, &world, entity.clone()).unwrap();
+		client.take_sent();
+
+		// Маленькое смещение - дельтой, большое - телепортом
+		move_entity(&server, &entity, (2.0, 64.0, 1.0), None, true).unwrap();
+		assert_eq!(ids(&client.take_sent()), [UPDATE_ENTITY_POSITION]);
+
+		move_entity(&server, &entity, (2.0, 64.0, 30.0), None, true).unwrap();
+		assert_eq!(ids(&client.take_sent()), [TELEPORT_ENTITY]);
+
+		// Ушла за дальность прорисовки - убирается, вернулась - спавнится заново
+		move_entity(&server, &entity, (2.0, 64.0, 100.0), None, true).unwrap();
+		let sent = client.take_sent();
+		assert_eq!(ids(&sent), [TELEPORT_ENTITY, REMOVE_ENTITIES]);
+		assert_eq!(removed(&sent[1]), [entity.id]);
+		assert!(!client.entity_info().unwrap().is_tracking(entity.id));
+
+		move_entity(&server, &entity, (2.0, 64.0, 2.0), None, true).unwrap();
+		assert_eq!(ids(&client.take_sent()), [SPAWN_ENTITY]);
+	}
+
+	#[test]
+	fn moving_player_updates_own_tracking() {
+		let (server, world) = server();
+		let client = player(&server, &world, "Steve", (0.0, 64.0, 0.0));
+		let entity = item(&server, (100.0, 64.0, 0.0)); // чанк (6, 0)
+		spawn_entity(&server, &world, entity.clone()).unwrap();
+		assert!(client.take_sent().is_empty());
+
+		let own = client.entity_info().unwrap().entity.clone();
+		move_entity(&server, &own, (70.0, 64.0, 0.0), None, true).unwrap();
+		assert_eq!(ids(&client.take_sent()), [SPAWN_ENTITY]);
+
+		move_entity(&server, &own, (0.0, 64.0, 0.0), None, true).unwrap();
+		let sent = client.take_sent();
+		assert_eq!(ids(&sent), [REMOVE_ENTITIES]);
+		assert_eq!(removed(&sent[0]), [entity.id]);
+	}
+
+	#[test]
+	fn removal_goes_only_to_trackers() {
+		let (server, world) = server();
+		let near = player(&server, &world, "Steve", (0.0, 64.0, 0.0));
+		let far = player(&server, &world, "Alex", (500.0, 64.0, 0.0));
+		let entity = item(&server, (1.0, 64.0, 1.0));
+		spawn_entity(&server, &world, entity.clone()).unwrap();
+		near.take_sent();
+
+		remove_entity(&server, entity.id).unwrap();
+
+		let sent = near.take_sent();
+		assert_eq!(ids(&sent), [REMOVE_ENTITIES]);
+		assert_eq!(removed(&sent[0]), [entity.id]);
+		assert!(far.take_sent().is_empty());
+		assert!(!world.entities.contains(entity.id));
+	}
+
+	#[test]
+	fn other_worlds_are_invisible() {
+		let (server, world) = server();
+		let client = player(&server, &world, "Steve", (0.0, 64.0, 0.0));
+
+		let entity = item(&server, (1.0, 64.0, 1.0));
+		entity.set_world("nether");
+		world.entities.add(entity.clone());
+		update_tracking(&client).unwrap();
+
+		assert!(client.take_sent().is_empty());
+		assert!(!client.entity_info().unwrap().is_tracking(entity.id));
+	}
+
+	#[test]
+	fn spawn_sends_set_metadata() {
+		let (server, world) = server();
+		let client = player(&server, &world, "Steve", (0.0, 64.0, 0.0));
+
+		let entity = item(&server, (1.0, 64.0, 1.0));
+		entity.metadata().set(fields::entity::NO_GRAVITY, true);
+		spawn_entity(&server, &world, entity.clone()).unwrap();
+
+		let sent = client.take_sent();
+		assert_eq!(ids(&sent), [SPAWN_ENTITY, SET_ENTITY_METADATA]);
+		let packet = metadata(&sent[1]);
+		assert_eq!(packet.entity_id.0, entity.id);
+		assert_eq!(packet.metadata.0, [(5, MetadataValue::Boolean(true))]);
+	}
+
+	#[test]
+	fn dirty_metadata_goes_to_viewers_and_owner() {
+		let (server, world) = server();
+		let steve = player(&server, &world, "Steve", (0.0, 64.0, 0.0));
+		let alex = player(&server, &world, "Alex", (10.0, 64.0, 0.0));
+		let far = player(&server, &world, "Herobrine", (500.0, 64.0, 0.0));
+		let steve_id = steve.entity_info().unwrap().entity.id;
+		steve.take_sent(); // спавны вошедших позже
+		alex.take_sent();
+
+		steve
+			.entity_info()
+			.unwrap()
+			.entity
+			.metadata()
+			.set(fields::entity::POSE, Pose::Sneaking);
+		flush_metadata(&server);
+
+		for client in [&steve, &alex] {
+			let sent = client.take_sent();
+			assert_eq!(ids(&sent), [SET_ENTITY_METADATA]);
+			let packet = metadata(&sent[0]);
+			assert_eq!(packet.entity_id.0, steve_id);
+			assert_eq!(
+				packet.metadata.0,
+				[(6, MetadataValue::Pose(Pose::Sneaking))]
+			);
+		}
+		assert!(far.take_sent().is_empty());
+
+		// Уже отправленные поля повторно не уходят
+		flush_metadata(&server);
+		assert!(steve.take_sent().is_empty());
+		assert!(alex.take_sent().is_empty());
+	}
+}
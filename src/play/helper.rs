@@ -3,13 +3,27 @@ use std::{
 	time::{SystemTime, UNIX_EPOCH},
 };
 
-use rust_mc_proto::{DataReader, DataWriter, Packet};
+use log::info;
+use rust_mc_proto::{DataWriter, Packet};
 
 use crate::{
 	ServerError,
-	data::{ReadWriteNBT, component::TextComponent},
-	player::context::ClientContext,
-	protocol::packet_id::{clientbound, serverbound},
+	data::component::TextComponent,
+	player::context::{ClientContext, GameMode},
+	protocol::{
+		packet_id,
+		packets::{
+			VarInt,
+			clientbound::{
+				self,
+				play::{
+					EntityAnimation, EntityEvent, GameEvent, SetCenterChunk, SetHealth, SetHeldItem,
+					SynchronizePlayerPosition, SystemChatMessage, UnloadChunk,
+				},
+			},
+			serverbound,
+		},
+	},
 };
 
 pub fn send_game_event(
@@ -17,12 +31,31 @@ pub fn send_game_event(
 	event: u8,
 	value: f32,
 ) -> Result<(), ServerError> {
-	let mut packet = Packet::empty(clientbound::play::GAME_EVENT);
+	client.send(&GameEvent { event, value })
+}
+
+/// Меняет режим игры и сообщает об этом клиенту
+pub fn set_game_mode(client: Arc<ClientContext>, game_mode: GameMode) -> Result<(), ServerError> {
+	client.entity_info().unwrap().set_game_mode(game_mode);
+	send_game_event(client, 3, game_mode.id() as f32) // 3 - Change game mode
+}
 
-	packet.write_byte(event)?;
-	packet.write_float(value)?;
+/// Отправляет клиенту здоровье с сервера, голода пока нет, поэтому еда всегда полная
+pub fn send_health(client: &Arc<ClientContext>) -> Result<(), ServerError> {
+	client.send(&SetHealth {
+		health: client.entity_info().unwrap().health(),
+		food: VarInt(20),
+		saturation: 5.0,
+	})
+}
 
-	client.write_packet(&packet)
+/// Выбирает слот хотбара у клиента
+pub fn set_held_slot(client: &Arc<ClientContext>, slot: usize) -> Result<(), ServerError> {
+	let info = client.entity_info().unwrap();
+	info.set_held_slot(slot);
+	client.send(&SetHeldItem {
+		slot: VarInt(info.held_slot() as i32),
+	})
 }
 
 pub fn send_entity_event(
@@ -30,12 +63,7 @@ pub fn send_entity_event(
 	entity_id: i32,
 	status: u8,
 ) -> Result<(), ServerError> {
-	let mut packet = Packet::empty(clientbound::play::ENTITY_EVENT);
-
-	packet.write_int(entity_id)?;
-	packet.write_byte(status)?;
-
-	client.write_packet(&packet)
+	client.send(&EntityEvent { entity_id, status })
 }
 
 pub fn send_entity_animation(
@@ -43,14 +71,10 @@ pub fn send_entity_animation(
 	entity_id: i32,
 	animation: u8,
 ) -> Result<(), ServerError> {
-	let mut packet = Packet::empty(clientbound::play::ENTITY_ANIMATION);
-
-	packet.write_varint(entity_id)?;
-	packet.write_byte(animation)?;
-
-	receiver.write_packet(&packet)?;
-
-	Ok(())
+	receiver.send(&EntityAnimation {
+		entity_id: VarInt(entity_id),
+		animation,
+	})
 }
 
 pub fn play_global_sound(
@@ -60,7 +84,7 @@ pub fn play_global_sound(
 	pitch: f32,
 	category: i32,
 ) -> Result<(), ServerError> {
-	let mut packet = Packet::empty(clientbound::play::ENTITY_SOUND_EFFECT);
+	let mut packet = Packet::empty(packet_id::clientbound::play::ENTITY_SOUND_EFFECT);
 
 	let timestamp = SystemTime::now()
 		.duration_since(UNIX_EPOCH)
@@ -82,6 +106,7 @@ pub fn play_global_sound(
 	Ok(())
 }
 
+#[allow(clippy::too_many_arguments)]
 pub fn sync_player_pos(
 	client: Arc<ClientContext>,
 	x: f64,
@@ -100,50 +125,59 @@ pub fn sync_player_pos(
 		.as_millis()
 		& 0xFFFFFFFF) as i32;
 
-	let mut packet = Packet::empty(clientbound::play::SYNCHRONIZE_PLAYER_POSITION);
-
-	packet.write_varint(timestamp)?;
-	packet.write_double(x)?;
-	packet.write_double(y)?;
-	packet.write_double(z)?;
-	packet.write_double(vel_x)?;
-	packet.write_double(vel_y)?;
-	packet.write_double(vel_z)?;
-	packet.write_float(yaw)?;
-	packet.write_float(pitch)?;
-	packet.write_int(flags)?;
-
-	client.write_packet(&packet)?;
-
-	Ok(())
+	client.send(&SynchronizePlayerPosition {
+		teleport_id: VarInt(timestamp),
+		x,
+		y,
+		z,
+		velocity_x: vel_x,
+		velocity_y: vel_y,
+		velocity_z: vel_z,
+		yaw,
+		pitch,
+		flags,
+	})
 }
 
 pub fn set_center_chunk(client: Arc<ClientContext>, x: i32, z: i32) -> Result<(), ServerError> {
-	let mut packet = Packet::empty(clientbound::play::SET_CENTER_CHUNK);
-
-	packet.write_varint(x)?;
-	packet.write_varint(z)?;
-
-	client.write_packet(&packet)
+	client.send(&SetCenterChunk {
+		x: VarInt(x),
+		z: VarInt(z),
+	})
 }
 
+/// Отправляет keep alive, ответ приходит в основной цикл игрока и разбирается в handle_keep_alive
+///
+/// Если клиент не ответил на прошлый keep alive, соединение закрывается
 pub fn send_keep_alive(client: Arc<ClientContext>) -> Result<(), ServerError> {
 	let timestamp = SystemTime::now()
 		.duration_since(UNIX_EPOCH)
 		.unwrap()
-		.as_secs() as i64;
+		.as_millis() as i64;
 
-	let mut packet = Packet::empty(clientbound::play::KEEP_ALIVE);
-	packet.write_long(timestamp)?;
-	client.write_packet(&packet)?;
+	if client
+		.entity_info()
+		.unwrap()
+		.set_keep_alive(timestamp)
+		.is_some()
+	{
+		info!("{} не ответил на keep alive", client.addr());
+		client.close();
+		return Err(ServerError::ConnectionClosed);
+	}
+
+	client.send(&clientbound::play::KeepAlive { id: timestamp })
+}
 
-	let mut packet = client.read_packet(&[serverbound::play::KEEP_ALIVE])?;
-	let timestamp2 = packet.read_long()?;
-	if timestamp2 != timestamp {
+pub fn handle_keep_alive(
+	client: Arc<ClientContext>,
+	packet: serverbound::play::KeepAlive,
+) -> Result<(), ServerError> {
+	if client.entity_info().unwrap().answer_keep_alive(packet.id) {
+		Ok(())
+	} else {
 		// Послать клиента нахуй
 		Err(ServerError::WrongPacket)
-	} else {
-		Ok(())
 	}
 }
 
@@ -152,15 +186,12 @@ pub fn send_system_message(
 	message: TextComponent,
 	is_action_bar: bool,
 ) -> Result<(), ServerError> {
-	let mut packet = Packet::empty(clientbound::play::SYSTEM_CHAT_MESSAGE);
-	packet.write_nbt(&message)?;
-	packet.write_boolean(is_action_bar)?;
-	client.write_packet(&packet)
+	client.send(&SystemChatMessage {
+		content: message,
+		overlay: is_action_bar,
+	})
 }
 
 pub fn unload_chunk(client: Arc<ClientContext>, x: i32, z: i32) -> Result<(), ServerError> {
-	let mut packet = Packet::empty(clientbound::play::UNLOAD_CHUNK);
-	packet.write_int(z)?;
-	packet.write_int(x)?;
-	client.write_packet(&packet)
+	client.send(&UnloadChunk { z, x })
 }
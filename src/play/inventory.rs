@@ -0,0 +1,378 @@
+use std::{
+	f64::consts::PI,
+	sync::{Arc, Mutex},
+};
+
+use uuid::Uuid;
+
+use crate::{
+	ServerError,
+	context::ServerContext,
+	data::{metadata::fields, slot::Slot},
+	play::entity::{remove_entity, spawn_entity},
+	player::{
+		context::{ClientContext, GameMode},
+		inventory::{ClickMode, Container, PLAYER_WINDOW, slots},
+	},
+	protocol::packets::{
+		VarInt,
+		clientbound::play::{
+			CloseContainer as CloseScreen, OpenScreen, SetContainerContent, SetContainerSlot,
+		},
+		serverbound::play::{ClickContainer, CloseContainer, SetCreativeModeSlot},
+	},
+	trigger_event, trigger_event_ignore,
+	world::entity::{self, Entity},
+};
+
+// Синхронизация инвентаря и контейнеров с клиентом
+//
+// Клиент сам применяет клик у себя и присылает, какие слоты у него поменялись
+// Если у сервера вышло то же самое и state id совпал, отвечать ничего не надо,
+// иначе отправляем все окно заново с новым state id
+//
+// Выброшенные предметы спавнятся сущностями перед игроком и пропадают через 5 минут, как в ванилле
+// Физики у сервера нет, летят и падают они только у клиентов
+
+const ITEM_LIFETIME: u64 = 6000; // сколько тиков выброшенный предмет лежит в мире
+
+/// Отправляет все слоты открытого окна и предмет на курсоре
+pub fn sync_window(client: &Arc<ClientContext>) -> Result<(), ServerError> {
+	let info = client.entity_info().unwrap();
+
+	let packet = {
+		let mut inventory = info.inventory();
+		let window = inventory.window().cloned();
+		let container = window.as_ref().map(|o| o.container.lock().unwrap());
+
+		SetContainerContent {
+			window_id: VarInt(inventory.window_id() as i32),
+			state_id: VarInt(inventory.next_state_id()),
+			slots: inventory.window_slots(container.as_deref()),
+			carried_item: inventory.cursor().clone(),
+		}
+	};
+
+	client.send(&packet)
+}
+
+/// Меняет слот в окне инвентаря игрока (см. player::inventory::slots) и сообщает клиенту
+pub fn set_player_slot(
+	client: &Arc<ClientContext>,
+	index: usize,
+	item: Slot,
+) -> Result<(), ServerError> {
+	let info = client.entity_info().unwrap();
+
+	let packet = {
+		let mut inventory = info.inventory();
+		inventory.set_slot(index, item.clone());
+
+		SetContainerSlot {
+			window_id: VarInt(PLAYER_WINDOW as i32),
+			state_id: VarInt(inventory.next_state_id()),
+			slot: index as i16,
+			item,
+		}
+	};
+
+	client.send(&packet)
+}
+
+/// Кладет предмет в инвентарь игрока, возвращает то, что не влезло
+pub fn give_item(client: &Arc<ClientContext>, item: Slot) -> Result<Slot, ServerError> {
+	let leftover = client.entity_info().unwrap().inventory().add_item(item);
+	sync_window(client)?;
+	Ok(leftover)
+}
+
+/// Выбрасывает предмет перед игроком, как Q в ванилле
+pub fn drop_item(client: &Arc<ClientContext>, item: Slot) -> Result<(), ServerError> {
+	if item.is_empty() {
+		return Ok(());
+	}
+
+	let info = client.entity_info().unwrap();
+	let (x, y, z) = info.eye_position();
+	let (yaw, pitch) = info.rotation();
+	let (yaw, pitch) = ((yaw as f64).to_radians(), (pitch as f64).to_radians());
+
+	// Бросок вперед по взгляду с небольшим случайным разбросом
+	let angle = rand::random::<f64>() * 2.0 * PI;
+	let spread = rand::random::<f64>() * 0.02;
+	let velocity = (
+		-yaw.sin() * pitch.cos() * 0.3 + angle.cos() * spread,
+		-pitch.sin() * 0.3 + 0.1 + (rand::random::<f64>() - rand::random::<f64>()) * 0.1,
+		yaw.cos() * pitch.cos() * 0.3 + angle.sin() * spread,
+	);
+
+	let server = &client.server;
+	let entity = Entity::new(
+		server.world.next_entity_id(),
+		Uuid::from_u128(rand::random()),
+		entity::ITEM,
+	)
+	.with_position((x, y - 0.3, z));
+	entity.set_velocity(velocity);
+	entity.metadata().set(fields::item::ITEM, item);
+
+	let entity_id = entity.id;
+	let world = client.world()?;
+	spawn_entity(server, &world, Arc::new(entity))?;
+
+	server.planner().run_later(ITEM_LIFETIME, move |server, _| {
+		remove_entity(server, entity_id)
+	});
+
+	Ok(())
+}
+
+/// Забирает один предмет из основной руки, например после установки блока
+pub fn consume_held_item(client: &Arc<ClientContext>) -> Result<(), ServerError> {
+	let info = client.entity_info().unwrap();
+	let index = slots::HOTBAR.start + info.held_slot();
+
+	let mut item = info.held_item();
+	item.split(1);
+
+	set_player_slot(client, index, item)
+}
+
+/// Открывает игроку контейнер, предыдущий при этом закрывается, возвращает айди окна
+pub fn open_container(
+	client: &Arc<ClientContext>,
+	container: Arc<Mutex<Container>>,
+) -> Result<u8, ServerError> {
+	close_window(client)?;
+
+	let window_id = client
+		.entity_info()
+		.unwrap()
+		.inventory()
+		.open_window(container.clone());
+
+	let (kind, title) = {
+		let container = container.lock().unwrap();
+		(container.kind, container.title.clone())
+	};
+
+	client.send(&OpenScreen {
+		window_id: VarInt(window_id as i32),
+		window_type: VarInt(kind.id()),
+		title,
+	})?;
+
+	sync_window(client)?;
+
+	Ok(window_id)
+}
+
+/// Закрывает открытый контейнер у клиента
+pub fn close_container(client: &Arc<ClientContext>) -> Result<(), ServerError> {
+	let window_id = client.entity_info().unwrap().inventory().window_id();
+	if window_id == PLAYER_WINDOW {
+		return Ok(());
+	}
+
+	client.send(&CloseScreen {
+		window_id: VarInt(window_id as i32),
+	})?;
+
+	close_window(client)
+}
+
+/// Отправляет содержимое контейнера всем, у кого он открыт, после того как его поменял плагин
+pub fn update_container(
+	server: &Arc<ServerContext>,
+	container: &Arc<Mutex<Container>>,
+) -> Result<(), ServerError> {
+	update_viewers(server, container, None)
+}
+
+// Рассылает окно всем, у кого открыт этот контейнер, кроме except
+fn update_viewers(
+	server: &Arc<ServerContext>,
+	container: &Arc<Mutex<Container>>,
+	except: Option<&Arc<ClientContext>>,
+) -> Result<(), ServerError> {
+	for player in server.players() {
+		if except.is_some_and(|o| o.id == player.id) {
+			continue;
+		}
+
+		let Some(info) = player.entity_info() else {
+			continue;
+		};
+
+		let viewing = info
+			.inventory()
+			.window()
+			.is_some_and(|o| Arc::ptr_eq(&o.container, container));
+
+		if viewing {
+			sync_window(&player)?;
+		}
+	}
+
+	Ok(())
+}
+
+/// Забывает открытое окно, Close Container клиенту при этом не отправляется
+///
+/// Курсор и сетка крафта возвращаются в инвентарь, что не влезло выбрасывается
+pub fn close_window(client: &Arc<ClientContext>) -> Result<(), ServerError> {
+	let info = client.entity_info().unwrap();
+
+	let (window, moved, leftover) = {
+		let mut inventory = info.inventory();
+		let window = inventory.window().cloned();
+		let moved = !inventory.cursor().is_empty()
+			|| inventory.slots()[slots::CRAFTING]
+				.iter()
+				.any(|o| !o.is_empty());
+
+		let leftover = inventory.close_window();
+
+		(window, moved, leftover)
+	};
+
+	if let Some(window) = window {
+		trigger_event_ignore!(client, container_close, window.container.clone());
+	}
+
+	for item in leftover {
+		drop_item(client, item)?;
+	}
+
+	if moved && client.is_alive() {
+		sync_window(client)?;
+	}
+
+	Ok(())
+}
+
+pub fn handle_close_container(
+	client: Arc<ClientContext>,
+	packet: CloseContainer,
+) -> Result<(), ServerError> {
+	let window_id = client.entity_info().unwrap().inventory().window_id();
+	if packet.window_id.0 != window_id as i32 {
+		return Ok(());
+	}
+
+	close_window(&client)
+}
+
+pub fn handle_click_container(
+	client: Arc<ClientContext>,
+	packet: ClickContainer,
+) -> Result<(), ServerError> {
+	let info = client.entity_info().unwrap();
+	let creative = info.game_mode() == GameMode::Creative;
+
+	let Some(mode) = ClickMode::from_id(packet.mode.0) else {
+		return sync_window(&client);
+	};
+
+	let container = {
+		let inventory = info.inventory();
+		if packet.window_id.0 != inventory.window_id() as i32 {
+			return Ok(()); // клик в окне, которое уже закрыто
+		}
+		inventory.window().map(|o| o.container.clone())
+	};
+
+	let mut cancel = false;
+	trigger_event!(
+		client,
+		container_click,
+		container.clone(),
+		packet.slot,
+		packet.button,
+		mode,
+		&mut cancel
+	);
+	if cancel {
+		return sync_window(&client);
+	}
+
+	let (in_sync, container_changed, dropped) = {
+		let mut inventory = info.inventory();
+
+		// Листенеры могли закрыть или поменять окно
+		if packet.window_id.0 != inventory.window_id() as i32 {
+			return sync_window(&client);
+		}
+
+		let mut guard = container.as_ref().map(|o| o.lock().unwrap());
+		let container_len = guard.as_ref().map(|o| o.len()).unwrap_or(0);
+
+		let before = inventory.window_slots(guard.as_deref());
+		let dropped = inventory.click(
+			&client.server.world.items,
+			guard.as_deref_mut(),
+			packet.slot,
+			packet.button,
+			mode,
+			creative,
+		);
+		let after = inventory.window_slots(guard.as_deref());
+
+		let changed: Vec<usize> = (0..after.len())
+			.filter(|&i| before[i] != after[i])
+			.collect();
+
+		// Клиент должен был предсказать ровно то же самое
+		let in_sync = packet.state_id.0 == inventory.state_id()
+			&& packet.carried_item.matches(inventory.cursor())
+			&& packet.changed_slots.iter().all(|o| {
+				o.slot >= 0
+					&& after
+						.get(o.slot as usize)
+						.is_some_and(|s| o.item.matches(s))
+			}) && changed
+			.iter()
+			.all(|i| packet.changed_slots.iter().any(|o| o.slot as usize == *i));
+
+		(in_sync, changed.iter().any(|&i| i < container_len), dropped)
+	};
+
+	for item in dropped {
+		drop_item(&client, item)?;
+	}
+
+	if !in_sync {
+		sync_window(&client)?;
+	}
+
+	if container_changed && let Some(container) = &container {
+		update_viewers(&client.server, container, Some(&client))?;
+	}
+
+	Ok(())
+}
+
+pub fn handle_creative_slot(
+	client: Arc<ClientContext>,
+	packet: SetCreativeModeSlot,
+) -> Result<(), ServerError> {
+	let info = client.entity_info().unwrap();
+	if info.game_mode() != GameMode::Creative {
+		return Ok(());
+	}
+
+	let item = packet.item;
+	if !item.is_empty() && item.count > item.max_stack_size() {
+		return sync_window(&client);
+	}
+
+	// Слот -1 это выброс предмета из креативного меню
+	match packet.slot {
+		-1 => drop_item(&client, item),
+		slot if (0..slots::COUNT as i16).contains(&slot) => {
+			info.inventory().set_slot(slot as usize, item);
+			Ok(())
+		}
+		_ => Ok(()),
+	}
+}
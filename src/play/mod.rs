@@ -1,26 +1,66 @@
-use std::sync::atomic::Ordering;
-use std::{sync::Arc, thread, time::Duration};
+use std::sync::{
+	Arc, Mutex,
+	atomic::{AtomicBool, Ordering},
+};
 
+use block::{handle_player_action, handle_use_item_on};
+use chunk::{ChunkData, send_light_changes};
+use command::{handle_chat_command, handle_command_suggestions, send_commands};
 use config::handle_configuration_state;
+use entity::{move_entity, remove_entity, spawn_entity, update_tracking, view_distance, viewers};
 use helper::{
-	send_entity_animation, send_entity_event, send_game_event, send_keep_alive, send_system_message,
-	set_center_chunk, sync_player_pos, unload_chunk,
+	handle_keep_alive, send_entity_animation, send_entity_event, send_game_event, send_health,
+	send_keep_alive, send_system_message, set_center_chunk, set_held_slot, sync_player_pos,
+	unload_chunk,
+};
+use inventory::{
+	close_window, handle_click_container, handle_close_container, handle_creative_slot, sync_window,
 };
-use log::debug;
-use rust_mc_proto::{DataReader, DataWriter, Packet};
+use log::error;
+use rust_mc_proto::Packet;
 use uuid::Uuid;
 
+use crate::data::metadata::{
+	Pose,
+	fields::{self, entity::flags},
+};
 use crate::event::Listener;
-use crate::player::context::PlayerEntityInfo;
+use crate::player::{context::PlayerEntityInfo, data::save_player};
+use crate::protocol::handler::finish_connection;
+use crate::world::entity::Entity;
 use crate::{
 	ServerError, data::component::TextComponent, event::PacketHandler, player::context::ClientContext,
 };
 
-use crate::protocol::{ConnectionState, packet_id::*};
+use crate::protocol::{
+	ConnectionState,
+	packet_id::*,
+	packets::{
+		VarInt,
+		clientbound::play::{Login, PlayerInfoAdd, PlayerInfoEntry, PlayerInfoRemove},
+		serverbound::play::{
+			ChatCommand, PlayPacket, SetPlayerPosition, SetPlayerPositionAndRotation, SetPlayerRotation,
+			SignedChatCommand,
+		},
+	},
+};
 
+pub mod block;
+pub mod chunk;
+pub mod command;
 pub mod config;
+pub mod entity;
 pub mod helper;
+pub mod inventory;
 pub mod planner;
+pub mod workers;
+pub mod world;
+
+// Раз во сколько тиков проверяем, не перешел ли игрок в другой чанк
+const CHUNK_UPDATE_TICKS: u64 = 5;
+
+// Сколько байт может ждать отправки клиенту, остальные чанки дошлются в следующий раз
+const CHUNK_BACKLOG: usize = 2 * 1024 * 1024;
 
 pub struct PlayHandler;
 
@@ -66,194 +106,109 @@ impl Listener for PlayListener {
 }
 
 pub fn send_login(client: Arc<ClientContext>) -> Result<(), ServerError> {
+	let world = client.world()?;
+
 	// Отправка пакета Login
-	let mut packet = Packet::empty(clientbound::play::LOGIN);
-
-	packet.write_int(client.entity_info().unwrap().entity_id)?; // Entity ID
-	packet.write_boolean(false)?; // Is hardcore
-	packet.write_varint(4)?; // Dimension Names
-	packet.write_string("minecraft:overworld")?;
-	packet.write_string("minecraft:nether")?;
-	packet.write_string("minecraft:the_end")?;
-	packet.write_string("minecraft:overworld_caves")?;
-	packet.write_varint(0)?; // Max Players
-	packet.write_varint(8)?; // View Distance
-	packet.write_varint(5)?; // Simulation Distance
-	packet.write_boolean(false)?; // Reduced Debug Info
-	packet.write_boolean(true)?; // Enable respawn screen
-	packet.write_boolean(false)?; // Do limited crafting
-
-	packet.write_varint(0)?; // Dimension Type
-	packet.write_string("minecraft:overworld")?; // Dimension Name
-	packet.write_long(0x0f38f26ad09c3e20)?; // Hashed seed
-	packet.write_byte(0)?; // Game mode
-	packet.write_signed_byte(-1)?; // Previous Game mode
-	packet.write_boolean(false)?; // Is Debug
-	packet.write_boolean(true)?; // Is Flat
-	packet.write_boolean(false)?; // Has death location
-	packet.write_varint(20)?; // Portal cooldown
-	packet.write_varint(60)?; // Sea level
-
-	packet.write_boolean(false)?; // Enforces Secure Chat
-
-	client.write_packet(&packet)
+	client.send(&Login {
+		entity_id: client.entity_info().unwrap().entity_id,
+		is_hardcore: false,
+		dimension_names: client.server.world.names(),
+		max_players: VarInt(0),
+		view_distance: VarInt(8),
+		simulation_distance: VarInt(5),
+		reduced_debug_info: false,
+		enable_respawn_screen: true,
+		do_limited_crafting: false,
+		dimension_type: VarInt(world.dimension_id()),
+		dimension_name: world.name.clone(),
+		hashed_seed: world.hashed_seed,
+		game_mode: client.entity_info().unwrap().game_mode().id(),
+		previous_game_mode: -1,
+		is_debug: false,
+		is_flat: world.is_flat(),
+		death_location: None,
+		portal_cooldown: VarInt(20),
+		sea_level: VarInt(60),
+		enforces_secure_chat: false,
+	})
 }
 
-pub fn send_example_chunk(client: Arc<ClientContext>, x: i32, z: i32) -> Result<(), ServerError> {
-	let mut packet = Packet::empty(clientbound::play::CHUNK_DATA_AND_UPDATE_LIGHT);
+pub fn send_chunk(client: Arc<ClientContext>, x: i32, z: i32) -> Result<(), ServerError> {
+	let world = client.world()?;
 
-	packet.write_int(x)?;
-	packet.write_int(z)?;
+	// Свет мог дойти до соседних чанков, которые уже есть у игроков
+	let changes = world.light_chunk(x, z)?;
+	send_light_changes(&client.server, &world, &changes)?;
 
-	// heightmap
+	let chunk = world.chunk(x, z)?;
+	let data = ChunkData::from_chunk(
+		&chunk.read().unwrap(),
+		&client.server.world.blocks,
+		&client.server.world.registries,
+		&world.dimension,
+	);
 
-	packet.write_varint(1)?; // heightmaps count
-	packet.write_varint(0)?; // MOTION_BLOCKING - 0
-	// bits per entry is ceil(log2(385)) = 9 where 385 is the world height
-	// so, the length of the following array is (9 * 16 * 16) / 8 = 37
-	// ... idk how it came to that
-	packet.write_varint(37)?; // Length of the following long array 
-	for _ in 0..37 {
-		packet.write_long(0)?; // THIS WORKS ONLY BECAUSE OUR HEIGHT IS 0
-	}
-
-	// sending chunk data
-
-	let mut chunk_data = Vec::new();
-
-	// we want to fill the area from -64 to 0, so it will be 4 chunk sections
-
-	for _ in 0..4 {
-		chunk_data.write_short(4096)?; // non-air blocks count, 16 * 16 * 16 = 4096 stone blocks
-
-		// blocks paletted container
-		chunk_data.write_byte(0)?; // Bits Per Entry, use Single valued palette format
-		chunk_data.write_varint(1)?; // block state id in the registry 
-
-		// biomes palleted container
-		chunk_data.write_byte(0)?; // Bits Per Entry, use Single valued palette format
-		chunk_data.write_varint(1)?; // biome id in the registry
-	}
-
-	// air chunk sections
-
-	for _ in 0..20 {
-		chunk_data.write_short(0)?; // non-air blocks count, 0
-
-		// blocks paletted container
-		chunk_data.write_byte(0)?; // Bits Per Entry, use Single valued palette format
-		chunk_data.write_varint(0)?; // block state id in the registry (0 for air)
-
-		// biomes palleted container
-		chunk_data.write_byte(0)?; // Bits Per Entry, use Single valued palette format
-		chunk_data.write_varint(27)?; // biome id in the registry
-	}
-
-	packet.write_usize_varint(chunk_data.len())?;
-	packet.write_bytes(&chunk_data)?;
-
-	packet.write_byte(0)?;
-
-	// light data
-
-	packet.write_byte(0)?;
-	packet.write_byte(0)?;
-	packet.write_byte(0)?;
-	packet.write_byte(0)?;
-	packet.write_byte(0)?;
-	packet.write_byte(0)?;
-
-	client.write_packet(&packet)?;
+	client.send(&data.to_packet()?)?;
+	client.set_chunk_loaded(x, z, true);
 
 	Ok(())
 }
 
-pub fn send_example_chunks_in_distance(
+pub fn send_chunks_in_distance(
 	client: Arc<ClientContext>,
-	chunks: &mut Vec<(i32, i32)>,
 	distance: i32,
 	center: (i32, i32),
 ) -> Result<(), ServerError> {
-	let mut new_chunks = Vec::new();
-
-	for x in -distance + center.0..=distance + center.0 {
-		for z in -distance + center.1..=distance + center.1 {
-			if !chunks.contains(&(x, z)) {
-				send_example_chunk(client.clone(), x as i32, z as i32)?;
-			}
-			new_chunks.push((x, z));
+	for (x, z) in client.loaded_chunks() {
+		if (x - center.0).abs() > distance || (z - center.1).abs() > distance {
+			unload_chunk(client.clone(), x, z)?;
+			client.set_chunk_loaded(x, z, false);
 		}
 	}
 
-	for (x, z) in chunks.iter() {
-		if !new_chunks.contains(&(*x, *z)) {
-			unload_chunk(client.clone(), *x, *z)?;
+	// Ближние первыми, чтобы игрок не ждал свой чанк из-за дальних
+	let mut missing = (-distance..=distance)
+		.flat_map(|x| (-distance..=distance).map(move |z| (x, z)))
+		.filter(|(x, z)| !client.is_chunk_loaded(center.0 + x, center.1 + z))
+		.collect::<Vec<_>>();
+	missing.sort_by_key(|(x, z)| x * x + z * z);
+
+	for (x, z) in missing {
+		if client.pending_bytes() > CHUNK_BACKLOG {
+			// Клиент не успевает принимать
+			break;
 		}
+		send_chunk(client.clone(), center.0 + x, center.1 + z)?;
 	}
 
-	*chunks = new_chunks;
-
 	Ok(())
 }
 
-pub fn remove_player(
+/// Убирает игрока из списка игроков (таб) у receiver, сущность убирается через entity::remove_entity
+pub fn remove_player_info(
 	receiver: Arc<ClientContext>,
 	player: Arc<ClientContext>,
 ) -> Result<(), ServerError> {
-	let mut packet = Packet::empty(clientbound::play::PLAYER_INFO_REMOVE);
-
-	packet.write_varint(1)?;
-	packet.write_uuid(&player.entity_info().unwrap().uuid)?;
-
-	receiver.write_packet(&packet)?;
-
-	let mut packet = Packet::empty(clientbound::play::REMOVE_ENTITIES);
-
-	packet.write_varint(1)?;
-	packet.write_varint(player.entity_info().unwrap().entity_id)?; // Entity ID
-
-	receiver.write_packet(&packet)?;
-
-	Ok(())
+	receiver.send(&PlayerInfoRemove {
+		uuids: vec![player.entity_info().unwrap().uuid],
+	})
 }
 
-pub fn send_player(
+/// Добавляет игрока в список игроков (таб) у receiver, без этого клиент не заспавнит сущность игрока
+pub fn send_player_info(
 	receiver: Arc<ClientContext>,
 	player: Arc<ClientContext>,
 ) -> Result<(), ServerError> {
-	let mut packet = Packet::empty(clientbound::play::PLAYER_INFO_UPDATE);
-
-	packet.write_byte(0x01)?; // only Add Player 
-	packet.write_varint(1)?; // players list
-	packet.write_uuid(&player.entity_info().unwrap().uuid)?; // player uuid
-	packet.write_string(&player.player_info().unwrap().name)?; // player name
-	packet.write_varint(0)?; // no properties
-
-	receiver.write_packet(&packet)?;
-
-	let mut packet = Packet::empty(clientbound::play::SPAWN_ENTITY);
-
-	let (x, y, z) = player.entity_info().unwrap().position();
-	let (yaw, pitch) = player.entity_info().unwrap().rotation();
-	let (vel_x, vel_y, vel_z) = player.entity_info().unwrap().velocity();
-
-	packet.write_varint(player.entity_info().unwrap().entity_id)?; // Entity ID
-	packet.write_uuid(&player.entity_info().unwrap().uuid)?; // Entity UUID
-	packet.write_varint(148)?; // Entity type TODO: move to const
-	packet.write_double(x)?;
-	packet.write_double(y)?;
-	packet.write_double(z)?;
-	packet.write_signed_byte((pitch / 360.0 * 256.0) as i8)?;
-	packet.write_signed_byte((yaw / 360.0 * 256.0) as i8)?;
-	packet.write_signed_byte((yaw / 360.0 * 256.0) as i8)?; // head yaw TODO: make player head yaw field
-	packet.write_varint(0)?;
-	packet.write_short(vel_x as i16)?;
-	packet.write_short(vel_y as i16)?;
-	packet.write_short(vel_z as i16)?;
-
-	receiver.write_packet(&packet)?;
-
-	Ok(())
+	let entity_info = player.entity_info().unwrap();
+	let player_info = player.player_info().unwrap();
+
+	receiver.send(&PlayerInfoAdd {
+		players: vec![PlayerInfoEntry {
+			uuid: entity_info.uuid,
+			name: player_info.name,
+			properties: player_info.properties, // skin and other properties
+		}],
+	})
 }
 
 pub fn get_offline_uuid(name: &str) -> Uuid {
@@ -266,7 +221,7 @@ pub fn get_offline_uuid(name: &str) -> Uuid {
 		namespaces_bytes[i] = *byte;
 	}
 	let namespace = Uuid::from_bytes(namespaces_bytes);
-	Uuid::new_v3(&namespace, (&name[2..]).as_bytes())
+	Uuid::new_v3(&namespace, &name.as_bytes()[2..])
 }
 
 pub fn send_rainbow_message(
@@ -281,42 +236,116 @@ pub fn handle_play_state(
 	client: Arc<ClientContext>, // Контекст клиента
 ) -> Result<(), ServerError> {
 	let player_name = client.player_info().unwrap().name;
-	let player_uuid = get_offline_uuid(&client.player_info().unwrap().name); // TODO: authenticated uuid
-	let entity_id = client
+	let player_uuid = client.player_info().unwrap().uuid;
+	let entity_id = client.server.world.next_entity_id();
+
+	// Игрок появляется там, где вышел, а если не заходил или его мир не загружен - на спавне основного мира
+	let data = match client
 		.server
 		.world
-		.entity_id_counter
-		.fetch_add(1, Ordering::SeqCst);
-
-	client.set_entity_info(PlayerEntityInfo::new(entity_id, player_uuid));
-
-	client.entity_info().unwrap().set_position((8.0, 0.0, 8.0)); // set 8 0 8 as position
+		.player_data
+		.load(player_uuid, client.server.world.item_nbt())
+	{
+		Ok(data) => data,
+		Err(error) => {
+			error!("Не удалось загрузить данные игрока {player_name}: {error:?}");
+			None
+		}
+	};
+	let saved_world = data
+		.as_ref()
+		.and_then(|o| client.server.world.get(&o.world));
+	let world = match &saved_world {
+		Some(world) => world.clone(),
+		None => client.server.world.default_world()?,
+	};
+	let (spawn_x, spawn_y, spawn_z) = match (&data, &saved_world) {
+		(Some(data), Some(_)) => data.position,
+		_ => world.spawn_point(),
+	};
+	let (yaw, pitch) = data.as_ref().map(|o| o.rotation).unwrap_or_default();
+
+	let entity = Arc::new(
+		Entity::player(entity_id, player_uuid, client.id).with_position((spawn_x, spawn_y, spawn_z)),
+	);
+	entity.set_world(&world.name);
+	entity.set_rotation((yaw, pitch));
 
-	thread::spawn({
-		let client = client.clone();
+	{
+		let client_info = client.client_info().unwrap();
+		let mut metadata = entity.metadata();
+		metadata.set(
+			fields::player::SKIN_PARTS,
+			client_info.displayed_skin_parts as i8,
+		);
+		metadata.set(fields::player::MAIN_HAND, client_info.main_hand as i8);
+	}
 
-		move || {
-			let _ = client.run_read_loop();
-			client.close();
+	let permission_level = client
+		.server
+		.config
+		.server
+		.permission_level(&player_name, player_uuid);
+
+	client.set_entity_info(PlayerEntityInfo::new(
+		entity.clone(),
+		data
+			.as_ref()
+			.map(|o| o.game_mode)
+			.unwrap_or(client.server.config.server.game_mode),
+		permission_level,
+	));
+	client.server.clients.register_entity(&client, entity_id);
+
+	if let Some(data) = data {
+		let info = client.entity_info().unwrap();
+		// Умершие игроки возрождаются с полным здоровьем
+		info.set_health(if data.health > 0.0 { data.health } else { 20.0 });
+		info.set_held_slot(data.held_slot);
+		*info.plugin_data() = data.plugin_data;
+		*info.other_nbt() = data.other;
+
+		let mut inventory = info.inventory();
+		for (index, slot) in data.inventory.into_iter().enumerate() {
+			inventory.set_slot(index, slot);
 		}
-	});
+	}
+
+	// Пакеты которые не ждет никто конкретный складываются в буфер, их разбирает тик игрока
+	client.start_buffering();
 
 	send_login(client.clone())?;
-	sync_player_pos(client.clone(), 8.0, 0.0, 8.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0)?;
+	sync_player_pos(
+		client.clone(),
+		spawn_x,
+		spawn_y,
+		spawn_z,
+		0.0,
+		0.0,
+		0.0,
+		yaw,
+		pitch,
+		0,
+	)?;
 	send_game_event(client.clone(), 13, 0.0)?; // 13 - Start waiting for level chunks
 	// send_game_event(client.clone(), 3, 1.0)?; // 3 - Set gamemode, 1.0 - creative
-	send_entity_event(client.clone(), entity_id, 28)?; // 28 - give op level 4
-	set_center_chunk(client.clone(), 0, 0)?;
+	// 24 + уровень прав - op level, от него зависит, какие команды клиент считает доступными
+	send_entity_event(client.clone(), entity_id, 24 + permission_level)?;
+	send_commands(&client)?;
+	sync_window(&client)?;
+	send_health(&client)?;
+	set_held_slot(&client, client.entity_info().unwrap().held_slot())?;
 
-	let mut chunks = Vec::new();
+	let (chunk_x, chunk_z) = entity.chunk();
+	set_center_chunk(client.clone(), chunk_x, chunk_z)?;
 
-	let view_distance = client.client_info().unwrap().view_distance as i32 / 2;
+	let view_distance = view_distance(&client);
 
-	send_example_chunks_in_distance(client.clone(), &mut chunks, view_distance, (0, 0))?;
+	send_chunks_in_distance(client.clone(), view_distance, (chunk_x, chunk_z))?;
 
 	// sync_player_pos(client.clone(), 8.0, 0.0, 8.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0)?;
 
-	// send_rainbow_message(&client, format!("Your IP: {}", client.addr))?;
+	// send_rainbow_message(&client, format!("Your IP: {}", client.addr()))?;
 	// send_rainbow_message(
 	// 	&client,
 	// 	format!("Your brand: {}", client.client_info().unwrap().brand),
@@ -330,241 +359,227 @@ pub fn handle_play_state(
 	// send_rainbow_message(&client, format!("Your Entity ID: {}", entity_id))?;
 
 	for player in client.server.players() {
-		if client.addr == player.addr {
+		if client.id == player.id {
 			continue;
 		}
-		send_player(client.clone(), player.clone())?;
-		send_player(player.clone(), client.clone())?;
+		send_player_info(client.clone(), player.clone())?;
+		send_player_info(player.clone(), client.clone())?;
 		send_rainbow_message(&player, format!("{} joined the game", player_name))?;
 	}
 
-	thread::spawn({
-		let client = client.clone();
+	// Сущность попадает в мир после Player Info, без него клиенты не заспавнят игрока
+	spawn_entity(&client.server, &world, entity)?;
+	update_tracking(&client)?;
+
+	// Сам игрок живет на тиках сервера и сам забирает пакеты из соединения. Пока тик их не разобрал,
+	// очередь соединения растет, а когда она заполнится, реактор перестанет читать сокет
+	let mut ticks_alive = 0u64;
+	let player = client.clone();
+	let chunks = Arc::new(ChunkStream {
+		view_distance,
+		center: Mutex::new((chunk_x, chunk_z)),
+		running: AtomicBool::new(false),
+	});
 
-		move || -> Result<(), ServerError> {
-			while client.is_alive() {
-				let mut packet = client.read_packet(&[
-					serverbound::play::SET_PLAYER_POSITION,
-					serverbound::play::SET_PLAYER_POSITION_AND_ROTATION,
-					serverbound::play::SET_PLAYER_ROTATION,
-					serverbound::play::CHAT_MESSAGE,
-					serverbound::play::CLICK_CONTAINER,
-					serverbound::play::CHAT_COMMAND,
-					serverbound::play::SIGNED_CHAT_COMMAND,
-					serverbound::play::PLAYER_COMMAND,
-					serverbound::play::SWING_ARM,
-				])?;
-
-				match packet.id() {
-					serverbound::play::CLICK_CONTAINER => {
-						let _ = packet.read_varint()?; // window id
-						let _ = packet.read_varint()?; // state id
-						let slot = packet.read_short()?; // slot
-						let _ = packet.read_byte()?; // button
-						let _ = packet.read_varint()?; // mode
-						// i cannot read item slots now
-
-						send_rainbow_message(&client, format!("index clicked: {slot}"))?;
-					}
-					serverbound::play::SWING_ARM => {
-						let hand = packet.read_varint()?; // hand (0 - main, 1 - off)
-
-						send_rainbow_message(&client, format!("hand swinged: {hand}"))?;
-
-						let animation = match hand {
-							0 => 0, // 0 - mainhand swing animatiom
-							1 => 3, // 3 - offhand swing animatiom
-							_ => continue,
-						};
-
-						for player in client.server.players() {
-							if client.addr == player.addr {
-								continue;
-							}
-
-							send_entity_animation(player, client.entity_info().unwrap().entity_id, animation)?;
-						}
-					}
-					serverbound::play::PLAYER_COMMAND => {
-						let _ = packet.read_varint()?; // entity id
-						let action = packet.read_varint()?; // action id
-						let _ = packet.read_varint()?; // jump boost on horse
-
-						if action == 0 {
-							// press sneak key
-						} else if action == 1 {
-							// release sneak key
-						}
-					}
-					serverbound::play::CHAT_COMMAND | serverbound::play::SIGNED_CHAT_COMMAND => {
-						let command = packet.read_string()?;
-
-						if command == "gamemode creative" {
-							send_game_event(client.clone(), 3, 1.0)?; // 3 - Set gamemode
-							send_rainbow_message(&client, format!("gamemode creative installed"))?;
-						} else if command == "gamemode survival" {
-							send_game_event(client.clone(), 3, 0.0)?; // 3 - Set gamemode
-							send_rainbow_message(&client, format!("gamemode survival installed"))?;
-						} else if command == "kill" {
-							sync_player_pos(client.clone(), 8.0, 0.0, 8.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0)?;
-							send_rainbow_message(&client, format!("killed"))?;
-						}
-					}
-					serverbound::play::CHAT_MESSAGE => {
-						let message_text = packet.read_string()?;
-						// skip remaining data coz they suck
-
-						let mut message =
-							TextComponent::rainbow(format!("{} said: ", client.player_info().unwrap().name));
-
-						message.italic = Some(true);
-
-						let text_message = TextComponent::builder()
-							.color("white")
-							.text(&message_text)
-							.italic(false)
-							.build();
-
-						if let Some(extra) = &mut message.extra {
-							extra.push(text_message);
-						}
-
-						for player in client.server.players() {
-							send_system_message(player.clone(), message.clone(), false)?;
-						}
-					}
-					serverbound::play::SET_PLAYER_POSITION => {
-						let x = packet.read_double()?;
-						let y = packet.read_double()?;
-						let z = packet.read_double()?;
-						let flags = packet.read_byte()?; // flags
-
-						let prev = client.entity_info().unwrap().position();
-
-						for player in client.server.players() {
-							if client.addr == player.addr {
-								continue;
-							}
-
-							let mut packet = Packet::empty(clientbound::play::UPDATE_ENTITY_POSITION);
-							packet.write_varint(client.entity_info().unwrap().entity_id)?;
-							packet.write_short((x * 4096.0 - prev.0 * 4096.0) as i16)?; // formula: currentX * 4096 - prevX * 4096
-							packet.write_short((y * 4096.0 - prev.1 * 4096.0) as i16)?;
-							packet.write_short((z * 4096.0 - prev.2 * 4096.0) as i16)?;
-							packet.write_boolean(flags & 0x01 != 0)?;
-							player.write_packet(&packet)?;
-						}
-
-						client.entity_info().unwrap().set_position((x, y, z));
-					}
-					serverbound::play::SET_PLAYER_POSITION_AND_ROTATION => {
-						let x = packet.read_double()?;
-						let y = packet.read_double()?;
-						let z = packet.read_double()?;
-						let yaw = packet.read_float()?;
-						let pitch = packet.read_float()?;
-						let flags = packet.read_byte()?; // flags
-
-						let prev = client.entity_info().unwrap().position();
-
-						for player in client.server.players() {
-							if client.addr == player.addr {
-								continue;
-							}
-
-							let mut packet =
-								Packet::empty(clientbound::play::UPDATE_ENTITY_POSITION_AND_ROTATION);
-							packet.write_varint(client.entity_info().unwrap().entity_id)?;
-							packet.write_short((x * 4096.0 - prev.0 * 4096.0) as i16)?; // formula: currentX * 4096 - prevX * 4096
-							packet.write_short((y * 4096.0 - prev.1 * 4096.0) as i16)?;
-							packet.write_short((z * 4096.0 - prev.2 * 4096.0) as i16)?;
-							packet.write_signed_byte((yaw / 360.0 * 256.0) as i8)?;
-							packet.write_signed_byte((pitch / 360.0 * 256.0) as i8)?;
-							packet.write_boolean(flags & 0x01 != 0)?;
-							player.write_packet(&packet)?;
-
-							let mut packet = Packet::empty(clientbound::play::SET_HEAD_ROTATION);
-							packet.write_varint(client.entity_info().unwrap().entity_id)?;
-							packet.write_signed_byte((yaw / 360.0 * 256.0) as i8)?;
-							player.write_packet(&packet)?;
-						}
-
-						client.entity_info().unwrap().set_position((x, y, z));
-						client.entity_info().unwrap().set_rotation((yaw, pitch));
-					}
-					serverbound::play::SET_PLAYER_ROTATION => {
-						let yaw = packet.read_float()?;
-						let pitch = packet.read_float()?;
-						let flags = packet.read_byte()?; // flags
-
-						for player in client.server.players() {
-							if client.addr == player.addr {
-								continue;
-							}
-
-							let mut packet = Packet::empty(clientbound::play::UPDATE_ENTITY_ROTATION);
-							packet.write_varint(client.entity_info().unwrap().entity_id)?;
-							packet.write_signed_byte((yaw / 360.0 * 256.0) as i8)?;
-							packet.write_signed_byte((pitch / 360.0 * 256.0) as i8)?;
-							packet.write_boolean(flags & 0x01 != 0)?;
-							player.write_packet(&packet)?;
-
-							let mut packet = Packet::empty(clientbound::play::SET_HEAD_ROTATION);
-							packet.write_varint(client.entity_info().unwrap().entity_id)?;
-							packet.write_signed_byte((yaw / 360.0 * 256.0) as i8)?;
-							player.write_packet(&packet)?;
-						}
-
-						client.entity_info().unwrap().set_rotation((yaw, pitch));
-					}
-					_ => {}
+	client
+		.server
+		.planner()
+		.run_repeating(1, 1, move |server, task| {
+			let client = &player;
+			if let Err(error) = tick_player(client, &chunks, ticks_alive) {
+				if !matches!(error, ServerError::ConnectionClosed) {
+					error!("Ошибка в тике игрока {}: {error:?}", client.addr());
 				}
+				client.close();
+			}
+
+			if !client.is_alive() {
+				// Отключение сохраняет игрока на диск, это работа не для тика
+				server.planner().cancel(task);
+				let client = client.clone();
+				server.workers().submit(move || finish_connection(client));
 			}
 
+			ticks_alive += 1;
 			Ok(())
-		}
-	});
+		});
 
-	let mut ticks_alive = 0u64;
+	Ok(())
+}
 
-	while client.is_alive() {
-		if ticks_alive % 200 == 0 {
-			// 10 secs timer
-			send_keep_alive(client.clone())?;
-		}
+// Досылка чанков игроку, сама досылка идет в пуле чанков, а тик только ее запускает
+struct ChunkStream {
+	view_distance: i32,
+	center: Mutex<(i32, i32)>, // последний центр, отправленный клиенту
+	running: AtomicBool,       // задача уже в пуле, вторую не ставим
+}
 
-		if ticks_alive % 20 == 0 {
-			// 1 sec timer
-			let (x, _, z) = client.entity_info().unwrap().position();
+// Один тик игрока, вызывается планировщиком
+fn tick_player(
+	client: &Arc<ClientContext>,
+	chunks: &Arc<ChunkStream>,
+	ticks_alive: u64,
+) -> Result<(), ServerError> {
+	// Разбираем все что клиент прислал за тик
+	while let Some(mut packet) = client.try_read_packet(PlayPacket::IDS)? {
+		handle_play_packet(client.clone(), PlayPacket::decode(&mut packet)?)?;
+	}
 
-			let (chunk_x, chunk_z) = ((x / 16.0) as i64, (z / 16.0) as i64);
-			let (chunk_x, chunk_z) = (chunk_x as i32, chunk_z as i32);
+	if ticks_alive.is_multiple_of(200) {
+		// 10 secs timer
+		send_keep_alive(client.clone())?;
+	}
 
-			set_center_chunk(client.clone(), chunk_x, chunk_z)?;
-			send_example_chunks_in_distance(
-				client.clone(),
-				&mut chunks,
-				view_distance,
-				(chunk_x, chunk_z),
-			)?;
-		}
+	if ticks_alive.is_multiple_of(20) {
+		// 1 sec timer
+		update_tracking(client)?;
+	}
 
-		// text animation
-		{
-			let animation_text = format!("Ticks alive: {}         жёпа", ticks_alive);
-			let animation_index = ((ticks_alive + 40) % 300) as usize;
-			let animation_end = animation_text.len() + 20;
+	// Генерация, свет и отправка чанков долгие, поэтому идут в пуле, а не в тике
+	if ticks_alive.is_multiple_of(CHUNK_UPDATE_TICKS) && !chunks.running.swap(true, Ordering::SeqCst)
+	{
+		let client = client.clone();
+		let chunks = chunks.clone();
+		client.server.clone().chunk_workers().submit(move || {
+			if let Err(error) = stream_chunks(&client, &chunks)
+				&& !matches!(error, ServerError::ConnectionClosed)
+			{
+				error!("Ошибка отправки чанков игроку {}: {error:?}", client.addr());
+				client.close();
+			}
+			chunks.running.store(false, Ordering::SeqCst);
+		});
+	}
+
+	Ok(())
+}
+
+// Досылает чанки вокруг игрока, если он перешел в другой чанк или чанки еще не все
+fn stream_chunks(client: &Arc<ClientContext>, chunks: &ChunkStream) -> Result<(), ServerError> {
+	let _lock = client.lock_chunks();
+
+	let chunk = client.entity_info().unwrap().entity.chunk();
+	let mut center = chunks.center.lock().unwrap();
+	if chunk != *center {
+		*center = chunk;
+		set_center_chunk(client.clone(), chunk.0, chunk.1)?;
+	}
 
-			if animation_index < animation_end {
-				let now_length = (animation_index + 1).min(animation_text.chars().count());
-				let now_text = animation_text.chars().take(now_length).collect();
+	send_chunks_in_distance(client.clone(), chunks.view_distance, chunk)
+}
 
-				send_system_message(client.clone(), TextComponent::rainbow(now_text), true)?;
+// Обработка одного пакета в тике игрока
+fn handle_play_packet(client: Arc<ClientContext>, packet: PlayPacket) -> Result<(), ServerError> {
+	match packet {
+		PlayPacket::PlayerAction(packet) => handle_player_action(client, packet)?,
+		PlayPacket::UseItemOn(packet) => handle_use_item_on(client, packet)?,
+		PlayPacket::SetHeldItem(packet) => {
+			if (0..9).contains(&packet.slot) {
+				client
+					.entity_info()
+					.unwrap()
+					.set_held_slot(packet.slot as usize);
+			}
+		}
+		PlayPacket::SetCreativeModeSlot(packet) => handle_creative_slot(client, packet)?,
+		PlayPacket::KeepAlive(packet) => handle_keep_alive(client, packet)?,
+		PlayPacket::ClickContainer(packet) => handle_click_container(client, packet)?,
+		PlayPacket::CloseContainer(packet) => handle_close_container(client, packet)?,
+		PlayPacket::SwingArm(packet) => {
+			let hand = packet.hand.0; // hand (0 - main, 1 - off)
+
+			let animation = match hand {
+				0 => 0, // 0 - mainhand swing animatiom
+				1 => 3, // 3 - offhand swing animatiom
+				_ => return Ok(()),
+			};
+
+			let entity = client.entity_info().unwrap().entity.clone();
+			for player in viewers(&client.server, &entity) {
+				send_entity_animation(player, entity.id, animation)?;
+			}
+		}
+		PlayPacket::PlayerCommand(packet) => {
+			let entity = client.entity_info().unwrap().entity.clone();
+			let mut metadata = entity.metadata();
+
+			match packet.action.0 {
+				0 | 1 => {
+					let sneaking = packet.action.0 == 0; // 0 - press sneak key, 1 - release
+					metadata.set_flag(fields::entity::FLAGS, flags::CROUCHING, sneaking);
+					metadata.set(
+						fields::entity::POSE,
+						if sneaking {
+							Pose::Sneaking
+						} else {
+							Pose::Standing
+						},
+					);
+				}
+				3 | 4 => {
+					let sprinting = packet.action.0 == 3; // 3 - start sprinting, 4 - stop
+					metadata.set_flag(fields::entity::FLAGS, flags::SPRINTING, sprinting);
+				}
+				_ => {}
 			}
 		}
+		PlayPacket::ChatCommand(ChatCommand { command })
+		| PlayPacket::SignedChatCommand(SignedChatCommand { command, .. }) => {
+			handle_chat_command(client, command)?
+		}
+		PlayPacket::CommandSuggestionsRequest(packet) => handle_command_suggestions(client, packet)?,
+		PlayPacket::ChatMessage(packet) => {
+			let mut message =
+				TextComponent::rainbow(format!("{} said: ", client.player_info().unwrap().name));
+
+			message.italic = Some(true);
+
+			let text_message = TextComponent::builder()
+				.color("white")
+				.text(&packet.message)
+				.italic(false)
+				.build();
 
-		thread::sleep(Duration::from_millis(50)); // 1 tick
-		ticks_alive += 1;
+			if let Some(extra) = &mut message.extra {
+				extra.push(text_message);
+			}
+
+			for player in client.server.players() {
+				send_system_message(player.clone(), message.clone(), false)?;
+			}
+		}
+		PlayPacket::SetPlayerPosition(SetPlayerPosition { x, y, z, flags }) => {
+			let entity = client.entity_info().unwrap().entity.clone();
+			move_entity(&client.server, &entity, (x, y, z), None, flags & 0x01 != 0)?;
+		}
+		PlayPacket::SetPlayerPositionAndRotation(SetPlayerPositionAndRotation {
+			x,
+			y,
+			z,
+			yaw,
+			pitch,
+			flags,
+		}) => {
+			let entity = client.entity_info().unwrap().entity.clone();
+			move_entity(
+				&client.server,
+				&entity,
+				(x, y, z),
+				Some((yaw, pitch)),
+				flags & 0x01 != 0,
+			)?;
+		}
+		PlayPacket::SetPlayerRotation(SetPlayerRotation { yaw, pitch, flags }) => {
+			let entity = client.entity_info().unwrap().entity.clone();
+			let position = entity.position();
+			move_entity(
+				&client.server,
+				&entity,
+				position,
+				Some((yaw, pitch)),
+				flags & 0x01 != 0,
+			)?;
+		}
 	}
 
 	Ok(())
@@ -573,12 +588,32 @@ pub fn handle_play_state(
 pub fn handle_disconnect(
 	client: Arc<ClientContext>, // Контекст клиента
 ) -> Result<(), ServerError> {
+	if client.entity_info().is_none() {
+		// Клиент не дошел до режима Play (например просто запросил статус)
+		return Ok(());
+	}
+
+	// Курсор и сетку крафта возвращаем в инвентарь, иначе они не сохранятся
+	if let Err(error) = close_window(&client) {
+		error!("Не удалось закрыть окно при выходе: {error:?}");
+	}
+
+	// Сохраняем до того, как игрок пропадет из мира
+	if let Err(error) = save_player(&client) {
+		error!(
+			"Не удалось сохранить игрока {}: {error:?}",
+			client.player_info().unwrap().name
+		);
+	}
+
+	remove_entity(&client.server, client.entity_info().unwrap().entity_id)?;
+
 	for player in client.server.players() {
-		if client.addr == player.addr {
+		if client.id == player.id {
 			continue;
 		}
 
-		remove_player(player.clone(), client.clone())?;
+		remove_player_info(player.clone(), client.clone())?;
 		send_rainbow_message(
 			&player,
 			format!("{} left the game", client.player_info().unwrap().name),
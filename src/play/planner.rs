@@ -1 +1,292 @@
-// TODO: thread-safe planner like BukkitScheduler
+use std::{
+	collections::{HashMap, VecDeque},
+	sync::{
+		Arc, Mutex,
+		atomic::{AtomicBool, AtomicU64, Ordering},
+	},
+	thread,
+	time::{Duration, Instant},
+};
+
+use log::{error, warn};
+
+use crate::{ServerError, context::ServerContext, play::entity::flush_metadata};
+
+// Планировщик задач и основной цикл тиков сервера, как BukkitScheduler
+//
+// Все тики идут в одном потоке с постоянной частотой 20 TPS
+// Если тик затянулся, следующие идут без паузы, пока сервер не догонит расписание,
+// а если отставание больше MAX_LAG тиков, пропущенные тики просто выкидываются
+// Задачи выполняются без блокировки планировщика, так что внутри задачи можно планировать и отменять задачи
+
+pub const TICKS_PER_SECOND: u64 = 20;
+pub const TICK_DURATION: Duration = Duration::from_millis(1000 / TICKS_PER_SECOND);
+
+const MAX_LAG: u32 = 40; // 2 секунды
+const STATS_SIZE: usize = 100; // по скольким последним тикам считается статистика
+
+pub type TaskId = u64;
+
+type Task = Box<dyn FnMut(&Arc<ServerContext>, TaskId) -> Result<(), ServerError> + Send>;
+
+struct ScheduledTask {
+	id: TaskId,
+	tick: u64,           // на каком тике выполнить
+	period: Option<u64>, // для повторяющихся задач
+	task: Task,
+}
+
+#[derive(Default)]
+struct TickStats {
+	starts: VecDeque<Instant>,
+	durations: VecDeque<Duration>,
+}
+
+pub struct Planner {
+	tick: AtomicU64,
+	next_id: AtomicU64,
+	tasks: Mutex<Vec<ScheduledTask>>,
+	running: Mutex<HashMap<TaskId, bool>>, // задачи текущего тика, уже вынутые из очереди, и отменили ли их
+	stats: Mutex<TickStats>,
+}
+
+impl Default for Planner {
+	fn default() -> Self {
+		Self::new()
+	}
+}
+
+impl Planner {
+	pub fn new() -> Planner {
+		Planner {
+			tick: AtomicU64::new(0),
+			next_id: AtomicU64::new(0),
+			tasks: Mutex::new(Vec::new()),
+			running: Mutex::new(HashMap::new()),
+			stats: Mutex::new(TickStats::default()),
+		}
+	}
+
+	fn schedule(&self, delay: u64, period: Option<u64>, task: Task) -> TaskId {
+		let id = self.next_id.fetch_add(1, Ordering::SeqCst);
+		self.tasks.lock().unwrap().push(ScheduledTask {
+			id,
+			tick: self.current_tick() + delay.max(1),
+			period: period.map(|o| o.max(1)),
+			task,
+		});
+		id
+	}
+
+	/// Выполняет задачу на следующем тике
+	pub fn run<F>(&self, task: F) -> TaskId
+	where
+		F: FnMut(&Arc<ServerContext>, TaskId) -> Result<(), ServerError> + Send + 'static,
+	{
+		self.schedule(1, None, Box::new(task))
+	}
+
+	/// Выполняет задачу через delay тиков, 0 значит на следующем тике
+	pub fn run_later<F>(&self, delay: u64, task: F) -> TaskId
+	where
+		F: FnMut(&Arc<ServerContext>, TaskId) -> Result<(), ServerError> + Send + 'static,
+	{
+		self.schedule(delay, None, Box::new(task))
+	}
+
+	/// Выполняет задачу через delay тиков и дальше каждые period тиков, пока ее не отменят
+	pub fn run_repeating<F>(&self, delay: u64, period: u64, task: F) -> TaskId
+	where
+		F: FnMut(&Arc<ServerContext>, TaskId) -> Result<(), ServerError> + Send + 'static,
+	{
+		self.schedule(delay, Some(period), Box::new(task))
+	}
+
+	/// Отменяет задачу, можно звать из самой задачи
+	pub fn cancel(&self, id: TaskId) {
+		self.tasks.lock().unwrap().retain(|o| o.id != id);
+
+		// Задачи этого тика уже не в очереди, помечаем, чтобы она не выполнилась или не вернулась в очередь
+		if let Some(cancelled) = self.running.lock().unwrap().get_mut(&id) {
+			*cancelled = true;
+		}
+	}
+
+	pub fn is_scheduled(&self, id: TaskId) -> bool {
+		self.tasks.lock().unwrap().iter().any(|o| o.id == id)
+	}
+
+	/// Номер текущего тика с запуска сервера
+	pub fn current_tick(&self) -> u64 {
+		self.tick.load(Ordering::SeqCst)
+	}
+
+	/// Выполняет задачи, которым пришло время
+	fn run_tasks(&self, server: &Arc<ServerContext>) {
+		let tick = self.tick.fetch_add(1, Ordering::SeqCst) + 1;
+
+		let due: Vec<ScheduledTask> = {
+			let mut tasks = self.tasks.lock().unwrap();
+			let (due, rest) = std::mem::take(&mut *tasks)
+				.into_iter()
+				.partition(|o| o.tick <= tick);
+			*tasks = rest;
+			due
+		};
+		*self.running.lock().unwrap() = due.iter().map(|o| (o.id, false)).collect();
+
+		for mut scheduled in due {
+			// Отменена задачей, которая выполнилась раньше в этом же тике
+			if self.running.lock().unwrap().get(&scheduled.id) == Some(&true) {
+				self.running.lock().unwrap().remove(&scheduled.id);
+				continue;
+			}
+
+			if let Err(error) = (scheduled.task)(server, scheduled.id) {
+				error!("Ошибка в задаче {}: {error:?}", scheduled.id);
+			}
+
+			if self.running.lock().unwrap().remove(&scheduled.id) == Some(true) {
+				continue;
+			}
+
+			if let Some(period) = scheduled.period {
+				scheduled.tick = tick + period;
+				self.tasks.lock().unwrap().push(scheduled);
+			}
+		}
+	}
+
+	fn record(&self, start: Instant, duration: Duration) {
+		let mut stats = self.stats.lock().unwrap();
+		stats.starts.push_back(start);
+		stats.durations.push_back(duration);
+		if stats.starts.len() > STATS_SIZE {
+			stats.starts.pop_front();
+			stats.durations.pop_front();
+		}
+	}
+
+	/// Среднее время тика в миллисекундах за последние тики
+	pub fn mspt(&self) -> f64 {
+		let stats = self.stats.lock().unwrap();
+		if stats.durations.is_empty() {
+			return 0.0;
+		}
+		let total: Duration = stats.durations.iter().sum();
+		total.as_secs_f64() * 1000.0 / stats.durations.len() as f64
+	}
+
+	/// Тиков в секунду за последние тики, не больше 20
+	pub fn tps(&self) -> f64 {
+		let stats = self.stats.lock().unwrap();
+		let (Some(first), Some(last)) = (stats.starts.front(), stats.starts.back()) else {
+			return TICKS_PER_SECOND as f64;
+		};
+		let elapsed = last.duration_since(*first).as_secs_f64();
+		if elapsed <= 0.0 {
+			return TICKS_PER_SECOND as f64;
+		}
+		((stats.starts.len() - 1) as f64 / elapsed).min(TICKS_PER_SECOND as f64)
+	}
+}
+
+// Один тик сервера: задачи, потом симуляция мира
+fn tick(server: &Arc<ServerContext>) {
+	server.planner().run_tasks(server);
+	flush_metadata(server);
+}
+
+/// Основной цикл тиков, крутится пока running не сбросят
+pub fn run_ticks(server: Arc<ServerContext>, running: Arc<AtomicBool>) {
+	let mut next = Instant::now();
+
+	while running.load(Ordering::SeqCst) {
+		let start = Instant::now();
+		tick(&server);
+		server.planner().record(start, start.elapsed());
+
+		next += TICK_DURATION;
+		let now = Instant::now();
+
+		if next > now {
+			thread::sleep(next - now);
+		} else if now - next > TICK_DURATION * MAX_LAG {
+			let skipped = ((now - next).as_millis() / TICK_DURATION.as_millis()) as u64;
+			warn!("Сервер не успевает, пропущено тиков: {skipped}");
+			next = now;
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use std::sync::atomic::AtomicUsize;
+
+	use super::*;
+	use crate::config::Config;
+
+	fn server() -> Arc<ServerContext> {
+		Arc::new(ServerContext::new(Arc::new(Config::default())))
+	}
+
+	#[test]
+	fn cancel_unknown_or_finished_task() {
+		let server = server();
+		let planner = server.planner();
+
+		let id = planner.run(|_, _| Ok(()));
+		planner.run_tasks(&server);
+		planner.cancel(id);
+		planner.cancel(12345);
+
+		assert!(planner.running.lock().unwrap().is_empty());
+		assert!(!planner.is_scheduled(id));
+	}
+
+	#[test]
+	fn cancel_repeating_task_from_itself() {
+		let server = server();
+		let planner = server.planner();
+		let runs = Arc::new(AtomicUsize::new(0));
+
+		let counter = runs.clone();
+		let id = planner.run_repeating(1, 1, move |server, id| {
+			if counter.fetch_add(1, Ordering::SeqCst) == 2 {
+				server.planner().cancel(id);
+			}
+			Ok(())
+		});
+
+		for _ in 0..10 {
+			planner.run_tasks(&server);
+		}
+		assert_eq!(runs.load(Ordering::SeqCst), 3);
+		assert!(!planner.is_scheduled(id));
+		assert!(planner.running.lock().unwrap().is_empty());
+	}
+
+	#[test]
+	fn cancel_task_of_the_same_tick() {
+		let server = server();
+		let planner = server.planner();
+		let runs = Arc::new(AtomicUsize::new(0));
+
+		let later = planner.next_id.load(Ordering::SeqCst) + 1;
+		planner.run(move |server, _| {
+			server.planner().cancel(later);
+			Ok(())
+		});
+		let counter = runs.clone();
+		let id = planner.run_repeating(1, 1, move |_, _| {
+			counter.fetch_add(1, Ordering::SeqCst);
+			Ok(())
+		});
+		assert_eq!(id, later);
+
+		planner.run_tasks(&server);
+		planner.run_tasks(&server);
+		assert_eq!(runs.load(Ordering::SeqCst), 0);
+		assert!(!planner.is_scheduled(id));
+	}
+}
@@ -0,0 +1,141 @@
+use std::{
+	panic::{AssertUnwindSafe, catch_unwind},
+	sync::{
+		Arc, Mutex,
+		mpsc::{self, Sender},
+	},
+	thread::{self, JoinHandle},
+};
+
+use log::error;
+
+// Пул потоков для работы, которой не место в тике: логин, сохранение вышедших игроков
+//
+// Потоков фиксированное число, задачи ждут в общей очереди. Паника в задаче логируется,
+// а поток остается в пуле
+
+type Job = Box<dyn FnOnce() + Send>;
+
+pub struct WorkerPool {
+	sender: Mutex<Option<Sender<Job>>>,
+	threads: Mutex<Vec<JoinHandle<()>>>,
+}
+
+impl WorkerPool {
+	/// `name` - имя потоков, чтобы их было видно в отладчике
+	pub fn new(name: &str, size: usize) -> WorkerPool {
+		let (sender, receiver) = mpsc::channel::<Job>();
+		let receiver = Arc::new(Mutex::new(receiver));
+
+		let threads = (0..size.max(1))
+			.map(|index| {
+				let receiver = receiver.clone();
+				thread::Builder::new()
+					.name(format!("{name}-{index}"))
+					.spawn(move || {
+						loop {
+							// Лок отпускается сразу после recv, задачи идут параллельно
+							let job = receiver.lock().unwrap().recv();
+							let Ok(job) = job else {
+								break; // пул остановлен и очередь пуста
+							};
+							if catch_unwind(AssertUnwindSafe(job)).is_err() {
+								error!(
+									"Задача в пуле {} упала",
+									thread::current().name().unwrap_or("")
+								);
+							}
+						}
+					})
+					.expect("Не удалось запустить поток пула")
+			})
+			.collect();
+
+		WorkerPool {
+			sender: Mutex::new(Some(sender)),
+			threads: Mutex::new(threads),
+		}
+	}
+
+	/// Ставит задачу в очередь, false если пул уже остановлен
+	pub fn submit(&self, job: impl FnOnce() + Send + 'static) -> bool {
+		match self.sender.lock().unwrap().as_ref() {
+			Some(sender) => sender.send(Box::new(job)).is_ok(),
+			None => false,
+		}
+	}
+
+	/// Перестает принимать задачи и ждет, пока потоки доделают очередь
+	pub fn shutdown(&self) {
+		self.sender.lock().unwrap().take();
+
+		let threads = std::mem::take(&mut *self.threads.lock().unwrap());
+		for thread in threads {
+			let _ = thread.join();
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use std::{
+		sync::atomic::{AtomicUsize, Ordering},
+		time::Duration,
+	};
+
+	use super::*;
+
+	#[test]
+	fn runs_queued_jobs_before_shutdown() {
+		let pool = WorkerPool::new("test", 2);
+		let done = Arc::new(AtomicUsize::new(0));
+
+		for _ in 0..10 {
+			let done = done.clone();
+			assert!(pool.submit(move || {
+				thread::sleep(Duration::from_millis(5));
+				done.fetch_add(1, Ordering::SeqCst);
+			}));
+		}
+		pool.shutdown();
+
+		assert_eq!(done.load(Ordering::SeqCst), 10);
+		assert!(!pool.submit(|| {}));
+	}
+
+	#[test]
+	fn threads_are_limited() {
+		let pool = WorkerPool::new("test", 3);
+		let running = Arc::new(AtomicUsize::new(0));
+		let peak = Arc::new(AtomicUsize::new(0));
+
+		for _ in 0..12 {
+			let running = running.clone();
+			let peak = peak.clone();
+			pool.submit(move || {
+				let now = running.fetch_add(1, Ordering::SeqCst) + 1;
+				peak.fetch_max(now, Ordering::SeqCst);
+				thread::sleep(Duration::from_millis(10));
+				running.fetch_sub(1, Ordering::SeqCst);
+			});
+		}
+		pool.shutdown();
+
+		assert!(peak.load(Ordering::SeqCst) <= 3);
+	}
+
+	#[test]
+	fn panic_does_not_kill_worker() {
+		let pool = WorkerPool::new("test", 1);
+		let done = Arc::new(AtomicUsize::new(0));
+
+		pool.submit(|| panic!("задача упала"));
+		let counter = done.clone();
+		pool.submit(move || {
+			counter.fetch_add(1, Ordering::SeqCst);
+		});
+		pool.shutdown();
+
+		assert_eq!(done.load(Ordering::SeqCst), 1);
+	}
+}
@@ -0,0 +1,86 @@
+use std::sync::Arc;
+
+use super::{
+	entity::{remove_entity, spawn_entity, update_tracking},
+	helper::{send_entity_event, send_game_event, set_center_chunk, sync_player_pos},
+	inventory::sync_window,
+};
+use crate::{
+	ServerError,
+	context::{ServerContext, World},
+	player::context::ClientContext,
+	protocol::packets::{VarInt, clientbound::play::Respawn},
+};
+
+// Переходы игроков между мирами
+//
+// Клиент сам выгружает старый мир, все чанки и сущности при Respawn,
+// поэтому у сервера достаточно забыть, что у игрока было загружено, и отправить все заново
+
+/// Переносит игрока в другой мир, без позиции - на спавн этого мира
+pub fn change_world(
+	client: &Arc<ClientContext>,
+	world: &Arc<World>,
+	position: Option<(f64, f64, f64)>,
+) -> Result<(), ServerError> {
+	let server = &client.server;
+	let info = client.entity_info().unwrap();
+	let entity = info.entity.clone();
+
+	// Чанки нового мира дошлет досылка чанков в тике игрока, см. play::stream_chunks
+	let _lock = client.lock_chunks();
+
+	// Из старого мира игрок пропадает у всех, а у самого игрока пропадает все
+	remove_entity(server, entity.id)?;
+	info.tracked().clear();
+	for (x, z) in client.loaded_chunks() {
+		client.set_chunk_loaded(x, z, false);
+	}
+
+	let (x, y, z) = position.unwrap_or_else(|| world.spawn_point());
+	let (yaw, pitch) = entity.rotation();
+	entity.set_world(&world.name);
+	entity.set_position((x, y, z));
+
+	let game_mode = info.game_mode().id();
+	client.send(&Respawn {
+		dimension_type: VarInt(world.dimension_id()),
+		dimension_name: world.name.clone(),
+		hashed_seed: world.hashed_seed,
+		game_mode,
+		previous_game_mode: game_mode as i8,
+		is_debug: false,
+		is_flat: world.is_flat(),
+		death_location: None,
+		portal_cooldown: VarInt(20),
+		sea_level: VarInt(60),
+		data_kept: 0x03, // атрибуты и метаданные остаются
+	})?;
+
+	// Новый игрок на клиенте не помнит уровень прав
+	send_entity_event(client.clone(), entity.id, 24 + info.permission_level())?;
+	sync_player_pos(client.clone(), x, y, z, 0.0, 0.0, 0.0, yaw, pitch, 0)?;
+	send_game_event(client.clone(), 13, 0.0)?; // 13 - Start waiting for level chunks
+	sync_window(client)?;
+
+	let (chunk_x, chunk_z) = entity.chunk();
+	set_center_chunk(client.clone(), chunk_x, chunk_z)?;
+
+	spawn_entity(server, world, entity)?;
+	update_tracking(client)
+}
+
+/// Уводит всех игроков в основной мир и выгружает мир с диска
+pub fn unload_world(server: &Arc<ServerContext>, name: &str) -> Result<(), ServerError> {
+	let default = server.world.default_world()?;
+	if default.name == name {
+		return Err(ServerError::MainWorld);
+	}
+
+	for player in server.players_in(name) {
+		change_world(&player, &default, None)?;
+	}
+
+	server.world.unload_world(name)?;
+	Ok(())
+}
@@ -1,67 +1,82 @@
 use std::{
-	collections::VecDeque,
+	collections::{HashMap, HashSet, VecDeque},
 	hash::Hash,
-	net::{SocketAddr, TcpStream},
+	net::{IpAddr, SocketAddr},
 	sync::{
-		Arc, Mutex, RwLock,
+		Arc, Mutex, MutexGuard, RwLock,
 		atomic::{AtomicBool, Ordering},
 	},
-	thread,
-	time::Duration,
+	time::Instant,
 };
 
-use rust_mc_proto::{MinecraftConnection, Packet};
+use craftflow_nbt::DynNBT;
+use rust_mc_proto::Packet;
+use serde::{Deserialize, Serialize};
 use uuid::Uuid;
 
-use super::helper::ProtocolHelper;
-use crate::{ServerError, context::ServerContext, protocol::ConnectionState};
+use super::{helper::ProtocolHelper, inventory::PlayerInventory, registry::ClientId};
+use crate::{
+	ServerError,
+	context::{ServerContext, World},
+	data::component::TextComponent,
+	data::slot::Slot,
+	network::connection::Connection,
+	protocol::{ConnectionState, packets::PacketData, session::ProfileProperty},
+	world::entity::Entity,
+};
+
+// Сколько неожиданных пакетов держим в буфере, см. start_buffering
+const MAX_BUFFERED_PACKETS: usize = 256;
 
 // Клиент контекст
 // Должен быть обернут в Arc для передачи между потоками
 pub struct ClientContext {
 	pub server: Arc<ServerContext>,
-	pub addr: SocketAddr,
-	conn: RwLock<MinecraftConnection<TcpStream>>,
+	pub id: ClientId, // айди подключения, ключ в ServerContext.clients
+	conn: Arc<Connection>,
 	handshake: RwLock<Option<Handshake>>,
 	client_info: RwLock<Option<ClientInfo>>,
 	player_info: RwLock<Option<PlayerInfo>>,
 	state: RwLock<ConnectionState>,
 	packet_buffer: Mutex<VecDeque<Packet>>,
-	read_loop: AtomicBool,
-	is_alive: AtomicBool,
+	buffering: AtomicBool,
 	entity_info: RwLock<Option<Arc<PlayerEntityInfo>>>,
+	loaded_chunks: RwLock<HashSet<(i32, i32)>>, // чанки, которые отправлены клиенту
+	chunk_sending: Mutex<()>,                   // см. lock_chunks
+	finished: AtomicBool,                       // отключение уже обработано
 }
 
-// Реализуем сравнение через адрес
-// IPv4 не должен обманывать, иначе у нас случится коллапс
+// Сравнение через айди подключения, адреса могут совпадать
 impl PartialEq for ClientContext {
 	fn eq(&self, other: &Self) -> bool {
-		self.addr == other.addr
+		self.id == other.id
 	}
 }
 
 impl Hash for ClientContext {
 	fn hash<H: std::hash::Hasher>(&self, state: &mut H) {
-		self.addr.hash(state);
+		self.id.hash(state);
 	}
 }
 
 impl Eq for ClientContext {}
 
 impl ClientContext {
-	pub fn new(server: Arc<ServerContext>, conn: MinecraftConnection<TcpStream>) -> ClientContext {
+	pub fn new(server: Arc<ServerContext>, conn: Arc<Connection>) -> ClientContext {
 		ClientContext {
+			id: server.clients.next_id(),
 			server,
-			addr: conn.get_ref().peer_addr().unwrap(),
-			conn: RwLock::new(conn),
+			conn,
 			handshake: RwLock::new(None),
 			client_info: RwLock::new(None),
 			player_info: RwLock::new(None),
 			state: RwLock::new(ConnectionState::Handshake),
 			packet_buffer: Mutex::new(VecDeque::new()),
-			read_loop: AtomicBool::new(false),
-			is_alive: AtomicBool::new(true),
+			buffering: AtomicBool::new(false),
 			entity_info: RwLock::new(None),
+			loaded_chunks: RwLock::new(HashSet::new()),
+			chunk_sending: Mutex::new(()),
+			finished: AtomicBool::new(false),
 		}
 	}
 
@@ -95,6 +110,16 @@ impl ClientContext {
 		Ok(())
 	}
 
+	/// Адрес игрока: адрес сокета, а за балансировщиком или прокси - тот, что они переслали
+	pub fn addr(&self) -> SocketAddr {
+		self.conn.addr()
+	}
+
+	/// Заменяет айпи на настоящий от Velocity или BungeeCord, порт остается от сокета
+	pub fn set_forwarded_ip(self: &Arc<Self>, ip: IpAddr) {
+		self.conn.set_addr(SocketAddr::new(ip, self.addr().port()));
+	}
+
 	pub fn handshake(self: &Arc<Self>) -> Option<Handshake> {
 		self.handshake.read().unwrap().clone()
 	}
@@ -111,6 +136,27 @@ impl ClientContext {
 		self.entity_info.read().unwrap().clone()
 	}
 
+	pub fn is_chunk_loaded(self: &Arc<Self>, x: i32, z: i32) -> bool {
+		self.loaded_chunks.read().unwrap().contains(&(x, z))
+	}
+
+	pub fn set_chunk_loaded(self: &Arc<Self>, x: i32, z: i32, loaded: bool) {
+		if loaded {
+			self.loaded_chunks.write().unwrap().insert((x, z));
+		} else {
+			self.loaded_chunks.write().unwrap().remove(&(x, z));
+		}
+	}
+
+	pub fn loaded_chunks(self: &Arc<Self>) -> Vec<(i32, i32)> {
+		self.loaded_chunks.read().unwrap().iter().copied().collect()
+	}
+
+	/// Держится, пока игроку отправляются чанки, чтобы смена мира не попала в середину отправки
+	pub fn lock_chunks(&self) -> MutexGuard<'_, ()> {
+		self.chunk_sending.lock().unwrap()
+	}
+
 	pub fn state(self: &Arc<Self>) -> ConnectionState {
 		self.state.read().unwrap().clone()
 	}
@@ -128,30 +174,32 @@ impl ClientContext {
 			packet.get_mut().set_position(0);
 		}
 		if !cancelled {
-			match self.conn.write().unwrap().write_packet(&packet) {
-				Ok(_) => {}
-				Err(e) => {
-					self.is_alive.store(false, Ordering::SeqCst);
-					return Err(e.into());
-				}
-			};
+			self.conn.write_packet(&packet)?;
 		}
 		Ok(())
 	}
 
-	pub fn run_read_loop(self: &Arc<Self>) -> Result<(), ServerError> {
-		self.read_loop.store(true, Ordering::SeqCst);
-
-		let mut conn = self.conn.read().unwrap().try_clone()?; // так можно делать т.к сокет это просто поинтер
+	/// Включает буферизацию пакетов
+	///
+	/// До этого read_packet ругается на любой неожиданный пакет (так и надо в Login и Configuration),
+	/// а после - складывает неожиданные пакеты в буфер, откуда их потом можно достать
+	pub fn start_buffering(self: &Arc<Self>) {
+		self.buffering.store(true, Ordering::SeqCst);
+	}
 
-		while self.is_alive() {
-			let mut packet = match conn.read_packet() {
-				Ok(v) => v,
-				Err(e) => {
-					self.is_alive.store(false, Ordering::SeqCst);
-					return Err(e.into());
+	// Берет следующий пакет из соединения и прогоняет через пакет хандлеры
+	// Если `wait` - ждет пока пакет придет, иначе возвращает None
+	fn next_packet(self: &Arc<Self>, wait: bool) -> Result<Option<Packet>, ServerError> {
+		loop {
+			let mut packet = if wait {
+				self.conn.read_packet()?
+			} else {
+				match self.conn.try_read_packet()? {
+					Some(packet) => packet,
+					None => return Ok(None),
 				}
 			};
+
 			let mut cancelled = false;
 			let state = self.state();
 			for handler in self
@@ -163,71 +211,66 @@ impl ClientContext {
 				packet.get_mut().set_position(0);
 			}
 			if !cancelled {
-				self.packet_buffer.lock().unwrap().push_back(packet);
+				return Ok(Some(packet));
 			}
 		}
-
-		Ok(())
 	}
 
 	/// Please avoid using of this bullshit
 	pub fn read_any_packet(self: &Arc<Self>) -> Result<Packet, ServerError> {
-		if self.read_loop.load(Ordering::SeqCst) {
-			loop {
-				if let Some(packet) = self.packet_buffer.lock().unwrap().pop_front() {
-					return Ok(packet);
-				}
-				thread::sleep(Duration::from_millis(4));
-			}
-		} else {
-			let state = self.state();
+		if self.buffering.load(Ordering::SeqCst)
+			&& let Some(packet) = self.packet_buffer.lock().unwrap().pop_front()
+		{
+			return Ok(packet);
+		}
 
-			loop {
-				let mut packet = match self.conn.write().unwrap().read_packet() {
-					Ok(v) => v,
-					Err(e) => {
-						self.is_alive.store(false, Ordering::SeqCst);
-						return Err(e.into());
-					}
-				};
-				let mut cancelled = false;
-				for handler in self
-					.server
-					.packet_handlers(|o| o.on_incoming_packet_priority())
-					.iter()
-				{
-					handler.on_incoming_packet(self.clone(), &mut packet, &mut cancelled, state.clone())?;
-					packet.get_mut().set_position(0);
-				}
-				if !cancelled {
-					break Ok(packet);
-				}
+		loop {
+			if let Some(packet) = self.next_packet(true)? {
+				return Ok(packet);
 			}
 		}
 	}
 
+	/// Как read_any_packet, но не ждет, а возвращает None если пакетов нет
+	pub fn try_read_any_packet(self: &Arc<Self>) -> Result<Option<Packet>, ServerError> {
+		if let Some(packet) = self.packet_buffer.lock().unwrap().pop_front() {
+			return Ok(Some(packet));
+		}
+
+		self.next_packet(false)
+	}
+
+	// Пакеты, которые никто не ждет, не должны копиться бесконечно - старые выкидываем
+	fn buffer_packet(self: &Arc<Self>, packet: Packet) {
+		let mut buffer = self.packet_buffer.lock().unwrap();
+		if buffer.len() >= MAX_BUFFERED_PACKETS {
+			buffer.pop_front();
+		}
+		buffer.push_back(packet);
+	}
+
+	fn take_buffered(self: &Arc<Self>, ids: &[u8]) -> Option<Packet> {
+		let mut buffer = self.packet_buffer.lock().unwrap();
+		let index = buffer.iter().position(|o| ids.contains(&o.id()))?;
+		buffer.remove(index)
+	}
+
 	pub fn read_packet(self: &Arc<Self>, ids: &[u8]) -> Result<Packet, ServerError> {
-		if self.read_loop.load(Ordering::SeqCst) {
+		if self.buffering.load(Ordering::SeqCst) {
+			if let Some(packet) = self.take_buffered(ids) {
+				return Ok(packet);
+			}
+
 			loop {
-				{
-					let mut locked = self.packet_buffer.lock().unwrap();
-					for (i, packet) in locked.clone().iter().enumerate() {
-						if ids.contains(&packet.id()) {
-							locked.remove(i);
-							return Ok(packet.clone());
-						}
+				if let Some(packet) = self.next_packet(true)? {
+					if ids.contains(&packet.id()) {
+						return Ok(packet);
 					}
+					self.buffer_packet(packet);
 				}
-				thread::sleep(Duration::from_millis(4));
 			}
 		} else {
-			let packet = match self.read_any_packet() {
-				Ok(v) => v,
-				Err(e) => {
-					self.is_alive.store(false, Ordering::SeqCst);
-					return Err(e);
-				}
-			};
+			let packet = self.read_any_packet()?;
 
 			if ids.contains(&packet.id()) {
 				Ok(packet)
@@ -237,25 +280,104 @@ impl ClientContext {
 		}
 	}
 
+	/// Как read_packet в режиме буферизации, но не ждет, а возвращает None если нужного пакета нет
+	pub fn try_read_packet(self: &Arc<Self>, ids: &[u8]) -> Result<Option<Packet>, ServerError> {
+		if let Some(packet) = self.take_buffered(ids) {
+			return Ok(Some(packet));
+		}
+
+		while let Some(packet) = self.next_packet(false)? {
+			if ids.contains(&packet.id()) {
+				return Ok(Some(packet));
+			}
+			self.buffer_packet(packet);
+		}
+
+		Ok(None)
+	}
+
+	/// Отправляет типизированный пакет
+	pub fn send<P: PacketData>(self: &Arc<Self>, packet: &P) -> Result<(), ServerError> {
+		if self.state() != P::STATE {
+			return Err(ServerError::UnexpectedState);
+		}
+		self.write_packet(&packet.encode()?)
+	}
+
+	/// Читает типизированный пакет, как read_packet но с одним айди
+	pub fn receive<P: PacketData>(self: &Arc<Self>) -> Result<P, ServerError> {
+		if self.state() != P::STATE {
+			return Err(ServerError::UnexpectedState);
+		}
+		P::decode(&mut self.read_packet(&[P::ID])?)
+	}
+
 	pub fn push_packet_back(self: &Arc<Self>, packet: Packet) {
 		self.packet_buffer.lock().unwrap().push_back(packet)
 	}
 
 	pub fn close(self: &Arc<Self>) {
-		self.conn.write().unwrap().close();
+		self.conn.close();
 	}
 
 	pub fn set_compression(self: &Arc<Self>, threshold: Option<usize>) {
-		self.conn.write().unwrap().set_compression(threshold);
+		self.conn.set_compression(threshold);
+	}
+
+	/// Включает шифрование на соединении, все следующие пакеты будут шифроваться
+	pub fn set_encryption(self: &Arc<Self>, shared_secret: &[u8]) -> Result<(), ServerError> {
+		self.conn.set_encryption(shared_secret)
+	}
+
+	pub fn is_encrypted(self: &Arc<Self>) -> bool {
+		self.conn.is_encrypted()
+	}
+
+	/// Сколько байт еще не ушло клиенту
+	pub fn pending_bytes(self: &Arc<Self>) -> usize {
+		self.conn.pending_bytes()
+	}
+
+	/// Пакеты, отправленные игроку с прошлого вызова, для тестов
+	#[cfg(test)]
+	pub(crate) fn take_sent(&self) -> Vec<Packet> {
+		self.conn.take_sent()
 	}
 
 	pub fn is_alive(self: &Arc<Self>) -> bool {
-		self.is_alive.load(Ordering::SeqCst)
+		!self.conn.is_closed()
+	}
+
+	/// true только при первом вызове, см. finish_connection
+	pub(crate) fn mark_finished(self: &Arc<Self>) -> bool {
+		!self.finished.swap(true, Ordering::SeqCst)
+	}
+
+	/// Мир, в котором сейчас игрок, до входа в игру - основной
+	pub fn world(self: &Arc<Self>) -> Result<Arc<World>, ServerError> {
+		match self.entity_info() {
+			Some(info) => {
+				let name = info.entity.world();
+				self
+					.server
+					.world
+					.get(&name)
+					.ok_or(ServerError::UnknownWorld(name))
+			}
+			None => self.server.world.default_world(),
+		}
 	}
 
 	pub fn protocol_helper(self: &Arc<Self>) -> ProtocolHelper {
 		ProtocolHelper::new(self.clone())
 	}
+
+	/// Отправляет причину и закрывает соединение, не дожидаясь, пока клиент уйдет сам
+	pub fn kick(self: &Arc<Self>, reason: TextComponent) -> Result<(), ServerError> {
+		let result = self.protocol_helper().disconnect(reason);
+		self.close();
+		result
+	}
 }
 
 #[derive(Clone)]
@@ -283,48 +405,242 @@ pub struct ClientInfo {
 pub struct PlayerInfo {
 	pub name: String,
 	pub uuid: Uuid,
+	pub properties: Vec<ProfileProperty>, // Свойства профиля, например скин (textures)
+}
+
+/// Игрок в режиме Play без сокета, для тестов, все что ему отправляется - остается в буфере
+#[cfg(test)]
+pub(crate) fn test_player(
+	server: &Arc<ServerContext>,
+	name: &str,
+	permission_level: u8,
+) -> Arc<ClientContext> {
+	let client = Arc::new(ClientContext::new(
+		server.clone(),
+		Arc::new(Connection::detached()),
+	));
+	let uuid = crate::play::get_offline_uuid(name);
+	client.set_player_info(PlayerInfo {
+		name: name.to_string(),
+		uuid,
+		properties: Vec::new(),
+	});
+	client.set_state(ConnectionState::Play).unwrap();
+
+	let entity_id = server.world.next_entity_id();
+	client.set_entity_info(PlayerEntityInfo::new(
+		Arc::new(Entity::player(entity_id, uuid, client.id)),
+		GameMode::Survival,
+		permission_level,
+	));
+
+	server.clients.add(client.clone());
+	server.clients.register_player(&client);
+	server.clients.register_entity(&client, entity_id);
+	client
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum GameMode {
+	#[default]
+	Survival,
+	Creative,
+	Adventure,
+	Spectator,
+}
+
+impl GameMode {
+	pub fn from_id(id: u8) -> Option<GameMode> {
+		match id {
+			0 => Some(GameMode::Survival),
+			1 => Some(GameMode::Creative),
+			2 => Some(GameMode::Adventure),
+			3 => Some(GameMode::Spectator),
+			_ => None,
+		}
+	}
+
+	/// Айди для Login и Game Event
+	pub fn id(self) -> u8 {
+		match self {
+			GameMode::Survival => 0,
+			GameMode::Creative => 1,
+			GameMode::Adventure => 2,
+			GameMode::Spectator => 3,
+		}
+	}
+
+	/// Может ли игрок ломать и ставить блоки
+	pub fn can_build(self) -> bool {
+		matches!(self, GameMode::Survival | GameMode::Creative)
+	}
+
+	/// Насколько далеко игрок достает до блоков
+	pub fn block_reach(self) -> f64 {
+		match self {
+			GameMode::Creative => 5.0,
+			_ => 4.5,
+		}
+	}
 }
 
+// Блок, который игрок ломает, и когда он начал
+pub type Digging = ((i32, i32, i32), Instant);
+
 pub struct PlayerEntityInfo {
 	pub entity_id: i32,
 	pub uuid: Uuid,
-	position: RwLock<(f64, f64, f64)>,
-	velocity: RwLock<(f64, f64, f64)>,
-	rotation: RwLock<(f32, f32)>,
+	pub entity: Arc<Entity>, // сущность игрока в мире, позиция и поворот хранятся в ней
+	game_mode: RwLock<GameMode>,
+	permission_level: RwLock<u8>,
+	held_slot: RwLock<usize>,
+	health: RwLock<f32>,
+	inventory: Mutex<PlayerInventory>,
+	plugin_data: Mutex<HashMap<String, DynNBT>>, // сохраняется вместе с игроком, см. player::data
+	other_nbt: Mutex<HashMap<String, DynNBT>>, // ванильные теги из файла игрока, см. PlayerData::other
+	tracked: Mutex<HashSet<i32>>,              // айди сущностей, которые заспавнены у клиента
+	keep_alive: Mutex<Option<i64>>,            // айди keep alive, на который клиент еще не ответил
+	digging: Mutex<Option<Digging>>,           // блок, который игрок начал ломать, и когда
 }
 
 impl PlayerEntityInfo {
-	pub fn new(entity_id: i32, uuid: Uuid) -> PlayerEntityInfo {
+	pub fn new(entity: Arc<Entity>, game_mode: GameMode, permission_level: u8) -> PlayerEntityInfo {
 		PlayerEntityInfo {
-			entity_id,
-			uuid,
-			position: RwLock::new((0.0, 0.0, 0.0)),
-			velocity: RwLock::new((0.0, 0.0, 0.0)),
-			rotation: RwLock::new((0.0, 0.0)),
+			entity_id: entity.id,
+			uuid: entity.uuid,
+			entity,
+			game_mode: RwLock::new(game_mode),
+			permission_level: RwLock::new(permission_level.min(4)),
+			held_slot: RwLock::new(0),
+			health: RwLock::new(20.0),
+			inventory: Mutex::new(PlayerInventory::new()),
+			plugin_data: Mutex::new(HashMap::new()),
+			other_nbt: Mutex::new(HashMap::new()),
+			tracked: Mutex::new(HashSet::new()),
+			keep_alive: Mutex::new(None),
+			digging: Mutex::new(None),
 		}
 	}
 
 	pub fn set_position(self: &Arc<Self>, position: (f64, f64, f64)) {
-		*self.position.write().unwrap() = position;
+		self.entity.set_position(position);
 	}
 
 	pub fn set_velocity(self: &Arc<Self>, velocity: (f64, f64, f64)) {
-		*self.velocity.write().unwrap() = velocity;
+		self.entity.set_velocity(velocity);
 	}
 
 	pub fn set_rotation(self: &Arc<Self>, rotation: (f32, f32)) {
-		*self.rotation.write().unwrap() = rotation;
+		self.entity.set_rotation(rotation);
 	}
 
 	pub fn position(self: &Arc<Self>) -> (f64, f64, f64) {
-		self.position.read().unwrap().clone()
+		self.entity.position()
 	}
 
 	pub fn velocity(self: &Arc<Self>) -> (f64, f64, f64) {
-		self.velocity.read().unwrap().clone()
+		self.entity.velocity()
 	}
 
 	pub fn rotation(self: &Arc<Self>) -> (f32, f32) {
-		self.rotation.read().unwrap().clone()
+		self.entity.rotation()
+	}
+
+	/// Сущности, которые сейчас заспавнены у клиента, см. play::entity
+	pub fn tracked(self: &Arc<Self>) -> MutexGuard<'_, HashSet<i32>> {
+		self.tracked.lock().unwrap()
+	}
+
+	pub fn is_tracking(self: &Arc<Self>, entity_id: i32) -> bool {
+		self.tracked().contains(&entity_id)
+	}
+
+	/// Запоминает отправленный keep alive, возвращает предыдущий, если на него так и не ответили
+	pub fn set_keep_alive(self: &Arc<Self>, id: i64) -> Option<i64> {
+		self.keep_alive.lock().unwrap().replace(id)
+	}
+
+	/// Отмечает keep alive отвеченным, false если такого не ждали
+	pub fn answer_keep_alive(self: &Arc<Self>, id: i64) -> bool {
+		let mut keep_alive = self.keep_alive.lock().unwrap();
+		if *keep_alive == Some(id) {
+			*keep_alive = None;
+			true
+		} else {
+			false
+		}
+	}
+
+	/// Запоминает начало ломания блока, см. play::block
+	pub fn start_digging(self: &Arc<Self>, pos: (i32, i32, i32)) {
+		*self.digging.lock().unwrap() = Some((pos, Instant::now()));
+	}
+
+	/// Сбрасывает ломание, возвращает блок и когда его начали ломать
+	pub fn stop_digging(self: &Arc<Self>) -> Option<Digging> {
+		self.digging.lock().unwrap().take()
+	}
+
+	pub fn set_game_mode(self: &Arc<Self>, game_mode: GameMode) {
+		*self.game_mode.write().unwrap() = game_mode;
+	}
+
+	pub fn game_mode(self: &Arc<Self>) -> GameMode {
+		*self.game_mode.read().unwrap()
+	}
+
+	pub fn set_permission_level(self: &Arc<Self>, level: u8) {
+		*self.permission_level.write().unwrap() = level.min(4);
+	}
+
+	/// Уровень прав как у операторов в ванилле, 0 - обычный игрок, 4 - все команды
+	pub fn permission_level(self: &Arc<Self>) -> u8 {
+		*self.permission_level.read().unwrap()
+	}
+
+	pub fn set_held_slot(self: &Arc<Self>, slot: usize) {
+		*self.held_slot.write().unwrap() = slot.min(8);
+	}
+
+	pub fn held_slot(self: &Arc<Self>) -> usize {
+		*self.held_slot.read().unwrap()
+	}
+
+	/// Только значение на сервере, клиенту его отправляет helper::send_health
+	pub fn set_health(self: &Arc<Self>, health: f32) {
+		*self.health.write().unwrap() = health.clamp(0.0, 20.0);
+	}
+
+	pub fn health(self: &Arc<Self>) -> f32 {
+		*self.health.read().unwrap()
+	}
+
+	/// Данные плагинов, которые переживают перезаход, ключи лучше писать с неймспейсом плагина
+	pub fn plugin_data(self: &Arc<Self>) -> MutexGuard<'_, HashMap<String, DynNBT>> {
+		self.plugin_data.lock().unwrap()
+	}
+
+	/// Ванильные теги файла игрока, которые сервер не понимает, но должен сохранить
+	pub fn other_nbt(self: &Arc<Self>) -> MutexGuard<'_, HashMap<String, DynNBT>> {
+		self.other_nbt.lock().unwrap()
+	}
+
+	/// Инвентарь вместе с курсором и открытым контейнером
+	///
+	/// Пока держишь инвентарь, контейнер открытого окна можно лочить, наоборот нельзя
+	pub fn inventory(self: &Arc<Self>) -> MutexGuard<'_, PlayerInventory> {
+		self.inventory.lock().unwrap()
+	}
+
+	/// Предмет в основной руке
+	pub fn held_item(self: &Arc<Self>) -> Slot {
+		self.inventory().hotbar(self.held_slot()).clone()
+	}
+
+	/// Глаза игрока
+	pub fn eye_position(self: &Arc<Self>) -> (f64, f64, f64) {
+		let (x, y, z) = self.position();
+		(x, y + 1.62, z)
 	}
 }
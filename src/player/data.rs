@@ -0,0 +1,430 @@
+use std::{
+	collections::HashMap,
+	fs,
+	path::{Path, PathBuf},
+	sync::{Arc, Mutex},
+};
+
+use craftflow_nbt::DynNBT;
+use uuid::Uuid;
+
+use super::{
+	context::{ClientContext, GameMode},
+	inventory::slots,
+};
+use crate::{
+	ServerError,
+	data::slot::{Slot, nbt::ItemNbt},
+	world::{chunk::DATA_VERSION, region::Compression},
+};
+
+// Данные игроков между заходами: <папка мира>/playerdata/<uuid>.dat
+//
+// Формат как у ванильного 1.21.5 (gzip NBT), так что файлы можно переносить между ванильным миром и сервером
+// Из ванильных тегов понимаем позицию, поворот, измерение, режим игры, здоровье, выбранный слот и инвентарь,
+// остальные (опыт, еда, эффекты, эндер сундук) хранятся как есть и пишутся обратно при сохранении.
+// Предметы пишутся вместе с компонентами, см. ItemNbt
+
+const PLUGIN_DATA: &str = "PluginData";
+
+// Теги, которые сервер понимает сам и пишет заново, все остальные попадают в PlayerData::other
+const KNOWN_TAGS: &[&str] = &[
+	"DataVersion",
+	"Pos",
+	"Rotation",
+	"Dimension",
+	"playerGameType",
+	"Health",
+	"SelectedItemSlot",
+	"Inventory",
+	"equipment",
+	PLUGIN_DATA,
+];
+
+// Ванильные имена слотов экипировки (тег equipment) и их номера в окне инвентаря
+const EQUIPMENT: &[(&str, usize)] = &[
+	("head", slots::HEAD),
+	("chest", slots::CHEST),
+	("legs", slots::LEGS),
+	("feet", slots::FEET),
+	("offhand", slots::OFFHAND),
+];
+
+#[derive(Debug, Clone)]
+pub struct PlayerData {
+	pub world: String,
+	pub position: (f64, f64, f64),
+	pub rotation: (f32, f32),
+	pub game_mode: GameMode,
+	pub health: f32,
+	pub held_slot: usize,
+	pub inventory: Vec<Slot>, // слоты окна инвентаря игрока, см. inventory::slots
+	pub plugin_data: HashMap<String, DynNBT>, // данные плагинов, см. PlayerEntityInfo::plugin_data
+	pub other: HashMap<String, DynNBT>, // ванильные теги, которые сервер не понимает
+}
+
+impl PlayerData {
+	/// Снимок игрока, None если он еще не в игре
+	pub fn from_player(client: &Arc<ClientContext>) -> Option<PlayerData> {
+		let info = client.entity_info()?;
+
+		Some(PlayerData {
+			world: info.entity.world(),
+			position: info.position(),
+			rotation: info.rotation(),
+			game_mode: info.game_mode(),
+			health: info.health(),
+			held_slot: info.held_slot(),
+			inventory: info.inventory().slots().to_vec(),
+			plugin_data: info.plugin_data().clone(),
+			other: info.other_nbt().clone(),
+		})
+	}
+
+	pub fn from_nbt(nbt: &DynNBT, items: ItemNbt) -> Result<PlayerData, ServerError> {
+		let compound = nbt.as_compound().ok_or(ServerError::DeNbt)?;
+		let numbers = |key: &str| {
+			compound
+				.get(key)
+				.and_then(|o| o.as_list())
+				.map(|o| o.iter().filter_map(number).collect::<Vec<f64>>())
+				.unwrap_or_default()
+		};
+
+		let position = match numbers("Pos")[..] {
+			[x, y, z] => (x, y, z),
+			_ => return Err(ServerError::DeNbt),
+		};
+		let rotation = match numbers("Rotation")[..] {
+			[yaw, pitch] => (yaw as f32, pitch as f32),
+			_ => (0.0, 0.0),
+		};
+
+		let mut inventory = vec![Slot::empty(); slots::COUNT];
+
+		for item in compound
+			.get("Inventory")
+			.and_then(|o| o.as_list())
+			.into_iter()
+			.flatten()
+		{
+			let index = item
+				.as_compound()
+				.and_then(|o| o.get("Slot"))
+				.and_then(number)
+				.and_then(|o| window_slot(o as i32));
+			if let (Some(index), Some(slot)) = (index, items.read_slot(item)) {
+				inventory[index] = slot;
+			}
+		}
+
+		// С 1.21.5 броня и вторая рука лежат отдельно от Inventory
+		if let Some(equipment) = compound.get("equipment").and_then(|o| o.as_compound()) {
+			for (name, index) in EQUIPMENT {
+				if let Some(slot) = equipment.get(*name).and_then(|o| items.read_slot(o)) {
+					inventory[*index] = slot;
+				}
+			}
+		}
+
+		Ok(PlayerData {
+			world: compound
+				.get("Dimension")
+				.and_then(|o| o.as_string())
+				.cloned()
+				.unwrap_or_else(|| "minecraft:overworld".to_string()),
+			position,
+			rotation,
+			game_mode: compound
+				.get("playerGameType")
+				.and_then(number)
+				.and_then(|o| GameMode::from_id(o as u8))
+				.unwrap_or_default(),
+			health: compound
+				.get("Health")
+				.and_then(number)
+				.map(|o| o as f32)
+				.unwrap_or(20.0),
+			held_slot: compound
+				.get("SelectedItemSlot")
+				.and_then(number)
+				.map(|o| (o as usize).min(8))
+				.unwrap_or(0),
+			inventory,
+			plugin_data: compound
+				.get(PLUGIN_DATA)
+				.and_then(|o| o.as_compound())
+				.cloned()
+				.unwrap_or_default(),
+			other: compound
+				.iter()
+				.filter(|(key, _)| !KNOWN_TAGS.contains(&key.as_str()))
+				.map(|(key, value)| (key.clone(), value.clone()))
+				.collect(),
+		})
+	}
+
+	pub fn to_nbt(&self, items: ItemNbt) -> DynNBT {
+		let (x, y, z) = self.position;
+		let (yaw, pitch) = self.rotation;
+
+		let mut inventory = Vec::new();
+		for (index, slot) in self.inventory.iter().enumerate() {
+			let (Some(number), Some(mut item)) = (vanilla_slot(index), items.write_slot(slot)) else {
+				continue;
+			};
+			item.insert("Slot".to_string(), DynNBT::Byte(number));
+			inventory.push(DynNBT::Compound(item));
+		}
+
+		let equipment = EQUIPMENT
+			.iter()
+			.filter_map(|(name, index)| {
+				let item = items.write_slot(self.inventory.get(*index)?)?;
+				Some((name.to_string(), DynNBT::Compound(item)))
+			})
+			.collect();
+
+		let mut compound = self.other.clone();
+		compound.insert("DataVersion".to_string(), DynNBT::Int(DATA_VERSION));
+		compound.insert(
+			"Pos".to_string(),
+			DynNBT::List(vec![
+				DynNBT::Double(x),
+				DynNBT::Double(y),
+				DynNBT::Double(z),
+			]),
+		);
+		compound.insert(
+			"Rotation".to_string(),
+			DynNBT::List(vec![DynNBT::Float(yaw), DynNBT::Float(pitch)]),
+		);
+		compound.insert("Dimension".to_string(), DynNBT::String(self.world.clone()));
+		compound.insert(
+			"playerGameType".to_string(),
+			DynNBT::Int(self.game_mode.id() as i32),
+		);
+		compound.insert("Health".to_string(), DynNBT::Float(self.health));
+		compound.insert(
+			"SelectedItemSlot".to_string(),
+			DynNBT::Int(self.held_slot as i32),
+		);
+		compound.insert("Inventory".to_string(), DynNBT::List(inventory));
+		compound.insert("equipment".to_string(), DynNBT::Compound(equipment));
+		if !self.plugin_data.is_empty() {
+			compound.insert(
+				PLUGIN_DATA.to_string(),
+				DynNBT::Compound(self.plugin_data.clone()),
+			);
+		}
+
+		DynNBT::Compound(compound)
+	}
+}
+
+/// Папка playerdata, файлы пишутся через временный файл, предыдущая версия остается в <uuid>.dat_old
+pub struct PlayerDataStore {
+	path: PathBuf,
+	lock: Mutex<()>, // сохранение на выходе и автосохранение могут писать один файл одновременно
+}
+
+impl PlayerDataStore {
+	pub fn new(path: impl AsRef<Path>) -> PlayerDataStore {
+		PlayerDataStore {
+			path: path.as_ref().to_path_buf(),
+			lock: Mutex::new(()),
+		}
+	}
+
+	pub fn path(&self) -> &Path {
+		&self.path
+	}
+
+	pub fn file(&self, uuid: Uuid) -> PathBuf {
+		self.path.join(format!("{uuid}.dat"))
+	}
+
+	/// Данные игрока, None если он еще не заходил
+	pub fn load(&self, uuid: Uuid, items: ItemNbt) -> Result<Option<PlayerData>, ServerError> {
+		let path = self.file(uuid);
+		if !path.exists() {
+			return Ok(None);
+		}
+
+		let data = fs::read(&path).map_err(|e| ServerError::Storage(e.to_string()))?;
+		let data = Compression::Gzip.decompress(&data)?;
+		let (_, (_, nbt)) =
+			craftflow_nbt::from_slice_named::<DynNBT>(&data).map_err(|_| ServerError::DeNbt)?;
+
+		PlayerData::from_nbt(&nbt, items).map(Some)
+	}
+
+	pub fn save(&self, uuid: Uuid, data: &PlayerData, items: ItemNbt) -> Result<(), ServerError> {
+		let mut bytes = Vec::new();
+		craftflow_nbt::to_writer_named(&mut bytes, "", &data.to_nbt(items))
+			.map_err(|_| ServerError::SerNbt)?;
+		let bytes = Compression::Gzip.compress(&bytes)?;
+
+		let storage_error = |e: std::io::Error| ServerError::Storage(e.to_string());
+		let path = self.file(uuid);
+		let temp = self.path.join(format!("{uuid}.dat.tmp"));
+
+		let _lock = self.lock.lock().unwrap();
+		fs::create_dir_all(&self.path).map_err(storage_error)?;
+		fs::write(&temp, bytes).map_err(storage_error)?;
+		if path.exists() {
+			fs::rename(&path, self.path.join(format!("{uuid}.dat_old"))).map_err(storage_error)?;
+		}
+		fs::rename(&temp, &path).map_err(storage_error)
+	}
+}
+
+/// Сохраняет игрока на диск, если он в игре
+pub fn save_player(client: &Arc<ClientContext>) -> Result<(), ServerError> {
+	let (Some(data), Some(info)) = (PlayerData::from_player(client), client.player_info()) else {
+		return Ok(());
+	};
+	let world = &client.server.world;
+	world.player_data.save(info.uuid, &data, world.item_nbt())
+}
+
+fn number(nbt: &DynNBT) -> Option<f64> {
+	match nbt {
+		DynNBT::Double(value) => Some(*value),
+		DynNBT::Float(value) => Some(*value as f64),
+		DynNBT::Long(value) => Some(*value as f64),
+		DynNBT::Int(value) => Some(*value as f64),
+		DynNBT::Short(value) => Some(*value as f64),
+		DynNBT::Byte(value) => Some(*value as f64),
+		_ => None,
+	}
+}
+
+// Номер слота в ванильном Inventory -> слот окна, 100..103 и -106 - броня и вторая рука до 1.21.5
+fn window_slot(slot: i32) -> Option<usize> {
+	match slot {
+		0..=8 => Some(slots::HOTBAR.start + slot as usize),
+		9..=35 => Some(slot as usize),
+		100 => Some(slots::FEET),
+		101 => Some(slots::LEGS),
+		102 => Some(slots::CHEST),
+		103 => Some(slots::HEAD),
+		-106 => Some(slots::OFFHAND),
+		_ => None,
+	}
+}
+
+// Слот окна -> номер в Inventory, только основной инвентарь и хотбар
+fn vanilla_slot(index: usize) -> Option<i8> {
+	if slots::HOTBAR.contains(&index) {
+		Some((index - slots::HOTBAR.start) as i8)
+	} else if slots::MAIN.contains(&index) {
+		Some(index as i8)
+	} else {
+		None
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::world::{item::ItemRegistry, registry::Registries};
+
+	fn items() -> ItemRegistry {
+		let mut items = ItemRegistry::new();
+		items.register("minecraft:stone", 1);
+		items.register("minecraft:diamond_helmet", 2);
+		items
+	}
+
+	fn vanilla_player() -> DynNBT {
+		let item = |id: &str, slot: i8| {
+			DynNBT::Compound(HashMap::from([
+				("id".to_string(), DynNBT::String(id.to_string())),
+				("count".to_string(), DynNBT::Int(5)),
+				("Slot".to_string(), DynNBT::Byte(slot)),
+			]))
+		};
+
+		DynNBT::Compound(HashMap::from([
+			("DataVersion".to_string(), DynNBT::Int(4325)),
+			(
+				"Pos".to_string(),
+				DynNBT::List(vec![
+					DynNBT::Double(1.5),
+					DynNBT::Double(64.0),
+					DynNBT::Double(-3.5),
+				]),
+			),
+			("Health".to_string(), DynNBT::Float(7.0)),
+			("XpLevel".to_string(), DynNBT::Int(30)),
+			("foodLevel".to_string(), DynNBT::Int(12)),
+			(
+				"abilities".to_string(),
+				DynNBT::Compound(HashMap::from([("mayfly".to_string(), DynNBT::Byte(1))])),
+			),
+			(
+				"EnderItems".to_string(),
+				DynNBT::List(vec![item("minecraft:stone", 0)]),
+			),
+			(
+				"Inventory".to_string(),
+				DynNBT::List(vec![item("minecraft:stone", 0), item("minecraft:stone", 9)]),
+			),
+			(
+				"equipment".to_string(),
+				DynNBT::Compound(HashMap::from([(
+					"head".to_string(),
+					DynNBT::Compound(HashMap::from([
+						(
+							"id".to_string(),
+							DynNBT::String("minecraft:diamond_helmet".to_string()),
+						),
+						("count".to_string(), DynNBT::Int(1)),
+					])),
+				)])),
+			),
+		]))
+	}
+
+	#[test]
+	fn unknown_tags_are_written_back() {
+		let (items, registries) = (items(), Registries::vanilla());
+		let codec = ItemNbt::new(&items, &registries);
+
+		let data = PlayerData::from_nbt(&vanilla_player(), codec).unwrap();
+		assert!(data.other.contains_key("XpLevel"));
+		assert!(!data.other.contains_key("Health"));
+
+		let nbt = data.to_nbt(ItemNbt::new(&items, &registries));
+		let compound = nbt.as_compound().unwrap();
+		let original = vanilla_player();
+		let original = original.as_compound().unwrap();
+		for key in ["XpLevel", "foodLevel", "abilities", "EnderItems"] {
+			assert_eq!(compound.get(key), original.get(key), "{key}");
+		}
+	}
+
+	#[test]
+	fn modelled_tags_come_from_player() {
+		let (items, registries) = (items(), Registries::vanilla());
+
+		let mut data =
+			PlayerData::from_nbt(&vanilla_player(), ItemNbt::new(&items, &registries)).unwrap();
+		assert_eq!(data.position, (1.5, 64.0, -3.5));
+		assert_eq!(data.health, 7.0);
+		assert_eq!(data.inventory[slots::HOTBAR.start].item_id, 1);
+		assert_eq!(data.inventory[9].count, 5);
+		assert_eq!(data.inventory[slots::HEAD].item_id, 2);
+
+		data.health = 20.0;
+		data.inventory[9] = Slot::empty();
+
+		let nbt = data.to_nbt(ItemNbt::new(&items, &registries));
+		let loaded = PlayerData::from_nbt(&nbt, ItemNbt::new(&items, &registries)).unwrap();
+		assert_eq!(loaded.health, 20.0);
+		assert!(loaded.inventory[9].is_empty());
+		assert_eq!(loaded.inventory[slots::HEAD].item_id, 2);
+		assert_eq!(loaded.other, data.other);
+	}
+}
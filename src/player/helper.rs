@@ -1,16 +1,17 @@
 use std::{
-	io::Read,
 	sync::Arc,
 	time::{Duration, SystemTime},
 };
 
-use rust_mc_proto::{DataReader, DataWriter, Packet};
-
 use crate::{
 	ServerError,
-	data::{ReadWriteNBT, component::TextComponent},
+	data::component::TextComponent,
 	protocol::{
-		packet_id::{clientbound, serverbound},
+		packets::{
+			RemainingBytes, VarInt,
+			clientbound::{configuration, login, play},
+			serverbound,
+		},
 		*,
 	},
 };
@@ -39,41 +40,33 @@ impl ProtocolHelper {
 
 	pub fn reset_chat(&self) -> Result<(), ServerError> {
 		match self.state {
-			ConnectionState::Configuration => {
-				self
-					.client
-					.write_packet(&Packet::empty(clientbound::configuration::RESET_CHAT))?;
-				Ok(())
-			}
+			ConnectionState::Configuration => self.client.send(&configuration::ResetChat {}),
 			_ => Err(ServerError::UnexpectedState),
 		}
 	}
 
 	pub fn store_cookie(&self, id: &str, data: &[u8]) -> Result<(), ServerError> {
-		self.client.write_packet(&Packet::build(
-			match self.state {
-				ConnectionState::Configuration => clientbound::configuration::STORE_COOKIE,
-				ConnectionState::Play => clientbound::play::STORE_COOKIE,
-				_ => return Err(ServerError::UnexpectedState),
-			},
-			|p| {
-				p.write_string(id)?;
-				p.write_bytes(data)
-			},
-		)?)?;
-		Ok(())
+		match self.state {
+			ConnectionState::Configuration => self.client.send(&configuration::StoreCookie {
+				key: id.to_string(),
+				payload: data.to_vec(),
+			}),
+			ConnectionState::Play => self.client.send(&play::StoreCookie {
+				key: id.to_string(),
+				payload: data.to_vec(),
+			}),
+			_ => Err(ServerError::UnexpectedState),
+		}
 	}
 
 	/// Leave from Configuration to Play state
 	pub fn leave_configuration(&self) -> Result<(), ServerError> {
 		match self.state {
 			ConnectionState::Configuration => {
+				self.client.send(&configuration::FinishConfiguration {})?;
 				self
 					.client
-					.write_packet(&Packet::empty(clientbound::configuration::FINISH))?;
-				self
-					.client
-					.read_packet(&[serverbound::configuration::ACKNOWLEDGE_FINISH])?;
+					.receive::<serverbound::configuration::AcknowledgeFinishConfiguration>()?;
 				self.client.set_state(ConnectionState::Play)?;
 				Ok(())
 			}
@@ -85,12 +78,10 @@ impl ProtocolHelper {
 	pub fn enter_configuration(&self) -> Result<(), ServerError> {
 		match self.state {
 			ConnectionState::Play => {
+				self.client.send(&play::StartConfiguration {})?;
 				self
 					.client
-					.write_packet(&Packet::empty(clientbound::play::START_CONFIGURATION))?;
-				self
-					.client
-					.read_packet(&[serverbound::play::ACKNOWLEDGE_CONFIGURATION])?;
+					.receive::<serverbound::play::AcknowledgeConfiguration>()?;
 				self.client.set_state(ConnectionState::Configuration)?;
 				Ok(())
 			}
@@ -103,20 +94,14 @@ impl ProtocolHelper {
 		match self.state {
 			ConnectionState::Play => {
 				let time = SystemTime::now();
-				self
-					.client
-					.write_packet(&Packet::empty(clientbound::play::PING))?;
-				self.client.read_packet(&[serverbound::play::PONG])?;
+				self.client.send(&play::Ping { id: 0 })?;
+				self.client.receive::<serverbound::play::Pong>()?;
 				Ok(SystemTime::now().duration_since(time).unwrap())
 			}
 			ConnectionState::Configuration => {
 				let time = SystemTime::now();
-				self
-					.client
-					.write_packet(&Packet::empty(clientbound::configuration::PING))?;
-				self
-					.client
-					.read_packet(&[serverbound::configuration::PONG])?;
+				self.client.send(&configuration::Ping { id: 0 })?;
+				self.client.receive::<serverbound::configuration::Pong>()?;
 				Ok(SystemTime::now().duration_since(time).unwrap())
 			}
 			_ => Err(ServerError::UnexpectedState),
@@ -124,68 +109,43 @@ impl ProtocolHelper {
 	}
 
 	pub fn disconnect(&self, reason: TextComponent) -> Result<(), ServerError> {
-		let packet = match self.state {
-			ConnectionState::Login => {
-				let text = reason.as_json()?;
-				Packet::build(0x00, |p| p.write_string(&text))?
-			}
-			ConnectionState::Configuration => {
-				let mut packet = Packet::empty(0x02);
-				packet.write_nbt(&reason)?;
-				packet
-			}
-			ConnectionState::Play => {
-				let mut packet = Packet::empty(0x1C);
-				packet.write_nbt(&reason)?;
-				packet
-			}
+		match self.state {
+			ConnectionState::Login => self.client.send(&login::Disconnect {
+				reason: reason.as_json()?,
+			}),
+			ConnectionState::Configuration => self.client.send(&configuration::Disconnect { reason }),
+			ConnectionState::Play => self.client.send(&play::Disconnect { reason }),
 			_ => {
 				self.client.close();
-				return Ok(());
+				Ok(())
 			}
-		};
-		self.client.write_packet(&packet)?;
-		Ok(())
+		}
 	}
 
 	/// Returns cookie content
 	pub fn request_cookie(&self, id: &str) -> Result<Option<Vec<u8>>, ServerError> {
 		match self.state {
 			ConnectionState::Configuration => {
-				let mut packet = Packet::empty(clientbound::configuration::COOKIE_REQUEST);
-				packet.write_string(id)?;
-				self.client.write_packet(&packet)?;
-
-				let mut packet = self
-					.client
-					.read_packet(&[serverbound::configuration::COOKIE_RESPONSE])?;
-				packet.read_string()?;
-				let data = if packet.read_boolean()? {
-					let n = packet.read_usize_varint()?;
-					Some(packet.read_bytes(n)?)
-				} else {
-					None
-				};
-
-				Ok(data)
+				self.client.send(&configuration::CookieRequest {
+					key: id.to_string(),
+				})?;
+				Ok(
+					self
+						.client
+						.receive::<serverbound::configuration::CookieResponse>()?
+						.payload,
+				)
 			}
 			ConnectionState::Play => {
-				let mut packet = Packet::empty(clientbound::play::COOKIE_REQUEST);
-				packet.write_string(id)?;
-				self.client.write_packet(&packet)?;
-
-				let mut packet = self
-					.client
-					.read_packet(&[serverbound::play::COOKIE_RESPONSE])?;
-				packet.read_string()?;
-				let data = if packet.read_boolean()? {
-					let n = packet.read_usize_varint()?;
-					Some(packet.read_bytes(n)?)
-				} else {
-					None
-				};
-
-				Ok(data)
+				self.client.send(&play::CookieRequest {
+					key: id.to_string(),
+				})?;
+				Ok(
+					self
+						.client
+						.receive::<serverbound::play::CookieResponse>()?
+						.payload,
+				)
 			}
 			_ => Err(ServerError::UnexpectedState),
 		}
@@ -200,39 +160,33 @@ impl ProtocolHelper {
 	) -> Result<(i32, Option<Vec<u8>>), ServerError> {
 		match self.state {
 			ConnectionState::Login => {
-				let mut packet = Packet::empty(clientbound::login::PLUGIN_REQUEST);
-				packet.write_varint(id)?;
-				packet.write_string(channel)?;
-				packet.write_bytes(data)?;
-				self.client.write_packet(&packet)?;
+				self.client.send(&login::PluginRequest {
+					message_id: VarInt(id),
+					channel: channel.to_string(),
+					data: RemainingBytes(data.to_vec()),
+				})?;
 
-				let mut packet = self
+				let response = self
 					.client
-					.read_packet(&[serverbound::login::PLUGIN_RESPONSE])?;
-				let identifier = packet.read_varint()?;
-				let data = if packet.read_boolean()? {
-					let mut data = Vec::new();
-					packet.get_mut().read_to_end(&mut data).unwrap();
-					Some(data)
-				} else {
-					None
-				};
+					.receive::<serverbound::login::PluginResponse>()?;
 
-				Ok((identifier, data))
+				Ok((response.message_id.0, response.data.map(|o| o.0)))
 			}
 			_ => Err(ServerError::UnexpectedState),
 		}
 	}
 
 	pub fn send_plugin_message(&self, channel: &str, data: &[u8]) -> Result<(), ServerError> {
-		let mut packet = match self.state {
-			ConnectionState::Configuration => Packet::empty(clientbound::configuration::PLUGIN_MESSAGE),
-			ConnectionState::Play => Packet::empty(clientbound::play::PLUGIN_MESSAGE),
-			_ => return Err(ServerError::UnexpectedState),
-		};
-		packet.write_string(channel)?;
-		packet.write_bytes(data)?;
-		self.client.write_packet(&packet)?;
-		Ok(())
+		match self.state {
+			ConnectionState::Configuration => self.client.send(&configuration::PluginMessage {
+				channel: channel.to_string(),
+				data: RemainingBytes(data.to_vec()),
+			}),
+			ConnectionState::Play => self.client.send(&play::PluginMessage {
+				channel: channel.to_string(),
+				data: RemainingBytes(data.to_vec()),
+			}),
+			_ => Err(ServerError::UnexpectedState),
+		}
 	}
 }
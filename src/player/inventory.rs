@@ -0,0 +1,978 @@
+use std::{
+	mem,
+	ops::Range,
+	sync::{Arc, Mutex},
+};
+
+use crate::{
+	data::{
+		component::TextComponent,
+		slot::{Slot, components::DataComponent},
+	},
+	world::item::ItemRegistry,
+};
+
+// Инвентарь игрока и контейнеры
+//
+// Сервер сам применяет каждый клик и сверяет результат с тем, что предсказал клиент
+// Окно это то, что видит клиент: у инвентаря игрока (окно 0) все 46 слотов,
+// у открытого контейнера сначала слоты контейнера, потом 27 слотов основного инвентаря и 9 хотбара
+// Номера слотов везде как в окне, а не в самом контейнере
+// В слоты брони кладется только то, что туда надевается, и по одному предмету
+
+/// Слоты в окне инвентаря игрока
+pub mod slots {
+	use std::ops::Range;
+
+	pub const CRAFTING_RESULT: usize = 0;
+	pub const CRAFTING: Range<usize> = 1..5;
+	pub const ARMOR: Range<usize> = 5..9; // голова, грудь, ноги, ботинки
+	pub const HEAD: usize = 5;
+	pub const CHEST: usize = 6;
+	pub const LEGS: usize = 7;
+	pub const FEET: usize = 8;
+	pub const MAIN: Range<usize> = 9..36;
+	pub const HOTBAR: Range<usize> = 36..45;
+	pub const OFFHAND: usize = 45;
+	pub const COUNT: usize = 46;
+}
+
+/// Слот за пределами окна, клик туда выбрасывает предмет из курсора
+pub const OUTSIDE_SLOT: i16 = -999;
+
+/// Айди окна инвентаря игрока, он открыт всегда
+pub const PLAYER_WINDOW: u8 = 0;
+
+/// Тип меню из реестра minecraft:menu, сразу с кол-вом своих слотов
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ContainerKind {
+	Generic9x1,
+	Generic9x2,
+	Generic9x3,
+	Generic9x4,
+	Generic9x5,
+	Generic9x6,
+	Generic3x3,
+	Hopper,
+	ShulkerBox,
+}
+
+impl ContainerKind {
+	/// Айди для Open Screen
+	pub fn id(self) -> i32 {
+		match self {
+			ContainerKind::Generic9x1 => 0,
+			ContainerKind::Generic9x2 => 1,
+			ContainerKind::Generic9x3 => 2,
+			ContainerKind::Generic9x4 => 3,
+			ContainerKind::Generic9x5 => 4,
+			ContainerKind::Generic9x6 => 5,
+			ContainerKind::Generic3x3 => 6,
+			ContainerKind::Hopper => 16,
+			ContainerKind::ShulkerBox => 20,
+		}
+	}
+
+	pub fn size(self) -> usize {
+		match self {
+			ContainerKind::Generic9x1 => 9,
+			ContainerKind::Generic9x2 => 18,
+			ContainerKind::Generic9x3 => 27,
+			ContainerKind::Generic9x4 => 36,
+			ContainerKind::Generic9x5 => 45,
+			ContainerKind::Generic9x6 => 54,
+			ContainerKind::Generic3x3 => 9,
+			ContainerKind::Hopper => 5,
+			ContainerKind::ShulkerBox => 27,
+		}
+	}
+
+	/// Сундук на rows рядов (от 1 до 6)
+	pub fn chest(rows: usize) -> ContainerKind {
+		match rows {
+			0 | 1 => ContainerKind::Generic9x1,
+			2 => ContainerKind::Generic9x2,
+			3 => ContainerKind::Generic9x3,
+			4 => ContainerKind::Generic9x4,
+			5 => ContainerKind::Generic9x5,
+			_ => ContainerKind::Generic9x6,
+		}
+	}
+}
+
+/// Контейнер со слотами, например сундук или меню плагина
+///
+/// Один контейнер могут смотреть несколько игроков сразу, поэтому он живет в Arc<Mutex<_>>
+#[derive(Debug, Clone)]
+pub struct Container {
+	pub kind: ContainerKind,
+	pub title: TextComponent,
+	slots: Vec<Slot>,
+}
+
+impl Container {
+	pub fn new(kind: ContainerKind, title: TextComponent) -> Container {
+		Container {
+			kind,
+			title,
+			slots: vec![Slot::empty(); kind.size()],
+		}
+	}
+
+	pub fn chest(rows: usize, title: TextComponent) -> Container {
+		Container::new(ContainerKind::chest(rows), title)
+	}
+
+	/// Сразу оборачивает в Arc<Mutex<_>> для открытия игрокам
+	pub fn shared(self) -> Arc<Mutex<Container>> {
+		Arc::new(Mutex::new(self))
+	}
+
+	pub fn len(&self) -> usize {
+		self.slots.len()
+	}
+
+	pub fn is_empty(&self) -> bool {
+		self.slots.is_empty()
+	}
+
+	pub fn slot(&self, index: usize) -> Option<&Slot> {
+		self.slots.get(index)
+	}
+
+	pub fn set_slot(&mut self, index: usize, item: Slot) {
+		if let Some(o) = self.slots.get_mut(index) {
+			*o = item;
+		}
+	}
+
+	pub fn slots(&self) -> &[Slot] {
+		&self.slots
+	}
+}
+
+/// Открытый у игрока контейнер
+#[derive(Clone)]
+pub struct Window {
+	pub id: u8,
+	pub container: Arc<Mutex<Container>>,
+}
+
+/// Режим клика из Click Container
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ClickMode {
+	Pickup,     // обычный клик левой или правой
+	QuickMove,  // шифт-клик
+	Swap,       // цифра хотбара или F
+	Clone,      // средняя кнопка в креативе
+	Throw,      // Q над слотом
+	QuickCraft, // перетаскивание с зажатой кнопкой
+	PickupAll,  // двойной клик
+}
+
+impl ClickMode {
+	pub fn from_id(id: i32) -> Option<ClickMode> {
+		Some(match id {
+			0 => ClickMode::Pickup,
+			1 => ClickMode::QuickMove,
+			2 => ClickMode::Swap,
+			3 => ClickMode::Clone,
+			4 => ClickMode::Throw,
+			5 => ClickMode::QuickCraft,
+			6 => ClickMode::PickupAll,
+			_ => return None,
+		})
+	}
+}
+
+// Перетаскивание предметов, собирается из нескольких кликов с режимом QuickCraft
+struct Drag {
+	button: i8, // 0 - левая (поровну), 1 - правая (по одному), 2 - средняя (креатив, полные стаки)
+	slots: Vec<usize>,
+}
+
+pub struct PlayerInventory {
+	slots: Vec<Slot>,
+	cursor: Slot,
+	state_id: i32,
+	window: Option<Window>,
+	next_window_id: u8,
+	drag: Option<Drag>,
+}
+
+impl Default for PlayerInventory {
+	fn default() -> Self {
+		Self::new()
+	}
+}
+
+impl PlayerInventory {
+	pub fn new() -> PlayerInventory {
+		PlayerInventory {
+			slots: vec![Slot::empty(); slots::COUNT],
+			cursor: Slot::empty(),
+			state_id: 0,
+			window: None,
+			next_window_id: 1,
+			drag: None,
+		}
+	}
+
+	/// Слот в окне инвентаря игрока
+	pub fn slot(&self, index: usize) -> Option<&Slot> {
+		self.slots.get(index)
+	}
+
+	pub fn set_slot(&mut self, index: usize, item: Slot) {
+		if let Some(o) = self.slots.get_mut(index) {
+			*o = item;
+		}
+	}
+
+	pub fn slots(&self) -> &[Slot] {
+		&self.slots
+	}
+
+	/// Предмет в слоте хотбара (0..9)
+	pub fn hotbar(&self, index: usize) -> &Slot {
+		&self.slots[slots::HOTBAR.start + index.min(8)]
+	}
+
+	pub fn offhand(&self) -> &Slot {
+		&self.slots[slots::OFFHAND]
+	}
+
+	pub fn cursor(&self) -> &Slot {
+		&self.cursor
+	}
+
+	pub fn set_cursor(&mut self, item: Slot) {
+		self.cursor = item;
+	}
+
+	/// Последний state id, который видел клиент
+	pub fn state_id(&self) -> i32 {
+		self.state_id
+	}
+
+	/// Новый state id для пакета синхронизации, клиент шлет его обратно в Click Container
+	pub fn next_state_id(&mut self) -> i32 {
+		self.state_id = (self.state_id + 1) & 0x7FFF;
+		self.state_id
+	}
+
+	pub fn window(&self) -> Option<&Window> {
+		self.window.as_ref()
+	}
+
+	pub fn window_id(&self) -> u8 {
+		self.window.as_ref().map(|o| o.id).unwrap_or(PLAYER_WINDOW)
+	}
+
+	/// Запоминает открытый контейнер и возвращает айди для него (1..=100 как в ванилле)
+	pub fn open_window(&mut self, container: Arc<Mutex<Container>>) -> u8 {
+		let id = self.next_window_id;
+		self.next_window_id = id % 100 + 1;
+		self.window = Some(Window { id, container });
+		self.drag = None;
+		id
+	}
+
+	/// Закрывает окно, предмет с курсора и сетка крафта возвращаются в инвентарь
+	///
+	/// Возвращает то, что не влезло и должно быть выброшено
+	pub fn close_window(&mut self) -> Vec<Slot> {
+		self.window = None;
+		self.drag = None;
+		self.slots[slots::CRAFTING_RESULT] = Slot::empty();
+
+		let mut items = vec![mem::take(&mut self.cursor)];
+		for index in slots::CRAFTING {
+			items.push(mem::take(&mut self.slots[index]));
+		}
+
+		items
+			.into_iter()
+			.map(|o| self.add_item(o))
+			.filter(|o| !o.is_empty())
+			.collect()
+	}
+
+	/// Кладет предмет в инвентарь: сначала досыпает в такие же стаки, потом в пустые слоты
+	///
+	/// Возвращает то, что не влезло
+	pub fn add_item(&mut self, item: Slot) -> Slot {
+		let order: Vec<usize> = slots::HOTBAR.chain(slots::MAIN).collect();
+		move_into(&mut self.slots, item, &order)
+	}
+
+	/// Все слоты окна подряд, как их отправлять в Set Container Content
+	pub fn window_slots(&self, container: Option<&Container>) -> Vec<Slot> {
+		match container {
+			Some(container) => container
+				.slots
+				.iter()
+				.chain(&self.slots[slots::MAIN])
+				.chain(&self.slots[slots::HOTBAR])
+				.cloned()
+				.collect(),
+			None => self.slots.clone(),
+		}
+	}
+
+	/// Применяет клик к окну, container это открытый контейнер (None для инвентаря игрока)
+	///
+	/// По реестру предметов проверяется, что можно надеть в слоты брони
+	/// Возвращает выброшенные предметы
+	pub fn click(
+		&mut self,
+		items: &ItemRegistry,
+		container: Option<&mut Container>,
+		slot: i16,
+		button: i8,
+		mode: ClickMode,
+		creative: bool,
+	) -> Vec<Slot> {
+		if mode != ClickMode::QuickCraft {
+			self.drag = None;
+		}
+
+		let mut view = WindowView {
+			player: &mut self.slots,
+			container,
+			items,
+		};
+		let cursor = &mut self.cursor;
+
+		let index = match slot {
+			OUTSIDE_SLOT => None,
+			slot if slot >= 0 && (slot as usize) < view.len() => Some(slot as usize),
+			_ if mode == ClickMode::QuickCraft => None, // в начале и конце перетаскивания слот -999
+			_ => return Vec::new(),
+		};
+
+		let mut dropped = Vec::new();
+
+		match (mode, index) {
+			(ClickMode::Pickup, None) => match button {
+				0 => dropped.push(mem::take(cursor)),
+				1 => dropped.push(cursor.split(1)),
+				_ => {}
+			},
+			(ClickMode::Pickup, Some(index)) => pickup(&mut view, index, button, cursor),
+			(ClickMode::QuickMove, Some(index)) => quick_move(&mut view, index),
+			(ClickMode::Swap, Some(index)) => {
+				let target = match button {
+					0..=8 => slots::HOTBAR.start + button as usize,
+					40 => slots::OFFHAND,
+					_ => return dropped,
+				};
+				let incoming = &view.player[target];
+				if view.player_index(index) == Some(target)
+					|| (view.is_result(index) && !incoming.is_empty())
+					|| (!incoming.is_empty()
+						&& (!view.accepts(index, incoming) || incoming.count > view.max_count(index, incoming)))
+				{
+					return dropped;
+				}
+
+				let item = mem::take(view.get_mut(index));
+				let other = mem::replace(&mut view.player[target], item);
+				*view.get_mut(index) = other;
+			}
+			(ClickMode::Clone, Some(index)) => {
+				let item = view.get(index);
+				if creative && cursor.is_empty() && !item.is_empty() {
+					*cursor = item.with_count(item.max_stack_size());
+				}
+			}
+			(ClickMode::Throw, Some(index)) if cursor.is_empty() => {
+				let item = view.get_mut(index);
+				let count = if button == 1 { item.count } else { 1 };
+				dropped.push(item.split(count));
+			}
+			(ClickMode::QuickCraft, index) => {
+				self.drag = drag(&mut view, self.drag.take(), index, button, cursor, creative);
+			}
+			(ClickMode::PickupAll, Some(index)) => pickup_all(&mut view, index, cursor),
+			_ => {}
+		}
+
+		dropped.retain(|o| !o.is_empty());
+		dropped
+	}
+}
+
+// Слоты окна поверх инвентаря игрока и открытого контейнера
+struct WindowView<'a> {
+	player: &'a mut [Slot],
+	container: Option<&'a mut Container>,
+	items: &'a ItemRegistry,
+}
+
+impl WindowView<'_> {
+	fn len(&self) -> usize {
+		match &self.container {
+			Some(container) => container.len() + slots::MAIN.len() + slots::HOTBAR.len(),
+			None => slots::COUNT,
+		}
+	}
+
+	// Номер слота в инвентаре игрока для слота окна
+	fn player_index(&self, index: usize) -> Option<usize> {
+		match &self.container {
+			Some(container) if index < container.len() => None,
+			Some(container) => Some(index - container.len() + slots::MAIN.start),
+			None => Some(index),
+		}
+	}
+
+	fn get(&self, index: usize) -> &Slot {
+		match self.player_index(index) {
+			Some(index) => &self.player[index],
+			None => &self.container.as_ref().unwrap().slots[index],
+		}
+	}
+
+	fn get_mut(&mut self, index: usize) -> &mut Slot {
+		match self.player_index(index) {
+			Some(index) => &mut self.player[index],
+			None => &mut self.container.as_mut().unwrap().slots[index],
+		}
+	}
+
+	// Слот результата крафта, из него можно только забирать
+	fn is_result(&self, index: usize) -> bool {
+		self.container.is_none() && index == slots::CRAFTING_RESULT
+	}
+
+	fn is_armor(&self, index: usize) -> bool {
+		self.container.is_none() && slots::ARMOR.contains(&index)
+	}
+
+	// Слот брони, в который надевается предмет
+	fn armor_slot(&self, item: &Slot) -> Option<usize> {
+		let equippable = item.components.iter().find_map(|o| match o {
+			DataComponent::Equippable(o) => Some(o.slot.0),
+			_ => None,
+		});
+
+		match equippable {
+			// Айди из EquipmentSlot: 2 - ботинки, 3 - ноги, 4 - грудь, 5 - голова
+			Some(2) => Some(slots::FEET),
+			Some(3) => Some(slots::LEGS),
+			Some(4) => Some(slots::CHEST),
+			Some(5) => Some(slots::HEAD),
+			Some(_) => None,
+			None => self.items.name(item.item_id).and_then(default_armor_slot),
+		}
+	}
+
+	// Можно ли положить предмет в слот окна
+	fn accepts(&self, index: usize, item: &Slot) -> bool {
+		if !self.is_armor(index) || item.is_empty() {
+			return true;
+		}
+
+		// Про предмет, которого нет в реестре, ничего не знаем и не мешаем клиенту
+		let unknown = self.items.name(item.item_id).is_none()
+			&& !item
+				.components
+				.iter()
+				.any(|o| matches!(o, DataComponent::Equippable(_)));
+
+		unknown || self.armor_slot(item) == Some(index)
+	}
+
+	// Сколько таких предметов влезает в слот окна
+	fn max_count(&self, index: usize, item: &Slot) -> i32 {
+		if self.is_armor(index) {
+			1
+		} else {
+			item.max_stack_size()
+		}
+	}
+
+	// Куда шифт-клик переносит предмет из слота, по порядку
+	fn quick_move_targets(&self, index: usize, item: &Slot) -> Vec<usize> {
+		let reversed = |range: Range<usize>| range.rev().collect::<Vec<_>>();
+
+		let mut targets = match &self.container {
+			Some(container) if index < container.len() => reversed(container.len()..self.len()),
+			Some(container) => (0..container.len()).collect(),
+			None => match index {
+				slots::CRAFTING_RESULT => reversed(slots::MAIN.start..slots::HOTBAR.end),
+				i if slots::MAIN.contains(&i) => slots::HOTBAR.collect(),
+				i if slots::HOTBAR.contains(&i) => slots::MAIN.collect(),
+				_ => (slots::MAIN.start..slots::HOTBAR.end).collect(),
+			},
+		};
+
+		// Броню из инвентаря шифт-клик сначала пробует надеть
+		if (slots::MAIN.start..slots::HOTBAR.end).contains(&index)
+			&& self.container.is_none()
+			&& let Some(armor) = self.armor_slot(item)
+		{
+			targets.insert(0, armor);
+		}
+
+		targets
+	}
+}
+
+// Слот брони для ванильного предмета без компонента equippable в стаке, дефолтных компонентов сервер не знает
+fn default_armor_slot(name: &str) -> Option<usize> {
+	let name = name.strip_prefix("minecraft:").unwrap_or(name);
+
+	if name.ends_with("_helmet")
+		|| name.ends_with("_head")
+		|| name.ends_with("_skull")
+		|| name == "carved_pumpkin"
+	{
+		Some(slots::HEAD)
+	} else if name.ends_with("_chestplate") || name == "elytra" {
+		Some(slots::CHEST)
+	} else if name.ends_with("_leggings") {
+		Some(slots::LEGS)
+	} else if name.ends_with("_boots") {
+		Some(slots::FEET)
+	} else {
+		None
+	}
+}
+
+// Досыпает item в такие же стаки из order, остаток кладет в первые пустые слоты
+fn move_into(slots: &mut [Slot], mut item: Slot, order: &[usize]) -> Slot {
+	for &index in order {
+		if item.is_empty() {
+			break;
+		}
+		let slot = &mut slots[index];
+		if !slot.is_empty() && slot.is_same_item(&item) {
+			let count = (slot.max_stack_size() - slot.count).min(item.count).max(0);
+			slot.count += count;
+			item.split(count);
+		}
+	}
+
+	for &index in order {
+		if item.is_empty() {
+			break;
+		}
+		if slots[index].is_empty() {
+			let count = item.max_stack_size().min(item.count);
+			slots[index] = item.split(count);
+		}
+	}
+
+	item
+}
+
+fn pickup(view: &mut WindowView, index: usize, button: i8, cursor: &mut Slot) {
+	let result = view.is_result(index);
+	if !view.accepts(index, cursor) {
+		return;
+	}
+
+	let limit = view.max_count(index, cursor);
+	let slot = view.get_mut(index);
+
+	// Из слота результата только забираем, и только если влезет в курсор целиком
+	if result {
+		if !slot.is_empty()
+			&& (cursor.is_empty()
+				|| (cursor.is_same_item(slot) && cursor.count + slot.count <= cursor.max_stack_size()))
+		{
+			let count = cursor.count;
+			*cursor = slot.split(slot.count);
+			cursor.count += count;
+		}
+		return;
+	}
+
+	match button {
+		0 => {
+			if !cursor.is_empty() && !slot.is_empty() && cursor.is_same_item(slot) {
+				let count = (limit - slot.count).min(cursor.count).max(0);
+				slot.count += count;
+				cursor.split(count);
+			} else if !cursor.is_empty() && slot.is_empty() {
+				*slot = cursor.split(cursor.count.min(limit));
+			} else if cursor.count <= limit || cursor.is_empty() {
+				mem::swap(cursor, slot);
+			}
+		}
+		1 => {
+			if cursor.is_empty() {
+				let half = (slot.count + 1) / 2;
+				*cursor = slot.split(half);
+			} else if slot.is_empty() {
+				*slot = cursor.split(1);
+			} else if cursor.is_same_item(slot) {
+				if slot.count < limit {
+					slot.count += 1;
+					cursor.split(1);
+				}
+			} else if cursor.count <= limit {
+				mem::swap(cursor, slot);
+			}
+		}
+		_ => {}
+	}
+}
+
+fn quick_move(view: &mut WindowView, index: usize) {
+	let item = mem::take(view.get_mut(index));
+	if item.is_empty() {
+		return;
+	}
+
+	let targets = view.quick_move_targets(index, &item);
+	let mut item = item;
+
+	// Сначала такие же стаки, потом пустые слоты, как в move_into, но через окно
+	for pass in 0..2 {
+		for &target in &targets {
+			if item.is_empty() {
+				break;
+			}
+			if !view.accepts(target, &item) {
+				continue;
+			}
+			let limit = view.max_count(target, &item);
+			let slot = view.get_mut(target);
+			if pass == 0 && !slot.is_empty() && slot.is_same_item(&item) {
+				let count = (limit - slot.count).min(item.count).max(0);
+				slot.count += count;
+				item.split(count);
+			} else if pass == 1 && slot.is_empty() {
+				let count = limit.min(item.count);
+				*slot = item.split(count);
+			}
+		}
+	}
+
+	*view.get_mut(index) = item;
+}
+
+fn drag(
+	view: &mut WindowView,
+	state: Option<Drag>,
+	index: Option<usize>,
+	button: i8,
+	cursor: &mut Slot,
+	creative: bool,
+) -> Option<Drag> {
+	// button: стадия в младших двух битах (0 - начало, 1 - слот, 2 - конец), кнопка в старших
+	let (stage, kind) = (button & 3, button >> 2);
+
+	match (stage, state) {
+		(0, None) => {
+			if cursor.is_empty() || (kind == 2 && !creative) || kind > 2 {
+				return None;
+			}
+			Some(Drag {
+				button: kind,
+				slots: Vec::new(),
+			})
+		}
+		(1, Some(mut drag)) if drag.button == kind => {
+			if let Some(index) = index {
+				let slot = view.get(index);
+				let fits = slot.is_empty()
+					|| (slot.is_same_item(cursor) && slot.count < view.max_count(index, cursor));
+				if fits
+					&& view.accepts(index, cursor)
+					&& !view.is_result(index)
+					&& !drag.slots.contains(&index)
+				{
+					drag.slots.push(index);
+				}
+			}
+			Some(drag)
+		}
+		(2, Some(drag)) if drag.button == kind => {
+			if drag.slots.is_empty() || cursor.is_empty() {
+				return None;
+			}
+
+			let per_slot = match drag.button {
+				0 => (cursor.count / drag.slots.len() as i32).max(1),
+				1 => 1,
+				_ => cursor.max_stack_size(),
+			};
+
+			for index in drag.slots {
+				if cursor.is_empty() {
+					break;
+				}
+				let limit = view.max_count(index, cursor);
+				let slot = view.get_mut(index);
+				let had = if slot.is_empty() { 0 } else { slot.count };
+				let count = per_slot.min(limit - had).max(0);
+
+				let item = match drag.button {
+					2 => cursor.with_count(count),
+					_ => cursor.split(count),
+				};
+				if item.is_empty() {
+					continue;
+				}
+				*slot = item.with_count(had + item.count);
+			}
+			None
+		}
+		_ => None, // стадии перепутались, начинаем заново
+	}
+}
+
+fn pickup_all(view: &mut WindowView, index: usize, cursor: &mut Slot) {
+	if cursor.is_empty() || !view.get(index).is_empty() {
+		return;
+	}
+
+	// Сначала неполные стаки, потом полные, как в ванилле
+	for pass in 0..2 {
+		for i in 0..view.len() {
+			if cursor.count >= cursor.max_stack_size() {
+				return;
+			}
+			if view.is_result(i) {
+				continue;
+			}
+			let slot = view.get_mut(i);
+			if slot.is_empty() || !slot.is_same_item(cursor) {
+				continue;
+			}
+			if pass == 0 && slot.count >= slot.max_stack_size() {
+				continue;
+			}
+			let count = (cursor.max_stack_size() - cursor.count).min(slot.count);
+			slot.split(count);
+			cursor.count += count;
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::{
+		data::slot::components::{Equippable, Holder},
+		protocol::packets::VarInt,
+	};
+
+	const STONE: i32 = 1;
+	const HELMET: i32 = 900;
+	const PUMPKIN: i32 = 901;
+	const UNKNOWN: i32 = 902;
+
+	fn items() -> ItemRegistry {
+		let mut items = ItemRegistry::new();
+		items.register("minecraft:diamond_helmet", HELMET);
+		items.register("minecraft:carved_pumpkin", PUMPKIN);
+		items
+	}
+
+	fn equippable(slot: i32) -> DataComponent {
+		DataComponent::Equippable(Equippable {
+			slot: VarInt(slot),
+			equip_sound: Holder::Reference(0),
+			asset_id: None,
+			camera_overlay: None,
+			allowed_entities: None,
+			dispensable: true,
+			swappable: true,
+			damage_on_hurt: true,
+			equip_on_interact: false,
+		})
+	}
+
+	// Клик левой кнопкой с предметом на курсоре, возвращает что осталось на курсоре
+	fn place(inventory: &mut PlayerInventory, index: usize, item: Slot) -> Slot {
+		inventory.set_cursor(item);
+		inventory.click(&items(), None, index as i16, 0, ClickMode::Pickup, false);
+		inventory.cursor().clone()
+	}
+
+	#[test]
+	fn close_window_returns_crafting_grid() {
+		let mut inventory = PlayerInventory::new();
+		inventory.set_cursor(Slot::new(STONE, 3));
+		inventory.set_slot(slots::CRAFTING.start, Slot::new(STONE, 2));
+		inventory.set_slot(slots::CRAFTING.end - 1, Slot::new(HELMET, 1));
+		inventory.set_slot(slots::CRAFTING_RESULT, Slot::new(PUMPKIN, 1));
+
+		assert!(inventory.close_window().is_empty());
+		assert!(inventory.cursor().is_empty());
+		assert!(
+			inventory.slots()[..slots::ARMOR.start]
+				.iter()
+				.all(|o| o.is_empty())
+		);
+		assert_eq!(inventory.hotbar(0), &Slot::new(STONE, 5));
+		assert_eq!(inventory.hotbar(1), &Slot::new(HELMET, 1));
+	}
+
+	#[test]
+	fn close_window_returns_leftovers() {
+		let mut inventory = PlayerInventory::new();
+		for index in slots::MAIN.chain(slots::HOTBAR) {
+			inventory.set_slot(index, Slot::new(PUMPKIN, 64));
+		}
+		inventory.set_cursor(Slot::new(STONE, 3));
+		inventory.set_slot(slots::CRAFTING.start, Slot::new(HELMET, 1));
+
+		assert_eq!(
+			inventory.close_window(),
+			vec![Slot::new(STONE, 3), Slot::new(HELMET, 1)]
+		);
+		assert!(inventory.slot(slots::CRAFTING.start).unwrap().is_empty());
+	}
+
+	#[test]
+	fn clicks_outside_drop_cursor() {
+		let mut inventory = PlayerInventory::new();
+		inventory.set_cursor(Slot::new(STONE, 3));
+
+		let dropped = inventory.click(&items(), None, OUTSIDE_SLOT, 1, ClickMode::Pickup, false);
+		assert_eq!(dropped, vec![Slot::new(STONE, 1)]);
+
+		let dropped = inventory.click(&items(), None, OUTSIDE_SLOT, 0, ClickMode::Pickup, false);
+		assert_eq!(dropped, vec![Slot::new(STONE, 2)]);
+		assert!(inventory.cursor().is_empty());
+	}
+
+	#[test]
+	fn armor_slots_accept_only_equippable_items() {
+		let mut inventory = PlayerInventory::new();
+
+		assert_eq!(
+			place(&mut inventory, slots::HEAD, Slot::new(STONE, 1)),
+			Slot::new(STONE, 1)
+		);
+		assert_eq!(
+			place(&mut inventory, slots::CHEST, Slot::new(HELMET, 1)),
+			Slot::new(HELMET, 1)
+		);
+		assert!(inventory.slots()[slots::ARMOR].iter().all(|o| o.is_empty()));
+
+		assert!(place(&mut inventory, slots::HEAD, Slot::new(HELMET, 1)).is_empty());
+		assert_eq!(inventory.slot(slots::HEAD), Some(&Slot::new(HELMET, 1)));
+
+		// Компонент в стаке важнее имени предмета
+		let boots = Slot::new(STONE, 1).with_component(equippable(2));
+		assert!(place(&mut inventory, slots::FEET, boots.clone()).is_empty());
+		assert_eq!(inventory.slot(slots::FEET), Some(&boots));
+
+		let mainhand = Slot::new(HELMET, 1).with_component(equippable(0));
+		assert_eq!(
+			place(&mut inventory, slots::LEGS, mainhand.clone()),
+			mainhand
+		);
+
+		// Неизвестный предмет проверить нельзя
+		assert!(place(&mut inventory, slots::LEGS, Slot::new(UNKNOWN, 1)).is_empty());
+	}
+
+	#[test]
+	fn armor_slots_hold_one_item() {
+		let mut inventory = PlayerInventory::new();
+
+		assert_eq!(
+			place(&mut inventory, slots::HEAD, Slot::new(PUMPKIN, 5)),
+			Slot::new(PUMPKIN, 4)
+		);
+		assert_eq!(inventory.slot(slots::HEAD), Some(&Slot::new(PUMPKIN, 1)));
+
+		// Стак не меняется местами с надетым предметом
+		assert_eq!(
+			place(&mut inventory, slots::HEAD, Slot::new(PUMPKIN, 4)),
+			Slot::new(PUMPKIN, 4)
+		);
+		assert_eq!(
+			place(&mut inventory, slots::HEAD, Slot::new(HELMET, 1)),
+			Slot::new(PUMPKIN, 1)
+		);
+		assert_eq!(inventory.slot(slots::HEAD), Some(&Slot::new(HELMET, 1)));
+	}
+
+	#[test]
+	fn swap_checks_armor_slots() {
+		let mut inventory = PlayerInventory::new();
+		inventory.set_slot(slots::HOTBAR.start, Slot::new(STONE, 1));
+		inventory.set_slot(slots::HOTBAR.start + 1, Slot::new(HELMET, 1));
+
+		inventory.click(
+			&items(),
+			None,
+			slots::HEAD as i16,
+			0,
+			ClickMode::Swap,
+			false,
+		);
+		assert!(inventory.slot(slots::HEAD).unwrap().is_empty());
+
+		inventory.click(
+			&items(),
+			None,
+			slots::HEAD as i16,
+			1,
+			ClickMode::Swap,
+			false,
+		);
+		assert_eq!(inventory.slot(slots::HEAD), Some(&Slot::new(HELMET, 1)));
+		assert!(inventory.hotbar(1).is_empty());
+	}
+
+	#[test]
+	fn quick_move_equips_armor() {
+		let mut inventory = PlayerInventory::new();
+		inventory.set_slot(slots::MAIN.start, Slot::new(HELMET, 1));
+		inventory.set_slot(slots::MAIN.start + 1, Slot::new(PUMPKIN, 3));
+
+		let index = slots::MAIN.start as i16;
+		inventory.click(&items(), None, index, 0, ClickMode::QuickMove, false);
+		assert_eq!(inventory.slot(slots::HEAD), Some(&Slot::new(HELMET, 1)));
+
+		// Голова занята, тыква уходит в хотбар как обычно
+		inventory.click(&items(), None, index + 1, 0, ClickMode::QuickMove, false);
+		assert_eq!(inventory.hotbar(0), &Slot::new(PUMPKIN, 3));
+
+		// Обратно броня снимается в инвентарь
+		inventory.click(
+			&items(),
+			None,
+			slots::HEAD as i16,
+			0,
+			ClickMode::QuickMove,
+			false,
+		);
+		assert!(inventory.slot(slots::HEAD).unwrap().is_empty());
+		assert_eq!(
+			inventory.slot(slots::MAIN.start),
+			Some(&Slot::new(HELMET, 1))
+		);
+	}
+
+	#[test]
+	fn drag_skips_armor_slots() {
+		let mut inventory = PlayerInventory::new();
+		inventory.set_cursor(Slot::new(STONE, 4));
+
+		let items = items();
+		let mut click = |slot: i16, button: i8| {
+			inventory.click(&items, None, slot, button, ClickMode::QuickCraft, false);
+		};
+		click(OUTSIDE_SLOT, 0);
+		click(slots::HEAD as i16, 1);
+		click(slots::MAIN.start as i16, 1);
+		click(OUTSIDE_SLOT, 2);
+
+		assert!(inventory.slot(slots::HEAD).unwrap().is_empty());
+		assert_eq!(
+			inventory.slot(slots::MAIN.start),
+			Some(&Slot::new(STONE, 4))
+		);
+	}
+}
@@ -1,2 +1,5 @@
 pub mod context;
+pub mod data;
 pub mod helper;
+pub mod inventory;
+pub mod registry;
@@ -0,0 +1,338 @@
+use std::{
+	sync::{
+		Arc, Condvar, Mutex,
+		atomic::{AtomicU64, Ordering},
+	},
+	time::{Duration, Instant},
+};
+
+use dashmap::DashMap;
+use uuid::Uuid;
+
+use super::context::ClientContext;
+
+// Все подключения сервера
+//
+// Ключ - айди подключения, а не адрес: за прокси и NAT адреса совпадают,
+// а с PROXY protocol или форвардингом адрес сокета вообще не адрес игрока
+// Игроков можно искать по uuid, имени и айди сущности, индексы заполняются по ходу логина
+
+/// Уникальный на все время работы сервера айди подключения
+pub type ClientId = u64;
+
+pub struct ClientRegistry {
+	clients: DashMap<ClientId, Arc<ClientContext>>,
+	by_uuid: DashMap<Uuid, ClientId>,
+	by_name: DashMap<String, ClientId>, // имя в нижнем регистре
+	by_entity: DashMap<i32, ClientId>,
+	next_id: AtomicU64,
+	removed: (Mutex<()>, Condvar), // будит тех, кто ждет отключения клиента
+}
+
+impl Default for ClientRegistry {
+	fn default() -> Self {
+		Self::new()
+	}
+}
+
+impl ClientRegistry {
+	pub fn new() -> ClientRegistry {
+		ClientRegistry {
+			clients: DashMap::new(),
+			by_uuid: DashMap::new(),
+			by_name: DashMap::new(),
+			by_entity: DashMap::new(),
+			next_id: AtomicU64::new(1),
+			removed: (Mutex::new(()), Condvar::new()),
+		}
+	}
+
+	/// Свободный айди для нового подключения
+	pub fn next_id(&self) -> ClientId {
+		self.next_id.fetch_add(1, Ordering::SeqCst)
+	}
+
+	pub fn add(&self, client: Arc<ClientContext>) {
+		self.clients.insert(client.id, client);
+	}
+
+	/// Убирает клиента и его записи в индексах
+	///
+	/// Индекс чистится только если указывает на этого клиента,
+	/// после повторного входа там уже новая сессия
+	pub fn remove(&self, client: &Arc<ClientContext>) -> Option<Arc<ClientContext>> {
+		let removed = self.clients.remove(&client.id).map(|(_, o)| o);
+
+		if let Some(info) = client.player_info() {
+			self.by_uuid.remove_if(&info.uuid, |_, id| *id == client.id);
+			self
+				.by_name
+				.remove_if(&info.name.to_lowercase(), |_, id| *id == client.id);
+		}
+		if let Some(entity) = client.entity_info() {
+			self
+				.by_entity
+				.remove_if(&entity.entity_id, |_, id| *id == client.id);
+		}
+
+		let _lock = self.removed.0.lock().unwrap();
+		self.removed.1.notify_all();
+
+		removed
+	}
+
+	/// Заносит игрока в индексы по uuid и имени, player_info должен быть уже выставлен
+	///
+	/// Возвращает прошлую сессию с тем же uuid, если она есть
+	pub fn register_player(&self, client: &Arc<ClientContext>) -> Option<Arc<ClientContext>> {
+		let info = client.player_info()?;
+
+		let old = self
+			.by_uuid
+			.insert(info.uuid, client.id)
+			.filter(|&id| id != client.id)
+			.and_then(|id| self.get(id));
+		self.by_name.insert(info.name.to_lowercase(), client.id);
+
+		old
+	}
+
+	/// Заносит сущность игрока в индекс, зовется после set_entity_info
+	pub fn register_entity(&self, client: &Arc<ClientContext>, entity_id: i32) {
+		self.by_entity.insert(entity_id, client.id);
+	}
+
+	pub fn get(&self, id: ClientId) -> Option<Arc<ClientContext>> {
+		self.clients.get(&id).map(|o| o.clone())
+	}
+
+	pub fn contains(&self, id: ClientId) -> bool {
+		self.clients.contains_key(&id)
+	}
+
+	pub fn by_uuid(&self, uuid: Uuid) -> Option<Arc<ClientContext>> {
+		self.by_uuid.get(&uuid).and_then(|o| self.get(*o))
+	}
+
+	/// Поиск по имени без учета регистра
+	pub fn by_name(&self, name: &str) -> Option<Arc<ClientContext>> {
+		self
+			.by_name
+			.get(&name.to_lowercase())
+			.and_then(|o| self.get(*o))
+	}
+
+	pub fn by_entity(&self, entity_id: i32) -> Option<Arc<ClientContext>> {
+		self.by_entity.get(&entity_id).and_then(|o| self.get(*o))
+	}
+
+	/// Все подключения, включая тех, кто только смотрит статус
+	pub fn all(&self) -> Vec<Arc<ClientContext>> {
+		self.clients.iter().map(|o| o.clone()).collect()
+	}
+
+	/// Вызывает f для каждого подключения, мапа при этом не блокируется
+	pub fn for_each(&self, mut f: impl FnMut(&Arc<ClientContext>)) {
+		for client in self.all() {
+			f(&client);
+		}
+	}
+
+	/// Ждет, пока клиент отключится, false если не дождались за timeout
+	pub fn wait_removed(&self, id: ClientId, timeout: Duration) -> bool {
+		self.wait_until(|| !self.contains(id), timeout)
+	}
+
+	/// Ждет, пока отключатся все клиенты, false если не дождались за timeout
+	pub fn wait_empty(&self, timeout: Duration) -> bool {
+		self.wait_until(|| self.is_empty(), timeout)
+	}
+
+	fn wait_until(&self, done: impl Fn() -> bool, timeout: Duration) -> bool {
+		let deadline = Instant::now() + timeout;
+		let mut lock = self.removed.0.lock().unwrap();
+
+		while !done() {
+			let Some(left) = deadline.checked_duration_since(Instant::now()) else {
+				return false;
+			};
+			lock = self.removed.1.wait_timeout(lock, left).unwrap().0;
+		}
+
+		true
+	}
+
+	pub fn len(&self) -> usize {
+		self.clients.len()
+	}
+
+	pub fn is_empty(&self) -> bool {
+		self.clients.is_empty()
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use std::thread;
+
+	use super::*;
+	use crate::{
+		config::Config,
+		context::ServerContext,
+		data::component::TextComponent,
+		network::connection::Connection,
+		play::get_offline_uuid,
+		player::context::{PlayerInfo, test_player},
+		protocol::packet_id::clientbound::play::DISCONNECT,
+	};
+
+	fn server() -> Arc<ServerContext> {
+		Arc::new(ServerContext::new(Arc::new(Config::default())))
+	}
+
+	// Новое подключение, еще без логина
+	fn connect(server: &Arc<ServerContext>) -> Arc<ClientContext> {
+		let client = Arc::new(ClientContext::new(
+			server.clone(),
+			Arc::new(Connection::detached()),
+		));
+		server.clients.add(client.clone());
+		client
+	}
+
+	fn login(client: &Arc<ClientContext>, name: &str) -> Option<Arc<ClientContext>> {
+		client.set_player_info(PlayerInfo {
+			name: name.to_string(),
+			uuid: get_offline_uuid(name),
+			properties: Vec::new(),
+		});
+		client.server.clients.register_player(client)
+	}
+
+	#[test]
+	fn keyed_by_connection_id() {
+		let server = server();
+		let first = connect(&server);
+		let second = connect(&server);
+
+		// Адрес один и тот же, а подключения разные
+		assert_eq!(first.addr(), second.addr());
+		assert_ne!(first.id, second.id);
+		assert_eq!(server.clients.len(), 2);
+		assert!(Arc::ptr_eq(&server.clients.get(first.id).unwrap(), &first));
+		assert!(Arc::ptr_eq(
+			&server.clients.get(second.id).unwrap(),
+			&second
+		));
+
+		server.clients.remove(&first);
+		assert!(!server.clients.contains(first.id));
+		assert!(server.clients.contains(second.id));
+		assert_eq!(server.clients.all().len(), 1);
+	}
+
+	#[test]
+	fn indexes() {
+		let server = server();
+		let steve = test_player(&server, "Steve", 0);
+		let entity_id = steve.entity_info().unwrap().entity_id;
+		let uuid = steve.player_info().unwrap().uuid;
+
+		for found in [
+			server.clients.by_uuid(uuid),
+			server.clients.by_name("Steve"),
+			server.clients.by_name("sTEVE"),
+			server.clients.by_entity(entity_id),
+			server.get_player_by_name("steve"),
+			server.get_player_by_entity(entity_id),
+		] {
+			assert!(Arc::ptr_eq(&found.unwrap(), &steve));
+		}
+		assert!(server.clients.by_name("Alex").is_none());
+
+		// Пока нет сущности, в игре игрока нет, но в реестре он уже есть
+		let alex = connect(&server);
+		assert!(login(&alex, "Alex").is_none());
+		assert!(server.clients.by_name("alex").is_some());
+		assert!(server.get_player_by_name("alex").is_none());
+		assert_eq!(server.player_count(), 1);
+
+		server.clients.remove(&steve);
+		assert!(server.clients.by_uuid(uuid).is_none());
+		assert!(server.clients.by_name("Steve").is_none());
+		assert!(server.clients.by_entity(entity_id).is_none());
+	}
+
+	#[test]
+	fn duplicate_login_returns_old_session() {
+		let server = server();
+		let old = test_player(&server, "Steve", 0);
+		let entity_id = old.entity_info().unwrap().entity_id;
+
+		let new = connect(&server);
+		let found = login(&new, "Steve").unwrap();
+		assert!(Arc::ptr_eq(&found, &old));
+		assert!(login(&new, "Steve").is_none()); // сам себе не дубликат
+
+		// Индексы уже у новой сессии, удаление старой их не трогает
+		server.clients.remove(&old);
+		let uuid = new.player_info().unwrap().uuid;
+		assert!(Arc::ptr_eq(&server.clients.by_uuid(uuid).unwrap(), &new));
+		assert!(Arc::ptr_eq(&server.clients.by_name("Steve").unwrap(), &new));
+		assert!(server.clients.by_entity(entity_id).is_none());
+	}
+
+	#[test]
+	fn old_session_is_kicked() {
+		let server = server();
+		let old = test_player(&server, "Steve", 0);
+		let new = connect(&server);
+		assert!(Arc::ptr_eq(&login(&new, "Steve").unwrap(), &old));
+
+		old
+			.kick(TextComponent::translatable(
+				"multiplayer.disconnect.duplicate_login",
+				Vec::new(),
+			))
+			.unwrap();
+
+		let sent = old.take_sent();
+		assert_eq!(sent.len(), 1);
+		assert_eq!(sent[0].id(), DISCONNECT);
+		assert!(!old.is_alive());
+		assert!(new.is_alive());
+	}
+
+	#[test]
+	fn wait_removed() {
+		let server = server();
+		let client = connect(&server);
+		let other = connect(&server);
+
+		assert!(server.clients.wait_removed(12345, Duration::ZERO));
+		assert!(
+			!server
+				.clients
+				.wait_removed(client.id, Duration::from_millis(20))
+		);
+
+		let remover = {
+			let server = server.clone();
+			let client = client.clone();
+			thread::spawn(move || {
+				thread::sleep(Duration::from_millis(20));
+				server.clients.remove(&client);
+			})
+		};
+		assert!(
+			server
+				.clients
+				.wait_removed(client.id, Duration::from_secs(5))
+		);
+		remover.join().unwrap();
+
+		assert!(!server.clients.wait_empty(Duration::from_millis(20)));
+		server.clients.remove(&other);
+		assert!(server.clients.wait_empty(Duration::ZERO));
+	}
+}
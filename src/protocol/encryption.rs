@@ -0,0 +1,141 @@
+use aes::{
+	Aes128,
+	cipher::{BlockDecryptMut, BlockEncryptMut, KeyIvInit, generic_array::GenericArray},
+};
+use num_bigint::BigInt;
+use rsa::{Pkcs1v15Encrypt, RsaPrivateKey, pkcs8::EncodePublicKey};
+use sha1::{Digest, Sha1};
+
+use crate::ServerError;
+
+type Aes128Cfb8Enc = cfb8::Encryptor<Aes128>;
+type Aes128Cfb8Dec = cfb8::Decryptor<Aes128>;
+
+// Ключи сервера для обмена Encryption Request / Encryption Response
+// Генерируются один раз на весь сервер, как и в ванилле
+pub struct ServerKeys {
+	private_key: RsaPrivateKey,
+	public_key_der: Vec<u8>,
+}
+
+impl ServerKeys {
+	pub fn generate() -> Result<ServerKeys, ServerError> {
+		let private_key =
+			RsaPrivateKey::new(&mut rand::thread_rng(), 1024).map_err(|_| ServerError::Encryption)?;
+		let public_key_der = private_key
+			.to_public_key()
+			.to_public_key_der()
+			.map_err(|_| ServerError::Encryption)?
+			.into_vec();
+
+		Ok(ServerKeys {
+			private_key,
+			public_key_der,
+		})
+	}
+
+	/// Публичный ключ в формате ASN.1 DER, именно его ждет клиент
+	pub fn public_key_der(&self) -> &[u8] {
+		&self.public_key_der
+	}
+
+	pub fn decrypt(&self, data: &[u8]) -> Result<Vec<u8>, ServerError> {
+		self
+			.private_key
+			.decrypt(Pkcs1v15Encrypt, data)
+			.map_err(|_| ServerError::Encryption)
+	}
+}
+
+/// Хеш сервера для сессионного сервера
+///
+/// Это sha1 дайджест, который записывается как знаковое число в hex (может начинаться с минуса)
+pub fn server_hash(server_id: &str, shared_secret: &[u8], public_key_der: &[u8]) -> String {
+	let mut hasher = Sha1::new();
+	hasher.update(server_id.as_bytes());
+	hasher.update(shared_secret);
+	hasher.update(public_key_der);
+	BigInt::from_signed_bytes_be(&hasher.finalize()).to_str_radix(16)
+}
+
+// Шифр соединения, AES/CFB8 в обе стороны
+// shared secret используется и как ключ и как IV
+pub struct Cipher {
+	encryptor: Aes128Cfb8Enc,
+	decryptor: Aes128Cfb8Dec,
+}
+
+impl Cipher {
+	pub fn new(shared_secret: &[u8]) -> Result<Cipher, ServerError> {
+		Ok(Cipher {
+			encryptor: Aes128Cfb8Enc::new_from_slices(shared_secret, shared_secret)
+				.map_err(|_| ServerError::Encryption)?,
+			decryptor: Aes128Cfb8Dec::new_from_slices(shared_secret, shared_secret)
+				.map_err(|_| ServerError::Encryption)?,
+		})
+	}
+
+	/// Разделяет шифр на две половинки, для записи и для чтения
+	pub fn split(self) -> (Encryptor, Decryptor) {
+		(Encryptor(self.encryptor), Decryptor(self.decryptor))
+	}
+}
+
+pub struct Encryptor(Aes128Cfb8Enc);
+
+impl Encryptor {
+	pub fn encrypt(&mut self, data: &mut [u8]) {
+		for byte in data.chunks_mut(1) {
+			self.0.encrypt_block_mut(GenericArray::from_mut_slice(byte));
+		}
+	}
+}
+
+pub struct Decryptor(Aes128Cfb8Dec);
+
+impl Decryptor {
+	pub fn decrypt(&mut self, data: &mut [u8]) {
+		for byte in data.chunks_mut(1) {
+			self.0.decrypt_block_mut(GenericArray::from_mut_slice(byte));
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn server_hash_matches_vanilla() {
+		// Известные значения из описания протокола: sha1 от ника, записанный как у клиента
+		let hash = |name: &str| {
+			let mut hasher = Sha1::new();
+			hasher.update(name.as_bytes());
+			BigInt::from_signed_bytes_be(&hasher.finalize()).to_str_radix(16)
+		};
+		assert_eq!(hash("Notch"), "4ed1f46bbe04bc756bcb17c0c7ce3e4632f06a48");
+		assert_eq!(hash("jeb_"), "-7c9d5b0044c130109a5d7b5fb5c317c02b4e28c1");
+		assert_eq!(hash("simon"), "88e16a1019277b15d58faf0541e11910eb756f6");
+		assert_eq!(server_hash("Notch", &[], &[]), hash("Notch"));
+	}
+
+	#[test]
+	fn cipher_round_trip() {
+		let secret: [u8; 16] = rand::random();
+		let (mut encryptor, _) = Cipher::new(&secret).unwrap().split();
+		let (_, mut decryptor) = Cipher::new(&secret).unwrap().split();
+
+		let data: Vec<u8> = (0..=255).collect();
+		let mut buffer = data.clone();
+		// Поток шифруется кусками разного размера, как приходят пакеты
+		encryptor.encrypt(&mut buffer[..7]);
+		encryptor.encrypt(&mut buffer[7..]);
+		assert_ne!(buffer, data);
+
+		decryptor.decrypt(&mut buffer[..100]);
+		decryptor.decrypt(&mut buffer[100..]);
+		assert_eq!(buffer, data);
+
+		assert!(Cipher::new(&secret[..15]).is_err());
+	}
+}
@@ -0,0 +1,185 @@
+use std::net::IpAddr;
+
+use hmac::{Hmac, Mac};
+use rust_mc_proto::{DataReader, Packet};
+use serde::{Deserialize, Serialize};
+use sha2::Sha256;
+use uuid::Uuid;
+
+use super::{packets::PacketField, session::ProfileProperty};
+use crate::ServerError;
+
+// Данные игрока от прокси (Velocity, BungeeCord), который сам проверил игрока через Mojang
+//
+// Legacy (BungeeCord): прокси дописывает в адрес рукопожатия через \0 настоящий айпи, uuid и свойства профиля
+// Velocity modern: сервер шлет Login Plugin Request в канал velocity:player_info,
+// прокси отвечает теми же данными, подписанными HMAC-SHA256 общим секретом
+
+pub const VELOCITY_CHANNEL: &str = "velocity:player_info";
+const VELOCITY_VERSION: u8 = 1; // MODERN_DEFAULT, без ключей чата
+
+/// Как сервер узнает настоящих игроков за прокси, с включенным режимом прямые подключения не пускаются
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ForwardingMode {
+	#[default]
+	None,
+	#[serde(alias = "bungeecord")]
+	Legacy,
+	Velocity,
+}
+
+#[derive(Debug, Clone)]
+pub struct ForwardedPlayer {
+	pub ip: IpAddr,
+	pub uuid: Uuid,
+	pub name: Option<String>, // legacy не пересылает имя, оно берется из Login Start
+	pub properties: Vec<ProfileProperty>,
+}
+
+/// Разбирает адрес из рукопожатия BungeeCord, возвращает настоящий адрес сервера и данные игрока
+pub fn parse_legacy(address: &str) -> Option<(String, ForwardedPlayer)> {
+	let mut parts = address.split('\0');
+	let host = parts.next()?.to_string();
+	let ip = parts.next()?.parse().ok()?;
+	let uuid = Uuid::parse_str(parts.next()?).ok()?;
+	let properties = match parts.next() {
+		Some(json) => serde_json::from_str(json).ok()?,
+		None => Vec::new(),
+	};
+
+	Some((
+		host,
+		ForwardedPlayer {
+			ip,
+			uuid,
+			name: None,
+			properties,
+		},
+	))
+}
+
+/// Тело запроса velocity:player_info - максимальная версия, которую понимает сервер
+pub fn velocity_request() -> Vec<u8> {
+	vec![VELOCITY_VERSION]
+}
+
+/// Проверяет подпись ответа Velocity и достает из него игрока
+pub fn parse_velocity(data: &[u8], secret: &str) -> Result<ForwardedPlayer, ServerError> {
+	let error = |text: &str| ServerError::Forwarding(text.to_string());
+
+	if secret.is_empty() {
+		return Err(error("пустой секрет"));
+	}
+	if data.len() < 32 {
+		return Err(error("слишком короткий ответ"));
+	}
+	let (signature, data) = data.split_at(32);
+
+	let mut mac =
+		Hmac::<Sha256>::new_from_slice(secret.as_bytes()).map_err(|_| error("неверный секрет"))?;
+	mac.update(data);
+	mac
+		.verify_slice(signature)
+		.map_err(|_| error("подпись не сошлась, секреты на прокси и сервере разные"))?;
+
+	let mut packet = Packet::from_bytes(0, data);
+	let version = packet.read_i32_varint()?;
+	if version < 1 || version > VELOCITY_VERSION as i32 {
+		return Err(error("неподдерживаемая версия"));
+	}
+
+	Ok(ForwardedPlayer {
+		ip: packet
+			.read_string()?
+			.parse()
+			.map_err(|_| error("неверный айпи"))?,
+		uuid: packet.read_uuid()?,
+		name: Some(packet.read_string()?),
+		properties: Vec::<ProfileProperty>::read_field(&mut packet)?,
+	})
+}
+
+#[cfg(test)]
+mod tests {
+	use rust_mc_proto::DataWriter;
+
+	use super::*;
+
+	const SECRET: &str = "forwarding-secret";
+
+	fn properties() -> Vec<ProfileProperty> {
+		vec![ProfileProperty {
+			name: "textures".to_string(),
+			value: "dGV4dHVyZXM=".to_string(),
+			signature: Some("c2lnbmF0dXJl".to_string()),
+		}]
+	}
+
+	// Ответ прокси: подпись HMAC-SHA256 и данные игрока
+	fn response(version: i32, secret: &str) -> Vec<u8> {
+		let mut packet = Packet::empty(0);
+		packet.write_i32_varint(version).unwrap();
+		packet.write_string("203.0.113.7").unwrap();
+		packet.write_uuid(&Uuid::from_u128(7)).unwrap();
+		packet.write_string("Steve").unwrap();
+		properties().write_field(&mut packet).unwrap();
+		let data = packet.get_bytes().to_vec();
+
+		let mut mac = Hmac::<Sha256>::new_from_slice(secret.as_bytes()).unwrap();
+		mac.update(&data);
+		let mut signed = mac.finalize().into_bytes().to_vec();
+		signed.extend(data);
+		signed
+	}
+
+	#[test]
+	fn velocity_valid_signature() {
+		let player = parse_velocity(&response(1, SECRET), SECRET).unwrap();
+		assert_eq!(player.ip, "203.0.113.7".parse::<IpAddr>().unwrap());
+		assert_eq!(player.uuid, Uuid::from_u128(7));
+		assert_eq!(player.name.as_deref(), Some("Steve"));
+		assert_eq!(player.properties, properties());
+	}
+
+	#[test]
+	fn velocity_bad_signature() {
+		assert!(parse_velocity(&response(1, "other-secret"), SECRET).is_err());
+
+		let mut data = response(1, SECRET);
+		let last = data.len() - 1;
+		data[last] ^= 1;
+		assert!(parse_velocity(&data, SECRET).is_err());
+
+		assert!(parse_velocity(&data[..20], SECRET).is_err());
+	}
+
+	#[test]
+	fn velocity_wrong_version() {
+		assert!(parse_velocity(&response(0, SECRET), SECRET).is_err());
+		assert!(parse_velocity(&response(VELOCITY_VERSION as i32 + 1, SECRET), SECRET).is_err());
+	}
+
+	#[test]
+	fn velocity_empty_secret() {
+		assert!(parse_velocity(&response(1, ""), "").is_err());
+	}
+
+	#[test]
+	fn legacy_handshake() {
+		let uuid = Uuid::from_u128(7);
+		let address = format!(
+			"play.example.com\x00203.0.113.7\x00{}\x00{}",
+			uuid.simple(),
+			serde_json::to_string(&properties()).unwrap()
+		);
+		let (host, player) = parse_legacy(&address).unwrap();
+		assert_eq!(host, "play.example.com");
+		assert_eq!(player.ip, "203.0.113.7".parse::<IpAddr>().unwrap());
+		assert_eq!(player.uuid, uuid);
+		assert_eq!(player.properties, properties());
+
+		assert!(parse_legacy("play.example.com").is_none());
+		assert!(parse_legacy("play.example.com\x00not-an-ip\x00").is_none());
+	}
+}
@@ -1,21 +1,30 @@
-use std::{
-	io::{Cursor, Read},
-	sync::Arc,
-};
+use std::{io::Cursor, sync::Arc, time::Duration};
 
 use crate::{
 	ServerError,
+	data::component::TextComponent,
+	play::get_offline_uuid,
 	player::context::{ClientContext, ClientInfo, Handshake, PlayerInfo},
 };
-use rust_mc_proto::{DataReader, DataWriter, Packet};
+use log::{error, info, warn};
+use rust_mc_proto::{DataReader, DataWriter};
 
-use crate::trigger_event;
+use crate::{trigger_event, trigger_event_ignore};
 
-use super::{ConnectionState, packet_id::*};
+use super::{
+	ConnectionState,
+	encryption::server_hash,
+	forwarding::{self, ForwardedPlayer, ForwardingMode},
+	packets::{RemainingBytes, VarInt, clientbound, serverbound, serverbound::status::StatusPacket},
+	status::StatusResponse,
+};
 
 // TODO: move brand to the config
 pub const BRAND: &str = "rust_mc_serv";
 
+// Сколько ждать отключения прошлой сессии при повторном входе
+const DUPLICATE_LOGIN_TIMEOUT: Duration = Duration::from_secs(5);
+
 pub fn handle_connection(
 	client: Arc<ClientContext>, // Контекст клиента
 ) -> Result<(), ServerError> {
@@ -23,20 +32,28 @@ pub fn handle_connection(
 	// Получение пакетов производится через client.conn(),
 	// ВАЖНО: не помещать сам client.conn() в переменные,
 	// он должен сразу убиваться иначе соединение гдето задедлочится
-	let mut packet = client.read_packet(&[serverbound::handshake::HANDSHAKE])?;
-
-	let protocol_version = packet.read_varint()?; // Получаем версия протокола, может быть отрицательным если наш клиент дэбил
-	let server_address = packet.read_string()?; // Получаем домен/адрес сервера к которому пытается подключиться клиент, например "play.example.com", а не айпи
-	let server_port = packet.read_unsigned_short()?; // Все тоже самое что и с адресом сервера и все потому же и за тем же
-	let next_state = packet.read_varint()?; // Тип подключения: 1 для получения статуса и пинга, 2 и 3 для обычного подключения
+	let handshake = client.receive::<serverbound::handshake::Handshake>()?;
+
+	// BungeeCord дописывает данные игрока в адрес, в рукопожатие кладем уже чистый адрес
+	let mut server_address = handshake.server_address;
+	let legacy = if client.server.config.server.forwarding == ForwardingMode::Legacy
+		&& handshake.next_state.0 == 2
+	{
+		forwarding::parse_legacy(&server_address).map(|(host, player)| {
+			server_address = host;
+			player
+		})
+	} else {
+		None
+	};
 
 	client.set_handshake(Handshake {
-		protocol_version,
+		protocol_version: handshake.protocol_version.0,
 		server_address,
-		server_port,
+		server_port: handshake.server_port,
 	});
 
-	match next_state {
+	match handshake.next_state.0 {
 		1 => {
 			// Тип подключения - статус
 			client.set_state(ConnectionState::Status)?; // Мы находимся в режиме Status
@@ -45,40 +62,24 @@ pub fn handle_connection(
 				// Чтение запроса
 				let mut packet = client.read_any_packet()?;
 
-				match packet.id() {
-					serverbound::status::REQUEST => {
-						// Запрос статуса
-						let mut packet = Packet::empty(clientbound::status::RESPONSE);
-
-						// Дефолтный статус
-						let mut status = "{
-							\"version\": {
-								\"name\": \"Error\",
-								\"protocol\": 0
-							},
-							\"description\": {\"text\": \"Internal server error\"}
-						}"
-						.to_string();
+				match StatusPacket::decode(&mut packet)? {
+					StatusPacket::StatusRequest(_) => {
+						// Статус из конфига
+						let mut status = StatusResponse::new(&client.server);
 
 						// Опрос всех листенеров
 						trigger_event!(client, status, &mut status);
 
 						// Отправка статуса
-						packet.write_string(&status)?;
-
-						client.write_packet(&packet)?;
+						client.send(&clientbound::status::StatusResponse {
+							json: status.as_json()?,
+						})?;
 					}
-					serverbound::status::PING_REQUEST => {
+					StatusPacket::PingRequest(ping) => {
 						// Пинг
-						// Раньше мы просто отправляли ему его-же пакет, но сейчас,
-						// С приходом к власти констант айди-пакетов, нам приходится делать такое непотребство
-						let timestamp = packet.read_long()?;
-						let mut packet = Packet::empty(clientbound::status::PONG_RESPONSE);
-						packet.write_long(timestamp)?;
-						client.write_packet(&packet)?;
-					}
-					id => {
-						return Err(ServerError::UnexpectedPacket(id));
+						client.send(&clientbound::status::PongResponse {
+							timestamp: ping.timestamp,
+						})?;
 					}
 				}
 			}
@@ -88,94 +89,168 @@ pub fn handle_connection(
 			client.set_state(ConnectionState::Login)?; // Мы находимся в режиме Login
 
 			// Читаем пакет Login Start
-			let mut packet = client.read_packet(&[serverbound::login::START])?;
+			let name = client.receive::<serverbound::login::LoginStart>()?.name;
+
+			// Данные от прокси, он уже проверил игрока, так что шифрование и сессии пропускаем
+			let forwarded = match client.server.config.server.forwarding {
+				ForwardingMode::None => None,
+				ForwardingMode::Legacy => match legacy {
+					Some(player) => Some(player),
+					None => {
+						client.protocol_helper().disconnect(TextComponent::new(
+							"If you wish to use IP forwarding, please enable it in your BungeeCord config as well!"
+								.to_string(),
+						))?;
+						return Err(ServerError::Forwarding(
+							"нет данных в рукопожатии".to_string(),
+						));
+					}
+				},
+				ForwardingMode::Velocity => Some(velocity_forwarding(&client)?),
+			};
+
+			let (name, uuid, properties) = if let Some(player) = forwarded {
+				info!("{} за прокси, настоящий айпи: {}", client.addr(), player.ip);
+				client.set_forwarded_ip(player.ip);
+				(player.name.unwrap_or(name), player.uuid, player.properties)
+			} else if client.server.config.server.online_mode {
+				// Online mode: обмен ключами и проверка через сессионный сервер
+				let keys = client.server.keys()?;
+				let verify_token: [u8; 4] = rand::random();
+
+				client.send(&clientbound::login::EncryptionRequest {
+					server_id: String::new(), // всегда пустой
+					public_key: keys.public_key_der().to_vec(),
+					verify_token: verify_token.to_vec(),
+					should_authenticate: true,
+				})?;
+
+				let response = client.receive::<serverbound::login::EncryptionResponse>()?;
+
+				// Токен, который не расшифровывается или не совпадает, значит клиент не знает наш ключ
+				let decrypted = keys
+					.decrypt(&response.shared_secret)
+					.and_then(|secret| Ok((secret, keys.decrypt(&response.verify_token)?)));
+				let shared_secret = match decrypted {
+					Ok((shared_secret, client_verify_token)) if client_verify_token == verify_token => {
+						shared_secret
+					}
+					other => {
+						client
+							.protocol_helper()
+							.disconnect(TextComponent::new("Invalid verify token!".to_string()))?;
+						return Err(other.err().unwrap_or(ServerError::WrongPacket));
+					}
+				};
+
+				// С этого момента все пакеты шифруются
+				client.set_encryption(&shared_secret)?;
 
-			let name = packet.read_string()?;
-			let uuid = packet.read_uuid()?;
+				let server_hash = server_hash("", &shared_secret, keys.public_key_der());
+				let ip = if client.server.config.server.prevent_proxy_connections {
+					Some(client.addr().ip())
+				} else {
+					None
+				};
+
+				match client
+					.server
+					.session_verifier()
+					.verify(&name, &server_hash, ip)?
+				{
+					Some(profile) => (profile.name, profile.id, profile.properties),
+					None => {
+						client
+							.protocol_helper()
+							.disconnect(TextComponent::new("Failed to verify username!".to_string()))?;
+						return Err(ServerError::NotAuthenticated);
+					}
+				}
+			} else {
+				let uuid = get_offline_uuid(&name);
+				(name, uuid, Vec::new())
+			};
 
 			client.set_player_info(PlayerInfo {
 				name: name.clone(),
-				uuid: uuid.clone(),
+				uuid,
+				properties: properties.clone(),
 			});
 
-			if client.server.config.server.online_mode {
-				// TODO: encryption packets
+			// Тот же игрок уже на сервере - старую сессию кикаем и ждем, пока она сохранится
+			if let Some(old) = client.server.clients.register_player(&client) {
+				info!("{name} зашел повторно, отключаем прошлую сессию");
+				let _ = old.kick(TextComponent::translatable(
+					"multiplayer.disconnect.duplicate_login",
+					Vec::new(),
+				));
+				if !client
+					.server
+					.clients
+					.wait_removed(old.id, DUPLICATE_LOGIN_TIMEOUT)
+				{
+					warn!("Прошлая сессия {name} не отключилась вовремя");
+				}
 			}
 
 			// Отправляем пакет Set Compression если сжатие указано
 			if let Some(threshold) = client.server.config.server.compression_threshold {
-				client.write_packet(&Packet::build(clientbound::login::SET_COMPRESSION, |p| {
-					p.write_usize_varint(threshold)
-				})?)?;
+				client.send(&clientbound::login::SetCompression {
+					threshold: VarInt(threshold as i32),
+				})?;
 				client.set_compression(Some(threshold)); // Устанавливаем сжатие на соединении
 			}
 
 			// Отправка пакета Login Success
-			client.write_packet(&Packet::build(clientbound::login::SUCCESS, |p| {
-				p.write_uuid(&uuid)?;
-				p.write_string(&name)?;
-				p.write_varint(0)
-			})?)?;
+			client.send(&clientbound::login::LoginSuccess {
+				uuid,
+				name,
+				properties,
+			})?;
 
-			client.read_packet(&[serverbound::login::ACKNOWLEDGED])?; // Пакет Login Acknowledged
+			client.receive::<serverbound::login::LoginAcknowledged>()?;
 
 			client.set_state(ConnectionState::Configuration)?; // Мы перешли в режим Configuration
 
 			// Получение бренда клиента из Serverbound Plugin Message
 			// Identifier канала откуда берется бренд: minecraft:brand
 			let brand = loop {
-				let mut packet = client.read_packet(&[serverbound::configuration::PLUGIN_MESSAGE])?; // Пакет Serverbound Plugin Message
-
-				let identifier = packet.read_string()?;
-
-				let mut data = Vec::new();
-				packet.get_mut().read_to_end(&mut data).unwrap();
+				let message = client.receive::<serverbound::configuration::PluginMessage>()?;
 
-				if identifier == "minecraft:brand" {
-					break Cursor::new(data).read_string()?;
+				if message.channel == "minecraft:brand" {
+					break Cursor::new(message.data.0).read_string()?;
 				} else {
-					trigger_event!(client, plugin_message, &identifier, &data);
+					trigger_event!(client, plugin_message, &message.channel, &message.data.0);
 				}
 			};
 
-			let mut packet = client.read_packet(&[serverbound::configuration::CLIENT_INFORMATION])?; // Пакет Client Information
-
-			let locale = packet.read_string()?; // for example: en_us
-			let view_distance = packet.read_signed_byte()?; // client-side render distance in chunks
-			let chat_mode = packet.read_varint()?; // 0: enabled, 1: commands only, 2: hidden. See Chat#Client chat mode for more information. 
-			let chat_colors = packet.read_boolean()?; // this settings does nothing on client but can be used on serverside
-			let displayed_skin_parts = packet.read_byte()?; // bit mask https://minecraft.wiki/w/Java_Edition_protocol#Client_Information_(configuration)
-			let main_hand = packet.read_varint()?; // 0 for left and 1 for right
-			let enable_text_filtering = packet.read_boolean()?; // filtering text for profanity, always false for offline mode
-			let allow_server_listings = packet.read_boolean()?; // allows showing player in server listings in status
-			let particle_status = packet.read_varint()?; // 0 for all, 1 for decreased, 2 for minimal
+			let info = client.receive::<serverbound::configuration::ClientInformation>()?;
 
 			client.set_client_info(ClientInfo {
 				brand,
-				locale,
-				view_distance,
-				chat_mode,
-				chat_colors,
-				displayed_skin_parts,
-				main_hand,
-				enable_text_filtering,
-				allow_server_listings,
-				particle_status,
+				locale: info.locale,
+				view_distance: info.view_distance,
+				chat_mode: info.chat_mode.0,
+				chat_colors: info.chat_colors,
+				displayed_skin_parts: info.displayed_skin_parts,
+				main_hand: info.main_hand.0,
+				enable_text_filtering: info.enable_text_filtering,
+				allow_server_listings: info.allow_server_listings,
+				particle_status: info.particle_status.0,
 			});
 
-			client.write_packet(&Packet::build(
-				clientbound::configuration::PLUGIN_MESSAGE,
-				|p| {
-					p.write_string("minecraft:brand")?;
-					p.write_string(BRAND)
-				},
-			)?)?;
+			let mut brand = Vec::new();
+			brand.write_string(BRAND)?;
+			client.send(&clientbound::configuration::PluginMessage {
+				channel: "minecraft:brand".to_string(),
+				data: RemainingBytes(brand),
+			})?;
 
-			client.write_packet(&Packet::empty(clientbound::configuration::FINISH))?;
+			client.send(&clientbound::configuration::FinishConfiguration {})?;
 
 			// На этом моменте пакет хандер ловит пакет и перед ним делает свое мракобесие
 
-			client.read_packet(&[serverbound::configuration::ACKNOWLEDGE_FINISH])?;
+			client.receive::<serverbound::configuration::AcknowledgeFinishConfiguration>()?;
 
 			client.set_state(ConnectionState::Play)?; // Мы перешли в режим Play
 
@@ -189,3 +264,71 @@ pub fn handle_connection(
 
 	Ok(())
 }
+
+/// Ведет подключение до входа в игру в потоке пула, дальше клиентом занимается тик игрока
+pub fn run_connection(client: Arc<ClientContext>) {
+	info!("Подключение: {}", client.addr());
+
+	// Добавляем клиента в список клиентов сервера
+	client.server.clients.add(client.clone());
+
+	match handle_connection(client.clone()) {
+		Ok(_) => {}
+		Err(ServerError::ConnectionClosed) => {}
+		Err(error) => {
+			error!("Ошибка подключения: {error:?}");
+			client.close();
+		}
+	};
+
+	// В игре отключение обработает тик игрока, когда увидит закрытое соединение
+	if client.state() != ConnectionState::Play || !client.is_alive() {
+		finish_connection(client);
+	}
+}
+
+/// Отключение клиента: событие disconnect, удаление из списка клиентов и закрытие соединения
+///
+/// Может вызываться несколько раз, сработает только первый
+pub fn finish_connection(client: Arc<ClientContext>) {
+	if !client.mark_finished() {
+		return;
+	}
+
+	client.close();
+	trigger_event_ignore!(client, disconnect);
+
+	// Удаляем клиента из списка клиентов
+	client.server.clients.remove(&client);
+
+	info!("Отключение: {}", client.addr());
+}
+
+/// Запрашивает у Velocity данные игрока, без ответа или с неверной подписью игрок кикается
+fn velocity_forwarding(client: &Arc<ClientContext>) -> Result<ForwardedPlayer, ServerError> {
+	let message_id = rand::random::<u16>() as i32;
+
+	let (response_id, data) = client.protocol_helper().send_login_plugin_request(
+		message_id,
+		forwarding::VELOCITY_CHANNEL,
+		&forwarding::velocity_request(),
+	)?;
+
+	let Some(data) = data.filter(|_| response_id == message_id) else {
+		// Клиент без прокси не знает канал и отвечает пустым ответом
+		client.protocol_helper().disconnect(TextComponent::new(
+			"This server requires you to connect with Velocity.".to_string(),
+		))?;
+		return Err(ServerError::Forwarding("прямое подключение".to_string()));
+	};
+
+	match forwarding::parse_velocity(&data, &client.server.config.server.forwarding_secret) {
+		Ok(player) => Ok(player),
+		Err(error) => {
+			client.protocol_helper().disconnect(TextComponent::new(
+				"Unable to verify player details.".to_string(),
+			))?;
+			Err(error)
+		}
+	}
+}
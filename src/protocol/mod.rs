@@ -1,5 +1,13 @@
+pub mod encryption;
+pub mod forwarding;
 pub mod handler;
 pub mod packet_id;
+pub mod packets;
+pub mod session;
+pub mod status;
+
+// Версия протокола 1.21.5, под которую написан сервер
+pub const PROTOCOL_VERSION: i32 = 770;
 
 #[derive(Debug, Clone, PartialEq)]
 pub enum ConnectionState {
@@ -0,0 +1,707 @@
+// Пакеты которые отправляет сервер
+// Айдишники берутся из packet_id, структура модулей такая же
+
+pub mod status {
+	use crate::protocol::packet_id::clientbound::status::*;
+
+	packet! {
+		pub struct StatusResponse(RESPONSE, Status) {
+			pub json: String,
+		}
+	}
+
+	packet! {
+		pub struct PongResponse(PONG_RESPONSE, Status) {
+			pub timestamp: i64,
+		}
+	}
+}
+
+pub mod login {
+	use uuid::Uuid;
+
+	use crate::protocol::{
+		packet_id::clientbound::login::*,
+		packets::{RemainingBytes, VarInt},
+		session::ProfileProperty,
+	};
+
+	packet! {
+		pub struct Disconnect(DISCONNECT, Login) {
+			pub reason: String, // JSON текст-компонент
+		}
+	}
+
+	packet! {
+		pub struct EncryptionRequest(ENCRYPTION_REQUEST, Login) {
+			pub server_id: String,
+			pub public_key: Vec<u8>,
+			pub verify_token: Vec<u8>,
+			pub should_authenticate: bool,
+		}
+	}
+
+	packet! {
+		pub struct LoginSuccess(SUCCESS, Login) {
+			pub uuid: Uuid,
+			pub name: String,
+			pub properties: Vec<ProfileProperty>,
+		}
+	}
+
+	packet! {
+		pub struct SetCompression(SET_COMPRESSION, Login) {
+			pub threshold: VarInt,
+		}
+	}
+
+	packet! {
+		pub struct PluginRequest(PLUGIN_REQUEST, Login) {
+			pub message_id: VarInt,
+			pub channel: String,
+			pub data: RemainingBytes,
+		}
+	}
+
+	packet! {
+		pub struct CookieRequest(COOKIE_REQUEST, Login) {
+			pub key: String,
+		}
+	}
+}
+
+pub mod configuration {
+	use crate::{
+		data::component::TextComponent,
+		protocol::{
+			packet_id::clientbound::configuration::*,
+			packets::{KnownPack, RegistryDataEntry, RegistryTags, RemainingBytes},
+		},
+	};
+
+	packet! {
+		pub struct CookieRequest(COOKIE_REQUEST, Configuration) {
+			pub key: String,
+		}
+	}
+
+	packet! {
+		pub struct PluginMessage(PLUGIN_MESSAGE, Configuration) {
+			pub channel: String,
+			pub data: RemainingBytes,
+		}
+	}
+
+	packet! {
+		pub struct Disconnect(DISCONNECT, Configuration) {
+			pub reason: TextComponent,
+		}
+	}
+
+	packet! {
+		pub struct FinishConfiguration(FINISH, Configuration) {}
+	}
+
+	packet! {
+		pub struct KeepAlive(KEEP_ALIVE, Configuration) {
+			pub id: i64,
+		}
+	}
+
+	packet! {
+		pub struct Ping(PING, Configuration) {
+			pub id: i32,
+		}
+	}
+
+	packet! {
+		pub struct ResetChat(RESET_CHAT, Configuration) {}
+	}
+
+	packet! {
+		pub struct StoreCookie(STORE_COOKIE, Configuration) {
+			pub key: String,
+			pub payload: Vec<u8>,
+		}
+	}
+
+	packet! {
+		pub struct FeatureFlags(FEATURE_FLAGS, Configuration) {
+			pub flags: Vec<String>,
+		}
+	}
+
+	packet! {
+		pub struct KnownPacks(KNOWN_PACKS, Configuration) {
+			pub packs: Vec<KnownPack>,
+		}
+	}
+
+	packet! {
+		pub struct RegistryData(REGISTRY_DATA, Configuration) {
+			pub registry_id: String,
+			pub entries: Vec<RegistryDataEntry>,
+		}
+	}
+
+	packet! {
+		pub struct UpdateTags(UPDATE_TAGS, Configuration) {
+			pub registries: Vec<RegistryTags>,
+		}
+	}
+}
+
+pub mod play {
+	use craftflow_nbt::DynNBT;
+	use rust_mc_proto::{DataReader, DataWriter, Packet};
+	use uuid::Uuid;
+
+	use crate::{
+		ServerError,
+		data::{component::TextComponent, metadata::Metadata, slot::Slot},
+		protocol::{
+			ConnectionState,
+			packet_id::clientbound::play::*,
+			packets::{Angle, PacketData, PacketField, Position, RemainingBytes, VarInt},
+			session::ProfileProperty,
+		},
+	};
+
+	packet! {
+		pub struct SpawnEntity(SPAWN_ENTITY, Play) {
+			pub entity_id: VarInt,
+			pub uuid: Uuid,
+			pub entity_type: VarInt,
+			pub x: f64,
+			pub y: f64,
+			pub z: f64,
+			pub pitch: Angle,
+			pub yaw: Angle,
+			pub head_yaw: Angle,
+			pub data: VarInt,
+			pub velocity_x: i16,
+			pub velocity_y: i16,
+			pub velocity_z: i16,
+		}
+	}
+
+	packet! {
+		pub struct EntityAnimation(ENTITY_ANIMATION, Play) {
+			pub entity_id: VarInt,
+			pub animation: u8,
+		}
+	}
+
+	packet! {
+		pub struct CookieRequest(COOKIE_REQUEST, Play) {
+			pub key: String,
+		}
+	}
+
+	packet! {
+		pub struct PluginMessage(PLUGIN_MESSAGE, Play) {
+			pub channel: String,
+			pub data: RemainingBytes,
+		}
+	}
+
+	packet! {
+		pub struct Disconnect(DISCONNECT, Play) {
+			pub reason: TextComponent,
+		}
+	}
+
+	packet! {
+		pub struct OpenScreen(OPEN_SCREEN, Play) {
+			pub window_id: VarInt,
+			pub window_type: VarInt, // айди из реестра minecraft:menu
+			pub title: TextComponent,
+		}
+	}
+
+	packet! {
+		pub struct CloseContainer(CLOSE_CONTAINER, Play) {
+			pub window_id: VarInt,
+		}
+	}
+
+	packet! {
+		pub struct SetContainerContent(SET_CONTAINER_CONTENT, Play) {
+			pub window_id: VarInt,
+			pub state_id: VarInt,
+			pub slots: Vec<Slot>,
+			pub carried_item: Slot,
+		}
+	}
+
+	packet! {
+		pub struct SetContainerSlot(SET_CONTAINER_SLOT, Play) {
+			pub window_id: VarInt,
+			pub state_id: VarInt,
+			pub slot: i16,
+			pub item: Slot,
+		}
+	}
+
+	packet! {
+		pub struct SetContainerProperty(SET_CONTAINER_PROPERTY, Play) {
+			pub window_id: VarInt,
+			pub property: i16,
+			pub value: i16,
+		}
+	}
+
+	packet! {
+		pub struct EntityEvent(ENTITY_EVENT, Play) {
+			pub entity_id: i32,
+			pub status: u8,
+		}
+	}
+
+	packet! {
+		pub struct UnloadChunk(UNLOAD_CHUNK, Play) {
+			pub z: i32, // да, сначала Z
+			pub x: i32,
+		}
+	}
+
+	packet_field! {
+		pub struct Heightmap {
+			pub kind: VarInt, // 1 - WORLD_SURFACE, 4 - MOTION_BLOCKING
+			pub data: Vec<i64>,
+		}
+	}
+
+	packet_field! {
+		pub struct BlockEntity {
+			pub packed_xz: u8, // ((x & 15) << 4) | (z & 15)
+			pub y: i16,
+			pub kind: VarInt,
+			pub data: DynNBT,
+		}
+	}
+
+	// Маски и массивы света, общие для Chunk Data and Update Light и Update Light
+	packet_field! {
+		pub struct LightData {
+			pub sky_light_mask: Vec<i64>,
+			pub block_light_mask: Vec<i64>,
+			pub empty_sky_light_mask: Vec<i64>,
+			pub empty_block_light_mask: Vec<i64>,
+			pub sky_light: Vec<Vec<u8>>, // по 2048 байт на секцию из маски
+			pub block_light: Vec<Vec<u8>>,
+		}
+	}
+
+	packet! {
+		pub struct ChunkDataAndUpdateLight(CHUNK_DATA_AND_UPDATE_LIGHT, Play) {
+			pub x: i32,
+			pub z: i32,
+			pub heightmaps: Vec<Heightmap>,
+			pub data: Vec<u8>, // секции подряд, см. play::chunk
+			pub block_entities: Vec<BlockEntity>,
+			pub light: LightData,
+		}
+	}
+
+	packet! {
+		pub struct AcknowledgeBlockChange(ACKNOWLEDGE_BLOCK_CHANGE, Play) {
+			pub sequence: VarInt,
+		}
+	}
+
+	packet! {
+		pub struct BlockUpdate(BLOCK_UPDATE, Play) {
+			pub location: Position,
+			pub block_id: VarInt,
+		}
+	}
+
+	packet! {
+		pub struct UpdateLight(UPDATE_LIGHT, Play) {
+			pub x: VarInt,
+			pub z: VarInt,
+			pub light: LightData,
+		}
+	}
+
+	packet! {
+		pub struct GameEvent(GAME_EVENT, Play) {
+			pub event: u8,
+			pub value: f32,
+		}
+	}
+
+	packet! {
+		pub struct KeepAlive(KEEP_ALIVE, Play) {
+			pub id: i64,
+		}
+	}
+
+	packet_field! {
+		pub struct DeathLocation {
+			pub dimension: String,
+			pub location: Position,
+		}
+	}
+
+	packet! {
+		pub struct Login(LOGIN, Play) {
+			pub entity_id: i32,
+			pub is_hardcore: bool,
+			pub dimension_names: Vec<String>,
+			pub max_players: VarInt,
+			pub view_distance: VarInt,
+			pub simulation_distance: VarInt,
+			pub reduced_debug_info: bool,
+			pub enable_respawn_screen: bool,
+			pub do_limited_crafting: bool,
+			pub dimension_type: VarInt,
+			pub dimension_name: String,
+			pub hashed_seed: i64,
+			pub game_mode: u8,
+			pub previous_game_mode: i8,
+			pub is_debug: bool,
+			pub is_flat: bool,
+			pub death_location: Option<DeathLocation>,
+			pub portal_cooldown: VarInt,
+			pub sea_level: VarInt,
+			pub enforces_secure_chat: bool,
+		}
+	}
+
+	packet! {
+		/// Пересоздает мир на клиенте, так игрок переходит между мирами
+		pub struct Respawn(RESPAWN, Play) {
+			pub dimension_type: VarInt,
+			pub dimension_name: String,
+			pub hashed_seed: i64,
+			pub game_mode: u8,
+			pub previous_game_mode: i8,
+			pub is_debug: bool,
+			pub is_flat: bool,
+			pub death_location: Option<DeathLocation>,
+			pub portal_cooldown: VarInt,
+			pub sea_level: VarInt,
+			pub data_kept: u8, // 0x01 - атрибуты, 0x02 - метаданные
+		}
+	}
+
+	packet! {
+		pub struct UpdateEntityPosition(UPDATE_ENTITY_POSITION, Play) {
+			pub entity_id: VarInt,
+			pub delta_x: i16, // формула: currentX * 4096 - prevX * 4096
+			pub delta_y: i16,
+			pub delta_z: i16,
+			pub on_ground: bool,
+		}
+	}
+
+	packet! {
+		pub struct UpdateEntityPositionAndRotation(UPDATE_ENTITY_POSITION_AND_ROTATION, Play) {
+			pub entity_id: VarInt,
+			pub delta_x: i16,
+			pub delta_y: i16,
+			pub delta_z: i16,
+			pub yaw: Angle,
+			pub pitch: Angle,
+			pub on_ground: bool,
+		}
+	}
+
+	packet! {
+		pub struct UpdateEntityRotation(UPDATE_ENTITY_ROTATION, Play) {
+			pub entity_id: VarInt,
+			pub yaw: Angle,
+			pub pitch: Angle,
+			pub on_ground: bool,
+		}
+	}
+
+	packet! {
+		pub struct SetEntityMetadata(SET_ENTITY_METADATA, Play) {
+			pub entity_id: VarInt,
+			pub metadata: Metadata,
+		}
+	}
+
+	// В протоколе entity_position_sync, полная позиция когда дельта не влезает в i16
+	packet! {
+		pub struct TeleportEntity(TELEPORT_ENTITY, Play) {
+			pub entity_id: VarInt,
+			pub x: f64,
+			pub y: f64,
+			pub z: f64,
+			pub velocity_x: f64,
+			pub velocity_y: f64,
+			pub velocity_z: f64,
+			pub yaw: f32,
+			pub pitch: f32,
+			pub on_ground: bool,
+		}
+	}
+
+	packet! {
+		pub struct SetHealth(SET_HEALTH, Play) {
+			pub health: f32, // 0 и меньше - смерть
+			pub food: VarInt, // 0..20
+			pub saturation: f32,
+		}
+	}
+
+	packet! {
+		pub struct SetHeldItem(SET_HELD_ITEM, Play) {
+			pub slot: VarInt, // 0..8
+		}
+	}
+
+	packet! {
+		pub struct Ping(PING, Play) {
+			pub id: i32,
+		}
+	}
+
+	packet! {
+		pub struct PlayerInfoRemove(PLAYER_INFO_REMOVE, Play) {
+			pub uuids: Vec<Uuid>,
+		}
+	}
+
+	#[derive(Debug, Clone)]
+	pub struct PlayerInfoEntry {
+		pub uuid: Uuid,
+		pub name: String,
+		pub properties: Vec<ProfileProperty>,
+	}
+
+	// Player Info Update с одним действием Add Player
+	// Поля игрока зависят от маски действий, поэтому пакет собирается руками
+	#[derive(Debug, Clone)]
+	pub struct PlayerInfoAdd {
+		pub players: Vec<PlayerInfoEntry>,
+	}
+
+	impl PlayerInfoAdd {
+		const ADD_PLAYER: u8 = 0x01;
+	}
+
+	impl PacketData for PlayerInfoAdd {
+		const ID: u8 = PLAYER_INFO_UPDATE;
+		const STATE: ConnectionState = ConnectionState::Play;
+
+		fn read_fields(packet: &mut Packet) -> Result<Self, ServerError> {
+			if u8::read_field(packet)? != Self::ADD_PLAYER {
+				return Err(ServerError::WrongPacket);
+			}
+
+			let count = VarInt::read_field(packet)?.0;
+			let mut players = Vec::new();
+			for _ in 0..count {
+				players.push(PlayerInfoEntry {
+					uuid: Uuid::read_field(packet)?,
+					name: String::read_field(packet)?,
+					properties: Vec::read_field(packet)?,
+				});
+			}
+
+			Ok(PlayerInfoAdd { players })
+		}
+
+		fn write_fields(&self, packet: &mut Packet) -> Result<(), ServerError> {
+			packet.write_byte(Self::ADD_PLAYER)?;
+			packet.write_usize_varint(self.players.len())?;
+			for player in &self.players {
+				player.uuid.write_field(packet)?;
+				player.name.write_field(packet)?;
+				player.properties.write_field(packet)?;
+			}
+			Ok(())
+		}
+	}
+
+	/// Узел графа команд, поля зависят от флагов, поэтому PacketField руками
+	#[derive(Debug, Clone, PartialEq)]
+	pub struct CommandNode {
+		pub kind: u8, // 0 - корень, 1 - литерал, 2 - аргумент
+		pub executable: bool,
+		pub children: Vec<VarInt>,
+		pub redirect: Option<VarInt>,
+		pub name: String,                // пусто у корня
+		pub parser: VarInt,              // только у аргумента, айди из minecraft:command_argument_type
+		pub properties: Vec<u8>,         // свойства парсера, уже закодированные
+		pub suggestions: Option<String>, // например minecraft:ask_server
+	}
+
+	impl CommandNode {
+		const EXECUTABLE: u8 = 0x04;
+		const REDIRECT: u8 = 0x08;
+		const SUGGESTIONS: u8 = 0x10;
+
+		// Длина свойств парсера, без нее узел не дочитать
+		fn read_properties(parser: i32, packet: &mut Packet) -> Result<Vec<u8>, ServerError> {
+			let mut properties = Packet::empty(0);
+			match parser {
+				1..=4 => {
+					let flags = packet.read_byte()?;
+					let size = match parser {
+						1 | 3 => 4,
+						_ => 8,
+					};
+					properties.write_byte(flags)?;
+					for _ in 0..(flags & 1) + ((flags >> 1) & 1) {
+						properties.write_bytes(&packet.read_bytes(size)?)?;
+					}
+				}
+				5 => properties.write_varint(packet.read_varint()?)?,
+				6 => properties.write_byte(packet.read_byte()?)?,
+				0 | 7..=11 => {}
+				_ => return Err(ServerError::WrongPacket),
+			}
+			Ok(properties.get_bytes().to_vec())
+		}
+	}
+
+	impl PacketField for CommandNode {
+		fn read_field(packet: &mut Packet) -> Result<Self, ServerError> {
+			let flags = packet.read_byte()?;
+			let kind = flags & 0x03;
+			let children = Vec::read_field(packet)?;
+			let redirect = match flags & Self::REDIRECT {
+				0 => None,
+				_ => Some(VarInt::read_field(packet)?),
+			};
+			let name = match kind {
+				0 => String::new(),
+				_ => packet.read_string()?,
+			};
+			let (parser, properties) = match kind {
+				2 => {
+					let parser = packet.read_varint()?;
+					(VarInt(parser), Self::read_properties(parser, packet)?)
+				}
+				_ => (VarInt(0), Vec::new()),
+			};
+			let suggestions = match flags & Self::SUGGESTIONS {
+				0 => None,
+				_ => Some(packet.read_string()?),
+			};
+
+			Ok(CommandNode {
+				kind,
+				executable: flags & Self::EXECUTABLE != 0,
+				children,
+				redirect,
+				name,
+				parser,
+				properties,
+				suggestions,
+			})
+		}
+
+		fn write_field(&self, packet: &mut Packet) -> Result<(), ServerError> {
+			let mut flags = self.kind & 0x03;
+			if self.executable {
+				flags |= Self::EXECUTABLE;
+			}
+			if self.redirect.is_some() {
+				flags |= Self::REDIRECT;
+			}
+			if self.suggestions.is_some() {
+				flags |= Self::SUGGESTIONS;
+			}
+
+			packet.write_byte(flags)?;
+			self.children.write_field(packet)?;
+			if let Some(redirect) = &self.redirect {
+				redirect.write_field(packet)?;
+			}
+			if self.kind != 0 {
+				packet.write_string(&self.name)?;
+			}
+			if self.kind == 2 {
+				self.parser.write_field(packet)?;
+				packet.write_bytes(&self.properties)?;
+			}
+			if let Some(suggestions) = &self.suggestions {
+				packet.write_string(suggestions)?;
+			}
+			Ok(())
+		}
+	}
+
+	packet! {
+		pub struct Commands(COMMANDS, Play) {
+			pub nodes: Vec<CommandNode>,
+			pub root: VarInt,
+		}
+	}
+
+	packet_field! {
+		pub struct CommandSuggestion {
+			pub text: String,
+			pub tooltip: Option<TextComponent>,
+		}
+	}
+
+	packet! {
+		pub struct CommandSuggestionsResponse(COMMAND_SUGGESTIONS_RESPONSE, Play) {
+			pub id: VarInt,
+			pub start: VarInt,
+			pub length: VarInt,
+			pub matches: Vec<CommandSuggestion>,
+		}
+	}
+
+	packet! {
+		pub struct SynchronizePlayerPosition(SYNCHRONIZE_PLAYER_POSITION, Play) {
+			pub teleport_id: VarInt,
+			pub x: f64,
+			pub y: f64,
+			pub z: f64,
+			pub velocity_x: f64,
+			pub velocity_y: f64,
+			pub velocity_z: f64,
+			pub yaw: f32,
+			pub pitch: f32,
+			pub flags: i32,
+		}
+	}
+
+	packet! {
+		pub struct RemoveEntities(REMOVE_ENTITIES, Play) {
+			pub entity_ids: Vec<VarInt>,
+		}
+	}
+
+	packet! {
+		pub struct SetHeadRotation(SET_HEAD_ROTATION, Play) {
+			pub entity_id: VarInt,
+			pub head_yaw: Angle,
+		}
+	}
+
+	packet! {
+		pub struct SetCenterChunk(SET_CENTER_CHUNK, Play) {
+			pub x: VarInt,
+			pub z: VarInt,
+		}
+	}
+
+	packet! {
+		pub struct StartConfiguration(START_CONFIGURATION, Play) {}
+	}
+
+	packet! {
+		pub struct StoreCookie(STORE_COOKIE, Play) {
+			pub key: String,
+			pub payload: Vec<u8>,
+		}
+	}
+
+	packet! {
+		pub struct SystemChatMessage(SYSTEM_CHAT_MESSAGE, Play) {
+			pub content: TextComponent,
+			pub overlay: bool, // true - в экшн бар
+		}
+	}
+}
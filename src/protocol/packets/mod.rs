@@ -0,0 +1,587 @@
+use std::io::Read;
+
+use craftflow_nbt::DynNBT;
+use rust_mc_proto::{DataReader, DataWriter, Packet};
+use uuid::Uuid;
+
+use crate::{
+	ServerError,
+	data::{ReadWriteNBT, ReadWritePosition, component::TextComponent},
+	protocol::{ConnectionState, session::ProfileProperty},
+};
+
+// Типизированные пакеты
+//
+// Каждый пакет это структ, поля которого читаются и пишутся в том порядке, в котором объявлены
+// Структы генерируются макросом packet!, он же реализует PacketData с айди и стейтом пакета
+// Если пакет слишком хитрый для макроса (например Player Info Update), PacketData реализуется руками
+
+/// Поле пакета, которое умеет читать и писать себя
+pub trait PacketField: Sized {
+	fn read_field(packet: &mut Packet) -> Result<Self, ServerError>;
+	fn write_field(&self, packet: &mut Packet) -> Result<(), ServerError>;
+}
+
+/// Пакет с известным айди и стейтом
+pub trait PacketData: Sized {
+	const ID: u8;
+	const STATE: ConnectionState;
+
+	fn read_fields(packet: &mut Packet) -> Result<Self, ServerError>;
+	fn write_fields(&self, packet: &mut Packet) -> Result<(), ServerError>;
+
+	/// Собирает сырой пакет
+	fn encode(&self) -> Result<Packet, ServerError> {
+		let mut packet = Packet::empty(Self::ID);
+		self.write_fields(&mut packet)?;
+		Ok(packet)
+	}
+
+	/// Разбирает сырой пакет, айди должен совпадать
+	fn decode(packet: &mut Packet) -> Result<Self, ServerError> {
+		if packet.id() != Self::ID {
+			return Err(ServerError::UnexpectedPacket(packet.id()));
+		}
+		Self::read_fields(packet)
+	}
+}
+
+/// Пример использования:
+///
+/// ```ignore
+/// packet! {
+///     pub struct KeepAlive(clientbound::play::KEEP_ALIVE, Play) {
+///         pub id: i64,
+///     }
+/// }
+/// ```
+macro_rules! packet {
+	(
+		$(#[$meta:meta])*
+		$vis:vis struct $name:ident ($id:expr, $state:ident) {
+			$($(#[$field_meta:meta])* $field_vis:vis $field:ident : $ty:ty),* $(,)?
+		}
+	) => {
+		$(#[$meta])*
+		#[derive(Debug, Clone)]
+		$vis struct $name {
+			$($(#[$field_meta])* $field_vis $field: $ty),*
+		}
+
+		impl $crate::protocol::packets::PacketData for $name {
+			const ID: u8 = $id;
+			const STATE: $crate::protocol::ConnectionState = $crate::protocol::ConnectionState::$state;
+
+			#[allow(unused_variables)]
+			fn read_fields(
+				packet: &mut rust_mc_proto::Packet,
+			) -> Result<Self, $crate::ServerError> {
+				Ok(Self {
+					$($field: $crate::protocol::packets::PacketField::read_field(packet)?),*
+				})
+			}
+
+			#[allow(unused_variables)]
+			fn write_fields(
+				&self,
+				packet: &mut rust_mc_proto::Packet,
+			) -> Result<(), $crate::ServerError> {
+				$($crate::protocol::packets::PacketField::write_field(&self.$field, packet)?;)*
+				Ok(())
+			}
+		}
+	};
+}
+
+/// Тоже самое что и packet!, только для составных полей без айди
+macro_rules! packet_field {
+	(
+		$(#[$meta:meta])*
+		$vis:vis struct $name:ident {
+			$($(#[$field_meta:meta])* $field_vis:vis $field:ident : $ty:ty),* $(,)?
+		}
+	) => {
+		$(#[$meta])*
+		#[derive(Debug, Clone, PartialEq)]
+		$vis struct $name {
+			$($(#[$field_meta])* $field_vis $field: $ty),*
+		}
+
+		impl $crate::protocol::packets::PacketField for $name {
+			fn read_field(packet: &mut rust_mc_proto::Packet) -> Result<Self, $crate::ServerError> {
+				Ok(Self {
+					$($field: $crate::protocol::packets::PacketField::read_field(packet)?),*
+				})
+			}
+
+			fn write_field(&self, packet: &mut rust_mc_proto::Packet) -> Result<(), $crate::ServerError> {
+				$($crate::protocol::packets::PacketField::write_field(&self.$field, packet)?;)*
+				Ok(())
+			}
+		}
+	};
+}
+
+// Чтобы составные поля можно было объявлять и вне протокола (например компоненты предметов в data::slot)
+pub(crate) use packet_field;
+
+/// Енам из нескольких пакетов одного стейта, чтобы матчить пакеты вместо айдишников
+///
+/// ```ignore
+/// match PlayPacket::decode(&mut client.read_packet(PlayPacket::IDS)?)? {
+///     PlayPacket::SwingArm(packet) => {}
+///     ...
+/// }
+/// ```
+macro_rules! packet_enum {
+	(
+		$(#[$meta:meta])*
+		$vis:vis enum $name:ident {
+			$($variant:ident($ty:ty)),* $(,)?
+		}
+	) => {
+		$(#[$meta])*
+		#[derive(Debug, Clone)]
+		$vis enum $name {
+			$($variant($ty)),*
+		}
+
+		impl $name {
+			/// Айди всех пакетов енама, для ClientContext::read_packet
+			pub const IDS: &[u8] = &[$(<$ty as $crate::protocol::packets::PacketData>::ID),*];
+
+			pub fn decode(packet: &mut rust_mc_proto::Packet) -> Result<Self, $crate::ServerError> {
+				$(
+					if packet.id() == <$ty as $crate::protocol::packets::PacketData>::ID {
+						return Ok(Self::$variant(
+							<$ty as $crate::protocol::packets::PacketData>::read_fields(packet)?,
+						));
+					}
+				)*
+				Err($crate::ServerError::UnexpectedPacket(packet.id()))
+			}
+
+			pub fn encode(&self) -> Result<rust_mc_proto::Packet, $crate::ServerError> {
+				match self {
+					$(Self::$variant(packet) => {
+						$crate::protocol::packets::PacketData::encode(packet)
+					}),*
+				}
+			}
+		}
+	};
+}
+
+pub mod clientbound;
+pub mod serverbound;
+
+/// VarInt, в отличие от i32 который пишется как Int
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct VarInt(pub i32);
+
+/// VarLong, в отличие от i64 который пишется как Long
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct VarLong(pub i64);
+
+/// Угол в градусах, передается одним байтом (1/256 полного оборота)
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct Angle(pub f32);
+
+/// Позиция блока, упакованная в Long
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Position {
+	pub x: i64,
+	pub y: i64,
+	pub z: i64,
+}
+
+/// Все оставшиеся байты пакета, без префикса длины
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct RemainingBytes(pub Vec<u8>);
+
+// Примитивы
+
+macro_rules! primitive_field {
+	($ty:ty, $read:ident, $write:ident) => {
+		impl PacketField for $ty {
+			fn read_field(packet: &mut Packet) -> Result<Self, ServerError> {
+				Ok(packet.$read()?)
+			}
+
+			fn write_field(&self, packet: &mut Packet) -> Result<(), ServerError> {
+				Ok(packet.$write(*self)?)
+			}
+		}
+	};
+}
+
+primitive_field!(bool, read_boolean, write_boolean);
+primitive_field!(u8, read_byte, write_byte);
+primitive_field!(i8, read_signed_byte, write_signed_byte);
+primitive_field!(u16, read_unsigned_short, write_unsigned_short);
+primitive_field!(i16, read_short, write_short);
+primitive_field!(i32, read_int, write_int);
+primitive_field!(i64, read_long, write_long);
+primitive_field!(f32, read_float, write_float);
+primitive_field!(f64, read_double, write_double);
+
+impl PacketField for VarInt {
+	fn read_field(packet: &mut Packet) -> Result<Self, ServerError> {
+		Ok(VarInt(packet.read_varint()?))
+	}
+
+	fn write_field(&self, packet: &mut Packet) -> Result<(), ServerError> {
+		Ok(packet.write_varint(self.0)?)
+	}
+}
+
+impl PacketField for VarLong {
+	fn read_field(packet: &mut Packet) -> Result<Self, ServerError> {
+		Ok(VarLong(packet.read_varlong()?))
+	}
+
+	fn write_field(&self, packet: &mut Packet) -> Result<(), ServerError> {
+		Ok(packet.write_varlong(self.0)?)
+	}
+}
+
+impl PacketField for Angle {
+	fn read_field(packet: &mut Packet) -> Result<Self, ServerError> {
+		Ok(Angle(packet.read_byte()? as f32 / 256.0 * 360.0))
+	}
+
+	fn write_field(&self, packet: &mut Packet) -> Result<(), ServerError> {
+		// сначала в i32, чтобы углы больше 180 не упирались в границы i8
+		Ok(packet.write_byte((self.0 / 360.0 * 256.0) as i32 as u8)?)
+	}
+}
+
+impl PacketField for Position {
+	fn read_field(packet: &mut Packet) -> Result<Self, ServerError> {
+		let (x, y, z) = packet.read_position()?;
+		Ok(Position { x, y, z })
+	}
+
+	fn write_field(&self, packet: &mut Packet) -> Result<(), ServerError> {
+		packet.write_position(self.x, self.y, self.z)
+	}
+}
+
+impl PacketField for String {
+	fn read_field(packet: &mut Packet) -> Result<Self, ServerError> {
+		Ok(packet.read_string()?)
+	}
+
+	fn write_field(&self, packet: &mut Packet) -> Result<(), ServerError> {
+		Ok(packet.write_string(self)?)
+	}
+}
+
+impl PacketField for Uuid {
+	fn read_field(packet: &mut Packet) -> Result<Self, ServerError> {
+		Ok(packet.read_uuid()?)
+	}
+
+	fn write_field(&self, packet: &mut Packet) -> Result<(), ServerError> {
+		Ok(packet.write_uuid(self)?)
+	}
+}
+
+impl PacketField for RemainingBytes {
+	fn read_field(packet: &mut Packet) -> Result<Self, ServerError> {
+		let mut data = Vec::new();
+		packet
+			.get_mut()
+			.read_to_end(&mut data)
+			.map_err(|_| ServerError::WrongPacket)?;
+		Ok(RemainingBytes(data))
+	}
+
+	fn write_field(&self, packet: &mut Packet) -> Result<(), ServerError> {
+		Ok(packet.write_bytes(&self.0)?)
+	}
+}
+
+// Текст-компоненты в режиме Play и Configuration передаются как NBT
+impl PacketField for TextComponent {
+	fn read_field(packet: &mut Packet) -> Result<Self, ServerError> {
+		packet.read_nbt()
+	}
+
+	fn write_field(&self, packet: &mut Packet) -> Result<(), ServerError> {
+		packet.write_nbt(self)
+	}
+}
+
+// Безымянный NBT, как в Play
+impl PacketField for DynNBT {
+	fn read_field(packet: &mut Packet) -> Result<Self, ServerError> {
+		packet.read_nbt()
+	}
+
+	fn write_field(&self, packet: &mut Packet) -> Result<(), ServerError> {
+		packet.write_nbt(self)
+	}
+}
+
+impl PacketField for ProfileProperty {
+	fn read_field(packet: &mut Packet) -> Result<Self, ServerError> {
+		Ok(ProfileProperty {
+			name: packet.read_string()?,
+			value: packet.read_string()?,
+			signature: Option::<String>::read_field(packet)?,
+		})
+	}
+
+	fn write_field(&self, packet: &mut Packet) -> Result<(), ServerError> {
+		packet.write_string(&self.name)?;
+		packet.write_string(&self.value)?;
+		self.signature.write_field(packet)
+	}
+}
+
+// Массив с префиксом длины в VarInt
+impl<T: PacketField> PacketField for Vec<T> {
+	fn read_field(packet: &mut Packet) -> Result<Self, ServerError> {
+		let count = packet.read_usize_varint()?;
+		let mut items = Vec::with_capacity(count.min(1024));
+		for _ in 0..count {
+			items.push(T::read_field(packet)?);
+		}
+		Ok(items)
+	}
+
+	fn write_field(&self, packet: &mut Packet) -> Result<(), ServerError> {
+		packet.write_usize_varint(self.len())?;
+		for item in self {
+			item.write_field(packet)?;
+		}
+		Ok(())
+	}
+}
+
+// Для рекурсивных полей
+impl<T: PacketField> PacketField for Box<T> {
+	fn read_field(packet: &mut Packet) -> Result<Self, ServerError> {
+		Ok(Box::new(T::read_field(packet)?))
+	}
+
+	fn write_field(&self, packet: &mut Packet) -> Result<(), ServerError> {
+		self.as_ref().write_field(packet)
+	}
+}
+
+// Опциональное поле с префиксом Boolean
+impl<T: PacketField> PacketField for Option<T> {
+	fn read_field(packet: &mut Packet) -> Result<Self, ServerError> {
+		if packet.read_boolean()? {
+			Ok(Some(T::read_field(packet)?))
+		} else {
+			Ok(None)
+		}
+	}
+
+	fn write_field(&self, packet: &mut Packet) -> Result<(), ServerError> {
+		packet.write_boolean(self.is_some())?;
+		if let Some(value) = self {
+			value.write_field(packet)?;
+		}
+		Ok(())
+	}
+}
+
+packet_field! {
+	/// Датапак, о котором договариваются клиент и сервер в Known Packs
+	pub struct KnownPack {
+		pub namespace: String,
+		pub id: String,
+		pub version: String,
+	}
+}
+
+packet_field! {
+	/// Запись реестра в Registry Data, без данных клиент берет их из известного пака
+	pub struct RegistryDataEntry {
+		pub id: String,
+		pub data: Option<DynNBT>,
+	}
+}
+
+packet_field! {
+	/// Теги одного реестра в Update Tags
+	pub struct RegistryTags {
+		pub registry: String,
+		pub tags: Vec<Tag>,
+	}
+}
+
+packet_field! {
+	pub struct Tag {
+		pub name: String,
+		pub entries: Vec<VarInt>,
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::protocol::packets::serverbound::status::StatusPacket;
+
+	packet! {
+		struct Everything(0x42, Play) {
+			flag: bool,
+			byte: i8,
+			short: u16,
+			int: i32,
+			long: i64,
+			float: f32,
+			double: f64,
+			varint: VarInt,
+			varlong: VarLong,
+			angle: Angle,
+			position: Position,
+			text: String,
+			uuid: Uuid,
+			missing: Option<String>,
+			present: Option<VarInt>,
+			list: Vec<KnownPack>,
+			rest: RemainingBytes,
+		}
+	}
+
+	// Пакет как его прочитает другая сторона
+	fn reread(packet: &Packet) -> Packet {
+		Packet::from_bytes(packet.id(), packet.get_bytes())
+	}
+
+	#[test]
+	fn fields_round_trip() {
+		let packet = Everything {
+			flag: true,
+			byte: -5,
+			short: 65000,
+			int: -123456,
+			long: i64::MIN,
+			float: 1.5,
+			double: -0.25,
+			varint: VarInt(-1),
+			varlong: VarLong(1 << 40),
+			angle: Angle(270.0),
+			position: Position {
+				x: -33_554_432,
+				y: -2048,
+				z: 33_554_431,
+			},
+			text: "привет".to_string(),
+			uuid: Uuid::from_u128(0x0123_4567_89ab_cdef_0123_4567_89ab_cdef),
+			missing: None,
+			present: Some(VarInt(300)),
+			list: vec![crate::world::registry::core_pack()],
+			rest: RemainingBytes(vec![1, 2, 3]),
+		};
+
+		let encoded = packet.encode().unwrap();
+		assert_eq!(encoded.id(), 0x42);
+
+		let decoded = Everything::decode(&mut reread(&encoded)).unwrap();
+		assert!(decoded.flag);
+		assert_eq!(decoded.byte, -5);
+		assert_eq!(decoded.short, 65000);
+		assert_eq!(decoded.int, -123456);
+		assert_eq!(decoded.long, i64::MIN);
+		assert_eq!(decoded.float, 1.5);
+		assert_eq!(decoded.double, -0.25);
+		assert_eq!(decoded.varint, VarInt(-1));
+		assert_eq!(decoded.varlong, VarLong(1 << 40));
+		assert_eq!(decoded.angle, Angle(270.0));
+		assert_eq!(decoded.position, packet.position);
+		assert_eq!(decoded.text, packet.text);
+		assert_eq!(decoded.uuid, packet.uuid);
+		assert_eq!(decoded.missing, None);
+		assert_eq!(decoded.present, Some(VarInt(300)));
+		assert_eq!(decoded.list, packet.list);
+		assert_eq!(decoded.rest, packet.rest);
+	}
+
+	#[test]
+	fn wire_format() {
+		packet! {
+			struct Small(0x01, Play) {
+				varint: VarInt,
+				int: i32,
+				option: Option<u8>,
+				list: Vec<u8>,
+				angle: Angle,
+			}
+		}
+
+		let packet = Small {
+			varint: VarInt(300),
+			int: 1,
+			option: None,
+			list: vec![7, 8],
+			angle: Angle(90.0),
+		};
+		assert_eq!(
+			packet.encode().unwrap().get_bytes(),
+			[0xAC, 0x02, 0, 0, 0, 1, 0, 2, 7, 8, 64]
+		);
+	}
+
+	#[test]
+	fn wrong_or_short_packets_are_errors() {
+		let mut other = Packet::from_bytes(0x43, &[]);
+		assert!(matches!(
+			Everything::decode(&mut other),
+			Err(ServerError::UnexpectedPacket(0x43))
+		));
+
+		let mut truncated = Packet::from_bytes(0x42, &[1, 2]);
+		assert!(Everything::decode(&mut truncated).is_err());
+	}
+
+	#[test]
+	fn enum_dispatches_by_id() {
+		let ping = serverbound::status::PingRequest { timestamp: 77 };
+		let mut packet = reread(&ping.encode().unwrap());
+
+		assert!(StatusPacket::IDS.contains(&packet.id()));
+		match StatusPacket::decode(&mut packet).unwrap() {
+			StatusPacket::PingRequest(ping) => assert_eq!(ping.timestamp, 77),
+			other => panic!("{other:?}"),
+		}
+
+		let mut unknown = Packet::from_bytes(0x7F, &[]);
+		assert!(StatusPacket::decode(&mut unknown).is_err());
+	}
+
+	#[test]
+	fn player_info_add_round_trip() {
+		use clientbound::play::{PlayerInfoAdd, PlayerInfoEntry};
+
+		let packet = PlayerInfoAdd {
+			players: vec![PlayerInfoEntry {
+				uuid: Uuid::from_u128(7),
+				name: "Steve".to_string(),
+				properties: vec![ProfileProperty {
+					name: "textures".to_string(),
+					value: "abc".to_string(),
+					signature: Some("sig".to_string()),
+				}],
+			}],
+		};
+
+		let encoded = packet.encode().unwrap();
+		assert_eq!(encoded.get_bytes()[..2], [0x01, 1]); // маска Add Player и один игрок
+
+		let decoded = PlayerInfoAdd::decode(&mut reread(&encoded)).unwrap();
+		assert_eq!(decoded.players.len(), 1);
+		assert_eq!(decoded.players[0].uuid, Uuid::from_u128(7));
+		assert_eq!(decoded.players[0].name, "Steve");
+		assert_eq!(decoded.players[0].properties, packet.players[0].properties);
+
+		// Другие действия пакет не понимает
+		let mut other = Packet::from_bytes(encoded.id(), &[0x02, 0]);
+		assert!(PlayerInfoAdd::decode(&mut other).is_err());
+	}
+}
@@ -0,0 +1,327 @@
+// Пакеты которые отправляет клиент
+// Айдишники берутся из packet_id, структура модулей такая же
+
+pub mod handshake {
+	use crate::protocol::{packet_id::serverbound::handshake::*, packets::VarInt};
+
+	packet! {
+		pub struct Handshake(HANDSHAKE, Handshake) {
+			pub protocol_version: VarInt, // может быть отрицательным если наш клиент дэбил
+			pub server_address: String, // домен/адрес сервера, например "play.example.com", а не айпи
+			pub server_port: u16,
+			pub next_state: VarInt, // 1 для получения статуса и пинга, 2 и 3 для обычного подключения
+		}
+	}
+}
+
+pub mod status {
+	use crate::protocol::packet_id::serverbound::status::*;
+
+	packet! {
+		pub struct StatusRequest(REQUEST, Status) {}
+	}
+
+	packet! {
+		pub struct PingRequest(PING_REQUEST, Status) {
+			pub timestamp: i64,
+		}
+	}
+
+	packet_enum! {
+		pub enum StatusPacket {
+			StatusRequest(StatusRequest),
+			PingRequest(PingRequest),
+		}
+	}
+}
+
+pub mod login {
+	use uuid::Uuid;
+
+	use crate::protocol::{
+		packet_id::serverbound::login::*,
+		packets::{RemainingBytes, VarInt},
+	};
+
+	packet! {
+		pub struct LoginStart(START, Login) {
+			pub name: String,
+			pub uuid: Uuid, // uuid который прислал клиент, верить ему нельзя
+		}
+	}
+
+	packet! {
+		pub struct EncryptionResponse(ENCRYPTION_RESPONSE, Login) {
+			pub shared_secret: Vec<u8>, // зашифрован публичным ключом сервера
+			pub verify_token: Vec<u8>, // тоже
+		}
+	}
+
+	packet! {
+		pub struct PluginResponse(PLUGIN_RESPONSE, Login) {
+			pub message_id: VarInt,
+			pub data: Option<RemainingBytes>,
+		}
+	}
+
+	packet! {
+		pub struct LoginAcknowledged(ACKNOWLEDGED, Login) {}
+	}
+
+	packet! {
+		pub struct CookieResponse(COOKIE_RESPONSE, Login) {
+			pub key: String,
+			pub payload: Option<Vec<u8>>,
+		}
+	}
+}
+
+pub mod configuration {
+	use crate::protocol::{
+		packet_id::serverbound::configuration::*,
+		packets::{KnownPack, RemainingBytes, VarInt},
+	};
+
+	packet! {
+		pub struct ClientInformation(CLIENT_INFORMATION, Configuration) {
+			pub locale: String, // for example: en_us
+			pub view_distance: i8, // client-side render distance in chunks
+			pub chat_mode: VarInt, // 0: enabled, 1: commands only, 2: hidden
+			pub chat_colors: bool, // this settings does nothing on client but can be used on serverside
+			pub displayed_skin_parts: u8, // bit mask https://minecraft.wiki/w/Java_Edition_protocol#Client_Information_(configuration)
+			pub main_hand: VarInt, // 0 for left and 1 for right
+			pub enable_text_filtering: bool, // filtering text for profanity, always false for offline mode
+			pub allow_server_listings: bool, // allows showing player in server listings in status
+			pub particle_status: VarInt, // 0 for all, 1 for decreased, 2 for minimal
+		}
+	}
+
+	packet! {
+		pub struct CookieResponse(COOKIE_RESPONSE, Configuration) {
+			pub key: String,
+			pub payload: Option<Vec<u8>>,
+		}
+	}
+
+	packet! {
+		pub struct PluginMessage(PLUGIN_MESSAGE, Configuration) {
+			pub channel: String,
+			pub data: RemainingBytes,
+		}
+	}
+
+	packet! {
+		pub struct AcknowledgeFinishConfiguration(ACKNOWLEDGE_FINISH, Configuration) {}
+	}
+
+	packet! {
+		pub struct KeepAlive(KEEP_ALIVE, Configuration) {
+			pub id: i64,
+		}
+	}
+
+	packet! {
+		pub struct Pong(PONG, Configuration) {
+			pub id: i32,
+		}
+	}
+
+	packet! {
+		pub struct KnownPacks(KNOWN_PACKS, Configuration) {
+			pub packs: Vec<KnownPack>,
+		}
+	}
+}
+
+pub mod play {
+	use crate::{
+		data::slot::{HashedSlot, Slot},
+		protocol::{
+			packet_id::serverbound::play::*,
+			packets::{Position, RemainingBytes, VarInt},
+		},
+	};
+
+	packet_field! {
+		/// Слот, который изменился у клиента после клика
+		pub struct ChangedSlot {
+			pub slot: i16,
+			pub item: HashedSlot,
+		}
+	}
+
+	packet! {
+		pub struct ChatCommand(CHAT_COMMAND, Play) {
+			pub command: String,
+		}
+	}
+
+	packet! {
+		pub struct SignedChatCommand(SIGNED_CHAT_COMMAND, Play) {
+			pub command: String,
+			pub signed_data: RemainingBytes, // таймстамп, соль, подписи аргументов и т.д.
+		}
+	}
+
+	packet! {
+		pub struct CommandSuggestionsRequest(COMMAND_SUGGESTIONS_REQUEST, Play) {
+			pub id: VarInt,
+			pub text: String, // вместе с начальным /
+		}
+	}
+
+	packet! {
+		pub struct ChatMessage(CHAT_MESSAGE, Play) {
+			pub message: String,
+			pub signed_data: RemainingBytes, // skip remaining data coz they suck
+		}
+	}
+
+	packet! {
+		pub struct AcknowledgeConfiguration(ACKNOWLEDGE_CONFIGURATION, Play) {}
+	}
+
+	packet! {
+		pub struct ClickContainer(CLICK_CONTAINER, Play) {
+			pub window_id: VarInt,
+			pub state_id: VarInt,
+			pub slot: i16,
+			pub button: i8,
+			pub mode: VarInt,
+			pub changed_slots: Vec<ChangedSlot>,
+			pub carried_item: HashedSlot,
+		}
+	}
+
+	packet! {
+		pub struct CloseContainer(CLOSE_CONTAINER, Play) {
+			pub window_id: VarInt,
+		}
+	}
+
+	packet! {
+		pub struct CookieResponse(COOKIE_RESPONSE, Play) {
+			pub key: String,
+			pub payload: Option<Vec<u8>>,
+		}
+	}
+
+	packet! {
+		pub struct PluginMessage(PLUGIN_MESSAGE, Play) {
+			pub channel: String,
+			pub data: RemainingBytes,
+		}
+	}
+
+	packet! {
+		pub struct KeepAlive(KEEP_ALIVE, Play) {
+			pub id: i64,
+		}
+	}
+
+	packet! {
+		pub struct SetPlayerPosition(SET_PLAYER_POSITION, Play) {
+			pub x: f64,
+			pub y: f64,
+			pub z: f64,
+			pub flags: u8, // 0x01 - on ground, 0x02 - pushing against wall
+		}
+	}
+
+	packet! {
+		pub struct SetPlayerPositionAndRotation(SET_PLAYER_POSITION_AND_ROTATION, Play) {
+			pub x: f64,
+			pub y: f64,
+			pub z: f64,
+			pub yaw: f32,
+			pub pitch: f32,
+			pub flags: u8,
+		}
+	}
+
+	packet! {
+		pub struct SetPlayerRotation(SET_PLAYER_ROTATION, Play) {
+			pub yaw: f32,
+			pub pitch: f32,
+			pub flags: u8,
+		}
+	}
+
+	packet! {
+		pub struct Pong(PONG, Play) {
+			pub id: i32,
+		}
+	}
+
+	packet! {
+		pub struct PlayerCommand(PLAYER_COMMAND, Play) {
+			pub entity_id: VarInt,
+			pub action: VarInt, // 0 - press sneak key, 1 - release sneak key и т.д.
+			pub jump_boost: VarInt, // jump boost on horse
+		}
+	}
+
+	packet! {
+		pub struct SwingArm(SWING_ARM, Play) {
+			pub hand: VarInt, // 0 - main, 1 - off
+		}
+	}
+
+	packet! {
+		pub struct PlayerAction(PLAYER_ACTION, Play) {
+			pub status: VarInt, // 0 - начал копать, 1 - отменил, 2 - докопал, 3..6 - дроп, еда, смена рук
+			pub location: Position,
+			pub face: i8,
+			pub sequence: VarInt,
+		}
+	}
+
+	packet! {
+		pub struct UseItemOn(USE_ITEM_ON, Play) {
+			pub hand: VarInt, // 0 - main, 1 - off
+			pub location: Position,
+			pub face: VarInt, // 0 -Y, 1 +Y, 2 -Z, 3 +Z, 4 -X, 5 +X
+			pub cursor_x: f32,
+			pub cursor_y: f32,
+			pub cursor_z: f32,
+			pub inside_block: bool,
+			pub world_border_hit: bool,
+			pub sequence: VarInt,
+		}
+	}
+
+	packet! {
+		pub struct SetHeldItem(SET_HELD_ITEM, Play) {
+			pub slot: i16, // 0..9
+		}
+	}
+
+	packet! {
+		pub struct SetCreativeModeSlot(SET_CREATIVE_MODE_SLOT, Play) {
+			pub slot: i16,
+			pub item: Slot,
+		}
+	}
+
+	packet_enum! {
+		/// Пакеты, которые разбирает тик игрока из handle_play_state
+		pub enum PlayPacket {
+			SetPlayerPosition(SetPlayerPosition),
+			SetPlayerPositionAndRotation(SetPlayerPositionAndRotation),
+			SetPlayerRotation(SetPlayerRotation),
+			ChatMessage(ChatMessage),
+			ClickContainer(ClickContainer),
+			CloseContainer(CloseContainer),
+			ChatCommand(ChatCommand),
+			SignedChatCommand(SignedChatCommand),
+			CommandSuggestionsRequest(CommandSuggestionsRequest),
+			PlayerCommand(PlayerCommand),
+			SwingArm(SwingArm),
+			PlayerAction(PlayerAction),
+			UseItemOn(UseItemOn),
+			SetHeldItem(SetHeldItem),
+			SetCreativeModeSlot(SetCreativeModeSlot),
+			KeepAlive(KeepAlive),
+		}
+	}
+}
@@ -0,0 +1,200 @@
+use std::net::IpAddr;
+
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use crate::ServerError;
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct ProfileProperty {
+	pub name: String,
+	pub value: String,
+	pub signature: Option<String>,
+}
+
+// Профиль игрока, который возвращает сессионный сервер
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct GameProfile {
+	pub id: Uuid,
+	pub name: String,
+	#[serde(default)]
+	pub properties: Vec<ProfileProperty>,
+}
+
+// Проверка того, что игрок действительно заходил на сервер через свой лаунчер
+// По умолчанию используется MojangSessionVerifier, но можно подсунуть свой
+// (например для тестов с локальным сессионным сервером) через ServerContext::set_session_verifier
+pub trait SessionVerifier: Sync + Send {
+	/// Возвращает None если игрок не авторизован
+	fn verify(
+		&self,
+		name: &str,
+		server_hash: &str,
+		ip: Option<IpAddr>,
+	) -> Result<Option<GameProfile>, ServerError>;
+}
+
+// Проверка через HTTP API сессионного сервера в формате Mojang
+pub struct MojangSessionVerifier {
+	url: String,
+}
+
+impl MojangSessionVerifier {
+	/// `url` - базовый адрес сессионного сервера, например `https://sessionserver.mojang.com`
+	pub fn new(url: &str) -> Self {
+		Self {
+			url: url.trim_end_matches('/').to_string(),
+		}
+	}
+}
+
+impl SessionVerifier for MojangSessionVerifier {
+	fn verify(
+		&self,
+		name: &str,
+		server_hash: &str,
+		ip: Option<IpAddr>,
+	) -> Result<Option<GameProfile>, ServerError> {
+		let mut request = ureq::get(&format!("{}/session/minecraft/hasJoined", self.url))
+			.query("username", name)
+			.query("serverId", server_hash);
+
+		if let Some(ip) = ip {
+			request = request.query("ip", &ip.to_string());
+		}
+
+		let response = request
+			.call()
+			.map_err(|e| ServerError::SessionServer(e.to_string()))?;
+
+		// 204 No Content - игрок не авторизован
+		if response.status() == 204 {
+			return Ok(None);
+		}
+
+		let profile = response
+			.into_json::<GameProfile>()
+			.map_err(|e| ServerError::SessionServer(e.to_string()))?;
+
+		Ok(Some(profile))
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use std::{
+		io::{BufRead, BufReader, Write},
+		net::{Ipv4Addr, TcpListener},
+		thread::{self, JoinHandle},
+	};
+
+	use rsa::{Pkcs1v15Encrypt, RsaPublicKey, pkcs8::DecodePublicKey};
+
+	use super::*;
+	use crate::protocol::encryption::{ServerKeys, server_hash};
+
+	// Сессионный сервер на случайном порту, профиль отдает только на ожидаемый serverId
+	// Возвращает адрес и поток, который после requests запросов отдаст их строки запроса
+	fn mock_session_server(
+		server_id: String,
+		profile: GameProfile,
+		requests: usize,
+	) -> (String, JoinHandle<Vec<String>>) {
+		let listener = TcpListener::bind((Ipv4Addr::LOCALHOST, 0)).unwrap();
+		let url = format!("http://{}/", listener.local_addr().unwrap());
+
+		let handle = thread::spawn(move || {
+			let mut lines = Vec::new();
+			for stream in listener.incoming().take(requests) {
+				let mut stream = stream.unwrap();
+				let mut reader = BufReader::new(stream.try_clone().unwrap());
+
+				let mut line = String::new();
+				reader.read_line(&mut line).unwrap();
+				let mut header = String::new();
+				while reader.read_line(&mut header).unwrap() > 2 {
+					header.clear();
+				}
+
+				let target = line.split(' ').nth(1).unwrap_or_default();
+				let (path, query) = target.split_once('?').unwrap_or((target, ""));
+				let joined = path == "/session/minecraft/hasJoined"
+					&& query.contains(&format!("username={}", profile.name))
+					&& query.contains(&format!("serverId={server_id}"));
+
+				let response = match joined {
+					true => {
+						let body = serde_json::to_string(&profile).unwrap();
+						format!(
+							"HTTP/1.1 200 OK\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{body}",
+							body.len()
+						)
+					}
+					false => "HTTP/1.1 204 No Content\r\nConnection: close\r\n\r\n".to_string(),
+				};
+				stream.write_all(response.as_bytes()).unwrap();
+				lines.push(line.trim_end().to_string());
+			}
+			lines
+		});
+
+		(url, handle)
+	}
+
+	#[test]
+	fn verifies_player_after_key_exchange() {
+		let keys = ServerKeys::generate().unwrap();
+
+		// Сторона клиента: шифрует общий секрет публичным ключом сервера и считает хеш сам
+		let shared_secret: [u8; 16] = rand::random();
+		let public_key = RsaPublicKey::from_public_key_der(keys.public_key_der()).unwrap();
+		let encrypted = public_key
+			.encrypt(&mut rand::thread_rng(), Pkcs1v15Encrypt, &shared_secret)
+			.unwrap();
+		let client_hash = server_hash("", &shared_secret, keys.public_key_der());
+
+		let profile = GameProfile {
+			id: Uuid::from_u128(rand::random()),
+			name: "Steve".to_string(),
+			properties: vec![ProfileProperty {
+				name: "textures".to_string(),
+				value: "e30=".to_string(),
+				signature: Some("c2lnbg==".to_string()),
+			}],
+		};
+		let (url, server) = mock_session_server(client_hash, profile.clone(), 2);
+		let verifier = MojangSessionVerifier::new(&url);
+
+		// Сторона сервера
+		let decrypted = keys.decrypt(&encrypted).unwrap();
+		assert_eq!(decrypted, shared_secret);
+		let hash = server_hash("", &decrypted, keys.public_key_der());
+		let ip = Some(IpAddr::from(Ipv4Addr::new(10, 0, 0, 1)));
+
+		let verified = verifier.verify("Steve", &hash, ip).unwrap().unwrap();
+		assert_eq!(verified.id, profile.id);
+		assert_eq!(verified.properties, profile.properties);
+
+		// С другим секретом хеш не сойдется, игрок не заходил на этот сервер
+		let wrong = server_hash("", &[0; 16], keys.public_key_der());
+		assert!(verifier.verify("Steve", &wrong, None).unwrap().is_none());
+
+		let lines = server.join().unwrap();
+		assert!(lines[0].contains("ip=10.0.0.1"), "{}", lines[0]);
+		assert!(!lines[1].contains("ip="), "{}", lines[1]);
+	}
+
+	#[test]
+	fn unreachable_session_server_is_an_error() {
+		let port = TcpListener::bind((Ipv4Addr::LOCALHOST, 0))
+			.unwrap()
+			.local_addr()
+			.unwrap()
+			.port();
+		let verifier = MojangSessionVerifier::new(&format!("http://127.0.0.1:{port}"));
+		assert!(matches!(
+			verifier.verify("Steve", "hash", None),
+			Err(ServerError::SessionServer(_))
+		));
+	}
+}
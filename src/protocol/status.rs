@@ -0,0 +1,275 @@
+use std::{fs, path::Path, sync::Arc};
+
+use base64::{Engine, prelude::BASE64_STANDARD};
+use log::warn;
+use rand::seq::SliceRandom;
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use super::PROTOCOL_VERSION;
+use crate::{ServerError, context::ServerContext, data::component::TextComponent};
+
+// Ответ на Status Request, то что клиент показывает в списке серверов
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StatusVersion {
+	pub name: String,
+	pub protocol: i32, // если не совпадает с версией клиента, он пишет что сервер устарел
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StatusPlayer {
+	pub name: String,
+	pub id: Uuid,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StatusPlayers {
+	pub max: i32,
+	pub online: i32,
+	#[serde(default, skip_serializing_if = "Vec::is_empty")]
+	pub sample: Vec<StatusPlayer>, // показывается при наведении на онлайн
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct StatusResponse {
+	pub version: StatusVersion,
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	pub players: Option<StatusPlayers>, // None - вместо онлайна "???"
+	pub description: TextComponent,
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	pub favicon: Option<String>, // data:image/png;base64,...
+	#[serde(default)]
+	pub enforces_secure_chat: bool,
+}
+
+impl StatusResponse {
+	/// Статус из конфига с реальным онлайном
+	pub fn new(server: &Arc<ServerContext>) -> StatusResponse {
+		let config = &server.config.status;
+
+		let players = (!config.hide_players).then(|| {
+			let players = server.players();
+
+			// Игроки, которые запретили показывать себя в списке, в выборку не попадают
+			let mut sample: Vec<StatusPlayer> = players
+				.iter()
+				.filter(|o| o.client_info().is_none_or(|o| o.allow_server_listings))
+				.filter_map(|o| o.player_info())
+				.map(|o| StatusPlayer {
+					name: o.name,
+					id: o.uuid,
+				})
+				.collect();
+			sample.shuffle(&mut rand::thread_rng());
+			sample.truncate(config.sample_size);
+
+			StatusPlayers {
+				max: config.max_players,
+				online: players.len() as i32,
+				sample,
+			}
+		});
+
+		StatusResponse {
+			version: StatusVersion {
+				name: config.version_name.clone(),
+				protocol: PROTOCOL_VERSION,
+			},
+			players,
+			description: TextComponent::from_mini(&config.motd),
+			favicon: server.favicon().map(|o| o.to_string()),
+			enforces_secure_chat: false,
+		}
+	}
+
+	pub fn as_json(&self) -> Result<String, ServerError> {
+		serde_json::to_string(self).map_err(|_| ServerError::SerTextComponent)
+	}
+}
+
+/// Читает png и делает из него data url для поля favicon, None если файла нет или это не png
+pub fn load_favicon(path: impl AsRef<Path>) -> Option<String> {
+	let path = path.as_ref();
+	let data = fs::read(path).ok()?;
+
+	if !data.starts_with(b"\x89PNG\r\n\x1a\n") || data.len() < 24 {
+		warn!("Иконка сервера {} не является png", path.display());
+		return None;
+	}
+
+	// Ширина и высота лежат в IHDR сразу после сигнатуры
+	let width = u32::from_be_bytes([data[16], data[17], data[18], data[19]]);
+	let height = u32::from_be_bytes([data[20], data[21], data[22], data[23]]);
+	if (width, height) != (64, 64) {
+		warn!(
+			"Иконка сервера {} размером {width}x{height}, клиент ждет 64x64",
+			path.display()
+		);
+	}
+
+	Some(favicon_url(&data))
+}
+
+/// Data url из содержимого png
+pub fn favicon_url(png: &[u8]) -> String {
+	format!("data:image/png;base64,{}", BASE64_STANDARD.encode(png))
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::{
+		config::Config,
+		player::context::{ClientContext, ClientInfo, test_player},
+	};
+
+	fn server(config: Config) -> Arc<ServerContext> {
+		Arc::new(ServerContext::new(Arc::new(config)))
+	}
+
+	fn hidden(client: &Arc<ClientContext>) {
+		client.set_client_info(ClientInfo {
+			brand: "vanilla".to_string(),
+			locale: "en_us".to_string(),
+			view_distance: 2,
+			chat_mode: 0,
+			chat_colors: true,
+			displayed_skin_parts: 0,
+			main_hand: 1,
+			enable_text_filtering: false,
+			allow_server_listings: false,
+			particle_status: 0,
+		});
+	}
+
+	// Начало png: сигнатура и IHDR с размерами
+	fn png(width: u32, height: u32) -> Vec<u8> {
+		let mut data = b"\x89PNG\r\n\x1a\n\0\0\0\x0dIHDR".to_vec();
+		data.extend(width.to_be_bytes());
+		data.extend(height.to_be_bytes());
+		data
+	}
+
+	fn temp_file(name: &str, data: &[u8]) -> std::path::PathBuf {
+		let path =
+			std::env::temp_dir().join(format!("rust_mc_serv_status_{}_{name}", std::process::id()));
+		fs::write(&path, data).unwrap();
+		path
+	}
+
+	#[test]
+	fn built_from_config() {
+		let mut config = Config::default();
+		config.status.motd = "<red>Hello".to_string();
+		config.status.version_name = "Test 1.21.5".to_string();
+		config.status.max_players = 100;
+		config.status.favicon = "missing-icon.png".to_string();
+		let server = server(config);
+
+		let status = StatusResponse::new(&server);
+		assert_eq!(status.version.name, "Test 1.21.5");
+		assert_eq!(status.version.protocol, PROTOCOL_VERSION);
+		assert_eq!(status.description, TextComponent::from_mini("<red>Hello"));
+		assert!(status.favicon.is_none());
+
+		let players = status.players.unwrap();
+		assert_eq!((players.max, players.online), (100, 0));
+		assert!(players.sample.is_empty());
+	}
+
+	#[test]
+	fn sample_has_real_players() {
+		let server = server(Config::default());
+		let steve = test_player(&server, "Steve", 0);
+		let alex = test_player(&server, "Alex", 0);
+		hidden(&alex); // запретил показывать себя в списке
+
+		let players = StatusResponse::new(&server).players.unwrap();
+		assert_eq!(players.online, 2);
+		assert_eq!(players.sample.len(), 1);
+		assert_eq!(players.sample[0].name, "Steve");
+		assert_eq!(players.sample[0].id, steve.player_info().unwrap().uuid);
+	}
+
+	#[test]
+	fn sample_size_and_hidden_players() {
+		let mut config = Config::default();
+		config.status.sample_size = 2;
+		let crowded = server(config);
+		for name in ["Steve", "Alex", "Notch", "Jeb_"] {
+			test_player(&crowded, name, 0);
+		}
+
+		let players = StatusResponse::new(&crowded).players.unwrap();
+		assert_eq!(players.online, 4);
+		assert_eq!(players.sample.len(), 2);
+
+		let mut config = Config::default();
+		config.status.hide_players = true;
+		let server = server(config);
+		test_player(&server, "Steve", 0);
+
+		let status = StatusResponse::new(&server);
+		assert!(status.players.is_none());
+		assert!(!status.as_json().unwrap().contains("\"players\""));
+	}
+
+	#[test]
+	fn json_fields() {
+		let server = server(Config::default());
+		test_player(&server, "Steve", 0);
+
+		let json: serde_json::Value =
+			serde_json::from_str(&StatusResponse::new(&server).as_json().unwrap()).unwrap();
+		assert_eq!(json["version"]["protocol"], PROTOCOL_VERSION);
+		assert_eq!(json["players"]["max"], 20);
+		assert_eq!(json["players"]["online"], 1);
+		assert_eq!(json["players"]["sample"][0]["name"], "Steve");
+		assert_eq!(json["enforcesSecureChat"], false);
+		assert!(json.get("favicon").is_none());
+		assert!(json.get("description").is_some());
+	}
+
+	#[test]
+	fn favicon_from_png() {
+		let icon = png(64, 64);
+		let path = temp_file("icon.png", &icon);
+		assert_eq!(load_favicon(&path), Some(favicon_url(&icon)));
+		assert!(favicon_url(&icon).starts_with("data:image/png;base64,iVBORw0KGgo"));
+
+		// Неверный размер только пишется в лог
+		let large = png(128, 128);
+		assert_eq!(
+			load_favicon(temp_file("large.png", &large)),
+			Some(favicon_url(&large))
+		);
+
+		assert_eq!(
+			load_favicon(temp_file("icon.txt", b"not a png at all, really")),
+			None
+		);
+		assert_eq!(load_favicon(temp_file("short.png", &icon[..20])), None);
+		assert_eq!(load_favicon(path.with_extension("missing")), None);
+
+		let mut config = Config::default();
+		config.status.favicon = path.to_str().unwrap().to_string();
+		let server = server(config);
+		assert_eq!(
+			StatusResponse::new(&server).favicon,
+			Some(favicon_url(&icon))
+		);
+	}
+
+	#[test]
+	fn favicon_can_be_replaced() {
+		let mut server = ServerContext::new(Arc::new(Config::default()));
+		server.set_favicon(&png(64, 64));
+		let server = Arc::new(server);
+		assert_eq!(
+			StatusResponse::new(&server).favicon.as_deref(),
+			Some(favicon_url(&png(64, 64)).as_str())
+		);
+	}
+}
@@ -0,0 +1,390 @@
+use std::{
+	collections::{BTreeMap, HashMap},
+	fs,
+	path::Path,
+};
+
+use serde::Deserialize;
+
+use super::chunk::BlockState;
+use crate::ServerError;
+
+// Айдишник в глобальной палитре, который уходит клиенту в чанках и Block Update
+pub type BlockStateId = i32;
+
+// Размер индекса в прямой (direct) палитре, ceil(log2(кол-во стейтов блоков в 1.21.5))
+pub const DIRECT_BITS: usize = 15;
+
+// Встроенные айдишники самых частых блоков 1.21.5,
+// чтобы генераторы работали даже без полного репорта
+type BuiltinState = (
+	&'static str,
+	&'static [(&'static str, &'static str)],
+	BlockStateId,
+);
+
+const BUILTIN_STATES: &[BuiltinState] = &[
+	("minecraft:air", &[], 0),
+	("minecraft:stone", &[], 1),
+	("minecraft:granite", &[], 2),
+	("minecraft:polished_granite", &[], 3),
+	("minecraft:diorite", &[], 4),
+	("minecraft:polished_diorite", &[], 5),
+	("minecraft:andesite", &[], 6),
+	("minecraft:polished_andesite", &[], 7),
+	("minecraft:grass_block", &[("snowy", "true")], 8),
+	("minecraft:grass_block", &[("snowy", "false")], 9),
+	("minecraft:dirt", &[], 10),
+	("minecraft:coarse_dirt", &[], 11),
+	("minecraft:podzol", &[("snowy", "true")], 12),
+	("minecraft:podzol", &[("snowy", "false")], 13),
+	("minecraft:cobblestone", &[], 14),
+	("minecraft:oak_planks", &[], 15),
+	("minecraft:bedrock", &[], 85),
+	("minecraft:water", &[("level", "0")], 86),
+	("minecraft:sand", &[], 118),
+];
+
+// Репорт blocks.json из ванильного генератора данных:
+// java -DbundlerMainClass=net.minecraft.data.Main -jar server.jar --reports
+#[derive(Deserialize)]
+struct ReportBlock {
+	#[serde(default)]
+	states: Vec<ReportState>,
+}
+
+#[derive(Deserialize)]
+struct ReportState {
+	id: BlockStateId,
+	#[serde(default)]
+	default: bool,
+	#[serde(default)]
+	properties: BTreeMap<String, String>,
+}
+
+// Соответствие BlockState <-> айди в глобальной палитре
+pub struct BlockRegistry {
+	ids: HashMap<BlockState, BlockStateId>,
+	states: HashMap<BlockStateId, BlockState>,
+	defaults: HashMap<String, BlockStateId>, // дефолтный стейт для блока без свойств
+}
+
+impl Default for BlockRegistry {
+	fn default() -> Self {
+		Self::new()
+	}
+}
+
+impl BlockRegistry {
+	/// Реестр только со встроенными блоками
+	pub fn new() -> BlockRegistry {
+		let mut registry = BlockRegistry {
+			ids: HashMap::new(),
+			states: HashMap::new(),
+			defaults: HashMap::new(),
+		};
+
+		for (name, properties, id) in BUILTIN_STATES {
+			let mut state = BlockState::new(name);
+			for (key, value) in properties.iter() {
+				state = state.with_property(key, value);
+			}
+			registry.defaults.entry(name.to_string()).or_insert(*id);
+			registry.register(state, *id);
+		}
+
+		// У травы дефолтный стейт snowy=false
+		registry
+			.defaults
+			.insert("minecraft:grass_block".to_string(), 9);
+		registry.defaults.insert("minecraft:podzol".to_string(), 13);
+
+		registry
+	}
+
+	/// Реестр из ванильного репорта blocks.json, встроенные блоки им перезаписываются
+	pub fn load_report(path: impl AsRef<Path>) -> Result<BlockRegistry, ServerError> {
+		let content = fs::read_to_string(path).map_err(|e| ServerError::Storage(e.to_string()))?;
+		let report: HashMap<String, ReportBlock> =
+			serde_json::from_str(&content).map_err(|e| ServerError::Storage(e.to_string()))?;
+
+		let mut registry = BlockRegistry::new();
+
+		for (name, block) in report {
+			for state in block.states {
+				if state.default || state.properties.is_empty() {
+					registry.defaults.insert(name.clone(), state.id);
+				}
+				registry.register(
+					BlockState {
+						name: name.clone(),
+						properties: state.properties,
+					},
+					state.id,
+				);
+			}
+		}
+
+		Ok(registry)
+	}
+
+	pub fn register(&mut self, state: BlockState, id: BlockStateId) {
+		self.states.insert(id, state.clone());
+		self.ids.insert(state, id);
+	}
+
+	/// Айди стейта, если такого стейта нет - айди дефолтного стейта этого блока
+	pub fn id(&self, state: &BlockState) -> Option<BlockStateId> {
+		self
+			.ids
+			.get(state)
+			.or_else(|| self.defaults.get(&state.name))
+			.copied()
+	}
+
+	pub fn state(&self, id: BlockStateId) -> Option<&BlockState> {
+		self.states.get(&id)
+	}
+
+	/// Дефолтный стейт блока со всеми свойствами, None если блок неизвестен
+	pub fn default_state(&self, name: &str) -> Option<&BlockState> {
+		self.defaults.get(name).and_then(|o| self.states.get(o))
+	}
+}
+
+// Прочность блоков, которых нет в таблице: как у обсидиана, чтобы неизвестное нельзя было сломать мгновенно
+pub const UNKNOWN_HARDNESS: f32 = 50.0;
+
+/// Прочность блока из ванильных данных, 0 - ломается сразу, меньше нуля - не ломается в выживании
+///
+/// Неизвестные блоки ломаются медленно, см. UNKNOWN_HARDNESS
+pub fn hardness(state: &BlockState) -> f32 {
+	let name = state.name.trim_start_matches("minecraft:");
+
+	match name {
+		"bedrock"
+		| "barrier"
+		| "light"
+		| "end_portal"
+		| "end_portal_frame"
+		| "end_gateway"
+		| "nether_portal"
+		| "command_block"
+		| "chain_command_block"
+		| "repeating_command_block"
+		| "structure_block"
+		| "jigsaw"
+		| "reinforced_deepslate"
+		| "water"
+		| "lava"
+		| "bubble_column" => return -1.0,
+		"obsidian" | "crying_obsidian" | "respawn_anchor" => return 50.0,
+		"ancient_debris" | "netherite_block" => return 50.0,
+		"ender_chest" => return 22.5,
+		"cobweb" => return 4.0,
+		"chain" | "bell" | "coal_block" => return 5.0,
+		"lantern" | "soul_lantern" => return 3.5,
+		"copper_block" | "lapis_block" | "hopper" | "end_stone_bricks" => return 3.0,
+		"lectern" => return 2.5,
+		"nether_bricks" | "red_nether_bricks" | "bone_block" => return 2.0,
+		"prismarine" | "prismarine_bricks" | "dark_prismarine" | "purpur_block" | "purpur_pillar"
+		| "amethyst_block" | "dripstone_block" | "pointed_dripstone" | "mud_bricks" | "tuff_bricks"
+		| "piston" | "sticky_piston" => return 1.5,
+		"packed_mud" => return 1.0,
+		"rail" | "powered_rail" | "detector_rail" | "activator_rail" => return 0.7,
+		"composter" | "beehive" => return 0.6,
+		"dried_kelp_block" | "magma_block" | "target" | "lever" => return 0.5,
+		"bee_nest" => return 0.3,
+		"sculk" => return 0.2,
+		"moss_block" | "moss_carpet" => return 0.1,
+		"anvil" | "chipped_anvil" | "damaged_anvil" | "enchanting_table" => return 5.0,
+		"iron_block" | "diamond_block" | "emerald_block" | "gold_block" | "spawner" | "iron_door"
+		| "iron_trapdoor" | "iron_bars" | "redstone_block" => return 5.0,
+		"deepslate" | "cobbled_deepslate" | "polished_deepslate" | "deepslate_bricks"
+		| "deepslate_tiles" | "chiseled_deepslate" | "furnace" | "dispenser" | "dropper"
+		| "observer" => return 3.5,
+		"cobblestone" | "mossy_cobblestone" | "bricks" | "cauldron" | "crafting_table" | "chest"
+		| "trapped_chest" | "barrel" | "bookshelf" | "jukebox" => return 2.5,
+		"stone"
+		| "granite"
+		| "polished_granite"
+		| "diorite"
+		| "polished_diorite"
+		| "andesite"
+		| "polished_andesite"
+		| "stone_bricks"
+		| "mossy_stone_bricks"
+		| "cracked_stone_bricks"
+		| "chiseled_stone_bricks"
+		| "smooth_stone"
+		| "tuff"
+		| "blackstone"
+		| "basalt"
+		| "polished_basalt" => return 1.5,
+		"end_stone" => return 3.0,
+		"sandstone" | "red_sandstone" | "quartz_block" | "note_block" | "calcite" => return 0.8,
+		"grass_block" | "mycelium" | "podzol" | "gravel" | "clay" | "farmland" | "dirt_path"
+		| "sponge" | "wet_sponge" => return 0.6,
+		"dirt" | "coarse_dirt" | "rooted_dirt" | "sand" | "red_sand" | "soul_sand" | "soul_soil"
+		| "ice" | "packed_ice" | "mud" | "hay_block" | "cake" => return 0.5,
+		"netherrack" | "crimson_nylium" | "warped_nylium" => return 0.4,
+		"glass" | "glass_pane" | "glowstone" | "sea_lantern" | "redstone_lamp" | "beacon" => {
+			return 0.3;
+		}
+		"snow_block" | "cactus" | "ladder" => return 0.4,
+		"snow" | "vine" | "cocoa" => return 0.2,
+		"pumpkin" | "carved_pumpkin" | "jack_o_lantern" | "melon" => return 1.0,
+		"mushroom_stem" | "brown_mushroom_block" | "red_mushroom_block" => return 0.2,
+		"air"
+		| "cave_air"
+		| "void_air"
+		| "short_grass"
+		| "tall_grass"
+		| "fern"
+		| "large_fern"
+		| "dead_bush"
+		| "dandelion"
+		| "poppy"
+		| "blue_orchid"
+		| "allium"
+		| "azure_bluet"
+		| "oxeye_daisy"
+		| "cornflower"
+		| "lily_of_the_valley"
+		| "wither_rose"
+		| "sunflower"
+		| "lilac"
+		| "rose_bush"
+		| "peony"
+		| "torchflower"
+		| "pink_petals"
+		| "sugar_cane"
+		| "wheat"
+		| "carrots"
+		| "potatoes"
+		| "beetroots"
+		| "nether_wart"
+		| "sweet_berry_bush"
+		| "brown_mushroom"
+		| "red_mushroom"
+		| "crimson_fungus"
+		| "warped_fungus"
+		| "crimson_roots"
+		| "warped_roots"
+		| "nether_sprouts"
+		| "lily_pad"
+		| "seagrass"
+		| "tall_seagrass"
+		| "kelp"
+		| "kelp_plant"
+		| "torch"
+		| "wall_torch"
+		| "soul_torch"
+		| "soul_wall_torch"
+		| "redstone_torch"
+		| "redstone_wall_torch"
+		| "redstone_wire"
+		| "repeater"
+		| "comparator"
+		| "tripwire"
+		| "tnt"
+		| "slime_block"
+		| "honey_block"
+		| "scaffolding"
+		| "flower_pot"
+		| "fire"
+		| "soul_fire"
+		| "structure_void"
+		| "bamboo_sapling"
+		| "frogspawn"
+		| "big_dripleaf_stem"
+		| "small_dripleaf"
+		| "hanging_roots"
+		| "pumpkin_stem"
+		| "melon_stem"
+		| "attached_pumpkin_stem"
+		| "attached_melon_stem" => return 0.0,
+		_ => {}
+	}
+
+	// Целые семейства блоков с одинаковой прочностью
+	const BY_SUFFIX: &[(&str, f32)] = &[
+		("_sapling", 0.0),
+		("_tulip", 0.0),
+		("_coral", 0.0),
+		("_coral_fan", 0.0),
+		("_coral_wall_fan", 0.0),
+		("_ore", 3.0),
+		("_log", 2.0),
+		("_wood", 2.0),
+		("crimson_stem", 2.0),
+		("warped_stem", 2.0),
+		("_hyphae", 2.0),
+		("_planks", 2.0),
+		("_fence", 2.0),
+		("_fence_gate", 2.0),
+		("_door", 3.0),
+		("_trapdoor", 3.0),
+		("_leaves", 0.2),
+		("_wool", 0.8),
+		("_carpet", 0.1),
+		("_concrete", 1.8),
+		("_concrete_powder", 0.5),
+		("_glazed_terracotta", 1.4),
+		("terracotta", 1.25),
+		("_glass", 0.3),
+		("_glass_pane", 0.3),
+		("_bed", 0.2),
+		("_button", 0.5),
+		("_pressure_plate", 0.5),
+		("_sign", 1.0),
+		("_banner", 1.0),
+		("_candle", 0.1),
+		("_shulker_box", 2.0),
+		("_stairs", 1.5),
+		("_slab", 1.5),
+		("_wall", 1.5),
+	];
+
+	BY_SUFFIX
+		.iter()
+		.find(|(suffix, _)| name.ends_with(suffix))
+		.map(|(_, hardness)| *hardness)
+		.unwrap_or(UNKNOWN_HARDNESS)
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn block(name: &str) -> f32 {
+		hardness(&BlockState::new(name))
+	}
+
+	#[test]
+	fn known_blocks() {
+		assert_eq!(block("minecraft:stone"), 1.5);
+		assert_eq!(block("minecraft:obsidian"), 50.0);
+		assert_eq!(block("minecraft:bedrock"), -1.0);
+		assert_eq!(block("minecraft:short_grass"), 0.0);
+		assert_eq!(block("minecraft:deepslate_diamond_ore"), 3.0);
+		assert_eq!(block("minecraft:oak_log"), 2.0);
+		assert_eq!(block("minecraft:white_terracotta"), 1.25);
+		assert_eq!(block("minecraft:white_glazed_terracotta"), 1.4);
+	}
+
+	#[test]
+	fn stems() {
+		assert_eq!(block("minecraft:crimson_stem"), 2.0);
+		assert_eq!(block("minecraft:stripped_warped_stem"), 2.0);
+		assert_eq!(block("minecraft:pumpkin_stem"), 0.0);
+		assert_eq!(block("minecraft:attached_melon_stem"), 0.0);
+		assert_eq!(block("minecraft:mushroom_stem"), 0.2);
+	}
+
+	#[test]
+	fn unknown_blocks_are_not_instant() {
+		assert_eq!(block("minecraft:unknown_block"), UNKNOWN_HARDNESS);
+		assert_eq!(block("plugin:custom_stem_block"), UNKNOWN_HARDNESS);
+	}
+}
@@ -0,0 +1,559 @@
+use std::collections::{BTreeMap, HashMap};
+
+use craftflow_nbt::DynNBT;
+
+use crate::ServerError;
+
+pub const DATA_VERSION: i32 = 4325; // 1.21.5
+pub const SECTION_BLOCKS: usize = 16 * 16 * 16;
+pub const SECTION_BIOMES: usize = 4 * 4 * 4;
+
+// Блок с его свойствами, как в палитре чанка, например minecraft:oak_log[axis=y]
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct BlockState {
+	pub name: String,
+	pub properties: BTreeMap<String, String>,
+}
+
+impl BlockState {
+	pub fn new(name: &str) -> BlockState {
+		BlockState {
+			name: name.to_string(),
+			properties: BTreeMap::new(),
+		}
+	}
+
+	/// Разбирает строку вида minecraft:oak_log[axis=y], без неймспейса подставляет minecraft
+	pub fn parse(value: &str) -> BlockState {
+		let (name, properties) = match value.split_once('[') {
+			Some((name, properties)) => (name, properties.trim_end_matches(']')),
+			None => (value, ""),
+		};
+
+		let name = if name.contains(':') {
+			name.to_string()
+		} else {
+			format!("minecraft:{name}")
+		};
+
+		BlockState {
+			name,
+			properties: properties
+				.split(',')
+				.filter_map(|o| o.split_once('='))
+				.map(|(k, v)| (k.trim().to_string(), v.trim().to_string()))
+				.collect(),
+		}
+	}
+
+	pub fn air() -> BlockState {
+		BlockState::new("minecraft:air")
+	}
+
+	pub fn with_property(mut self, key: &str, value: &str) -> BlockState {
+		self.properties.insert(key.to_string(), value.to_string());
+		self
+	}
+
+	pub fn is_air(&self) -> bool {
+		matches!(
+			self.name.as_str(),
+			"minecraft:air" | "minecraft:cave_air" | "minecraft:void_air"
+		)
+	}
+
+	fn from_nbt(nbt: &DynNBT) -> Result<BlockState, ServerError> {
+		let compound = nbt.as_compound().ok_or(ServerError::DeNbt)?;
+
+		let name = compound
+			.get("Name")
+			.and_then(|o| o.as_string())
+			.ok_or(ServerError::DeNbt)?
+			.clone();
+
+		let mut properties = BTreeMap::new();
+		if let Some(props) = compound.get("Properties").and_then(|o| o.as_compound()) {
+			for (key, value) in props {
+				properties.insert(
+					key.clone(),
+					value.as_string().ok_or(ServerError::DeNbt)?.clone(),
+				);
+			}
+		}
+
+		Ok(BlockState { name, properties })
+	}
+
+	fn to_nbt(&self) -> DynNBT {
+		let mut compound = HashMap::new();
+		compound.insert("Name".to_string(), DynNBT::String(self.name.clone()));
+		if !self.properties.is_empty() {
+			compound.insert(
+				"Properties".to_string(),
+				DynNBT::Compound(
+					self
+						.properties
+						.iter()
+						.map(|(k, v)| (k.clone(), DynNBT::String(v.clone())))
+						.collect(),
+				),
+			);
+		}
+		DynNBT::Compound(compound)
+	}
+}
+
+// Палитра + упакованные индексы, в том виде как они лежат на диске
+//
+// Индексы не переходят через границу лонга (формат с 1.16),
+// размер индекса не меньше `min_bits` (4 для блоков, 1 для биомов), при одном элементе палитры data пустая
+#[derive(Debug, Clone)]
+pub struct PalettedStorage<T> {
+	palette: Vec<T>,
+	data: Vec<i64>,
+	size: usize,
+	min_bits: usize,
+}
+
+impl<T: Clone + PartialEq> PalettedStorage<T> {
+	pub fn filled(value: T, size: usize, min_bits: usize) -> PalettedStorage<T> {
+		PalettedStorage {
+			palette: vec![value],
+			data: Vec::new(),
+			size,
+			min_bits,
+		}
+	}
+
+	fn from_parts(
+		palette: Vec<T>,
+		data: Vec<i64>,
+		size: usize,
+		min_bits: usize,
+	) -> Result<PalettedStorage<T>, ServerError> {
+		let storage = PalettedStorage {
+			palette,
+			data,
+			size,
+			min_bits,
+		};
+
+		if storage.palette.is_empty() {
+			return Err(ServerError::DeNbt);
+		}
+
+		let bits = storage.bits();
+		if bits != 0 && storage.data.len() != size.div_ceil(64 / bits) {
+			return Err(ServerError::DeNbt);
+		}
+
+		Ok(storage)
+	}
+
+	/// Сколько значений хранится (4096 для блоков, 64 для биомов)
+	pub fn size(&self) -> usize {
+		self.size
+	}
+
+	pub fn palette(&self) -> &[T] {
+		&self.palette
+	}
+
+	pub fn data(&self) -> &[i64] {
+		&self.data
+	}
+
+	/// Сколько бит занимает один индекс, 0 если в палитре один элемент
+	pub fn bits(&self) -> usize {
+		Self::bits_for(self.palette.len(), self.min_bits)
+	}
+
+	fn bits_for(palette_len: usize, min_bits: usize) -> usize {
+		if palette_len <= 1 {
+			0
+		} else {
+			min_bits.max((usize::BITS - (palette_len - 1).leading_zeros()) as usize)
+		}
+	}
+
+	/// Индекс значения в палитре
+	pub fn palette_index(&self, index: usize) -> usize {
+		let palette_index = self.index_at(index);
+		if palette_index < self.palette.len() {
+			palette_index
+		} else {
+			0
+		}
+	}
+
+	fn index_at(&self, index: usize) -> usize {
+		let bits = self.bits();
+		if bits == 0 {
+			return 0;
+		}
+		let per_long = 64 / bits;
+		let long = self.data[index / per_long] as u64;
+		((long >> ((index % per_long) * bits)) & ((1 << bits) - 1)) as usize
+	}
+
+	fn set_index_at(&mut self, index: usize, value: usize) {
+		let bits = self.bits();
+		let per_long = 64 / bits;
+		let shift = (index % per_long) * bits;
+		let mask = ((1u64 << bits) - 1) << shift;
+		let long = &mut self.data[index / per_long];
+		*long = ((*long as u64 & !mask) | ((value as u64) << shift)) as i64;
+	}
+
+	pub fn get(&self, index: usize) -> &T {
+		// Битые индексы (больше палитры) считаем первым элементом палитры
+		&self.palette[self.palette_index(index)]
+	}
+
+	/// Ставит значение, возвращает старое
+	pub fn set(&mut self, index: usize, value: T) -> T {
+		let old = self.get(index).clone();
+		if old == value {
+			return old;
+		}
+
+		let palette_index = match self.palette.iter().position(|o| *o == value) {
+			Some(i) => i,
+			None => {
+				let old_bits = self.bits();
+				let indices = (0..self.size).map(|i| self.index_at(i)).collect::<Vec<_>>();
+
+				self.palette.push(value);
+
+				let bits = self.bits();
+				if bits != old_bits {
+					// Индекс больше не влезает, перепаковываем все
+					self.data = vec![0; self.size.div_ceil(64 / bits)];
+					for (i, palette_index) in indices.into_iter().enumerate() {
+						self.set_index_at(i, palette_index);
+					}
+				}
+
+				self.palette.len() - 1
+			}
+		};
+
+		self.set_index_at(index, palette_index);
+
+		old
+	}
+
+	fn to_nbt(&self, value_to_nbt: impl Fn(&T) -> DynNBT) -> DynNBT {
+		let mut compound = HashMap::new();
+		compound.insert(
+			"palette".to_string(),
+			DynNBT::List(self.palette.iter().map(value_to_nbt).collect()),
+		);
+		if !self.data.is_empty() {
+			compound.insert("data".to_string(), DynNBT::LongArray(self.data.clone()));
+		}
+		DynNBT::Compound(compound)
+	}
+
+	fn from_nbt(
+		nbt: &DynNBT,
+		size: usize,
+		min_bits: usize,
+		value_from_nbt: impl Fn(&DynNBT) -> Result<T, ServerError>,
+	) -> Result<PalettedStorage<T>, ServerError> {
+		let compound = nbt.as_compound().ok_or(ServerError::DeNbt)?;
+
+		let palette = compound
+			.get("palette")
+			.and_then(|o| o.as_list())
+			.ok_or(ServerError::DeNbt)?
+			.iter()
+			.map(value_from_nbt)
+			.collect::<Result<Vec<T>, ServerError>>()?;
+
+		let data = compound
+			.get("data")
+			.and_then(|o| o.as_long_array())
+			.cloned()
+			.unwrap_or_default();
+
+		PalettedStorage::from_parts(palette, data, size, min_bits)
+	}
+}
+
+// Секция чанка 16x16x16
+#[derive(Debug, Clone)]
+pub struct ChunkSection {
+	pub y: i8,
+	pub blocks: PalettedStorage<BlockState>,
+	pub biomes: PalettedStorage<String>,
+	pub block_light: Option<Vec<u8>>, // 2048 байт, по полбайта на блок
+	pub sky_light: Option<Vec<u8>>,
+}
+
+impl ChunkSection {
+	pub fn empty(y: i8, biome: &str) -> ChunkSection {
+		ChunkSection {
+			y,
+			blocks: PalettedStorage::filled(BlockState::air(), SECTION_BLOCKS, 4),
+			biomes: PalettedStorage::filled(biome.to_string(), SECTION_BIOMES, 1),
+			block_light: None,
+			sky_light: None,
+		}
+	}
+
+	/// Координаты внутри секции, 0..16
+	pub fn get_block(&self, x: usize, y: usize, z: usize) -> &BlockState {
+		self.blocks.get((y << 8) | (z << 4) | x)
+	}
+
+	pub fn set_block(&mut self, x: usize, y: usize, z: usize, block: BlockState) -> BlockState {
+		self.blocks.set((y << 8) | (z << 4) | x, block)
+	}
+
+	/// Координаты внутри секции, 0..16, биомы хранятся кусками 4x4x4
+	pub fn get_biome(&self, x: usize, y: usize, z: usize) -> &str {
+		self
+			.biomes
+			.get(((y >> 2) << 4) | ((z >> 2) << 2) | (x >> 2))
+	}
+
+	pub fn set_biome(&mut self, x: usize, y: usize, z: usize, biome: &str) {
+		self.biomes.set(
+			((y >> 2) << 4) | ((z >> 2) << 2) | (x >> 2),
+			biome.to_string(),
+		);
+	}
+
+	fn from_nbt(nbt: &DynNBT) -> Result<ChunkSection, ServerError> {
+		let compound = nbt.as_compound().ok_or(ServerError::DeNbt)?;
+
+		let y = compound
+			.get("Y")
+			.and_then(|o| o.as_byte())
+			.ok_or(ServerError::DeNbt)?;
+
+		let blocks = match compound.get("block_states") {
+			Some(nbt) => PalettedStorage::from_nbt(nbt, SECTION_BLOCKS, 4, BlockState::from_nbt)?,
+			None => PalettedStorage::filled(BlockState::air(), SECTION_BLOCKS, 4),
+		};
+
+		let biomes = match compound.get("biomes") {
+			Some(nbt) => PalettedStorage::from_nbt(nbt, SECTION_BIOMES, 1, |o| {
+				o.as_string().cloned().ok_or(ServerError::DeNbt)
+			})?,
+			None => PalettedStorage::filled("minecraft:plains".to_string(), SECTION_BIOMES, 1),
+		};
+
+		Ok(ChunkSection {
+			y,
+			blocks,
+			biomes,
+			block_light: compound
+				.get("BlockLight")
+				.and_then(|o| o.as_byte_array())
+				.cloned(),
+			sky_light: compound
+				.get("SkyLight")
+				.and_then(|o| o.as_byte_array())
+				.cloned(),
+		})
+	}
+
+	fn to_nbt(&self) -> DynNBT {
+		let mut compound = HashMap::new();
+		compound.insert("Y".to_string(), DynNBT::Byte(self.y));
+		compound.insert(
+			"block_states".to_string(),
+			self.blocks.to_nbt(BlockState::to_nbt),
+		);
+		compound.insert(
+			"biomes".to_string(),
+			self.biomes.to_nbt(|o| DynNBT::String(o.clone())),
+		);
+		if let Some(light) = &self.block_light {
+			compound.insert("BlockLight".to_string(), DynNBT::ByteArray(light.clone()));
+		}
+		if let Some(light) = &self.sky_light {
+			compound.insert("SkyLight".to_string(), DynNBT::ByteArray(light.clone()));
+		}
+		DynNBT::Compound(compound)
+	}
+}
+
+// Чанк в формате Anvil (1.18+)
+//
+// Разбираем только то что нужно серверу: координаты, статус и секции,
+// все остальные теги (хейтмапы, блок энтити, тики и т.д.) храним как есть и пишем обратно
+#[derive(Debug, Clone)]
+pub struct Chunk {
+	pub x: i32,
+	pub z: i32,
+	pub min_section: i32, // yPos, самая нижняя секция (-4 в обычном мире)
+	pub status: String,   // minecraft:full для готовых чанков
+	pub sections: Vec<ChunkSection>, // отсортированы по y
+	other: HashMap<String, DynNBT>,
+	dirty: bool,
+}
+
+impl Chunk {
+	/// Пустой чанк из воздуха, `sections` - сколько секций по высоте
+	pub fn empty(x: i32, z: i32, min_section: i32, sections: usize) -> Chunk {
+		Chunk {
+			x,
+			z,
+			min_section,
+			status: "minecraft:full".to_string(),
+			sections: (0..sections)
+				.map(|i| ChunkSection::empty((min_section + i as i32) as i8, "minecraft:plains"))
+				.collect(),
+			other: HashMap::new(),
+			dirty: true,
+		}
+	}
+
+	pub fn is_dirty(&self) -> bool {
+		self.dirty
+	}
+
+	pub fn mark_dirty(&mut self) {
+		self.dirty = true;
+	}
+
+	pub fn mark_saved(&mut self) {
+		self.dirty = false;
+	}
+
+	pub fn section(&self, section_y: i32) -> Option<&ChunkSection> {
+		self.sections.iter().find(|o| o.y as i32 == section_y)
+	}
+
+	pub fn section_mut(&mut self, section_y: i32) -> Option<&mut ChunkSection> {
+		self.sections.iter_mut().find(|o| o.y as i32 == section_y)
+	}
+
+	/// Секция, если ее нет - создается пустая
+	pub fn section_or_insert(&mut self, section_y: i32) -> &mut ChunkSection {
+		if self.section(section_y).is_none() {
+			let position = self
+				.sections
+				.iter()
+				.position(|o| o.y as i32 > section_y)
+				.unwrap_or(self.sections.len());
+			self.sections.insert(
+				position,
+				ChunkSection::empty(section_y as i8, "minecraft:plains"),
+			);
+		}
+
+		self.section_mut(section_y).unwrap()
+	}
+
+	/// Посчитан ли свет (isLightOn), если нет - свет в секциях не актуален
+	pub fn is_light_on(&self) -> bool {
+		self
+			.other
+			.get("isLightOn")
+			.and_then(|o| o.as_byte())
+			.is_some_and(|o| o != 0)
+	}
+
+	pub fn set_light_on(&mut self, light_on: bool) {
+		self
+			.other
+			.insert("isLightOn".to_string(), DynNBT::Byte(light_on as i8));
+	}
+
+	/// x и z внутри чанка (0..16), y абсолютный
+	pub fn get_block(&self, x: usize, y: i32, z: usize) -> BlockState {
+		self
+			.section(y >> 4)
+			.map(|o| o.get_block(x, (y & 15) as usize, z).clone())
+			.unwrap_or_else(BlockState::air)
+	}
+
+	/// x и z внутри чанка (0..16), y абсолютный, возвращает старый блок
+	pub fn set_block(&mut self, x: usize, y: i32, z: usize, block: BlockState) -> BlockState {
+		let old = self
+			.section_or_insert(y >> 4)
+			.set_block(x, (y & 15) as usize, z, block);
+
+		// Свет теперь неактуален, его пересчитает LightEngine (World::set_block),
+		// либо ванилла при загрузке
+		self.set_light_on(false);
+		self.dirty = true;
+
+		old
+	}
+
+	pub fn from_nbt(nbt: DynNBT) -> Result<Chunk, ServerError> {
+		let mut other = nbt.into_compound().ok_or(ServerError::DeNbt)?;
+
+		let x = other
+			.remove("xPos")
+			.and_then(|o| o.into_int())
+			.ok_or(ServerError::DeNbt)?;
+		let z = other
+			.remove("zPos")
+			.and_then(|o| o.into_int())
+			.ok_or(ServerError::DeNbt)?;
+		let min_section = other
+			.remove("yPos")
+			.and_then(|o| o.into_int())
+			.unwrap_or(-4);
+		let status = other
+			.remove("Status")
+			.and_then(|o| o.into_string())
+			.unwrap_or_else(|| "minecraft:full".to_string());
+
+		let mut sections = other
+			.remove("sections")
+			.and_then(|o| o.into_list())
+			.unwrap_or_default()
+			.iter()
+			.map(ChunkSection::from_nbt)
+			.collect::<Result<Vec<ChunkSection>, ServerError>>()?;
+		sections.sort_by_key(|o| o.y);
+
+		// Версию все равно перезапишем своей
+		other.remove("DataVersion");
+
+		Ok(Chunk {
+			x,
+			z,
+			min_section,
+			status,
+			sections,
+			other,
+			dirty: false,
+		})
+	}
+
+	pub fn to_nbt(&self) -> DynNBT {
+		let mut compound = self.other.clone();
+		compound.insert("DataVersion".to_string(), DynNBT::Int(DATA_VERSION));
+		compound.insert("xPos".to_string(), DynNBT::Int(self.x));
+		compound.insert("zPos".to_string(), DynNBT::Int(self.z));
+		compound.insert("yPos".to_string(), DynNBT::Int(self.min_section));
+		compound.insert("Status".to_string(), DynNBT::String(self.status.clone()));
+		compound.insert(
+			"sections".to_string(),
+			DynNBT::List(self.sections.iter().map(ChunkSection::to_nbt).collect()),
+		);
+		DynNBT::Compound(compound)
+	}
+
+	/// Читает чанк из несжатого NBT, как он лежит в регион файле
+	pub fn from_bytes(data: &[u8]) -> Result<Chunk, ServerError> {
+		let (_, (_, nbt)) =
+			craftflow_nbt::from_slice_named::<DynNBT>(data).map_err(|_| ServerError::DeNbt)?;
+		Chunk::from_nbt(nbt)
+	}
+
+	pub fn to_bytes(&self) -> Result<Vec<u8>, ServerError> {
+		let mut data = Vec::new();
+		craftflow_nbt::to_writer_named(&mut data, "", &self.to_nbt())
+			.map_err(|_| ServerError::SerNbt)?;
+		Ok(data)
+	}
+}
@@ -0,0 +1,511 @@
+use std::{
+	collections::BTreeMap,
+	fs,
+	io::Read,
+	path::{Path, PathBuf},
+};
+
+use flate2::{Crc, read::DeflateDecoder};
+use log::{debug, warn};
+use serde::Deserialize;
+
+use super::registry::{Registries, TagValue};
+use crate::ServerError;
+
+// Датапаки: папки и zip архивы с pack.mcmeta в корне
+//
+// Из пака берутся только json в data/<ns>/: записи синхронизируемых реестров
+// (data/<ns>/worldgen/biome/*.json, data/<ns>/dimension_type/*.json, ...) и теги (data/<ns>/tags/<реестр>/*.json),
+// все остальное (рецепты, лут, функции) сервер пока не понимает и пропускает
+
+#[derive(Deserialize)]
+struct PackMeta {
+	pack: PackInfo,
+}
+
+#[derive(Deserialize)]
+struct PackInfo {
+	pack_format: i32,
+	#[serde(default)]
+	description: serde_json::Value,
+}
+
+#[derive(Deserialize)]
+struct TagFile {
+	#[serde(default)]
+	replace: bool,
+	#[serde(default)]
+	values: Vec<TagFileValue>,
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum TagFileValue {
+	Id(i32), // не ванильно, но так можно указать айди блока или предмета, которых сервер не знает по имени
+	Name(String),
+	Entry { id: String }, // {"id": "...", "required": false}
+}
+
+pub struct Datapack {
+	pub name: String, // имя папки или архива
+	pub description: String,
+	pub pack_format: i32,
+	files: BTreeMap<String, Vec<u8>>, // путь внутри пака -> содержимое
+}
+
+impl Datapack {
+	/// Читает датапак из папки или zip архива
+	pub fn load(path: impl AsRef<Path>) -> Result<Datapack, ServerError> {
+		let path = path.as_ref();
+		let error = |e: String| ServerError::Datapack(format!("{}: {e}", path.display()));
+
+		let files = if path.is_dir() {
+			let mut files = BTreeMap::new();
+			read_dir(path, path, &mut files).map_err(|e| error(e.to_string()))?;
+			files
+		} else {
+			let data = fs::read(path).map_err(|e| error(e.to_string()))?;
+			read_zip(&data).map_err(error)?
+		};
+
+		let meta = files
+			.get("pack.mcmeta")
+			.ok_or_else(|| error("нет pack.mcmeta".to_string()))?;
+		let meta: PackMeta = serde_json::from_slice(meta).map_err(|e| error(e.to_string()))?;
+
+		Ok(Datapack {
+			name: path
+				.file_name()
+				.map(|o| o.to_string_lossy().to_string())
+				.unwrap_or_default(),
+			description: match meta.pack.description {
+				serde_json::Value::String(text) => text,
+				serde_json::Value::Null => String::new(),
+				other => other.to_string(),
+			},
+			pack_format: meta.pack.pack_format,
+			files,
+		})
+	}
+
+	/// Пути всех файлов внутри пака
+	pub fn files(&self) -> impl Iterator<Item = &str> {
+		self.files.keys().map(|o| o.as_str())
+	}
+
+	pub fn file(&self, path: &str) -> Option<&[u8]> {
+		self.files.get(path).map(|o| o.as_slice())
+	}
+
+	/// Добавляет в реестры записи и теги из пака, возвращает сколько записей и тегов добавлено
+	pub fn apply(&self, registries: &mut Registries) -> (usize, usize) {
+		let (mut entries, mut tags) = (0, 0);
+
+		for (path, data) in self.files.iter() {
+			let Some(path) = path
+				.strip_prefix("data/")
+				.and_then(|o| o.strip_suffix(".json"))
+			else {
+				continue;
+			};
+			let Some((namespace, path)) = path.split_once('/') else {
+				continue;
+			};
+
+			let result = match path.strip_prefix("tags/") {
+				Some(path) => self
+					.apply_tag(registries, namespace, path, data)
+					.map(|o| tags += o as usize),
+				None => self
+					.apply_entry(registries, namespace, path, data)
+					.map(|o| entries += o as usize),
+			};
+
+			if let Err(error) = result {
+				warn!(
+					"Датапак {}: data/{namespace}/{path}.json: {error}",
+					self.name
+				);
+			}
+		}
+
+		(entries, tags)
+	}
+
+	fn apply_entry(
+		&self,
+		registries: &mut Registries,
+		namespace: &str,
+		path: &str,
+		data: &[u8],
+	) -> Result<bool, String> {
+		let Some((registry, entry)) = split_registry(path, |o| registries.registry(o).is_some()) else {
+			return Ok(false);
+		};
+
+		let value: serde_json::Value = serde_json::from_slice(data).map_err(|e| e.to_string())?;
+		registries.add_json(&registry, &format!("{namespace}:{entry}"), &value);
+		Ok(true)
+	}
+
+	fn apply_tag(
+		&self,
+		registries: &mut Registries,
+		namespace: &str,
+		path: &str,
+		data: &[u8],
+	) -> Result<bool, String> {
+		let Some((registry, tag)) = split_registry(path, |o| {
+			registries.registry(o).is_some() || registries.has_tags(o)
+		}) else {
+			debug!("Датапак {}: теги неизвестного реестра {path}", self.name);
+			return Ok(false);
+		};
+
+		let file: TagFile = serde_json::from_slice(data).map_err(|e| e.to_string())?;
+		let values = file
+			.values
+			.into_iter()
+			.map(|o| match o {
+				TagFileValue::Id(id) => TagValue::Id(id),
+				TagFileValue::Name(name) | TagFileValue::Entry { id: name } => TagValue::Name(name),
+			})
+			.collect();
+
+		let tag = format!("{namespace}:{tag}");
+		if file.replace {
+			registries.set_tag(&registry, &tag, values);
+		} else {
+			registries.add_tag(&registry, &tag, values);
+		}
+		Ok(true)
+	}
+}
+
+/// Все датапаки из папки по алфавиту, сломанные пропускаются с предупреждением
+pub fn load_datapacks(dir: impl AsRef<Path>) -> Vec<Datapack> {
+	let Ok(read) = fs::read_dir(dir) else {
+		return Vec::new();
+	};
+
+	let mut paths: Vec<PathBuf> = read
+		.filter_map(|o| o.ok())
+		.map(|o| o.path())
+		.filter(|o| o.is_dir() || o.extension().is_some_and(|o| o == "zip"))
+		.collect();
+	paths.sort();
+
+	paths
+		.into_iter()
+		.filter_map(|path| match Datapack::load(&path) {
+			Ok(pack) => Some(pack),
+			Err(error) => {
+				warn!("Не удалось загрузить датапак: {error:?}");
+				None
+			}
+		})
+		.collect()
+}
+
+// Путь вроде worldgen/biome/my_biome делится на реестр minecraft:worldgen/biome и имя my_biome,
+// реестр ищется самый длинный из известных
+fn split_registry(path: &str, known: impl Fn(&str) -> bool) -> Option<(String, String)> {
+	path
+		.match_indices('/')
+		.map(|(i, _)| i)
+		.rev()
+		.map(|i| {
+			(
+				format!("minecraft:{}", &path[..i]),
+				path[i + 1..].to_string(),
+			)
+		})
+		.find(|(registry, _)| known(registry))
+}
+
+fn read_dir(root: &Path, dir: &Path, files: &mut BTreeMap<String, Vec<u8>>) -> std::io::Result<()> {
+	for entry in fs::read_dir(dir)? {
+		let path = entry?.path();
+		if path.is_dir() {
+			read_dir(root, &path, files)?;
+			continue;
+		}
+
+		let name = path
+			.strip_prefix(root)
+			.unwrap()
+			.components()
+			.map(|o| o.as_os_str().to_string_lossy())
+			.collect::<Vec<_>>()
+			.join("/");
+		if name == "pack.mcmeta" || name.starts_with("data/") {
+			files.insert(name, fs::read(&path)?);
+		}
+	}
+	Ok(())
+}
+
+// Минимальное чтение zip: центральный каталог, без zip64 и шифрования,
+// сжатие только stored (0) и deflate (8), размер и crc32 каждого файла проверяются
+fn read_zip(data: &[u8]) -> Result<BTreeMap<String, Vec<u8>>, String> {
+	let u16_at = |i: usize| {
+		data
+			.get(i..i + 2)
+			.map(|o| u16::from_le_bytes([o[0], o[1]]) as usize)
+	};
+	let u32_at = |i: usize| {
+		data
+			.get(i..i + 4)
+			.map(|o| u32::from_le_bytes([o[0], o[1], o[2], o[3]]) as usize)
+	};
+	let broken = || "битый zip".to_string();
+
+	// Конец центрального каталога ищется с конца, после него может быть комментарий до 64кб
+	let end = (0..data.len().saturating_sub(21))
+		.rev()
+		.take(0xFFFF + 22)
+		.find(|&i| u32_at(i) == Some(0x06054b50))
+		.ok_or_else(broken)?;
+	let count = u16_at(end + 10).ok_or_else(broken)?;
+	let mut offset = u32_at(end + 16).ok_or_else(broken)?;
+
+	let mut files = BTreeMap::new();
+
+	for _ in 0..count {
+		if u32_at(offset) != Some(0x02014b50) {
+			return Err(broken());
+		}
+		let method = u16_at(offset + 10).ok_or_else(broken)?;
+		let crc = u32_at(offset + 16).ok_or_else(broken)?;
+		let compressed = u32_at(offset + 20).ok_or_else(broken)?;
+		let size = u32_at(offset + 24).ok_or_else(broken)?;
+		let name_len = u16_at(offset + 28).ok_or_else(broken)?;
+		let extra_len = u16_at(offset + 30).ok_or_else(broken)?;
+		let comment_len = u16_at(offset + 32).ok_or_else(broken)?;
+		let local = u32_at(offset + 42).ok_or_else(broken)?;
+		let name = data
+			.get(offset + 46..offset + 46 + name_len)
+			.ok_or_else(broken)?;
+		let name = String::from_utf8_lossy(name).to_string();
+		offset += 46 + name_len + extra_len + comment_len;
+
+		if name.ends_with('/') || !(name == "pack.mcmeta" || name.starts_with("data/")) {
+			continue;
+		}
+
+		if u32_at(local) != Some(0x04034b50) {
+			return Err(broken());
+		}
+		let start = local
+			+ 30
+			+ u16_at(local + 26).ok_or_else(broken)?
+			+ u16_at(local + 28).ok_or_else(broken)?;
+		let raw = data.get(start..start + compressed).ok_or_else(broken)?;
+
+		let content = match method {
+			0 => raw.to_vec(),
+			8 => {
+				// Размер из заголовка не доверяем, распаковываем не больше него
+				let mut content = Vec::new();
+				DeflateDecoder::new(raw)
+					.take(size as u64 + 1)
+					.read_to_end(&mut content)
+					.map_err(|e| format!("{name}: {e}"))?;
+				content
+			}
+			method => return Err(format!("{name}: неподдерживаемое сжатие {method}")),
+		};
+
+		let mut actual = Crc::new();
+		actual.update(&content);
+		if content.len() != size || actual.sum() as usize != crc {
+			return Err(format!("{name}: размер или crc32 не совпадает"));
+		}
+
+		files.insert(name, content);
+	}
+
+	Ok(files)
+}
+
+#[cfg(test)]
+mod tests {
+	use std::io::Write;
+
+	use flate2::{Compression, write::DeflateEncoder};
+
+	use super::*;
+
+	const META: &[u8] = br#"{"pack": {"pack_format": 71, "description": "test"}}"#;
+
+	// Собирает zip из файлов (имя, метод сжатия, содержимое)
+	fn zip(entries: &[(&str, u16, &[u8])]) -> Vec<u8> {
+		let mut data = Vec::new();
+		let mut central = Vec::new();
+
+		for (name, method, content) in entries {
+			let raw = match method {
+				8 => {
+					let mut encoder = DeflateEncoder::new(Vec::new(), Compression::default());
+					encoder.write_all(content).unwrap();
+					encoder.finish().unwrap()
+				}
+				_ => content.to_vec(),
+			};
+			let mut crc = Crc::new();
+			crc.update(content);
+
+			// Общая часть локального заголовка и записи каталога, с версии до crc, размеров и длины имени
+			let mut common = Vec::new();
+			common.extend(0u16.to_le_bytes()); // флаги
+			common.extend(method.to_le_bytes());
+			common.extend(0u32.to_le_bytes()); // время и дата
+			common.extend(crc.sum().to_le_bytes());
+			common.extend((raw.len() as u32).to_le_bytes());
+			common.extend((content.len() as u32).to_le_bytes());
+			common.extend((name.len() as u16).to_le_bytes());
+			common.extend(0u16.to_le_bytes()); // extra
+
+			let local = data.len() as u32;
+			data.extend(0x04034b50u32.to_le_bytes());
+			data.extend(20u16.to_le_bytes());
+			data.extend(&common);
+			data.extend(name.as_bytes());
+			data.extend(&raw);
+
+			central.extend(0x02014b50u32.to_le_bytes());
+			central.extend(20u16.to_le_bytes()); // кем создан
+			central.extend(20u16.to_le_bytes()); // версия для распаковки
+			central.extend(&common);
+			central.extend([0; 10]); // комментарий, диск, атрибуты
+			central.extend(local.to_le_bytes());
+			central.extend(name.as_bytes());
+		}
+
+		let offset = data.len() as u32;
+		data.extend(&central);
+		data.extend(0x06054b50u32.to_le_bytes());
+		data.extend([0; 4]); // номера дисков
+		data.extend((entries.len() as u16).to_le_bytes());
+		data.extend((entries.len() as u16).to_le_bytes());
+		data.extend((central.len() as u32).to_le_bytes());
+		data.extend(offset.to_le_bytes());
+		data.extend(0u16.to_le_bytes()); // комментарий
+		data
+	}
+
+	// Смещение первой записи центрального каталога
+	fn central_offset(data: &[u8]) -> usize {
+		let end = data.len() - 22;
+		u32::from_le_bytes(data[end + 16..end + 20].try_into().unwrap()) as usize
+	}
+
+	#[test]
+	fn reads_stored_and_deflated_files() {
+		let biome = br#"{"temperature": 0.5}"#.repeat(20);
+		let data = zip(&[
+			("pack.mcmeta", 0, META),
+			("data/", 0, b""),
+			("data/test/worldgen/biome/a.json", 8, &biome),
+			("assets/test/icon.png", 12, b"not read"),
+		]);
+
+		let files = read_zip(&data).unwrap();
+		assert_eq!(files.len(), 2);
+		assert_eq!(files["pack.mcmeta"], META);
+		assert_eq!(files["data/test/worldgen/biome/a.json"], biome);
+	}
+
+	#[test]
+	fn skips_archive_comment() {
+		let mut data = zip(&[("pack.mcmeta", 8, META)]);
+		let comment = b"made by hand";
+		let len = data.len();
+		data[len - 2..].copy_from_slice(&(comment.len() as u16).to_le_bytes());
+		data.extend(comment);
+
+		assert_eq!(read_zip(&data).unwrap()["pack.mcmeta"], META);
+	}
+
+	#[test]
+	fn rejects_malformed_archives() {
+		let data = zip(&[("pack.mcmeta", 0, META), ("data/a.json", 8, b"{}")]);
+		let central = central_offset(&data);
+		assert_eq!(read_zip(&data).unwrap().len(), 2);
+
+		assert!(read_zip(b"").is_err());
+		assert!(read_zip(b"PK\x05\x06").is_err());
+		assert!(read_zip(&data[..data.len() - 4]).is_err());
+
+		// Каталог указывает за конец архива
+		let mut broken = data.clone();
+		let end = broken.len() - 22;
+		broken[end + 16..end + 20].copy_from_slice(&u32::MAX.to_le_bytes());
+		assert!(read_zip(&broken).is_err());
+
+		// Не та сигнатура записи каталога
+		let mut broken = data.clone();
+		broken[central] = 0;
+		assert!(read_zip(&broken).is_err());
+
+		// Сжатый размер больше самого архива
+		let mut broken = data.clone();
+		broken[central + 20..central + 24].copy_from_slice(&u32::MAX.to_le_bytes());
+		assert!(read_zip(&broken).is_err());
+
+		// Огромный распакованный размер не должен выделять память заранее
+		let mut broken = data.clone();
+		broken[central + 24..central + 28].copy_from_slice(&u32::MAX.to_le_bytes());
+		assert!(read_zip(&broken).is_err());
+
+		// Испорченное содержимое ловится по crc32
+		let mut broken = data.clone();
+		broken[30 + "pack.mcmeta".len()] ^= 0xFF;
+		assert!(read_zip(&broken).is_err());
+
+		// Неподдерживаемое сжатие
+		let mut broken = data.clone();
+		broken[central + 10] = 12;
+		assert!(read_zip(&broken).is_err());
+	}
+
+	#[test]
+	fn rejects_broken_deflate_stream() {
+		let data = zip(&[("data/a.json", 8, &[7; 1000])]);
+		let mut broken = data.clone();
+		// Тип блока 3 зарезервирован в deflate
+		broken[30 + "data/a.json".len()] = 0x07;
+		assert!(read_zip(&broken).is_err());
+	}
+
+	#[test]
+	fn loads_zip_datapack() {
+		let dir = std::env::temp_dir().join(format!("rust_mc_serv_datapack_{}", std::process::id()));
+		let _ = fs::remove_dir_all(&dir);
+		fs::create_dir_all(&dir).unwrap();
+
+		let path = dir.join("test.zip");
+		fs::write(
+			&path,
+			zip(&[
+				("pack.mcmeta", 8, META),
+				("data/test/tags/item/a.json", 0, b"{}"),
+			]),
+		)
+		.unwrap();
+		fs::write(dir.join("broken.zip"), b"PK not a zip").unwrap();
+
+		let pack = Datapack::load(&path).unwrap();
+		assert_eq!(pack.name, "test.zip");
+		assert_eq!(pack.description, "test");
+		assert_eq!(pack.pack_format, 71);
+		assert_eq!(pack.file("data/test/tags/item/a.json"), Some(&b"{}"[..]));
+
+		// Сломанный архив пропускается
+		let packs = load_datapacks(&dir);
+		assert_eq!(packs.len(), 1);
+		assert_eq!(packs[0].name, "test.zip");
+
+		fs::remove_dir_all(&dir).unwrap();
+	}
+}
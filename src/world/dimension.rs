@@ -0,0 +1,321 @@
+use std::collections::HashMap;
+
+use craftflow_nbt::DynNBT;
+
+use crate::ServerError;
+
+// Границы высоты из ванильного DimensionType
+pub const MIN_Y: i32 = -2032;
+pub const MAX_Y: i32 = 2031;
+pub const MAX_HEIGHT: i32 = 4064;
+
+/// Тип измерения, от него зависит высота мира
+///
+/// Клиент знает высоту по типу из реестра, поэтому чанки должны строиться ровно под нее
+#[derive(Debug, Clone, PartialEq)]
+pub struct DimensionType {
+	pub name: String, // ключ в реестре minecraft:dimension_type
+	pub min_y: i32,   // кратно 16
+	pub height: i32,  // кратно 16
+	pub logical_height: i32,
+	pub has_skylight: bool,
+	pub has_ceiling: bool,
+	pub ultrawarm: bool,
+	pub natural: bool,
+	pub coordinate_scale: f64,
+	pub bed_works: bool,
+	pub respawn_anchor_works: bool,
+	pub piglin_safe: bool,
+	pub has_raids: bool,
+	pub ambient_light: f32,
+	pub fixed_time: Option<i64>,
+	pub infiniburn: String,
+	pub effects: String,
+	pub monster_spawn_light_level: DynNBT, // число или int provider
+	pub monster_spawn_block_light_limit: i32,
+}
+
+impl DimensionType {
+	/// Тип с заданной высотой, все остальное как у обычного мира
+	pub fn new(name: &str, min_y: i32, height: i32) -> DimensionType {
+		DimensionType {
+			name: name.to_string(),
+			min_y,
+			height,
+			logical_height: height,
+			has_skylight: true,
+			has_ceiling: false,
+			ultrawarm: false,
+			natural: true,
+			coordinate_scale: 1.0,
+			bed_works: true,
+			respawn_anchor_works: false,
+			piglin_safe: false,
+			has_raids: true,
+			ambient_light: 0.0,
+			fixed_time: None,
+			infiniburn: "#minecraft:infiniburn_overworld".to_string(),
+			effects: "minecraft:overworld".to_string(),
+			monster_spawn_light_level: DynNBT::Int(0),
+			monster_spawn_block_light_limit: 0,
+		}
+	}
+
+	pub fn overworld() -> DimensionType {
+		DimensionType::new("minecraft:overworld", -64, 384)
+	}
+
+	pub fn overworld_caves() -> DimensionType {
+		DimensionType {
+			has_ceiling: true,
+			..DimensionType::new("minecraft:overworld_caves", -64, 384)
+		}
+	}
+
+	pub fn the_nether() -> DimensionType {
+		DimensionType {
+			logical_height: 128,
+			has_skylight: false,
+			has_ceiling: true,
+			ultrawarm: true,
+			natural: false,
+			coordinate_scale: 8.0,
+			bed_works: false,
+			respawn_anchor_works: true,
+			piglin_safe: true,
+			has_raids: false,
+			ambient_light: 0.1,
+			fixed_time: Some(18000),
+			infiniburn: "#minecraft:infiniburn_nether".to_string(),
+			effects: "minecraft:the_nether".to_string(),
+			monster_spawn_block_light_limit: 15,
+			..DimensionType::new("minecraft:the_nether", 0, 256)
+		}
+	}
+
+	pub fn the_end() -> DimensionType {
+		DimensionType {
+			has_skylight: false,
+			natural: false,
+			bed_works: false,
+			fixed_time: Some(6000),
+			infiniburn: "#minecraft:infiniburn_end".to_string(),
+			effects: "minecraft:the_end".to_string(),
+			..DimensionType::new("minecraft:the_end", 0, 256)
+		}
+	}
+
+	/// Ванильный тип по имени, `minecraft:` можно не писать
+	pub fn vanilla(name: &str) -> Option<DimensionType> {
+		match name.trim_start_matches("minecraft:") {
+			"overworld" => Some(DimensionType::overworld()),
+			"overworld_caves" => Some(DimensionType::overworld_caves()),
+			"the_nether" => Some(DimensionType::the_nether()),
+			"the_end" => Some(DimensionType::the_end()),
+			_ => None,
+		}
+	}
+
+	/// Тип из данных записи реестра minecraft:dimension_type
+	///
+	/// Без высоты тип не собрать, отсутствующие свойства берутся как у обычного мира
+	pub fn from_nbt(name: &str, data: &DynNBT) -> Option<DimensionType> {
+		let data = data.as_compound()?;
+		let number = |key: &str| match data.get(key)? {
+			DynNBT::Byte(value) => Some(*value as f64),
+			DynNBT::Short(value) => Some(*value as f64),
+			DynNBT::Int(value) => Some(*value as f64),
+			DynNBT::Long(value) => Some(*value as f64),
+			DynNBT::Float(value) => Some(*value as f64),
+			DynNBT::Double(value) => Some(*value),
+			_ => None,
+		};
+		let string = |key: &str| match data.get(key)? {
+			DynNBT::String(value) => Some(value.clone()),
+			_ => None,
+		};
+
+		let mut dimension = DimensionType::new(name, number("min_y")? as i32, number("height")? as i32);
+
+		let flags = [
+			("has_skylight", &mut dimension.has_skylight),
+			("has_ceiling", &mut dimension.has_ceiling),
+			("ultrawarm", &mut dimension.ultrawarm),
+			("natural", &mut dimension.natural),
+			("bed_works", &mut dimension.bed_works),
+			("respawn_anchor_works", &mut dimension.respawn_anchor_works),
+			("piglin_safe", &mut dimension.piglin_safe),
+			("has_raids", &mut dimension.has_raids),
+		];
+		for (key, flag) in flags {
+			if let Some(value) = number(key) {
+				*flag = value != 0.0;
+			}
+		}
+
+		if let Some(value) = number("logical_height") {
+			dimension.logical_height = value as i32;
+		}
+		if let Some(value) = number("coordinate_scale") {
+			dimension.coordinate_scale = value;
+		}
+		if let Some(value) = number("ambient_light") {
+			dimension.ambient_light = value as f32;
+		}
+		dimension.fixed_time = number("fixed_time").map(|o| o as i64);
+		if let Some(value) = string("infiniburn") {
+			dimension.infiniburn = value;
+		}
+		if let Some(value) = string("effects") {
+			dimension.effects = value;
+		}
+		if let Some(value) = data.get("monster_spawn_light_level") {
+			dimension.monster_spawn_light_level = value.clone();
+		}
+		if let Some(value) = number("monster_spawn_block_light_limit") {
+			dimension.monster_spawn_block_light_limit = value as i32;
+		}
+
+		Some(dimension)
+	}
+
+	/// Данные для записи в реестре
+	pub fn to_nbt(&self) -> DynNBT {
+		let bool = |value: bool| DynNBT::Byte(value as i8);
+		let string = |value: &str| DynNBT::String(value.to_string());
+
+		let mut data = HashMap::from([
+			("has_skylight".to_string(), bool(self.has_skylight)),
+			("has_ceiling".to_string(), bool(self.has_ceiling)),
+			("ultrawarm".to_string(), bool(self.ultrawarm)),
+			("natural".to_string(), bool(self.natural)),
+			(
+				"coordinate_scale".to_string(),
+				DynNBT::Double(self.coordinate_scale),
+			),
+			("bed_works".to_string(), bool(self.bed_works)),
+			(
+				"respawn_anchor_works".to_string(),
+				bool(self.respawn_anchor_works),
+			),
+			("min_y".to_string(), DynNBT::Int(self.min_y)),
+			("height".to_string(), DynNBT::Int(self.height)),
+			(
+				"logical_height".to_string(),
+				DynNBT::Int(self.logical_height),
+			),
+			("infiniburn".to_string(), string(&self.infiniburn)),
+			("effects".to_string(), string(&self.effects)),
+			(
+				"ambient_light".to_string(),
+				DynNBT::Float(self.ambient_light),
+			),
+			("piglin_safe".to_string(), bool(self.piglin_safe)),
+			("has_raids".to_string(), bool(self.has_raids)),
+			(
+				"monster_spawn_light_level".to_string(),
+				self.monster_spawn_light_level.clone(),
+			),
+			(
+				"monster_spawn_block_light_limit".to_string(),
+				DynNBT::Int(self.monster_spawn_block_light_limit),
+			),
+		]);
+		if let Some(time) = self.fixed_time {
+			data.insert("fixed_time".to_string(), DynNBT::Long(time));
+		}
+
+		DynNBT::Compound(data)
+	}
+
+	/// Проверяет высоту по тем же правилам, что и клиент, с неверной высотой он не зайдет в мир
+	pub fn validate(&self) -> Result<(), ServerError> {
+		let error = |text: String| {
+			Err(ServerError::InvalidDimension(format!(
+				"{}: {text}",
+				self.name
+			)))
+		};
+
+		if self.min_y % 16 != 0 || self.height % 16 != 0 {
+			return error("min_y и height должны быть кратны 16".to_string());
+		}
+		if !(16..=MAX_HEIGHT).contains(&self.height) {
+			return error(format!("height должна быть от 16 до {MAX_HEIGHT}"));
+		}
+		if self.min_y < MIN_Y || self.max_y() - 1 > MAX_Y {
+			return error(format!(
+				"мир должен помещаться в высоты от {MIN_Y} до {MAX_Y}"
+			));
+		}
+		if self.logical_height > self.height {
+			return error("logical_height больше height".to_string());
+		}
+
+		Ok(())
+	}
+
+	/// Самая нижняя секция
+	pub fn min_section(&self) -> i32 {
+		self.min_y >> 4
+	}
+
+	pub fn section_count(&self) -> usize {
+		(self.height >> 4) as usize
+	}
+
+	/// Первая высота над миром
+	pub fn max_y(&self) -> i32 {
+		self.min_y + self.height
+	}
+
+	pub fn contains_y(&self, y: i32) -> bool {
+		(self.min_y..self.max_y()).contains(&y)
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn nbt_round_trip_keeps_properties() {
+		for dimension in [
+			DimensionType::overworld(),
+			DimensionType::the_nether(),
+			DimensionType::the_end(),
+		] {
+			let data = dimension.to_nbt();
+			assert_eq!(
+				DimensionType::from_nbt(&dimension.name, &data),
+				Some(dimension)
+			);
+		}
+	}
+
+	#[test]
+	fn tall_dimensions_are_allowed() {
+		let dimension = DimensionType::new("test:tall", MIN_Y, MAX_HEIGHT);
+		assert!(dimension.validate().is_ok());
+		assert_eq!(dimension.section_count(), 254);
+	}
+
+	#[test]
+	fn bad_heights_are_rejected() {
+		for (min_y, height) in [
+			(0, 0),
+			(0, 4080),
+			(-2048, 256),
+			(2000, 64),
+			(8, 256),
+			(0, 100),
+		] {
+			assert!(
+				DimensionType::new("test:bad", min_y, height)
+					.validate()
+					.is_err(),
+				"{min_y} {height}"
+			);
+		}
+	}
+}
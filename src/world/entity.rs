@@ -0,0 +1,177 @@
+use std::sync::{Arc, Mutex, MutexGuard, RwLock};
+
+use dashmap::DashMap;
+use uuid::Uuid;
+
+use crate::{data::metadata::EntityMetadata, player::registry::ClientId};
+
+// Сущности мира
+//
+// Реестр живет в мире и не привязан к соединениям, игроки в нем тоже есть как обычные сущности
+// Айди сущностей общие на все миры и выдаются через WorldContext::next_entity_id
+// Рассылкой спавна, движения и удаления занимается play::entity, здесь только состояние
+
+// Айдишник типа сущности в реестре minecraft:entity_type
+pub type EntityType = i32;
+
+pub const ITEM: EntityType = 68;
+pub const PLAYER: EntityType = 148;
+
+pub struct Entity {
+	pub id: i32,
+	pub uuid: Uuid,
+	pub entity_type: EntityType,
+	pub data: i32,                // поле data из Spawn Entity, смысл зависит от типа
+	pub player: Option<ClientId>, // айди подключения игрока, если это его сущность
+	world: RwLock<String>,        // имя мира, выставляется при спавне
+	position: RwLock<(f64, f64, f64)>,
+	velocity: RwLock<(f64, f64, f64)>, // блоков за тик
+	rotation: RwLock<(f32, f32)>,
+	head_yaw: RwLock<f32>,
+	on_ground: RwLock<bool>,
+	metadata: Mutex<EntityMetadata>,
+}
+
+impl Entity {
+	pub fn new(id: i32, uuid: Uuid, entity_type: EntityType) -> Entity {
+		Entity {
+			id,
+			uuid,
+			entity_type,
+			data: 0,
+			player: None,
+			world: RwLock::new(String::new()),
+			position: RwLock::new((0.0, 0.0, 0.0)),
+			velocity: RwLock::new((0.0, 0.0, 0.0)),
+			rotation: RwLock::new((0.0, 0.0)),
+			head_yaw: RwLock::new(0.0),
+			on_ground: RwLock::new(false),
+			metadata: Mutex::new(EntityMetadata::new()),
+		}
+	}
+
+	/// Сущность игрока, которую клиент этого игрока у себя не спавнит
+	pub fn player(id: i32, uuid: Uuid, client: ClientId) -> Entity {
+		Entity {
+			player: Some(client),
+			..Entity::new(id, uuid, PLAYER)
+		}
+	}
+
+	pub fn with_data(mut self, data: i32) -> Entity {
+		self.data = data;
+		self
+	}
+
+	pub fn with_position(self, position: (f64, f64, f64)) -> Entity {
+		*self.position.write().unwrap() = position;
+		self
+	}
+
+	pub fn set_world(&self, world: &str) {
+		*self.world.write().unwrap() = world.to_string();
+	}
+
+	/// Имя мира, в котором сущность
+	pub fn world(&self) -> String {
+		self.world.read().unwrap().clone()
+	}
+
+	pub fn set_position(&self, position: (f64, f64, f64)) {
+		*self.position.write().unwrap() = position;
+	}
+
+	pub fn set_velocity(&self, velocity: (f64, f64, f64)) {
+		*self.velocity.write().unwrap() = velocity;
+	}
+
+	pub fn set_rotation(&self, rotation: (f32, f32)) {
+		*self.rotation.write().unwrap() = rotation;
+	}
+
+	pub fn set_head_yaw(&self, head_yaw: f32) {
+		*self.head_yaw.write().unwrap() = head_yaw;
+	}
+
+	pub fn set_on_ground(&self, on_ground: bool) {
+		*self.on_ground.write().unwrap() = on_ground;
+	}
+
+	pub fn position(&self) -> (f64, f64, f64) {
+		*self.position.read().unwrap()
+	}
+
+	pub fn velocity(&self) -> (f64, f64, f64) {
+		*self.velocity.read().unwrap()
+	}
+
+	pub fn rotation(&self) -> (f32, f32) {
+		*self.rotation.read().unwrap()
+	}
+
+	pub fn head_yaw(&self) -> f32 {
+		*self.head_yaw.read().unwrap()
+	}
+
+	pub fn on_ground(&self) -> bool {
+		*self.on_ground.read().unwrap()
+	}
+
+	/// Метаданные, измененные поля разошлются зрителям на следующем тике
+	pub fn metadata(&self) -> MutexGuard<'_, EntityMetadata> {
+		self.metadata.lock().unwrap()
+	}
+
+	/// Чанк, в котором стоит сущность
+	pub fn chunk(&self) -> (i32, i32) {
+		let (x, _, z) = self.position();
+		((x.floor() as i32) >> 4, (z.floor() as i32) >> 4)
+	}
+}
+
+/// Все сущности мира по айди
+pub struct EntityRegistry {
+	entities: DashMap<i32, Arc<Entity>>,
+}
+
+impl Default for EntityRegistry {
+	fn default() -> Self {
+		Self::new()
+	}
+}
+
+impl EntityRegistry {
+	pub fn new() -> EntityRegistry {
+		EntityRegistry {
+			entities: DashMap::new(),
+		}
+	}
+
+	pub fn add(&self, entity: Arc<Entity>) {
+		self.entities.insert(entity.id, entity);
+	}
+
+	pub fn remove(&self, id: i32) -> Option<Arc<Entity>> {
+		self.entities.remove(&id).map(|(_, o)| o)
+	}
+
+	pub fn get(&self, id: i32) -> Option<Arc<Entity>> {
+		self.entities.get(&id).map(|o| o.clone())
+	}
+
+	pub fn contains(&self, id: i32) -> bool {
+		self.entities.contains_key(&id)
+	}
+
+	pub fn all(&self) -> Vec<Arc<Entity>> {
+		self.entities.iter().map(|o| o.clone()).collect()
+	}
+
+	pub fn len(&self) -> usize {
+		self.entities.len()
+	}
+
+	pub fn is_empty(&self) -> bool {
+		self.entities.is_empty()
+	}
+}
@@ -0,0 +1,179 @@
+use serde::{Deserialize, Serialize};
+use serde_default::DefaultFromSerde;
+
+use super::{WorldGenerator, empty_chunk, fill_layer};
+use crate::{
+	ServerError,
+	world::{
+		chunk::{BlockState, Chunk, ChunkSection},
+		dimension::DimensionType,
+	},
+};
+
+// Слой суперплоского мира, слои идут снизу вверх
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FlatLayer {
+	pub block: String, // например "minecraft:bedrock" или "oak_log[axis=y]"
+	pub height: u32,
+}
+
+impl FlatLayer {
+	pub fn new(block: &str, height: u32) -> FlatLayer {
+		FlatLayer {
+			block: block.to_string(),
+			height,
+		}
+	}
+}
+
+#[derive(Debug, DefaultFromSerde, Serialize, Deserialize, Clone)]
+pub struct FlatSettings {
+	#[serde(default = "default_layers")]
+	pub layers: Vec<FlatLayer>,
+	#[serde(default = "default_biome")]
+	pub biome: String,
+}
+
+fn default_layers() -> Vec<FlatLayer> {
+	vec![
+		FlatLayer::new("minecraft:bedrock", 1),
+		FlatLayer::new("minecraft:dirt", 2),
+		FlatLayer::new("minecraft:grass_block", 1),
+	]
+}
+fn default_biome() -> String {
+	"minecraft:plains".to_string()
+}
+
+// Суперплоский мир, как в ванилле: одинаковые слои во всех чанках
+//
+// Слои кладутся от низа мира, так что в измерениях разной высоты поверхность на разной высоте
+pub struct FlatGenerator {
+	layers: Vec<(BlockState, i32, i32)>, // блок, от, до (не включительно), от низа мира
+	biome: String,
+}
+
+impl FlatGenerator {
+	pub fn new(settings: &FlatSettings) -> FlatGenerator {
+		let mut y = 0;
+
+		FlatGenerator {
+			layers: settings
+				.layers
+				.iter()
+				.map(|layer| {
+					let from = y;
+					y += layer.height as i32;
+					(BlockState::parse(&layer.block), from, y)
+				})
+				.collect(),
+			biome: settings.biome.clone(),
+		}
+	}
+
+	/// Высота верхнего слоя
+	pub fn surface(&self, dimension: &DimensionType) -> i32 {
+		let top = self.layers.last().map(|o| o.2).unwrap_or(0);
+		dimension.min_y + top.min(dimension.height)
+	}
+}
+
+impl WorldGenerator for FlatGenerator {
+	fn generate(&self, x: i32, z: i32, dimension: &DimensionType) -> Result<Chunk, ServerError> {
+		let mut chunk = empty_chunk(x, z, dimension);
+
+		for section in chunk.sections.iter_mut() {
+			*section = ChunkSection::empty(section.y, &self.biome);
+		}
+
+		for (block, from, to) in self.layers.iter() {
+			if !block.is_air() {
+				let from = dimension.min_y + (*from).min(dimension.height);
+				let to = dimension.min_y + (*to).min(dimension.height);
+				fill_layer(&mut chunk, from, to, block);
+			}
+		}
+
+		Ok(chunk)
+	}
+
+	fn spawn_point(&self, dimension: &DimensionType) -> (f64, f64, f64) {
+		(8.0, self.surface(dimension) as f64, 8.0)
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn layers_start_at_world_bottom() {
+		let generator = FlatGenerator::new(&FlatSettings::default());
+
+		for dimension in [DimensionType::overworld(), DimensionType::the_nether()] {
+			let chunk = generator.generate(3, -2, &dimension).unwrap();
+			let bottom = dimension.min_y;
+
+			assert_eq!((chunk.x, chunk.z), (3, -2));
+			assert_eq!(chunk.get_block(0, bottom, 0).name, "minecraft:bedrock");
+			assert_eq!(chunk.get_block(5, bottom + 1, 7).name, "minecraft:dirt");
+			assert_eq!(chunk.get_block(15, bottom + 2, 15).name, "minecraft:dirt");
+			assert_eq!(
+				chunk.get_block(9, bottom + 3, 2).name,
+				"minecraft:grass_block"
+			);
+			assert!(chunk.get_block(9, bottom + 4, 2).is_air());
+
+			assert_eq!(generator.surface(&dimension), bottom + 4);
+			assert_eq!(
+				generator.spawn_point(&dimension),
+				(8.0, (bottom + 4) as f64, 8.0)
+			);
+		}
+	}
+
+	#[test]
+	fn custom_layers_and_biome() {
+		let generator = FlatGenerator::new(&FlatSettings {
+			layers: vec![
+				FlatLayer::new("minecraft:stone", 2),
+				FlatLayer::new("minecraft:air", 3),
+				FlatLayer::new("minecraft:oak_log[axis=y]", 1),
+			],
+			biome: "minecraft:desert".to_string(),
+		});
+		let dimension = DimensionType::overworld();
+		let chunk = generator.generate(0, 0, &dimension).unwrap();
+		let bottom = dimension.min_y;
+
+		assert_eq!(chunk.get_block(0, bottom + 1, 0).name, "minecraft:stone");
+		assert!(chunk.get_block(0, bottom + 2, 0).is_air());
+		let log = chunk.get_block(0, bottom + 5, 0);
+		assert_eq!(log.name, "minecraft:oak_log");
+		assert_eq!(log.properties.get("axis").map(|o| o.as_str()), Some("y"));
+
+		assert!(
+			chunk
+				.sections
+				.iter()
+				.all(|o| o.get_biome(0, 0, 0) == "minecraft:desert")
+		);
+	}
+
+	#[test]
+	fn layers_are_cut_at_world_top() {
+		let generator = FlatGenerator::new(&FlatSettings {
+			layers: vec![FlatLayer::new("minecraft:stone", 1000)],
+			biome: default_biome(),
+		});
+		let dimension = DimensionType::overworld();
+		let chunk = generator.generate(0, 0, &dimension).unwrap();
+
+		assert_eq!(generator.surface(&dimension), dimension.max_y());
+		assert_eq!(
+			chunk.get_block(0, dimension.max_y() - 1, 0).name,
+			"minecraft:stone"
+		);
+		assert_eq!(chunk.sections.len(), dimension.section_count());
+	}
+}
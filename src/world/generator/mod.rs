@@ -0,0 +1,89 @@
+use super::{
+	chunk::{BlockState, Chunk},
+	dimension::DimensionType,
+};
+use crate::ServerError;
+
+pub mod flat;
+pub mod noise;
+pub mod void;
+
+pub use flat::FlatGenerator;
+pub use noise::NoiseGenerator;
+pub use void::VoidGenerator;
+
+// Генератор мира, вызывается когда чанка нет ни в памяти ни на диске
+//
+// Регистрируется через ServerContext::add_generator под своим именем,
+// какой генератор использовать выбирается для каждого мира в server.toml (world.generator)
+// Один генератор может работать на несколько миров разной высоты
+pub trait WorldGenerator: Sync + Send {
+	/// Генерирует чанк с координатами x z (в чанках) под высоту измерения
+	fn generate(&self, x: i32, z: i32, dimension: &DimensionType) -> Result<Chunk, ServerError>;
+
+	/// Где появляются игроки
+	fn spawn_point(&self, _dimension: &DimensionType) -> (f64, f64, f64) {
+		(8.0, 0.0, 8.0)
+	}
+}
+
+/// Пустой чанк высоты измерения, с которого удобно начинать генерацию
+pub fn empty_chunk(x: i32, z: i32, dimension: &DimensionType) -> Chunk {
+	Chunk::empty(x, z, dimension.min_section(), dimension.section_count())
+}
+
+/// Заливает блоком колонку по всему чанку, y абсолютные, `to` не включительно
+pub fn fill_layer(chunk: &mut Chunk, from: i32, to: i32, block: &BlockState) {
+	for y in from..to {
+		for x in 0..16 {
+			for z in 0..16 {
+				chunk.set_block(x, y, z, block.clone());
+			}
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::{
+		config::{Config, WorldSettings},
+		context::WorldContext,
+	};
+
+	// Генератор из плагина: один слой песка на высоте 0
+	struct SandGenerator;
+
+	impl WorldGenerator for SandGenerator {
+		fn generate(&self, x: i32, z: i32, dimension: &DimensionType) -> Result<Chunk, ServerError> {
+			let mut chunk = empty_chunk(x, z, dimension);
+			fill_layer(&mut chunk, 0, 1, &BlockState::new("minecraft:sand"));
+			Ok(chunk)
+		}
+	}
+
+	#[test]
+	fn worlds_use_registered_generators() {
+		let path = std::env::temp_dir().join(format!("rust_mc_serv_generator_{}", std::process::id()));
+		let path = path.to_str().unwrap();
+
+		let mut context = WorldContext::new(&Config::default());
+		context.add_generator("sand", Box::new(SandGenerator));
+
+		let world = context
+			.create_world(&WorldSettings::new("sand", "overworld", "sand", path))
+			.unwrap();
+		let chunk = world.chunk(2, 3).unwrap();
+		let chunk = chunk.read().unwrap();
+
+		assert_eq!((chunk.x, chunk.z), (2, 3));
+		assert_eq!(chunk.get_block(5, 0, 5).name, "minecraft:sand");
+		assert!(chunk.get_block(5, 1, 5).is_air());
+		assert!(!world.is_flat());
+
+		assert!(matches!(
+			context.create_world(&WorldSettings::new("other", "overworld", "missing", path)),
+			Err(ServerError::UnknownGenerator(_))
+		));
+	}
+}
@@ -0,0 +1,249 @@
+use serde::{Deserialize, Serialize};
+use serde_default::DefaultFromSerde;
+
+use super::{WorldGenerator, empty_chunk};
+use crate::{
+	ServerError,
+	world::{
+		chunk::{BlockState, Chunk},
+		dimension::DimensionType,
+	},
+};
+
+#[derive(Debug, DefaultFromSerde, Serialize, Deserialize, Clone)]
+pub struct NoiseSettings {
+	#[serde(default)]
+	pub seed: i64,
+	#[serde(default = "default_sea_level")]
+	pub sea_level: i32,
+	#[serde(default = "default_base_height")]
+	pub base_height: i32, // средняя высота поверхности
+	#[serde(default = "default_amplitude")]
+	pub amplitude: f64, // насколько холмы отклоняются от средней высоты
+	#[serde(default = "default_scale")]
+	pub scale: f64, // размер холмов в блоках
+}
+
+fn default_sea_level() -> i32 {
+	62
+}
+fn default_base_height() -> i32 {
+	64
+}
+fn default_amplitude() -> f64 {
+	24.0
+}
+fn default_scale() -> f64 {
+	128.0
+}
+
+// Простой генератор рельефа на фрактальном value noise
+//
+// Без пещер, деревьев и биомов, только карта высот: камень, земля, трава, песок у воды и море
+pub struct NoiseGenerator {
+	settings: NoiseSettings,
+}
+
+impl NoiseGenerator {
+	pub fn new(settings: NoiseSettings) -> NoiseGenerator {
+		NoiseGenerator { settings }
+	}
+
+	/// Высота поверхности в блоке x z (в блоках)
+	pub fn height(&self, x: i32, z: i32) -> i32 {
+		let mut value = 0.0;
+		let mut amplitude = 1.0;
+		let mut frequency = 1.0 / self.settings.scale;
+		let mut total = 0.0;
+
+		for octave in 0..4 {
+			value += amplitude
+				* value_noise(
+					self.settings.seed.wrapping_add(octave),
+					x as f64 * frequency,
+					z as f64 * frequency,
+				);
+			total += amplitude;
+			amplitude *= 0.5;
+			frequency *= 2.0;
+		}
+
+		self.settings.base_height + (value / total * self.settings.amplitude) as i32
+	}
+}
+
+impl WorldGenerator for NoiseGenerator {
+	fn generate(
+		&self,
+		chunk_x: i32,
+		chunk_z: i32,
+		dimension: &DimensionType,
+	) -> Result<Chunk, ServerError> {
+		let mut chunk = empty_chunk(chunk_x, chunk_z, dimension);
+		let (min_y, max_y) = (dimension.min_y, dimension.max_y() - 1);
+
+		let bedrock = BlockState::new("minecraft:bedrock");
+		let stone = BlockState::new("minecraft:stone");
+		let dirt = BlockState::new("minecraft:dirt");
+		let grass = BlockState::new("minecraft:grass_block").with_property("snowy", "false");
+		let sand = BlockState::new("minecraft:sand");
+		let water = BlockState::new("minecraft:water").with_property("level", "0");
+
+		let sea_level = self.settings.sea_level.clamp(min_y, max_y);
+
+		for x in 0..16 {
+			for z in 0..16 {
+				let height = self
+					.height(chunk_x * 16 + x as i32, chunk_z * 16 + z as i32)
+					.clamp(min_y, max_y);
+				let beach = height <= sea_level + 1;
+
+				chunk.set_block(x, min_y, z, bedrock.clone());
+
+				for y in min_y + 1..=height {
+					let block = if y == height {
+						if beach { &sand } else { &grass }
+					} else if y > height - 4 {
+						if beach { &sand } else { &dirt }
+					} else {
+						&stone
+					};
+					chunk.set_block(x, y, z, block.clone());
+				}
+
+				for y in height + 1..=sea_level {
+					chunk.set_block(x, y, z, water.clone());
+				}
+			}
+		}
+
+		Ok(chunk)
+	}
+
+	fn spawn_point(&self, dimension: &DimensionType) -> (f64, f64, f64) {
+		let height = self
+			.height(8, 8)
+			.max(self.settings.sea_level)
+			.clamp(dimension.min_y, dimension.max_y() - 1);
+		(8.0, height as f64 + 1.0, 8.0)
+	}
+}
+
+// Псевдослучайное число от -1 до 1 для точки сетки
+fn hash(seed: i64, x: i64, z: i64) -> f64 {
+	// splitmix64
+	let mut value = (seed as u64)
+		.wrapping_add((x as u64).wrapping_mul(0x9E3779B97F4A7C15))
+		.wrapping_add((z as u64).wrapping_mul(0xC2B2AE3D27D4EB4F));
+	value = (value ^ (value >> 30)).wrapping_mul(0xBF58476D1CE4E5B9);
+	value = (value ^ (value >> 27)).wrapping_mul(0x94D049BB133111EB);
+	value ^= value >> 31;
+
+	(value >> 11) as f64 / (1u64 << 53) as f64 * 2.0 - 1.0
+}
+
+// Значения в узлах сетки, плавно интерполированные между ними
+fn value_noise(seed: i64, x: f64, z: f64) -> f64 {
+	let (x0, z0) = (x.floor(), z.floor());
+	let (fx, fz) = (smooth(x - x0), smooth(z - z0));
+	let (x0, z0) = (x0 as i64, z0 as i64);
+
+	let a = hash(seed, x0, z0);
+	let b = hash(seed, x0 + 1, z0);
+	let c = hash(seed, x0, z0 + 1);
+	let d = hash(seed, x0 + 1, z0 + 1);
+
+	lerp(lerp(a, b, fx), lerp(c, d, fx), fz)
+}
+
+fn smooth(t: f64) -> f64 {
+	t * t * (3.0 - 2.0 * t)
+}
+
+fn lerp(a: f64, b: f64, t: f64) -> f64 {
+	a + (b - a) * t
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn generator(seed: i64) -> NoiseGenerator {
+		NoiseGenerator::new(NoiseSettings {
+			seed,
+			..Default::default()
+		})
+	}
+
+	#[test]
+	fn same_seed_same_terrain() {
+		let (a, b, other) = (generator(1), generator(1), generator(2));
+		let heights = |generator: &NoiseGenerator| {
+			(-64..64)
+				.map(|i| generator.height(i * 13, i * 7))
+				.collect::<Vec<_>>()
+		};
+
+		assert_eq!(heights(&a), heights(&b));
+		assert_ne!(heights(&a), heights(&other));
+	}
+
+	#[test]
+	fn heights_stay_in_amplitude() {
+		let generator = generator(42);
+		let settings = NoiseSettings::default();
+
+		for x in (-2000..2000).step_by(37) {
+			for z in (-2000..2000).step_by(41) {
+				let height = generator.height(x, z);
+				assert!((height - settings.base_height).abs() <= settings.amplitude as i32);
+			}
+		}
+	}
+
+	#[test]
+	fn columns_follow_height_map() {
+		let generator = generator(7);
+		let dimension = DimensionType::overworld();
+		let sea_level = NoiseSettings::default().sea_level;
+
+		for (chunk_x, chunk_z) in [(0, 0), (-3, 5)] {
+			let chunk = generator.generate(chunk_x, chunk_z, &dimension).unwrap();
+
+			for (x, z) in [(0, 0), (15, 15), (4, 11)] {
+				let height = generator.height(chunk_x * 16 + x as i32, chunk_z * 16 + z as i32);
+				let surface = chunk.get_block(x, height, z);
+
+				assert_eq!(
+					chunk.get_block(x, dimension.min_y, z).name,
+					"minecraft:bedrock"
+				);
+				assert_eq!(chunk.get_block(x, height - 10, z).name, "minecraft:stone");
+				assert!(!surface.is_air());
+				match height <= sea_level + 1 {
+					true => assert_eq!(surface.name, "minecraft:sand"),
+					false => assert_eq!(surface.name, "minecraft:grass_block"),
+				}
+
+				// Над поверхностью только вода до уровня моря
+				for y in height + 1..height + 40 {
+					let block = chunk.get_block(x, y, z);
+					match y <= sea_level {
+						true => assert_eq!(block.name, "minecraft:water"),
+						false => assert!(block.is_air()),
+					}
+				}
+			}
+		}
+	}
+
+	#[test]
+	fn spawn_is_above_ground_and_water() {
+		let generator = generator(3);
+		let dimension = DimensionType::overworld();
+		let (_, y, _) = generator.spawn_point(&dimension);
+
+		assert!(y as i32 > generator.height(8, 8));
+		assert!(y as i32 > NoiseSettings::default().sea_level);
+	}
+}
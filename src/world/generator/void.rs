@@ -0,0 +1,41 @@
+use super::{WorldGenerator, empty_chunk};
+use crate::{
+	ServerError,
+	world::{
+		chunk::{Chunk, ChunkSection},
+		dimension::DimensionType,
+	},
+};
+
+// Пустой мир, только биом the_void
+pub struct VoidGenerator;
+
+impl WorldGenerator for VoidGenerator {
+	fn generate(&self, x: i32, z: i32, dimension: &DimensionType) -> Result<Chunk, ServerError> {
+		let mut chunk = empty_chunk(x, z, dimension);
+
+		for section in chunk.sections.iter_mut() {
+			*section = ChunkSection::empty(section.y, "minecraft:the_void");
+		}
+
+		Ok(chunk)
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn only_air() {
+		let dimension = DimensionType::overworld();
+		let chunk = VoidGenerator.generate(1, 1, &dimension).unwrap();
+
+		assert_eq!(chunk.sections.len(), dimension.section_count());
+		for section in chunk.sections.iter() {
+			assert!(section.blocks.palette().iter().all(|o| o.is_air()));
+			assert_eq!(section.get_biome(0, 0, 0), "minecraft:the_void");
+		}
+		assert_eq!(VoidGenerator.spawn_point(&dimension), (8.0, 0.0, 8.0));
+	}
+}
@@ -0,0 +1,100 @@
+use std::{collections::HashMap, fs, path::Path};
+
+use serde::Deserialize;
+
+use crate::ServerError;
+
+// Айдишник предмета в реестре minecraft:item
+pub type ItemId = i32;
+
+// Встроенные айдишники блоков-предметов 1.21.5, у которых есть стейты в BlockRegistry
+const BUILTIN_ITEMS: &[(&str, ItemId)] = &[
+	("minecraft:air", 0),
+	("minecraft:stone", 1),
+	("minecraft:granite", 2),
+	("minecraft:polished_granite", 3),
+	("minecraft:diorite", 4),
+	("minecraft:polished_diorite", 5),
+	("minecraft:andesite", 6),
+	("minecraft:polished_andesite", 7),
+	("minecraft:grass_block", 27),
+	("minecraft:dirt", 28),
+	("minecraft:coarse_dirt", 29),
+	("minecraft:podzol", 30),
+	("minecraft:cobblestone", 35),
+	("minecraft:oak_planks", 36),
+];
+
+// Репорт registries.json из ванильного генератора данных (лежит рядом с blocks.json)
+#[derive(Deserialize)]
+pub(super) struct ReportRegistry {
+	pub(super) entries: HashMap<String, ReportEntry>,
+}
+
+#[derive(Deserialize)]
+pub(super) struct ReportEntry {
+	pub(super) protocol_id: i32,
+}
+
+// Соответствие имя предмета <-> айди
+pub struct ItemRegistry {
+	ids: HashMap<String, ItemId>,
+	names: HashMap<ItemId, String>,
+}
+
+impl Default for ItemRegistry {
+	fn default() -> Self {
+		Self::new()
+	}
+}
+
+impl ItemRegistry {
+	/// Реестр только со встроенными предметами
+	pub fn new() -> ItemRegistry {
+		let mut registry = ItemRegistry {
+			ids: HashMap::new(),
+			names: HashMap::new(),
+		};
+
+		for (name, id) in BUILTIN_ITEMS {
+			registry.register(name, *id);
+		}
+
+		registry
+	}
+
+	/// Реестр из ванильного репорта registries.json (реестр minecraft:item)
+	pub fn load_report(path: impl AsRef<Path>) -> Result<ItemRegistry, ServerError> {
+		let content = fs::read_to_string(path).map_err(|e| ServerError::Storage(e.to_string()))?;
+		let mut report: HashMap<String, ReportRegistry> =
+			serde_json::from_str(&content).map_err(|e| ServerError::Storage(e.to_string()))?;
+
+		let items = report
+			.remove("minecraft:item")
+			.ok_or_else(|| ServerError::Storage("нет реестра minecraft:item".to_string()))?;
+
+		let mut registry = ItemRegistry {
+			ids: HashMap::new(),
+			names: HashMap::new(),
+		};
+
+		for (name, entry) in items.entries {
+			registry.register(&name, entry.protocol_id);
+		}
+
+		Ok(registry)
+	}
+
+	pub fn register(&mut self, name: &str, id: ItemId) {
+		self.ids.insert(name.to_string(), id);
+		self.names.insert(id, name.to_string());
+	}
+
+	pub fn id(&self, name: &str) -> Option<ItemId> {
+		self.ids.get(name).copied()
+	}
+
+	pub fn name(&self, id: ItemId) -> Option<&str> {
+		self.names.get(&id).map(|o| o.as_str())
+	}
+}
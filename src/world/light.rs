@@ -0,0 +1,820 @@
+use std::{
+	collections::VecDeque,
+	sync::{Arc, RwLock, RwLockWriteGuard},
+};
+
+use super::{
+	chunk::{BlockState, Chunk, SECTION_BLOCKS},
+	dimension::DimensionType,
+};
+
+// Движок света
+//
+// Свет хранится в секциях по полбайта на блок (как в Anvil), а считается здесь побайтово
+// для области 3x3 чанка: свет от источника уходит не дальше 15 блоков,
+// поэтому изменения в центральном чанке никогда не выходят за соседние чанки
+//
+// Высота мира берется из типа измерения, все чанки области одной высоты
+//
+// Чанки без isLightOn=1 считаются неосвещенными, в них свет не пишется и из них не берется,
+// когда такой чанк освещается, он сам подтягивает свет с границ соседей
+
+/// 4096 полубайтов
+pub const SECTION_LIGHT: usize = SECTION_BLOCKS / 2;
+
+const MAX_LIGHT: u8 = 15;
+
+// Порядок не важен, но вниз должно быть (0, -1, 0), см. next_level
+const DIRECTIONS: [(i32, i32, i32); 6] = [
+	(0, -1, 0),
+	(0, 1, 0),
+	(-1, 0, 0),
+	(1, 0, 0),
+	(0, 0, -1),
+	(0, 0, 1),
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LightKind {
+	Sky,
+	Block,
+}
+
+/// Маска секций произвольной длины, как BitSet в протоколе: бит i лежит в long i / 64
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SectionMask(Vec<u64>);
+
+impl SectionMask {
+	pub fn new() -> SectionMask {
+		SectionMask::default()
+	}
+
+	/// Маска с битами 0..count
+	pub fn all(count: usize) -> SectionMask {
+		let mut mask = SectionMask::new();
+		for bit in 0..count {
+			mask.set(bit);
+		}
+		mask
+	}
+
+	pub fn set(&mut self, bit: usize) {
+		let index = bit / 64;
+		if self.0.len() <= index {
+			self.0.resize(index + 1, 0);
+		}
+		self.0[index] |= 1 << (bit % 64);
+	}
+
+	pub fn contains(&self, bit: usize) -> bool {
+		self
+			.0
+			.get(bit / 64)
+			.is_some_and(|o| o & (1 << (bit % 64)) != 0)
+	}
+
+	pub fn is_empty(&self) -> bool {
+		self.0.iter().all(|o| *o == 0)
+	}
+
+	/// Номера выставленных битов по возрастанию
+	pub fn bits(&self) -> impl Iterator<Item = usize> + '_ {
+		(0..self.0.len() * 64).filter(|o| self.contains(*o))
+	}
+
+	/// Та же маска, сдвинутая на shift битов вверх
+	pub fn shifted(&self, shift: usize) -> SectionMask {
+		let mut mask = SectionMask::new();
+		for bit in self.bits() {
+			mask.set(bit + shift);
+		}
+		mask
+	}
+
+	/// Лонги для пакета, без нулей в конце
+	pub fn to_longs(&self) -> Vec<i64> {
+		let len = self.0.iter().rposition(|o| *o != 0).map_or(0, |o| o + 1);
+		self.0[..len].iter().map(|o| *o as i64).collect()
+	}
+}
+
+/// Секции чанка, в которых поменялся свет, бит 0 это самая нижняя секция мира
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LightChange {
+	pub x: i32,
+	pub z: i32,
+	pub sky: SectionMask,
+	pub block: SectionMask,
+}
+
+fn property<'a>(state: &'a BlockState, key: &str) -> Option<&'a str> {
+	state.properties.get(key).map(|o| o.as_str())
+}
+
+/// Сколько света излучает блок
+pub fn emission(state: &BlockState) -> u8 {
+	let name = state.name.trim_start_matches("minecraft:");
+	let lit = property(state, "lit") == Some("true");
+
+	match name {
+		"light" => property(state, "level")
+			.and_then(|o| o.parse().ok())
+			.unwrap_or(MAX_LIGHT),
+		"glowstone"
+		| "sea_lantern"
+		| "jack_o_lantern"
+		| "shroomlight"
+		| "lava"
+		| "fire"
+		| "beacon"
+		| "conduit"
+		| "lantern"
+		| "end_gateway"
+		| "end_portal"
+		| "ochre_froglight"
+		| "verdant_froglight"
+		| "pearlescent_froglight" => 15,
+		"torch" | "wall_torch" | "end_rod" => 14,
+		"nether_portal" => 11,
+		"soul_torch" | "soul_wall_torch" | "soul_lantern" | "soul_fire" | "crying_obsidian" => 10,
+		"enchanting_table" | "ender_chest" | "glow_lichen" => 7,
+		"amethyst_cluster" => 5,
+		"large_amethyst_bud" => 4,
+		"magma_block" => 3,
+		"medium_amethyst_bud" => 2,
+		"brewing_stand" | "dragon_egg" | "small_amethyst_bud" | "sculk_sensor" => 1,
+		"campfire" | "redstone_lamp" | "copper_bulb" if lit => 15,
+		"furnace" | "smoker" | "blast_furnace" if lit => 13,
+		"soul_campfire" if lit => 10,
+		"redstone_ore" | "deepslate_redstone_ore" if lit => 9,
+		"redstone_torch" | "redstone_wall_torch" if lit => 7,
+		"cave_vines" | "cave_vines_plant" if property(state, "berries") == Some("true") => 14,
+		_ => 0,
+	}
+}
+
+/// Сколько света блок забирает, 15 - не пропускает совсем
+pub fn opacity(state: &BlockState) -> u8 {
+	let name = state.name.trim_start_matches("minecraft:");
+
+	if state.is_air() {
+		return 0;
+	}
+
+	match name {
+		"tinted_glass" => return MAX_LIGHT,
+		"water" | "bubble_column" | "ice" | "frosted_ice" | "cobweb" | "slime_block"
+		| "honey_block" | "lava" => return 1,
+		"glass"
+		| "glass_pane"
+		| "candle"
+		| "iron_bars"
+		| "chain"
+		| "barrier"
+		| "light"
+		| "torch"
+		| "wall_torch"
+		| "soul_torch"
+		| "soul_wall_torch"
+		| "redstone_torch"
+		| "redstone_wall_torch"
+		| "lantern"
+		| "soul_lantern"
+		| "end_rod"
+		| "fire"
+		| "soul_fire"
+		| "ladder"
+		| "lever"
+		| "vine"
+		| "snow"
+		| "short_grass"
+		| "tall_grass"
+		| "fern"
+		| "large_fern"
+		| "dead_bush"
+		| "dandelion"
+		| "poppy"
+		| "blue_orchid"
+		| "allium"
+		| "azure_bluet"
+		| "oxeye_daisy"
+		| "cornflower"
+		| "lily_of_the_valley"
+		| "sunflower"
+		| "lilac"
+		| "rose_bush"
+		| "peony"
+		| "sugar_cane"
+		| "redstone_wire"
+		| "repeater"
+		| "comparator"
+		| "tripwire"
+		| "tripwire_hook"
+		| "rail"
+		| "powered_rail"
+		| "detector_rail"
+		| "activator_rail"
+		| "cactus"
+		| "bamboo"
+		| "scaffolding"
+		| "nether_portal"
+		| "end_portal"
+		| "end_gateway"
+		| "beacon"
+		| "conduit"
+		| "seagrass"
+		| "tall_seagrass"
+		| "kelp"
+		| "kelp_plant"
+		| "glow_lichen"
+		| "cave_vines"
+		| "cave_vines_plant"
+		| "brewing_stand"
+		| "enchanting_table"
+		| "flower_pot" => return 0,
+		_ => {}
+	}
+
+	if name.ends_with("_leaves") {
+		return 1;
+	}
+
+	const TRANSPARENT_SUFFIXES: &[&str] = &[
+		"_glass",
+		"_glass_pane",
+		"_sapling",
+		"_tulip",
+		"_button",
+		"_pressure_plate",
+		"_sign",
+		"_fence",
+		"_fence_gate",
+		"_wall",
+		"_door",
+		"_trapdoor",
+		"_carpet",
+		"_slab",
+		"_stairs",
+		"_banner",
+		"_candle",
+		"_coral",
+		"_coral_fan",
+		"_mushroom",
+		"_amethyst_bud",
+		"amethyst_cluster",
+		"_head",
+		"_skull",
+		"_bed",
+		"_torch",
+		"_rail",
+		"_bars",
+	];
+
+	if TRANSPARENT_SUFFIXES.iter().any(|o| name.ends_with(o)) {
+		0
+	} else {
+		MAX_LIGHT
+	}
+}
+
+// Освещенный чанк внутри области, свет и свойства блоков развернуты побайтово
+struct LightChunk<'a> {
+	chunk: RwLockWriteGuard<'a, Chunk>,
+	min_section: i32,
+	sections: usize,
+	opacity: Vec<u8>,
+	emission: Vec<u8>,
+	sky: Vec<u8>,
+	block: Vec<u8>,
+	sky_changed: SectionMask,
+	block_changed: SectionMask,
+}
+
+impl<'a> LightChunk<'a> {
+	fn new(chunk: RwLockWriteGuard<'a, Chunk>, dimension: &DimensionType) -> LightChunk<'a> {
+		let (min_section, sections) = (dimension.min_section(), dimension.section_count());
+		let size = sections * SECTION_BLOCKS;
+		let mut light = LightChunk {
+			chunk,
+			min_section,
+			sections,
+			opacity: vec![0; size],
+			emission: vec![0; size],
+			sky: vec![0; size],
+			block: vec![0; size],
+			sky_changed: SectionMask::new(),
+			block_changed: SectionMask::new(),
+		};
+
+		for section_index in 0..sections {
+			let offset = section_index * SECTION_BLOCKS;
+			let Some(section) = light.chunk.section(min_section + section_index as i32) else {
+				continue;
+			};
+
+			// Свойства считаем один раз на элемент палитры
+			let palette = section
+				.blocks
+				.palette()
+				.iter()
+				.map(|o| (opacity(o), emission(o)))
+				.collect::<Vec<_>>();
+
+			if let [(opacity, emission)] = palette[..] {
+				light.opacity[offset..offset + SECTION_BLOCKS].fill(opacity);
+				light.emission[offset..offset + SECTION_BLOCKS].fill(emission);
+				continue;
+			}
+
+			for i in 0..SECTION_BLOCKS {
+				let (opacity, emission) = palette[section.blocks.palette_index(i)];
+				light.opacity[offset + i] = opacity;
+				light.emission[offset + i] = emission;
+			}
+		}
+
+		read_light(&light.chunk, min_section, &mut light.sky, &mut light.block);
+
+		light
+	}
+
+	fn light(&self, kind: LightKind) -> &Vec<u8> {
+		match kind {
+			LightKind::Sky => &self.sky,
+			LightKind::Block => &self.block,
+		}
+	}
+
+	fn light_mut(&mut self, kind: LightKind) -> &mut Vec<u8> {
+		match kind {
+			LightKind::Sky => &mut self.sky,
+			LightKind::Block => &mut self.block,
+		}
+	}
+
+	fn mark_changed(&mut self, kind: LightKind, section_index: usize) {
+		match kind {
+			LightKind::Sky => self.sky_changed.set(section_index),
+			LightKind::Block => self.block_changed.set(section_index),
+		}
+	}
+
+	// Самая верхняя граница секций, где есть непрозрачные блоки
+	fn top(&self) -> i32 {
+		let min_y = self.min_section * 16;
+		(0..self.sections)
+			.rev()
+			.find(|i| {
+				self.opacity[i * SECTION_BLOCKS..(i + 1) * SECTION_BLOCKS]
+					.iter()
+					.any(|o| *o > 0)
+			})
+			.map(|i| min_y + (i as i32 + 1) * 16)
+			.unwrap_or(min_y)
+	}
+
+	// Пишет измененные секции обратно в чанк
+	fn finish(mut self) -> Option<LightChange> {
+		if self.sky_changed.is_empty() && self.block_changed.is_empty() {
+			return None;
+		}
+
+		for section_index in 0..self.sections {
+			let sky = self.sky_changed.contains(section_index);
+			let block = self.block_changed.contains(section_index);
+			if !sky && !block {
+				continue;
+			}
+
+			let range = section_index * SECTION_BLOCKS..(section_index + 1) * SECTION_BLOCKS;
+			let sky_array = pack(&self.sky[range.clone()]);
+			let block_array = pack(&self.block[range]);
+
+			let section = self
+				.chunk
+				.section_or_insert(self.min_section + section_index as i32);
+			if sky {
+				section.sky_light = Some(sky_array);
+			}
+			if block {
+				section.block_light = Some(block_array);
+			}
+		}
+
+		self.chunk.set_light_on(true);
+		self.chunk.mark_dirty();
+
+		Some(LightChange {
+			x: self.chunk.x,
+			z: self.chunk.z,
+			sky: self.sky_changed,
+			block: self.block_changed,
+		})
+	}
+}
+
+// Свет всех секций мира побайтово, сколько секций - по длине массивов
+//
+// Секции без SkyLight наследуют нижний слой секции выше (над миром всегда 15),
+// секции без BlockLight темные
+fn read_light(chunk: &Chunk, min_section: i32, sky: &mut [u8], block: &mut [u8]) {
+	let mut sky_above = [MAX_LIGHT; 256];
+
+	for section_index in (0..sky.len() / SECTION_BLOCKS).rev() {
+		let range = section_index * SECTION_BLOCKS..(section_index + 1) * SECTION_BLOCKS;
+		let section = chunk.section(min_section + section_index as i32);
+
+		match section.and_then(|o| o.sky_light.as_ref()) {
+			Some(array) => unpack(array, &mut sky[range.clone()]),
+			None => {
+				for layer in sky[range.clone()].chunks_exact_mut(256) {
+					layer.copy_from_slice(&sky_above);
+				}
+			}
+		}
+		if let Some(array) = section.and_then(|o| o.block_light.as_ref()) {
+			unpack(array, &mut block[range.clone()]);
+		}
+
+		sky_above.copy_from_slice(&sky[range.start..range.start + 256]);
+	}
+}
+
+/// Небесный и блочный свет секций мира снизу вверх, по SECTION_LIGHT байт на секцию
+pub fn section_light(chunk: &Chunk, dimension: &DimensionType) -> (Vec<Vec<u8>>, Vec<Vec<u8>>) {
+	let size = dimension.section_count() * SECTION_BLOCKS;
+	let (mut sky, mut block) = (vec![0; size], vec![0; size]);
+	read_light(chunk, dimension.min_section(), &mut sky, &mut block);
+
+	(
+		sky.chunks(SECTION_BLOCKS).map(pack).collect(),
+		block.chunks(SECTION_BLOCKS).map(pack).collect(),
+	)
+}
+
+/// Полубайты из Anvil в побайтовый массив, четный индекс в младшей половине
+fn unpack(array: &[u8], out: &mut [u8]) {
+	out.fill(0);
+	for (pair, byte) in out.chunks_exact_mut(2).zip(array.iter()) {
+		pair[0] = byte & 15;
+		pair[1] = byte >> 4;
+	}
+}
+
+fn pack(light: &[u8]) -> Vec<u8> {
+	light
+		.chunks(2)
+		.map(|o| (o[0] & 15) | ((o[1] & 15) << 4))
+		.collect()
+}
+
+// Свет в соседнем блоке, если свет идет из блока со светом `level` в направлении `direction`
+fn next_level(kind: LightKind, level: u8, direction: (i32, i32, i32), opacity: u8) -> u8 {
+	if opacity >= MAX_LIGHT {
+		return 0;
+	}
+
+	// Небесный свет идет вниз не ослабевая, пока не встретит что-то полупрозрачное
+	if kind == LightKind::Sky && direction.1 == -1 && level == MAX_LIGHT && opacity == 0 {
+		return MAX_LIGHT;
+	}
+
+	level.saturating_sub(opacity.max(1))
+}
+
+/// Область 3x3 чанка вокруг центрального, в которой считается свет
+pub struct LightEngine<'a> {
+	center_x: i32,
+	center_z: i32,
+	min_section: i32,
+	sections: usize,
+	chunks: Vec<Option<LightChunk<'a>>>, // по строкам, начиная с (x - 1, z - 1)
+}
+
+impl<'a> LightEngine<'a> {
+	/// Блокирует чанки области на запись, `chunks` это 9 чанков по строкам начиная с (x - 1, z - 1)
+	///
+	/// Центральный чанк берется всегда, соседи только загруженные и освещенные
+	pub fn new(
+		center_x: i32,
+		center_z: i32,
+		chunks: &'a [Option<Arc<RwLock<Chunk>>>],
+		dimension: &DimensionType,
+	) -> LightEngine<'a> {
+		let chunks = chunks
+			.iter()
+			.enumerate()
+			.map(|(i, chunk)| {
+				let chunk = chunk.as_ref()?.write().unwrap();
+				if i == 4 || chunk.is_light_on() {
+					Some(LightChunk::new(chunk, dimension))
+				} else {
+					None
+				}
+			})
+			.collect();
+
+		LightEngine {
+			center_x,
+			center_z,
+			min_section: dimension.min_section(),
+			sections: dimension.section_count(),
+			chunks,
+		}
+	}
+
+	// Чанк области, секция и индекс блока в развернутых массивах
+	fn locate(&self, x: i32, y: i32, z: i32) -> Option<(usize, usize)> {
+		let chunk_x = (x >> 4) - self.center_x + 1;
+		let chunk_z = (z >> 4) - self.center_z + 1;
+		if !(0..3).contains(&chunk_x) || !(0..3).contains(&chunk_z) {
+			return None;
+		}
+
+		let section_index = (y >> 4) - self.min_section;
+		if !(0..self.sections as i32).contains(&section_index) {
+			return None;
+		}
+
+		let chunk = (chunk_z * 3 + chunk_x) as usize;
+		self.chunks[chunk].as_ref()?;
+
+		let index = section_index as usize * SECTION_BLOCKS
+			+ (((y & 15) << 8) | ((z & 15) << 4) | (x & 15)) as usize;
+
+		Some((chunk, index))
+	}
+
+	// Низ мира и первая высота над ним
+	fn height(&self) -> (i32, i32) {
+		let min_y = self.min_section * 16;
+		(min_y, min_y + self.sections as i32 * 16)
+	}
+
+	fn get(&self, kind: LightKind, chunk: usize, index: usize) -> u8 {
+		self.chunks[chunk].as_ref().unwrap().light(kind)[index]
+	}
+
+	fn set(&mut self, kind: LightKind, chunk: usize, index: usize, level: u8) {
+		let chunk = self.chunks[chunk].as_mut().unwrap();
+		if chunk.light(kind)[index] != level {
+			chunk.light_mut(kind)[index] = level;
+			chunk.mark_changed(kind, index / SECTION_BLOCKS);
+		}
+	}
+
+	fn opacity(&self, chunk: usize, index: usize) -> u8 {
+		self.chunks[chunk].as_ref().unwrap().opacity[index]
+	}
+
+	fn emission(&self, chunk: usize, index: usize) -> u8 {
+		self.chunks[chunk].as_ref().unwrap().emission[index]
+	}
+
+	/// Считает свет в центральном чанке с нуля и распространяет его на соседей
+	pub fn light_center(&mut self) {
+		let base_x = self.center_x * 16;
+		let base_z = self.center_z * 16;
+		let (min_y, max_y) = self.height();
+		let sections = self.sections;
+
+		// Выше самого высокого непрозрачного блока области небесный свет везде 15,
+		// распространять его оттуда бесполезно
+		let top = self
+			.chunks
+			.iter()
+			.flatten()
+			.map(|o| o.top())
+			.max()
+			.unwrap_or(min_y);
+
+		let center = self.chunks[4].as_mut().unwrap();
+		center.block.copy_from_slice(&center.emission);
+		center.sky_changed = SectionMask::all(sections);
+		center.block_changed = SectionMask::all(sections);
+
+		// Небо сверху вниз по колонкам, пока над секцией везде 15 и она прозрачная - просто заливаем
+		let mut levels = [MAX_LIGHT; 256];
+		for section_index in (0..sections).rev() {
+			let range = section_index * SECTION_BLOCKS..(section_index + 1) * SECTION_BLOCKS;
+
+			if levels.iter().all(|o| *o == MAX_LIGHT)
+				&& center.opacity[range.clone()].iter().all(|o| *o == 0)
+			{
+				center.sky[range].fill(MAX_LIGHT);
+				continue;
+			}
+
+			for y in (0..16).rev() {
+				for (column, level) in levels.iter_mut().enumerate() {
+					let index = range.start + (y << 8) + column;
+					*level = next_level(LightKind::Sky, *level, (0, -1, 0), center.opacity[index]);
+					center.sky[index] = *level;
+				}
+			}
+		}
+
+		let mut sky = VecDeque::new();
+		let mut block = VecDeque::new();
+
+		// Источники блочного света внутри чанка
+		for (index, emission) in self.chunks[4].as_ref().unwrap().emission.iter().enumerate() {
+			if *emission > 1 {
+				let (section_index, index) = (index / SECTION_BLOCKS, index % SECTION_BLOCKS);
+				block.push_back((
+					base_x + (index & 15) as i32,
+					min_y + (section_index * 16 + (index >> 8)) as i32,
+					base_z + ((index >> 4) & 15) as i32,
+				));
+			}
+		}
+
+		for y in min_y..max_y {
+			if y <= top {
+				for z in base_z..base_z + 16 {
+					for x in base_x..base_x + 16 {
+						let (chunk, index) = self.locate(x, y, z).unwrap();
+						if self.get(LightKind::Sky, chunk, index) > 1 {
+							sky.push_back((x, y, z));
+						}
+					}
+				}
+			}
+
+			// Свет с границ соседних чанков
+			for i in 0..16 {
+				for (x, z) in [
+					(base_x - 1, base_z + i),
+					(base_x + 16, base_z + i),
+					(base_x + i, base_z - 1),
+					(base_x + i, base_z + 16),
+				] {
+					let Some((chunk, index)) = self.locate(x, y, z) else {
+						continue;
+					};
+					if y <= top && self.get(LightKind::Sky, chunk, index) > 1 {
+						sky.push_back((x, y, z));
+					}
+					if self.get(LightKind::Block, chunk, index) > 1 {
+						block.push_back((x, y, z));
+					}
+				}
+			}
+		}
+
+		self.increase(LightKind::Sky, sky);
+		self.increase(LightKind::Block, block);
+	}
+
+	/// Пересчитывает свет вокруг блока, который уже поменялся в чанке (координаты абсолютные)
+	pub fn update_block(&mut self, x: i32, y: i32, z: i32) {
+		if self.locate(x, y, z).is_none() {
+			return;
+		}
+
+		self.update(LightKind::Sky, x, y, z);
+		self.update(LightKind::Block, x, y, z);
+	}
+
+	fn update(&mut self, kind: LightKind, x: i32, y: i32, z: i32) {
+		let (chunk, index) = self.locate(x, y, z).unwrap();
+
+		let mut increase = self.decrease(kind, x, y, z);
+
+		match kind {
+			LightKind::Block => {
+				let emission = self.emission(chunk, index);
+				if emission > self.get(kind, chunk, index) {
+					self.set(kind, chunk, index, emission);
+				}
+				increase.push_back((x, y, z));
+			}
+			LightKind::Sky => {
+				// Свет заходит обратно от соседей, а на самом верху мира - прямо с неба
+				for (dx, dy, dz) in DIRECTIONS {
+					if self.locate(x + dx, y + dy, z + dz).is_some() {
+						increase.push_back((x + dx, y + dy, z + dz));
+					}
+				}
+				if y == self.height().1 - 1 {
+					let level = next_level(kind, MAX_LIGHT, (0, -1, 0), self.opacity(chunk, index));
+					if level > self.get(kind, chunk, index) {
+						self.set(kind, chunk, index, level);
+						increase.push_back((x, y, z));
+					}
+				}
+			}
+		}
+
+		self.increase(kind, increase);
+	}
+
+	// Гасит свет, который зависел от блока, возвращает блоки, откуда свет надо распространить заново
+	fn decrease(&mut self, kind: LightKind, x: i32, y: i32, z: i32) -> VecDeque<(i32, i32, i32)> {
+		let mut increase = VecDeque::new();
+		let mut queue = VecDeque::new();
+
+		let (chunk, index) = self.locate(x, y, z).unwrap();
+		queue.push_back((x, y, z, self.get(kind, chunk, index)));
+		self.set(kind, chunk, index, 0);
+
+		while let Some((x, y, z, level)) = queue.pop_front() {
+			for direction in DIRECTIONS {
+				let (nx, ny, nz) = (x + direction.0, y + direction.1, z + direction.2);
+				let Some((chunk, index)) = self.locate(nx, ny, nz) else {
+					continue;
+				};
+
+				let neighbor = self.get(kind, chunk, index);
+				if neighbor == 0 {
+					continue;
+				}
+
+				let dependent = neighbor < level
+					|| (kind == LightKind::Sky
+						&& direction.1 == -1
+						&& level == MAX_LIGHT
+						&& neighbor == MAX_LIGHT);
+
+				if dependent {
+					self.set(kind, chunk, index, 0);
+					queue.push_back((nx, ny, nz, neighbor));
+
+					// Погашенный источник светит снова сам по себе
+					if kind == LightKind::Block {
+						let emission = self.emission(chunk, index);
+						if emission > 0 {
+							self.set(kind, chunk, index, emission);
+							increase.push_back((nx, ny, nz));
+						}
+					}
+				} else {
+					increase.push_back((nx, ny, nz));
+				}
+			}
+		}
+
+		increase
+	}
+
+	// Обычное распространение света в ширину
+	fn increase(&mut self, kind: LightKind, mut queue: VecDeque<(i32, i32, i32)>) {
+		while let Some((x, y, z)) = queue.pop_front() {
+			let (chunk, index) = self.locate(x, y, z).unwrap();
+			let level = self.get(kind, chunk, index);
+			if level <= 1 {
+				continue;
+			}
+
+			for direction in DIRECTIONS {
+				let (nx, ny, nz) = (x + direction.0, y + direction.1, z + direction.2);
+				let Some((chunk, index)) = self.locate(nx, ny, nz) else {
+					continue;
+				};
+
+				let next = next_level(kind, level, direction, self.opacity(chunk, index));
+				if next > self.get(kind, chunk, index) {
+					self.set(kind, chunk, index, next);
+					queue.push_back((nx, ny, nz));
+				}
+			}
+		}
+	}
+
+	/// Записывает свет в чанки и отпускает их, возвращает что поменялось
+	pub fn finish(self) -> Vec<LightChange> {
+		self
+			.chunks
+			.into_iter()
+			.flatten()
+			.filter_map(LightChunk::finish)
+			.collect()
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn section_mask_grows_past_64_bits() {
+		let mut mask = SectionMask::new();
+		mask.set(0);
+		mask.set(63);
+		mask.set(64);
+		mask.set(255);
+
+		assert!(mask.contains(64) && mask.contains(255) && !mask.contains(65));
+		assert_eq!(mask.bits().collect::<Vec<_>>(), vec![0, 63, 64, 255]);
+		assert_eq!(mask.to_longs(), vec![i64::MIN + 1, 1, 0, i64::MIN]);
+		assert_eq!(mask.shifted(1).bits().last(), Some(256));
+	}
+
+	#[test]
+	fn section_mask_longs() {
+		assert!(SectionMask::new().to_longs().is_empty());
+		assert_eq!(SectionMask::all(26).to_longs(), vec![(1 << 26) - 1]);
+		assert_eq!(SectionMask::all(256).to_longs(), vec![-1; 4]);
+		assert_eq!(SectionMask::all(66).to_longs(), vec![-1, 3]);
+	}
+}
@@ -0,0 +1,11 @@
+pub mod block;
+pub mod chunk;
+pub mod datapack;
+pub mod dimension;
+pub mod entity;
+pub mod generator;
+pub mod item;
+pub mod light;
+pub mod region;
+pub mod registry;
+pub mod storage;
@@ -0,0 +1,700 @@
+use std::{
+	fs::{self, File, OpenOptions},
+	io::{Read, Seek, SeekFrom, Write},
+	path::{Path, PathBuf},
+	time::{SystemTime, UNIX_EPOCH},
+};
+
+use flate2::{
+	Compression as FlateLevel,
+	read::{GzDecoder, ZlibDecoder},
+	write::{GzEncoder, ZlibEncoder},
+};
+use serde::{Deserialize, Serialize};
+
+use crate::ServerError;
+
+const SECTOR_SIZE: usize = 4096;
+const HEADER_SECTORS: usize = 2; // таблица оффсетов + таблица таймстампов
+const EXTERNAL_FLAG: u8 = 0x80; // чанк лежит в отдельном файле c.<x>.<z>.mcc
+const MAX_CHUNK_SIZE: usize = 32 * 1024 * 1024; // больше распакованного NBT чанка не читаем
+
+// Сжатие чанков в регион файле, айдишники как в ванилле
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Compression {
+	Gzip = 1,
+	Zlib = 2,
+	None = 3,
+	Lz4 = 4,
+}
+
+impl Compression {
+	pub fn from_id(id: u8) -> Result<Compression, ServerError> {
+		match id {
+			1 => Ok(Compression::Gzip),
+			2 => Ok(Compression::Zlib),
+			3 => Ok(Compression::None),
+			4 => Ok(Compression::Lz4),
+			id => Err(ServerError::Storage(format!(
+				"Неизвестный тип сжатия чанка: {id}"
+			))),
+		}
+	}
+
+	pub fn compress(self, data: &[u8]) -> Result<Vec<u8>, ServerError> {
+		match self {
+			Compression::Gzip => {
+				let mut encoder = GzEncoder::new(Vec::new(), FlateLevel::default());
+				encoder.write_all(data).map_err(storage_error)?;
+				encoder.finish().map_err(storage_error)
+			}
+			Compression::Zlib => {
+				let mut encoder = ZlibEncoder::new(Vec::new(), FlateLevel::default());
+				encoder.write_all(data).map_err(storage_error)?;
+				encoder.finish().map_err(storage_error)
+			}
+			Compression::None => Ok(data.to_vec()),
+			Compression::Lz4 => Ok(lz4_block::compress(data)),
+		}
+	}
+
+	/// Распаковывает NBT чанка, больше MAX_CHUNK_SIZE считается битым чанком
+	pub fn decompress(self, data: &[u8]) -> Result<Vec<u8>, ServerError> {
+		let limit = MAX_CHUNK_SIZE as u64 + 1;
+		let mut output = Vec::new();
+		match self {
+			Compression::Gzip => {
+				GzDecoder::new(data)
+					.take(limit)
+					.read_to_end(&mut output)
+					.map_err(storage_error)?;
+			}
+			Compression::Zlib => {
+				ZlibDecoder::new(data)
+					.take(limit)
+					.read_to_end(&mut output)
+					.map_err(storage_error)?;
+			}
+			Compression::None => output.extend_from_slice(data),
+			Compression::Lz4 => output = lz4_block::decompress(data)?,
+		}
+
+		if output.len() > MAX_CHUNK_SIZE {
+			return Err(ServerError::Storage(format!(
+				"Чанк больше {MAX_CHUNK_SIZE} байт"
+			)));
+		}
+		Ok(output)
+	}
+}
+
+// Регион файл r.<x>.<z>.mca, хранит 32x32 чанка
+//
+// Формат: https://minecraft.wiki/w/Region_file_format
+// Первые два сектора - заголовок, дальше данные чанков, выровненные по секторам в 4 KiB
+pub struct RegionFile {
+	path: PathBuf,
+	file: File,
+	locations: [u32; 1024], // оффсет в секторах << 8 | кол-во секторов
+	timestamps: [u32; 1024],
+	used: Vec<bool>, // какие сектора заняты
+}
+
+impl RegionFile {
+	/// Открывает регион файл, если файла нет - создает пустой
+	pub fn open(path: impl AsRef<Path>) -> Result<RegionFile, ServerError> {
+		let path = path.as_ref().to_path_buf();
+
+		let mut file = OpenOptions::new()
+			.read(true)
+			.write(true)
+			.create(true)
+			.truncate(false)
+			.open(&path)
+			.map_err(storage_error)?;
+
+		let mut header = vec![0u8; SECTOR_SIZE * HEADER_SECTORS];
+		let length = file.metadata().map_err(storage_error)?.len() as usize;
+
+		if length < header.len() {
+			// Новый или обрезанный файл, пишем пустой заголовок
+			file.set_len(0).map_err(storage_error)?;
+			file.write_all(&header).map_err(storage_error)?;
+		} else {
+			file.read_exact(&mut header).map_err(storage_error)?;
+		}
+
+		let mut locations = [0u32; 1024];
+		let mut timestamps = [0u32; 1024];
+
+		for i in 0..1024 {
+			locations[i] = u32::from_be_bytes(header[i * 4..i * 4 + 4].try_into().unwrap());
+			timestamps[i] = u32::from_be_bytes(
+				header[SECTOR_SIZE + i * 4..SECTOR_SIZE + i * 4 + 4]
+					.try_into()
+					.unwrap(),
+			);
+		}
+
+		let total_sectors = length.max(header.len()).div_ceil(SECTOR_SIZE);
+		let mut used = vec![false; total_sectors];
+		used[..HEADER_SECTORS].fill(true);
+
+		for location in locations.iter_mut() {
+			let (offset, count) = ((*location >> 8) as usize, (*location & 0xFF) as usize);
+			if *location == 0 {
+				continue;
+			}
+			if count == 0 || offset < HEADER_SECTORS || offset + count > total_sectors {
+				// Битая запись, считаем что чанка нет
+				*location = 0;
+				continue;
+			}
+			used[offset..offset + count].fill(true);
+		}
+
+		Ok(RegionFile {
+			path,
+			file,
+			locations,
+			timestamps,
+			used,
+		})
+	}
+
+	/// Координаты региона, в котором лежит чанк
+	pub fn region_pos(chunk_x: i32, chunk_z: i32) -> (i32, i32) {
+		(chunk_x >> 5, chunk_z >> 5)
+	}
+
+	/// Имя файла региона, например r.0.-1.mca
+	pub fn file_name(region_x: i32, region_z: i32) -> String {
+		format!("r.{region_x}.{region_z}.mca")
+	}
+
+	fn index(chunk_x: i32, chunk_z: i32) -> usize {
+		((chunk_x & 31) + (chunk_z & 31) * 32) as usize
+	}
+
+	pub fn has_chunk(&self, chunk_x: i32, chunk_z: i32) -> bool {
+		self.locations[Self::index(chunk_x, chunk_z)] != 0
+	}
+
+	/// Время последнего сохранения чанка в секундах, 0 если чанка нет
+	pub fn timestamp(&self, chunk_x: i32, chunk_z: i32) -> u32 {
+		self.timestamps[Self::index(chunk_x, chunk_z)]
+	}
+
+	/// Читает и распаковывает NBT чанка, None если чанк еще не сохранялся
+	pub fn read_chunk(&mut self, chunk_x: i32, chunk_z: i32) -> Result<Option<Vec<u8>>, ServerError> {
+		let location = self.locations[Self::index(chunk_x, chunk_z)];
+		if location == 0 {
+			return Ok(None);
+		}
+
+		let (offset, count) = ((location >> 8) as usize, (location & 0xFF) as usize);
+
+		let mut data = vec![0u8; count * SECTOR_SIZE];
+		self
+			.file
+			.seek(SeekFrom::Start((offset * SECTOR_SIZE) as u64))
+			.map_err(storage_error)?;
+		self.file.read_exact(&mut data).map_err(storage_error)?;
+
+		let broken = || {
+			ServerError::Storage(format!(
+				"Битый чанк {chunk_x} {chunk_z} в {}",
+				self.path.display()
+			))
+		};
+
+		let length = data
+			.get(0..4)
+			.map(|o| u32::from_be_bytes(o.try_into().unwrap()) as usize)
+			.ok_or_else(broken)?;
+		if length == 0 || length + 4 > data.len() {
+			return Err(broken());
+		}
+
+		let compression = data[4];
+		let payload = if compression & EXTERNAL_FLAG != 0 {
+			fs::read(self.external_path(chunk_x, chunk_z)).map_err(storage_error)?
+		} else {
+			data[5..4 + length].to_vec()
+		};
+
+		Compression::from_id(compression & !EXTERNAL_FLAG)?
+			.decompress(&payload)
+			.map(Some)
+	}
+
+	/// Сжимает и записывает NBT чанка
+	///
+	/// Если чанк не влезает в 255 секторов (1 MiB) - он уходит в отдельный .mcc файл, как в ванилле
+	///
+	/// Новые данные пишутся в свободные сектора и только потом на них переключается заголовок,
+	/// так что если сервер упадет посреди записи, в файле останется старая версия чанка
+	pub fn write_chunk(
+		&mut self,
+		chunk_x: i32,
+		chunk_z: i32,
+		nbt: &[u8],
+		compression: Compression,
+	) -> Result<(), ServerError> {
+		let payload = compression.compress(nbt)?;
+		let index = Self::index(chunk_x, chunk_z);
+		let external_path = self.external_path(chunk_x, chunk_z);
+
+		let mut data = Vec::with_capacity(payload.len() + 5);
+		let external = (payload.len() + 5).div_ceil(SECTOR_SIZE) > 255;
+
+		if external {
+			// Старый .mcc может быть еще нужен заголовку, подменяем его целиком
+			let temp = external_path.with_extension("mcc.tmp");
+			fs::write(&temp, &payload).map_err(storage_error)?;
+			fs::rename(&temp, &external_path).map_err(storage_error)?;
+			data.extend_from_slice(&1u32.to_be_bytes());
+			data.push(compression as u8 | EXTERNAL_FLAG);
+		} else {
+			data.extend_from_slice(&(payload.len() as u32 + 1).to_be_bytes());
+			data.push(compression as u8);
+			data.extend_from_slice(&payload);
+		}
+
+		let count = data.len().div_ceil(SECTOR_SIZE);
+		data.resize(count * SECTOR_SIZE, 0);
+
+		// Старые сектора еще заняты, поэтому новые с ними не пересекутся
+		let offset = self.allocate(count);
+
+		self
+			.file
+			.seek(SeekFrom::Start((offset * SECTOR_SIZE) as u64))
+			.map_err(storage_error)?;
+		self.file.write_all(&data).map_err(storage_error)?;
+
+		let timestamp = SystemTime::now()
+			.duration_since(UNIX_EPOCH)
+			.map(|o| o.as_secs() as u32)
+			.unwrap_or_default();
+
+		let old = self.locations[index];
+		self.locations[index] = ((offset as u32) << 8) | count as u32;
+		self.timestamps[index] = timestamp;
+
+		self.write_header(index)?;
+
+		if old != 0 {
+			let (offset, old_count) = ((old >> 8) as usize, (old & 0xFF) as usize);
+			self.used[offset..offset + old_count].fill(false);
+		}
+		if !external {
+			let _ = fs::remove_file(external_path);
+		}
+
+		Ok(())
+	}
+
+	/// Удаляет чанк из региона
+	pub fn delete_chunk(&mut self, chunk_x: i32, chunk_z: i32) -> Result<(), ServerError> {
+		let index = Self::index(chunk_x, chunk_z);
+		let location = self.locations[index];
+		if location == 0 {
+			return Ok(());
+		}
+
+		let (offset, count) = ((location >> 8) as usize, (location & 0xFF) as usize);
+		self.used[offset..offset + count].fill(false);
+		self.locations[index] = 0;
+		self.timestamps[index] = 0;
+		let _ = fs::remove_file(self.external_path(chunk_x, chunk_z));
+
+		self.write_header(index)
+	}
+
+	pub fn flush(&mut self) -> Result<(), ServerError> {
+		self.file.sync_data().map_err(storage_error)
+	}
+
+	// Первый свободный промежуток нужной длины, либо конец файла
+	fn allocate(&mut self, count: usize) -> usize {
+		let mut run = 0;
+		for (i, used) in self.used.iter().enumerate() {
+			run = if *used { 0 } else { run + 1 };
+			if run == count {
+				let offset = i + 1 - count;
+				self.used[offset..offset + count].fill(true);
+				return offset;
+			}
+		}
+
+		// Доращиваем файл, захватывая свободный хвост если он есть
+		let offset = self.used.len() - run;
+		self.used.resize(offset + count, true);
+		self.used[offset..].fill(true);
+		offset
+	}
+
+	fn write_header(&mut self, index: usize) -> Result<(), ServerError> {
+		self
+			.file
+			.seek(SeekFrom::Start((index * 4) as u64))
+			.map_err(storage_error)?;
+		self
+			.file
+			.write_all(&self.locations[index].to_be_bytes())
+			.map_err(storage_error)?;
+		self
+			.file
+			.seek(SeekFrom::Start((SECTOR_SIZE + index * 4) as u64))
+			.map_err(storage_error)?;
+		self
+			.file
+			.write_all(&self.timestamps[index].to_be_bytes())
+			.map_err(storage_error)
+	}
+
+	fn external_path(&self, chunk_x: i32, chunk_z: i32) -> PathBuf {
+		self
+			.path
+			.with_file_name(format!("c.{chunk_x}.{chunk_z}.mcc"))
+	}
+}
+
+fn storage_error(error: impl std::fmt::Display) -> ServerError {
+	ServerError::Storage(error.to_string())
+}
+
+// Сжатие LZ4 в майнкрафте - это не стандартный lz4 frame,
+// а формат LZ4BlockOutputStream из lz4-java:
+// блоки с заголовком "LZ4Block", токен, длины и xxhash32 чексумма, в конце пустой блок
+mod lz4_block {
+	use super::storage_error;
+	use crate::ServerError;
+	use twox_hash::XxHash32;
+
+	const MAGIC: &[u8; 8] = b"LZ4Block";
+	const HEADER_SIZE: usize = MAGIC.len() + 1 + 4 + 4 + 4;
+	const METHOD_RAW: u8 = 0x10;
+	const METHOD_LZ4: u8 = 0x20;
+	const SEED: u32 = 0x9747b28c;
+	const BLOCK_SIZE: usize = 1 << 16; // дефолтный размер блока в lz4-java
+	const COMPRESSION_LEVEL: u8 = 6; // log2(BLOCK_SIZE) - 10
+
+	fn checksum(data: &[u8]) -> u32 {
+		XxHash32::oneshot(SEED, data) & 0x0FFFFFFF
+	}
+
+	pub fn decompress(mut input: &[u8]) -> Result<Vec<u8>, ServerError> {
+		let mut output = Vec::new();
+
+		loop {
+			if input.len() < HEADER_SIZE || &input[..MAGIC.len()] != MAGIC {
+				return Err(ServerError::Storage("Битый LZ4 блок".to_string()));
+			}
+
+			let token = input[8];
+			let compressed = u32::from_le_bytes(input[9..13].try_into().unwrap()) as usize;
+			let decompressed = u32::from_le_bytes(input[13..17].try_into().unwrap()) as usize;
+			let check = u32::from_le_bytes(input[17..21].try_into().unwrap());
+			input = &input[HEADER_SIZE..];
+
+			if decompressed == 0 {
+				// Конец потока
+				return Ok(output);
+			}
+
+			if input.len() < compressed {
+				return Err(ServerError::Storage("Обрезанный LZ4 блок".to_string()));
+			}
+
+			// Размер блока в lz4-java задается уровнем в младших битах токена, максимум 32 MiB
+			let max_block = 1usize << (10 + (token & 0x0F).min(15));
+			if decompressed > max_block || output.len() + decompressed > super::MAX_CHUNK_SIZE {
+				return Err(ServerError::Storage("Слишком большой LZ4 блок".to_string()));
+			}
+
+			let block = match token & 0xF0 {
+				METHOD_RAW => input[..compressed].to_vec(),
+				METHOD_LZ4 => {
+					lz4_flex::block::decompress(&input[..compressed], decompressed).map_err(storage_error)?
+				}
+				_ => {
+					return Err(ServerError::Storage(
+						"Неизвестный метод LZ4 блока".to_string(),
+					));
+				}
+			};
+
+			if block.len() != decompressed || checksum(&block) != check {
+				return Err(ServerError::Storage(
+					"Неверная чексумма LZ4 блока".to_string(),
+				));
+			}
+
+			output.extend_from_slice(&block);
+			input = &input[compressed..];
+		}
+	}
+
+	pub fn compress(input: &[u8]) -> Vec<u8> {
+		let mut output = Vec::new();
+
+		for block in input.chunks(BLOCK_SIZE) {
+			let compressed = lz4_flex::block::compress(block);
+
+			// Если сжатие не помогло, пишем как есть
+			let (method, data) = if compressed.len() < block.len() {
+				(METHOD_LZ4, &compressed[..])
+			} else {
+				(METHOD_RAW, block)
+			};
+
+			write_header(
+				&mut output,
+				method,
+				data.len(),
+				block.len(),
+				checksum(block),
+			);
+			output.extend_from_slice(data);
+		}
+
+		write_header(&mut output, METHOD_RAW, 0, 0, 0);
+
+		output
+	}
+
+	fn write_header(
+		output: &mut Vec<u8>,
+		method: u8,
+		compressed: usize,
+		decompressed: usize,
+		check: u32,
+	) {
+		output.extend_from_slice(MAGIC);
+		output.push(method | COMPRESSION_LEVEL);
+		output.extend_from_slice(&(compressed as u32).to_le_bytes());
+		output.extend_from_slice(&(decompressed as u32).to_le_bytes());
+		output.extend_from_slice(&check.to_le_bytes());
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use rand::Rng;
+	use twox_hash::XxHash32;
+
+	use super::*;
+
+	const ALL: [Compression; 4] = [
+		Compression::Gzip,
+		Compression::Zlib,
+		Compression::None,
+		Compression::Lz4,
+	];
+
+	// Пустая папка под регионы теста
+	fn temp_dir(name: &str) -> PathBuf {
+		let path =
+			std::env::temp_dir().join(format!("rust_mc_serv_region_{name}_{}", std::process::id()));
+		let _ = fs::remove_dir_all(&path);
+		fs::create_dir_all(&path).unwrap();
+		path
+	}
+
+	// Похоже на NBT чанка: много повторов и немного шума
+	fn chunk_data(size: usize) -> Vec<u8> {
+		let mut rng = rand::thread_rng();
+		(0..size)
+			.map(|i| match i % 64 {
+				0 => rng.r#gen(),
+				o => o as u8,
+			})
+			.collect()
+	}
+
+	#[test]
+	fn compression_round_trip() {
+		// Больше одного блока LZ4
+		let data = chunk_data(200_000);
+		for compression in ALL {
+			let compressed = compression.compress(&data).unwrap();
+			assert_eq!(
+				compression.decompress(&compressed).unwrap(),
+				data,
+				"{compression:?}"
+			);
+			assert_eq!(
+				Compression::from_id(compression as u8).unwrap(),
+				compression
+			);
+		}
+		assert!(Compression::from_id(5).is_err());
+	}
+
+	#[test]
+	fn lz4_block_format() {
+		let data = chunk_data(1000);
+		let compressed = lz4_block::compress(&data);
+		assert_eq!(&compressed[..8], b"LZ4Block");
+		// Поток заканчивается пустым блоком
+		assert_eq!(&compressed[compressed.len() - 21..][..8], b"LZ4Block");
+		assert_eq!(&compressed[compressed.len() - 12..], &[0; 12]);
+
+		// Несжатый блок, как его пишет lz4-java для несжимаемых данных
+		let raw = b"hello";
+		let mut stream = b"LZ4Block".to_vec();
+		stream.push(0x16);
+		stream.extend_from_slice(&5u32.to_le_bytes());
+		stream.extend_from_slice(&5u32.to_le_bytes());
+		stream.extend_from_slice(&(XxHash32::oneshot(0x9747b28c, raw) & 0x0FFFFFFF).to_le_bytes());
+		stream.extend_from_slice(raw);
+		stream.extend_from_slice(b"LZ4Block\x16");
+		stream.extend_from_slice(&[0; 12]);
+		assert_eq!(lz4_block::decompress(&stream).unwrap(), raw);
+
+		let mut broken = compressed.clone();
+		broken[17] ^= 1; // чексумма первого блока
+		assert!(lz4_block::decompress(&broken).is_err());
+		assert!(lz4_block::decompress(&compressed[..30]).is_err());
+	}
+
+	#[test]
+	fn region_round_trip() {
+		let dir = temp_dir("round_trip");
+		let path = dir.join(RegionFile::file_name(-1, 0));
+
+		let chunks: Vec<(i32, i32, Vec<u8>)> = ALL
+			.iter()
+			.enumerate()
+			.map(|(i, _)| (-32 + i as i32, 31, chunk_data(10_000 + i * 5000)))
+			.collect();
+
+		let mut region = RegionFile::open(&path).unwrap();
+		for ((x, z, data), compression) in chunks.iter().zip(ALL) {
+			region.write_chunk(*x, *z, data, compression).unwrap();
+		}
+		region.flush().unwrap();
+		drop(region);
+
+		let mut region = RegionFile::open(&path).unwrap();
+		for (x, z, data) in &chunks {
+			assert!(region.has_chunk(*x, *z));
+			assert!(region.timestamp(*x, *z) > 0);
+			assert_eq!(region.read_chunk(*x, *z).unwrap().as_ref(), Some(data));
+		}
+		assert_eq!(region.read_chunk(0, 0).unwrap(), None);
+
+		region.delete_chunk(chunks[0].0, chunks[0].1).unwrap();
+		drop(region);
+		let mut region = RegionFile::open(&path).unwrap();
+		assert_eq!(region.read_chunk(chunks[0].0, chunks[0].1).unwrap(), None);
+
+		fs::remove_dir_all(dir).unwrap();
+	}
+
+	#[test]
+	fn rewrite_keeps_old_sectors_until_header_update() {
+		let dir = temp_dir("rewrite");
+		let mut region = RegionFile::open(dir.join("r.0.0.mca")).unwrap();
+
+		region
+			.write_chunk(0, 0, &chunk_data(5000), Compression::None)
+			.unwrap();
+		let old = region.locations[0];
+
+		let data = chunk_data(6000);
+		region.write_chunk(0, 0, &data, Compression::None).unwrap();
+		let new = region.locations[0];
+
+		// Новая версия легла мимо старой, а старые сектора освободились после записи заголовка
+		assert_ne!(old >> 8, new >> 8);
+		let (offset, count) = ((old >> 8) as usize, (old & 0xFF) as usize);
+		assert!(!region.used[offset..offset + count].iter().any(|o| *o));
+		assert_eq!(region.read_chunk(0, 0).unwrap(), Some(data));
+
+		// И достаются следующему чанку
+		region
+			.write_chunk(1, 0, &chunk_data(100), Compression::None)
+			.unwrap();
+		assert_eq!(region.locations[1] >> 8, old >> 8);
+
+		fs::remove_dir_all(dir).unwrap();
+	}
+
+	#[test]
+	fn external_chunks() {
+		let dir = temp_dir("external");
+		let path = dir.join("r.0.0.mca");
+		let external = dir.join("c.3.4.mcc");
+
+		// Несжимаемые данные больше 255 секторов
+		let mut data = vec![0u8; 256 * SECTOR_SIZE];
+		rand::thread_rng().fill(&mut data[..]);
+
+		let mut region = RegionFile::open(&path).unwrap();
+		region.write_chunk(3, 4, &data, Compression::None).unwrap();
+		assert!(external.exists());
+		assert_eq!(region.locations[RegionFile::index(3, 4)] & 0xFF, 1);
+		drop(region);
+
+		let mut region = RegionFile::open(&path).unwrap();
+		assert_eq!(region.read_chunk(3, 4).unwrap(), Some(data));
+
+		// Чанк снова влез в регион, .mcc больше не нужен
+		let small = chunk_data(1000);
+		region.write_chunk(3, 4, &small, Compression::Zlib).unwrap();
+		assert!(!external.exists());
+		assert_eq!(region.read_chunk(3, 4).unwrap(), Some(small));
+
+		fs::remove_dir_all(dir).unwrap();
+	}
+
+	#[test]
+	fn broken_headers_are_not_chunks() {
+		let dir = temp_dir("broken_headers");
+		let path = dir.join(RegionFile::file_name(0, 0));
+
+		// Чанк 0 0: 0 секторов, чанк 1 0: длина больше сектора, чанк 2 0: нулевая длина
+		let mut data = vec![0u8; SECTOR_SIZE * 4];
+		data[0..4].copy_from_slice(&(2u32 << 8).to_be_bytes());
+		data[4..8].copy_from_slice(&((2u32 << 8) | 1).to_be_bytes());
+		data[8..12].copy_from_slice(&((3u32 << 8) | 1).to_be_bytes());
+		data[SECTOR_SIZE * 2..SECTOR_SIZE * 2 + 4].copy_from_slice(&5000u32.to_be_bytes());
+		fs::write(&path, &data).unwrap();
+
+		let mut region = RegionFile::open(&path).unwrap();
+		assert!(!region.has_chunk(0, 0));
+		assert_eq!(region.read_chunk(0, 0).unwrap(), None);
+		assert!(matches!(
+			region.read_chunk(1, 0),
+			Err(ServerError::Storage(_))
+		));
+		assert!(matches!(
+			region.read_chunk(2, 0),
+			Err(ServerError::Storage(_))
+		));
+
+		fs::remove_dir_all(dir).unwrap();
+	}
+
+	#[test]
+	fn oversized_chunks_are_rejected() {
+		// Блок, который обещает распаковаться в 4 GiB
+		let mut stream = b"LZ4Block".to_vec();
+		stream.push(0x26);
+		stream.extend_from_slice(&1u32.to_le_bytes());
+		stream.extend_from_slice(&u32::MAX.to_le_bytes());
+		stream.extend_from_slice(&0u32.to_le_bytes());
+		stream.push(0);
+		assert!(lz4_block::decompress(&stream).is_err());
+
+		let data = vec![0u8; MAX_CHUNK_SIZE + 1];
+		for compression in ALL {
+			let compressed = compression.compress(&data).unwrap();
+			assert!(compression.decompress(&compressed).is_err());
+		}
+	}
+}
@@ -0,0 +1,548 @@
+use std::{
+	collections::{BTreeMap, HashMap},
+	fs,
+	path::Path,
+};
+
+use craftflow_nbt::DynNBT;
+use log::warn;
+use serde::Deserialize;
+
+use super::{dimension::DimensionType, item::ReportRegistry};
+use crate::{
+	ServerError,
+	data::json_to_nbt,
+	protocol::packets::{
+		KnownPack, RegistryDataEntry, RegistryTags, Tag, VarInt,
+		clientbound::configuration::{RegistryData, UpdateTags},
+	},
+};
+
+// Синхронизируемые с клиентом реестры (биомы, типы измерений, типы урона и т.д.) и теги
+//
+// Ванильные записи лежат в registries.json и tags.json без данных, клиент берет их из пака minecraft:core,
+// если сказал в Known Packs, что знает его. Свои записи плагины и датапаки добавляют вместе с NBT данными,
+// айди записи - ее индекс в реестре, поэтому новые записи всегда идут в конец
+//
+// Реестры, которые клиенту не отправляются (entity_type, fluid, ...), лежат в builtin_registries.json,
+// по ним имена в тегах переводятся в айди. Блоки и предметы берутся из ванильного репорта, если он есть
+
+pub const BIOME: &str = "minecraft:worldgen/biome";
+pub const DIMENSION_TYPE: &str = "minecraft:dimension_type";
+
+// Теги могут ссылаться на теги, ограничиваем вложенность на случай циклов
+const MAX_TAG_DEPTH: usize = 16;
+
+/// Ванильный пак, из которого взяты registries.json и tags.json
+pub fn core_pack() -> KnownPack {
+	KnownPack {
+		namespace: "minecraft".to_string(),
+		id: "core".to_string(),
+		version: "1.21.5".to_string(),
+	}
+}
+
+#[derive(Deserialize)]
+struct RegistryFile {
+	id: String,
+	entries: Vec<String>,
+}
+
+/// Значение в теге: имя записи, `#тег` или числовой айди (для блоков и предметов, которых сервер не знает по имени)
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(untagged)]
+pub enum TagValue {
+	Id(i32),
+	Name(String),
+}
+
+impl From<&str> for TagValue {
+	fn from(value: &str) -> Self {
+		TagValue::Name(value.to_string())
+	}
+}
+
+#[derive(Debug, Clone)]
+pub struct RegistryEntry {
+	pub id: String,
+	pub data: Option<DynNBT>,
+	pub pack: Option<KnownPack>, // пак, из которого клиент может взять данные сам
+}
+
+#[derive(Debug, Clone)]
+pub struct Registry {
+	pub id: String,
+	entries: Vec<RegistryEntry>,
+	indices: HashMap<String, usize>,
+}
+
+impl Registry {
+	pub fn new(id: &str) -> Registry {
+		Registry {
+			id: id.to_string(),
+			entries: Vec::new(),
+			indices: HashMap::new(),
+		}
+	}
+
+	/// Добавляет запись в конец, а если она уже есть - заменяет ее, айди не меняется
+	pub fn insert(&mut self, entry: RegistryEntry) -> i32 {
+		if let Some(index) = self.indices.get(&entry.id) {
+			self.entries[*index] = entry;
+			return *index as i32;
+		}
+
+		self.indices.insert(entry.id.clone(), self.entries.len());
+		self.entries.push(entry);
+		self.entries.len() as i32 - 1
+	}
+
+	/// Запись со своими данными, клиенту они уходят всегда
+	pub fn add(&mut self, id: &str, data: DynNBT) -> i32 {
+		self.insert(RegistryEntry {
+			id: id.to_string(),
+			data: Some(data),
+			pack: None,
+		})
+	}
+
+	pub fn get(&self, id: &str) -> Option<&RegistryEntry> {
+		self.indices.get(id).map(|o| &self.entries[*o])
+	}
+
+	/// Айди записи, который уходит клиенту
+	pub fn index(&self, id: &str) -> Option<i32> {
+		self.indices.get(id).map(|o| *o as i32)
+	}
+
+	/// Имя записи по айди
+	pub fn name(&self, index: i32) -> Option<&str> {
+		let index = usize::try_from(index).ok()?;
+		self.entries.get(index).map(|o| o.id.as_str())
+	}
+
+	pub fn entries(&self) -> &[RegistryEntry] {
+		&self.entries
+	}
+
+	pub fn len(&self) -> usize {
+		self.entries.len()
+	}
+
+	pub fn is_empty(&self) -> bool {
+		self.entries.is_empty()
+	}
+}
+
+pub struct Registries {
+	known_packs: Vec<KnownPack>, // паки, которые сервер предлагает клиенту в Known Packs
+	registries: Vec<Registry>,
+	builtin: Vec<Registry>, // не синхронизируются, нужны только для тегов
+	tags: BTreeMap<String, BTreeMap<String, Vec<TagValue>>>, // реестр -> тег -> значения
+}
+
+impl Default for Registries {
+	fn default() -> Self {
+		Self::vanilla()
+	}
+}
+
+impl Registries {
+	/// Пустые реестры, клиент с ними не зайдет
+	pub fn empty() -> Registries {
+		Registries {
+			known_packs: Vec::new(),
+			registries: Vec::new(),
+			builtin: Vec::new(),
+			tags: BTreeMap::new(),
+		}
+	}
+
+	/// Ванильные реестры и теги 1.21.5
+	pub fn vanilla() -> Registries {
+		let mut registries = Registries::empty();
+		registries.known_packs.push(core_pack());
+
+		let files: Vec<RegistryFile> =
+			serde_json::from_str(include_str!("registries.json")).expect("broken registries.json");
+		for file in files {
+			let registry = registries.registry_mut(&file.id);
+			for id in file.entries {
+				registry.insert(RegistryEntry {
+					id,
+					data: None,
+					pack: Some(core_pack()),
+				});
+			}
+		}
+
+		let files: Vec<RegistryFile> = serde_json::from_str(include_str!("builtin_registries.json"))
+			.expect("broken builtin_registries.json");
+		for file in files {
+			let mut registry = Registry::new(&file.id);
+			for id in file.entries {
+				registry.insert(RegistryEntry {
+					id,
+					data: None,
+					pack: None,
+				});
+			}
+			registries.builtin.push(registry);
+		}
+
+		registries.tags = serde_json::from_str(include_str!("tags.json")).expect("broken tags.json");
+
+		registries
+	}
+
+	pub fn registry(&self, id: &str) -> Option<&Registry> {
+		self.registries.iter().find(|o| o.id == id)
+	}
+
+	/// Реестр, который клиенту не отправляется (minecraft:block, minecraft:entity_type, ...)
+	pub fn builtin(&self, id: &str) -> Option<&Registry> {
+		self.builtin.iter().find(|o| o.id == id)
+	}
+
+	/// Несинхронизируемые реестры из ванильного репорта registries.json, заменяют встроенные
+	pub fn load_report(&mut self, path: impl AsRef<Path>) -> Result<(), ServerError> {
+		let content = fs::read_to_string(path).map_err(|e| ServerError::Storage(e.to_string()))?;
+		let report: HashMap<String, ReportRegistry> =
+			serde_json::from_str(&content).map_err(|e| ServerError::Storage(e.to_string()))?;
+
+		for (id, report) in report {
+			if self.registry(&id).is_some() {
+				continue; // айди синхронизируемых реестров задает сервер
+			}
+
+			// Айди записи - ее индекс, поэтому вставляем по порядку protocol_id
+			let mut entries = report.entries.into_iter().collect::<Vec<_>>();
+			entries.sort_by_key(|o| o.1.protocol_id);
+
+			let mut registry = Registry::new(&id);
+			for (name, entry) in entries {
+				if registry.len() as i32 != entry.protocol_id {
+					return Err(ServerError::Storage(format!(
+						"пропущен айди {} в реестре {id}",
+						registry.len()
+					)));
+				}
+				registry.insert(RegistryEntry {
+					id: name,
+					data: None,
+					pack: None,
+				});
+			}
+
+			self.builtin.retain(|o| o.id != id);
+			self.builtin.push(registry);
+		}
+
+		Ok(())
+	}
+
+	/// Реестр по имени, если его нет - создается пустой
+	pub fn registry_mut(&mut self, id: &str) -> &mut Registry {
+		match self.registries.iter().position(|o| o.id == id) {
+			Some(index) => &mut self.registries[index],
+			None => {
+				self.registries.push(Registry::new(id));
+				self.registries.last_mut().unwrap()
+			}
+		}
+	}
+
+	pub fn registries(&self) -> &[Registry] {
+		&self.registries
+	}
+
+	pub fn known_packs(&self) -> &[KnownPack] {
+		&self.known_packs
+	}
+
+	/// Добавляет или заменяет запись с данными, возвращает ее айди
+	pub fn add(&mut self, registry: &str, id: &str, data: DynNBT) -> i32 {
+		self.registry_mut(registry).add(id, data)
+	}
+
+	/// То же самое, но данные в json, как в датапаках
+	pub fn add_json(&mut self, registry: &str, id: &str, data: &serde_json::Value) -> i32 {
+		self.add(registry, id, json_to_nbt(data))
+	}
+
+	pub fn add_biome(&mut self, id: &str, data: DynNBT) -> i32 {
+		self.add(BIOME, id, data)
+	}
+
+	pub fn add_dimension_type(&mut self, dimension: &DimensionType) -> i32 {
+		self.add(DIMENSION_TYPE, &dimension.name, dimension.to_nbt())
+	}
+
+	/// Заменяет тег целиком, как `"replace": true` в датапаке
+	pub fn set_tag(&mut self, registry: &str, tag: &str, values: Vec<TagValue>) {
+		self
+			.tags
+			.entry(registry.to_string())
+			.or_default()
+			.insert(tag.to_string(), values);
+	}
+
+	/// Дописывает значения в тег, тег создается если его нет
+	pub fn add_tag(&mut self, registry: &str, tag: &str, values: Vec<TagValue>) {
+		self
+			.tags
+			.entry(registry.to_string())
+			.or_default()
+			.entry(tag.to_string())
+			.or_default()
+			.extend(values);
+	}
+
+	pub fn tag(&self, registry: &str, tag: &str) -> Option<&Vec<TagValue>> {
+		self.tags.get(registry).and_then(|o| o.get(tag))
+	}
+
+	/// Айди записи, для несинхронизируемых реестров - из встроенных
+	pub fn id(&self, registry: &str, entry: &str) -> Option<i32> {
+		self
+			.registry(registry)
+			.or_else(|| self.builtin(registry))
+			.and_then(|o| o.index(entry))
+	}
+
+	pub fn name(&self, registry: &str, id: i32) -> Option<&str> {
+		self
+			.registry(registry)
+			.or_else(|| self.builtin(registry))
+			.and_then(|o| o.name(id))
+	}
+
+	pub fn biome_id(&self, name: &str) -> Option<i32> {
+		self.id(BIOME, name)
+	}
+
+	pub fn biome_count(&self) -> usize {
+		self.registry(BIOME).map(|o| o.len()).unwrap_or(0)
+	}
+
+	/// Тип измерения из реестра, высота берется из данных записи или из ванильного типа
+	pub fn dimension_type(&self, name: &str) -> Option<DimensionType> {
+		let entry = self.registry(DIMENSION_TYPE)?.get(name)?;
+		match &entry.data {
+			Some(data) => DimensionType::from_nbt(name, data),
+			None => DimensionType::vanilla(name).map(|o| DimensionType {
+				name: name.to_string(),
+				..o
+			}),
+		}
+	}
+
+	/// Есть ли у реестра теги, даже если его записи не синхронизируются (block, item, ...)
+	pub fn has_tags(&self, registry: &str) -> bool {
+		self.tags.contains_key(registry)
+	}
+
+	/// Пакеты Registry Data, по одному на реестр
+	///
+	/// known - паки, которые клиент подтвердил в Known Packs, записи из них уходят без данных
+	pub fn registry_data(&self, known: &[KnownPack]) -> Vec<RegistryData> {
+		let mut missing = 0;
+
+		let packets = self
+			.registries
+			.iter()
+			.map(|registry| RegistryData {
+				registry_id: registry.id.clone(),
+				entries: registry
+					.entries
+					.iter()
+					.map(|o| {
+						let data = match &o.pack {
+							Some(pack) if known.contains(pack) => None,
+							_ => {
+								if o.data.is_none() {
+									missing += 1;
+								}
+								o.data.clone()
+							}
+						};
+						RegistryDataEntry {
+							id: o.id.clone(),
+							data,
+						}
+					})
+					.collect(),
+			})
+			.collect();
+
+		// Клиент другой версии или с модами, без данных он скорее всего отключится
+		if missing > 0 {
+			warn!(
+				"Клиент не знает ванильные паки, а данных для {missing} записей реестров у сервера нет"
+			);
+		}
+
+		packets
+	}
+
+	/// Пакет Update Tags со всеми тегами, имена и ссылки на теги заменяются айдишниками
+	pub fn update_tags(&self) -> UpdateTags {
+		let registries = self
+			.tags
+			.iter()
+			.map(|(registry, tags)| RegistryTags {
+				registry: registry.clone(),
+				tags: tags
+					.keys()
+					.map(|name| {
+						let mut entries = Vec::new();
+						self.resolve_tag(registry, name, 0, &mut entries);
+						entries.dedup();
+						Tag {
+							name: name.clone(),
+							entries: entries.into_iter().map(VarInt).collect(),
+						}
+					})
+					.collect(),
+			})
+			.collect();
+
+		UpdateTags { registries }
+	}
+
+	fn resolve_tag(&self, registry: &str, tag: &str, depth: usize, out: &mut Vec<i32>) {
+		let Some(values) = self.tag(registry, tag) else {
+			warn!("Неизвестный тег #{tag} в {registry}");
+			return;
+		};
+
+		if depth > MAX_TAG_DEPTH {
+			warn!("Слишком глубокая вложенность тегов в #{tag} ({registry})");
+			return;
+		}
+
+		for value in values {
+			match value {
+				TagValue::Id(id) => out.push(*id),
+				TagValue::Name(name) => match name.strip_prefix('#') {
+					Some(tag) => self.resolve_tag(registry, tag, depth + 1, out),
+					None => match self.id(registry, name) {
+						Some(id) => out.push(id),
+						None => warn!("Неизвестная запись {name} в теге #{tag} ({registry})"),
+					},
+				},
+			}
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn tag_ids(registries: &Registries, registry: &str, tag: &str) -> Vec<i32> {
+		registries
+			.update_tags()
+			.registries
+			.into_iter()
+			.find(|o| o.registry == registry)
+			.and_then(|o| o.tags.into_iter().find(|o| o.name == tag))
+			.map(|o| o.entries.into_iter().map(|o| o.0).collect())
+			.unwrap()
+	}
+
+	#[test]
+	fn builtin_registries_resolve_names() {
+		let registries = Registries::vanilla();
+
+		assert_eq!(
+			tag_ids(&registries, "minecraft:fluid", "minecraft:lava"),
+			[4, 3]
+		);
+		assert_eq!(
+			tag_ids(
+				&registries,
+				"minecraft:game_event",
+				"minecraft:allay_can_listen"
+			),
+			[33]
+		);
+		assert_eq!(
+			tag_ids(&registries, "minecraft:entity_type", "minecraft:skeletons"),
+			[109, 122, 140, 110, 16]
+		);
+		assert_eq!(
+			registries.id("minecraft:entity_type", "minecraft:player"),
+			Some(148)
+		);
+
+		// Несинхронизируемые реестры клиенту не уходят
+		assert!(registries.registry("minecraft:entity_type").is_none());
+		assert!(
+			registries
+				.registry_data(&[])
+				.iter()
+				.all(|o| o.registry_id != "minecraft:fluid")
+		);
+	}
+
+	#[test]
+	fn report_replaces_builtin_registries() {
+		let path = std::env::temp_dir().join(format!(
+			"rust_mc_serv_registries_{}.json",
+			std::process::id()
+		));
+		fs::write(
+			&path,
+			r#"{
+				"minecraft:block": {"entries": {
+					"minecraft:stone": {"protocol_id": 1},
+					"minecraft:air": {"protocol_id": 0}
+				}},
+				"minecraft:fluid": {"entries": {"minecraft:empty": {"protocol_id": 0}}},
+				"minecraft:worldgen/biome": {"entries": {"minecraft:plains": {"protocol_id": 7}}}
+			}"#,
+		)
+		.unwrap();
+
+		let mut registries = Registries::vanilla();
+		let plains = registries.biome_id("minecraft:plains");
+		registries.load_report(&path).unwrap();
+		fs::remove_file(&path).unwrap();
+
+		assert_eq!(registries.id("minecraft:block", "minecraft:stone"), Some(1));
+		assert_eq!(registries.id("minecraft:fluid", "minecraft:lava"), None);
+		assert_eq!(registries.biome_id("minecraft:plains"), plains);
+
+		registries.set_tag(
+			"minecraft:block",
+			"custom:rocks",
+			vec![
+				"minecraft:stone".into(),
+				TagValue::Id(2),
+				"minecraft:missing".into(),
+			],
+		);
+		assert_eq!(
+			tag_ids(&registries, "minecraft:block", "custom:rocks"),
+			[1, 2]
+		);
+	}
+
+	#[test]
+	fn report_with_gaps_is_rejected() {
+		let path = std::env::temp_dir().join(format!(
+			"rust_mc_serv_registries_gap_{}.json",
+			std::process::id()
+		));
+		fs::write(
+			&path,
+			r#"{"minecraft:block": {"entries": {"minecraft:stone": {"protocol_id": 1}}}}"#,
+		)
+		.unwrap();
+
+		let result = Registries::vanilla().load_report(&path);
+		fs::remove_file(&path).unwrap();
+		assert!(result.is_err());
+	}
+}
@@ -0,0 +1,202 @@
+use std::{
+	collections::{HashMap, hash_map::Entry},
+	fs,
+	path::{Path, PathBuf},
+	sync::{Arc, Mutex, RwLock},
+};
+
+use dashmap::DashMap;
+
+use super::{
+	chunk::Chunk,
+	region::{Compression, RegionFile},
+};
+use crate::ServerError;
+
+// Хранилище чанков мира
+//
+// Загруженные чанки лежат в памяти, незагруженные читаются из папки region при первом обращении
+// Измененные чанки (Chunk::is_dirty) пишутся обратно через save_chunk / save_all
+pub struct WorldStorage {
+	path: PathBuf,
+	compression: Compression,
+	regions: Mutex<HashMap<(i32, i32), RegionFile>>,
+	chunks: DashMap<(i32, i32), Arc<RwLock<Chunk>>>,
+}
+
+impl WorldStorage {
+	/// `path` - папка мира (в ней будет папка region), `compression` - чем сжимать сохраняемые чанки
+	pub fn new(path: impl AsRef<Path>, compression: Compression) -> WorldStorage {
+		WorldStorage {
+			path: path.as_ref().to_path_buf(),
+			compression,
+			regions: Mutex::new(HashMap::new()),
+			chunks: DashMap::new(),
+		}
+	}
+
+	pub fn path(&self) -> &Path {
+		&self.path
+	}
+
+	pub fn region_path(&self) -> PathBuf {
+		self.path.join("region")
+	}
+
+	/// Чанк из памяти, либо загруженный с диска, None если чанка нет нигде
+	pub fn chunk(&self, x: i32, z: i32) -> Result<Option<Arc<RwLock<Chunk>>>, ServerError> {
+		if let Some(chunk) = self.chunks.get(&(x, z)) {
+			return Ok(Some(chunk.clone()));
+		}
+
+		let Some(chunk) = self.load_chunk(x, z)? else {
+			return Ok(None);
+		};
+
+		// Пока мы читали, чанк мог загрузить другой поток, тогда берем его версию
+		Ok(Some(
+			self
+				.chunks
+				.entry((x, z))
+				.or_insert_with(|| Arc::new(RwLock::new(chunk)))
+				.clone(),
+		))
+	}
+
+	/// Чанк только из памяти, без чтения с диска
+	pub fn loaded_chunk(&self, x: i32, z: i32) -> Option<Arc<RwLock<Chunk>>> {
+		self.chunks.get(&(x, z)).map(|o| o.clone())
+	}
+
+	pub fn loaded_chunks(&self) -> Vec<(i32, i32)> {
+		self.chunks.iter().map(|o| *o.key()).collect()
+	}
+
+	/// Кладет чанк в память (например только что сгенерированный), на диск он попадет при сохранении
+	pub fn insert_chunk(&self, mut chunk: Chunk) -> Arc<RwLock<Chunk>> {
+		chunk.mark_dirty();
+		let pos = (chunk.x, chunk.z);
+		let chunk = Arc::new(RwLock::new(chunk));
+		self.chunks.insert(pos, chunk.clone());
+		chunk
+	}
+
+	/// Чанк из памяти или с диска, а если его нет нигде - созданный через `create` (например генератором)
+	pub fn chunk_or_insert_with<F>(
+		&self,
+		x: i32,
+		z: i32,
+		create: F,
+	) -> Result<Arc<RwLock<Chunk>>, ServerError>
+	where
+		F: FnOnce() -> Result<Chunk, ServerError>,
+	{
+		if let Some(chunk) = self.chunk(x, z)? {
+			return Ok(chunk);
+		}
+
+		let mut chunk = create()?;
+		chunk.mark_dirty();
+
+		Ok(
+			self
+				.chunks
+				.entry((x, z))
+				.or_insert_with(|| Arc::new(RwLock::new(chunk)))
+				.clone(),
+		)
+	}
+
+	/// Читает чанк прямо с диска, в память не кладет
+	pub fn load_chunk(&self, x: i32, z: i32) -> Result<Option<Chunk>, ServerError> {
+		let mut regions = self.regions.lock().unwrap();
+		let Some(region) = self.region(&mut regions, x, z, false)? else {
+			return Ok(None);
+		};
+
+		match region.read_chunk(x, z)? {
+			Some(data) => Chunk::from_bytes(&data).map(Some),
+			None => Ok(None),
+		}
+	}
+
+	/// Сохраняет чанк если он изменен
+	pub fn save_chunk(&self, x: i32, z: i32) -> Result<(), ServerError> {
+		let Some(chunk) = self.loaded_chunk(x, z) else {
+			return Ok(());
+		};
+
+		let mut chunk = chunk.write().unwrap();
+		if !chunk.is_dirty() {
+			return Ok(());
+		}
+
+		let data = chunk.to_bytes()?;
+
+		let mut regions = self.regions.lock().unwrap();
+		if let Some(region) = self.region(&mut regions, x, z, true)? {
+			region.write_chunk(x, z, &data, self.compression)?;
+		}
+
+		chunk.mark_saved();
+
+		Ok(())
+	}
+
+	/// Сохраняет все измененные чанки, возвращает сколько сохранено
+	pub fn save_all(&self) -> Result<usize, ServerError> {
+		let dirty = self
+			.chunks
+			.iter()
+			.filter(|o| o.read().unwrap().is_dirty())
+			.map(|o| *o.key())
+			.collect::<Vec<_>>();
+
+		for (x, z) in dirty.iter() {
+			self.save_chunk(*x, *z)?;
+		}
+
+		for region in self.regions.lock().unwrap().values_mut() {
+			region.flush()?;
+		}
+
+		Ok(dirty.len())
+	}
+
+	/// Сохраняет и выгружает чанк из памяти
+	pub fn unload_chunk(&self, x: i32, z: i32) -> Result<(), ServerError> {
+		self.save_chunk(x, z)?;
+		self.chunks.remove(&(x, z));
+		Ok(())
+	}
+
+	// Открытый регион файл чанка, `create` - создавать ли файл если его нет
+	fn region<'a>(
+		&self,
+		regions: &'a mut HashMap<(i32, i32), RegionFile>,
+		x: i32,
+		z: i32,
+		create: bool,
+	) -> Result<Option<&'a mut RegionFile>, ServerError> {
+		let pos = RegionFile::region_pos(x, z);
+
+		let region = match regions.entry(pos) {
+			Entry::Occupied(entry) => entry.into_mut(),
+			Entry::Vacant(entry) => {
+				let path = self.region_path().join(RegionFile::file_name(pos.0, pos.1));
+
+				if !path.exists() {
+					if !create {
+						return Ok(None);
+					}
+					fs::create_dir_all(self.region_path())
+						.map_err(|e| ServerError::Storage(e.to_string()))?;
+				}
+
+				entry.insert(RegionFile::open(path)?)
+			}
+		};
+
+		Ok(Some(region))
+	}
+}